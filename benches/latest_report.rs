@@ -0,0 +1,85 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use jupiter::provider::homebrew::{WeatherReport, LATEST_REPORT_QUERY};
+
+// The CPU side of GET /api/weather_reports: building the query and serializing the row, via the
+// generic select path versus the prepared single-row fast path. Allocations per request are
+// counted with a wrapping global allocator and printed before the timings.
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn report() -> WeatherReport {
+    let mut report = WeatherReport::new();
+    report.temperature = Some(21.4);
+    report.humidity = Some(48.0);
+    report.pm25 = Some(6.5);
+    report.pm10 = Some(11.0);
+    report.co2 = Some(612.0);
+    report.battery_voltage = Some(3.91);
+    report.battery_percent = Some(87.0);
+    report.rssi = Some(-68.0);
+    report.device_type = "indoor".to_string();
+    report.device_id = Some("living-room".to_string());
+    report
+}
+
+fn select_path(report: &WeatherReport) -> Vec<u8> {
+    let (query, params) = WeatherReport::select_query(Some(1), None, Some("timestamp DESC".to_string()), None);
+    black_box((query, params));
+    let rows = [report.clone()];
+    serde_json::to_string(rows.first().unwrap()).unwrap().into_bytes()
+}
+
+fn fast_path(report: &WeatherReport, buffer: &mut Vec<u8>) -> Vec<u8> {
+    black_box(LATEST_REPORT_QUERY);
+    report.write_json(buffer).unwrap();
+    buffer.as_slice().to_vec()
+}
+
+fn allocations_per_call(mut f: impl FnMut()) -> f64 {
+    const CALLS: usize = 1000;
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..CALLS {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / CALLS as f64
+}
+
+fn bench_latest_report(c: &mut Criterion) {
+    let report = report();
+    let mut buffer = Vec::with_capacity(512);
+
+    println!("latest_report/select allocations per request: {:.1}",
+        allocations_per_call(|| { black_box(select_path(&report)); }));
+    println!("latest_report/fast_path allocations per request: {:.1}",
+        allocations_per_call(|| { black_box(fast_path(&report, &mut buffer)); }));
+
+    c.bench_function("latest_report/select", |b| b.iter(|| select_path(black_box(&report))));
+    c.bench_function("latest_report/fast_path", |b| b.iter(|| fast_path(black_box(&report), &mut buffer)));
+}
+
+criterion_group!(benches, bench_latest_report);
+criterion_main!(benches);
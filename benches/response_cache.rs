@@ -0,0 +1,55 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use jupiter::provider::combo::CachedWeatherData;
+use jupiter::response_cache::ResponseCache;
+use jupiter::response_format::{self, ResponseFormat};
+
+// Cached combo conditions served by re-serializing the cache row on every request (the old path)
+// versus copying bytes serialized once per format.
+
+fn cached_conditions() -> CachedWeatherData {
+    let mut data = CachedWeatherData::new();
+    data.accuweather = Some(serde_json::json!({
+        "LocalObservationDateTime": "2024-07-01T14:55:00-04:00",
+        "WeatherText": "Partly sunny",
+        "WeatherIcon": 3,
+        "HasPrecipitation": false,
+        "IsDayTime": true,
+        "Temperature": {"Metric": {"Value": 27.2, "Unit": "C"}, "Imperial": {"Value": 81.0, "Unit": "F"}},
+        "RealFeelTemperature": {"Metric": {"Value": 29.4, "Unit": "C"}, "Imperial": {"Value": 85.0, "Unit": "F"}},
+        "RelativeHumidity": 58,
+        "Wind": {"Direction": {"Degrees": 225, "Localized": "SW"}, "Speed": {"Metric": {"Value": 14.8, "Unit": "km/h"}}},
+        "UVIndex": 7,
+        "UVIndexText": "High",
+        "Visibility": {"Metric": {"Value": 16.1, "Unit": "km"}},
+        "Pressure": {"Metric": {"Value": 1014.9, "Unit": "mb"}},
+        "Link": "http://www.accuweather.com/en/us/springfield/current-weather"
+    }).to_string());
+    data.homebrew = Some(serde_json::json!({
+        "id": 48211, "oid": "kq9XbLh2pZr7TfA", "temperature": 26.8, "humidity": 55.0,
+        "percipitation": 0.0, "pm10": 12.0, "pm25": 7.5, "co2": null, "tvoc": null,
+        "wind_speed": 3.9, "wind_direction": 230.0, "battery_voltage": 3.9, "battery_percent": 88.0,
+        "rssi": -71.0, "device_type": "outdoor", "device_id": "garden", "timestamp": 1719860100
+    }).to_string());
+    data
+}
+
+fn bench_cached_conditions(c: &mut Criterion) {
+    let data = cached_conditions();
+    let cache = ResponseCache::new();
+    for format in [ResponseFormat::Json, ResponseFormat::Cbor, ResponseFormat::MessagePack] {
+        cache.insert(format, response_format::encode(format, &data).unwrap(), data.timestamp);
+    }
+
+    for (name, format) in [("json", ResponseFormat::Json), ("cbor", ResponseFormat::Cbor), ("msgpack", ResponseFormat::MessagePack)] {
+        c.bench_function(&format!("cached_conditions/{}/serialize", name), |b| {
+            b.iter(|| response_format::encode(format, black_box(&data.clone())).unwrap())
+        });
+        c.bench_function(&format!("cached_conditions/{}/pre_serialized", name), |b| {
+            b.iter(|| cache.get(black_box(format), data.timestamp, 3600).unwrap().body.to_vec())
+        });
+    }
+}
+
+criterion_group!(benches, bench_cached_conditions);
+criterion_main!(benches);
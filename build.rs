@@ -0,0 +1,11 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // The gRPC service code is generated from proto/jupiter.proto only with the `grpc` feature
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/jupiter.proto");
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/jupiter.proto").expect("compile proto/jupiter.proto");
+    }
+}
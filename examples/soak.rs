@@ -0,0 +1,242 @@
+// Soak test: both servers run in-process against Postgres in a throwaway Docker container while
+// workers drive continuous ingest and query load through the typed client. The process's RSS,
+// open file descriptors and database pool sizes are sampled as it runs; the run fails when they
+// are still growing at the end, which is how leaked connections, runtimes or buffers show up.
+// Linux only (samples come from /proc/self), needs a Docker daemon:
+//
+//     cargo run --release --example soak
+//
+// SOAK_DURATION_SECS (default 4 hours), SOAK_SAMPLE_SECS (60) and SOAK_WORKERS (4) shape the run,
+// SOAK_RSS_GROWTH_PERCENT (20) and SOAK_FD_GROWTH (16) set how much growth is tolerated. Ctrl-C
+// ends the run early and still judges the samples taken so far.
+
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use testcontainers::clients::Cli;
+use testcontainers_modules::postgres::Postgres;
+
+use jupiter::client::{JupiterClient, NewWeatherReport};
+use jupiter::db_pool::{get_combo_pool, get_homebrew_pool, DatabasePool};
+use jupiter::devices::DeviceRegistry;
+use jupiter::provider::common::WeatherError;
+use jupiter::provider::{combo, homebrew};
+
+const API_KEY: &str = "soak-test-key";
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("free local port")
+}
+
+/// The servers rate limit by remote address including the port, so every request gets its
+/// own connection instead of reusing a pooled one
+fn client(port: u16) -> JupiterClient {
+    let http = reqwest::Client::builder()
+        .pool_max_idle_per_host(0)
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("http client");
+    JupiterClient::new(&format!("http://127.0.0.1:{}", port), API_KEY)
+        .expect("client")
+        .with_http_client(http)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    rss_kb: u64,
+    fds: u64,
+    homebrew_pool: u64,
+    combo_pool: u64,
+}
+
+fn rss_kb() -> u64 {
+    std::fs::read_to_string("/proc/self/status").ok()
+        .and_then(|status| status.lines()
+            .find(|line| line.starts_with("VmRSS:"))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|kb| kb.parse().ok()))
+        .unwrap_or(0)
+}
+
+fn open_fds() -> u64 {
+    std::fs::read_dir("/proc/self/fd").map(|entries| entries.count() as u64).unwrap_or(0)
+}
+
+fn pool_size(pool: Option<Arc<DatabasePool>>) -> u64 {
+    pool.map(|pool| pool.status().size as u64).unwrap_or(0)
+}
+
+fn sample() -> Sample {
+    Sample {
+        rss_kb: rss_kb(),
+        fds: open_fds(),
+        homebrew_pool: pool_size(get_homebrew_pool()),
+        combo_pool: pool_size(get_combo_pool()),
+    }
+}
+
+/// One round of the mixed load: an API report and an rtl_433 event written, then the read paths
+/// that hit the database, the combo cache and the device registry
+async fn round(homebrew: &JupiterClient, combo: &JupiterClient, i: u64) -> Result<(), WeatherError> {
+    homebrew.post_report(&NewWeatherReport {
+        temperature: Some((i % 40) as f64),
+        humidity: Some(45.0),
+        co2: Some(600.0 + (i % 200) as f64),
+        device_type: "indoor".to_string(),
+        ..Default::default()
+    }).await?;
+    homebrew.ingest_rtl433(&format!(
+        r#"{{"time":"2024-05-01 12:00:00","model":"Acurite-Tower","channel":"A","id":1234,"temperature_C":{},"humidity":71}}"#,
+        (i % 30) as f64 / 2.0,
+    )).await?;
+    homebrew.latest_report().await?;
+    homebrew.value("co2", Some("indoor")).await?;
+    homebrew.device_statuses().await?;
+    combo.current_conditions().await?;
+    Ok(())
+}
+
+/// A metric has grown when even its lowest value over the last quarter of the run is above its
+/// highest over the first quarter (after warm-up) by more than `tolerance`; steady state noise
+/// overlaps between the two windows, a leak doesn't
+fn grew(samples: &[Sample], metric: fn(&Sample) -> u64, tolerance: impl Fn(u64) -> u64) -> Option<(u64, u64)> {
+    let quarter = samples.len() / 4;
+    let early = samples[..quarter].iter().map(metric).max()?;
+    let late = samples[samples.len() - quarter..].iter().map(metric).min()?;
+    (late > early + tolerance(early)).then_some((early, late))
+}
+
+/// A sampled metric with the growth it may show before the run fails, given its early maximum
+type Check = (&'static str, fn(&Sample) -> u64, Box<dyn Fn(u64) -> u64>);
+
+fn judge(samples: &[Sample]) -> Vec<String> {
+    // Caches, pools and allocator arenas fill up during the first tenth of the run
+    let warmed = &samples[(samples.len() / 10).max(1)..];
+    if warmed.len() < 8 {
+        println!("soak: only {} samples after warm-up, too short to judge growth", warmed.len());
+        return Vec::new();
+    }
+
+    let rss_percent: u64 = env_or("SOAK_RSS_GROWTH_PERCENT", 20);
+    let fd_growth: u64 = env_or("SOAK_FD_GROWTH", 16);
+    let checks: [Check; 4] = [
+        ("RSS (kB)", |s| s.rss_kb, Box::new(move |early| early * rss_percent / 100)),
+        ("open file descriptors", |s| s.fds, Box::new(move |_| fd_growth)),
+        ("homebrew pool connections", |s| s.homebrew_pool, Box::new(|_| 0)),
+        ("combo pool connections", |s| s.combo_pool, Box::new(|_| 0)),
+    ];
+
+    checks.into_iter()
+        .filter_map(|(name, metric, tolerance)| grew(warmed, metric, tolerance)
+            .map(|(early, late)| format!("{} grew from at most {} to at least {}", name, early, late)))
+        .collect()
+}
+
+#[tokio::main]
+async fn main() {
+    if !cfg!(target_os = "linux") {
+        eprintln!("soak: samples are read from /proc/self, Linux only");
+        std::process::exit(2);
+    }
+
+    let duration = Duration::from_secs(env_or("SOAK_DURATION_SECS", 4 * 60 * 60));
+    let interval = Duration::from_secs(env_or("SOAK_SAMPLE_SECS", 60));
+    let workers: u64 = env_or("SOAK_WORKERS", 4);
+
+    let docker = Cli::default();
+    let postgres = docker.run(Postgres::default());
+    let address = format!("127.0.0.1:{}", postgres.get_host_port_ipv4(5432));
+
+    let registry: DeviceRegistry = serde_json::from_value(serde_json::json!({
+        "devices": [{
+            "id": "garden",
+            "name": "Garden sensor",
+            "device_type": "outdoor",
+            "rtl433": { "model": "Acurite-Tower", "channel": "A", "id": 1234 }
+        }]
+    })).expect("device registry");
+
+    let mut homebrew = homebrew::Config::new(API_KEY, homebrew::PostgresServer {
+        db_name: "postgres".to_string(),
+        username: "postgres".to_string(),
+        password: "postgres".into(),
+        address: address.clone(),
+    }, free_port()).with_devices(registry);
+    homebrew.init().await.expect("homebrew server");
+
+    let mut combo = combo::Config::new(None, Some(homebrew.clone()), API_KEY, Some(60), combo::PostgresServer {
+        db_name: "postgres".to_string(),
+        username: "postgres".to_string(),
+        password: "postgres".into(),
+        address,
+    }, free_port(), "12345".to_string());
+    combo.init().await.expect("combo server");
+
+    println!("soak: {} workers for {:?}, sampling every {:?}", workers, duration, interval);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let requests = Arc::new(AtomicU64::new(0));
+    let errors = Arc::new(AtomicU64::new(0));
+    let tasks = (0..workers).map(|worker| {
+        let (homebrew_client, combo_client) = (client(homebrew.port), client(combo.port));
+        let (stop, requests, errors) = (stop.clone(), requests.clone(), errors.clone());
+        tokio::spawn(async move {
+            let mut i = worker;
+            while !stop.load(Ordering::Relaxed) {
+                if let Err(e) = round(&homebrew_client, &combo_client, i).await {
+                    if errors.fetch_add(1, Ordering::Relaxed) < 10 {
+                        eprintln!("soak: request failed: {}", e);
+                    }
+                }
+                requests.fetch_add(1, Ordering::Relaxed);
+                i += workers;
+            }
+        })
+    }).collect::<Vec<_>>();
+
+    let started = Instant::now();
+    let mut samples = Vec::new();
+    let mut ticker = tokio::time::interval(interval);
+    while started.elapsed() < duration {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => {
+                println!("soak: interrupted, judging the samples so far");
+                break;
+            }
+        }
+        let sample = sample();
+        println!(
+            "soak: {:>6}s rounds={} errors={} rss_kb={} fds={} homebrew_pool={} combo_pool={}",
+            started.elapsed().as_secs(), requests.load(Ordering::Relaxed), errors.load(Ordering::Relaxed),
+            sample.rss_kb, sample.fds, sample.homebrew_pool, sample.combo_pool,
+        );
+        samples.push(sample);
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for task in tasks {
+        let _ = task.await;
+    }
+    combo.shutdown().await;
+    homebrew.shutdown().await;
+
+    let failures = judge(&samples);
+    if failures.is_empty() {
+        println!("soak: passed, {} rounds with {} errors", requests.load(Ordering::Relaxed), errors.load(Ordering::Relaxed));
+    } else {
+        for failure in &failures {
+            eprintln!("soak: FAILED: {}", failure);
+        }
+        std::process::exit(1);
+    }
+}
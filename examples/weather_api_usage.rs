@@ -8,7 +8,7 @@ use std::env;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    simple_logger::init().unwrap();
+    jupiter::logging::init_from_env();
     
     println!("Weather Provider API Examples\n");
     
@@ -31,13 +31,13 @@ async fn example_single_provider() -> Result<(), Box<dyn std::error::Error>> {
         Ok(weather) => {
             println!("Provider: {}", provider.name());
             println!("Location: {}", weather.location.name);
-            println!("Temperature: {:.1}°C", weather.temperature);
-            println!("Feels like: {:.1}°C", weather.feels_like.unwrap_or(0.0));
+            println!("Temperature: {:.1}°C", weather.temperature.celsius());
+            println!("Feels like: {:.1}°C", weather.feels_like.map_or(0.0, |t| t.celsius()));
             println!("Humidity: {:.0}%", weather.humidity.unwrap_or(0.0));
             println!("Description: {}", weather.description);
             
             if let Some(wind_speed) = weather.wind_speed {
-                println!("Wind: {:.1} m/s", wind_speed);
+                println!("Wind: {:.1} km/h", wind_speed.kmh());
             }
             
             if let Some(precipitation) = weather.precipitation {
@@ -80,7 +80,7 @@ async fn example_combo_provider() -> Result<(), Box<dyn std::error::Error>> {
     match combo.get_current_weather("New York").await {
         Ok(weather) => {
             println!("\nAveraged Weather Data:");
-            println!("Temperature: {:.1}°C", weather.temperature);
+            println!("Temperature: {:.1}°C", weather.temperature.celsius());
             println!("Description: {}", weather.description);
         }
         Err(e) => {
@@ -102,11 +102,7 @@ async fn example_homebrew_monitoring() -> Result<(), Box<dyn std::error::Error>>
         return Ok(());
     }
     
-    let config = HomebrewConfig {
-        apikey: "internal_key".to_string(),
-        pg: PostgresServer::new(),
-        port: 8080,
-    };
+    let config = HomebrewConfig::new("internal_key", PostgresServer::new()?, 8080);
     
     let mut provider = HomebrewProvider::new(config.clone());
     
@@ -129,7 +125,7 @@ async fn example_homebrew_monitoring() -> Result<(), Box<dyn std::error::Error>>
     match provider.get_current_weather("home").await {
         Ok(weather) => {
             println!("Home Station Data:");
-            println!("Temperature: {:.1}°C", weather.temperature);
+            println!("Temperature: {:.1}°C", weather.temperature.celsius());
             
             if let Some(humidity) = weather.humidity {
                 println!("Humidity: {:.0}%", humidity);
@@ -228,7 +224,7 @@ async fn example_forecast_comparison() -> Result<(), Box<dyn std::error::Error>>
                 for day in &forecast.daily {
                     println!("  {}", day.date);
                     println!("    Min: {:.1}°C, Max: {:.1}°C", 
-                        day.temperature_min, day.temperature_max);
+                        day.temperature_min.celsius(), day.temperature_max.celsius());
                     
                     if let Some(humidity) = day.humidity {
                         println!("    Humidity: {:.0}%", humidity);
@@ -0,0 +1,194 @@
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+
+use crate::db_pool::get_homebrew_pool;
+use crate::devices::Device;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::homebrew::WeatherReport;
+use crate::query_cache;
+use crate::summary;
+use crate::report_chain::ChainEntry;
+use crate::utils::time::safe_timestamp_with_fallback;
+
+// Data-subject operations for a single device: export everything stored for it, and
+// irreversibly erase it. Erasure is two-step: the first request returns a confirmation token,
+// which has to be sent back within CONFIRMATION_TTL_SECS to actually delete anything.
+// Every erasure is recorded in `erasure_log` (without any of the erased readings).
+
+const CONFIRMATION_TTL_SECS: i64 = 10 * 60;
+
+// Pending erasure confirmations: token -> (device id, expiry)
+static PENDING_ERASURES: Lazy<Mutex<HashMap<String, (String, i64)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.erasure_log (
+        id serial NOT NULL,
+        device_id varchar NOT NULL,
+        reports_deleted BIGINT DEFAULT 0,
+        chain_entries_erased BIGINT DEFAULT 0,
+        timestamp BIGINT DEFAULT 0,
+        CONSTRAINT erasure_log_pkey PRIMARY KEY (id));"
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceExport {
+    pub device_id: String,
+    pub exported_at: i64,
+    pub registration: Option<Device>,
+    pub reports: Vec<WeatherReport>,
+    pub audit_entries: Vec<ChainEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErasureConfirmation {
+    pub device_id: String,
+    pub confirmation_token: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErasureResult {
+    pub device_id: String,
+    pub reports_deleted: u64,
+    pub chain_entries_erased: u64,
+    pub timestamp: i64,
+}
+
+/// Issues a single-use token confirming the erasure of `device_id`
+pub fn request_erasure(device_id: &str) -> JupiterResult<ErasureConfirmation> {
+    let token: String = thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+    let expires_at = safe_timestamp_with_fallback() + CONFIRMATION_TTL_SECS;
+
+    let mut pending = PENDING_ERASURES.lock()
+        .map_err(|e| JupiterError::LockError(format!("Erasure confirmations lock poisoned: {}", e)))?;
+    pending.retain(|_, (_, expiry)| *expiry > safe_timestamp_with_fallback());
+    pending.insert(token.clone(), (device_id.to_string(), expires_at));
+
+    Ok(ErasureConfirmation {
+        device_id: device_id.to_string(),
+        confirmation_token: token,
+        expires_at,
+    })
+}
+
+/// Consumes a confirmation token; it has to have been issued for the same device and not expired
+fn take_confirmation(device_id: &str, token: &str) -> JupiterResult<()> {
+    let mut pending = PENDING_ERASURES.lock()
+        .map_err(|e| JupiterError::LockError(format!("Erasure confirmations lock poisoned: {}", e)))?;
+    match pending.remove(token) {
+        Some((id, expiry)) if id == device_id && expiry > safe_timestamp_with_fallback() => Ok(()),
+        _ => Err(JupiterError::ValidationError("Invalid or expired confirmation token".to_string())),
+    }
+}
+
+/// Everything stored for a device: its registration, reports and audit chain entries
+pub async fn export_device(device_id: &str, registration: Option<Device>) -> JupiterResult<DeviceExport> {
+    let pool = get_homebrew_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+
+    let rows = client.query("SELECT * FROM weather_reports WHERE device_id = $1 ORDER BY timestamp", &[&device_id]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+    let reports = rows.iter().map(WeatherReport::from_row).collect::<JupiterResult<Vec<_>>>()?;
+
+    // The chain table only exists when REPORT_HASH_CHAIN is enabled
+    let oids: Vec<String> = reports.iter().map(|r| r.oid.clone()).collect();
+    let audit_entries = match client.query("SELECT * FROM report_chain WHERE report_oid = ANY($1) ORDER BY id", &[&oids]).await {
+        Ok(rows) => rows.iter().map(|row| ChainEntry {
+            id: row.get("id"),
+            report_oid: row.get("report_oid"),
+            payload: row.get("payload"),
+            prev_hash: row.get("prev_hash"),
+            hash: row.get("hash"),
+            timestamp: row.get("timestamp"),
+        }).collect(),
+        Err(_) => Vec::new(),
+    };
+
+    Ok(DeviceExport {
+        device_id: device_id.to_string(),
+        exported_at: safe_timestamp_with_fallback(),
+        registration,
+        reports,
+        audit_entries,
+    })
+}
+
+/// Irreversibly deletes a device's reports and their rollups and blanks the payloads of its
+/// audit chain entries, then records the erasure itself in `erasure_log`
+pub async fn erase_device(device_id: &str, confirmation_token: &str) -> JupiterResult<ErasureResult> {
+    take_confirmation(device_id, confirmation_token)?;
+
+    let pool = get_homebrew_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let mut client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    let db_error = |e: tokio_postgres::Error| JupiterError::DatabaseError(format!("Erasure failed: {}", e));
+
+    let transaction = client.transaction().await.map_err(db_error)?;
+    let chain_exists: bool = transaction.query_one("SELECT to_regclass('public.report_chain') IS NOT NULL", &[]).await
+        .map_err(db_error)?
+        .get(0);
+    let chain_entries_erased = if chain_exists {
+        transaction.execute(
+            "UPDATE report_chain SET payload = '' WHERE report_oid IN (SELECT oid FROM weather_reports WHERE device_id = $1)",
+            &[&device_id],
+        ).await.map_err(db_error)?
+    } else {
+        0
+    };
+    let reports_deleted = transaction.execute("DELETE FROM weather_reports WHERE device_id = $1", &[&device_id]).await
+        .map_err(db_error)?;
+    for table in ["weather_reports_hourly", "weather_reports_daily"] {
+        transaction.execute(&format!("DELETE FROM {} WHERE device_id = $1", table), &[&device_id]).await
+            .map_err(db_error)?;
+    }
+
+    let timestamp = safe_timestamp_with_fallback();
+    transaction.execute(
+        "INSERT INTO erasure_log (device_id, reports_deleted, chain_entries_erased, timestamp) VALUES ($1, $2, $3, $4)",
+        &[&device_id, &(reports_deleted as i64), &(chain_entries_erased as i64), &timestamp],
+    ).await.map_err(db_error)?;
+    transaction.commit().await.map_err(db_error)?;
+    query_cache::RANGE_CACHE.clear();
+    // The summary views would keep the device's readings in their averages until the next refresh
+    if summary::enabled() {
+        if let Err(e) = summary::refresh().await {
+            log::error!("[admin] Failed to refresh the summary views after erasing {}: {}", device_id, e);
+        }
+    }
+
+    let result = ErasureResult {
+        device_id: device_id.to_string(),
+        reports_deleted,
+        chain_entries_erased,
+        timestamp,
+    };
+
+    log::warn!("[admin] Erased data for device {}: {} reports deleted, {} audit entries blanked",
+        device_id, result.reports_deleted, result.chain_entries_erased);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirmation_tokens() {
+        let confirmation = request_erasure("garden").unwrap();
+        assert_eq!(confirmation.confirmation_token.len(), 32);
+
+        // Tokens are bound to the device they were issued for...
+        assert!(take_confirmation("shed", &confirmation.confirmation_token).is_err());
+        let confirmation = request_erasure("garden").unwrap();
+        assert!(take_confirmation("garden", &confirmation.confirmation_token).is_ok());
+        // ...and can only be used once
+        assert!(take_confirmation("garden", &confirmation.confirmation_token).is_err());
+    }
+}
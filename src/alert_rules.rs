@@ -0,0 +1,418 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_postgres::Row;
+
+use crate::db_pool::homebrew_client;
+use crate::devices::{self, PLACEMENTS};
+use crate::error::Result as JupiterResult;
+use crate::provider::common::{Alert, AlertSeverity};
+use crate::provider::homebrew::{self, WeatherReport};
+use crate::utils::time::format_rfc3339;
+
+// Threshold rules for homebrew readings, kept in the homebrew database's `alert_rules` table and
+// managed at /api/alert_rules. A rule compares one report field of the devices it covers (one
+// device id or placement, every device without `device`) with a value:
+//
+// { "id": "indoor-co2", "metric": "co2", "comparator": ">", "value": 1000, "severity": "Moderate",
+//   "duration_secs": 600, "device": "indoor", "title": "High CO2 Levels" }
+//
+// Every report is checked as it is stored. A rule alerts for a device once the device's readings
+// have met it for `duration_secs` (0: from the first one), and clears at the first reading that
+// doesn't, or after STALE_SECS without readings. When rules on the same metric and devices alert
+// for a device together, only the most severe one shows, so rules at 1000 and 2000 ppm escalate.
+// The migration creating the table adds the thresholds the homebrew alerts used to have built in.
+
+/// Readings older than this no longer keep an alert up
+const STALE_SECS: i64 = 3600;
+
+fn default_severity() -> AlertSeverity {
+    AlertSeverity::Moderate
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Comparator {
+    #[serde(rename = ">", alias = "gt")]
+    Above,
+    #[serde(rename = ">=", alias = "gte")]
+    AtLeast,
+    #[serde(rename = "<", alias = "lt")]
+    Below,
+    #[serde(rename = "<=", alias = "lte")]
+    AtMost,
+}
+
+impl Comparator {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Comparator::Above => ">",
+            Comparator::AtLeast => ">=",
+            Comparator::Below => "<",
+            Comparator::AtMost => "<=",
+        }
+    }
+
+    pub fn holds(&self, reading: f64, value: f64) -> bool {
+        match self {
+            Comparator::Above => reading > value,
+            Comparator::AtLeast => reading >= value,
+            Comparator::Below => reading < value,
+            Comparator::AtMost => reading <= value,
+        }
+    }
+
+    /// Whether high readings meet it, for telling escalating rules from opposite ones
+    fn upward(&self) -> bool {
+        matches!(self, Comparator::Above | Comparator::AtLeast)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: String,
+    pub metric: String,
+    pub comparator: Comparator,
+    pub value: f64,
+    #[serde(default = "default_severity")]
+    pub severity: AlertSeverity,
+    #[serde(default)]
+    pub duration_secs: i64,
+    #[serde(default)]
+    pub device: Option<String>,     // Device id or placement
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl AlertRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if !devices::valid_id(&self.id) {
+            return Err("id must be 1-64 letters, digits, - or _".to_string());
+        }
+        if !WeatherReport::is_value_field(&self.metric) {
+            return Err(format!("unknown metric '{}'", self.metric));
+        }
+        if !self.value.is_finite() {
+            return Err("value must be a number".to_string());
+        }
+        if !(0..=7 * 86_400).contains(&self.duration_secs) {
+            return Err("duration_secs must be between 0 and 604800".to_string());
+        }
+        match &self.device {
+            Some(device) if !devices::valid_id(device) => Err(format!("device must be a device id or one of {}", PLACEMENTS.join(", "))),
+            _ => Ok(()),
+        }
+    }
+
+    fn covers(&self, report: &WeatherReport) -> bool {
+        match &self.device {
+            Some(device) if PLACEMENTS.contains(&device.as_str()) => report.device_type == *device,
+            Some(device) => report.device_id.as_deref() == Some(device.as_str()),
+            None => true,
+        }
+    }
+
+    pub fn title(&self) -> String {
+        self.title.clone().unwrap_or_else(|| format!("{} {} {}", self.metric, self.comparator.symbol(), self.value))
+    }
+}
+
+/// The rules the homebrew alerts had built in, added by the migration creating the table
+pub fn default_rules() -> Vec<AlertRule> {
+    let rule = |id: &str, metric: &str, value: f64, severity: AlertSeverity, device: &str, title: &str| AlertRule {
+        id: id.to_string(),
+        metric: metric.to_string(),
+        comparator: Comparator::Above,
+        value,
+        severity,
+        duration_secs: 0,
+        device: Some(device.to_string()),
+        title: Some(title.to_string()),
+        enabled: true,
+    };
+    vec![
+        rule("outdoor-pm25", "pm25", 35.0, AlertSeverity::Moderate, "outdoor", "Poor Air Quality (PM2.5)"),
+        rule("outdoor-pm25-severe", "pm25", 55.0, AlertSeverity::Severe, "outdoor", "Poor Air Quality (PM2.5)"),
+        rule("indoor-co2", "co2", 1000.0, AlertSeverity::Moderate, "indoor", "High CO2 Levels"),
+        rule("indoor-co2-severe", "co2", 2000.0, AlertSeverity::Severe, "indoor", "High CO2 Levels"),
+        rule("indoor-tvoc", "tvoc", 500.0, AlertSeverity::Moderate, "indoor", "High TVOC Levels"),
+        rule("indoor-tvoc-severe", "tvoc", 1000.0, AlertSeverity::Severe, "indoor", "High TVOC Levels"),
+    ]
+}
+
+/// A device meeting a rule
+#[derive(Debug, Clone, PartialEq)]
+struct Tracked {
+    since: i64,     // First of the readings in a row meeting the rule
+    last: i64,
+    reading: f64,
+}
+
+/// Metric, direction, `device` and device of rules escalating one another
+type Escalation = (String, bool, Option<String>, String);
+
+/// The rules and, per rule and device, how long the device's readings have met it
+#[derive(Debug, Default)]
+pub struct Engine {
+    rules: Vec<AlertRule>,
+    tracked: HashMap<(String, String), Tracked>,
+}
+
+impl Engine {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self { rules, tracked: HashMap::new() }
+    }
+
+    /// Uses `rules` from now on, keeping the progress of rules that didn't change
+    pub fn set_rules(&mut self, rules: Vec<AlertRule>) {
+        let old = std::mem::take(&mut self.rules);
+        self.tracked.retain(|(id, _), _| {
+            let unchanged = |rules: &[AlertRule]| rules.iter().find(|rule| rule.id == *id).cloned();
+            unchanged(&old).is_some() && unchanged(&old) == unchanged(&rules)
+        });
+        self.rules = rules;
+    }
+
+    pub fn observe(&mut self, report: &WeatherReport) {
+        let device = report.device_id.clone().unwrap_or_else(|| report.device_type.clone());
+        for rule in self.rules.iter().filter(|rule| rule.enabled && rule.covers(report)) {
+            let Some(reading) = report.value(&rule.metric) else { continue };
+            let key = (rule.id.clone(), device.clone());
+            if !rule.comparator.holds(reading, rule.value) {
+                self.tracked.remove(&key);
+                continue;
+            }
+            let tracked = self.tracked.entry(key).or_insert(Tracked { since: report.timestamp, last: report.timestamp, reading });
+            // Late reports don't move the latest reading back
+            if report.timestamp >= tracked.last {
+                tracked.last = report.timestamp;
+                tracked.reading = reading;
+            }
+        }
+    }
+
+    /// Alerts of the rules devices have met for long enough, the most severe of escalating rules
+    pub fn alerts(&self, now: i64) -> Vec<Alert> {
+        let mut shown: HashMap<Escalation, (&AlertRule, &Tracked)> = HashMap::new();
+        for ((id, device), tracked) in &self.tracked {
+            let Some(rule) = self.rules.iter().find(|rule| rule.id == *id) else { continue };
+            if now - tracked.last >= STALE_SECS || tracked.last - tracked.since < rule.duration_secs {
+                continue;
+            }
+            let key = (rule.metric.clone(), rule.comparator.upward(), rule.device.clone(), device.clone());
+            match shown.get(&key) {
+                Some((current, _)) if current.severity >= rule.severity => {},
+                _ => { shown.insert(key, (rule, tracked)); },
+            }
+        }
+        let mut alerts: Vec<(i64, Alert)> = shown.into_iter().map(|((_, _, _, device), (rule, tracked))| {
+            let held = match (tracked.last - tracked.since) / 60 {
+                0 => String::new(),
+                minutes => format!(" for {} minutes", minutes),
+            };
+            (tracked.since, Alert {
+                title: rule.title(),
+                description: format!("{} on {} is {:.1} ({} {}){}", rule.metric, device, tracked.reading, rule.comparator.symbol(), rule.value, held),
+                severity: rule.severity.clone(),
+                start: format_rfc3339(tracked.since),
+                end: None,
+                regions: vec![device],
+            })
+        }).collect();
+        alerts.sort_by(|(a_since, a), (b_since, b)| b.severity.cmp(&a.severity).then(a_since.cmp(b_since)).then_with(|| a.regions.cmp(&b.regions)));
+        alerts.into_iter().map(|(_, alert)| alert).collect()
+    }
+}
+
+static ENGINE: Lazy<RwLock<Engine>> = Lazy::new(|| RwLock::new(Engine::new(default_rules())));
+
+/// Checks a stored report against the rules
+pub fn observe(report: &WeatherReport) {
+    if let Ok(mut engine) = ENGINE.write() {
+        engine.observe(report);
+    }
+}
+
+/// Alerts of the rules met now
+pub fn active_alerts() -> Vec<Alert> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    ENGINE.read().map(|engine| engine.alerts(now)).unwrap_or_default()
+}
+
+/// Serves the rules as they are in the table, after a change through /api/alert_rules
+pub async fn reload() -> JupiterResult<()> {
+    let rules = select_all().await?;
+    if let Ok(mut engine) = ENGINE.write() {
+        engine.set_rules(rules);
+    }
+    Ok(())
+}
+
+/// Loads the rules (the built-in ones without a Postgres database) and checks the last
+/// STALE_SECS of reports against them, so alerts already on show without waiting for readings
+pub async fn load(config: &homebrew::Config) -> JupiterResult<()> {
+    if config.store.uses_postgres() {
+        reload().await?;
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let mut recent = WeatherReport::select(config.clone(), Some(homebrew::RANGE_LIMIT), None, Some("timestamp".to_string()), None).await?;
+    recent.retain(|report| now - report.timestamp < STALE_SECS);
+    if let Ok(mut engine) = ENGINE.write() {
+        for report in recent.iter().rev() {
+            engine.observe(report);
+        }
+    }
+    Ok(())
+}
+
+pub fn sql_build_statement() -> &'static str {
+    r#"CREATE TABLE IF NOT EXISTS public.alert_rules (
+        id varchar NOT NULL,
+        details jsonb NOT NULL DEFAULT '{}',
+        created_at BIGINT DEFAULT 0,
+        CONSTRAINT alert_rules_pkey PRIMARY KEY (id));
+    INSERT INTO alert_rules (id, details) VALUES
+        ('outdoor-pm25', '{"metric": "pm25", "comparator": ">", "value": 35, "severity": "Moderate", "device": "outdoor", "title": "Poor Air Quality (PM2.5)"}'),
+        ('outdoor-pm25-severe', '{"metric": "pm25", "comparator": ">", "value": 55, "severity": "Severe", "device": "outdoor", "title": "Poor Air Quality (PM2.5)"}'),
+        ('indoor-co2', '{"metric": "co2", "comparator": ">", "value": 1000, "severity": "Moderate", "device": "indoor", "title": "High CO2 Levels"}'),
+        ('indoor-co2-severe', '{"metric": "co2", "comparator": ">", "value": 2000, "severity": "Severe", "device": "indoor", "title": "High CO2 Levels"}'),
+        ('indoor-tvoc', '{"metric": "tvoc", "comparator": ">", "value": 500, "severity": "Moderate", "device": "indoor", "title": "High TVOC Levels"}'),
+        ('indoor-tvoc-severe', '{"metric": "tvoc", "comparator": ">", "value": 1000, "severity": "Severe", "device": "indoor", "title": "High TVOC Levels"}')
+    ON CONFLICT (id) DO NOTHING;"#
+}
+
+/// Everything but the id, stored as `details`
+fn details(rule: &AlertRule) -> JupiterResult<Value> {
+    let mut details = serde_json::to_value(rule)?;
+    if let Some(fields) = details.as_object_mut() {
+        fields.remove("id");
+    }
+    Ok(details)
+}
+
+fn from_row(row: &Row) -> JupiterResult<AlertRule> {
+    let mut details: Value = row.try_get("details")?;
+    let id: String = row.try_get("id")?;
+    if let Some(fields) = details.as_object_mut() {
+        fields.insert("id".to_string(), Value::String(id));
+    }
+    Ok(serde_json::from_value(details)?)
+}
+
+pub async fn select_all() -> JupiterResult<Vec<AlertRule>> {
+    let rows = homebrew_client().await?.query("SELECT * FROM alert_rules ORDER BY id", &[]).await?;
+    rows.iter().map(from_row).collect()
+}
+
+/// Adds a rule unless one with its id exists; returns whether it was added
+pub async fn insert(rule: &AlertRule, created_at: i64) -> JupiterResult<bool> {
+    let inserted = homebrew_client().await?.execute(
+        "INSERT INTO alert_rules (id, details, created_at) VALUES ($1, $2, $3) ON CONFLICT (id) DO NOTHING",
+        &[&rule.id, &details(rule)?, &created_at],
+    ).await?;
+    Ok(inserted == 1)
+}
+
+/// Replaces everything but the id of a rule; returns whether it exists
+pub async fn update(rule: &AlertRule) -> JupiterResult<bool> {
+    let updated = homebrew_client().await?.execute("UPDATE alert_rules SET details = $2 WHERE id = $1", &[&rule.id, &details(rule)?]).await?;
+    Ok(updated == 1)
+}
+
+/// Returns whether the rule existed
+pub async fn delete(id: &str) -> JupiterResult<bool> {
+    Ok(homebrew_client().await?.execute("DELETE FROM alert_rules WHERE id = $1", &[&id]).await? == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(device_id: &str, device_type: &str, timestamp: i64, co2: f64) -> WeatherReport {
+        let mut report = WeatherReport::new();
+        report.device_id = Some(device_id.to_string());
+        report.device_type = device_type.to_string();
+        report.timestamp = timestamp;
+        report.co2 = Some(co2);
+        report
+    }
+
+    #[test]
+    fn test_rules_parse_and_validate() {
+        let rule: AlertRule = serde_json::from_str(r#"{"id": "damp", "metric": "humidity", "comparator": "gte", "value": 70, "duration_secs": 1800}"#).unwrap();
+        assert_eq!(rule.comparator, Comparator::AtLeast);
+        assert_eq!(rule.severity, AlertSeverity::Moderate);
+        assert!(rule.enabled);
+        assert_eq!(rule.validate(), Ok(()));
+        assert_eq!(rule.title(), "humidity >= 70");
+
+        assert!(AlertRule { metric: "radon".to_string(), ..rule.clone() }.validate().is_err());
+        assert!(AlertRule { duration_secs: -1, ..rule.clone() }.validate().is_err());
+        assert!(serde_json::from_str::<AlertRule>(r#"{"id": "x", "metric": "co2", "comparator": "~", "value": 1}"#).is_err());
+
+        // The seeded rules are the built-in ones
+        let seeded: Vec<(String, Value)> = default_rules().iter().map(|rule| (rule.id.clone(), details(rule).unwrap())).collect();
+        for (id, details) in seeded {
+            assert!(sql_build_statement().contains(&format!("'{}'", id)));
+            let mut details = details;
+            details.as_object_mut().unwrap().retain(|field, _| !matches!(field.as_str(), "duration_secs" | "enabled"));
+            assert!(details.as_object().unwrap().len() == 6);
+        }
+    }
+
+    #[test]
+    fn test_duration_and_escalation() {
+        let mut rules = default_rules();
+        rules[2].duration_secs = 600;
+        let mut engine = Engine::new(rules);
+
+        engine.observe(&reading("office", "indoor", 1000, 1500.0));
+        engine.observe(&reading("porch", "outdoor", 1000, 2500.0));
+        assert!(engine.alerts(1000).is_empty());
+
+        engine.observe(&reading("office", "indoor", 1300, 1600.0));
+        assert!(engine.alerts(1300).is_empty());
+        engine.observe(&reading("office", "indoor", 1600, 2100.0));
+        let alerts = engine.alerts(1600);
+        // Moderate held for 10 minutes, severe from its first reading: only the severe one shows
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, AlertSeverity::Severe);
+        assert_eq!(alerts[0].title, "High CO2 Levels");
+        assert_eq!(alerts[0].regions, vec!["office".to_string()]);
+        assert_eq!(alerts[0].description, "co2 on office is 2100.0 (> 2000)");
+
+        engine.observe(&reading("office", "indoor", 1900, 1800.0));
+        let alerts = engine.alerts(1900);
+        assert_eq!(alerts[0].severity, AlertSeverity::Moderate);
+        assert_eq!(alerts[0].description, "co2 on office is 1800.0 (> 1000) for 15 minutes");
+        assert_eq!(alerts[0].start, "1970-01-01T00:16:40Z");
+
+        // Stale readings, then a reading back under the threshold, clear it
+        assert!(engine.alerts(1900 + STALE_SECS).is_empty());
+        engine.observe(&reading("office", "indoor", 2200, 800.0));
+        assert!(engine.alerts(2200).is_empty());
+    }
+
+    #[test]
+    fn test_changed_rules_start_over() {
+        let mut engine = Engine::new(default_rules());
+        engine.observe(&reading("office", "indoor", 1000, 1500.0));
+        assert_eq!(engine.alerts(1000).len(), 1);
+
+        let mut rules = default_rules();
+        rules[2].value = 1200.0;
+        rules[4].enabled = false;
+        engine.set_rules(rules);
+        assert!(engine.alerts(1000).is_empty());
+        engine.observe(&reading("office", "indoor", 1060, 1500.0));
+        assert_eq!(engine.alerts(1060)[0].description, "co2 on office is 1500.0 (> 1200)");
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+
+// US EPA Air Quality Index (2024 PM2.5 breakpoints) and the European Environment Agency's
+// European Air Quality Index from pollutant concentrations (GET /api/air_quality).
+// Each US row is (concentration low, concentration high, index low, index high).
+
+const PM25_BREAKPOINTS: [(f64, f64, f64, f64); 6] = [
+    (0.0, 9.0, 0.0, 50.0),
+    (9.1, 35.4, 51.0, 100.0),
+    (35.5, 55.4, 101.0, 150.0),
+    (55.5, 125.4, 151.0, 200.0),
+    (125.5, 225.4, 201.0, 300.0),
+    (225.5, 325.4, 301.0, 500.0),
+];
+
+const PM10_BREAKPOINTS: [(f64, f64, f64, f64); 6] = [
+    (0.0, 54.0, 0.0, 50.0),
+    (55.0, 154.0, 51.0, 100.0),
+    (155.0, 254.0, 101.0, 150.0),
+    (255.0, 354.0, 151.0, 200.0),
+    (355.0, 424.0, 201.0, 300.0),
+    (425.0, 604.0, 301.0, 500.0),
+];
+
+// Ozone 8-hour, ppm. Above 0.200 ppm the EPA switches to 1-hour averages, which
+// providers don't report, so higher concentrations are capped at 300.
+const O3_BREAKPOINTS: [(f64, f64, f64, f64); 5] = [
+    (0.0, 0.054, 0.0, 50.0),
+    (0.055, 0.070, 51.0, 100.0),
+    (0.071, 0.085, 101.0, 150.0),
+    (0.086, 0.105, 151.0, 200.0),
+    (0.106, 0.200, 201.0, 300.0),
+];
+
+// Nitrogen dioxide 1-hour, ppb
+const NO2_BREAKPOINTS: [(f64, f64, f64, f64); 6] = [
+    (0.0, 53.0, 0.0, 50.0),
+    (54.0, 100.0, 51.0, 100.0),
+    (101.0, 360.0, 101.0, 150.0),
+    (361.0, 649.0, 151.0, 200.0),
+    (650.0, 1249.0, 201.0, 300.0),
+    (1250.0, 2049.0, 301.0, 500.0),
+];
+
+// Sulfur dioxide 1-hour, ppb
+const SO2_BREAKPOINTS: [(f64, f64, f64, f64); 6] = [
+    (0.0, 35.0, 0.0, 50.0),
+    (36.0, 75.0, 51.0, 100.0),
+    (76.0, 185.0, 101.0, 150.0),
+    (186.0, 304.0, 151.0, 200.0),
+    (305.0, 604.0, 201.0, 300.0),
+    (605.0, 1004.0, 301.0, 500.0),
+];
+
+// Carbon monoxide 8-hour, ppm
+const CO_BREAKPOINTS: [(f64, f64, f64, f64); 6] = [
+    (0.0, 4.4, 0.0, 50.0),
+    (4.5, 9.4, 51.0, 100.0),
+    (9.5, 12.4, 101.0, 150.0),
+    (12.5, 15.4, 151.0, 200.0),
+    (15.5, 30.4, 201.0, 300.0),
+    (30.5, 50.4, 301.0, 500.0),
+];
+
+// Molar volume of an ideal gas at 25°C and 1 atm, litres, for µg/m³ to ppb
+const MOLAR_VOLUME: f64 = 24.45;
+
+// European AQI upper band limits in µg/m³ for levels 1-5; anything above is level 6
+const EU_PM25_BANDS: [f64; 5] = [10.0, 20.0, 25.0, 50.0, 75.0];
+const EU_PM10_BANDS: [f64; 5] = [20.0, 40.0, 50.0, 100.0, 150.0];
+const EU_NO2_BANDS: [f64; 5] = [40.0, 90.0, 120.0, 230.0, 340.0];
+const EU_O3_BANDS: [f64; 5] = [50.0, 100.0, 130.0, 240.0, 380.0];
+const EU_SO2_BANDS: [f64; 5] = [100.0, 200.0, 350.0, 500.0, 750.0];
+
+const EU_CATEGORIES: [&str; 6] = ["Good", "Fair", "Moderate", "Poor", "Very Poor", "Extremely Poor"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pollutant {
+    Pm25,
+    Pm10,
+    O3,
+    No2,
+    So2,
+    Co,
+}
+
+impl Pollutant {
+    pub fn all() -> [Pollutant; 6] {
+        [Pollutant::Pm25, Pollutant::Pm10, Pollutant::O3, Pollutant::No2, Pollutant::So2, Pollutant::Co]
+    }
+
+    // g/mol, gases only
+    fn molar_mass(&self) -> Option<f64> {
+        match self {
+            Pollutant::O3 => Some(48.00),
+            Pollutant::No2 => Some(46.01),
+            Pollutant::So2 => Some(64.07),
+            Pollutant::Co => Some(28.01),
+            Pollutant::Pm25 | Pollutant::Pm10 => None,
+        }
+    }
+}
+
+/// Concentration of a gas in ppb for a reading in µg/m³; particulates are returned unchanged
+pub fn to_ppb(pollutant: Pollutant, micrograms: f64) -> f64 {
+    match pollutant.molar_mass() {
+        Some(mass) => micrograms * MOLAR_VOLUME / mass,
+        None => micrograms,
+    }
+}
+
+fn index_for(concentration: f64, breakpoints: &[(f64, f64, f64, f64)]) -> u32 {
+    let (c_low, c_high, i_low, i_high) = breakpoints.iter()
+        .find(|(_, c_high, _, _)| concentration <= *c_high)
+        .copied()
+        .unwrap_or(breakpoints[breakpoints.len() - 1]);
+    let concentration = concentration.clamp(c_low, c_high);
+    ((i_high - i_low) / (c_high - c_low) * (concentration - c_low) + i_low).round() as u32
+}
+
+/// AQI for a 24h PM2.5 concentration in µg/m³ (truncated to 0.1 as the EPA specifies)
+pub fn pm25_aqi(pm25: f64) -> u32 {
+    index_for((pm25.max(0.0) * 10.0).trunc() / 10.0, &PM25_BREAKPOINTS)
+}
+
+/// AQI for a 24h PM10 concentration in µg/m³ (truncated to whole units)
+pub fn pm10_aqi(pm10: f64) -> u32 {
+    index_for(pm10.max(0.0).trunc(), &PM10_BREAKPOINTS)
+}
+
+/// US AQI sub-index for a concentration in µg/m³, truncated to the precision the EPA specifies
+/// for each pollutant once converted to its reporting unit
+pub fn sub_index(pollutant: Pollutant, concentration: f64) -> u32 {
+    let concentration = concentration.max(0.0);
+    match pollutant {
+        Pollutant::Pm25 => pm25_aqi(concentration),
+        Pollutant::Pm10 => pm10_aqi(concentration),
+        Pollutant::O3 => index_for(to_ppb(pollutant, concentration).trunc() / 1000.0, &O3_BREAKPOINTS),
+        Pollutant::No2 => index_for(to_ppb(pollutant, concentration).trunc(), &NO2_BREAKPOINTS),
+        Pollutant::So2 => index_for(to_ppb(pollutant, concentration).trunc(), &SO2_BREAKPOINTS),
+        Pollutant::Co => index_for((to_ppb(pollutant, concentration) / 100.0).trunc() / 10.0, &CO_BREAKPOINTS),
+    }
+}
+
+/// EPA category of a US AQI value
+pub fn us_category(aqi: u32) -> &'static str {
+    match aqi {
+        0..=50 => "Good",
+        51..=100 => "Moderate",
+        101..=150 => "Unhealthy for Sensitive Groups",
+        151..=200 => "Unhealthy",
+        201..=300 => "Very Unhealthy",
+        _ => "Hazardous",
+    }
+}
+
+/// European AQI level (1 good to 6 extremely poor) for a concentration in µg/m³; carbon
+/// monoxide is not part of the European index
+pub fn european_level(pollutant: Pollutant, concentration: f64) -> Option<u8> {
+    let bands = match pollutant {
+        Pollutant::Pm25 => &EU_PM25_BANDS,
+        Pollutant::Pm10 => &EU_PM10_BANDS,
+        Pollutant::O3 => &EU_O3_BANDS,
+        Pollutant::No2 => &EU_NO2_BANDS,
+        Pollutant::So2 => &EU_SO2_BANDS,
+        Pollutant::Co => return None,
+    };
+    Some(bands.iter().take_while(|limit| concentration > **limit).count() as u8 + 1)
+}
+
+/// Name of a European AQI level
+pub fn european_category(level: u8) -> &'static str {
+    EU_CATEGORIES[(level.clamp(1, 6) - 1) as usize]
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollutantIndex {
+    pub pollutant: Pollutant,
+    pub concentration: f64,                    // µg/m³
+    pub us_aqi: u32,
+    pub us_category: String,
+    pub european_level: Option<u8>,
+    pub european_category: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AirQualityIndex {
+    pub us_aqi: Option<u32>,
+    pub us_category: Option<String>,
+    pub european_aqi: Option<u8>,
+    pub european_category: Option<String>,
+    pub dominant_pollutant: Option<Pollutant>,  // Highest US sub-index
+    pub pollutants: Vec<PollutantIndex>,
+}
+
+/// US and European indices for a set of concentrations in µg/m³. Both overall indices are the
+/// worst of the pollutant sub-indices.
+pub fn air_quality_index(concentrations: &[(Pollutant, f64)]) -> AirQualityIndex {
+    let pollutants: Vec<PollutantIndex> = concentrations.iter()
+        .map(|&(pollutant, concentration)| {
+            let us_aqi = sub_index(pollutant, concentration);
+            let european_level = european_level(pollutant, concentration);
+            PollutantIndex {
+                pollutant,
+                concentration,
+                us_aqi,
+                us_category: us_category(us_aqi).to_string(),
+                european_level,
+                european_category: european_level.map(|level| european_category(level).to_string()),
+            }
+        })
+        .collect();
+
+    let dominant = pollutants.iter()
+        .fold(None, |worst: Option<&PollutantIndex>, p| match worst {
+            Some(w) if w.us_aqi >= p.us_aqi => Some(w),
+            _ => Some(p),
+        });
+    let european_aqi = pollutants.iter().filter_map(|p| p.european_level).max();
+
+    AirQualityIndex {
+        us_aqi: dominant.map(|p| p.us_aqi),
+        us_category: dominant.map(|p| p.us_category.clone()),
+        european_aqi,
+        european_category: european_aqi.map(|level| european_category(level).to_string()),
+        dominant_pollutant: dominant.map(|p| p.pollutant),
+        pollutants,
+    }
+}
+
+/// Overall AQI is the worst of the available pollutant sub-indices
+pub fn us_aqi(pm25: Option<f64>, pm10: Option<f64>) -> Option<u32> {
+    match (pm25.map(pm25_aqi), pm10.map(pm10_aqi)) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pm25_aqi() {
+        assert_eq!(pm25_aqi(0.0), 0);
+        assert_eq!(pm25_aqi(9.0), 50);
+        assert_eq!(pm25_aqi(35.4), 100);
+        assert_eq!(pm25_aqi(35.47), 100);
+        assert_eq!(pm25_aqi(55.5), 151);
+        assert_eq!(pm25_aqi(1000.0), 500);
+    }
+
+    #[test]
+    fn test_combined_aqi() {
+        assert_eq!(us_aqi(Some(12.0), Some(160.0)), Some(103));
+        assert_eq!(us_aqi(None, Some(54.0)), Some(50));
+        assert_eq!(us_aqi(None, None), None);
+    }
+
+    #[test]
+    fn test_gas_sub_indices() {
+        // 100 µg/m³ of ozone is 50.9 ppb (0.050 ppm)
+        assert_eq!(sub_index(Pollutant::O3, 100.0), 46);
+        assert_eq!(sub_index(Pollutant::O3, 140.0), 101);
+        // 200 µg/m³ of NO2 is 106 ppb
+        assert_eq!(sub_index(Pollutant::No2, 200.0), 102);
+        // 10 mg/m³ of CO is 8.7 ppm
+        assert_eq!(sub_index(Pollutant::Co, 10_000.0), 93);
+        assert_eq!(us_category(101), "Unhealthy for Sensitive Groups");
+    }
+
+    #[test]
+    fn test_air_quality_index() {
+        let index = air_quality_index(&[(Pollutant::Pm25, 22.0), (Pollutant::No2, 30.0), (Pollutant::Co, 300.0)]);
+        assert_eq!(index.us_aqi, Some(75));
+        assert_eq!(index.us_category.as_deref(), Some("Moderate"));
+        assert_eq!(index.dominant_pollutant, Some(Pollutant::Pm25));
+        assert_eq!(index.european_aqi, Some(3));
+        assert_eq!(index.european_category.as_deref(), Some("Moderate"));
+        assert_eq!(index.pollutants[2].european_level, None);
+
+        assert_eq!(european_level(Pollutant::Pm10, 20.0), Some(1));
+        assert_eq!(european_level(Pollutant::So2, 900.0), Some(6));
+        assert_eq!(air_quality_index(&[]).us_aqi, None);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::time::{civil_from_days, days_from_civil};
+
+// Seasonal baselines from the station's own records: the daily outdoor temperature extremes of
+// the same week of the year in earlier years, summarized as percentiles. Days whose high or low
+// fall outside them, or beat every earlier day of their month, are called out in the homebrew
+// daily summaries and alerts ("Warmest March night in 3 years of records"). Days are UTC days
+// and weeks are 7-day blocks from January 1st, so week 53 is a day or two long.
+
+pub const LOW_PERCENTILE: f64 = 10.0;
+pub const HIGH_PERCENTILE: f64 = 90.0;
+
+/// Days of the same week in earlier years needed before their percentiles mean anything
+pub const MIN_BASELINE_DAYS: usize = 5;
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December",
+];
+
+/// Lowest and highest temperature of one day, in °C
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DayExtremes {
+    pub day: i64,                 // Days since 1970-01-01
+    pub low: f64,
+    pub high: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Percentiles {
+    pub low: f64,                 // LOW_PERCENTILE
+    pub median: f64,
+    pub high: f64,                // HIGH_PERCENTILE
+}
+
+/// What the same week looked like in earlier years
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub week: u32,
+    pub years: usize,
+    pub days: usize,
+    pub highs: Percentiles,
+    pub lows: Percentiles,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unusual {
+    pub day: i64,
+    pub record: bool,             // Beat every earlier day of its month rather than just the baseline
+    pub message: String,
+}
+
+/// Week of the year of a day, 1-53
+pub fn week_of_year(day: i64) -> u32 {
+    let (year, _, _) = civil_from_days(day);
+    ((day - days_from_civil(year, 1, 1)) / 7 + 1) as u32
+}
+
+/// Linearly interpolated percentile (0-100) of sorted values
+pub fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64))
+}
+
+fn percentiles(mut values: Vec<f64>) -> Option<Percentiles> {
+    values.sort_by(|a, b| a.total_cmp(b));
+    Some(Percentiles {
+        low: percentile(&values, LOW_PERCENTILE)?,
+        median: percentile(&values, 50.0)?,
+        high: percentile(&values, HIGH_PERCENTILE)?,
+    })
+}
+
+/// Baseline for `day` from the same week of the year in earlier years of `history`; None
+/// without MIN_BASELINE_DAYS of them
+pub fn baseline(history: &[DayExtremes], day: i64) -> Option<Baseline> {
+    let (year, _, _) = civil_from_days(day);
+    let week = week_of_year(day);
+    let same_week: Vec<&DayExtremes> = history.iter()
+        .filter(|d| civil_from_days(d.day).0 < year && week_of_year(d.day) == week)
+        .collect();
+    if same_week.len() < MIN_BASELINE_DAYS {
+        return None;
+    }
+    Some(Baseline {
+        week,
+        years: same_week.iter().map(|d| civil_from_days(d.day).0).collect::<BTreeSet<_>>().len(),
+        days: same_week.len(),
+        highs: percentiles(same_week.iter().map(|d| d.high).collect())?,
+        lows: percentiles(same_week.iter().map(|d| d.low).collect())?,
+    })
+}
+
+/// How `today` stands out from `history` (which may include it), if at all. Records need at
+/// least one earlier year with the same month on file; otherwise a high or low outside the
+/// seasonal baseline's percentiles is reported.
+pub fn unusual(history: &[DayExtremes], today: &DayExtremes) -> Vec<Unusual> {
+    let (year, month, _) = civil_from_days(today.day);
+    let month_name = MONTHS[(month - 1) as usize];
+    let same_month: Vec<&DayExtremes> = history.iter()
+        .filter(|d| d.day < today.day && civil_from_days(d.day).1 == month)
+        .collect();
+    let years: BTreeSet<i64> = same_month.iter().map(|d| civil_from_days(d.day).0).chain([year]).collect();
+    let baseline = baseline(history, today.day);
+
+    // (value, earlier values, what it is, warmer word, colder word, baseline percentiles)
+    let checks = [
+        (today.high, same_month.iter().map(|d| d.high).collect::<Vec<_>>(), "day", "Warmest", "Coolest", "high", baseline.as_ref().map(|b| b.highs)),
+        (today.low, same_month.iter().map(|d| d.low).collect::<Vec<_>>(), "night", "Warmest", "Coldest", "low", baseline.as_ref().map(|b| b.lows)),
+    ];
+    let mut found = Vec::new();
+    for (value, earlier, what, warmest, coldest, measure, percentiles) in checks {
+        if years.len() >= 2 {
+            let record = if earlier.iter().all(|&e| value > e) {
+                Some(warmest)
+            } else if earlier.iter().all(|&e| value < e) {
+                Some(coldest)
+            } else {
+                None
+            };
+            if let Some(superlative) = record {
+                found.push(Unusual {
+                    day: today.day,
+                    record: true,
+                    message: format!("{} {} {} in {} years of records ({:.1}°C)", superlative, month_name, what, years.len(), value),
+                });
+                continue;
+            }
+        }
+        let (Some(percentiles), Some(baseline)) = (percentiles, baseline.as_ref()) else { continue };
+        let (adjective, side, threshold, bound) = if value > percentiles.high {
+            ("warm", "above", HIGH_PERCENTILE, percentiles.high)
+        } else if value < percentiles.low {
+            (if what == "night" { "cold" } else { "cool" }, "below", LOW_PERCENTILE, percentiles.low)
+        } else {
+            continue;
+        };
+        found.push(Unusual {
+            day: today.day,
+            record: false,
+            message: format!(
+                "Unusually {} {} for week {}: {} of {:.1}°C, {} the {:.0}th percentile of {:.1}°C over {} years",
+                adjective, what, baseline.week, measure, value, side, threshold, bound, baseline.years
+            ),
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // March 10th to 16th of 2021-2023, lows of 2-8 and highs of 10-16
+    fn history() -> Vec<DayExtremes> {
+        let mut history = Vec::new();
+        for year in 2021..=2023 {
+            for (i, day) in (10..=16).enumerate() {
+                history.push(DayExtremes { day: days_from_civil(year, 3, day), low: 2.0 + i as f64, high: 10.0 + i as f64 });
+            }
+        }
+        history
+    }
+
+    #[test]
+    fn test_week_and_percentile() {
+        assert_eq!(week_of_year(days_from_civil(2024, 1, 1)), 1);
+        assert_eq!(week_of_year(days_from_civil(2024, 1, 8)), 2);
+        assert_eq!(week_of_year(days_from_civil(2024, 12, 31)), 53);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0, 5.0], 50.0), Some(3.0));
+        assert_eq!(percentile(&[0.0, 10.0], 90.0), Some(9.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_baseline_uses_earlier_years_only() {
+        let mut history = history();
+        history.push(DayExtremes { day: days_from_civil(2024, 3, 12), low: 30.0, high: 40.0 });
+        let baseline = baseline(&history, days_from_civil(2024, 3, 13)).expect("baseline");
+        // Week 11 of 2021-2023 starts on March 12th
+        assert_eq!(baseline.years, 3);
+        assert_eq!(baseline.days, 15);
+        assert_eq!(baseline.highs.median, 14.0);
+        assert!(super::baseline(&history, days_from_civil(2021, 3, 13)).is_none(), "nothing earlier");
+    }
+
+    #[test]
+    fn test_records() {
+        let today = DayExtremes { day: days_from_civil(2024, 3, 13), low: 9.5, high: 12.0 };
+        let unusual = unusual(&history(), &today);
+        assert_eq!(unusual.len(), 1);
+        assert!(unusual[0].record);
+        assert_eq!(unusual[0].message, "Warmest March night in 4 years of records (9.5°C)");
+
+        // Without an earlier March on file nothing is a record
+        let first_year: Vec<DayExtremes> = history().into_iter().filter(|d| d.day < days_from_civil(2022, 1, 1)).collect();
+        let today = DayExtremes { day: days_from_civil(2021, 3, 20), low: 20.0, high: 30.0 };
+        assert!(super::unusual(&first_year, &today).is_empty());
+    }
+
+    #[test]
+    fn test_outside_baseline() {
+        // Not a March record, but cold for the week
+        let mut history = history();
+        history.push(DayExtremes { day: days_from_civil(2022, 3, 28), low: -5.0, high: 4.0 });
+        let today = DayExtremes { day: days_from_civil(2024, 3, 13), low: -1.0, high: 13.0 };
+        let unusual = unusual(&history, &today);
+        assert_eq!(unusual.len(), 1);
+        assert!(!unusual[0].record);
+        assert!(unusual[0].message.starts_with("Unusually cold night for week 11: low of -1.0°C, below the 10th percentile"), "{}", unusual[0].message);
+    }
+}
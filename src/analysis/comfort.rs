@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use crate::units::{Speed, Temperature};
+
+// Apparent temperatures inside this band (°C) are considered ideal
+const IDEAL_LOW: f64 = 18.0;
+const IDEAL_HIGH: f64 = 24.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comfort {
+    pub score: u8,                    // 0 - 100, higher is more comfortable
+    pub level: String,
+    pub apparent_temperature: f64,    // Celsius, wind chill or heat index where they apply
+    pub clothing: String,
+}
+
+/// Apparent temperature: wind chill when cold and windy, heat index when hot and humid
+pub fn apparent_temperature(temperature: Temperature, humidity: Option<f64>, wind_speed: Option<Speed>) -> Temperature {
+    let wind_kmh = wind_speed.map_or(0.0, Speed::kmh);
+    let celsius = temperature.celsius();
+    if celsius <= 10.0 && wind_kmh > 4.8 {
+        // Environment Canada / NWS wind chill
+        let v = wind_kmh.powf(0.16);
+        return Temperature::from_celsius(13.12 + 0.6215 * celsius - 11.37 * v + 0.3965 * celsius * v);
+    }
+    if let Some(rh) = humidity {
+        if celsius >= 27.0 && rh >= 40.0 {
+            // Rothfusz regression, defined in Fahrenheit
+            let t = temperature.fahrenheit();
+            let hi = -42.379 + 2.04901523 * t + 10.14333127 * rh
+                - 0.22475541 * t * rh - 0.00683783 * t * t
+                - 0.05481717 * rh * rh + 0.00122874 * t * t * rh
+                + 0.00085282 * t * rh * rh - 0.00000199 * t * t * rh * rh;
+            return Temperature::from_fahrenheit(hi);
+        }
+    }
+    temperature
+}
+
+fn comfort_level(score: u8) -> &'static str {
+    match score {
+        80..=100 => "Very comfortable",
+        60..=79 => "Comfortable",
+        40..=59 => "Somewhat uncomfortable",
+        20..=39 => "Uncomfortable",
+        _ => "Very uncomfortable",
+    }
+}
+
+fn clothing_recommendation(apparent: f64, wind_speed: Option<Speed>, uv_index: Option<f64>) -> String {
+    let base = if apparent < -10.0 {
+        "Heavy winter coat, hat, gloves and scarf"
+    } else if apparent < 0.0 {
+        "Winter coat, hat and gloves"
+    } else if apparent < 8.0 {
+        "Warm jacket"
+    } else if apparent < 14.0 {
+        "Light jacket or sweater"
+    } else if apparent < 20.0 {
+        "Long sleeves"
+    } else if apparent < 26.0 {
+        "T-shirt"
+    } else {
+        "Shorts and breathable clothing"
+    };
+
+    let mut extras = Vec::new();
+    if wind_speed.is_some_and(|w| w.meters_per_second() > 8.0) {
+        extras.push("a windproof layer");
+    }
+    match uv_index {
+        Some(uv) if uv >= 6.0 => extras.push("sunglasses, sunscreen and a sun hat"),
+        Some(uv) if uv >= 3.0 => extras.push("sunglasses and sunscreen"),
+        _ => {}
+    }
+
+    if extras.is_empty() {
+        base.to_string()
+    } else {
+        format!("{}, plus {}", base, extras.join(" and "))
+    }
+}
+
+/// Scores how pleasant it feels outside and suggests what to wear.
+///
+/// Missing humidity, wind or UV readings simply don't count against the score.
+pub fn comfort(temperature: Temperature, humidity: Option<f64>, wind_speed: Option<Speed>, uv_index: Option<f64>) -> Comfort {
+    let apparent = apparent_temperature(temperature, humidity, wind_speed).celsius();
+
+    let mut penalty = if apparent < IDEAL_LOW {
+        (IDEAL_LOW - apparent) * 5.0
+    } else if apparent > IDEAL_HIGH {
+        (apparent - IDEAL_HIGH) * 5.0
+    } else {
+        0.0
+    };
+    if let Some(h) = humidity {
+        if h < 30.0 {
+            penalty += (30.0 - h) * 0.5;
+        } else if h > 60.0 {
+            penalty += (h - 60.0) * 0.75;
+        }
+    }
+    if let Some(w) = wind_speed.map(Speed::meters_per_second) {
+        if w > 8.0 {
+            penalty += (w - 8.0) * 3.0;
+        }
+    }
+    if let Some(uv) = uv_index {
+        if uv > 5.0 {
+            penalty += (uv - 5.0) * 4.0;
+        }
+    }
+
+    let score = (100.0 - penalty).clamp(0.0, 100.0).round() as u8;
+    Comfort {
+        score,
+        level: comfort_level(score).to_string(),
+        apparent_temperature: (apparent * 10.0).round() / 10.0,
+        clothing: clothing_recommendation(apparent, wind_speed, uv_index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apparent_temperature() {
+        // Calm mild weather is unchanged
+        let mild = Temperature::from_celsius(15.0);
+        assert_eq!(apparent_temperature(mild, Some(50.0), Some(Speed::from_meters_per_second(0.5))), mild);
+        // -5°C with 20 km/h wind feels like roughly -11.6°C
+        let windy = apparent_temperature(Temperature::from_celsius(-5.0), None, Some(Speed::from_kmh(20.0)));
+        assert!((windy.celsius() + 11.6).abs() < 0.2);
+        // 32°C at 60% humidity feels like roughly 37°C
+        let humid = apparent_temperature(Temperature::from_celsius(32.0), Some(60.0), None);
+        assert!((humid.celsius() - 37.1).abs() < 0.2);
+    }
+
+    #[test]
+    fn test_comfort() {
+        let pleasant = comfort(Temperature::from_celsius(21.0), Some(45.0), Some(Speed::from_meters_per_second(2.0)), Some(2.0));
+        assert_eq!(pleasant.score, 100);
+        assert_eq!(pleasant.level, "Very comfortable");
+        assert_eq!(pleasant.clothing, "T-shirt");
+
+        let winter = comfort(Temperature::from_celsius(-5.0), Some(80.0), Some(Speed::from_meters_per_second(10.0)), None);
+        assert!(winter.score < 20);
+        assert_eq!(winter.clothing, "Heavy winter coat, hat, gloves and scarf, plus a windproof layer");
+
+        let sunny = comfort(Temperature::from_celsius(28.0), Some(30.0), None, Some(8.0));
+        assert!(sunny.clothing.ends_with("plus sunglasses, sunscreen and a sun hat"));
+    }
+}
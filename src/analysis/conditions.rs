@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+// Stable condition codes derived from the providers' free-text descriptions, so clients can
+// pick icons and translations without parsing "Mostly sunny", "few clouds", "Flurries" etc.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConditionCode {
+    Clear,
+    PartlyCloudy,
+    Cloudy,
+    Fog,
+    Drizzle,
+    Rain,
+    Sleet,
+    Snow,
+    Thunderstorm,
+    Windy,
+    #[default]
+    Unknown,
+}
+
+// Checked in order, so "thunderstorms with rain" is a thunderstorm and "rain and snow" is sleet
+const KEYWORDS: &[(&[&str], ConditionCode)] = &[
+    (&["thunder", "storm", "t-storm", "lightning"], ConditionCode::Thunderstorm),
+    (&["sleet", "freezing", "ice", "hail", "rain and snow", "snow and rain"], ConditionCode::Sleet),
+    (&["snow", "flurries", "blizzard"], ConditionCode::Snow),
+    (&["drizzle"], ConditionCode::Drizzle),
+    (&["rain", "shower"], ConditionCode::Rain),
+    (&["fog", "mist", "haze", "hazy", "smoke", "dust"], ConditionCode::Fog),
+    (&["partly", "few clouds", "scattered clouds", "intermittent clouds", "mostly sunny", "mostly clear"], ConditionCode::PartlyCloudy),
+    (&["cloud", "overcast", "dreary"], ConditionCode::Cloudy),
+    (&["wind", "breez", "gust"], ConditionCode::Windy),
+    (&["clear", "sunny", "fair", "hot", "cold"], ConditionCode::Clear),
+];
+
+impl ConditionCode {
+    /// Maps a provider description such as "Light rain" or "Mostly cloudy" to a condition code
+    pub fn from_description(description: &str) -> ConditionCode {
+        let description = description.to_lowercase();
+        KEYWORDS.iter()
+            .find(|(keywords, _)| keywords.iter().any(|k| description.contains(k)))
+            .map(|(_, code)| *code)
+            .unwrap_or_default()
+    }
+
+    /// Weighted majority of several providers' codes, ignoring providers that didn't map
+    pub fn consensus(codes: impl IntoIterator<Item = (ConditionCode, f64)>) -> ConditionCode {
+        let mut totals: Vec<(ConditionCode, f64)> = Vec::new();
+        for (code, weight) in codes.into_iter().filter(|(code, _)| *code != ConditionCode::Unknown) {
+            match totals.iter_mut().find(|(c, _)| *c == code) {
+                Some((_, total)) => *total += weight,
+                None => totals.push((code, weight)),
+            }
+        }
+        // Ties go to the first provider that reported the code
+        totals.into_iter()
+            .fold(None, |best: Option<(ConditionCode, f64)>, (code, total)| match best {
+                Some((_, best_total)) if best_total >= total => best,
+                _ => Some((code, total)),
+            })
+            .map(|(code, _)| code)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_description() {
+        assert_eq!(ConditionCode::from_description("Sunny"), ConditionCode::Clear);
+        assert_eq!(ConditionCode::from_description("clear sky"), ConditionCode::Clear);
+        assert_eq!(ConditionCode::from_description("Mostly sunny"), ConditionCode::PartlyCloudy);
+        assert_eq!(ConditionCode::from_description("few clouds"), ConditionCode::PartlyCloudy);
+        assert_eq!(ConditionCode::from_description("overcast clouds"), ConditionCode::Cloudy);
+        assert_eq!(ConditionCode::from_description("Light rain"), ConditionCode::Rain);
+        assert_eq!(ConditionCode::from_description("Showers"), ConditionCode::Rain);
+        assert_eq!(ConditionCode::from_description("light intensity drizzle"), ConditionCode::Drizzle);
+        assert_eq!(ConditionCode::from_description("Rain and snow"), ConditionCode::Sleet);
+        assert_eq!(ConditionCode::from_description("Flurries"), ConditionCode::Snow);
+        assert_eq!(ConditionCode::from_description("thunderstorm with heavy rain"), ConditionCode::Thunderstorm);
+        assert_eq!(ConditionCode::from_description("mist"), ConditionCode::Fog);
+        assert_eq!(ConditionCode::from_description("Windy"), ConditionCode::Windy);
+        assert_eq!(ConditionCode::from_description("Homebrew station"), ConditionCode::Unknown);
+    }
+
+    #[test]
+    fn test_consensus() {
+        let codes = vec![
+            (ConditionCode::Rain, 1.0),
+            (ConditionCode::Cloudy, 1.5),
+            (ConditionCode::Rain, 1.0),
+            (ConditionCode::Unknown, 5.0),
+        ];
+        assert_eq!(ConditionCode::consensus(codes), ConditionCode::Rain);
+        assert_eq!(ConditionCode::consensus(vec![(ConditionCode::Unknown, 1.0)]), ConditionCode::Unknown);
+        assert_eq!(serde_json::to_string(&ConditionCode::PartlyCloudy).unwrap(), "\"partly-cloudy\"");
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+
+// Time series of one report field for charts (GET /api/history/{field}). Readings are averaged
+// into fixed steps. Periods without readings longer than the max gap mean the sensor was offline:
+// they are listed under `gaps` and their points stay null so charts break the line there.
+// Shorter holes are null too unless interpolation is asked for, which fills them linearly and
+// marks those points `interpolated`.
+
+/// Gaps up to this long are missed transmissions rather than an offline sensor
+pub const MAX_GAP_SECS: i64 = 15 * 60;
+pub const STEP_SECS: i64 = 5 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryPoint {
+    pub timestamp: i64,           // Start of the step
+    pub value: Option<f64>,       // Null where there is no reading to show
+    pub interpolated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gap {
+    pub start: i64,               // Last reading before the gap, or the start of the window
+    pub end: i64,                 // First reading after it, or the end of the window
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistorySeries {
+    pub field: String,
+    pub device: Option<String>,
+    pub start: i64,
+    pub end: i64,
+    pub step_secs: i64,
+    pub max_gap_secs: i64,
+    pub points: Vec<HistoryPoint>,
+    pub gaps: Vec<Gap>,
+}
+
+/// How a series is bucketed and how its holes are treated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryOptions {
+    pub start: i64,
+    pub end: i64,
+    pub step_secs: i64,
+    pub max_gap_secs: i64,
+    pub interpolate: bool,
+}
+
+/// Periods within [start, end] longer than `max_gap_secs` without a reading
+pub fn find_gaps(timestamps: &[i64], start: i64, end: i64, max_gap_secs: i64) -> Vec<Gap> {
+    let mut edges = vec![start];
+    edges.extend(timestamps.iter().copied().filter(|ts| (start..=end).contains(ts)));
+    edges.push(end);
+    edges.sort_unstable();
+    edges.windows(2)
+        .filter(|pair| pair[1] - pair[0] > max_gap_secs)
+        .map(|pair| Gap { start: pair[0], end: pair[1] })
+        .collect()
+}
+
+/// Points every `step_secs` from `start` to `end` with the average of the readings in each step
+pub fn series(field: &str, device: Option<String>, readings: &[(i64, f64)], options: HistoryOptions) -> HistorySeries {
+    let HistoryOptions { start, end, step_secs, max_gap_secs, interpolate } = options;
+    let steps = ((end - start).max(0) / step_secs.max(1) + 1) as usize;
+    let mut sums = vec![(0.0, 0usize); steps];
+    for &(timestamp, value) in readings.iter().filter(|(ts, _)| (start..=end).contains(ts)) {
+        let step = ((timestamp - start) / step_secs.max(1)) as usize;
+        if let Some((sum, count)) = sums.get_mut(step) {
+            *sum += value;
+            *count += 1;
+        }
+    }
+
+    let mut points: Vec<HistoryPoint> = sums.iter().enumerate().map(|(i, &(sum, count))| HistoryPoint {
+        timestamp: start + i as i64 * step_secs,
+        value: if count == 0 { None } else { Some(sum / count as f64) },
+        interpolated: false,
+    }).collect();
+
+    let timestamps: Vec<i64> = readings.iter().map(|(ts, _)| *ts).collect();
+    let gaps = find_gaps(&timestamps, start, end, max_gap_secs);
+    if interpolate {
+        let offline = |ts: i64| gaps.iter().any(|gap| gap.start < ts && ts < gap.end);
+        let known: Vec<usize> = (0..points.len()).filter(|&i| points[i].value.is_some()).collect();
+        for pair in known.windows(2) {
+            let (before, after) = (pair[0], pair[1]);
+            let (from, to) = (points[before].value.unwrap_or_default(), points[after].value.unwrap_or_default());
+            for (i, point) in points.iter_mut().enumerate().take(after).skip(before + 1) {
+                // Steps are compared by their middle, which is where their average sits
+                if offline(point.timestamp + step_secs / 2) {
+                    continue;
+                }
+                let fraction = (i - before) as f64 / (after - before) as f64;
+                point.value = Some(from + (to - from) * fraction);
+                point.interpolated = true;
+            }
+        }
+    }
+
+    HistorySeries {
+        field: field.to_string(),
+        device,
+        start,
+        end,
+        step_secs,
+        max_gap_secs,
+        points,
+        gaps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(interpolate: bool) -> HistoryOptions {
+        HistoryOptions { start: 0, end: 3600, step_secs: 300, max_gap_secs: 900, interpolate }
+    }
+
+    // Every 5 minutes for the first 20, two missed transmissions, then offline for half an hour
+    fn readings() -> Vec<(i64, f64)> {
+        vec![(0, 10.0), (300, 11.0), (600, 12.0), (1200, 14.0), (1500, 15.0), (3300, 21.0), (3600, 22.0)]
+    }
+
+    #[test]
+    fn test_gaps() {
+        let gaps = find_gaps(&[600, 1200, 3300], 0, 3600, 900);
+        assert_eq!(gaps, vec![Gap { start: 1200, end: 3300 }]);
+        // A sensor that hasn't reported since is offline up to the end of the window
+        assert_eq!(find_gaps(&[0, 300], 0, 3600, 900), vec![Gap { start: 300, end: 3600 }]);
+        assert_eq!(find_gaps(&[], 0, 3600, 900), vec![Gap { start: 0, end: 3600 }]);
+    }
+
+    #[test]
+    fn test_missing_steps_are_null() {
+        let series = series("temperature", None, &readings(), options(false));
+        assert_eq!(series.points.len(), 13);
+        assert_eq!(series.points[3], HistoryPoint { timestamp: 900, value: None, interpolated: false });
+        assert_eq!(series.points[4].value, Some(14.0));
+        assert_eq!(series.gaps, vec![Gap { start: 1500, end: 3300 }]);
+    }
+
+    #[test]
+    fn test_interpolates_short_gaps_only() {
+        let series = series("temperature", None, &readings(), options(true));
+        assert_eq!(series.points[3], HistoryPoint { timestamp: 900, value: Some(13.0), interpolated: true });
+        // Offline from 1500 to 3300: nothing is made up there
+        for point in &series.points[6..11] {
+            assert_eq!(point.value, None, "{}", point.timestamp);
+            assert!(!point.interpolated);
+        }
+        assert_eq!(series.points[11].value, Some(21.0));
+        assert!(!series.points[11].interpolated);
+    }
+
+    #[test]
+    fn test_steps_average_their_readings() {
+        let series = series("co2", Some("ws-1".to_string()), &[(0, 400.0), (100, 500.0), (700, 600.0)], options(false));
+        assert_eq!(series.points[0].value, Some(450.0));
+        assert_eq!(series.points[2].value, Some(600.0));
+        assert_eq!(series.device.as_deref(), Some("ws-1"));
+    }
+}
@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::road::dew_point;
+use crate::provider::common::HourlyForecast;
+
+// Heating/cooling pre-conditioning suggestions from the hourly forecast and indoor homebrew
+// readings (GET /api/hvac/hints).
+
+// Indoor comfort band the hints aim for, °C
+const HEATING_SETPOINT: f64 = 20.0;
+const COOLING_SETPOINT: f64 = 24.0;
+
+// Outdoor air has to be at least this much cooler than indoors to be worth letting in
+const FREE_COOLING_MARGIN: f64 = 2.0;
+// ...but not so cold that it pushes the house below the heating setpoint
+const FREE_COOLING_MIN_OUTDOOR: f64 = 12.0;
+
+const PRE_COOL_THRESHOLD: f64 = 28.0;
+const PRE_HEAT_THRESHOLD: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HvacHintKind {
+    FreeCooling,
+    PreCool,
+    PreHeat,
+    KeepClosed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HvacHint {
+    pub kind: HvacHintKind,
+    pub start: Option<String>,   // Forecast hour (HH:MM) the suggestion applies from
+    pub end: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HvacHints {
+    pub indoor_temperature: Option<f64>,
+    pub indoor_dew_point: Option<f64>,
+    pub hints: Vec<HvacHint>,
+}
+
+/// "2024-07-01T02:00:00-04:00" -> "02:00"; other formats are passed through unchanged
+fn hour_label(datetime: &str) -> String {
+    match datetime.get(10..16) {
+        Some(rest) if rest.starts_with('T') || rest.starts_with(' ') => rest[1..].to_string(),
+        _ => datetime.to_string(),
+    }
+}
+
+fn is_free_cooling_hour(hour: &HourlyForecast, indoor_temperature: f64, indoor_dew_point: Option<f64>) -> bool {
+    let outdoor = hour.temperature.celsius();
+    if outdoor > indoor_temperature - FREE_COOLING_MARGIN || outdoor < FREE_COOLING_MIN_OUTDOOR {
+        return false;
+    }
+    // Don't trade heat for humidity: outdoor air must also be drier when we can tell
+    match (indoor_dew_point, hour.humidity) {
+        (Some(indoor), Some(humidity)) => dew_point(outdoor, humidity) < indoor,
+        _ => true,
+    }
+}
+
+/// Computes pre-conditioning hints for the upcoming forecast hours
+pub fn hvac_hints(hourly: &[HourlyForecast], indoor_temperature: Option<f64>, indoor_humidity: Option<f64>) -> HvacHints {
+    let indoor_dew_point = match (indoor_temperature, indoor_humidity) {
+        (Some(t), Some(h)) => Some((dew_point(t, h) * 10.0).round() / 10.0),
+        _ => None,
+    };
+    let mut hints = Vec::new();
+
+    // Free cooling only helps when the house is warm enough to want it
+    if let Some(indoor) = indoor_temperature.filter(|t| *t > HEATING_SETPOINT + 1.0) {
+        let mut window: Option<(usize, usize)> = None;
+        let mut windows = Vec::new();
+        for (i, hour) in hourly.iter().enumerate() {
+            if is_free_cooling_hour(hour, indoor, indoor_dew_point) {
+                window = Some(window.map_or((i, i), |(start, _)| (start, i)));
+            } else if let Some(w) = window.take() {
+                windows.push(w);
+            }
+        }
+        windows.extend(window);
+
+        for (start, end) in windows {
+            let start_label = hour_label(&hourly[start].datetime);
+            // Windows cover whole forecast hours, so close them an hour after the last one
+            let end_label = hourly.get(end + 1)
+                .map(|h| hour_label(&h.datetime))
+                .unwrap_or_else(|| hour_label(&hourly[end].datetime));
+            let reason = if indoor_dew_point.is_some() && hourly[start..=end].iter().all(|h| h.humidity.is_some()) {
+                "outdoor dew point below indoor"
+            } else {
+                "outdoor air cooler than indoor"
+            };
+            hints.push(HvacHint {
+                kind: HvacHintKind::FreeCooling,
+                start: Some(start_label.clone()),
+                end: Some(end_label.clone()),
+                message: format!("Free cooling window {}–{}, {}", start_label, end_label, reason),
+            });
+        }
+    }
+
+    if let Some(peak) = hourly.iter().max_by(|a, b| a.temperature.celsius().total_cmp(&b.temperature.celsius())) {
+        if peak.temperature.celsius() >= PRE_COOL_THRESHOLD {
+            let coolest_before = hourly.iter()
+                .take_while(|h| !std::ptr::eq(*h, peak))
+                .min_by(|a, b| a.temperature.celsius().total_cmp(&b.temperature.celsius()));
+            hints.push(HvacHint {
+                kind: HvacHintKind::PreCool,
+                start: coolest_before.map(|h| hour_label(&h.datetime)),
+                end: Some(hour_label(&peak.datetime)),
+                message: format!(
+                    "Pre-cool to {:.0}°C before outdoor temperature peaks at {:.0}°C around {}",
+                    COOLING_SETPOINT - 1.0, peak.temperature.celsius(), hour_label(&peak.datetime)
+                ),
+            });
+        }
+    }
+
+    if let Some(low) = hourly.iter().min_by(|a, b| a.temperature.celsius().total_cmp(&b.temperature.celsius())) {
+        if low.temperature.celsius() <= PRE_HEAT_THRESHOLD {
+            // Heating ahead of the low is cheaper than recovering from it, especially with heat pumps
+            let start = hourly.iter()
+                .position(|h| std::ptr::eq(h, low))
+                .and_then(|i| i.checked_sub(3))
+                .map(|i| hour_label(&hourly[i].datetime));
+            hints.push(HvacHint {
+                kind: HvacHintKind::PreHeat,
+                start,
+                end: Some(hour_label(&low.datetime)),
+                message: format!(
+                    "Pre-heat to {:.0}°C ahead of the {:.0}°C low around {}",
+                    HEATING_SETPOINT + 1.0, low.temperature.celsius(), hour_label(&low.datetime)
+                ),
+            });
+        }
+    }
+
+    if let Some(indoor) = indoor_dew_point {
+        let humid_hours = hourly.iter()
+            .filter_map(|h| h.humidity.map(|rh| dew_point(h.temperature.celsius(), rh)))
+            .filter(|dp| *dp > indoor + 2.0)
+            .count();
+        if !hourly.is_empty() && humid_hours == hourly.len() {
+            hints.push(HvacHint {
+                kind: HvacHintKind::KeepClosed,
+                start: None,
+                end: None,
+                message: "Keep windows closed, outdoor air is more humid than indoor throughout the forecast".to_string(),
+            });
+        }
+    }
+
+    HvacHints {
+        indoor_temperature,
+        indoor_dew_point,
+        hints,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Temperature;
+
+    fn hour(datetime: &str, temperature: f64, humidity: f64) -> HourlyForecast {
+        HourlyForecast {
+            datetime: datetime.to_string(),
+            temperature: Temperature::from_celsius(temperature),
+            feels_like: None,
+            humidity: Some(humidity),
+            precipitation_probability: None,
+            precipitation_amount: None,
+            wind_speed: None,
+            wind_direction: None,
+            description: String::new(),
+            condition: Default::default(),
+            icon: None,
+            cloud_cover: None,
+            solar_irradiance: None,
+        }
+    }
+
+    #[test]
+    fn test_free_cooling_and_pre_cool() {
+        let hourly = vec![
+            // Cool but muggy, so no free cooling yet
+            hour("2024-07-01T01:00:00-04:00", 21.0, 80.0),
+            hour("2024-07-01T02:00:00-04:00", 17.0, 70.0),
+            hour("2024-07-01T03:00:00-04:00", 15.0, 75.0),
+            hour("2024-07-01T04:00:00-04:00", 16.0, 70.0),
+            hour("2024-07-01T05:00:00-04:00", 19.0, 60.0),
+            hour("2024-07-01T06:00:00-04:00", 24.0, 60.0),
+            hour("2024-07-01T14:00:00-04:00", 31.0, 40.0),
+        ];
+        let result = hvac_hints(&hourly, Some(25.0), Some(55.0));
+
+        assert_eq!(result.hints[0].kind, HvacHintKind::FreeCooling);
+        assert_eq!(result.hints[0].message, "Free cooling window 02:00–06:00, outdoor dew point below indoor");
+        assert_eq!(result.hints[1].kind, HvacHintKind::PreCool);
+        assert_eq!(result.hints[1].start.as_deref(), Some("03:00"));
+        assert_eq!(result.hints.len(), 2);
+    }
+
+    #[test]
+    fn test_pre_heat_without_indoor_readings() {
+        let hourly = vec![
+            hour("2024-01-05T18:00:00", 4.0, 70.0),
+            hour("2024-01-05T21:00:00", 1.0, 75.0),
+            hour("2024-01-06T00:00:00", -1.0, 80.0),
+            hour("2024-01-06T03:00:00", -3.0, 85.0),
+        ];
+        let result = hvac_hints(&hourly, None, None);
+
+        assert_eq!(result.hints.len(), 1);
+        assert_eq!(result.hints[0].kind, HvacHintKind::PreHeat);
+        assert_eq!(result.hints[0].start.as_deref(), Some("18:00"));
+        assert_eq!(result.hints[0].end.as_deref(), Some("03:00"));
+    }
+}
@@ -0,0 +1,15 @@
+// Derived guidance computed on top of raw provider readings.
+pub mod uv;
+pub mod road;
+pub mod aqi;
+pub mod comfort;
+pub mod summary;
+pub mod hvac;
+pub mod rooms;
+pub mod ventilation;
+pub mod conditions;
+pub mod nowcast;
+pub mod smoothing;
+pub mod history;
+pub mod baseline;
+pub mod solar;
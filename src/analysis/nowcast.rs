@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+
+use crate::provider::common::{MinutePrecipitation, MinutelyForecast, PrecipitationType};
+
+// "Light rain starting in 12 minutes" from a minute-level precipitation forecast (GET /api/nowcast).
+
+// Radar-derived nowcasts report traces below this (mm/h) even when it is dry
+const MIN_INTENSITY: f64 = 0.1;
+
+// Intensity bands, mm/h
+const LIGHT_MAX: f64 = 2.5;
+const MODERATE_MAX: f64 = 7.6;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Nowcast {
+    pub precipitating: bool,
+    pub precipitation_type: Option<PrecipitationType>,  // Of the current or next precipitation
+    pub starts_in_minutes: Option<i64>,                 // Set when it is dry now
+    pub stops_in_minutes: Option<i64>,                  // Set when it is precipitating now
+    pub peak_intensity: f64,                            // mm/h
+    pub summary: String,
+    pub forecast: MinutelyForecast,
+}
+
+fn is_wet(minute: &MinutePrecipitation) -> bool {
+    minute.intensity >= MIN_INTENSITY
+}
+
+fn describe(minute: &MinutePrecipitation) -> String {
+    let strength = if minute.intensity < LIGHT_MAX {
+        "Light"
+    } else if minute.intensity < MODERATE_MAX {
+        "Moderate"
+    } else {
+        "Heavy"
+    };
+    let kind = match minute.precipitation_type {
+        Some(PrecipitationType::Rain) => "rain",
+        Some(PrecipitationType::Snow) => "snow",
+        Some(PrecipitationType::Sleet) => "sleet",
+        Some(PrecipitationType::FreezingRain) => "freezing rain",
+        None => "precipitation",
+    };
+    format!("{} {}", strength, kind)
+}
+
+fn minutes(count: i64) -> String {
+    if count == 1 { "1 minute".to_string() } else { format!("{} minutes", count) }
+}
+
+/// When precipitation starts or stops within `forecast`, counted from `now` (unix seconds).
+/// Minutes that have already passed are ignored.
+pub fn nowcast(forecast: MinutelyForecast, now: i64) -> Nowcast {
+    let upcoming: Vec<&MinutePrecipitation> = forecast.minutes.iter()
+        .filter(|minute| minute.timestamp > now - 60)
+        .collect();
+    let offset = |minute: &MinutePrecipitation| (minute.timestamp - now).max(0) / 60;
+    let span = upcoming.last().map(|minute| offset(minute)).unwrap_or(0);
+    let peak_intensity = upcoming.iter().map(|minute| minute.intensity).fold(0.0, f64::max);
+
+    let current = upcoming.first().copied();
+    let precipitating = current.is_some_and(is_wet);
+    let (starts, stops) = if precipitating {
+        (None, upcoming.iter().find(|minute| !is_wet(minute)))
+    } else {
+        (upcoming.iter().find(|minute| is_wet(minute)), None)
+    };
+
+    let summary = match (current, starts, stops) {
+        (None, _, _) => "No minute-level forecast available".to_string(),
+        (Some(current), _, Some(stop)) => format!("{} stopping in {}", describe(current), minutes(offset(stop))),
+        (Some(current), _, None) if precipitating => format!("{} for at least the next {}", describe(current), minutes(span)),
+        (_, Some(start), _) => format!("{} starting in {}", describe(start), minutes(offset(start))),
+        _ => format!("No precipitation for the next {}", minutes(span)),
+    };
+
+    Nowcast {
+        precipitating,
+        precipitation_type: if precipitating { current } else { starts.copied() }.and_then(|minute| minute.precipitation_type),
+        starts_in_minutes: starts.map(|minute| offset(minute)),
+        stops_in_minutes: stops.map(|minute| offset(minute)),
+        peak_intensity,
+        summary,
+        forecast,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::common::Location;
+
+    fn forecast(intensities: &[f64]) -> MinutelyForecast {
+        MinutelyForecast {
+            location: Location {
+                latitude: 0.0,
+                longitude: 0.0,
+                name: "Springfield".to_string(),
+                country: None,
+                region: None,
+                postal_code: None,
+            },
+            provider: "Tomorrow.io".to_string(),
+            minutes: intensities.iter().enumerate().map(|(i, &intensity)| MinutePrecipitation {
+                timestamp: 1_700_000_000 + i as i64 * 60,
+                intensity,
+                probability: None,
+                precipitation_type: (intensity > 0.0).then_some(PrecipitationType::Rain),
+            }).collect(),
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_nowcast() {
+        let mut intensities = vec![0.0; 12];
+        intensities.extend([0.8, 1.5, 3.0, 3.2]);
+        let starting = nowcast(forecast(&intensities), 1_700_000_000);
+        assert!(!starting.precipitating);
+        assert_eq!(starting.starts_in_minutes, Some(12));
+        assert_eq!(starting.precipitation_type, Some(PrecipitationType::Rain));
+        assert_eq!(starting.peak_intensity, 3.2);
+        assert_eq!(starting.summary, "Light rain starting in 12 minutes");
+
+        // Two minutes later, the first two entries have passed
+        let stopping = nowcast(forecast(&[9.0, 9.0, 8.0, 0.5, 0.0]), 1_700_000_120);
+        assert!(stopping.precipitating);
+        assert_eq!(stopping.stops_in_minutes, Some(2));
+        assert_eq!(stopping.summary, "Heavy rain stopping in 2 minutes");
+
+        assert_eq!(nowcast(forecast(&[0.0; 61]), 1_700_000_000).summary, "No precipitation for the next 60 minutes");
+        assert_eq!(nowcast(forecast(&[]), 1_700_000_000).summary, "No minute-level forecast available");
+    }
+}
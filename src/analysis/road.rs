@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+use crate::units::Temperature;
+
+// Road surfaces radiate heat faster than the air above them, so on calm nights they
+// typically run a degree or two below the measured air temperature.
+const SURFACE_COOLING_OFFSET: f64 = 1.5;
+
+// Score at or above which frost/black ice is reported as likely
+const FROST_RISK_THRESHOLD: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoadCondition {
+    pub frost_risk: bool,
+    pub confidence: f64,                      // 0.0 - 1.0, confidence in `frost_risk`
+    pub estimated_surface_temperature: f64,   // Celsius
+    pub dew_point: Option<f64>,               // Celsius
+    pub factors: Vec<String>,
+}
+
+/// Dew point in Celsius using the Magnus formula
+pub fn dew_point(temperature: f64, humidity: f64) -> f64 {
+    let a = 17.625;
+    let b = 243.04;
+    let humidity = humidity.clamp(1.0, 100.0);
+    let gamma = (humidity / 100.0).ln() + (a * temperature) / (b + temperature);
+    (b * gamma) / (a - gamma)
+}
+
+/// Temperature trend in °C per hour from (timestamp, temperature) samples
+pub fn temperature_trend(samples: &[(i64, f64)]) -> Option<f64> {
+    let oldest = samples.iter().min_by_key(|(ts, _)| *ts)?;
+    let newest = samples.iter().max_by_key(|(ts, _)| *ts)?;
+    let hours = (newest.0 - oldest.0) as f64 / 3600.0;
+    if hours < 0.25 {
+        return None;
+    }
+    Some((newest.1 - oldest.1) / hours)
+}
+
+/// Estimates black-ice/frost risk on nearby roads.
+///
+/// `temperature_trend` is in °C per hour (negative when cooling) and `precipitation` in mm.
+pub fn estimate_road_condition(temperature: Temperature, humidity: Option<f64>, precipitation: Option<f64>, temperature_trend: Option<f64>) -> RoadCondition {
+    let temperature = temperature.celsius();
+    let mut surface = temperature - SURFACE_COOLING_OFFSET;
+    if let Some(trend) = temperature_trend {
+        // Pavement lags the air when warming but keeps losing heat when cooling
+        if trend < 0.0 {
+            surface += trend * 0.5;
+        }
+    }
+
+    let dew_point = humidity.map(|h| dew_point(temperature, h));
+    let mut factors = Vec::new();
+    let mut score: f64 = 0.0;
+
+    if surface <= 0.0 {
+        score += 0.4;
+        factors.push(format!("Estimated road surface at or below freezing ({:.1}°C)", surface));
+    } else if surface <= 2.0 {
+        score += 0.25;
+        factors.push(format!("Estimated road surface near freezing ({:.1}°C)", surface));
+    } else if surface <= 4.0 {
+        score += 0.1;
+    }
+
+    if let Some(dp) = dew_point {
+        if surface <= 0.0 && surface <= dp + 1.0 {
+            score += 0.25;
+            factors.push(format!("Surface at or below dew point ({:.1}°C), frost deposition likely", dp));
+        }
+    }
+    if let Some(h) = humidity {
+        if h >= 90.0 && surface <= 2.0 {
+            score += 0.1;
+            factors.push(format!("High humidity ({:.0}%)", h));
+        }
+    }
+    if let Some(p) = precipitation {
+        if p > 0.0 && surface <= 1.0 {
+            score += 0.3;
+            factors.push(format!("Recent precipitation ({:.1} mm) on a cold surface", p));
+        }
+    }
+    if let Some(trend) = temperature_trend {
+        if trend <= -1.0 && surface <= 4.0 {
+            score += 0.15;
+            factors.push(format!("Temperature falling {:.1}°C/h", -trend));
+        }
+    }
+
+    let score = score.min(1.0);
+    let frost_risk = score >= FROST_RISK_THRESHOLD;
+
+    // Each missing input lowers how much weight the verdict deserves
+    let missing = [humidity.is_none(), precipitation.is_none(), temperature_trend.is_none()]
+        .iter()
+        .filter(|m| **m)
+        .count() as f64;
+    let base_confidence = if frost_risk {
+        0.5 + (score - FROST_RISK_THRESHOLD)
+    } else {
+        0.5 + (FROST_RISK_THRESHOLD - score)
+    };
+    let confidence = (base_confidence * (1.0 - 0.1 * missing)).clamp(0.0, 1.0);
+
+    RoadCondition {
+        frost_risk,
+        confidence: (confidence * 100.0).round() / 100.0,
+        estimated_surface_temperature: (surface * 10.0).round() / 10.0,
+        dew_point: dew_point.map(|dp| (dp * 10.0).round() / 10.0),
+        factors,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dew_point() {
+        assert!((dew_point(20.0, 50.0) - 9.3).abs() < 0.1);
+        assert!((dew_point(0.0, 100.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_temperature_trend() {
+        assert_eq!(temperature_trend(&[(0, 4.0), (7200, 1.0)]), Some(-1.5));
+        assert_eq!(temperature_trend(&[(0, 4.0), (60, 1.0)]), None);
+        assert_eq!(temperature_trend(&[]), None);
+    }
+
+    #[test]
+    fn test_black_ice_after_rain() {
+        let condition = estimate_road_condition(Temperature::from_celsius(0.5), Some(95.0), Some(1.2), Some(-1.5));
+        assert!(condition.frost_risk);
+        assert!(condition.confidence > 0.8);
+        assert!(condition.estimated_surface_temperature < 0.0);
+    }
+
+    #[test]
+    fn test_warm_dry_road() {
+        let condition = estimate_road_condition(Temperature::from_celsius(15.0), Some(40.0), Some(0.0), Some(0.5));
+        assert!(!condition.frost_risk);
+        assert_eq!(condition.confidence, 1.0);
+        assert!(condition.factors.is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+use crate::provider::homebrew::WeatherReport;
+
+// Per-room indoor climate from the latest readings of each registered indoor device
+// (GET /api/rooms), plus differentials between rooms worth flagging.
+
+// Rooms this far apart are reported, e.g. "bedroom 4.0°C colder than living room"
+const TEMPERATURE_DIFFERENTIAL: f64 = 3.0;
+const HUMIDITY_DIFFERENTIAL: f64 = 20.0;
+const CO2_DIFFERENTIAL: f64 = 600.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomClimate {
+    pub room: String,
+    pub devices: Vec<String>,
+    pub temperature: Option<f64>,
+    pub humidity: Option<f64>,
+    pub co2: Option<f64>,
+    pub timestamp: i64,          // Most recent reading in the room
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomDifferential {
+    pub room: String,
+    pub reference_room: String,
+    pub metric: String,          // temperature, humidity or co2
+    pub difference: f64,         // `room` minus `reference_room`
+    pub message: String,
+}
+
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), v| (sum + v, count + 1));
+    if count == 0 { None } else { Some(sum / count as f64) }
+}
+
+/// Averages the readings of each room. `readings` pairs a room name with a device's latest report.
+pub fn summarize_rooms(readings: &[(String, WeatherReport)]) -> Vec<RoomClimate> {
+    let mut rooms: Vec<String> = readings.iter().map(|(room, _)| room.clone()).collect();
+    rooms.sort();
+    rooms.dedup();
+
+    rooms.into_iter().map(|room| {
+        let reports: Vec<&WeatherReport> = readings.iter()
+            .filter(|(r, _)| *r == room)
+            .map(|(_, report)| report)
+            .collect();
+        RoomClimate {
+            devices: reports.iter().filter_map(|r| r.device_id.clone()).collect(),
+            temperature: average(reports.iter().filter_map(|r| r.temperature)),
+            humidity: average(reports.iter().filter_map(|r| r.humidity)),
+            co2: average(reports.iter().filter_map(|r| r.co2)),
+            timestamp: reports.iter().map(|r| r.timestamp).max().unwrap_or(0),
+            room,
+        }
+    }).collect()
+}
+
+fn differential(rooms: &[RoomClimate], metric: &str, threshold: f64, value: fn(&RoomClimate) -> Option<f64>, describe: fn(&str, f64, &str) -> String) -> Vec<RoomDifferential> {
+    // Compare every room against the one at the other end of the scale
+    let reference = match rooms.iter()
+        .filter_map(|r| value(r).map(|v| (r, v)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+    {
+        Some(reference) => reference,
+        None => return Vec::new(),
+    };
+
+    rooms.iter()
+        .filter_map(|r| value(r).map(|v| (r, v)))
+        .filter(|(r, v)| r.room != reference.0.room && reference.1 - v >= threshold)
+        .map(|(r, v)| RoomDifferential {
+            room: r.room.clone(),
+            reference_room: reference.0.room.clone(),
+            metric: metric.to_string(),
+            difference: v - reference.1,
+            message: describe(&r.room, reference.1 - v, &reference.0.room),
+        })
+        .collect()
+}
+
+/// Rooms whose temperature, humidity or CO2 lag the warmest/most humid/stuffiest room by more than
+/// the configured thresholds
+pub fn room_differentials(rooms: &[RoomClimate]) -> Vec<RoomDifferential> {
+    let mut differentials = differential(rooms, "temperature", TEMPERATURE_DIFFERENTIAL, |r| r.temperature,
+        |room, diff, other| format!("{} {:.1}°C colder than {}", room, diff, other));
+    differentials.extend(differential(rooms, "humidity", HUMIDITY_DIFFERENTIAL, |r| r.humidity,
+        |room, diff, other| format!("{} {:.0}% drier than {}", room, diff, other)));
+    // For CO2 the interesting room is the stuffy one, so flip the comparison
+    differentials.extend(differential(rooms, "co2", CO2_DIFFERENTIAL, |r| r.co2.map(|c| -c),
+        |room, diff, other| format!("{} CO2 {:.0} ppm higher than {}", room, diff, other))
+        .into_iter()
+        .map(|d| RoomDifferential { difference: -d.difference, ..d }));
+    differentials
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(device: &str, temperature: f64, humidity: f64, co2: Option<f64>) -> WeatherReport {
+        let mut report = WeatherReport::new();
+        report.device_type = "indoor".to_string();
+        report.device_id = Some(device.to_string());
+        report.temperature = Some(temperature);
+        report.humidity = Some(humidity);
+        report.co2 = co2;
+        report
+    }
+
+    #[test]
+    fn test_room_summary_and_differentials() {
+        let readings = vec![
+            ("living room".to_string(), report("lr-1", 21.0, 45.0, Some(650.0))),
+            ("living room".to_string(), report("lr-2", 22.0, 47.0, None)),
+            ("bedroom".to_string(), report("bed", 17.5, 50.0, Some(1400.0))),
+        ];
+        let rooms = summarize_rooms(&readings);
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[1].room, "living room");
+        assert_eq!(rooms[1].devices, vec!["lr-1".to_string(), "lr-2".to_string()]);
+        assert_eq!(rooms[1].temperature, Some(21.5));
+        assert_eq!(rooms[1].co2, Some(650.0));
+
+        let differentials = room_differentials(&rooms);
+        assert_eq!(differentials.len(), 2);
+        assert_eq!(differentials[0].message, "bedroom 4.0°C colder than living room");
+        assert_eq!(differentials[0].difference, -4.0);
+        assert_eq!(differentials[1].message, "bedroom CO2 750 ppm higher than living room");
+        assert_eq!(differentials[1].difference, 750.0);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::provider::homebrew::WeatherReport;
+
+// Optional smoothing of homebrew sensor series (SENSOR_SMOOTHING) to take the jitter of cheap
+// sensors out of dashboards and alerts. It is applied when reports are read, per field and per
+// device in time order, so stored reports keep the raw readings and `?raw=true` still serves them.
+
+/// Fields that are smoothed; precipitation accumulates and wind direction is circular, so both
+/// are left raw
+pub const FIELDS: [&str; 10] = [
+    "temperature", "humidity", "pm10", "pm25", "co2", "tvoc", "wind_speed", "battery_voltage", "battery_percent", "rssi",
+];
+
+/// Readings of one device read back to smooth its latest one; older readings barely count
+pub const WINDOW: usize = 30;
+
+const EXPONENTIAL_ALPHA: f64 = 0.3;
+const KALMAN_NOISE_RATIO: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Smoothing {
+    #[default]
+    Off,
+    // Each reading moves the smoothed value `alpha` (0-1] of the way towards it
+    Exponential { alpha: f64 },
+    // Random-walk Kalman filter; the ratio of process to measurement noise per minute between
+    // readings sets how quickly it follows real changes, and after a long gap it trusts the
+    // next reading again. Only the ratio matters, so one setting suits every field's units.
+    Kalman { noise_ratio: f64 },
+}
+
+impl FromStr for Smoothing {
+    type Err = String;
+
+    /// "off", "exponential[:alpha]" or "kalman[:noise_ratio]"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let (method, parameter) = match s.split_once(':') {
+            Some((method, parameter)) => (method.trim(), Some(parameter.trim())),
+            None => (s.as_str(), None),
+        };
+        let value_or = |default: f64, valid: fn(f64) -> bool, expected: &str| match parameter {
+            None => Ok(default),
+            Some(value) => value.parse().ok().filter(|v: &f64| valid(*v))
+                .ok_or_else(|| format!("{} needs {}, got '{}'", method, expected, value)),
+        };
+        match method {
+            "off" | "none" | "" => Ok(Smoothing::Off),
+            "exponential" | "ema" => Ok(Smoothing::Exponential {
+                alpha: value_or(EXPONENTIAL_ALPHA, |a| a > 0.0 && a <= 1.0, "an alpha above 0 and at most 1")?,
+            }),
+            "kalman" => Ok(Smoothing::Kalman {
+                noise_ratio: value_or(KALMAN_NOISE_RATIO, |r| r.is_finite() && r > 0.0, "a noise ratio above 0")?,
+            }),
+            other => Err(format!("unknown smoothing '{}', expected off, exponential or kalman", other)),
+        }
+    }
+}
+
+impl fmt::Display for Smoothing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Smoothing::Off => write!(f, "off"),
+            Smoothing::Exponential { alpha } => write!(f, "exponential:{}", alpha),
+            Smoothing::Kalman { noise_ratio } => write!(f, "kalman:{}", noise_ratio),
+        }
+    }
+}
+
+impl Smoothing {
+    pub fn is_off(&self) -> bool {
+        *self == Smoothing::Off
+    }
+
+    /// Smoothed values of (timestamp, value) samples in time order
+    pub fn series(&self, samples: &[(i64, f64)]) -> Vec<f64> {
+        let Some(&(mut last_timestamp, first)) = samples.first() else { return Vec::new() };
+        let (mut estimate, mut variance) = (first, 1.0);
+        samples.iter().enumerate().map(|(i, &(timestamp, value))| {
+            if i > 0 {
+                match *self {
+                    Smoothing::Off => estimate = value,
+                    Smoothing::Exponential { alpha } => estimate += alpha * (value - estimate),
+                    Smoothing::Kalman { noise_ratio } => {
+                        // Measurement noise is 1, so the variance is relative to it
+                        variance += noise_ratio * (timestamp - last_timestamp).max(0) as f64 / 60.0;
+                        let gain = variance / (variance + 1.0);
+                        estimate += gain * (value - estimate);
+                        variance *= 1.0 - gain;
+                    },
+                }
+            }
+            last_timestamp = timestamp;
+            estimate
+        }).collect()
+    }
+
+    /// Replaces the `FIELDS` of `reports` with their smoothed values. Reports are grouped by
+    /// device id, or by device type for reports without one, and may come in any order.
+    pub fn smooth_reports(&self, reports: &mut [WeatherReport]) {
+        if self.is_off() {
+            return;
+        }
+        let mut devices: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, report) in reports.iter().enumerate() {
+            let device = report.device_id.clone().unwrap_or_else(|| format!("type:{}", report.device_type));
+            devices.entry(device).or_default().push(i);
+        }
+        for mut indices in devices.into_values() {
+            indices.sort_by_key(|&i| reports[i].timestamp);
+            for field in FIELDS {
+                let (present, samples): (Vec<usize>, Vec<(i64, f64)>) = indices.iter()
+                    .filter_map(|&i| reports[i].value(field).map(|v| (i, (reports[i].timestamp, v))))
+                    .unzip();
+                for (i, smoothed) in present.into_iter().zip(self.series(&samples)) {
+                    if let Some(value) = reports[i].value_mut(field) {
+                        *value = Some(smoothed);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(device_id: &str, timestamp: i64, temperature: f64) -> WeatherReport {
+        let mut report = WeatherReport::new();
+        report.device_id = Some(device_id.to_string());
+        report.timestamp = timestamp;
+        report.temperature = Some(temperature);
+        report.wind_direction = Some(350.0);
+        report
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("off".parse(), Ok(Smoothing::Off));
+        assert_eq!("exponential".parse(), Ok(Smoothing::Exponential { alpha: EXPONENTIAL_ALPHA }));
+        assert_eq!("Kalman:0.2".parse(), Ok(Smoothing::Kalman { noise_ratio: 0.2 }));
+        assert_eq!("kalman:0.2".parse::<Smoothing>().unwrap().to_string(), "kalman:0.2");
+        assert!("exponential:1.5".parse::<Smoothing>().is_err());
+        assert!("kalman:-1".parse::<Smoothing>().is_err());
+        assert!("median".parse::<Smoothing>().is_err());
+    }
+
+    #[test]
+    fn test_series_reduces_jitter() {
+        let samples: Vec<(i64, f64)> = (0..40).map(|i| (i * 60, if i % 2 == 0 { 20.5 } else { 19.5 })).collect();
+        for smoothing in [Smoothing::Exponential { alpha: 0.3 }, Smoothing::Kalman { noise_ratio: 0.05 }] {
+            let smoothed = smoothing.series(&samples);
+            assert_eq!(smoothed.len(), samples.len());
+            let tail = &smoothed[20..];
+            let spread = tail.iter().cloned().fold(f64::MIN, f64::max) - tail.iter().cloned().fold(f64::MAX, f64::min);
+            assert!(spread < 0.5, "{} left a spread of {}", smoothing, spread);
+        }
+        assert_eq!(Smoothing::Off.series(&samples), samples.iter().map(|&(_, v)| v).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_kalman_follows_after_a_gap() {
+        let smoothing = Smoothing::Kalman { noise_ratio: 0.05 };
+        let mut samples: Vec<(i64, f64)> = (0..30).map(|i| (i * 60, 10.0)).collect();
+        samples.push((30 * 60, 20.0));
+        let steady = *smoothing.series(&samples).last().unwrap();
+        samples.last_mut().unwrap().0 = 30 * 60 + 86_400;
+        let after_gap = *smoothing.series(&samples).last().unwrap();
+        assert!(steady < 13.0, "{}", steady);
+        assert!(after_gap > 19.5, "{}", after_gap);
+    }
+
+    #[test]
+    fn test_smooth_reports_per_device() {
+        // Newest first, as reports are selected, and two devices interleaved
+        let mut reports = vec![
+            report("a", 180, 30.0),
+            report("b", 120, 5.0),
+            report("a", 120, 20.0),
+            report("a", 60, 20.0),
+            report("b", 60, 5.0),
+        ];
+        Smoothing::Exponential { alpha: 0.5 }.smooth_reports(&mut reports);
+        assert_eq!(reports[0].temperature, Some(25.0));
+        assert_eq!(reports[1].temperature, Some(5.0));
+        assert_eq!(reports[0].wind_direction, Some(350.0));
+    }
+}
@@ -0,0 +1,350 @@
+use std::env;
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::conditions::ConditionCode;
+use crate::provider::common::{DailyForecast, HourlyForecast};
+use crate::utils::time::{civil_from_days, days_from_civil, format_rfc3339, parse_rfc3339};
+
+// Hourly PV production for the next 48 hours (GET /api/solar/forecast) of the panels described
+// by SOLAR_KWP (peak power, enables the estimate), SOLAR_TILT (degrees from horizontal, 30 by
+// default), SOLAR_AZIMUTH (degrees clockwise from north, 180 = south) and SOLAR_LOSSES_PERCENT
+// (inverter, wiring and soiling, 14 by default).
+//
+// Global horizontal irradiance is the provider's own hourly forecast when it has one
+// (AccuWeather), otherwise the Haurwitz clear-sky value reduced for the hour's cloud cover
+// (Kasten-Czeplak). Cloud cover falls back to the hour's condition and then to the daytime half
+// of the daily forecast, beyond that the hour is assumed clear. The Erbs model splits it into
+// direct and diffuse light, which are projected onto the panels with an isotropic sky, and the
+// output is derated for cell temperature.
+
+pub const FORECAST_HOURS: usize = 48;
+
+const SOLAR_CONSTANT: f64 = 1367.0;  // W/m² at the top of the atmosphere
+const ALBEDO: f64 = 0.2;             // Grass and soil; fresh snow reflects far more
+const STC_IRRADIANCE: f64 = 1000.0;  // Panels are rated at 1000 W/m² and 25°C cell temperature
+const TEMPERATURE_COEFFICIENT: f64 = -0.004;  // Crystalline silicon, per °C above 25
+const NOCT_RISE: f64 = 25.0;         // Cell temperature above ambient at 800 W/m² (NOCT 45°C)
+
+// Forecasts with 3-hourly steps (OpenWeather's 5 day forecast) cover the hours in between
+const MAX_STEP_SECS: i64 = 3 * 3600;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolarConfig {
+    pub peak_kw: f64,
+    pub tilt: f64,
+    pub azimuth: f64,
+    pub losses_percent: f64,
+}
+
+impl SolarConfig {
+    /// Reads the settings through `var`, None unless SOLAR_KWP is set; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        if var("SOLAR_KWP").is_none() {
+            return Ok(None);
+        }
+        let mut problems = Vec::new();
+        let mut number = |setting: &'static str, default: f64, range: (f64, f64)| match var(setting).map(|v| v.parse::<f64>()) {
+            None => default,
+            Some(Ok(value)) if value >= range.0 && value <= range.1 => value,
+            Some(_) => {
+                problems.push((setting, format!("must be a number from {} to {}", range.0, range.1)));
+                default
+            },
+        };
+        let config = Self {
+            peak_kw: number("SOLAR_KWP", 0.0, (0.0, 10_000.0)),
+            tilt: number("SOLAR_TILT", 30.0, (0.0, 90.0)),
+            azimuth: number("SOLAR_AZIMUTH", 180.0, (0.0, 360.0)),
+            losses_percent: number("SOLAR_LOSSES_PERCENT", 14.0, (0.0, 100.0)),
+        };
+        if problems.is_empty() {
+            Ok(Some(config))
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Option<Self>, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+}
+
+/// Where an hour's irradiance came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IrradianceSource {
+    Forecast,    // The provider's hourly solar irradiance
+    CloudCover,  // Clear sky reduced for the hour's cloud cover
+    Condition,   // ...with cloud cover guessed from the hour's condition
+    Daily,       // ...with the cloud cover of the day's daytime half
+    ClearSky,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolarHour {
+    pub time: String,             // Start of the hour, RFC 3339 UTC
+    pub sun_elevation: f64,       // Degrees above the horizon at the middle of the hour
+    pub cloud_cover: Option<f64>, // Percent
+    pub irradiance: f64,          // W/m² on the panels
+    pub power_kw: f64,            // Average over the hour, so also its kWh
+    pub source: IrradianceSource,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolarForecast {
+    pub system: SolarConfig,
+    pub total_kwh: f64,
+    pub peak_power_kw: f64,
+    pub hours: Vec<SolarHour>,
+}
+
+/// (elevation, azimuth clockwise from north) of the sun in degrees, NOAA's low accuracy equations
+pub fn sun_position(timestamp: i64, latitude: f64, longitude: f64) -> (f64, f64) {
+    let days = timestamp.div_euclid(86_400);
+    let (year, _, _) = civil_from_days(days);
+    let day_of_year = (days - days_from_civil(year, 1, 1)) as f64;
+    let hour = timestamp.rem_euclid(86_400) as f64 / 3600.0;
+    let gamma = 2.0 * PI / 365.0 * (day_of_year + (hour - 12.0) / 24.0);
+
+    let equation_of_time = 229.18 * (0.000075 + 0.001868 * gamma.cos() - 0.032077 * gamma.sin()
+        - 0.014615 * (2.0 * gamma).cos() - 0.040849 * (2.0 * gamma).sin());
+    let declination = 0.006918 - 0.399912 * gamma.cos() + 0.070257 * gamma.sin() - 0.006758 * (2.0 * gamma).cos()
+        + 0.000907 * (2.0 * gamma).sin() - 0.002697 * (3.0 * gamma).cos() + 0.00148 * (3.0 * gamma).sin();
+    let solar_minutes = hour * 60.0 + equation_of_time + 4.0 * longitude;
+    let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+
+    let latitude = latitude.to_radians();
+    let cos_zenith = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+    let elevation = 90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees();
+    let azimuth = hour_angle.sin().atan2(hour_angle.cos() * latitude.sin() - declination.tan() * latitude.cos()).to_degrees() + 180.0;
+    (elevation, azimuth.rem_euclid(360.0))
+}
+
+/// Haurwitz clear-sky global horizontal irradiance in W/m²
+fn clear_sky_irradiance(elevation: f64) -> f64 {
+    let cos_zenith = elevation.to_radians().sin();
+    if cos_zenith <= 0.0 {
+        return 0.0;
+    }
+    1098.0 * cos_zenith * (-0.057 / cos_zenith).exp()
+}
+
+/// Kasten-Czeplak reduction of clear-sky irradiance for cloud cover in percent
+fn cloudy_irradiance(clear_sky: f64, cloud_cover: f64) -> f64 {
+    clear_sky * (1.0 - 0.75 * (cloud_cover.clamp(0.0, 100.0) / 100.0).powf(3.4))
+}
+
+/// Rough cloud cover of a condition, None when it says nothing about the sky
+fn condition_cloud_cover(condition: ConditionCode) -> Option<f64> {
+    match condition {
+        ConditionCode::Clear => Some(10.0),
+        ConditionCode::PartlyCloudy => Some(50.0),
+        ConditionCode::Cloudy | ConditionCode::Fog => Some(90.0),
+        ConditionCode::Drizzle | ConditionCode::Rain | ConditionCode::Sleet | ConditionCode::Snow | ConditionCode::Thunderstorm => Some(95.0),
+        ConditionCode::Windy | ConditionCode::Unknown => None,
+    }
+}
+
+/// Irradiance in W/m² on panels with the given tilt and azimuth, from global horizontal irradiance
+fn plane_of_array(global: f64, elevation: f64, sun_azimuth: f64, day_of_year: f64, tilt: f64, azimuth: f64) -> f64 {
+    let cos_zenith = elevation.to_radians().sin();
+    if global <= 0.0 || cos_zenith <= 0.0 {
+        return 0.0;
+    }
+    // Erbs diffuse fraction from the clearness index
+    let extraterrestrial = SOLAR_CONSTANT * (1.0 + 0.033 * (2.0 * PI * day_of_year / 365.0).cos());
+    let clearness = (global / (extraterrestrial * cos_zenith)).min(1.0);
+    let diffuse_fraction = if clearness <= 0.22 {
+        1.0 - 0.09 * clearness
+    } else if clearness <= 0.8 {
+        0.9511 - 0.1604 * clearness + 4.388 * clearness.powi(2) - 16.638 * clearness.powi(3) + 12.336 * clearness.powi(4)
+    } else {
+        0.165
+    };
+    let diffuse = global * diffuse_fraction;
+    // Direct normal irradiance blows up with the sun at the horizon, where it's negligible anyway
+    let direct_normal = ((global - diffuse) / cos_zenith.max(0.087)).min(SOLAR_CONSTANT);
+
+    let tilt_rad = tilt.to_radians();
+    let zenith = (90.0 - elevation).to_radians();
+    let cos_incidence = zenith.cos() * tilt_rad.cos() + zenith.sin() * tilt_rad.sin() * (sun_azimuth - azimuth).to_radians().cos();
+    direct_normal * cos_incidence.max(0.0)
+        + diffuse * (1.0 + tilt_rad.cos()) / 2.0
+        + global * ALBEDO * (1.0 - tilt_rad.cos()) / 2.0
+}
+
+/// The forecast hour covering `timestamp` and when it starts: the latest one starting at or
+/// before it, if recent enough
+fn covering_hour<'a>(hours: &[(i64, &'a HourlyForecast)], timestamp: i64) -> Option<(i64, &'a HourlyForecast)> {
+    hours.iter()
+        .rev()
+        .find(|(start, _)| *start <= timestamp && timestamp - start < MAX_STEP_SECS)
+        .copied()
+}
+
+/// Estimates production for the 48 hours from the start of the hour containing `now`
+pub fn solar_forecast(
+    config: &SolarConfig,
+    latitude: f64,
+    longitude: f64,
+    hourly: &[HourlyForecast],
+    daily: &[DailyForecast],
+    now: i64,
+) -> SolarForecast {
+    let mut hours: Vec<(i64, &HourlyForecast)> = hourly.iter()
+        .filter_map(|h| parse_rfc3339(&h.datetime).map(|start| (start, h)))
+        .collect();
+    hours.sort_by_key(|(start, _)| *start);
+    let output_factor = config.peak_kw / STC_IRRADIANCE * (1.0 - config.losses_percent / 100.0);
+
+    let start = now - now.rem_euclid(3600);
+    let solar_hours: Vec<SolarHour> = (0..FORECAST_HOURS as i64)
+        .map(|i| {
+            let hour_start = start + i * 3600;
+            let middle = hour_start + 1800;
+            let (elevation, sun_azimuth) = sun_position(middle, latitude, longitude);
+            let covering = covering_hour(&hours, hour_start);
+            let forecast = covering.map(|(_, h)| h);
+
+            // Daily forecasts are dated in local time, which solar time approximates
+            let local_date = {
+                let (year, month, day) = civil_from_days((middle + (longitude * 240.0) as i64).div_euclid(86_400));
+                format!("{}-{:02}-{:02}", year, month, day)
+            };
+            let daily_cover = || daily.iter()
+                .find(|d| d.date.starts_with(&local_date))
+                .and_then(|d| d.day.as_ref())
+                .and_then(|day| day.cloud_cover);
+
+            let hour_cover = forecast.and_then(|h| h.cloud_cover);
+            let (cloud_cover, source) = match (hour_cover, forecast.and_then(|h| condition_cloud_cover(h.condition))) {
+                (Some(cover), _) => (Some(cover), IrradianceSource::CloudCover),
+                (None, Some(cover)) => (Some(cover), IrradianceSource::Condition),
+                (None, None) => match daily_cover() {
+                    Some(cover) => (Some(cover), IrradianceSource::Daily),
+                    None => (None, IrradianceSource::ClearSky),
+                },
+            };
+            let clear_sky = clear_sky_irradiance(elevation);
+            // Irradiance follows the sun, so unlike cloud cover it isn't stretched over later hours
+            let provided = covering.filter(|(start, _)| hour_start - start < 3600).and_then(|(_, h)| h.solar_irradiance);
+            let (global, source) = match provided {
+                Some(irradiance) => (irradiance, IrradianceSource::Forecast),
+                None => (cloud_cover.map_or(clear_sky, |cover| cloudy_irradiance(clear_sky, cover)), source),
+            };
+
+            let day_of_year = {
+                let days = middle.div_euclid(86_400);
+                (days - days_from_civil(civil_from_days(days).0, 1, 1) + 1) as f64
+            };
+            let irradiance = plane_of_array(global, elevation, sun_azimuth, day_of_year, config.tilt, config.azimuth);
+            let temperature_factor = match forecast {
+                Some(h) => {
+                    let cell = h.temperature.celsius() + NOCT_RISE * irradiance / 800.0;
+                    1.0 + TEMPERATURE_COEFFICIENT * (cell - 25.0)
+                },
+                None => 1.0,
+            };
+            let power_kw = (irradiance * output_factor * temperature_factor).max(0.0);
+
+            SolarHour {
+                time: format_rfc3339(hour_start),
+                sun_elevation: (elevation * 10.0).round() / 10.0,
+                cloud_cover,
+                irradiance: irradiance.round(),
+                power_kw: (power_kw * 1000.0).round() / 1000.0,
+                source,
+            }
+        })
+        .collect();
+
+    SolarForecast {
+        system: config.clone(),
+        total_kwh: (solar_hours.iter().map(|h| h.power_kw).sum::<f64>() * 100.0).round() / 100.0,
+        peak_power_kw: solar_hours.iter().map(|h| h.power_kw).fold(0.0, f64::max),
+        hours: solar_hours,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Temperature;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Result<Option<SolarConfig>, Vec<(&'static str, String)>> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        SolarConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    fn hour(datetime: &str, cloud_cover: Option<f64>, solar_irradiance: Option<f64>) -> HourlyForecast {
+        HourlyForecast {
+            datetime: datetime.to_string(),
+            temperature: Temperature::from_celsius(20.0),
+            feels_like: None,
+            humidity: None,
+            precipitation_probability: None,
+            precipitation_amount: None,
+            wind_speed: None,
+            wind_direction: None,
+            description: String::new(),
+            condition: Default::default(),
+            icon: None,
+            cloud_cover,
+            solar_irradiance,
+        }
+    }
+
+    #[test]
+    fn test_solar_config() {
+        assert_eq!(config(&[("SOLAR_TILT", "35")]).unwrap(), None);
+        assert_eq!(config(&[("SOLAR_KWP", "6.4"), ("SOLAR_AZIMUTH", "135")]).unwrap(),
+            Some(SolarConfig { peak_kw: 6.4, tilt: 30.0, azimuth: 135.0, losses_percent: 14.0 }));
+        assert_eq!(config(&[("SOLAR_KWP", "5"), ("SOLAR_TILT", "steep")]).unwrap_err(),
+            vec![("SOLAR_TILT", "must be a number from 0 to 90".to_string())]);
+    }
+
+    #[test]
+    fn test_sun_position() {
+        // Solar noon in Seattle around the June solstice: ~66° up, due south
+        let noon = parse_rfc3339("2024-06-20T20:10:00Z").unwrap();
+        let (elevation, azimuth) = sun_position(noon, 47.61, -122.33);
+        assert!((elevation - 65.8).abs() < 0.5, "{}", elevation);
+        assert!((azimuth - 180.0).abs() < 3.0, "{}", azimuth);
+
+        let (elevation, _) = sun_position(parse_rfc3339("2024-06-21T08:00:00Z").unwrap(), 47.61, -122.33);
+        assert!(elevation < 0.0);
+    }
+
+    #[test]
+    fn test_solar_forecast() {
+        let system = SolarConfig { peak_kw: 5.0, tilt: 30.0, azimuth: 180.0, losses_percent: 14.0 };
+        let now = parse_rfc3339("2024-06-20T19:25:00Z").unwrap();
+        let hourly = vec![
+            hour("2024-06-20T19:00:00Z", Some(0.0), None),
+            hour("2024-06-20T20:00:00Z", Some(100.0), None),
+            hour("2024-06-20T21:00:00Z", None, Some(500.0)),
+        ];
+        let forecast = solar_forecast(&system, 47.61, -122.33, &hourly, &[], now);
+
+        assert_eq!(forecast.hours.len(), FORECAST_HOURS);
+        assert_eq!(forecast.hours[0].time, "2024-06-20T19:00:00Z");
+        let (clear, overcast, provided) = (&forecast.hours[0], &forecast.hours[1], &forecast.hours[2]);
+        assert_eq!(clear.source, IrradianceSource::CloudCover);
+        assert_eq!(provided.source, IrradianceSource::Forecast);
+        // Clear midday sun on south facing panels gets close to the rated output less losses
+        assert!(clear.power_kw > 3.5 && clear.power_kw < 5.0, "{}", clear.power_kw);
+        assert!(overcast.power_kw < clear.power_kw / 2.0, "{}", overcast.power_kw);
+        assert!(provided.power_kw > overcast.power_kw && provided.power_kw < clear.power_kw);
+
+        // Nothing at night, and hours past the forecast are assumed clear
+        let night = forecast.hours.iter().find(|h| h.time == "2024-06-21T08:00:00Z").unwrap();
+        assert_eq!(night.power_kw, 0.0);
+        assert_eq!(night.source, IrradianceSource::ClearSky);
+        assert!(forecast.total_kwh > 40.0 && forecast.total_kwh < 80.0, "{}", forecast.total_kwh);
+        assert_eq!(forecast.peak_power_kw, forecast.hours.iter().map(|h| h.power_kw).fold(0.0, f64::max));
+    }
+}
@@ -0,0 +1,79 @@
+use crate::locale::Locale;
+use crate::provider::common::Weather;
+
+// Short plain-text sentences for voice assistants and family dashboards (GET /api/summary/spoken).
+
+/// Strips the per-provider breakdown the combo provider puts in its description
+fn short_description(description: &str) -> String {
+    let description = description.trim_start_matches("Combined: ");
+    let first = description.split(" | ").next().unwrap_or(description);
+    // "OpenWeather: light rain" -> "light rain"
+    let text = first.split_once(": ").map(|(_, text)| text).unwrap_or(first);
+    text.trim().to_lowercase()
+}
+
+/// One or two sentences describing current conditions, e.g.
+/// "It's 21 degrees and light rain in Springfield. Comfortable. Suggested clothing: T-shirt."
+/// Degrees are Fahrenheit for imperial locales.
+pub fn spoken_summary(weather: &Weather, locale: &Locale) -> String {
+    let description = short_description(&weather.description);
+    let degrees = locale.number(locale.temperature(weather.temperature.celsius()), 0);
+    let mut summary = if description.is_empty() {
+        format!("It's {} degrees in {}.", degrees, weather.location.name)
+    } else {
+        format!("It's {} degrees and {} in {}.", degrees, description, weather.location.name)
+    };
+
+    if let Some(ref comfort) = weather.comfort {
+        if (comfort.apparent_temperature - weather.temperature.celsius()).abs() >= 2.0 {
+            summary.push_str(&format!(" It feels like {}.", locale.number(locale.temperature(comfort.apparent_temperature), 0)));
+        }
+        summary.push_str(&format!(" {}. Suggested clothing: {}.", comfort.level, comfort.clothing));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::comfort::comfort;
+    use crate::analysis::conditions::ConditionCode;
+    use crate::provider::common::Location;
+    use crate::units::{Speed, Temperature};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_spoken_summary() {
+        let weather = Weather {
+            temperature: Temperature::from_celsius(21.2),
+            feels_like: None,
+            humidity: Some(45.0),
+            pressure: None,
+            wind_speed: Some(Speed::from_meters_per_second(2.0)),
+            wind_direction: None,
+            description: "Combined: OpenWeather: Light rain | AccuWeather: Showers".to_string(),
+            condition: ConditionCode::Rain,
+            icon: None,
+            precipitation: None,
+            visibility: None,
+            uv_index: None,
+            road_condition: None,
+            comfort: Some(comfort(Temperature::from_celsius(21.2), Some(45.0), Some(Speed::from_meters_per_second(2.0)), None)),
+            provider: "Combo".to_string(),
+            location: Location {
+                latitude: 0.0,
+                longitude: 0.0,
+                name: "Springfield".to_string(),
+                country: None,
+                region: None,
+                postal_code: None,
+            },
+            timestamp: 0,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
+        };
+
+        assert_eq!(spoken_summary(&weather, &Locale::default()), "It's 21 degrees and light rain in Springfield. Very comfortable. Suggested clothing: T-shirt.");
+        assert!(spoken_summary(&weather, &Locale::parse("en-US").unwrap()).starts_with("It's 70 degrees and light rain"));
+    }
+}
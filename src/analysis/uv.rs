@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// One UV index unit corresponds to 25 mW/m² of erythemally weighted irradiance
+const UV_INDEX_IRRADIANCE: f64 = 0.025;
+
+/// Fitzpatrick skin phototype
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkinType {
+    I,
+    II,
+    III,
+    IV,
+    V,
+    VI,
+}
+
+impl SkinType {
+    /// Parses a `skin_type` query parameter given as 1-6 or as a roman numeral
+    pub fn from_param(value: &str) -> Result<SkinType, UvGuidanceError> {
+        match value.trim().to_uppercase().as_str() {
+            "1" | "I" => Ok(SkinType::I),
+            "2" | "II" => Ok(SkinType::II),
+            "3" | "III" => Ok(SkinType::III),
+            "4" | "IV" => Ok(SkinType::IV),
+            "5" | "V" => Ok(SkinType::V),
+            "6" | "VI" => Ok(SkinType::VI),
+            _ => Err(UvGuidanceError::InvalidSkinType(value.to_string())),
+        }
+    }
+
+    /// The 1-6 form accepted by `from_param`
+    pub fn as_param(&self) -> &'static str {
+        match self {
+            SkinType::I => "1",
+            SkinType::II => "2",
+            SkinType::III => "3",
+            SkinType::IV => "4",
+            SkinType::V => "5",
+            SkinType::VI => "6",
+        }
+    }
+
+    /// Minimal erythema dose in J/m² (erythemally weighted)
+    pub fn minimal_erythema_dose(&self) -> f64 {
+        match self {
+            SkinType::I => 200.0,
+            SkinType::II => 250.0,
+            SkinType::III => 350.0,
+            SkinType::IV => 450.0,
+            SkinType::V => 600.0,
+            SkinType::VI => 1000.0,
+        }
+    }
+
+    fn is_fair(&self) -> bool {
+        matches!(self, SkinType::I | SkinType::II)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UvGuidanceError {
+    InvalidSkinType(String),
+    InvalidHour(String),
+}
+
+impl fmt::Display for UvGuidanceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UvGuidanceError::InvalidSkinType(value) => write!(f, "Invalid skin type '{}': expected 1-6 or I-VI", value),
+            UvGuidanceError::InvalidHour(value) => write!(f, "Invalid hour '{}': expected 0-23", value),
+        }
+    }
+}
+
+impl std::error::Error for UvGuidanceError {}
+
+/// Parses an `hour` query parameter (local hour of planned exposure)
+pub fn parse_hour(value: &str) -> Result<u32, UvGuidanceError> {
+    match value.trim().parse::<u32>() {
+        Ok(hour) if hour < 24 => Ok(hour),
+        _ => Err(UvGuidanceError::InvalidHour(value.to_string())),
+    }
+}
+
+/// WHO UV index exposure category
+pub fn uv_category(uv_index: f64) -> &'static str {
+    if uv_index < 3.0 {
+        "Low"
+    } else if uv_index < 6.0 {
+        "Moderate"
+    } else if uv_index < 8.0 {
+        "High"
+    } else if uv_index < 11.0 {
+        "Very High"
+    } else {
+        "Extreme"
+    }
+}
+
+// Share of the reported UV index expected at the given local hour. Readings are treated as
+// representative of the 10:00-16:00 peak window and taper off towards sunrise and sunset.
+fn time_of_day_factor(hour: u32) -> f64 {
+    match hour {
+        10..=15 => 1.0,
+        9 | 16 => 0.75,
+        8 | 17 => 0.5,
+        7 | 18 => 0.25,
+        _ => 0.0,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UvGuidance {
+    pub uv_index: f64,
+    pub effective_uv_index: f64,
+    pub category: String,
+    pub skin_type: SkinType,
+    pub hour: Option<u32>,
+    /// Minutes of unprotected exposure before reaching the minimal erythema dose,
+    /// `None` when the UV level is too low to burn
+    pub safe_exposure_minutes: Option<u32>,
+    pub recommended_spf: Option<u32>,
+    pub recommendations: Vec<String>,
+}
+
+/// Builds exposure guidance from a provider UV index for the given skin type and optional hour
+pub fn guidance(uv_index: f64, skin_type: SkinType, hour: Option<u32>) -> UvGuidance {
+    let uv_index = uv_index.max(0.0);
+    let effective_uv_index = match hour {
+        Some(h) => uv_index * time_of_day_factor(h),
+        None => uv_index,
+    };
+
+    let safe_exposure_minutes = if effective_uv_index < 0.5 {
+        None
+    } else {
+        let seconds = skin_type.minimal_erythema_dose() / (effective_uv_index * UV_INDEX_IRRADIANCE);
+        Some((seconds / 60.0).floor() as u32)
+    };
+
+    let mut recommendations = Vec::new();
+    let recommended_spf = if effective_uv_index < 3.0 {
+        recommendations.push("Minimal protection required for normal activity".to_string());
+        if skin_type.is_fair() && effective_uv_index >= 1.0 {
+            recommendations.push("Fair skin: use SPF 15+ for extended time outdoors".to_string());
+            Some(15)
+        } else {
+            None
+        }
+    } else if effective_uv_index < 6.0 {
+        recommendations.push("Wear sunglasses and a hat on bright days".to_string());
+        recommendations.push("Reapply sunscreen every 2 hours and after swimming".to_string());
+        Some(if skin_type.is_fair() { 30 } else { 15 })
+    } else if effective_uv_index < 8.0 {
+        recommendations.push("Seek shade during midday hours".to_string());
+        recommendations.push("Wear protective clothing, a wide-brimmed hat and sunglasses".to_string());
+        recommendations.push("Reapply sunscreen every 2 hours and after swimming".to_string());
+        Some(30)
+    } else if effective_uv_index < 11.0 {
+        recommendations.push("Minimize sun exposure between 10:00 and 16:00".to_string());
+        recommendations.push("Wear protective clothing, a wide-brimmed hat and sunglasses".to_string());
+        recommendations.push("Reapply sunscreen every 2 hours and after swimming".to_string());
+        Some(50)
+    } else {
+        recommendations.push("Avoid sun exposure between 10:00 and 16:00".to_string());
+        recommendations.push("Unprotected skin can burn in minutes".to_string());
+        recommendations.push("Reapply sunscreen every 2 hours and after swimming".to_string());
+        Some(50)
+    };
+
+    UvGuidance {
+        uv_index,
+        effective_uv_index,
+        category: uv_category(effective_uv_index).to_string(),
+        skin_type,
+        hour,
+        safe_exposure_minutes,
+        recommended_spf,
+        recommendations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skin_type_parsing() {
+        assert_eq!(SkinType::from_param("2"), Ok(SkinType::II));
+        assert_eq!(SkinType::from_param("iv"), Ok(SkinType::IV));
+        assert!(SkinType::from_param("7").is_err());
+        assert!(parse_hour("24").is_err());
+        assert_eq!(parse_hour("13"), Ok(13));
+    }
+
+    #[test]
+    fn test_safe_exposure_scales_with_skin_type() {
+        let fair = guidance(8.0, SkinType::I, None);
+        let dark = guidance(8.0, SkinType::VI, None);
+
+        assert_eq!(fair.category, "Very High");
+        assert_eq!(fair.safe_exposure_minutes, Some(16));
+        assert!(dark.safe_exposure_minutes > fair.safe_exposure_minutes);
+        assert_eq!(fair.recommended_spf, Some(50));
+    }
+
+    #[test]
+    fn test_time_of_day_adjustment() {
+        let noon = guidance(8.0, SkinType::III, Some(12));
+        let evening = guidance(8.0, SkinType::III, Some(17));
+        let night = guidance(8.0, SkinType::III, Some(22));
+
+        assert_eq!(noon.effective_uv_index, 8.0);
+        assert_eq!(evening.effective_uv_index, 4.0);
+        assert_eq!(night.safe_exposure_minutes, None);
+        assert_eq!(night.recommended_spf, None);
+    }
+}
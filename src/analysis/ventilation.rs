@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+// Ventilation quality from stored CO2 time series (GET /api/ventilation). While a room is
+// occupied CO2 builds up; once people leave it decays exponentially towards the outdoor level
+// at a rate set by the air change rate:
+//   C(t) - C_out = (C0 - C_out) * e^(-ACH * t)
+
+// Typical outdoor CO2 concentration, ppm
+const OUTDOOR_CO2: f64 = 420.0;
+
+// Changes smaller than this between samples are sensor noise and don't end a trend
+const NOISE_PPM: f64 = 15.0;
+
+// A rise or decay has to be at least this long and this large to be used
+const MIN_SEGMENT_SECS: i64 = 30 * 60;
+const MIN_SEGMENT_PPM: f64 = 100.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VentilationEstimate {
+    pub samples: usize,
+    pub buildup_rate: Option<f64>,          // ppm per hour while CO2 is rising
+    pub decay_rate: Option<f64>,            // ppm per hour while CO2 is falling
+    pub air_changes_per_hour: Option<f64>,
+    pub quality: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    start: (i64, f64),
+    end: (i64, f64),
+}
+
+impl Segment {
+    fn hours(&self) -> f64 {
+        (self.end.0 - self.start.0) as f64 / 3600.0
+    }
+
+    fn change(&self) -> f64 {
+        self.end.1 - self.start.1
+    }
+
+    fn is_significant(&self) -> bool {
+        self.end.0 - self.start.0 >= MIN_SEGMENT_SECS && self.change().abs() >= MIN_SEGMENT_PPM
+    }
+}
+
+/// Splits a series into runs of rising or falling CO2
+fn segments(samples: &[(i64, f64)]) -> Vec<Segment> {
+    let mut samples = samples.to_vec();
+    samples.sort_by_key(|(ts, _)| *ts);
+
+    let mut segments = Vec::new();
+    let mut current: Option<Segment> = None;
+    for sample in samples {
+        current = match current {
+            None => Some(Segment { start: sample, end: sample }),
+            Some(segment) => {
+                let direction = segment.change().signum();
+                let delta = sample.1 - segment.end.1;
+                if delta.abs() < NOISE_PPM || direction == 0.0 || delta.signum() == direction {
+                    Some(Segment { end: sample, ..segment })
+                } else {
+                    segments.push(segment);
+                    Some(Segment { start: segment.end, end: sample })
+                }
+            }
+        };
+    }
+    segments.extend(current);
+    segments.into_iter().filter(Segment::is_significant).collect()
+}
+
+fn quality(air_changes_per_hour: Option<f64>) -> &'static str {
+    match air_changes_per_hour {
+        Some(ach) if ach >= 4.0 => "Good",
+        Some(ach) if ach >= 2.0 => "Adequate",
+        Some(ach) if ach >= 0.5 => "Poor",
+        Some(_) => "Very poor",
+        None => "Unknown",
+    }
+}
+
+/// Estimates ventilation for a room from one or more CO2 series of (timestamp, ppm) samples,
+/// e.g. one per sensor in the room
+pub fn estimate_ventilation(series: &[Vec<(i64, f64)>]) -> VentilationEstimate {
+    let all: Vec<Segment> = series.iter().flat_map(|s| segments(s)).collect();
+    let rising: Vec<&Segment> = all.iter().filter(|s| s.change() > 0.0).collect();
+    let falling: Vec<&Segment> = all.iter().filter(|s| s.change() < 0.0).collect();
+
+    let rate = |segments: &[&Segment]| -> Option<f64> {
+        let hours: f64 = segments.iter().map(|s| s.hours()).sum();
+        if hours > 0.0 {
+            Some(segments.iter().map(|s| s.change()).sum::<f64>() / hours)
+        } else {
+            None
+        }
+    };
+
+    // Air changes from each decay, weighted by its duration
+    let (ach_sum, ach_hours) = falling.iter()
+        .filter(|s| s.end.1 > OUTDOOR_CO2)
+        .map(|s| (((s.start.1 - OUTDOOR_CO2) / (s.end.1 - OUTDOOR_CO2)).ln(), s.hours()))
+        .fold((0.0, 0.0), |(sum, hours), (ln_ratio, h)| (sum + ln_ratio, hours + h));
+    let air_changes_per_hour = if ach_hours > 0.0 {
+        Some((ach_sum / ach_hours * 100.0).round() / 100.0)
+    } else {
+        None
+    };
+
+    VentilationEstimate {
+        samples: series.iter().map(|s| s.len()).sum(),
+        buildup_rate: rate(&rising).map(|r| r.round()),
+        decay_rate: rate(&falling).map(|r| -r.round()),
+        air_changes_per_hour,
+        quality: quality(air_changes_per_hour).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_ventilation() {
+        // Occupied for two hours, then an exponential decay at 1 air change per hour
+        let mut samples = Vec::new();
+        for i in 0..=8 {
+            samples.push((i * 900, 500.0 + i as f64 * 100.0));
+        }
+        for i in 1..=8 {
+            let hours = i as f64 * 0.25;
+            samples.push((7200 + i * 900, OUTDOOR_CO2 + (1300.0 - OUTDOOR_CO2) * (-hours).exp()));
+        }
+
+        let estimate = estimate_ventilation(&[samples]);
+        assert_eq!(estimate.samples, 17);
+        assert_eq!(estimate.buildup_rate, Some(400.0));
+        assert_eq!(estimate.air_changes_per_hour, Some(1.0));
+        assert_eq!(estimate.quality, "Poor");
+        assert!(estimate.decay_rate.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_flat_series_is_unknown() {
+        let samples: Vec<(i64, f64)> = (0..10).map(|i| (i * 600, 450.0 + (i % 2) as f64 * 5.0)).collect();
+        let estimate = estimate_ventilation(&[samples]);
+        assert_eq!(estimate.air_changes_per_hour, None);
+        assert_eq!(estimate.buildup_rate, None);
+        assert_eq!(estimate.quality, "Unknown");
+    }
+}
@@ -0,0 +1,171 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::io::{Read, Write};
+
+use crate::db_pool::get_combo_pool;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::common::WeatherError;
+use crate::utils::time::safe_timestamp_with_fallback;
+
+// Raw provider responses, gzip-compressed into the combo database's `provider_payloads` table,
+// so readings can be re-normalized from the original payloads when a parsing bug is found.
+// Enabled by PAYLOAD_ARCHIVE_DAYS (days to keep, 0 or unset = off); the `payload_prune` job
+// deletes older rows and GET /api/admin/payloads reads them back.
+
+pub struct ArchiveConfig {
+    pub retention_days: u32,
+}
+
+impl ArchiveConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let retention_days = match env::var("PAYLOAD_ARCHIVE_DAYS") {
+            Ok(days) if !days.trim().is_empty() => days.trim().parse()
+                .map_err(|_| format!("must be a number of days, got {}", days))?,
+            _ => 0,
+        };
+        Ok(Self { retention_days })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.retention_days > 0
+    }
+}
+
+pub static ARCHIVE_CONFIG: Lazy<ArchiveConfig> = Lazy::new(|| {
+    ArchiveConfig::from_env().unwrap_or_else(|e| {
+        log::error!("Invalid PAYLOAD_ARCHIVE_DAYS, payload archival disabled: {}", e);
+        ArchiveConfig { retention_days: 0 }
+    })
+});
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.provider_payloads (
+        id serial NOT NULL,
+        provider varchar NOT NULL,
+        endpoint varchar NOT NULL,
+        location varchar NOT NULL,
+        fetched BIGINT DEFAULT 0,
+        payload BYTEA NOT NULL,
+        CONSTRAINT provider_payloads_pkey PRIMARY KEY (id));
+    CREATE INDEX IF NOT EXISTS provider_payloads_fetched_idx ON provider_payloads (provider, endpoint, fetched DESC);"
+}
+
+fn compress(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
+
+fn decompress(payload: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    GzDecoder::new(payload).read_to_end(&mut body)?;
+    Ok(body)
+}
+
+/// Archives one raw response when archival is enabled; failures are logged, never returned,
+/// so a full disk can't take the providers down
+pub async fn store(provider: &str, endpoint: &str, location: &str, body: &[u8]) {
+    if !ARCHIVE_CONFIG.enabled() {
+        return;
+    }
+    let result: JupiterResult<()> = async {
+        let payload = compress(body).map_err(JupiterError::IoError)?;
+        let pool = get_combo_pool()
+            .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+        let client = pool.get_connection_with_retry(3).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+        client.execute("INSERT INTO provider_payloads (provider, endpoint, location, fetched, payload) VALUES ($1, $2, $3, $4, $5)",
+            &[&provider, &endpoint, &location, &safe_timestamp_with_fallback(), &payload]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Insert failed: {}", e)))?;
+        Ok(())
+    }.await;
+    if let Err(e) = result {
+        log::warn!("Failed to archive {} {} payload: {}", provider, endpoint, e);
+    }
+}
+
+/// Reads a provider response, archives the raw body, then parses it
+pub async fn json<T: DeserializeOwned>(provider: &str, endpoint: &str, location: &str, response: reqwest::Response) -> Result<T, WeatherError> {
+    let body = response.bytes().await?;
+    store(provider, endpoint, location, &body).await;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Deletes payloads older than the retention window
+pub async fn prune() -> JupiterResult<()> {
+    let cutoff = safe_timestamp_with_fallback() - i64::from(ARCHIVE_CONFIG.retention_days) * 86_400;
+    let pool = get_combo_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    let deleted = client.execute("DELETE FROM provider_payloads WHERE fetched < $1", &[&cutoff]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Delete failed: {}", e)))?;
+    log::info!("Pruned {} archived provider payload(s)", deleted);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedPayload {
+    pub id: i32,
+    pub provider: String,
+    pub endpoint: String,
+    pub location: String,
+    pub fetched: i64,
+    /// The response as originally received; raw text when it isn't JSON
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Default)]
+pub struct PayloadFilter {
+    pub provider: Option<String>,
+    pub endpoint: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+/// Archived payloads matching `filter`, oldest first so they can be replayed in order
+pub async fn payloads(filter: &PayloadFilter, limit: i64) -> JupiterResult<Vec<ArchivedPayload>> {
+    let pool = get_combo_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    let rows = client.query("SELECT * FROM provider_payloads
+        WHERE ($1::varchar IS NULL OR provider = $1) AND ($2::varchar IS NULL OR endpoint = $2)
+          AND fetched >= $3 AND fetched <= $4
+        ORDER BY fetched ASC, id ASC LIMIT $5",
+        &[&filter.provider, &filter.endpoint, &filter.since.unwrap_or(0), &filter.until.unwrap_or(i64::MAX), &limit]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+
+    rows.iter().map(|row| {
+        let compressed: Vec<u8> = row.get("payload");
+        let body = decompress(&compressed).map_err(JupiterError::IoError)?;
+        let payload = serde_json::from_slice(&body)
+            .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&body).into_owned()));
+        Ok(ArchivedPayload {
+            id: row.get("id"),
+            provider: row.get("provider"),
+            endpoint: row.get("endpoint"),
+            location: row.get("location"),
+            fetched: row.get("fetched"),
+            payload,
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_compression_round_trip() {
+        let body = br#"[{"Key":"349727","WeatherText":"Light rain","Temperature":{"Metric":{"Value":21.2}}}]"#.repeat(20);
+        let compressed = compress(&body).unwrap();
+        assert!(compressed.len() < body.len() / 4);
+        assert_eq!(decompress(&compressed).unwrap(), body);
+    }
+}
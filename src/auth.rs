@@ -1,9 +1,25 @@
-use rouille::{Request, Response};
-use std::sync::Arc;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderMap, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
 use std::sync::Mutex;
 use std::collections::HashMap;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use once_cell::sync::Lazy;
+use crate::db_pool::homebrew_client;
 use crate::error::{JupiterError, Result as JupiterResult};
+use crate::metrics::{self, Counter};
+use crate::secret::Secret;
+
+static AUTH_FAILURES: Lazy<Arc<Counter>> = Lazy::new(|| metrics::counter(
+    "jupiter_auth_failures_total", "Rejected API requests by reason (missing_key, invalid_key, insufficient_scope or rate_limited)", &["reason"],
+));
 
 /// Performs constant-time comparison of two byte slices to prevent timing attacks
 pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
@@ -24,6 +40,7 @@ pub struct RateLimiter {
     attempts: Arc<Mutex<HashMap<String, Vec<Instant>>>>,
     max_attempts: usize,
     window: Duration,
+    shared: Option<&'static str>,
 }
 
 impl RateLimiter {
@@ -32,7 +49,27 @@ impl RateLimiter {
             attempts: Arc::new(Mutex::new(HashMap::new())),
             max_attempts,
             window: Duration::from_secs(window_seconds),
+            shared: None,
+        }
+    }
+
+    /// Counts `allow`ed requests across instances under `name` when they share a Redis server
+    /// (see `cluster`)
+    pub fn shared(mut self, name: &'static str) -> Self {
+        self.shared = Some(name);
+        self
+    }
+
+    /// `check_rate_limit`, counted across instances if shared; falls back to this instance's
+    /// count when Redis can't be reached
+    pub async fn allow(&self, client_id: &str) -> bool {
+        #[cfg(feature = "redis")]
+        if let (Some(name), Some(cluster)) = (self.shared, crate::cluster::get()) {
+            if let Some(allowed) = cluster.allow(name, client_id, self.max_attempts, self.window).await {
+                return allowed;
+            }
         }
+        self.check_rate_limit(client_id)
     }
 
     pub fn check_rate_limit(&self, client_id: &str) -> bool {
@@ -62,45 +99,361 @@ impl RateLimiter {
     }
 }
 
+fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")], "Unauthorized").into_response()
+}
+
+fn rate_limited(client_id: &str) -> Response {
+    log::warn!("Rate limit exceeded for client: {}", client_id);
+    AUTH_FAILURES.inc(&["rate_limited"]);
+    (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "60")], "Too Many Requests").into_response()
+}
+
 /// Validates the authorization header and performs authentication
 pub fn validate_auth_header(
-    request: &Request,
+    headers: &HeaderMap,
+    remote_addr: &SocketAddr,
     api_key: &str,
     rate_limiter: Option<&RateLimiter>,
 ) -> Result<(), Response> {
     // Get client identifier (IP address)
-    let client_id = request.remote_addr().to_string();
+    let client_id = remote_addr.to_string();
     
     // Check rate limit if enabled
     if let Some(limiter) = rate_limiter {
         if !limiter.check_rate_limit(&client_id) {
-            log::warn!("Rate limit exceeded for client: {}", client_id);
-            return Err(Response::text("Too Many Requests")
-                .with_status_code(429)
-                .with_additional_header("Retry-After", "60"));
+            return Err(rate_limited(&client_id));
         }
     }
     
     // Get the Authorization header
-    let auth_header = request.header("Authorization");
+    let auth_header = headers.get(header::AUTHORIZATION);
     
     match auth_header {
         Some(header_value) => {
             // Use constant-time comparison to prevent timing attacks
             if !constant_time_eq(header_value.as_bytes(), api_key.as_bytes()) {
                 log::warn!("Authentication failed from IP: {}", client_id);
-                return Err(Response::text("Unauthorized")
-                    .with_status_code(401)
-                    .with_additional_header("WWW-Authenticate", "Bearer"));
+                AUTH_FAILURES.inc(&["invalid_key"]);
+                return Err(unauthorized());
             }
             Ok(())
         }
         None => {
             log::warn!("Missing Authorization header from IP: {}", client_id);
-            Err(Response::text("Unauthorized")
-                .with_status_code(401)
-                .with_additional_header("WWW-Authenticate", "Bearer"))
+            AUTH_FAILURES.inc(&["missing_key"]);
+            Err(unauthorized())
+        }
+    }
+}
+
+/// What a key from the `api_keys` table may do. The configured server API key can do anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Ingest,   // Post reports and ingest bridge payloads; a device's key also reads its settings
+    Read,     // Any GET outside /api/admin
+    Admin,    // Everything, including managing devices and keys
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Ingest => "ingest",
+            Scope::Read => "read",
+            Scope::Admin => "admin",
+        }
+    }
+
+    /// Whether a key with this scope, bound to `device_id` if any, may make the request
+    pub fn permits(&self, method: &Method, path: &str, device_id: Option<&str>) -> bool {
+        match self {
+            Scope::Admin => true,
+            Scope::Read => (method == Method::GET || method == Method::HEAD) && !path.starts_with("/api/admin"),
+            Scope::Ingest => match (method, path) {
+                (&Method::POST, "/api/weather_reports" | "/api/ingest/rtl433" | "/api/ingest/ble") => true,
+                (&Method::GET, path) => device_id.is_some_and(|id| path == format!("/api/devices/{}/config", id)),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ingest" => Ok(Scope::Ingest),
+            "read" => Ok(Scope::Read),
+            "admin" => Ok(Scope::Admin),
+            other => Err(format!("unknown scope '{}', expected ingest, read or admin", other)),
+        }
+    }
+}
+
+/// A key from the `api_keys` table. Only a hash of the key itself is stored; revoked keys are
+/// kept so the table shows what a key was for and when it stopped working.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub scope: Scope,
+    pub device_id: Option<String>,    // Reports posted with the key are attributed to this device
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+    #[serde(skip)]
+    pub key_hash: String,
+}
+
+impl ApiKey {
+    /// A new key and the secret to hand out for it, which is not stored anywhere
+    pub fn issue(name: &str, scope: Scope, device_id: Option<String>, expires_at: Option<i64>, now: i64) -> (ApiKey, String) {
+        let random = |len: usize| thread_rng().sample_iter(&Alphanumeric).take(len).map(char::from).collect::<String>();
+        let secret = random(40);
+        let key = ApiKey {
+            id: random(12).to_lowercase(),
+            name: name.to_string(),
+            scope,
+            device_id,
+            created_at: now,
+            expires_at,
+            revoked_at: None,
+            key_hash: hash_key(&secret),
+        };
+        (key, secret)
+    }
+
+    pub fn is_active(&self, now: i64) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+
+    fn matches(&self, secret: &str) -> bool {
+        constant_time_eq(self.key_hash.as_bytes(), hash_key(secret).as_bytes())
+    }
+}
+
+/// SHA-256 of a key, hex encoded
+pub fn hash_key(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A key with the secret just issued for it, the only time the secret is shown
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IssuedKey {
+    pub key: ApiKey,
+    pub api_key: String,
+}
+
+/// Body of POST /api/admin/keys
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scope: Scope,
+    #[serde(default)]
+    pub device_id: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<i64>,    // Unix time, never by default
+}
+
+/// The keys shared by both servers' `require_api_key`. Readers take a snapshot; changes made
+/// through the API reload it from the table.
+#[derive(Debug, Clone, Default)]
+pub struct KeyStore(Arc<RwLock<Arc<Vec<ApiKey>>>>);
+
+impl KeyStore {
+    pub fn snapshot(&self) -> Arc<Vec<ApiKey>> {
+        match self.0.read() {
+            Ok(keys) => keys.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub fn replace(&self, keys: Vec<ApiKey>) {
+        match self.0.write() {
+            Ok(mut current) => *current = Arc::new(keys),
+            Err(poisoned) => *poisoned.into_inner() = Arc::new(keys),
+        }
+    }
+
+    pub async fn reload(&self) -> JupiterResult<()> {
+        self.replace(select_keys().await?);
+        Ok(())
+    }
+
+    /// The unexpired, unrevoked key with this secret
+    pub fn find(&self, secret: &str, now: i64) -> Option<ApiKey> {
+        self.snapshot().iter().find(|key| key.is_active(now) && key.matches(secret)).cloned()
+    }
+}
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.api_keys (
+        id varchar NOT NULL,
+        name varchar NOT NULL,
+        scope varchar NOT NULL,
+        device_id varchar NULL REFERENCES devices (id) ON DELETE CASCADE,
+        key_hash varchar NOT NULL UNIQUE,
+        created_at BIGINT DEFAULT 0,
+        expires_at BIGINT NULL,
+        revoked_at BIGINT NULL,
+        CONSTRAINT api_keys_pkey PRIMARY KEY (id));"
+}
+
+/// Device keys used to be a hash on the device's own row; part of homebrew migration 2
+pub fn sql_migrations() -> Vec<&'static str> {
+    vec![
+        "CREATE INDEX IF NOT EXISTS api_keys_device_id_idx ON api_keys (device_id);",
+        "DO $$ BEGIN
+            IF EXISTS (SELECT 1 FROM information_schema.columns WHERE table_name = 'devices' AND column_name = 'api_key_hash') THEN
+                INSERT INTO api_keys (id, name, scope, device_id, key_hash, created_at)
+                    SELECT 'device-' || id, name || ' device key', 'ingest', id, api_key_hash, created_at
+                    FROM devices WHERE api_key_hash IS NOT NULL
+                    ON CONFLICT DO NOTHING;
+                ALTER TABLE devices DROP COLUMN api_key_hash;
+            END IF;
+        END $$;",
+    ]
+}
+
+pub async fn select_keys() -> JupiterResult<Vec<ApiKey>> {
+    let rows = homebrew_client().await?.query("SELECT * FROM api_keys ORDER BY created_at, id", &[]).await?;
+    rows.iter().map(|row| {
+        let scope: String = row.try_get("scope")?;
+        Ok(ApiKey {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            scope: scope.parse().map_err(JupiterError::DatabaseError)?,
+            device_id: row.try_get("device_id")?,
+            created_at: row.try_get("created_at")?,
+            expires_at: row.try_get("expires_at")?,
+            revoked_at: row.try_get("revoked_at")?,
+            key_hash: row.try_get("key_hash")?,
+        })
+    }).collect()
+}
+
+pub async fn insert_key(key: &ApiKey) -> JupiterResult<()> {
+    homebrew_client().await?.execute(
+        "INSERT INTO api_keys (id, name, scope, device_id, key_hash, created_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        &[&key.id, &key.name, &key.scope.as_str(), &key.device_id, &key.key_hash, &key.created_at, &key.expires_at],
+    ).await?;
+    Ok(())
+}
+
+/// Returns whether the key existed and wasn't revoked already
+pub async fn revoke_key(id: &str, now: i64) -> JupiterResult<bool> {
+    Ok(homebrew_client().await?.execute("UPDATE api_keys SET revoked_at = $2 WHERE id = $1 AND revoked_at IS NULL", &[&id, &now]).await? == 1)
+}
+
+/// Revokes every key of a device, e.g. before issuing it a new one
+pub async fn revoke_device_keys(device_id: &str, now: i64) -> JupiterResult<u64> {
+    Ok(homebrew_client().await?.execute(
+        "UPDATE api_keys SET revoked_at = $2 WHERE device_id = $1 AND revoked_at IS NULL", &[&device_id, &now],
+    ).await?)
+}
+
+/// Server API key, keys from the `api_keys` table and per-client rate limit checked by
+/// `require_api_key`
+pub struct ApiKeyAuth {
+    api_key: Secret<String>,
+    rate_limiter: RateLimiter,
+    keys: Option<KeyStore>,
+}
+
+/// Request extension naming the device whose own key authenticated the request
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthenticatedDevice(pub String);
+
+/// Why `ApiKeyAuth::authorize` turned a request away
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Denied {
+    RateLimited,
+    Unauthorized,
+    Forbidden,
+}
+
+impl ApiKeyAuth {
+    /// Max 10 attempts per minute per client
+    pub fn new(api_key: Secret<String>) -> Self {
+        Self { api_key, rate_limiter: RateLimiter::new(10, 60).shared("auth"), keys: None }
+    }
+
+    /// Also accept the scoped keys in `keys`
+    pub fn with_keys(mut self, keys: KeyStore) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// The active stored key presented with the request, if any
+    fn stored_key(&self, request: &Request) -> Option<ApiKey> {
+        let keys = self.keys.as_ref()?;
+        let secret = request.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        keys.find(secret, now)
+    }
+
+    /// The checks of `require_api_key` for requests that don't go through axum, e.g. gRPC calls
+    /// given as the equivalent HTTP method and path. The device whose own key was presented, if any.
+    pub async fn authorize(&self, client_id: &str, secret: Option<&str>, method: &Method, path: &str) -> Result<Option<AuthenticatedDevice>, Denied> {
+        if !self.rate_limiter.allow(client_id).await {
+            log::warn!("Rate limit exceeded for client: {}", client_id);
+            AUTH_FAILURES.inc(&["rate_limited"]);
+            return Err(Denied::RateLimited);
+        }
+        let secret = match secret {
+            Some(secret) => secret,
+            None => {
+                log::warn!("Missing API key from {}", client_id);
+                AUTH_FAILURES.inc(&["missing_key"]);
+                return Err(Denied::Unauthorized);
+            }
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        if let Some(key) = self.keys.as_ref().and_then(|keys| keys.find(secret, now)) {
+            if !key.scope.permits(method, path, key.device_id.as_deref()) {
+                log::warn!("API key '{}' ({}) not permitted to {} {}", key.id, key.scope.as_str(), method, path);
+                AUTH_FAILURES.inc(&["insufficient_scope"]);
+                return Err(Denied::Forbidden);
+            }
+            return Ok(key.device_id.map(AuthenticatedDevice));
+        }
+        if !constant_time_eq(secret.as_bytes(), self.api_key.expose().as_bytes()) {
+            log::warn!("Authentication failed from {}", client_id);
+            AUTH_FAILURES.inc(&["invalid_key"]);
+            return Err(Denied::Unauthorized);
+        }
+        Ok(None)
+    }
+}
+
+/// Middleware rejecting requests that fail `validate_auth_header`, unless they carry a stored
+/// key whose scope permits them. Stored keys outside their scope get 403.
+pub async fn require_api_key(
+    State(auth): State<Arc<ApiKeyAuth>>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let client_id = remote_addr.to_string();
+    if !auth.rate_limiter.allow(&client_id).await {
+        return rate_limited(&client_id);
+    }
+    if let Some(key) = auth.stored_key(&request) {
+        if !key.scope.permits(request.method(), request.uri().path(), key.device_id.as_deref()) {
+            log::warn!("API key '{}' ({}) not permitted to {} {}", key.id, key.scope.as_str(), request.method(), request.uri().path());
+            AUTH_FAILURES.inc(&["insufficient_scope"]);
+            return (StatusCode::FORBIDDEN, "Forbidden").into_response();
+        }
+        if let Some(device_id) = key.device_id {
+            request.extensions_mut().insert(AuthenticatedDevice(device_id));
         }
+        return next.run(request).await;
+    }
+    match validate_auth_header(request.headers(), &remote_addr, auth.api_key.expose(), None) {
+        Ok(()) => next.run(request).await,
+        Err(response) => response,
     }
 }
 
@@ -116,6 +469,54 @@ mod tests {
         assert!(!constant_time_eq(b"", b"hello"));
     }
 
+    #[test]
+    fn test_scopes() {
+        assert!(Scope::Read.permits(&Method::GET, "/api/history/temperature", None));
+        assert!(!Scope::Read.permits(&Method::POST, "/api/weather_reports", None));
+        assert!(!Scope::Read.permits(&Method::GET, "/api/admin/keys", None));
+        assert!(Scope::Ingest.permits(&Method::POST, "/api/ingest/rtl433", None));
+        assert!(!Scope::Ingest.permits(&Method::GET, "/api/weather_reports", None));
+        assert!(Scope::Ingest.permits(&Method::GET, "/api/devices/shed/config", Some("shed")));
+        assert!(!Scope::Ingest.permits(&Method::GET, "/api/devices/garden/config", Some("shed")));
+        assert!(Scope::Admin.permits(&Method::DELETE, "/api/admin/keys/abc", None));
+        assert_eq!("Admin".parse(), Ok(Scope::Admin));
+        assert!("write".parse::<Scope>().is_err());
+    }
+
+    #[test]
+    fn test_key_store() {
+        let (ingest, ingest_secret) = ApiKey::issue("Shed sensor", Scope::Ingest, Some("shed".to_string()), None, 100);
+        let (expiring, expiring_secret) = ApiKey::issue("Dashboard", Scope::Read, None, Some(200), 100);
+        let (revoked, revoked_secret) = ApiKey::issue("Old bridge", Scope::Ingest, None, None, 100);
+        let revoked = ApiKey { revoked_at: Some(150), ..revoked };
+        assert_ne!(ingest_secret, expiring_secret);
+        let store = KeyStore::default();
+        store.replace(vec![ingest, expiring, revoked]);
+
+        assert_eq!(store.find(&ingest_secret, 150).map(|key| key.name), Some("Shed sensor".to_string()));
+        assert!(store.find(&expiring_secret, 150).is_some());
+        assert!(store.find(&expiring_secret, 200).is_none());
+        assert!(store.find(&revoked_secret, 150).is_none());
+        assert!(store.find("not-a-key", 150).is_none());
+        // Never serialized, so it can't leak through /api/admin/keys
+        assert!(!serde_json::to_string(&store.snapshot()[0]).unwrap().contains("key_hash"));
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let (ingest, ingest_secret) = ApiKey::issue("Shed sensor", Scope::Ingest, Some("shed".to_string()), None, 100);
+        let store = KeyStore::default();
+        store.replace(vec![ingest]);
+        let auth = ApiKeyAuth::new("server-key".into()).with_keys(store);
+
+        assert_eq!(auth.authorize("a", Some("server-key"), &Method::GET, "/api/current").await, Ok(None));
+        assert_eq!(auth.authorize("a", Some(&ingest_secret), &Method::POST, "/api/weather_reports").await,
+            Ok(Some(AuthenticatedDevice("shed".to_string()))));
+        assert_eq!(auth.authorize("a", Some(&ingest_secret), &Method::GET, "/api/current").await, Err(Denied::Forbidden));
+        assert_eq!(auth.authorize("a", Some("wrong"), &Method::GET, "/api/current").await, Err(Denied::Unauthorized));
+        assert_eq!(auth.authorize("a", None, &Method::GET, "/api/current").await, Err(Denied::Unauthorized));
+    }
+
     #[test]
     fn test_rate_limiter() {
         let limiter = RateLimiter::new(3, 60);
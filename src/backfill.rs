@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::archive::{self, ArchivedPayload, PayloadFilter};
+use crate::db_pool::get_combo_pool;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::{accuweather_enhanced, nws, tomorrow_io};
+use crate::provider::common::{Weather, WeatherError};
+
+// Replays archived `current` payloads through today's normalization code into
+// `normalized_observations` (one row per payload). POST /api/admin/backfill defaults to a dry
+// run that reports, per payload, the fields that would change; `apply=true` writes the rows.
+
+/// Payloads replayed per run; narrow the date range to backfill more
+const MAX_PAYLOADS: i64 = 10_000;
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.normalized_observations (
+        id serial NOT NULL,
+        payload_id INTEGER NOT NULL UNIQUE,
+        provider varchar NOT NULL,
+        location varchar NOT NULL,
+        fetched BIGINT DEFAULT 0,
+        weather TEXT NOT NULL,
+        normalized BIGINT DEFAULT 0,
+        CONSTRAINT normalized_observations_pkey PRIMARY KEY (id));
+    CREATE INDEX IF NOT EXISTS normalized_observations_fetched_idx ON normalized_observations (provider, fetched DESC);"
+}
+
+#[derive(Debug, Clone)]
+pub struct BackfillRequest {
+    pub provider: Option<String>,
+    pub since: i64,
+    pub until: i64,
+    pub apply: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RowStatus {
+    Added,
+    Changed,
+    Unchanged,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowDiff {
+    pub payload_id: i32,
+    pub provider: String,
+    pub location: String,
+    pub fetched: i64,
+    pub status: RowStatus,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<FieldChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackfillReport {
+    pub applied: bool,
+    pub payloads: usize,
+    /// MAX_PAYLOADS was reached; later payloads in the range were not replayed
+    pub truncated: bool,
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    /// Every row except unchanged ones
+    pub rows: Vec<RowDiff>,
+}
+
+/// Field-level differences between two JSON documents, with dotted paths for nested fields
+pub fn diff(old: &Value, new: &Value) -> Vec<FieldChange> {
+    fn walk(path: &str, old: &Value, new: &Value, changes: &mut Vec<FieldChange>) {
+        match (old, new) {
+            (Value::Object(old_fields), Value::Object(new_fields)) => {
+                let mut keys: Vec<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                for key in keys {
+                    let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    walk(&child, old_fields.get(key).unwrap_or(&Value::Null), new_fields.get(key).unwrap_or(&Value::Null), changes);
+                }
+            },
+            (old, new) if old != new => changes.push(FieldChange {
+                field: path.to_string(),
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            _ => {},
+        }
+    }
+    let mut changes = Vec::new();
+    walk("", old, new, &mut changes);
+    changes
+}
+
+/// Current weather from one archived payload using the normalization code in this build
+fn normalize(payload: &ArchivedPayload, accuweather_locations: &HashMap<String, Value>) -> Result<Weather, WeatherError> {
+    match payload.provider.as_str() {
+        "accuweather" => accuweather_enhanced::normalize_current(&payload.payload, accuweather_locations.get(&payload.location), &payload.location, payload.fetched),
+        #[cfg(feature = "enhanced-providers")]
+        "openweather" => crate::provider::openweather::normalize_current(&payload.payload, &payload.location),
+        "nws" => nws::normalize_current(&payload.payload, &payload.location),
+        "tomorrow_io" => tomorrow_io::normalize_current(&payload.payload, &payload.location),
+        other => Err(WeatherError::ConfigurationError(format!("No normalization for {} payloads", other))),
+    }
+}
+
+pub async fn run(request: &BackfillRequest) -> JupiterResult<BackfillReport> {
+    let payloads = archive::payloads(&PayloadFilter {
+        provider: request.provider.clone(),
+        endpoint: Some("current".to_string()),
+        since: Some(request.since),
+        until: Some(request.until),
+    }, MAX_PAYLOADS).await?;
+
+    // AccuWeather current conditions carry no location, so pair them with the newest archived
+    // location lookup for the same key
+    let mut accuweather_locations = HashMap::new();
+    if payloads.iter().any(|p| p.provider == "accuweather") {
+        let lookups = archive::payloads(&PayloadFilter {
+            provider: Some("accuweather".to_string()),
+            endpoint: Some("location".to_string()),
+            since: None,
+            until: Some(request.until),
+        }, MAX_PAYLOADS).await?;
+        for lookup in lookups {
+            accuweather_locations.insert(lookup.location, lookup.payload);
+        }
+    }
+
+    let pool = get_combo_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+
+    let ids: Vec<i32> = payloads.iter().map(|p| p.id).collect();
+    let existing: HashMap<i32, Value> = client.query("SELECT payload_id, weather FROM normalized_observations WHERE payload_id = ANY($1)", &[&ids]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
+        .iter()
+        .map(|row| {
+            let weather: String = row.get("weather");
+            (row.get("payload_id"), serde_json::from_str(&weather).unwrap_or(Value::Null))
+        })
+        .collect();
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let mut report = BackfillReport {
+        applied: request.apply,
+        payloads: payloads.len(),
+        truncated: payloads.len() as i64 == MAX_PAYLOADS,
+        ..BackfillReport::default()
+    };
+
+    for payload in &payloads {
+        let mut row = RowDiff {
+            payload_id: payload.id,
+            provider: payload.provider.clone(),
+            location: payload.location.clone(),
+            fetched: payload.fetched,
+            status: RowStatus::Unchanged,
+            changes: Vec::new(),
+            error: None,
+        };
+        let weather = match normalize(payload, &accuweather_locations).and_then(|w| Ok(serde_json::to_value(w)?)) {
+            Ok(weather) => weather,
+            Err(e) => {
+                report.failed += 1;
+                row.status = RowStatus::Failed;
+                row.error = Some(e.to_string());
+                report.rows.push(row);
+                continue;
+            },
+        };
+
+        match existing.get(&payload.id) {
+            None => {
+                report.added += 1;
+                row.status = RowStatus::Added;
+            },
+            Some(old) => {
+                row.changes = diff(old, &weather);
+                if row.changes.is_empty() {
+                    report.unchanged += 1;
+                    continue;
+                }
+                report.changed += 1;
+                row.status = RowStatus::Changed;
+            },
+        }
+
+        if request.apply {
+            client.execute("INSERT INTO normalized_observations (payload_id, provider, location, fetched, weather, normalized)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (payload_id) DO UPDATE SET weather = EXCLUDED.weather, normalized = EXCLUDED.normalized",
+                &[&payload.id, &payload.provider, &payload.location, &payload.fetched, &weather.to_string(), &now]).await
+                .map_err(|e| JupiterError::DatabaseError(format!("Upsert failed: {}", e)))?;
+        }
+        report.rows.push(row);
+    }
+    log::info!("[backfill] {} payload(s): {} added, {} changed, {} unchanged, {} failed{}",
+        report.payloads, report.added, report.changed, report.unchanged, report.failed,
+        if request.apply { "" } else { " (dry run)" });
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_reports_nested_fields() {
+        let old = json!({"temperature": 21.0, "comfort": {"level": "Comfortable", "score": 80}, "icon": "1"});
+        let new = json!({"temperature": 21.0, "comfort": {"level": "Very comfortable", "score": 80}, "uv_index": 3.0});
+
+        let fields: Vec<(String, Value, Value)> = diff(&old, &new).into_iter().map(|c| (c.field, c.old, c.new)).collect();
+        assert_eq!(fields, vec![
+            ("comfort.level".to_string(), json!("Comfortable"), json!("Very comfortable")),
+            ("icon".to_string(), json!("1"), Value::Null),
+            ("uv_index".to_string(), Value::Null, json!(3.0)),
+        ]);
+        assert!(diff(&old, &old).is_empty());
+    }
+}
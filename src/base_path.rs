@@ -0,0 +1,72 @@
+use axum::Router;
+
+// Optional URL prefix (BASE_PATH, e.g. "/weather") for hosting jupiter under a sub-path behind a
+// reverse proxy that forwards the full path. The routes are nested below the prefix, so handlers
+// keep matching on /api/... and /public/...; requests outside the prefix get a 404.
+
+/// Normalises a configured base path to "/segment[/segment...]"; "" and "/" mean no prefix
+pub fn normalize(path: &str) -> Option<String> {
+    let trimmed = path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(format!("/{}", trimmed))
+    }
+}
+
+pub fn from_env() -> Option<String> {
+    std::env::var("BASE_PATH").ok().and_then(|path| normalize(&path))
+}
+
+/// True if the base path only contains characters that can appear unescaped in a URL path
+pub fn is_valid(base_path: &str) -> bool {
+    base_path.starts_with('/')
+        && !base_path.contains("//")
+        && base_path.chars().all(|c| c.is_ascii_alphanumeric() || "/-._~".contains(c))
+}
+
+/// Nests `router` below `base_path`, if any. Only whole path segments match, so "/weather"
+/// accepts "/weather" and "/weather/api/..." but not "/weatherstation".
+pub fn nest<S: Clone + Send + Sync + 'static>(router: Router<S>, base_path: Option<&str>) -> Router<S> {
+    match base_path {
+        Some(prefix) => Router::new().nest(prefix, router),
+        None => router,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("/weather/"), Some("/weather".to_string()));
+        assert_eq!(normalize("weather"), Some("/weather".to_string()));
+        assert_eq!(normalize("/apps/weather"), Some("/apps/weather".to_string()));
+        assert_eq!(normalize("/"), None);
+        assert_eq!(normalize(""), None);
+        assert!(is_valid("/apps/weather"));
+        assert!(!is_valid("/weather?x=1"));
+    }
+
+    #[tokio::test]
+    async fn test_nest() {
+        use axum::body::{to_bytes, Body};
+        use axum::extract::RawQuery;
+        use axum::http::{Request, StatusCode};
+        use axum::routing::get;
+        use tower::ServiceExt;
+
+        let router = nest(Router::new().route("/api/pollen", get(|RawQuery(query): RawQuery| async move {
+            query.unwrap_or_default()
+        })), Some("/weather"));
+        let request = |uri: &str| router.clone().oneshot(Request::get(uri).body(Body::empty()).unwrap());
+
+        let response = request("/weather/api/pollen?x=1").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(&to_bytes(response.into_body(), 64).await.unwrap()[..], b"x=1");
+
+        assert_eq!(request("/weatherstation/api/pollen").await.unwrap().status(), StatusCode::NOT_FOUND);
+        assert_eq!(request("/api/pollen").await.unwrap().status(), StatusCode::NOT_FOUND);
+    }
+}
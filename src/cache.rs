@@ -0,0 +1,394 @@
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::db_pool::combo_client;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::utils::time::safe_timestamp_with_fallback;
+
+// Provider responses cached by the combo server: ComboProvider's conditions, forecasts, alerts
+// and so on, and the legacy current conditions of GET /. Entries are kept in memory in front of
+// the backend CACHE_BACKEND names, so they outlive restarts and are shared by instances:
+//
+// - `postgres` (the default): the `response_cache` table of the combo database;
+// - `redis`: the Redis server at REDIS_URL (builds with `--features redis`, see `cluster`);
+// - `memory`: this process only, lost on restart.
+//
+// An entry is fresh for the TTL of its caller (CACHE_TTL_SECS for the combo server). For
+// CACHE_STALE_SECS after that (60 by default, 0 turns it off) it is stale-while-revalidate:
+// one request refreshes it while the others keep getting the stale entry rather than queuing up
+// on the providers. Entries are kept for RETAIN_SECS, so they can still be served when every
+// provider fails. Backend errors are logged and treated as misses; the cache never fails a request.
+
+/// How long backends keep an entry after it was stored
+pub const RETAIN_SECS: u64 = 86_400;
+
+/// How long a refresh may take before another request takes over revalidating its entry
+const REVALIDATE_SECS: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub value: serde_json::Value,
+    pub stored_at: i64,
+}
+
+impl CacheEntry {
+    pub fn age(&self, now: i64) -> u64 {
+        (now - self.stored_at).max(0) as u64
+    }
+}
+
+/// Where cached entries are kept beyond this process
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn get(&self, key: &str) -> JupiterResult<Option<CacheEntry>>;
+
+    /// Stores `entry` under `key` for at least `retain`
+    async fn set(&self, key: &str, entry: &CacheEntry, retain: Duration) -> JupiterResult<()>;
+}
+
+/// Entries of this process, each until its expiry
+#[derive(Debug, Default)]
+pub struct Memory {
+    entries: RwLock<HashMap<String, (CacheEntry, i64)>>,
+}
+
+#[async_trait]
+impl CacheBackend for Memory {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn get(&self, key: &str) -> JupiterResult<Option<CacheEntry>> {
+        let entries = self.entries.read()
+            .map_err(|e| JupiterError::LockError(format!("Cache lock poisoned: {}", e)))?;
+        Ok(entries.get(key).filter(|(_, expires_at)| *expires_at > safe_timestamp_with_fallback()).map(|(entry, _)| entry.clone()))
+    }
+
+    async fn set(&self, key: &str, entry: &CacheEntry, retain: Duration) -> JupiterResult<()> {
+        let mut entries = self.entries.write()
+            .map_err(|e| JupiterError::LockError(format!("Cache lock poisoned: {}", e)))?;
+        let now = safe_timestamp_with_fallback();
+        entries.retain(|_, (_, expires_at)| *expires_at > now);
+        entries.insert(key.to_string(), (entry.clone(), now + retain.as_secs() as i64));
+        Ok(())
+    }
+}
+
+/// The `response_cache` table of the combo database
+pub struct Postgres;
+
+impl Postgres {
+    pub fn sql_build_statement() -> &'static str {
+        "CREATE TABLE IF NOT EXISTS public.response_cache (
+            key varchar NOT NULL,
+            value jsonb NOT NULL,
+            stored_at BIGINT NOT NULL,
+            expires_at BIGINT NOT NULL,
+            CONSTRAINT response_cache_pkey PRIMARY KEY (key));"
+    }
+}
+
+#[async_trait]
+impl CacheBackend for Postgres {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn get(&self, key: &str) -> JupiterResult<Option<CacheEntry>> {
+        let row = combo_client().await?
+            .query_opt("SELECT value, stored_at FROM response_cache WHERE key = $1 AND expires_at > $2", &[&key, &safe_timestamp_with_fallback()]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+        Ok(row.map(|row| CacheEntry { value: row.get(0), stored_at: row.get(1) }))
+    }
+
+    async fn set(&self, key: &str, entry: &CacheEntry, retain: Duration) -> JupiterResult<()> {
+        let client = combo_client().await?;
+        let now = safe_timestamp_with_fallback();
+        // A key per location and kind of response, so expired rows are few
+        client.execute("DELETE FROM response_cache WHERE expires_at <= $1", &[&now]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+        client.execute(
+            "INSERT INTO response_cache (key, value, stored_at, expires_at) VALUES ($1, $2, $3, $4) \
+            ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, stored_at = EXCLUDED.stored_at, expires_at = EXCLUDED.expires_at",
+            &[&key, &entry.value, &entry.stored_at, &(now + retain.as_secs() as i64)],
+        ).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// Keys under `jupiter:cache:` in the Redis server of `cluster`
+#[cfg(feature = "redis")]
+pub struct Redis;
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl CacheBackend for Redis {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn get(&self, key: &str) -> JupiterResult<Option<CacheEntry>> {
+        let Some(cluster) = crate::cluster::get() else { return Ok(None) };
+        match cluster.cache_get(key).await {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set(&self, key: &str, entry: &CacheEntry, retain: Duration) -> JupiterResult<()> {
+        if let Some(cluster) = crate::cluster::get() {
+            cluster.cache_set(key, &serde_json::to_string(entry)?, retain).await;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Memory,
+    Postgres,
+    Redis,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub backend: Backend,
+    pub stale_secs: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self { backend: Backend::Postgres, stale_secs: 60 }
+    }
+}
+
+impl CacheConfig {
+    /// Reads the settings through `var`; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let mut problems = Vec::new();
+        let mut config = Self::default();
+        match var("CACHE_BACKEND").map(|backend| backend.to_lowercase()).as_deref() {
+            None | Some("postgres") => {},
+            Some("memory") => config.backend = Backend::Memory,
+            Some("redis") if !cfg!(feature = "redis") => {
+                problems.push(("CACHE_BACKEND", "redis needs a build with the redis feature (cargo build --features redis)".to_string()));
+            },
+            Some("redis") if var("REDIS_URL").is_none() => {
+                problems.push(("CACHE_BACKEND", "redis needs REDIS_URL".to_string()));
+            },
+            Some("redis") => config.backend = Backend::Redis,
+            Some(other) => problems.push(("CACHE_BACKEND", format!("must be memory, postgres or redis, got '{}'", other))),
+        }
+        match var("CACHE_STALE_SECS").map(|v| v.parse::<u64>()) {
+            None => {},
+            Some(Ok(secs)) => config.stale_secs = secs,
+            Some(Err(_)) => problems.push(("CACHE_STALE_SECS", "must be a number of seconds (0 turns stale-while-revalidate off)".to_string())),
+        }
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+}
+
+/// What a lookup found
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lookup {
+    Fresh(CacheEntry),
+    /// Expired within the stale-while-revalidate window while another request refreshes it
+    Stale(CacheEntry),
+    /// The caller should refresh the entry and `set` it; the expired entry, if any, is for
+    /// when that fails
+    Revalidate(Option<CacheEntry>),
+}
+
+pub struct Cache {
+    memory: Memory,
+    backend: Option<Arc<dyn CacheBackend>>,
+    stale_secs: u64,
+    revalidating: Mutex<HashMap<String, Instant>>,  // Keys being refreshed, since when
+}
+
+impl Cache {
+    /// In this process only
+    pub fn memory(stale_secs: u64) -> Self {
+        Self { memory: Memory::default(), backend: None, stale_secs, revalidating: Mutex::new(HashMap::new()) }
+    }
+
+    /// In memory in front of `backend`
+    pub fn with_backend(backend: Arc<dyn CacheBackend>, stale_secs: u64) -> Self {
+        Self { backend: Some(backend), ..Self::memory(stale_secs) }
+    }
+
+    pub fn from_config(config: &CacheConfig) -> Self {
+        match config.backend {
+            Backend::Memory => Self::memory(config.stale_secs),
+            Backend::Postgres => Self::with_backend(Arc::new(Postgres), config.stale_secs),
+            #[cfg(feature = "redis")]
+            Backend::Redis => Self::with_backend(Arc::new(Redis), config.stale_secs),
+            #[cfg(not(feature = "redis"))]
+            Backend::Redis => Self::memory(config.stale_secs),
+        }
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.as_ref().map(|backend| backend.name()).unwrap_or("memory")
+    }
+
+    /// The newest entry for `key` whatever its age, asking the backend when memory has none
+    /// younger than `ttl`: another instance may have refreshed it
+    pub async fn get(&self, key: &str, ttl: u64) -> Option<CacheEntry> {
+        let now = safe_timestamp_with_fallback();
+        let local = self.memory.get(key).await.ok().flatten();
+        if local.as_ref().is_some_and(|entry| entry.age(now) < ttl) {
+            return local;
+        }
+        let Some(backend) = &self.backend else { return local };
+        let shared = backend.get(key).await
+            .map_err(|e| log::warn!("[cache] Failed to read {} from {}: {}", key, backend.name(), e))
+            .ok()
+            .flatten();
+        match (local, shared) {
+            (Some(local), Some(shared)) if shared.stored_at > local.stored_at => {
+                let _ = self.memory.set(key, &shared, Duration::from_secs(RETAIN_SECS)).await;
+                Some(shared)
+            },
+            (None, Some(shared)) => {
+                let _ = self.memory.set(key, &shared, Duration::from_secs(RETAIN_SECS)).await;
+                Some(shared)
+            },
+            (local, _) => local,
+        }
+    }
+
+    /// The entry for `key` if younger than `ttl` seconds, see `Lookup`
+    pub async fn lookup(&self, key: &str, ttl: u64) -> Lookup {
+        let entry = self.get(key, ttl).await;
+        let age = match &entry {
+            Some(entry) if entry.age(safe_timestamp_with_fallback()) < ttl => return Lookup::Fresh(entry.clone()),
+            Some(entry) => entry.age(safe_timestamp_with_fallback()),
+            None => return Lookup::Revalidate(None),
+        };
+        if age >= ttl.saturating_add(self.stale_secs) {
+            return Lookup::Revalidate(entry);
+        }
+        let Ok(mut revalidating) = self.revalidating.lock() else { return Lookup::Revalidate(entry) };
+        match revalidating.get(key) {
+            Some(since) if since.elapsed() < Duration::from_secs(REVALIDATE_SECS) => entry.map(Lookup::Stale).unwrap_or(Lookup::Revalidate(None)),
+            _ => {
+                revalidating.insert(key.to_string(), Instant::now());
+                Lookup::Revalidate(entry)
+            }
+        }
+    }
+
+    /// Stores `value` under `key`, ending its revalidation
+    pub async fn set(&self, key: &str, value: serde_json::Value) {
+        let entry = CacheEntry { value, stored_at: safe_timestamp_with_fallback() };
+        let retain = Duration::from_secs(RETAIN_SECS);
+        let _ = self.memory.set(key, &entry, retain).await;
+        if let Some(backend) = &self.backend {
+            if let Err(e) = backend.set(key, &entry, retain).await {
+                log::warn!("[cache] Failed to store {} in {}: {}", key, backend.name(), e);
+            }
+        }
+        self.release(key);
+    }
+
+    /// Ends the revalidation of `key` without a new entry, e.g. when the refresh failed
+    pub fn release(&self, key: &str) {
+        if let Ok(mut revalidating) = self.revalidating.lock() {
+            revalidating.remove(key);
+        }
+    }
+}
+
+pub static CACHE_CONFIG: Lazy<CacheConfig> = Lazy::new(|| {
+    CacheConfig::from_env().unwrap_or_else(|problems| {
+        for (setting, message) in problems {
+            log::error!("Invalid {}, caching in memory only: {}", setting, message);
+        }
+        CacheConfig { backend: Backend::Memory, ..CacheConfig::default() }
+    })
+});
+
+static SHARED: Lazy<Arc<Cache>> = Lazy::new(|| {
+    let cache = Cache::from_config(&CACHE_CONFIG);
+    log::info!("[cache] Caching provider responses in {}", cache.backend_name());
+    Arc::new(cache)
+});
+
+/// The cache of the combo server, configured from the environment
+pub fn shared() -> Arc<Cache> {
+    SHARED.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_cache_config() {
+        let config = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            CacheConfig::from_vars(|name| vars.get(name).cloned())
+        };
+        assert_eq!(config(&[]).unwrap(), CacheConfig::default());
+        assert_eq!(config(&[("CACHE_BACKEND", "Memory"), ("CACHE_STALE_SECS", "0")]).unwrap(), CacheConfig { backend: Backend::Memory, stale_secs: 0 });
+        assert_eq!(config(&[("CACHE_BACKEND", "disk")]).unwrap_err()[0].0, "CACHE_BACKEND");
+        assert_eq!(config(&[("CACHE_BACKEND", "redis")]).unwrap_err()[0].0, "CACHE_BACKEND");
+        assert_eq!(config(&[("CACHE_STALE_SECS", "-1")]).unwrap_err()[0].0, "CACHE_STALE_SECS");
+    }
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate() {
+        let cache = Cache::memory(60);
+        assert_eq!(cache.lookup("current:10001", 300).await, Lookup::Revalidate(None));
+
+        cache.set("current:10001", json!({"temperature": 21.5})).await;
+        assert!(matches!(cache.lookup("current:10001", 300).await, Lookup::Fresh(_)));
+
+        // Expired: the first request revalidates, the next ones get the stale entry meanwhile
+        let expired = CacheEntry { value: json!({"temperature": 20.0}), stored_at: safe_timestamp_with_fallback() - 310 };
+        cache.memory.set("current:10001", &expired, Duration::from_secs(RETAIN_SECS)).await.unwrap();
+        assert_eq!(cache.lookup("current:10001", 300).await, Lookup::Revalidate(Some(expired.clone())));
+        assert_eq!(cache.lookup("current:10001", 300).await, Lookup::Stale(expired.clone()));
+        cache.release("current:10001");
+        assert_eq!(cache.lookup("current:10001", 300).await, Lookup::Revalidate(Some(expired.clone())));
+
+        // Past the stale window every request revalidates, but the entry is kept for outages
+        assert_eq!(cache.lookup("current:10001", 200).await, Lookup::Revalidate(Some(expired.clone())));
+        assert_eq!(cache.lookup("current:10001", 200).await, Lookup::Revalidate(Some(expired.clone())));
+        assert_eq!(cache.get("current:10001", 0).await, Some(expired));
+    }
+
+    #[tokio::test]
+    async fn test_backend_shared() {
+        // Another instance's entries are read from the backend and kept in memory
+        let backend = Arc::new(Memory::default());
+        let other = Cache::with_backend(backend.clone(), 0);
+        let cache = Cache::with_backend(backend.clone(), 0);
+        other.set("alerts:10001", json!([])).await;
+        assert!(matches!(cache.lookup("alerts:10001", 300).await, Lookup::Fresh(_)));
+        assert_eq!(cache.backend_name(), "memory");
+
+        let newer = CacheEntry { value: json!(["Flood Watch"]), stored_at: safe_timestamp_with_fallback() + 1 };
+        backend.set("alerts:10001", &newer, Duration::from_secs(60)).await.unwrap();
+        assert_eq!(cache.get("alerts:10001", 0).await, Some(newer));
+    }
+}
@@ -0,0 +1,475 @@
+use reqwest::{Method, RequestBuilder, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::admin::{DeviceExport, ErasureConfirmation, ErasureResult};
+use crate::analysis::history::HistorySeries;
+use crate::analysis::hvac::HvacHints;
+use crate::analysis::nowcast::Nowcast;
+use crate::analysis::solar::SolarForecast;
+use crate::analysis::uv::{SkinType, UvGuidance};
+use crate::archive::{ArchivedPayload, PayloadFilter};
+use crate::auth::{ApiKey, IssuedKey, NewApiKey};
+use crate::backfill::{BackfillReport, BackfillRequest};
+use crate::deploy::{Candidate, Deployed, Preview, Running};
+use crate::devices::{Device, DeviceConfig, DeviceKey, DeviceStatus};
+use crate::ingest::IngestSummary;
+use crate::provider::air_quality::AirQualityReport;
+use crate::provider::aviation::TafReport;
+use crate::provider::combo::{CachedWeatherData, PublicCurrent, PublicDaily};
+use crate::provider::combo_enhanced::Capabilities;
+use crate::provider::common::{MarineForecast, PollenReport, Weather, WeatherError};
+use crate::provider::homebrew::{ReportPage, ReportRange, Resolution, RoomsReport, VentilationReport, WeatherReport};
+use crate::report_chain::ChainVerification;
+use crate::scheduler::{JobRun, JobStatus};
+use crate::slo::ProviderHealth;
+use crate::schema::{self, Schema};
+use crate::secret::Secret;
+
+// Async client for jupiter's own HTTP API, decoding responses into the same models the servers
+// serialize. One client talks to one server: point it at the homebrew port for reports, devices,
+// rooms and ingest, or at the combo port for conditions, forecasts and admin jobs (the combo
+// server also serves homebrew reports when it has a homebrew database). Include BASE_PATH in
+// the base URL when the server runs behind a prefix, e.g. "https://example.com/weather".
+// Responses tagged with a different X-Schema-Version than this build expects are rejected
+// instead of being decoded into the wrong shape.
+
+/// A homebrew reading, sent as the form fields accepted by POST /api/weather_reports
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NewWeatherReport {
+    pub temperature: Option<f64>,
+    pub humidity: Option<f64>,
+    pub percipitation: Option<f64>,
+    pub pm10: Option<f64>,
+    pub pm25: Option<f64>,
+    pub co2: Option<f64>,
+    pub tvoc: Option<f64>,
+    pub wind_speed: Option<f64>,
+    pub wind_direction: Option<f64>,
+    pub battery_voltage: Option<f64>,
+    pub battery_percent: Option<f64>,
+    pub rssi: Option<f64>,
+    pub device_type: String,    // Ignored when the report names a registered device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct JobStarted {
+    started: bool,
+}
+
+#[derive(Clone)]
+pub struct JupiterClient {
+    base_url: Url,
+    apikey: Secret<String>,
+    http: reqwest::Client,
+}
+
+impl JupiterClient {
+    pub fn new(base_url: &str, apikey: impl Into<Secret<String>>) -> Result<Self, WeatherError> {
+        let base_url = Url::parse(base_url)
+            .ok()
+            .filter(|url| url.scheme() == "http" || url.scheme() == "https")
+            .ok_or_else(|| WeatherError::ConfigurationError(format!("Invalid jupiter server URL '{}'", base_url)))?;
+        Ok(JupiterClient {
+            base_url,
+            apikey: apikey.into(),
+            http: reqwest::Client::new(),
+        })
+    }
+
+    /// Use a preconfigured reqwest client, e.g. with timeouts or a custom root certificate
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// `segments` appended to the base URL, each percent-encoded
+    pub fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base_url.clone();
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url
+    }
+
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        self.http.request(method, self.url(segments))
+            .header("Authorization", self.apikey.expose().as_str())
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, WeatherError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(status_error(status, body))
+    }
+
+    async fn text(&self, request: RequestBuilder) -> Result<String, WeatherError> {
+        Ok(self.send(request).await?.text().await?)
+    }
+
+    async fn json<T: DeserializeOwned>(&self, request: RequestBuilder, schema: Option<Schema>) -> Result<T, WeatherError> {
+        let response = self.send(request).await?;
+        if let Some(schema) = schema {
+            check_schema(response.headers().get(schema::HEADER).and_then(|v| v.to_str().ok()), schema)?;
+        }
+        let body = response.bytes().await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    // Homebrew reports, served by both servers
+
+    /// GET /api/weather_reports: the most recent homebrew report
+    pub async fn latest_report(&self) -> Result<WeatherReport, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "weather_reports"]), Some(Schema::WeatherReport)).await
+    }
+
+    /// POST /api/weather_reports: stores a reading and returns it as saved
+    pub async fn post_report(&self, report: &NewWeatherReport) -> Result<WeatherReport, WeatherError> {
+        self.json(self.request(Method::POST, &["api", "weather_reports"]).form(report), Some(Schema::WeatherReport)).await
+    }
+
+    /// GET /api/value/{field}: the latest value of one report field, optionally for a device id or
+    /// device type (indoor/outdoor/greenhouse/other)
+    pub async fn value(&self, field: &str, device: Option<&str>) -> Result<f64, WeatherError> {
+        let mut request = self.request(Method::GET, &["api", "value", field]);
+        if let Some(device) = device {
+            request = request.query(&[("device", device)]);
+        }
+        let text = self.text(request).await?;
+        text.trim().parse().map_err(|_| WeatherError::ParseError(format!("Expected a number for {}, got '{}'", field, text)))
+    }
+
+    /// GET /api/history/{field} over the last `hours`, with `query` pairs such as ("device",
+    /// "outdoor"), ("step", "600") or ("interpolate", "true")
+    pub async fn history(&self, field: &str, hours: i64, query: &[(&str, &str)]) -> Result<HistorySeries, WeatherError> {
+        let request = self.request(Method::GET, &["api", "history", field]).query(&[("hours", hours)]).query(query);
+        self.json(request, Some(Schema::History)).await
+    }
+
+    /// GET /api/weather_reports/history: reports between `start` and `end` (Unix seconds) at
+    /// `resolution`, optionally of one device type
+    pub async fn report_range(&self, start: i64, end: i64, resolution: Resolution, device_type: Option<&str>) -> Result<ReportRange, WeatherError> {
+        let mut request = self.request(Method::GET, &["api", "weather_reports", "history"])
+            .query(&[("start", start), ("end", end)])
+            .query(&[("resolution", resolution.as_str())]);
+        if let Some(device_type) = device_type {
+            request = request.query(&[("device_type", device_type)]);
+        }
+        self.json(request, Some(Schema::ReportRange)).await
+    }
+
+    /// GET /api/weather_reports/list: up to `limit` stored reports newest first, optionally of
+    /// one device id or placement, continuing at the `next_cursor` of the previous page
+    pub async fn list_reports(&self, limit: usize, device: Option<&str>, cursor: Option<&str>) -> Result<ReportPage, WeatherError> {
+        let mut request = self.request(Method::GET, &["api", "weather_reports", "list"]).query(&[("limit", limit)]);
+        for (name, value) in [("device", device), ("cursor", cursor)] {
+            if let Some(value) = value {
+                request = request.query(&[(name, value)]);
+            }
+        }
+        self.json(request, Some(Schema::ReportPage)).await
+    }
+
+    // Combo server
+
+    /// Current conditions from the combo cache, refreshed from the providers when stale
+    pub async fn current_conditions(&self) -> Result<CachedWeatherData, WeatherError> {
+        self.json(self.request(Method::GET, &[]), Some(Schema::CachedConditions)).await
+    }
+
+    /// GET /public/current, available without an API key in public mode
+    pub async fn public_current(&self) -> Result<PublicCurrent, WeatherError> {
+        self.json(self.request(Method::GET, &["public", "current"]), Some(Schema::PublicCurrent)).await
+    }
+
+    /// GET /public/daily: today's forecast, available without an API key in public mode
+    pub async fn public_daily(&self) -> Result<PublicDaily, WeatherError> {
+        self.json(self.request(Method::GET, &["public", "daily"]), Some(Schema::PublicDaily)).await
+    }
+
+    /// GET /api/pollen
+    pub async fn pollen(&self) -> Result<PollenReport, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "pollen"]), Some(Schema::Pollen)).await
+    }
+
+    /// GET /api/uv/guidance; the server defaults to skin type II and the current UV index
+    pub async fn uv_guidance(&self, skin_type: Option<SkinType>, hour: Option<u8>) -> Result<UvGuidance, WeatherError> {
+        let mut request = self.request(Method::GET, &["api", "uv", "guidance"]);
+        if let Some(skin_type) = skin_type {
+            request = request.query(&[("skin_type", skin_type.as_param())]);
+        }
+        if let Some(hour) = hour {
+            request = request.query(&[("hour", hour)]);
+        }
+        self.json(request, Some(Schema::UvGuidance)).await
+    }
+
+    /// GET /api/hvac/hints
+    pub async fn hvac_hints(&self) -> Result<HvacHints, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "hvac", "hints"]), Some(Schema::HvacHints)).await
+    }
+
+    /// GET /api/solar/forecast, NotFound unless the server has a PV system configured
+    pub async fn solar_forecast(&self) -> Result<SolarForecast, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "solar", "forecast"]), Some(Schema::SolarForecast)).await
+    }
+
+    /// GET /api/marine
+    pub async fn marine(&self) -> Result<MarineForecast, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "marine"]), Some(Schema::Marine)).await
+    }
+
+    /// GET /api/aviation: the decoded TAF for the configured airfield
+    pub async fn aviation(&self) -> Result<TafReport, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "aviation"]), Some(Schema::Aviation)).await
+    }
+
+    /// GET /api/current: conditions averaged across providers, with each provider's contribution
+    pub async fn current_weather(&self) -> Result<Weather, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "current"]), Some(Schema::CurrentWeather)).await
+    }
+
+    /// GET /api/capabilities: the features the combo server's providers support
+    pub async fn capabilities(&self) -> Result<Capabilities, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "capabilities"]), Some(Schema::Capabilities)).await
+    }
+
+    /// GET /api/air_quality: pollutant concentrations with US and European AQI
+    pub async fn air_quality(&self) -> Result<AirQualityReport, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "air_quality"]), Some(Schema::AirQuality)).await
+    }
+
+    /// GET /api/nowcast: when precipitation starts or stops within the next hour
+    pub async fn nowcast(&self) -> Result<Nowcast, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "nowcast"]), Some(Schema::Nowcast)).await
+    }
+
+    /// GET /api/summary/spoken
+    pub async fn spoken_summary(&self) -> Result<String, WeatherError> {
+        self.text(self.request(Method::GET, &["api", "summary", "spoken"])).await
+    }
+
+    /// GET /api/widget.svg
+    pub async fn widget_svg(&self) -> Result<String, WeatherError> {
+        self.text(self.request(Method::GET, &["api", "widget.svg"])).await
+    }
+
+    /// GET /api/admin/jobs
+    pub async fn jobs(&self) -> Result<Vec<JobStatus>, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "admin", "jobs"]), None).await
+    }
+
+    /// GET /api/admin/jobs/{name}/runs: the job's most recent runs, newest first
+    pub async fn job_runs(&self, name: &str, limit: i64) -> Result<Vec<JobRun>, WeatherError> {
+        let request = self.request(Method::GET, &["api", "admin", "jobs", name, "runs"]).query(&[("limit", limit)]);
+        self.json(request, None).await
+    }
+
+    /// POST /api/admin/jobs/{name}/run: false if the job was already running
+    pub async fn run_job(&self, name: &str) -> Result<bool, WeatherError> {
+        let response = self.request(Method::POST, &["api", "admin", "jobs", name, "run"]).send().await?;
+        match response.status() {
+            StatusCode::CONFLICT => Ok(false),
+            status if status.is_success() => Ok(serde_json::from_slice::<JobStarted>(&response.bytes().await?)?.started),
+            status => Err(status_error(status, response.text().await.unwrap_or_default())),
+        }
+    }
+
+    /// GET /api/admin/payloads: archived provider payloads, oldest first
+    pub async fn payloads(&self, filter: &PayloadFilter, limit: i64) -> Result<Vec<ArchivedPayload>, WeatherError> {
+        let mut request = self.request(Method::GET, &["api", "admin", "payloads"]).query(&[("limit", limit)]);
+        if let Some(provider) = &filter.provider {
+            request = request.query(&[("provider", provider)]);
+        }
+        if let Some(endpoint) = &filter.endpoint {
+            request = request.query(&[("endpoint", endpoint)]);
+        }
+        if let Some(since) = filter.since {
+            request = request.query(&[("since", since)]);
+        }
+        if let Some(until) = filter.until {
+            request = request.query(&[("until", until)]);
+        }
+        self.json(request, None).await
+    }
+
+    /// POST /api/admin/backfill: a dry run unless `apply` is set
+    pub async fn backfill(&self, backfill: &BackfillRequest) -> Result<BackfillReport, WeatherError> {
+        let mut request = self.request(Method::POST, &["api", "admin", "backfill"])
+            .query(&[("since", backfill.since), ("until", backfill.until)])
+            .query(&[("apply", backfill.apply)]);
+        if let Some(provider) = &backfill.provider {
+            request = request.query(&[("provider", provider)]);
+        }
+        self.json(request, None).await
+    }
+
+    /// GET /api/admin/providers/health: each provider's p95 latency and error rate against its SLO
+    pub async fn provider_health(&self) -> Result<Vec<ProviderHealth>, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "admin", "providers", "health"]), None).await
+    }
+
+    /// GET /api/admin/config: the provider and cache settings the combo server runs with
+    pub async fn running_config(&self) -> Result<Running, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "admin", "config"]), None).await
+    }
+
+    /// POST /api/admin/config: what `candidate` would change; an invalid candidate comes back
+    /// with its problems rather than as an error
+    pub async fn preview_config(&self, candidate: &Candidate) -> Result<Preview, WeatherError> {
+        let response = self.request(Method::POST, &["api", "admin", "config"]).json(candidate).send().await?;
+        match response.status() {
+            status if status.is_success() || status == StatusCode::UNPROCESSABLE_ENTITY => Ok(serde_json::from_slice(&response.bytes().await?)?),
+            status => Err(status_error(status, response.text().await.unwrap_or_default())),
+        }
+    }
+
+    /// POST /api/admin/config?confirm=: applies a previewed candidate
+    pub async fn deploy_config(&self, confirmation_token: &str) -> Result<Deployed, WeatherError> {
+        let request = self.request(Method::POST, &["api", "admin", "config"]).query(&[("confirm", confirmation_token)]);
+        self.json(request, None).await
+    }
+
+    // Homebrew server
+
+    /// POST /api/ingest/rtl433: one rtl_433 JSON event, or several as JSON lines
+    pub async fn ingest_rtl433(&self, events: &str) -> Result<IngestSummary, WeatherError> {
+        self.json(self.request(Method::POST, &["api", "ingest", "rtl433"]).body(events.to_string()), None).await
+    }
+
+    /// POST /api/ingest/ble: a Theengs/OpenMQTTGateway payload
+    pub async fn ingest_ble(&self, payload: &str) -> Result<IngestSummary, WeatherError> {
+        self.json(self.request(Method::POST, &["api", "ingest", "ble"]).body(payload.to_string()), None).await
+    }
+
+    /// GET /api/devices: every registered device
+    pub async fn devices(&self) -> Result<Vec<Device>, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "devices"]), Some(Schema::Devices)).await
+    }
+
+    /// POST /api/devices: registers a device and returns the API key issued for it
+    pub async fn register_device(&self, device: &Device) -> Result<DeviceKey, WeatherError> {
+        self.json(self.request(Method::POST, &["api", "devices"]).json(device), Some(Schema::DeviceKey)).await
+    }
+
+    /// PUT /api/devices/{id}: replaces the registration, keeping its API key
+    pub async fn update_device(&self, device: &Device) -> Result<Device, WeatherError> {
+        self.json(self.request(Method::PUT, &["api", "devices", &device.id]).json(device), Some(Schema::Device)).await
+    }
+
+    /// DELETE /api/devices/{id}
+    pub async fn unregister_device(&self, id: &str) -> Result<(), WeatherError> {
+        self.send(self.request(Method::DELETE, &["api", "devices", id])).await?;
+        Ok(())
+    }
+
+    /// POST /api/devices/{id}/key: replaces the device's keys with a new ingest key
+    pub async fn issue_device_key(&self, id: &str) -> Result<DeviceKey, WeatherError> {
+        self.json(self.request(Method::POST, &["api", "devices", id, "key"]), Some(Schema::DeviceKey)).await
+    }
+
+    /// GET /api/admin/keys: every stored key, without the keys themselves
+    pub async fn api_keys(&self) -> Result<Vec<ApiKey>, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "admin", "keys"]), None).await
+    }
+
+    /// POST /api/admin/keys: issues a key; the response is the only time it is shown
+    pub async fn create_api_key(&self, key: &NewApiKey) -> Result<IssuedKey, WeatherError> {
+        self.json(self.request(Method::POST, &["api", "admin", "keys"]).json(key), None).await
+    }
+
+    /// DELETE /api/admin/keys/{id}
+    pub async fn revoke_api_key(&self, id: &str) -> Result<(), WeatherError> {
+        self.send(self.request(Method::DELETE, &["api", "admin", "keys", id])).await?;
+        Ok(())
+    }
+
+    /// GET /api/devices/status
+    pub async fn device_statuses(&self) -> Result<Vec<DeviceStatus>, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "devices", "status"]), Some(Schema::DeviceStatus)).await
+    }
+
+    /// GET /api/devices/{id}/config
+    pub async fn device_config(&self, id: &str) -> Result<DeviceConfig, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "devices", id, "config"]), Some(Schema::DeviceConfig)).await
+    }
+
+    /// GET /api/admin/devices/{id}/export
+    pub async fn export_device(&self, id: &str) -> Result<DeviceExport, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "admin", "devices", id, "export"]), None).await
+    }
+
+    /// POST /api/admin/devices/{id}/erase without a token: issues the confirmation for `erase_device`
+    pub async fn request_erasure(&self, id: &str) -> Result<ErasureConfirmation, WeatherError> {
+        self.json(self.request(Method::POST, &["api", "admin", "devices", id, "erase"]), None).await
+    }
+
+    /// POST /api/admin/devices/{id}/erase?confirm=: irreversibly deletes the device's data
+    pub async fn erase_device(&self, id: &str, confirmation_token: &str) -> Result<ErasureResult, WeatherError> {
+        let request = self.request(Method::POST, &["api", "admin", "devices", id, "erase"])
+            .query(&[("confirm", confirmation_token)]);
+        self.json(request, None).await
+    }
+
+    /// GET /api/audit/verify
+    pub async fn verify_audit_chain(&self) -> Result<ChainVerification, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "audit", "verify"]), Some(Schema::AuditVerification)).await
+    }
+
+    /// GET /api/rooms
+    pub async fn rooms(&self) -> Result<RoomsReport, WeatherError> {
+        self.json(self.request(Method::GET, &["api", "rooms"]), Some(Schema::Rooms)).await
+    }
+
+    /// GET /api/ventilation over the last `hours` (1-336)
+    pub async fn ventilation(&self, hours: i64) -> Result<VentilationReport, WeatherError> {
+        let request = self.request(Method::GET, &["api", "ventilation"]).query(&[("hours", hours)]);
+        self.json(request, Some(Schema::Ventilation)).await
+    }
+}
+
+/// The error for a non-2xx response; the servers answer errors with a plain-text message
+pub fn status_error(status: StatusCode, body: String) -> WeatherError {
+    match status {
+        StatusCode::UNAUTHORIZED => WeatherError::InvalidApiKey,
+        StatusCode::TOO_MANY_REQUESTS => WeatherError::RateLimitExceeded,
+        StatusCode::NOT_FOUND => WeatherError::NotFound(body),
+        status => WeatherError::NetworkError(format!("{} {}", status.as_str(), body)),
+    }
+}
+
+/// Accepts a response whose schema header matches this build, or carries none (older servers)
+pub fn check_schema(header: Option<&str>, expected: Schema) -> Result<(), WeatherError> {
+    match header {
+        Some(version) if version != expected.header_value() => Err(WeatherError::ParseError(format!(
+            "Server responded with schema {}, this client supports {}", version, expected.header_value()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_urls_and_errors() {
+        let client = JupiterClient::new("https://example.com/weather/", "key").unwrap();
+        assert_eq!(client.url(&["api", "devices", "ws 1", "config"]).as_str(), "https://example.com/weather/api/devices/ws%201/config");
+        assert_eq!(client.url(&[]).as_str(), "https://example.com/weather");
+        assert!(JupiterClient::new("example.com", "key").is_err());
+
+        assert!(matches!(status_error(StatusCode::UNAUTHORIZED, "Unauthorized".to_string()), WeatherError::InvalidApiKey));
+        assert!(matches!(status_error(StatusCode::NOT_FOUND, "No data".to_string()), WeatherError::NotFound(msg) if msg == "No data"));
+
+        assert!(check_schema(Some("weather_report/1"), Schema::WeatherReport).is_ok());
+        assert!(check_schema(None, Schema::WeatherReport).is_ok());
+        assert!(check_schema(Some("weather_report/2"), Schema::WeatherReport).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use once_cell::sync::OnceCell;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use redis::aio::ConnectionManager;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// State shared by instances behind a load balancer, in the Redis server at REDIS_URL (builds
+// with `--features redis`), so they behave like one server:
+//
+// - rate limits (the API key limiter and PUBLIC_RATE_LIMIT) count requests across instances
+//   in fixed windows rather than per instance;
+// - only one instance at a time refreshes expired current conditions from the providers, the
+//   others wait for the row it stores in the shared combo database;
+// - a refresh that found no provider answering is remembered for FAILURE_TTL_SECS, during
+//   which every instance serves the last cached conditions instead of trying again;
+// - with CACHE_BACKEND=redis, cached provider responses (see `cache`) live here too.
+//
+// Redis is an optimization, never a dependency: when it can't be reached each instance falls
+// back to its own limiters and refreshes.
+
+/// Prefix of every key
+const PREFIX: &str = "jupiter";
+
+/// How long instances wait for another one's refresh before refreshing themselves, and the
+/// longest a refresh holds its lock
+pub const REFRESH_WAIT_SECS: u64 = 15;
+
+/// How long a refresh that got nothing from any provider keeps other refreshes away
+pub const FAILURE_TTL_SECS: u64 = 60;
+
+/// Deletes a lock only if it still holds the caller's token, so a refresh that outlived its
+/// lock doesn't release the one another instance took since
+const UNLOCK_SCRIPT: &str = "if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end";
+
+static CLUSTER: OnceCell<Cluster> = OnceCell::new();
+
+/// A lock taken by `try_lock`, released with `unlock`
+#[must_use]
+pub struct Lock {
+    name: String,
+    token: String,
+}
+
+#[derive(Clone)]
+pub struct Cluster {
+    connection: ConnectionManager,
+}
+
+/// The shared state, if REDIS_URL is set and was reachable at startup
+pub fn get() -> Option<&'static Cluster> {
+    CLUSTER.get()
+}
+
+/// Connects to REDIS_URL, if set; failures are logged and leave every instance on its own
+pub async fn connect_from_env() {
+    let url = match std::env::var("REDIS_URL") {
+        Ok(url) if !url.trim().is_empty() => url,
+        _ => return,
+    };
+    let connection = match redis::Client::open(url.trim()) {
+        Ok(client) => client.get_connection_manager().await,
+        Err(e) => Err(e),
+    };
+    match connection {
+        Ok(connection) => {
+            if CLUSTER.set(Cluster { connection }).is_ok() {
+                log::info!("[cluster] Sharing rate limits and refreshes through Redis");
+            }
+        },
+        Err(e) => log::error!("[cluster] Failed to connect to REDIS_URL, instances won't share state: {}", e),
+    }
+}
+
+fn key(kind: &str, name: &str) -> String {
+    format!("{}:{}:{}", PREFIX, kind, name)
+}
+
+/// Counter key of `client`'s current window of `window_secs`
+fn rate_key(limiter: &str, client: &str, window_secs: u64, now: u64) -> String {
+    key("rate", &format!("{}:{}:{}", limiter, client, now / window_secs.max(1)))
+}
+
+impl Cluster {
+    /// Counts a request of `client` against `limiter`; whether it is within `max` per window,
+    /// or None if Redis couldn't be asked
+    pub async fn allow(&self, limiter: &str, client: &str, max: usize, window: Duration) -> Option<bool> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let key = rate_key(limiter, client, window.as_secs(), now);
+        let (count,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1u64)
+            .expire(&key, window.as_secs().max(1) as i64).ignore()
+            .query_async(&mut self.connection.clone()).await
+            .map_err(|e| log::warn!("[cluster] Rate limit check failed, limiting locally: {}", e))
+            .ok()?;
+        Some(count <= max as u64)
+    }
+
+    /// Takes the lock `name` for at most `ttl` unless another instance holds it (Some(None));
+    /// None if Redis couldn't be asked
+    pub async fn try_lock(&self, name: &str, ttl: Duration) -> Option<Option<Lock>> {
+        let token: String = thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(key("lock", name)).arg(&token).arg("NX").arg("PX").arg(ttl.as_millis() as u64)
+            .query_async(&mut self.connection.clone()).await
+            .map_err(|e| log::warn!("[cluster] Failed to take lock {}: {}", name, e))
+            .ok()?;
+        Some(acquired.map(|_| Lock { name: name.to_string(), token }))
+    }
+
+    /// Releases `lock` unless it expired in the meantime
+    pub async fn unlock(&self, lock: Lock) {
+        let result: redis::RedisResult<i64> = redis::cmd("EVAL")
+            .arg(UNLOCK_SCRIPT).arg(1).arg(key("lock", &lock.name)).arg(&lock.token)
+            .query_async(&mut self.connection.clone()).await;
+        match result {
+            Ok(0) => log::debug!("[cluster] Lock {} expired before it was released", lock.name),
+            Ok(_) => {},
+            Err(e) => log::warn!("[cluster] Failed to release lock {}, it expires on its own: {}", lock.name, e),
+        }
+    }
+
+    /// Remembers for FAILURE_TTL_SECS that `name` failed
+    pub async fn mark_failed(&self, name: &str) {
+        let result: redis::RedisResult<()> = redis::cmd("SET")
+            .arg(key("failed", name)).arg(1).arg("EX").arg(FAILURE_TTL_SECS)
+            .query_async(&mut self.connection.clone()).await;
+        if let Err(e) = result {
+            log::warn!("[cluster] Failed to share failure of {}: {}", name, e);
+        }
+    }
+
+    /// The cached response stored under `name`, None if missing or Redis couldn't be asked
+    pub async fn cache_get(&self, name: &str) -> Option<String> {
+        redis::cmd("GET").arg(key("cache", name))
+            .query_async(&mut self.connection.clone()).await
+            .map_err(|e| log::warn!("[cluster] Failed to read cached {}: {}", name, e))
+            .ok()?
+    }
+
+    /// Caches `value` under `name` for `ttl`
+    pub async fn cache_set(&self, name: &str, value: &str, ttl: Duration) -> bool {
+        let result: redis::RedisResult<()> = redis::cmd("SET")
+            .arg(key("cache", name)).arg(value).arg("EX").arg(ttl.as_secs().max(1))
+            .query_async(&mut self.connection.clone()).await;
+        result.map_err(|e| log::warn!("[cluster] Failed to cache {}: {}", name, e)).is_ok()
+    }
+
+    /// Whether any instance marked `name` failed within FAILURE_TTL_SECS
+    pub async fn failed_recently(&self, name: &str) -> bool {
+        let result: redis::RedisResult<bool> = redis::cmd("EXISTS").arg(key("failed", name)).query_async(&mut self.connection.clone()).await;
+        result.unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(key("lock", "conditions"), "jupiter:lock:conditions");
+        assert_eq!(rate_key("auth", "10.0.0.5", 60, 6_000), "jupiter:rate:auth:10.0.0.5:100");
+        assert_eq!(rate_key("auth", "10.0.0.5", 60, 6_059), rate_key("auth", "10.0.0.5", 60, 6_000), "same window");
+        assert_ne!(rate_key("auth", "10.0.0.5", 60, 6_060), rate_key("auth", "10.0.0.5", 60, 6_000));
+    }
+}
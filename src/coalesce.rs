@@ -0,0 +1,94 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+use crate::metrics::{self, Counter};
+
+// Single-flight request coalescing. Callers asking for a key while a fetch for it is under way
+// wait for that fetch and share its result instead of starting their own, so ten clients
+// hitting a cold cache at once cost one upstream request. A key is only in flight while its
+// fetch runs; the next caller after it starts a new one. If the caller running a fetch goes
+// away (a dropped request), one of the waiting callers runs its own fetch instead.
+
+static COALESCED: Lazy<Arc<Counter>> = Lazy::new(|| metrics::counter(
+    "jupiter_coalesced_requests_total", "Requests that shared a fetch already under way instead of starting their own, by kind (provider or conditions)", &["kind"],
+));
+
+pub struct Coalescer<T> {
+    kind: &'static str,     // Metric label
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<T>>>>,
+}
+
+impl<T: Clone> Coalescer<T> {
+    pub fn new(kind: &'static str) -> Self {
+        Self { kind, in_flight: Mutex::new(HashMap::new()) }
+    }
+
+    /// The result of `fetch`, or of the fetch for `key` already under way
+    pub async fn run<F: Future<Output = T>>(&self, key: &str, fetch: impl FnOnce() -> F) -> T {
+        let cell = self.in_flight.lock().ok().map(|mut in_flight| in_flight.entry(key.to_string()).or_default().clone());
+        let Some(cell) = cell else { return fetch().await };
+        let mut fetched = false;
+        let result = cell.get_or_init(|| {
+            fetched = true;
+            fetch()
+        }).await.clone();
+        if fetched {
+            if let Ok(mut in_flight) = self.in_flight.lock() {
+                if in_flight.get(key).is_some_and(|current| Arc::ptr_eq(current, &cell)) {
+                    in_flight.remove(key);
+                }
+            }
+        } else {
+            COALESCED.inc(&[self.kind]);
+        }
+        result
+    }
+
+    /// Keys being fetched
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.lock().map(|in_flight| in_flight.len()).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_fetches_coalesce() {
+        let coalescer = Coalescer::new("test");
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            42
+        };
+
+        let results = futures_util::future::join_all((0..10).map(|_| coalescer.run("current:10001", fetch))).await;
+        assert_eq!(results, vec![42; 10]);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(coalescer.in_flight(), 0);
+
+        // Other keys and later calls fetch again
+        let (a, b) = tokio::join!(coalescer.run("current:10001", fetch), coalescer.run("current:94105", fetch));
+        assert_eq!((a, b), (42, 42));
+        assert_eq!(fetches.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_fetch_is_retried() {
+        let coalescer = Coalescer::new("test");
+        let abandoned = tokio::time::timeout(Duration::from_millis(10), coalescer.run("forecast", || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            1
+        })).await;
+        assert!(abandoned.is_err());
+        assert_eq!(coalescer.run("forecast", || async { 2 }).await, 2);
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+}
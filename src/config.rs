@@ -1,10 +1,27 @@
 use std::env;
 use std::fmt;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::smoothing::Smoothing;
+use crate::locale::Locale;
+use crate::provider::combo_enhanced::{FieldWeights, FusionPolicy, OutlierRejection, DISTANCE_SCALE_KM};
+use crate::provider::common::LocationQuery;
+use crate::scheduler::JobsConfig;
+use crate::secret::Secret;
 
 #[derive(Debug)]
 pub enum ConfigError {
     Missing(String),
     Invalid(String),
+    Problems(Vec<ConfigProblem>),    // Everything Config::validate found wrong
+}
+
+/// One validation failure; `field` is the setting's path, e.g. COMBO_PORT or JOBS_CONFIG.jobs.backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigProblem {
+    pub field: String,
+    pub message: String,
 }
 
 impl fmt::Display for ConfigError {
@@ -12,17 +29,199 @@ impl fmt::Display for ConfigError {
         match self {
             ConfigError::Missing(var) => write!(f, "Required environment variable {} is not set", var),
             ConfigError::Invalid(msg) => write!(f, "Invalid configuration: {}", msg),
+            ConfigError::Problems(problems) => {
+                write!(f, "{} configuration problem(s):", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  {}: {}", problem.field, problem.message)?;
+                }
+                Ok(())
+            }
         }
     }
 }
 
 impl std::error::Error for ConfigError {}
 
+/// Connection and thread counts, lowered on 64-128MB single-board computers, and the shutdown
+/// drain timeout (DB_POOL_SIZE, HTTP_WORKERS, RUNTIME_THREADS, SHUTDOWN_TIMEOUT_SECS)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    pub db_pool_size: usize,              // Max connections per database pool
+    pub http_workers: Option<usize>,      // Requests handled at once per server, unlimited when unset
+    pub runtime_threads: Option<usize>,   // Worker threads of the async runtime shared by both servers, one per core when unset
+    pub shutdown_timeout: Duration,       // How long shutdown waits for requests in flight before cutting them off
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self {
+            db_pool_size: 20,
+            http_workers: None,
+            runtime_threads: None,
+            shutdown_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ResourceLimits {
+    pub fn from_env() -> Self {
+        let count = |key: &str| env::var(key).ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .filter(|n| *n > 0);
+        Self {
+            db_pool_size: count("DB_POOL_SIZE").unwrap_or(20),
+            http_workers: count("HTTP_WORKERS"),
+            runtime_threads: count("RUNTIME_THREADS"),
+            shutdown_timeout: Duration::from_secs(count("SHUTDOWN_TIMEOUT_SECS").unwrap_or(10) as u64),
+        }
+    }
+
+    /// Multi-threaded runtime sized by `runtime_threads`
+    pub fn runtime(&self) -> std::io::Result<tokio::runtime::Runtime> {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        if let Some(threads) = self.runtime_threads {
+            builder.worker_threads(threads);
+        }
+        builder.enable_all().build()
+    }
+}
+
+/// Listening ports, combo cache lifetime and provider weights
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    pub homebrew_port: u16,        // HOMEBREW_PORT
+    pub combo_port: u16,           // COMBO_PORT
+    pub cache_ttl_secs: i64,       // CACHE_TTL_SECS, lifetime of cached combo conditions
+    pub accuweather_weight: f64,   // ACCUWEATHER_WEIGHT, relative to other providers when averaging
+    pub nws_weight: f64,           // NWS_WEIGHT, 0 leaves the National Weather Service out
+    pub tomorrow_io_weight: f64,   // TOMORROW_IO_WEIGHT, only used with TOMORROW_IO_KEY
+    pub fusion_policy: FusionPolicy,   // FUSION_POLICY, e.g. "humidity=blend,wind_speed=local"
+    pub outlier_rejection: OutlierRejection,   // OUTLIER_REJECTION, e.g. "stddev:3" or "delta:temperature=10"
+    pub field_weights: FieldWeights,   // FIELD_WEIGHTS, e.g. "nws.pressure=2,accuweather.uv_index=3"
+    pub distance_scale_km: f64,    // DISTANCE_SCALE_KM, 0 turns off distance-weighted blending
+    pub smoothing: Smoothing,      // SENSOR_SMOOTHING, e.g. "kalman" or "exponential:0.3"
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            homebrew_port: 9090,
+            combo_port: 9091,
+            cache_ttl_secs: 3600,
+            accuweather_weight: 1.0,
+            nws_weight: 0.0,
+            tomorrow_io_weight: 1.0,
+            fusion_policy: FusionPolicy::default(),
+            outlier_rejection: OutlierRejection::Off,
+            field_weights: FieldWeights::default(),
+            distance_scale_km: DISTANCE_SCALE_KM,
+            smoothing: Smoothing::Off,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        fn parse<T: std::str::FromStr>(key: &str, default: T, expected: &str) -> Result<T, ConfigError> {
+            match env::var(key) {
+                Ok(value) if !value.trim().is_empty() => value.trim().parse()
+                    .map_err(|_| ConfigError::Invalid(format!("{} must be {}, got {}", key, expected, value))),
+                _ => Ok(default),
+            }
+        }
+        let defaults = Self::default();
+        Ok(Self {
+            homebrew_port: parse("HOMEBREW_PORT", defaults.homebrew_port, "a port number")?,
+            combo_port: parse("COMBO_PORT", defaults.combo_port, "a port number")?,
+            cache_ttl_secs: parse("CACHE_TTL_SECS", defaults.cache_ttl_secs, "a number of seconds")?,
+            accuweather_weight: parse("ACCUWEATHER_WEIGHT", defaults.accuweather_weight, "a number")?,
+            nws_weight: parse("NWS_WEIGHT", defaults.nws_weight, "a number")?,
+            tomorrow_io_weight: parse("TOMORROW_IO_WEIGHT", defaults.tomorrow_io_weight, "a number")?,
+            fusion_policy: match env::var("FUSION_POLICY") {
+                Ok(value) => FusionPolicy::parse(&value)
+                    .map_err(|e| ConfigError::Invalid(format!("FUSION_POLICY: {}", e)))?,
+                Err(_) => defaults.fusion_policy,
+            },
+            outlier_rejection: match env::var("OUTLIER_REJECTION") {
+                Ok(value) => value.parse()
+                    .map_err(|e| ConfigError::Invalid(format!("OUTLIER_REJECTION: {}", e)))?,
+                Err(_) => defaults.outlier_rejection,
+            },
+            field_weights: match env::var("FIELD_WEIGHTS") {
+                Ok(value) => FieldWeights::parse(&value)
+                    .map_err(|e| ConfigError::Invalid(format!("FIELD_WEIGHTS: {}", e)))?,
+                Err(_) => defaults.field_weights,
+            },
+            distance_scale_km: parse("DISTANCE_SCALE_KM", defaults.distance_scale_km, "a distance in km")?,
+            smoothing: match env::var("SENSOR_SMOOTHING") {
+                Ok(value) => value.parse()
+                    .map_err(|e| ConfigError::Invalid(format!("SENSOR_SMOOTHING: {}", e)))?,
+                Err(_) => defaults.smoothing,
+            },
+        })
+    }
+
+    /// Problems with the settings of the combo server's providers and cache, which can also be
+    /// changed while it runs (see `deploy`); `location` is LOCATION
+    pub fn provider_problems(&self, location: &str) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        let mut problem = |field: &str, message: String| problems.push(ConfigProblem { field: field.to_string(), message });
+
+        // Below a minute the AccuWeather quota runs out; past a day conditions are meaningless
+        if !(60..=86_400).contains(&self.cache_ttl_secs) {
+            problem("CACHE_TTL_SECS", format!("must be between 60 and 86400 seconds, got {}", self.cache_ttl_secs));
+        }
+        if !self.accuweather_weight.is_finite() || self.accuweather_weight <= 0.0 || self.accuweather_weight > 100.0 {
+            problem("ACCUWEATHER_WEIGHT", format!("must be greater than 0 and at most 100, got {}", self.accuweather_weight));
+        }
+        if !self.nws_weight.is_finite() || !(0.0..=100.0).contains(&self.nws_weight) {
+            problem("NWS_WEIGHT", format!("must be between 0 and 100, got {}", self.nws_weight));
+        } else if self.nws_weight > 0.0 && !matches!(LocationQuery::parse(location), Ok(LocationQuery::Coordinates { .. })) {
+            // api.weather.gov has no geocoder
+            problem("NWS_WEIGHT", format!("needs LOCATION as latitude,longitude, got {}", location));
+        }
+        if !self.tomorrow_io_weight.is_finite() || !(0.0..=100.0).contains(&self.tomorrow_io_weight) {
+            problem("TOMORROW_IO_WEIGHT", format!("must be between 0 and 100, got {}", self.tomorrow_io_weight));
+        }
+        if !self.distance_scale_km.is_finite() || self.distance_scale_km < 0.0 {
+            problem("DISTANCE_SCALE_KM", format!("must be 0 or more, got {}", self.distance_scale_km));
+        }
+        // Weights of a misspelt provider would silently never apply
+        for provider in self.field_weights.providers() {
+            if !["accuweather", "nws", "tomorrow.io", "homebrew"].contains(&provider) {
+                problem("FIELD_WEIGHTS", format!("unknown provider '{}', expected AccuWeather, NWS, Tomorrow.io or Homebrew", provider));
+            }
+        }
+        problems
+    }
+}
+
+/// Problem with a "host[:port]" database address, if any
+fn address_problem(address: &str) -> Option<String> {
+    if address.is_empty() {
+        return Some("cannot be empty".to_string());
+    }
+    if address.contains("://") || address.contains('/') {
+        return Some(format!("must be host[:port] without a scheme or path, got {}", address));
+    }
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (host, Some(port)),
+        None => (address, None),
+    };
+    if host.is_empty() || host.chars().any(|c| c.is_whitespace()) {
+        return Some(format!("has an invalid host: {}", address));
+    }
+    match port.map(|p| p.parse::<u16>()) {
+        Some(Ok(0)) | Some(Err(_)) => Some(format!("has an invalid port (1-65535): {}", address)),
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DatabaseConfig {
     pub db_name: String,
     pub username: String,
-    pub password: String,
+    pub password: Secret<String>,
     pub address: String,
 }
 
@@ -34,6 +233,7 @@ impl DatabaseConfig {
             username: env::var("HOMEBREW_PG_USER")
                 .map_err(|_| ConfigError::Missing("HOMEBREW_PG_USER".to_string()))?,
             password: env::var("HOMEBREW_PG_PASS")
+                .map(Secret::new)
                 .map_err(|_| ConfigError::Missing("HOMEBREW_PG_PASS".to_string()))?,
             address: env::var("HOMEBREW_PG_ADDRESS")
                 .unwrap_or_else(|_| "localhost:5432".to_string()),
@@ -47,6 +247,7 @@ impl DatabaseConfig {
             username: env::var("COMBO_PG_USER")
                 .map_err(|_| ConfigError::Missing("COMBO_PG_USER".to_string()))?,
             password: env::var("COMBO_PG_PASS")
+                .map(Secret::new)
                 .map_err(|_| ConfigError::Missing("COMBO_PG_PASS".to_string()))?,
             address: env::var("COMBO_PG_ADDRESS")
                 .unwrap_or_else(|_| "localhost:5432".to_string()),
@@ -56,17 +257,52 @@ impl DatabaseConfig {
 
 #[derive(Debug, Clone)]
 pub struct WeatherConfig {
-    pub accu_key: String,
-    pub zip_code: String,
+    pub accu_key: Secret<String>,
+    pub tomorrow_io_key: Option<Secret<String>>,   // TOMORROW_IO_KEY, enables Tomorrow.io and /api/nowcast
+    pub openweather_key: Option<Secret<String>>,   // OPENWEATHER_KEY, pollutant concentrations for /api/air_quality
+    pub zip_code: String,    // Any LocationQuery: US ZIP, "CA:V6B1A1", "Vancouver,CA" or "49.28,-123.12"
 }
 
 impl WeatherConfig {
     pub fn from_env() -> Result<Self, ConfigError> {
         Ok(Self {
             accu_key: env::var("ACCUWEATHERKEY")
+                .map(Secret::new)
                 .map_err(|_| ConfigError::Missing("ACCUWEATHERKEY".to_string()))?,
-            zip_code: env::var("ZIP_CODE")
-                .map_err(|_| ConfigError::Missing("ZIP_CODE".to_string()))?,
+            tomorrow_io_key: env::var("TOMORROW_IO_KEY").ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(Secret::new),
+            openweather_key: env::var("OPENWEATHER_KEY").ok()
+                .filter(|v| !v.trim().is_empty())
+                .map(Secret::new),
+            // ZIP_CODE predates non-US locations and is still accepted
+            zip_code: env::var("LOCATION").ok()
+                .filter(|v| !v.trim().is_empty())
+                .or_else(|| env::var("ZIP_CODE").ok())
+                .ok_or_else(|| ConfigError::Missing("LOCATION (or ZIP_CODE)".to_string()))?,
+        })
+    }
+}
+
+// NOAA station ids for marine data (US only)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarineConfig {
+    pub tide_station: Option<String>,
+    pub buoy_station: Option<String>,
+}
+
+impl MarineConfig {
+    pub fn from_env() -> Option<Self> {
+        let tide_station = env::var("NOAA_TIDE_STATION").ok().filter(|v| !v.is_empty());
+        let buoy_station = env::var("NOAA_BUOY_STATION").ok().filter(|v| !v.is_empty());
+        
+        if tide_station.is_none() && buoy_station.is_none() {
+            return None;
+        }
+        
+        Some(Self {
+            tide_station,
+            buoy_station,
         })
     }
 }
@@ -76,6 +312,13 @@ pub struct Config {
     pub homebrew_database: Option<DatabaseConfig>,
     pub combo_database: Option<DatabaseConfig>,
     pub weather: WeatherConfig,
+    pub marine: Option<MarineConfig>,
+    pub airfield: Option<String>,
+    pub base_path: Option<String>,
+    pub limits: ResourceLimits,
+    pub servers: ServerConfig,
+    pub jobs: JobsConfig,
+    pub locale: Locale,
 }
 
 impl Config {
@@ -90,40 +333,184 @@ impl Config {
             homebrew_database,
             combo_database,
             weather: WeatherConfig::from_env()?,
+            marine: MarineConfig::from_env(),
+            airfield: env::var("AVIATION_STATION").ok()
+                .filter(|v| !v.is_empty())
+                .map(|v| v.trim().to_uppercase()),
+            base_path: crate::base_path::from_env(),
+            limits: ResourceLimits::from_env(),
+            servers: ServerConfig::from_env()?,
+            jobs: JobsConfig::from_env()
+                .map_err(|e| ConfigError::Invalid(format!("JOBS_CONFIG could not be loaded: {}", e)))?,
+            locale: Locale::from_env().map_err(ConfigError::Invalid)?,
         })
     }
     
+    /// Checks every setting and reports all problems at once
     pub fn validate(&self) -> Result<(), ConfigError> {
-        // Validate homebrew database if present
-        if let Some(db) = &self.homebrew_database {
-            if db.address.is_empty() {
-                return Err(ConfigError::Invalid("Homebrew database address cannot be empty".to_string()));
-            }
-        }
-        
-        // Validate combo database if present
-        if let Some(db) = &self.combo_database {
-            if db.address.is_empty() {
-                return Err(ConfigError::Invalid("Combo database address cannot be empty".to_string()));
+        let mut problems = Vec::new();
+        let mut problem = |field: &str, message: String| problems.push(ConfigProblem { field: field.to_string(), message });
+
+        for (field, db) in [("HOMEBREW_PG_ADDRESS", &self.homebrew_database), ("COMBO_PG_ADDRESS", &self.combo_database)] {
+            if let Some(message) = db.as_ref().and_then(|db| address_problem(&db.address)) {
+                problem(field, message);
             }
         }
         
-        // Validate ZIP code format (basic US ZIP code validation)
-        if self.weather.zip_code.len() != 5 || !self.weather.zip_code.chars().all(|c| c.is_numeric()) {
-            return Err(ConfigError::Invalid("ZIP_CODE must be a 5-digit US ZIP code".to_string()));
+        if let Err(message) = LocationQuery::parse(&self.weather.zip_code) {
+            problem("LOCATION", message);
         }
         
         // Validate API key is not empty
         if self.weather.accu_key.is_empty() {
-            return Err(ConfigError::Invalid("ACCUWEATHERKEY cannot be empty".to_string()));
+            problem("ACCUWEATHERKEY", "cannot be empty".to_string());
+        }
+        
+        if let Err(message) = crate::archive::ArchiveConfig::from_env() {
+            problem("PAYLOAD_ARCHIVE_DAYS", message);
+        }
+        if let Err(retention_problems) = crate::retention::RetentionConfig::from_env() {
+            for (setting, message) in retention_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(statsd_problems) = crate::statsd::StatsdConfig::from_env() {
+            for (setting, message) in statsd_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(influx_problems) = crate::influx::InfluxConfig::from_env() {
+            for (setting, message) in influx_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(slo_problems) = crate::slo::SloConfig::from_env() {
+            for (setting, message) in slo_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(breaker_problems) = crate::provider::health::BreakerConfig::from_env() {
+            for (setting, message) in breaker_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(budget_problems) = crate::provider::quota::BudgetConfig::from_env() {
+            for (setting, message) in budget_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(prefetch_problems) = crate::prefetch::PrefetchConfig::from_env() {
+            for (setting, message) in prefetch_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(cache_problems) = crate::cache::CacheConfig::from_env() {
+            for (setting, message) in cache_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(notification_problems) = crate::notifications::NotificationsConfig::from_env() {
+            for (setting, message) in notification_problems {
+                problem(&setting, message);
+            }
+        }
+        if let Err(quota_problems) = crate::usage::StorageQuotas::from_env() {
+            for (setting, message) in quota_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(message) = crate::query_cache::ttl_from_env() {
+            problem("QUERY_CACHE_TTL_SECS", message);
+        }
+        for setting in ["HOMEBREW_SCHEMA_VERSION", "COMBO_SCHEMA_VERSION"] {
+            if let Err(message) = crate::migrations::target_from_env(setting) {
+                problem(setting, message);
+            }
+        }
+        if let Err(timescale_problems) = crate::timescale::TimescaleConfig::from_env() {
+            for (setting, message) in timescale_problems {
+                problem(setting, message);
+            }
+        }
+        if let Err(solar_problems) = crate::analysis::solar::SolarConfig::from_env() {
+            for (setting, message) in solar_problems {
+                problem(setting, message);
+            }
+        }
+
+        if let Some(url) = env::var("REDIS_URL").ok().filter(|url| !url.trim().is_empty()) {
+            #[cfg(feature = "redis")]
+            if let Err(e) = redis::Client::open(url.trim()) {
+                problem("REDIS_URL", e.to_string());
+            }
+            #[cfg(not(feature = "redis"))]
+            {
+                let _ = url;
+                problem("REDIS_URL", "needs a build with the redis feature (cargo build --features redis)".to_string());
+            }
+        }
+
+        if crate::storage::sqlite_path_from_env().is_some() {
+            #[cfg(not(feature = "sqlite"))]
+            problem("HOMEBREW_SQLITE_PATH", "needs a build with the sqlite feature (cargo build --features sqlite)".to_string());
+            if crate::report_chain::enabled_from_env() {
+                problem("REPORT_HASH_CHAIN", "the audit chain needs the homebrew Postgres database, not HOMEBREW_SQLITE_PATH".to_string());
+            }
+            if matches!(crate::timescale::TimescaleConfig::from_env(), Ok(Some(_))) {
+                problem("TIMESCALE_ENABLED", "hypertables need the homebrew Postgres database, not HOMEBREW_SQLITE_PATH".to_string());
+            }
+        }
+
+        // NOAA station ids are alphanumeric (CO-OPS: 7 digits, NDBC: 5 characters)
+        if let Some(marine) = &self.marine {
+            for (field, station) in [("NOAA_TIDE_STATION", &marine.tide_station), ("NOAA_BUOY_STATION", &marine.buoy_station)] {
+                if let Some(station) = station.as_ref().filter(|s| !s.chars().all(|c| c.is_ascii_alphanumeric())) {
+                    problem(field, format!("invalid NOAA station id: {}", station));
+                }
+            }
+        }
+        
+        // Airfields are identified by their 4 character ICAO code (e.g. KJFK)
+        if let Some(airfield) = &self.airfield {
+            if airfield.len() != 4 || !airfield.chars().all(|c| c.is_ascii_alphanumeric()) {
+                problem("AVIATION_STATION", format!("must be a 4 character ICAO code, got {}", airfield));
+            }
         }
         
-        Ok(())
+        if let Some(base_path) = &self.base_path {
+            if !crate::base_path::is_valid(base_path) {
+                problem("BASE_PATH", format!("may only contain letters, digits and -._~ path segments, got {}", base_path));
+            }
+        }
+
+        let servers = &self.servers;
+        for (field, port) in [("HOMEBREW_PORT", servers.homebrew_port), ("COMBO_PORT", servers.combo_port)] {
+            if port == 0 {
+                problem(field, "must be between 1 and 65535".to_string());
+            }
+        }
+        let homebrew = self.homebrew_database.is_some() || crate::storage::sqlite_path_from_env().is_some();
+        if homebrew && servers.homebrew_port == servers.combo_port {
+            problem("COMBO_PORT", format!("conflicts with HOMEBREW_PORT, both are {}", servers.combo_port));
+        }
+        for provider_problem in servers.provider_problems(&self.weather.zip_code) {
+            problem(&provider_problem.field, provider_problem.message);
+        }
+
+        for (job, message) in self.jobs.invalid_schedules() {
+            problem(&format!("JOBS_CONFIG.jobs.{}", job), message);
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Problems(problems))
+        }
     }
 }
 
-fn load_env_file() {
-    // Try to load .env file if it exists
+/// Copies `.env` entries into the environment without overriding variables already set
+pub fn load_env_file() {
     if let Ok(contents) = std::fs::read_to_string(".env") {
         for line in contents.lines() {
             if line.starts_with('#') || line.is_empty() {
@@ -153,17 +540,35 @@ mod tests {
             homebrew_database: Some(DatabaseConfig {
                 db_name: "test".to_string(),
                 username: "user".to_string(),
-                password: "pass".to_string(),
+                password: "pass".into(),
                 address: "localhost:5432".to_string(),
             }),
             combo_database: None,
             weather: WeatherConfig {
-                accu_key: "test_key".to_string(),
+                accu_key: "test_key".into(),
+                tomorrow_io_key: None,
+                openweather_key: None,
                 zip_code: "12345".to_string(),
             },
+            marine: None,
+            airfield: None,
+            base_path: None,
+            limits: ResourceLimits::default(),
+            servers: ServerConfig::default(),
+            jobs: JobsConfig::default(),
+            locale: Locale::default(),
         };
         
         assert!(config.validate().is_ok());
+
+        for location in ["CA:V6B1A1", "GB:SW1A 1AA", "Vancouver,CA", "São Paulo", "49.28,-123.12"] {
+            let config = Config { weather: WeatherConfig { accu_key: "test_key".into(), tomorrow_io_key: None, openweather_key: None, zip_code: location.to_string() }, ..config.clone() };
+            assert!(config.validate().is_ok(), "{} should be valid", location);
+        }
+        assert_eq!(LocationQuery::parse("gb:sw1a 1aa"), Ok(LocationQuery::PostalCode { country: Some("GB".to_string()), code: "SW1A 1AA".to_string() }));
+        assert_eq!(LocationQuery::parse("Vancouver, CA").unwrap().country(), Some("CA"));
+        assert!(LocationQuery::parse("91.0,10.0").is_err());
+        assert!(LocationQuery::parse("Vancouver,Canada").is_err());
     }
     
     #[test]
@@ -172,14 +577,23 @@ mod tests {
             homebrew_database: Some(DatabaseConfig {
                 db_name: "test".to_string(),
                 username: "user".to_string(),
-                password: "pass".to_string(),
+                password: "pass".into(),
                 address: "localhost:5432".to_string(),
             }),
             combo_database: None,
             weather: WeatherConfig {
-                accu_key: "test_key".to_string(),
+                accu_key: "test_key".into(),
+                tomorrow_io_key: None,
+                openweather_key: None,
                 zip_code: "123".to_string(), // Invalid ZIP
             },
+            marine: None,
+            airfield: None,
+            base_path: None,
+            limits: ResourceLimits::default(),
+            servers: ServerConfig::default(),
+            jobs: JobsConfig::default(),
+            locale: Locale::default(),
         };
         
         assert!(config.validate().is_err());
@@ -192,15 +606,64 @@ mod tests {
             combo_database: Some(DatabaseConfig {
                 db_name: "test".to_string(),
                 username: "user".to_string(),
-                password: "pass".to_string(),
+                password: "pass".into(),
                 address: "localhost:5432".to_string(),
             }),
             weather: WeatherConfig {
-                accu_key: "".to_string(), // Empty API key
+                accu_key: "".into(), // Empty API key
+                tomorrow_io_key: None,
+                openweather_key: None,
                 zip_code: "12345".to_string(),
             },
+            marine: None,
+            airfield: None,
+            base_path: None,
+            limits: ResourceLimits::default(),
+            servers: ServerConfig::default(),
+            jobs: JobsConfig::default(),
+            locale: Locale::default(),
         };
         
         assert!(config.validate().is_err());
     }
+
+    #[test]
+    fn test_problems_are_aggregated() {
+        let mut jobs = JobsConfig::default();
+        jobs.jobs.insert("backup".to_string(), "0 25 * * *".to_string());
+        let config = Config {
+            homebrew_database: Some(DatabaseConfig {
+                db_name: "test".to_string(),
+                username: "user".to_string(),
+                password: "pass".into(),
+                address: "postgres://localhost:5432".to_string(),
+            }),
+            combo_database: None,
+            weather: WeatherConfig {
+                accu_key: "".into(),
+                tomorrow_io_key: None,
+                openweather_key: None,
+                zip_code: "12345".to_string(),
+            },
+            marine: None,
+            airfield: None,
+            base_path: None,
+            limits: ResourceLimits::default(),
+            servers: ServerConfig {
+                combo_port: 9090,
+                cache_ttl_secs: 5,
+                ..ServerConfig::default()
+            },
+            jobs,
+            locale: Locale::default(),
+        };
+
+        let problems = match config.validate() {
+            Err(ConfigError::Problems(problems)) => problems,
+            other => panic!("expected aggregated problems, got {:?}", other),
+        };
+        let fields: Vec<&str> = problems.iter().map(|p| p.field.as_str()).collect();
+        assert_eq!(fields, vec!["HOMEBREW_PG_ADDRESS", "ACCUWEATHERKEY", "COMBO_PORT", "CACHE_TTL_SECS", "JOBS_CONFIG.jobs.backup"]);
+        assert_eq!(address_problem("db.local:5433"), None);
+    }
 }
\ No newline at end of file
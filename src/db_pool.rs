@@ -1,10 +1,21 @@
 use deadpool_postgres::{Config, ManagerConfig, Pool, RecyclingMethod, Runtime};
 use tokio_postgres::NoTls;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use log::{info, error, warn};
 
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::metrics::{self, Counter, Histogram};
+use crate::secret::Secret;
 use crate::ssl_config::{create_homebrew_connector, create_combo_connector};
 
+static POOL_ACQUIRE: Lazy<Arc<Histogram>> = Lazy::new(|| metrics::histogram(
+    "jupiter_db_pool_acquire_duration_seconds", "Time to take a healthy connection from a database pool", &["pool"],
+));
+
+static POOL_ERRORS: Lazy<Arc<Counter>> = Lazy::new(|| metrics::counter(
+    "jupiter_db_pool_errors_total", "Failures to take a healthy connection from a database pool", &["pool"],
+));
+
 #[derive(Clone)]
 pub struct DatabasePool {
     pool: Pool,
@@ -15,7 +26,7 @@ pub struct DatabasePool {
 pub struct DatabaseConfig {
     pub db_name: String,
     pub username: String,
-    pub password: String,
+    pub password: Secret<String>,
     pub host: String,
     pub address: String,  // Combined host:port for backward compatibility
     pub port: Option<u16>,
@@ -26,6 +37,22 @@ pub struct DatabaseConfig {
     pub use_ssl: bool,
 }
 
+/// Splits a "host[:port]" address (PG_ADDRESS settings) into host and port, defaulting to 5432.
+/// IPv6 hosts need brackets when a port is given, e.g. "[::1]:5433".
+pub fn split_address(address: &str) -> (String, u16) {
+    if let Some((host, port)) = address.rsplit_once(':') {
+        if let Ok(port) = port.parse() {
+            if host.starts_with('[') && host.ends_with(']') {
+                return (host[1..host.len() - 1].to_string(), port);
+            }
+            if !host.contains(':') {
+                return (host.to_string(), port);
+            }
+        }
+    }
+    (address.to_string(), 5432)
+}
+
 impl DatabaseConfig {
     pub fn homebrew_from_env() -> Result<Self, crate::error::JupiterError> {
         use std::env;
@@ -42,7 +69,7 @@ impl DatabaseConfig {
         Ok(DatabaseConfig {
             db_name,
             username,
-            password,
+            password: password.into(),
             host: host.clone(),
             address: host,  // For backward compatibility
             port: Some(5432),
@@ -69,7 +96,7 @@ impl DatabaseConfig {
         Ok(DatabaseConfig {
             db_name,
             username,
-            password,
+            password: password.into(),
             host: host.clone(),
             address: host,  // For backward compatibility
             port: Some(5432),
@@ -109,7 +136,7 @@ impl DatabasePool {
         let mut cfg = Config::new();
         cfg.dbname = Some(config.db_name.clone());
         cfg.user = Some(config.username.clone());
-        cfg.password = Some(config.password.clone());
+        cfg.password = Some(config.password.expose().clone());
         cfg.host = Some(config.host.clone());
         cfg.port = config.port;
         
@@ -151,6 +178,18 @@ impl DatabasePool {
     }
 
     pub async fn get_connection(&self) -> Result<deadpool_postgres::Client, String> {
+        let start = Instant::now();
+        let connection = self.acquire().await;
+        match &connection {
+            Ok(_) => POOL_ACQUIRE.observe_since(&[&self.name], start),
+            Err(_) => POOL_ERRORS.inc(&[&self.name]),
+        }
+        connection
+    }
+
+    async fn acquire(&self) -> Result<deadpool_postgres::Client, String> {
+        #[cfg(feature = "fault-injection")]
+        crate::fault::pool_acquire(&self.name).await?;
         match self.pool.get().await {
             Ok(client) => {
                 // Perform a health check
@@ -261,6 +300,22 @@ pub fn get_combo_pool() -> Option<Arc<DatabasePool>> {
     COMBO_POOL.get().map(|pool| Arc::clone(pool))
 }
 
+async fn client_from(pool: Option<Arc<DatabasePool>>) -> JupiterResult<deadpool_postgres::Client> {
+    let pool = pool.ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))
+}
+
+/// A connection from the homebrew pool, for the modules keeping their tables in that database
+pub async fn homebrew_client() -> JupiterResult<deadpool_postgres::Client> {
+    client_from(get_homebrew_pool()).await
+}
+
+/// A connection from the combo pool, for the modules keeping their tables in that database
+pub async fn combo_client() -> JupiterResult<deadpool_postgres::Client> {
+    client_from(get_combo_pool()).await
+}
+
 // Cleanup function for graceful shutdown
 pub async fn shutdown_pools() {
     info!("Shutting down database connection pools...");
@@ -11,7 +11,7 @@ mod tests {
         let config = DatabaseConfig {
             db_name: String::from("invalid_db"),
             username: String::from("invalid_user"),
-            password: String::from("invalid_pass"),
+            password: "invalid_pass".into(),
             host: String::from("invalid_host"),
             address: String::from("invalid_host"),  // For backward compatibility
             port: Some(5432),
@@ -236,4 +236,14 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_split_address() {
+        use crate::db_pool::split_address;
+        assert_eq!(split_address("localhost:5432"), ("localhost".to_string(), 5432));
+        assert_eq!(split_address("127.0.0.1:49153"), ("127.0.0.1".to_string(), 49153));
+        assert_eq!(split_address("db.internal"), ("db.internal".to_string(), 5432));
+        assert_eq!(split_address("[::1]:5433"), ("::1".to_string(), 5433));
+        assert_eq!(split_address("::1"), ("::1".to_string(), 5432));
+    }
+}
@@ -0,0 +1,306 @@
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::config::{ConfigProblem, ServerConfig};
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::combo;
+use crate::provider::combo_enhanced::FusionPolicy;
+use crate::utils::time::safe_timestamp_with_fallback;
+
+// Blue/green changes to the combo server's provider weights, fusion policy and cache TTL while
+// it runs. POST /api/admin/config with a candidate validates it against the same rules as the
+// environment at startup and answers with what would change, including providers that would be
+// added or left out, and a confirmation token. Sending the token back within
+// CONFIRMATION_TTL_SECS builds a complete new server state next to the running one and swaps it
+// in at once: requests in flight finish on the old state, later ones see only the new one.
+// A token is refused if the settings were changed by someone else since the preview.
+//
+// Changes last until the process restarts; the environment stays the source of truth at startup.
+
+const CONFIRMATION_TTL_SECS: i64 = 10 * 60;
+
+// A previewed candidate awaiting its confirmation token
+struct Pending {
+    base: Settings,       // Running settings it was previewed against
+    settings: Settings,
+    expires_at: i64,
+}
+
+static PENDING: Lazy<Mutex<HashMap<String, Pending>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Settings of the combo server that can change while it runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub cache_ttl_secs: i64,
+    pub accuweather_weight: f64,
+    pub nws_weight: f64,                        // 0 leaves the National Weather Service out
+    pub tomorrow_io_weight: f64,                // Only used with TOMORROW_IO_KEY
+    pub distance_scale_km: f64,                 // 0 turns distance weighting off
+    pub fusion_policy: BTreeMap<String, String>,    // Field -> local, regional or blend
+}
+
+impl Settings {
+    pub fn of(config: &combo::Config) -> Self {
+        Self {
+            cache_ttl_secs: config.cache_timeout.unwrap_or(0),
+            accuweather_weight: config.accuweather_weight,
+            nws_weight: config.nws_weight,
+            tomorrow_io_weight: config.tomorrow_io_weight,
+            distance_scale_km: config.distance_scale_km.unwrap_or(0.0),
+            fusion_policy: FusionPolicy::FIELDS.iter()
+                .map(|field| (field.to_string(), config.fusion_policy.preference(field).as_str().to_string()))
+                .collect(),
+        }
+    }
+
+    fn fusion_policy(&self) -> Result<FusionPolicy, String> {
+        self.fusion_policy.iter().try_fold(FusionPolicy::default(), |policy, (field, preference)| {
+            Ok(policy.with(field, preference.parse()?))
+        })
+    }
+
+    /// `config` with these settings
+    pub fn apply(&self, config: combo::Config) -> combo::Config {
+        let mut config = config
+            .with_accuweather_weight(self.accuweather_weight)
+            .with_nws_weight(self.nws_weight)
+            .with_fusion_policy(self.fusion_policy().unwrap_or_default())
+            .with_distance_scale(Some(self.distance_scale_km));
+        config.cache_timeout = Some(self.cache_ttl_secs);
+        config.tomorrow_io_weight = self.tomorrow_io_weight;
+        config
+    }
+
+    /// Problems with these settings for a server at `location` (LOCATION)
+    pub fn problems(&self, location: &str) -> Vec<ConfigProblem> {
+        let servers = ServerConfig {
+            cache_ttl_secs: self.cache_ttl_secs,
+            accuweather_weight: self.accuweather_weight,
+            nws_weight: self.nws_weight,
+            tomorrow_io_weight: self.tomorrow_io_weight,
+            distance_scale_km: self.distance_scale_km,
+            ..ServerConfig::default()
+        };
+        servers.provider_problems(location)
+    }
+}
+
+/// Providers of current conditions and forecasts a configuration averages
+pub fn providers(config: &combo::Config) -> Vec<String> {
+    let mut providers = Vec::new();
+    if config.accu_client.is_some() && config.accuweather_weight > 0.0 {
+        providers.push("accuweather".to_string());
+    }
+    if config.nws.is_some() {
+        providers.push("nws".to_string());
+    }
+    if config.tomorrow_io.is_some() && config.tomorrow_io_weight > 0.0 {
+        providers.push("tomorrow_io".to_string());
+    }
+    providers
+}
+
+/// A proposed change; settings left out keep their running values
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_ttl_secs: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accuweather_weight: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nws_weight: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tomorrow_io_weight: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance_scale_km: Option<f64>,
+    /// Overrides of the default policy, as in FUSION_POLICY ("humidity=blend,wind_speed=local")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fusion_policy: Option<String>,
+}
+
+impl Candidate {
+    /// The running settings with this candidate's changes
+    pub fn settings(&self, running: &Settings) -> Result<Settings, Vec<ConfigProblem>> {
+        let mut settings = running.clone();
+        if let Some(policy) = &self.fusion_policy {
+            let policy = FusionPolicy::parse(policy)
+                .map_err(|message| vec![ConfigProblem { field: "FUSION_POLICY".to_string(), message }])?;
+            settings.fusion_policy = FusionPolicy::FIELDS.iter()
+                .map(|field| (field.to_string(), policy.preference(field).as_str().to_string()))
+                .collect();
+        }
+        settings.cache_ttl_secs = self.cache_ttl_secs.unwrap_or(settings.cache_ttl_secs);
+        settings.accuweather_weight = self.accuweather_weight.unwrap_or(settings.accuweather_weight);
+        settings.nws_weight = self.nws_weight.unwrap_or(settings.nws_weight);
+        settings.tomorrow_io_weight = self.tomorrow_io_weight.unwrap_or(settings.tomorrow_io_weight);
+        settings.distance_scale_km = self.distance_scale_km.unwrap_or(settings.distance_scale_km);
+        Ok(settings)
+    }
+}
+
+/// One setting that differs; fusion policy fields are named like "fusion_policy.humidity"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub setting: String,
+    pub from: Value,
+    pub to: Value,
+}
+
+fn flatten(prefix: &str, value: Value, into: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                let path = if prefix.is_empty() { name } else { format!("{}.{}", prefix, name) };
+                flatten(&path, value, into);
+            }
+        },
+        value => {
+            into.insert(prefix.to_string(), value);
+        },
+    }
+}
+
+/// Every setting that differs between `from` and `to`, by name
+pub fn diff(from: &Settings, to: &Settings) -> Vec<Change> {
+    let (mut before, mut after) = (BTreeMap::new(), BTreeMap::new());
+    flatten("", serde_json::to_value(from).unwrap_or(Value::Null), &mut before);
+    flatten("", serde_json::to_value(to).unwrap_or(Value::Null), &mut after);
+    after.into_iter()
+        .filter_map(|(setting, to)| {
+            let from = before.remove(&setting).unwrap_or(Value::Null);
+            (from != to).then_some(Change { setting, from, to })
+        })
+        .collect()
+}
+
+/// What the running server uses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Running {
+    pub settings: Settings,
+    pub providers: Vec<String>,
+}
+
+impl Running {
+    pub fn of(config: &combo::Config) -> Self {
+        Self { settings: Settings::of(config), providers: providers(config) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preview {
+    pub valid: bool,
+    pub problems: Vec<ConfigProblem>,
+    pub changes: Vec<Change>,
+    pub providers_added: Vec<String>,
+    pub providers_removed: Vec<String>,
+    /// Applies the candidate when sent back; only issued for a valid candidate that changes something
+    pub confirmation_token: Option<String>,
+    pub expires_at: Option<i64>,
+}
+
+/// Validates `candidate` against the running `config` and, if it is valid and changes
+/// something, issues a token to apply it
+pub fn preview(config: &combo::Config, candidate: &Candidate) -> JupiterResult<Preview> {
+    let running = Settings::of(config);
+    let (settings, problems) = match candidate.settings(&running) {
+        Ok(settings) => {
+            let problems = settings.problems(&config.zip_code);
+            (settings, problems)
+        },
+        Err(problems) => (running.clone(), problems),
+    };
+    let changes = if problems.is_empty() { diff(&running, &settings) } else { Vec::new() };
+    let (before, after) = (providers(config), providers(&settings.apply(config.clone())));
+
+    let mut preview = Preview {
+        valid: problems.is_empty(),
+        problems,
+        providers_added: after.iter().filter(|p| !before.contains(p)).cloned().collect(),
+        providers_removed: before.iter().filter(|p| !after.contains(p)).cloned().collect(),
+        changes,
+        confirmation_token: None,
+        expires_at: None,
+    };
+    if preview.valid && !preview.changes.is_empty() {
+        let token: String = thread_rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect();
+        let expires_at = safe_timestamp_with_fallback() + CONFIRMATION_TTL_SECS;
+        let mut pending = PENDING.lock()
+            .map_err(|e| JupiterError::LockError(format!("Pending deployments lock poisoned: {}", e)))?;
+        pending.retain(|_, pending| pending.expires_at > safe_timestamp_with_fallback());
+        pending.insert(token.clone(), Pending { base: running, settings, expires_at });
+        preview.confirmation_token = Some(token);
+        preview.expires_at = Some(expires_at);
+    }
+    Ok(preview)
+}
+
+/// Consumes a confirmation token, returning the settings it was previewed against and the
+/// settings to apply
+pub fn take_confirmation(token: &str) -> JupiterResult<(Settings, Settings)> {
+    let mut pending = PENDING.lock()
+        .map_err(|e| JupiterError::LockError(format!("Pending deployments lock poisoned: {}", e)))?;
+    match pending.remove(token) {
+        Some(pending) if pending.expires_at > safe_timestamp_with_fallback() => Ok((pending.base, pending.settings)),
+        _ => Err(JupiterError::ValidationError("Invalid or expired confirmation token".to_string())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deployed {
+    pub running: Running,
+    pub changes: Vec<Change>,
+    pub timestamp: i64,
+}
+
+impl Deployed {
+    pub fn new(running: Running, changes: Vec<Change>) -> Self {
+        Self { running, changes, timestamp: safe_timestamp_with_fallback() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running() -> Settings {
+        Settings {
+            cache_ttl_secs: 3600,
+            accuweather_weight: 1.0,
+            nws_weight: 0.0,
+            tomorrow_io_weight: 1.0,
+            distance_scale_km: 25.0,
+            fusion_policy: FusionPolicy::FIELDS.iter()
+                .map(|field| (field.to_string(), FusionPolicy::default().preference(field).as_str().to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_candidate_diff() {
+        let candidate = Candidate { cache_ttl_secs: Some(600), fusion_policy: Some("humidity=blend".to_string()), ..Default::default() };
+        let settings = candidate.settings(&running()).unwrap();
+        assert_eq!(settings.fusion_policy["temperature"], "local", "overrides apply to the default policy");
+        assert_eq!(diff(&running(), &settings), vec![
+            Change { setting: "cache_ttl_secs".to_string(), from: 3600.into(), to: 600.into() },
+            Change { setting: "fusion_policy.humidity".to_string(), from: "local".into(), to: "blend".into() },
+        ]);
+        assert!(diff(&running(), &Candidate::default().settings(&running()).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_candidate_problems() {
+        let settings = Candidate { cache_ttl_secs: Some(10), nws_weight: Some(1.0), ..Default::default() }.settings(&running()).unwrap();
+        let fields: Vec<String> = settings.problems("10001").into_iter().map(|p| p.field).collect();
+        assert_eq!(fields, vec!["CACHE_TTL_SECS", "NWS_WEIGHT"]);
+        assert!(settings.problems("40.7,-74.0").iter().all(|p| p.field != "NWS_WEIGHT"));
+
+        let problems = Candidate { fusion_policy: Some("humidity=always".to_string()), ..Default::default() }.settings(&running()).unwrap_err();
+        assert_eq!(problems[0].field, "FUSION_POLICY");
+        assert!(take_confirmation("not-a-token").is_err());
+    }
+}
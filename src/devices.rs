@@ -0,0 +1,583 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use tokio_postgres::Row;
+
+use crate::db_pool::homebrew_client;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::homebrew::WeatherReport;
+
+// Registry of known sensors, kept in the homebrew database's `devices` table and managed at
+// /api/devices. Devices listed in the JSON file named by DEVICE_REGISTRY_FILE are added to the
+// table on startup if it doesn't have them yet. Ingest bridges (rtl_433, BLE gateways, ...) use
+// the registry to decide which radio/bus identity belongs to which of our devices; readings from
+// unregistered sensors are dropped. Each registered device is issued an ingest key (see auth.rs)
+// to post reports as itself.
+//
+// {
+//   "defaults": { "reporting_interval_secs": 300 },
+//   "devices": [
+//     { "id": "garden", "name": "Garden tower", "device_type": "outdoor",
+//       "rtl433": { "model": "Acurite-Tower", "channel": "A", "id": 1234 } },
+//     { "id": "bedroom", "name": "Bedroom beacon", "device_type": "indoor", "room": "bedroom",
+//       "mac": "A4:C1:38:12:34:56", "config": { "calibration": { "temperature": -0.4 } } }
+//   ]
+// }
+//
+// `defaults` and each device's `config` are served to firmware at GET /api/devices/{id}/config.
+//
+// Devices are never deleted by unregistering them: DELETE /api/devices/{id} archives the device,
+// which keeps its reports and keys but refuses new reports and leaves it out of listings unless
+// they ask for `include_archived=true`. A paused device is still listed but its reports are
+// refused too, e.g. while a sensor is being recalibrated. PUT /api/devices/{id}/state moves a
+// device between states; `?purge=true` on the DELETE removes the registration for good.
+
+// Maintenance thresholds for battery/solar powered nodes
+const LOW_BATTERY_PERCENT: f64 = 20.0;
+const DEFAULT_LOW_BATTERY_VOLTAGE: f64 = 3.4;   // Single Li-ion cell
+const WEAK_SIGNAL_RSSI: f64 = -100.0;           // dBm
+const OFFLINE_AFTER_SECS: i64 = 2 * 3600;
+
+const DEFAULT_REPORTING_INTERVAL_SECS: u64 = 300;
+
+/// Where a device is placed, its `device_type`
+pub const PLACEMENTS: [&str; 4] = ["indoor", "outdoor", "greenhouse", "other"];
+
+fn default_device_type() -> String {
+    "other".to_string()
+}
+
+// rtl_433 reports ids/channels as numbers for some models and strings for others
+fn string_or_number<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(Option::<Value>::deserialize(deserializer)?.and_then(|v| value_to_string(&v)))
+}
+
+pub fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+pub fn normalize_mac(mac: &str) -> String {
+    mac.chars().filter(|c| c.is_ascii_hexdigit()).collect::<String>().to_uppercase()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rtl433Match {
+    pub model: String,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub channel: Option<String>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub id: Option<String>,
+}
+
+/// Where a device is in its lifecycle, kept in the `state` column
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceState {
+    #[default]
+    Active,
+    Paused,     // Registered, but its reports are refused
+    Archived,   // Reports refused, history kept, hidden from default listings
+}
+
+impl DeviceState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceState::Active => "active",
+            DeviceState::Paused => "paused",
+            DeviceState::Archived => "archived",
+        }
+    }
+
+    pub fn parse(state: &str) -> Option<Self> {
+        match state {
+            "active" => Some(DeviceState::Active),
+            "paused" => Some(DeviceState::Paused),
+            "archived" => Some(DeviceState::Archived),
+            _ => None,
+        }
+    }
+}
+
+/// Body of PUT /api/devices/{id}/state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateChange {
+    pub state: DeviceState,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_device_type")]
+    pub device_type: String, // Placement: indoor, outdoor, greenhouse or other
+    #[serde(default)]
+    pub rtl433: Option<Rtl433Match>,
+    #[serde(default)]
+    pub mac: Option<String>, // BLE address
+    #[serde(default)]
+    pub room: Option<String>, // Indoor devices in the same room are averaged together
+    #[serde(default)]
+    pub low_battery_voltage: Option<f64>, // Depends on the battery chemistry, e.g. 2.2 for 2xAA
+    #[serde(default)]
+    pub latitude: Option<f64>,  // Where the sensor is mounted, for distance-weighted blending
+    #[serde(default)]
+    pub longitude: Option<f64>,
+    #[serde(default)]
+    pub config: DeviceSettings,
+    #[serde(default)]
+    pub state: DeviceState, // Changed through PUT /api/devices/{id}/state only
+}
+
+/// Ids of devices and groups: 1-64 letters, digits, - or _
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Device {
+    /// Fails with `DeviceInactive` unless the device accepts reports
+    pub fn accepts_reports(&self) -> JupiterResult<()> {
+        match self.state {
+            DeviceState::Active => Ok(()),
+            state => Err(JupiterError::DeviceInactive(format!("device {} is {}", self.id, state.as_str()))),
+        }
+    }
+
+
+    /// Problem with a device as registered through /api/devices, if any
+    pub fn validate(&self) -> Result<(), String> {
+        if !valid_id(&self.id) {
+            return Err(format!("id must be 1-64 letters, digits, - or _, got '{}'", self.id));
+        }
+        if self.name.trim().is_empty() {
+            return Err("name cannot be empty".to_string());
+        }
+        if !PLACEMENTS.contains(&self.device_type.as_str()) {
+            return Err(format!("device_type must be one of {}, got '{}'", PLACEMENTS.join(", "), self.device_type));
+        }
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) =>
+                Err(format!("coordinates out of range: {},{}", latitude, longitude)),
+            (Some(_), None) | (None, Some(_)) => Err("latitude and longitude go together".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A registration with the ingest key just issued for it, the only time the key is shown
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceKey {
+    pub device: Device,
+    pub api_key: String,
+}
+
+/// Settings firmware polls on boot; unset values fall back to the registry defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceSettings {
+    #[serde(default)]
+    pub reporting_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub calibration: BTreeMap<String, f64>, // Report field -> offset the firmware adds to its readings
+    #[serde(default)]
+    pub units: Option<String>, // metric or imperial, for on-device displays
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceConfig {
+    pub id: String,
+    pub reporting_interval_secs: u64,
+    pub calibration: BTreeMap<String, f64>,
+    pub units: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceStatus {
+    pub id: String,
+    pub name: String,
+    pub device_type: String,
+    pub room: Option<String>,
+    pub last_seen: Option<i64>,
+    pub online: bool,
+    pub battery_voltage: Option<f64>,
+    pub battery_percent: Option<f64>,
+    pub rssi: Option<f64>,
+    pub low_battery: bool,
+    pub weak_signal: bool,
+}
+
+impl DeviceStatus {
+    /// Builds a device's status from its recent reports, newest first. Not every report
+    /// carries telemetry, so each value comes from the newest report that has it.
+    pub fn new(device: &Device, reports: &[WeatherReport], now: i64) -> Self {
+        let last_seen = reports.first().map(|r| r.timestamp);
+        let battery_voltage = reports.iter().find_map(|r| r.battery_voltage);
+        let battery_percent = reports.iter().find_map(|r| r.battery_percent);
+        let rssi = reports.iter().find_map(|r| r.rssi);
+        let low_voltage = device.low_battery_voltage.unwrap_or(DEFAULT_LOW_BATTERY_VOLTAGE);
+
+        Self {
+            id: device.id.clone(),
+            name: device.name.clone(),
+            device_type: device.device_type.clone(),
+            room: device.room.clone(),
+            last_seen,
+            online: last_seen.is_some_and(|ts| now - ts < OFFLINE_AFTER_SECS),
+            battery_voltage,
+            battery_percent,
+            rssi,
+            low_battery: battery_percent.is_some_and(|p| p < LOW_BATTERY_PERCENT)
+                || battery_voltage.is_some_and(|v| v < low_voltage),
+            weak_signal: rssi.is_some_and(|r| r < WEAK_SIGNAL_RSSI),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceRegistry {
+    #[serde(default)]
+    pub defaults: DeviceSettings,
+    pub devices: Vec<Device>,
+}
+
+impl DeviceRegistry {
+    pub fn from_file(path: &str) -> JupiterResult<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Loads the file named by DEVICE_REGISTRY_FILE, or an empty registry if unset
+    pub fn from_env() -> JupiterResult<Self> {
+        match std::env::var("DEVICE_REGISTRY_FILE") {
+            Ok(path) if !path.is_empty() => Self::from_file(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Device> {
+        self.devices.iter().find(|d| d.id == id)
+    }
+
+    /// Devices that aren't archived, what listings show by default
+    pub fn listed(&self) -> impl Iterator<Item = &Device> {
+        self.devices.iter().filter(|d| d.state != DeviceState::Archived)
+    }
+
+    /// Effective settings for a device, with per-device values overriding the defaults.
+    /// Calibration offsets are merged per field.
+    pub fn device_config(&self, id: &str) -> Option<DeviceConfig> {
+        let device = self.get(id)?;
+        let mut calibration = self.defaults.calibration.clone();
+        calibration.extend(device.config.calibration.clone());
+
+        Some(DeviceConfig {
+            id: device.id.clone(),
+            reporting_interval_secs: device.config.reporting_interval_secs
+                .or(self.defaults.reporting_interval_secs)
+                .unwrap_or(DEFAULT_REPORTING_INTERVAL_SECS),
+            calibration,
+            units: device.config.units.clone()
+                .or_else(|| self.defaults.units.clone())
+                .unwrap_or_else(|| "metric".to_string()),
+        })
+    }
+
+    /// Mean position of the outdoor devices with coordinates, which the combo server uses as
+    /// the location of the homebrew station
+    pub fn outdoor_position(&self) -> Option<(f64, f64)> {
+        let positions: Vec<(f64, f64)> = self.listed()
+            .filter(|d| d.device_type == "outdoor")
+            .filter_map(|d| d.latitude.zip(d.longitude))
+            .collect();
+        if positions.is_empty() {
+            return None;
+        }
+        let count = positions.len() as f64;
+        Some((
+            positions.iter().map(|(latitude, _)| latitude).sum::<f64>() / count,
+            positions.iter().map(|(_, longitude)| longitude).sum::<f64>() / count,
+        ))
+    }
+
+    /// Indoor devices that have been assigned to a room, archived ones aside
+    pub fn room_devices(&self) -> impl Iterator<Item = (&str, &Device)> {
+        self.listed()
+            .filter(|d| d.device_type == "indoor")
+            .filter_map(|d| d.room.as_deref().map(|room| (room, d)))
+    }
+
+    /// Finds the device registered for a BLE address, ignoring case and separators
+    pub fn find_mac(&self, mac: &str) -> Option<&Device> {
+        let mac = normalize_mac(mac);
+        self.devices.iter().find(|d| d.mac.as_deref().is_some_and(|m| normalize_mac(m) == mac))
+    }
+
+    /// Finds the device registered for an rtl_433 model/channel/id triple.
+    /// Channel and id only have to match when the registration specifies them.
+    pub fn find_rtl433(&self, model: &str, channel: Option<&str>, id: Option<&str>) -> Option<&Device> {
+        self.devices.iter().find(|device| {
+            let m = match device.rtl433 {
+                Some(ref m) => m,
+                None => return false,
+            };
+            m.model.eq_ignore_ascii_case(model)
+                && m.channel.as_deref().is_none_or(|c| Some(c) == channel)
+                && m.id.as_deref().is_none_or(|i| Some(i) == id)
+        })
+    }
+}
+
+/// The registry shared by the homebrew server, its ingest bridges and the combo server. Readers
+/// take a snapshot; changes made through /api/devices replace it as a whole.
+#[derive(Debug, Clone, Default)]
+pub struct SharedRegistry(Arc<RwLock<Arc<DeviceRegistry>>>);
+
+impl SharedRegistry {
+    pub fn new(registry: DeviceRegistry) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(registry))))
+    }
+
+    pub fn snapshot(&self) -> Arc<DeviceRegistry> {
+        match self.0.read() {
+            Ok(registry) => registry.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    pub fn replace(&self, registry: DeviceRegistry) {
+        match self.0.write() {
+            Ok(mut current) => *current = Arc::new(registry),
+            Err(poisoned) => *poisoned.into_inner() = Arc::new(registry),
+        }
+    }
+}
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.devices (
+        id varchar NOT NULL,
+        name varchar NOT NULL,
+        device_type varchar NOT NULL DEFAULT 'other',
+        latitude DOUBLE PRECISION NULL,
+        longitude DOUBLE PRECISION NULL,
+        details jsonb NOT NULL DEFAULT '{}',
+        created_at BIGINT DEFAULT 0,
+        CONSTRAINT devices_pkey PRIMARY KEY (id));"
+}
+
+pub fn sql_state_migration() -> &'static str {
+    "ALTER TABLE public.devices ADD COLUMN IF NOT EXISTS state varchar NOT NULL DEFAULT 'active';"
+}
+
+/// Bridge identities, room, battery threshold and settings, stored as `details`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Details {
+    #[serde(default)]
+    rtl433: Option<Rtl433Match>,
+    #[serde(default)]
+    mac: Option<String>,
+    #[serde(default)]
+    room: Option<String>,
+    #[serde(default)]
+    low_battery_voltage: Option<f64>,
+    #[serde(default)]
+    config: DeviceSettings,
+}
+
+fn details(device: &Device) -> JupiterResult<Value> {
+    Ok(serde_json::to_value(Details {
+        rtl433: device.rtl433.clone(),
+        mac: device.mac.clone(),
+        room: device.room.clone(),
+        low_battery_voltage: device.low_battery_voltage,
+        config: device.config.clone(),
+    })?)
+}
+
+fn from_row(row: &Row) -> JupiterResult<Device> {
+    let details: Details = serde_json::from_value(row.try_get("details")?)?;
+    let state: String = row.try_get("state")?;
+    Ok(Device {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        device_type: row.try_get("device_type")?,
+        rtl433: details.rtl433,
+        mac: details.mac,
+        room: details.room,
+        low_battery_voltage: details.low_battery_voltage,
+        latitude: row.try_get("latitude")?,
+        longitude: row.try_get("longitude")?,
+        config: details.config,
+        state: DeviceState::parse(&state)
+            .ok_or_else(|| JupiterError::DatabaseError(format!("unknown device state '{}'", state)))?,
+    })
+}
+
+pub async fn select_all() -> JupiterResult<Vec<Device>> {
+    let rows = homebrew_client().await?.query("SELECT * FROM devices ORDER BY id", &[]).await?;
+    rows.iter().map(from_row).collect()
+}
+
+/// Adds a device unless one with its id exists; returns whether it was added
+pub async fn insert(device: &Device, created_at: i64) -> JupiterResult<bool> {
+    let inserted = homebrew_client().await?.execute(
+        "INSERT INTO devices (id, name, device_type, latitude, longitude, details, created_at, state)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (id) DO NOTHING",
+        &[&device.id, &device.name, &device.device_type, &device.latitude, &device.longitude,
+          &details(device)?, &created_at, &device.state.as_str()],
+    ).await?;
+    Ok(inserted == 1)
+}
+
+/// Replaces everything but the id and state of a registered device; returns whether it exists
+pub async fn update(device: &Device) -> JupiterResult<bool> {
+    let updated = homebrew_client().await?.execute(
+        "UPDATE devices SET name = $2, device_type = $3, latitude = $4, longitude = $5, details = $6 WHERE id = $1",
+        &[&device.id, &device.name, &device.device_type, &device.latitude, &device.longitude, &details(device)?],
+    ).await?;
+    Ok(updated == 1)
+}
+
+/// Moves a registered device to `state`; returns whether it exists
+pub async fn set_state(id: &str, state: DeviceState) -> JupiterResult<bool> {
+    Ok(homebrew_client().await?.execute("UPDATE devices SET state = $2 WHERE id = $1", &[&id, &state.as_str()]).await? == 1)
+}
+
+/// Returns whether the device existed; its reports are kept, no longer attributed to it, and
+/// its API keys are deleted
+pub async fn delete(id: &str) -> JupiterResult<bool> {
+    Ok(homebrew_client().await?.execute("DELETE FROM devices WHERE id = $1", &[&id]).await? == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtl433_matching() {
+        let registry: DeviceRegistry = serde_json::from_str(r#"{"devices": [
+            {"id": "garden", "name": "Garden", "device_type": "outdoor", "rtl433": {"model": "Acurite-Tower", "channel": "A", "id": 1234}},
+            {"id": "garage", "name": "Garage", "rtl433": {"model": "LaCrosse-TX141THBv2", "channel": 1}}
+        ]}"#).unwrap();
+
+        assert_eq!(registry.find_rtl433("Acurite-Tower", Some("A"), Some("1234")).map(|d| d.id.as_str()), Some("garden"));
+        assert!(registry.find_rtl433("Acurite-Tower", Some("B"), Some("1234")).is_none());
+        // No id registered, so any id on channel 1 matches
+        assert_eq!(registry.find_rtl433("LaCrosse-TX141THBv2", Some("1"), Some("77")).map(|d| d.id.as_str()), Some("garage"));
+        assert_eq!(registry.get("garage").map(|d| d.device_type.as_str()), Some("other"));
+    }
+
+    #[test]
+    fn test_mac_matching() {
+        let registry: DeviceRegistry = serde_json::from_str(r#"{"devices": [
+            {"id": "bedroom", "name": "Bedroom", "device_type": "indoor", "mac": "a4:c1:38:12:34:56"}
+        ]}"#).unwrap();
+
+        assert_eq!(registry.find_mac("A4C138123456").map(|d| d.id.as_str()), Some("bedroom"));
+        assert_eq!(registry.find_mac("A4-C1-38-12-34-56").map(|d| d.id.as_str()), Some("bedroom"));
+        assert!(registry.find_mac("A4:C1:38:00:00:00").is_none());
+    }
+
+    #[test]
+    fn test_device_config() {
+        let registry: DeviceRegistry = serde_json::from_str(r#"{
+            "defaults": {"reporting_interval_secs": 600, "calibration": {"temperature": -0.5, "humidity": 2.0}},
+            "devices": [
+                {"id": "attic", "name": "Attic", "config": {"calibration": {"temperature": 0.3}, "units": "imperial"}},
+                {"id": "shed", "name": "Shed"}
+            ]
+        }"#).unwrap();
+
+        let attic = registry.device_config("attic").unwrap();
+        assert_eq!(attic.reporting_interval_secs, 600);
+        assert_eq!(attic.calibration.get("temperature"), Some(&0.3));
+        assert_eq!(attic.calibration.get("humidity"), Some(&2.0));
+        assert_eq!(attic.units, "imperial");
+        assert_eq!(registry.device_config("shed").unwrap().units, "metric");
+        assert!(registry.device_config("missing").is_none());
+    }
+
+    #[test]
+    fn test_outdoor_position() {
+        let registry: DeviceRegistry = serde_json::from_str(r#"{"devices": [
+            {"id": "backyard", "name": "Backyard", "device_type": "outdoor", "latitude": 49.25, "longitude": -123.10},
+            {"id": "front", "name": "Front yard", "device_type": "outdoor", "latitude": 49.27, "longitude": -123.12},
+            {"id": "kitchen", "name": "Kitchen", "device_type": "indoor", "latitude": 10.0, "longitude": 10.0},
+            {"id": "shed", "name": "Shed", "device_type": "outdoor"}
+        ]}"#).unwrap();
+
+        let (latitude, longitude) = registry.outdoor_position().unwrap();
+        assert!((latitude - 49.26).abs() < 1e-9);
+        assert!((longitude + 123.11).abs() < 1e-9);
+        assert!(DeviceRegistry::default().outdoor_position().is_none());
+    }
+
+    #[test]
+    fn test_device_validation() {
+        let device: Device = serde_json::from_str(
+            r#"{"id": "greenhouse-1", "name": "Tomatoes", "device_type": "greenhouse", "latitude": 49.26, "longitude": -123.11}"#
+        ).unwrap();
+        assert!(device.validate().is_ok());
+        assert!(Device { id: "bad id".to_string(), ..device.clone() }.validate().is_err());
+        assert!(Device { device_type: "attic".to_string(), ..device.clone() }.validate().is_err());
+        assert!(Device { longitude: None, ..device.clone() }.validate().is_err());
+        assert!(Device { latitude: Some(91.0), ..device.clone() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_device_state() {
+        let registry: DeviceRegistry = serde_json::from_str(r#"{"devices": [
+            {"id": "kitchen", "name": "Kitchen", "device_type": "indoor", "room": "kitchen"},
+            {"id": "old-kitchen", "name": "Old kitchen", "device_type": "indoor", "room": "kitchen", "state": "archived"},
+            {"id": "shed", "name": "Shed", "device_type": "outdoor", "state": "paused", "latitude": 49.0, "longitude": -123.0}
+        ]}"#).unwrap();
+
+        assert_eq!(registry.get("kitchen").map(|d| d.state), Some(DeviceState::Active));
+        assert_eq!(registry.listed().map(|d| d.id.as_str()).collect::<Vec<_>>(), vec!["kitchen", "shed"]);
+        assert_eq!(registry.room_devices().map(|(_, d)| d.id.as_str()).collect::<Vec<_>>(), vec!["kitchen"]);
+        assert_eq!(registry.outdoor_position(), Some((49.0, -123.0)));
+
+        assert!(registry.get("kitchen").unwrap().accepts_reports().is_ok());
+        let refused = registry.get("shed").unwrap().accepts_reports().unwrap_err();
+        assert!(matches!(refused, JupiterError::DeviceInactive(_)));
+        assert!(refused.to_string().contains("shed is paused"));
+        assert!(registry.get("old-kitchen").unwrap().accepts_reports().is_err());
+
+        for state in [DeviceState::Active, DeviceState::Paused, DeviceState::Archived] {
+            assert_eq!(DeviceState::parse(state.as_str()), Some(state));
+        }
+        assert!(serde_json::from_str::<StateChange>(r#"{"state": "deleted"}"#).is_err());
+    }
+
+    #[test]
+    fn test_device_status() {
+        let registry: DeviceRegistry = serde_json::from_str(r#"{"devices": [
+            {"id": "lora-1", "name": "Field node", "device_type": "outdoor", "low_battery_voltage": 2.2}
+        ]}"#).unwrap();
+        let device = registry.get("lora-1").unwrap();
+
+        let mut latest = WeatherReport::new();
+        latest.timestamp = 10_000;
+        latest.rssi = Some(-112.0);
+        let mut older = WeatherReport::new();
+        older.timestamp = 9_000;
+        older.battery_voltage = Some(2.4);
+        older.rssi = Some(-80.0);
+
+        let status = DeviceStatus::new(device, &[latest, older], 10_600);
+        assert!(status.online);
+        assert_eq!(status.last_seen, Some(10_000));
+        assert_eq!(status.battery_voltage, Some(2.4));
+        assert!(!status.low_battery);
+        assert!(status.weak_signal);
+
+        let status = DeviceStatus::new(device, &[], 10_600);
+        assert!(!status.online);
+        assert!(!status.low_battery && !status.weak_signal);
+    }
+}
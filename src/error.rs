@@ -15,6 +15,8 @@ pub enum JupiterError {
     RuntimeError(String),
     LockError(String),
     ServerError(String),
+    QuotaExceeded(String),
+    DeviceInactive(String),  // Reports from a paused or archived device
 }
 
 impl fmt::Display for JupiterError {
@@ -32,6 +34,8 @@ impl fmt::Display for JupiterError {
             JupiterError::RuntimeError(msg) => write!(f, "Runtime error: {}", msg),
             JupiterError::LockError(msg) => write!(f, "Lock error: {}", msg),
             JupiterError::ServerError(msg) => write!(f, "Server error: {}", msg),
+            JupiterError::QuotaExceeded(msg) => write!(f, "Storage quota exceeded: {}", msg),
+            JupiterError::DeviceInactive(msg) => write!(f, "Device not accepting reports: {}", msg),
         }
     }
 }
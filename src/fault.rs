@@ -0,0 +1,166 @@
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use std::env;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::provider::common::{
+    Alert, Forecast, HistoricalData, MarineForecast, MinutelyForecast, PollenReport, Weather, WeatherError,
+    WeatherFeature, WeatherProvider,
+};
+
+// Fault injection for chaos testing, compiled in only with the "fault-injection" feature.
+// Provider calls and database pool acquisition fail at configurable rates and are slowed by
+// configurable latencies, so tests and staging deployments can check that the combo server
+// degrades gracefully. Settings are read from the FAULT_* variables on first use and can be
+// replaced at runtime with `configure`.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultConfig {
+    pub provider_failure_rate: f64,  // FAULT_PROVIDER_FAILURE_RATE, share of provider calls that fail (0-1)
+    pub provider_latency_ms: u64,    // FAULT_PROVIDER_LATENCY_MS, added to every provider call
+    pub providers: Vec<String>,      // FAULT_PROVIDERS, comma-separated provider names; empty means all
+    pub pool_failure_rate: f64,      // FAULT_POOL_FAILURE_RATE, share of pool acquisitions that fail (0-1)
+    pub pool_latency_ms: u64,        // FAULT_POOL_LATENCY_MS, added to every pool acquisition
+}
+
+impl FaultConfig {
+    pub fn from_env() -> Self {
+        fn rate(key: &str) -> f64 {
+            match env::var(key).ok().map(|v| v.trim().parse::<f64>()) {
+                Some(Ok(rate)) if (0.0..=1.0).contains(&rate) => rate,
+                Some(_) => {
+                    log::warn!("{} must be a number between 0 and 1, ignoring it", key);
+                    0.0
+                },
+                None => 0.0,
+            }
+        }
+        fn millis(key: &str) -> u64 {
+            env::var(key).ok().and_then(|v| v.trim().parse().ok()).unwrap_or(0)
+        }
+        Self {
+            provider_failure_rate: rate("FAULT_PROVIDER_FAILURE_RATE"),
+            provider_latency_ms: millis("FAULT_PROVIDER_LATENCY_MS"),
+            providers: env::var("FAULT_PROVIDERS").unwrap_or_default()
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect(),
+            pool_failure_rate: rate("FAULT_POOL_FAILURE_RATE"),
+            pool_latency_ms: millis("FAULT_POOL_LATENCY_MS"),
+        }
+    }
+
+    fn affects(&self, provider: &str) -> bool {
+        self.providers.is_empty() || self.providers.iter().any(|name| name.eq_ignore_ascii_case(provider))
+    }
+}
+
+static FAULTS: Lazy<RwLock<FaultConfig>> = Lazy::new(|| {
+    let config = FaultConfig::from_env();
+    if config != FaultConfig::default() {
+        log::warn!("Fault injection enabled: {:?}", config);
+    }
+    RwLock::new(config)
+});
+
+/// Replaces the active fault settings; `FaultConfig::default()` turns injection off
+pub fn configure(config: FaultConfig) {
+    if let Ok(mut faults) = FAULTS.write() {
+        *faults = config;
+    }
+}
+
+pub fn current() -> FaultConfig {
+    FAULTS.read().map(|faults| faults.clone()).unwrap_or_default()
+}
+
+fn roll(rate: f64) -> bool {
+    rate > 0.0 && rand::thread_rng().gen::<f64>() < rate
+}
+
+/// Delays and possibly fails a call to `provider`
+pub async fn provider_call(provider: &str) -> Result<(), WeatherError> {
+    let faults = current();
+    if !faults.affects(provider) {
+        return Ok(());
+    }
+    if faults.provider_latency_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(faults.provider_latency_ms)).await;
+    }
+    if roll(faults.provider_failure_rate) {
+        return Err(WeatherError::NetworkError(format!("Injected fault in {}", provider)));
+    }
+    Ok(())
+}
+
+/// Delays and possibly fails taking a connection from the `pool` database pool
+pub async fn pool_acquire(pool: &str) -> Result<(), String> {
+    let faults = current();
+    if faults.pool_latency_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(faults.pool_latency_ms)).await;
+    }
+    if roll(faults.pool_failure_rate) {
+        return Err(format!("Injected fault acquiring a {} connection", pool));
+    }
+    Ok(())
+}
+
+/// Wraps a provider so every call goes through `provider_call` first
+pub struct FaultyProvider {
+    inner: Box<dyn WeatherProvider>,
+}
+
+impl FaultyProvider {
+    pub fn new(inner: Box<dyn WeatherProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for FaultyProvider {
+    async fn get_current_weather(&self, location: &str) -> Result<Weather, WeatherError> {
+        provider_call(self.inner.name()).await?;
+        self.inner.get_current_weather(location).await
+    }
+
+    async fn get_forecast(&self, location: &str, days: u8) -> Result<Forecast, WeatherError> {
+        provider_call(self.inner.name()).await?;
+        self.inner.get_forecast(location, days).await
+    }
+
+    async fn get_alerts(&self, location: &str) -> Result<Vec<Alert>, WeatherError> {
+        provider_call(self.inner.name()).await?;
+        self.inner.get_alerts(location).await
+    }
+
+    async fn get_historical(&self, location: &str, date: &str) -> Result<HistoricalData, WeatherError> {
+        provider_call(self.inner.name()).await?;
+        self.inner.get_historical(location, date).await
+    }
+
+    async fn get_pollen(&self, location: &str) -> Result<PollenReport, WeatherError> {
+        provider_call(self.inner.name()).await?;
+        self.inner.get_pollen(location).await
+    }
+
+    async fn get_marine(&self, location: &str) -> Result<MarineForecast, WeatherError> {
+        provider_call(self.inner.name()).await?;
+        self.inner.get_marine(location).await
+    }
+
+    async fn get_minutely(&self, location: &str) -> Result<MinutelyForecast, WeatherError> {
+        provider_call(self.inner.name()).await?;
+        self.inner.get_minutely(location).await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn supports_feature(&self, feature: WeatherFeature) -> bool {
+        self.inner.supports_feature(feature)
+    }
+}
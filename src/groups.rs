@@ -0,0 +1,342 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use tokio_postgres::Row;
+
+use crate::db_pool::homebrew_client;
+use crate::devices::{self, Device, DeviceRegistry, PLACEMENTS};
+use crate::error::Result as JupiterResult;
+use crate::provider::common::AlertSeverity;
+use crate::provider::homebrew::WeatherReport;
+
+// Groups of devices, kept in the homebrew database's `device_groups` table and managed at
+// /api/groups: a spot ("greenhouse", "roof"), a floor ("office-2") or a whole site ("office").
+// A group holds the devices it lists by id, every device with one of its `placements`, and the
+// devices of its subgroups, the groups naming it as their `parent`; placements alone give the
+// old indoor/outdoor split.
+//
+// {
+//   "id": "office-2", "name": "Office floor 2", "parent": "office",
+//   "devices": ["meeting-room", "open-plan"],
+//   "rules": [
+//     { "field": "co2", "above": 1200, "severity": "Moderate" },
+//     { "field": "temperature", "below": 18 }
+//   ]
+// }
+//
+// GET /api/groups/{id}/climate summarizes the latest reading of each member, and rules raise an
+// alert in the homebrew alerts while the group's mean crosses them. Archived devices are left out.
+
+/// Fields summarized for a group; wind direction doesn't average and telemetry is per device
+pub const SUMMARY_FIELDS: [&str; 8] = ["temperature", "humidity", "percipitation", "pm10", "pm25", "co2", "tvoc", "wind_speed"];
+
+fn default_severity() -> AlertSeverity {
+    AlertSeverity::Minor
+}
+
+/// Alerts while the group mean of `field` is above `above` or below `below`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupRule {
+    pub field: String,
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+    #[serde(default = "default_severity")]
+    pub severity: AlertSeverity,
+}
+
+impl GroupRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if !SUMMARY_FIELDS.contains(&self.field.as_str()) {
+            return Err(format!("rule field must be one of {}, got '{}'", SUMMARY_FIELDS.join(", "), self.field));
+        }
+        match (self.above, self.below) {
+            (None, None) => Err(format!("{} rule needs above or below", self.field)),
+            (Some(above), Some(below)) if above < below =>
+                Err(format!("{} rule would always alert: above {} is less than below {}", self.field, above, below)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Why the rule alerts for `climate`, if it does
+    pub fn check(&self, climate: &GroupClimate) -> Option<String> {
+        let mean = climate.fields.get(&self.field)?.mean;
+        let (side, limit) = match (self.above, self.below) {
+            (Some(above), _) if mean > above => ("above", above),
+            (_, Some(below)) if mean < below => ("below", below),
+            _ => return None,
+        };
+        Some(format!("{} {} averages {:.1}, {} {}", climate.name, self.field, mean, side, limit))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Group {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub devices: Vec<String>,
+    #[serde(default)]
+    pub placements: Vec<String>,
+    #[serde(default)]
+    pub rules: Vec<GroupRule>,
+}
+
+impl Group {
+    /// Problem with a group as saved through /api/groups alongside `groups`, if any
+    pub fn validate(&self, groups: &[Group], registry: &DeviceRegistry) -> Result<(), String> {
+        if !devices::valid_id(&self.id) {
+            return Err(format!("id must be 1-64 letters, digits, - or _, got '{}'", self.id));
+        }
+        if self.name.trim().is_empty() {
+            return Err("name cannot be empty".to_string());
+        }
+        if let Some(parent) = &self.parent {
+            if !groups.iter().any(|g| g.id == *parent) {
+                return Err(format!("unknown parent group '{}'", parent));
+            }
+            // Walk up from the parent; reaching this group again would make a loop
+            let mut seen = HashSet::new();
+            let mut ancestor = Some(parent.as_str());
+            while let Some(id) = ancestor {
+                if id == self.id || !seen.insert(id) {
+                    return Err(format!("group '{}' can't be inside its own subgroup", self.id));
+                }
+                ancestor = groups.iter().find(|g| g.id == id).and_then(|g| g.parent.as_deref());
+            }
+        }
+        if let Some(unknown) = self.devices.iter().find(|id| registry.get(id).is_none()) {
+            return Err(format!("unknown device '{}'", unknown));
+        }
+        if let Some(placement) = self.placements.iter().find(|p| !PLACEMENTS.contains(&p.as_str())) {
+            return Err(format!("placements must be among {}, got '{}'", PLACEMENTS.join(", "), placement));
+        }
+        self.rules.iter().try_for_each(GroupRule::validate)
+    }
+}
+
+/// Subgroups of `id`, at any depth
+pub fn descendants<'a>(groups: &'a [Group], id: &str) -> Vec<&'a Group> {
+    let mut found: Vec<&Group> = Vec::new();
+    let mut pending = vec![id.to_string()];
+    while let Some(parent) = pending.pop() {
+        for group in groups.iter().filter(|g| g.parent.as_deref() == Some(parent.as_str())) {
+            if group.id != id && !found.iter().any(|g| g.id == group.id) {
+                found.push(group);
+                pending.push(group.id.clone());
+            }
+        }
+    }
+    found
+}
+
+/// Devices of `group` and its subgroups that aren't archived, in registry order
+pub fn members<'a>(groups: &[Group], group: &Group, registry: &'a DeviceRegistry) -> Vec<&'a Device> {
+    let scope: Vec<&Group> = std::iter::once(group).chain(descendants(groups, &group.id)).collect();
+    registry.listed()
+        .filter(|device| scope.iter().any(|g| g.devices.contains(&device.id) || g.placements.contains(&device.device_type)))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSummary {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    pub devices: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupClimate {
+    pub group: String,
+    pub name: String,
+    pub devices: Vec<String>,        // Members
+    pub reporting: Vec<String>,      // Members with a recent reading
+    pub fields: BTreeMap<String, FieldSummary>,
+    pub timestamp: Option<i64>,      // Most recent reading
+}
+
+impl GroupClimate {
+    /// Summarizes the latest reading of each member that has one
+    pub fn new(group: &Group, members: &[&Device], readings: &[WeatherReport]) -> Self {
+        let fields = SUMMARY_FIELDS.iter().filter_map(|field| {
+            let values: Vec<f64> = readings.iter().filter_map(|r| r.value(field)).collect();
+            if values.is_empty() {
+                return None;
+            }
+            Some((field.to_string(), FieldSummary {
+                mean: values.iter().sum::<f64>() / values.len() as f64,
+                min: values.iter().copied().fold(f64::INFINITY, f64::min),
+                max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                devices: values.len(),
+            }))
+        }).collect();
+
+        Self {
+            group: group.id.clone(),
+            name: group.name.clone(),
+            devices: members.iter().map(|d| d.id.clone()).collect(),
+            reporting: readings.iter().filter_map(|r| r.device_id.clone()).collect(),
+            fields,
+            timestamp: readings.iter().map(|r| r.timestamp).max(),
+        }
+    }
+
+    /// Messages and severities of the rules of `group` crossed by this climate
+    pub fn breaches(&self, group: &Group) -> Vec<(String, AlertSeverity)> {
+        group.rules.iter()
+            .filter_map(|rule| rule.check(self).map(|message| (message, rule.severity.clone())))
+            .collect()
+    }
+}
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.device_groups (
+        id varchar NOT NULL,
+        name varchar NOT NULL,
+        parent varchar NULL,
+        details jsonb NOT NULL DEFAULT '{}',
+        created_at BIGINT DEFAULT 0,
+        CONSTRAINT device_groups_pkey PRIMARY KEY (id));"
+}
+
+/// Members and rules, stored as `details`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Details {
+    #[serde(default)]
+    devices: Vec<String>,
+    #[serde(default)]
+    placements: Vec<String>,
+    #[serde(default)]
+    rules: Vec<GroupRule>,
+}
+
+fn details(group: &Group) -> JupiterResult<Value> {
+    Ok(serde_json::to_value(Details {
+        devices: group.devices.clone(),
+        placements: group.placements.clone(),
+        rules: group.rules.clone(),
+    })?)
+}
+
+fn from_row(row: &Row) -> JupiterResult<Group> {
+    let details: Details = serde_json::from_value(row.try_get("details")?)?;
+    Ok(Group {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        parent: row.try_get("parent")?,
+        devices: details.devices,
+        placements: details.placements,
+        rules: details.rules,
+    })
+}
+
+pub async fn select_all() -> JupiterResult<Vec<Group>> {
+    let rows = homebrew_client().await?.query("SELECT * FROM device_groups ORDER BY id", &[]).await?;
+    rows.iter().map(from_row).collect()
+}
+
+/// Adds a group unless one with its id exists; returns whether it was added
+pub async fn insert(group: &Group, created_at: i64) -> JupiterResult<bool> {
+    let inserted = homebrew_client().await?.execute(
+        "INSERT INTO device_groups (id, name, parent, details, created_at) VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (id) DO NOTHING",
+        &[&group.id, &group.name, &group.parent, &details(group)?, &created_at],
+    ).await?;
+    Ok(inserted == 1)
+}
+
+/// Replaces everything but the id of a group; returns whether it exists
+pub async fn update(group: &Group) -> JupiterResult<bool> {
+    let updated = homebrew_client().await?.execute(
+        "UPDATE device_groups SET name = $2, parent = $3, details = $4 WHERE id = $1",
+        &[&group.id, &group.name, &group.parent, &details(group)?],
+    ).await?;
+    Ok(updated == 1)
+}
+
+/// Returns whether the group existed; its devices are left alone
+pub async fn delete(id: &str) -> JupiterResult<bool> {
+    Ok(homebrew_client().await?.execute("DELETE FROM device_groups WHERE id = $1", &[&id]).await? == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> DeviceRegistry {
+        serde_json::from_str(r#"{"devices": [
+            {"id": "meeting-room", "name": "Meeting room", "device_type": "indoor"},
+            {"id": "open-plan", "name": "Open plan", "device_type": "indoor"},
+            {"id": "lobby", "name": "Lobby", "device_type": "indoor", "state": "archived"},
+            {"id": "roof", "name": "Roof", "device_type": "outdoor"}
+        ]}"#).unwrap()
+    }
+
+    fn groups() -> Vec<Group> {
+        serde_json::from_str(r#"[
+            {"id": "office", "name": "Office", "devices": ["lobby"]},
+            {"id": "office-2", "name": "Office floor 2", "parent": "office", "devices": ["meeting-room", "open-plan"],
+             "rules": [{"field": "co2", "above": 1200, "severity": "Moderate"}, {"field": "temperature", "below": 18}]},
+            {"id": "outside", "name": "Outside", "placements": ["outdoor"]}
+        ]"#).unwrap()
+    }
+
+    #[test]
+    fn test_group_members() {
+        let (registry, groups) = (registry(), groups());
+        let ids = |group: &Group| members(&groups, group, &registry).iter().map(|d| d.id.clone()).collect::<Vec<_>>();
+
+        // The site takes in its floor; the archived lobby sensor is left out
+        assert_eq!(ids(&groups[0]), vec!["meeting-room", "open-plan"]);
+        assert_eq!(ids(&groups[2]), vec!["roof"]);
+        assert_eq!(descendants(&groups, "office").len(), 1);
+        assert!(descendants(&groups, "office-2").is_empty());
+    }
+
+    #[test]
+    fn test_group_validation() {
+        let (registry, groups) = (registry(), groups());
+        assert!(groups[1].validate(&groups, &registry).is_ok());
+
+        let office = Group { parent: Some("office-2".to_string()), ..groups[0].clone() };
+        assert!(office.validate(&groups, &registry).unwrap_err().contains("own subgroup"));
+        assert!(Group { parent: Some("basement".to_string()), ..groups[0].clone() }.validate(&groups, &registry).is_err());
+        assert!(Group { devices: vec!["attic".to_string()], ..groups[0].clone() }.validate(&groups, &registry).is_err());
+        assert!(Group { placements: vec!["roof".to_string()], ..groups[0].clone() }.validate(&groups, &registry).is_err());
+
+        let rule = GroupRule { field: "co2".to_string(), above: None, below: None, severity: AlertSeverity::Minor };
+        assert!(rule.validate().is_err());
+        assert!(GroupRule { field: "rssi".to_string(), above: Some(0.0), ..rule.clone() }.validate().is_err());
+        assert!(GroupRule { above: Some(10.0), below: Some(20.0), ..rule }.validate().is_err());
+    }
+
+    #[test]
+    fn test_group_climate() {
+        let (registry, groups) = (registry(), groups());
+        let floor = &groups[1];
+        let reading = |device: &str, temperature: f64, co2: f64, timestamp: i64| {
+            let mut report = WeatherReport::new();
+            report.device_id = Some(device.to_string());
+            report.temperature = Some(temperature);
+            report.co2 = Some(co2);
+            report.timestamp = timestamp;
+            report
+        };
+        let readings = [reading("meeting-room", 17.0, 1500.0, 100), reading("open-plan", 18.0, 1100.0, 160)];
+        let climate = GroupClimate::new(floor, &members(&groups, floor, &registry), &readings);
+
+        let co2 = &climate.fields["co2"];
+        assert_eq!((co2.mean, co2.min, co2.max, co2.devices), (1300.0, 1100.0, 1500.0, 2));
+        assert_eq!(climate.timestamp, Some(160));
+        assert!(!climate.fields.contains_key("humidity"));
+
+        let breaches = climate.breaches(floor);
+        assert_eq!(breaches.len(), 2);
+        assert_eq!(breaches[0], ("Office floor 2 co2 averages 1300.0, above 1200".to_string(), AlertSeverity::Moderate));
+        assert_eq!(breaches[1].1, AlertSeverity::Minor);
+    }
+}
@@ -0,0 +1,365 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use tonic::{Request, Response, Status};
+
+use crate::analysis::conditions::ConditionCode;
+use crate::auth::{ApiKeyAuth, AuthenticatedDevice, Denied};
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::combo::Config as ComboConfig;
+use crate::provider::combo_enhanced::ComboProvider;
+use crate::provider::common::{self, AlertSeverity, LocationQuery, WeatherError, WeatherProvider};
+use crate::provider::homebrew::{self, Config as HomebrewConfig, WeatherReport};
+use crate::units::{Pressure, Speed, Temperature};
+
+// gRPC server for internal services, defined by proto/jupiter.proto:
+//   jupiter.v1.WeatherService/GetCurrentWeather  {"location": "49.28,-123.12"}
+//   jupiter.v1.WeatherService/GetForecast        {"location": "", "days": 3}
+//   jupiter.v1.WeatherService/GetAlerts          {"location": "CA:V6B1A1"}
+//   jupiter.v1.HomebrewService/SubmitReport      {"temperature": 21.5, "humidity": 40.0, "device_id": "shed"}
+// Calls carry the key in `authorization` metadata and are checked like the HTTP route they
+// mirror, so a device's ingest key may submit reports but not read forecasts. An empty
+// location means the configured one.
+
+pub mod proto {
+    tonic::include_proto!("jupiter.v1");
+}
+
+use proto::homebrew_service_server::{HomebrewService, HomebrewServiceServer};
+use proto::weather_service_server::{WeatherService, WeatherServiceServer};
+
+const DEFAULT_FORECAST_DAYS: u32 = 5;
+const MAX_FORECAST_DAYS: u32 = 15;
+
+#[derive(Debug, Clone)]
+pub struct GrpcConfig {
+    pub listen: SocketAddr,
+}
+
+impl GrpcConfig {
+    /// Enabled by setting GRPC_LISTEN (e.g. `0.0.0.0:50051`)
+    pub fn from_env() -> JupiterResult<Option<Self>> {
+        let listen = match std::env::var("GRPC_LISTEN") {
+            Ok(listen) if !listen.is_empty() => listen,
+            _ => return Ok(None),
+        };
+        let listen = listen.parse()
+            .map_err(|_| JupiterError::ConfigurationError(format!("Invalid GRPC_LISTEN: {}", listen)))?;
+        Ok(Some(Self { listen }))
+    }
+}
+
+fn denied(denied: Denied) -> Status {
+    match denied {
+        Denied::RateLimited => Status::resource_exhausted("Too Many Requests"),
+        Denied::Unauthorized => Status::unauthenticated("Unauthorized"),
+        Denied::Forbidden => Status::permission_denied("Forbidden"),
+    }
+}
+
+/// Checks the call's key as for `method` `path` over HTTP
+async fn authorize<T>(auth: &ApiKeyAuth, request: &Request<T>, method: Method, path: &str) -> Result<Option<AuthenticatedDevice>, Status> {
+    let client_id = request.remote_addr().map(|addr| addr.to_string()).unwrap_or_default();
+    let secret = request.metadata().get("authorization").and_then(|value| value.to_str().ok());
+    auth.authorize(&client_id, secret, &method, path).await.map_err(denied)
+}
+
+fn weather_status(e: WeatherError) -> Status {
+    match e {
+        WeatherError::NotFound(_) => Status::not_found(e.to_string()),
+        WeatherError::RateLimitExceeded => Status::resource_exhausted(e.to_string()),
+        WeatherError::NetworkError(_) => Status::unavailable(e.to_string()),
+        WeatherError::InvalidApiKey | WeatherError::ConfigurationError(_) => Status::failed_precondition(e.to_string()),
+        WeatherError::ParseError(_) | WeatherError::DatabaseError(_) => Status::internal(e.to_string()),
+    }
+}
+
+fn condition_name(condition: ConditionCode) -> String {
+    serde_json::to_value(condition).ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+impl From<common::Location> for proto::Location {
+    fn from(location: common::Location) -> Self {
+        Self {
+            latitude: location.latitude,
+            longitude: location.longitude,
+            name: location.name,
+            country: location.country,
+            region: location.region,
+            postal_code: location.postal_code,
+        }
+    }
+}
+
+impl From<common::Weather> for proto::CurrentWeather {
+    fn from(weather: common::Weather) -> Self {
+        Self {
+            location: Some(weather.location.into()),
+            provider: weather.provider,
+            temperature: weather.temperature.celsius(),
+            feels_like: weather.feels_like.map(Temperature::celsius),
+            humidity: weather.humidity,
+            pressure: weather.pressure.map(Pressure::hpa),
+            wind_speed: weather.wind_speed.map(Speed::kmh),
+            wind_direction: weather.wind_direction,
+            description: weather.description,
+            condition: condition_name(weather.condition),
+            precipitation: weather.precipitation,
+            visibility: weather.visibility,
+            uv_index: weather.uv_index,
+            timestamp: weather.timestamp,
+        }
+    }
+}
+
+impl From<common::DailyForecast> for proto::DailyForecast {
+    fn from(day: common::DailyForecast) -> Self {
+        Self {
+            date: day.date,
+            temperature_min: day.temperature_min.celsius(),
+            temperature_max: day.temperature_max.celsius(),
+            humidity: day.humidity,
+            precipitation_probability: day.precipitation_probability,
+            precipitation_amount: day.precipitation_amount,
+            wind_speed: day.wind_speed.map(Speed::kmh),
+            wind_direction: day.wind_direction,
+            description: day.description,
+            condition: condition_name(day.condition),
+            sunrise: day.sunrise,
+            sunset: day.sunset,
+            solar_irradiance: day.solar_irradiance,
+        }
+    }
+}
+
+impl From<common::Forecast> for proto::Forecast {
+    fn from(forecast: common::Forecast) -> Self {
+        Self {
+            location: Some(forecast.location.into()),
+            provider: forecast.provider,
+            daily: forecast.daily.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<common::Alert> for proto::Alert {
+    fn from(alert: common::Alert) -> Self {
+        let severity = match alert.severity {
+            AlertSeverity::Minor => proto::AlertSeverity::Minor,
+            AlertSeverity::Moderate => proto::AlertSeverity::Moderate,
+            AlertSeverity::Severe => proto::AlertSeverity::Severe,
+            AlertSeverity::Extreme => proto::AlertSeverity::Extreme,
+        };
+        Self {
+            title: alert.title,
+            description: alert.description,
+            severity: severity.into(),
+            start: alert.start,
+            end: alert.end,
+            regions: alert.regions,
+        }
+    }
+}
+
+impl From<WeatherReport> for proto::Report {
+    fn from(report: WeatherReport) -> Self {
+        Self {
+            id: report.id,
+            oid: report.oid,
+            temperature: report.temperature,
+            humidity: report.humidity,
+            percipitation: report.percipitation,
+            pm10: report.pm10,
+            pm25: report.pm25,
+            co2: report.co2,
+            tvoc: report.tvoc,
+            wind_speed: report.wind_speed,
+            wind_direction: report.wind_direction,
+            battery_voltage: report.battery_voltage,
+            battery_percent: report.battery_percent,
+            rssi: report.rssi,
+            device_type: report.device_type,
+            device_id: report.device_id,
+            timestamp: report.timestamp,
+        }
+    }
+}
+
+impl proto::Report {
+    /// A new report with the submitted readings; id, oid and timestamp are the server's
+    fn into_report(self) -> WeatherReport {
+        let mut report = WeatherReport::new();
+        report.temperature = self.temperature;
+        report.humidity = self.humidity;
+        report.percipitation = self.percipitation;
+        report.pm10 = self.pm10;
+        report.pm25 = self.pm25;
+        report.co2 = self.co2;
+        report.tvoc = self.tvoc;
+        report.wind_speed = self.wind_speed;
+        report.wind_direction = self.wind_direction;
+        report.battery_voltage = self.battery_voltage;
+        report.battery_percent = self.battery_percent;
+        report.rssi = self.rssi;
+        report.device_type = self.device_type;
+        report.device_id = self.device_id;
+        report
+    }
+}
+
+pub struct Weather {
+    providers: Arc<ComboProvider>,
+    location: String,
+    auth: Arc<ApiKeyAuth>,
+}
+
+impl Weather {
+    /// The requested location, or the configured one if none was given; why it's invalid otherwise
+    fn location(&self, location: &str) -> Result<String, String> {
+        if location.trim().is_empty() {
+            return Ok(self.location.clone());
+        }
+        LocationQuery::parse(location)?;
+        Ok(location.trim().to_string())
+    }
+}
+
+#[tonic::async_trait]
+impl WeatherService for Weather {
+    async fn get_current_weather(&self, request: Request<proto::LocationRequest>) -> Result<Response<proto::CurrentWeather>, Status> {
+        authorize(&self.auth, &request, Method::GET, "/api/current").await?;
+        let location = self.location(&request.get_ref().location).map_err(Status::invalid_argument)?;
+        let weather = self.providers.get_current_weather(&location).await.map_err(weather_status)?;
+        Ok(Response::new(weather.into()))
+    }
+
+    async fn get_forecast(&self, request: Request<proto::ForecastRequest>) -> Result<Response<proto::Forecast>, Status> {
+        authorize(&self.auth, &request, Method::GET, "/api/forecast").await?;
+        let location = self.location(&request.get_ref().location).map_err(Status::invalid_argument)?;
+        let days = match request.get_ref().days {
+            0 => DEFAULT_FORECAST_DAYS,
+            days => days.min(MAX_FORECAST_DAYS),
+        };
+        let forecast = self.providers.get_forecast(&location, days as u8).await.map_err(weather_status)?;
+        Ok(Response::new(forecast.into()))
+    }
+
+    async fn get_alerts(&self, request: Request<proto::LocationRequest>) -> Result<Response<proto::AlertList>, Status> {
+        authorize(&self.auth, &request, Method::GET, "/api/alerts").await?;
+        let location = self.location(&request.get_ref().location).map_err(Status::invalid_argument)?;
+        let alerts = self.providers.get_alerts(&location).await.map_err(weather_status)?;
+        Ok(Response::new(proto::AlertList { alerts: alerts.into_iter().map(Into::into).collect() }))
+    }
+}
+
+pub struct Homebrew {
+    config: HomebrewConfig,
+    auth: Arc<ApiKeyAuth>,
+}
+
+#[tonic::async_trait]
+impl HomebrewService for Homebrew {
+    async fn submit_report(&self, request: Request<proto::Report>) -> Result<Response<proto::Report>, Status> {
+        let device = authorize(&self.auth, &request, Method::POST, "/api/weather_reports").await?;
+        let report = request.into_inner().into_report();
+        match homebrew::submit_report(&self.config, device, report).await {
+            Ok(report) => Ok(Response::new(report.into())),
+            Err((status, message)) => Err(match status {
+                StatusCode::BAD_REQUEST => Status::invalid_argument(message),
+                StatusCode::CONFLICT => Status::failed_precondition(message),
+                StatusCode::INSUFFICIENT_STORAGE => Status::resource_exhausted(message),
+                _ => Status::internal(message),
+            }),
+        }
+    }
+}
+
+/// Starts the gRPC server on its own thread, which exits once `shutdown_flag` is set and
+/// in-flight calls have finished. HomebrewService is only served with a homebrew database.
+/// Must be called from within the process runtime, which runs the calls.
+pub fn start(config: GrpcConfig, combo: &ComboConfig, shutdown_flag: Arc<AtomicBool>) -> JupiterResult<Vec<JoinHandle<()>>> {
+    let mut weather_auth = ApiKeyAuth::new(combo.apikey.clone());
+    if let Some(homebrew_config) = &combo.homebrew_config {
+        weather_auth = weather_auth.with_keys(homebrew_config.api_keys.clone());
+    }
+    let weather = Weather {
+        providers: Arc::new(combo.weather_providers()),
+        location: combo.zip_code.clone(),
+        auth: Arc::new(weather_auth),
+    };
+    let homebrew = combo.homebrew_config.clone().map(|config| Homebrew {
+        auth: Arc::new(ApiKeyAuth::new(config.apikey.clone()).with_keys(config.api_keys.clone())),
+        config,
+    });
+
+    let listener = std::net::TcpListener::bind(config.listen)?;
+    listener.set_nonblocking(true)?;
+    let runtime = tokio::runtime::Handle::current();
+    let incoming = {
+        let _guard = runtime.enter();
+        tokio::net::TcpListener::from_std(listener)?
+    };
+
+    log::info!("[grpc] Listening on {}", config.listen);
+    let server = thread::spawn(move || {
+        let router = tonic::transport::Server::builder()
+            .add_service(WeatherServiceServer::new(weather))
+            .add_optional_service(homebrew.map(HomebrewServiceServer::new));
+        let shutdown = async move {
+            while !shutdown_flag.load(Ordering::Relaxed) {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        let incoming = tonic::transport::server::TcpIncoming::from_listener(incoming, true, None);
+        match incoming {
+            Ok(incoming) => {
+                if let Err(e) = runtime.block_on(router.serve_with_incoming_shutdown(incoming, shutdown)) {
+                    log::error!("[grpc] Server error: {}", e);
+                }
+            },
+            Err(e) => log::error!("[grpc] Failed to accept connections: {}", e),
+        }
+        log::info!("[grpc] Server stopped");
+    });
+    Ok(vec![server])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let alert = common::Alert {
+            title: "Wind Advisory".to_string(),
+            description: "Gusts to 90 km/h".to_string(),
+            severity: AlertSeverity::Severe,
+            start: "2024-01-10T06:00:00Z".to_string(),
+            end: None,
+            regions: vec!["Metro Vancouver".to_string()],
+        };
+        let alert = proto::Alert::from(alert);
+        assert_eq!(alert.severity(), proto::AlertSeverity::Severe);
+        assert_eq!(alert.end, None);
+
+        let report = proto::Report { temperature: Some(21.5), device_type: "outdoor".to_string(), id: 7, ..Default::default() };
+        let report = report.into_report();
+        assert_eq!(report.temperature, Some(21.5));
+        assert_eq!(report.id, 0);
+        assert_eq!(proto::Report::from(report).device_type, "outdoor");
+        assert_eq!(condition_name(ConditionCode::PartlyCloudy), "partly-cloudy");
+    }
+
+    #[test]
+    fn test_denied() {
+        assert_eq!(denied(Denied::Forbidden).code(), tonic::Code::PermissionDenied);
+        assert_eq!(denied(Denied::Unauthorized).code(), tonic::Code::Unauthenticated);
+        assert_eq!(weather_status(WeatherError::RateLimitExceeded).code(), tonic::Code::ResourceExhausted);
+    }
+}
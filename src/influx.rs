@@ -0,0 +1,275 @@
+use once_cell::sync::OnceCell;
+use std::collections::VecDeque;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::provider::accuweather::CurrentCondition;
+use crate::provider::homebrew::WeatherReport;
+use crate::secret::Secret;
+
+// Every stored homebrew report and every set of current conditions the combo server caches,
+// written to InfluxDB in line protocol for Grafana dashboards:
+//
+//   INFLUX_URL          server, e.g. http://influx:8086; unset, nothing is written
+//   INFLUX_BUCKET       bucket to write to (InfluxDB 1.8: database/retention_policy)
+//   INFLUX_ORG          organization, if the server needs one
+//   INFLUX_TOKEN        API token (InfluxDB 1.8: username:password)
+//   INFLUX_FLUSH_SECS   how often queued points are written, 10 by default
+//
+// Reports become `weather_report` points tagged with device_type and device_id, with one field
+// per reading; AccuWeather conditions become `current_conditions` points tagged with source and
+// location. Points are queued and written in batches on a thread of their own, so a slow or
+// unreachable server never holds up ingest: failed batches are retried on the next flush, and
+// past MAX_PENDING points the oldest are dropped.
+
+/// Points kept while the server can't be reached
+const MAX_PENDING: usize = 100_000;
+
+/// Points written per request
+const MAX_BATCH: usize = 5_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfluxConfig {
+    pub url: String,
+    pub bucket: String,
+    pub org: Option<String>,
+    pub token: Option<Secret<String>>,
+    pub flush_secs: u64,
+}
+
+impl InfluxConfig {
+    /// Reads the settings through `var`, None without INFLUX_URL; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let Some(url) = var("INFLUX_URL") else {
+            return Ok(None);
+        };
+        let mut problems = Vec::new();
+
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            problems.push(("INFLUX_URL", format!("must be an http:// or https:// URL, got {}", url)));
+        }
+        let bucket = var("INFLUX_BUCKET").unwrap_or_default();
+        if bucket.is_empty() {
+            problems.push(("INFLUX_BUCKET", "is required with INFLUX_URL".to_string()));
+        }
+        let flush_secs = match var("INFLUX_FLUSH_SECS").map(|secs| secs.parse::<u64>()) {
+            None => 10,
+            Some(Ok(secs)) if secs > 0 => secs,
+            Some(_) => {
+                problems.push(("INFLUX_FLUSH_SECS", "must be a number of seconds above 0".to_string()));
+                10
+            },
+        };
+
+        if problems.is_empty() {
+            Ok(Some(Self {
+                url: url.trim_end_matches('/').to_string(),
+                bucket,
+                org: var("INFLUX_ORG"),
+                token: var("INFLUX_TOKEN").map(Secret::new),
+                flush_secs,
+            }))
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Option<Self>, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+}
+
+/// Measurement names and tag keys and values: commas, equals signs and spaces escaped, newlines
+/// (which end a point) replaced
+fn escape_key(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars().map(|c| if c == '\n' { ' ' } else { c }) {
+        if matches!(c, ',' | '=' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn string_field(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// `measurement,tags fields timestamp`, or None without any field
+fn point(measurement: &str, tags: &[(&str, &str)], fields: &[(&str, String)], timestamp: i64) -> Option<String> {
+    if fields.is_empty() {
+        return None;
+    }
+    let mut line = escape_key(measurement);
+    for (key, value) in tags.iter().filter(|(_, value)| !value.is_empty()) {
+        line.push_str(&format!(",{}={}", escape_key(key), escape_key(value)));
+    }
+    let fields: Vec<String> = fields.iter().map(|(key, value)| format!("{}={}", escape_key(key), value)).collect();
+    Some(format!("{} {} {}", line, fields.join(","), timestamp))
+}
+
+pub fn report_point(report: &WeatherReport) -> Option<String> {
+    const FIELDS: [&str; 12] = ["temperature", "humidity", "percipitation", "pm10", "pm25", "co2", "tvoc",
+        "wind_speed", "wind_direction", "battery_voltage", "battery_percent", "rssi"];
+    let fields: Vec<(&str, String)> = FIELDS.iter()
+        .filter_map(|field| report.value(field).filter(|v| v.is_finite()).map(|value| (*field, format!("{:?}", value))))
+        .collect();
+    let tags = [("device_type", report.device_type.as_str()), ("device_id", report.device_id.as_deref().unwrap_or(""))];
+    point("weather_report", &tags, &fields, report.timestamp)
+}
+
+pub fn conditions_point(location: &str, current: &CurrentCondition) -> Option<String> {
+    let mut fields = vec![
+        ("weather_text", string_field(&current.weather_text)),
+        ("is_day_time", current.is_day_time.to_string()),
+        ("has_precipitation", current.has_precipitation.to_string()),
+    ];
+    if current.temperature.metric.value.is_finite() {
+        fields.insert(0, ("temperature", format!("{:?}", current.temperature.metric.value)));
+    }
+    point("current_conditions", &[("source", "accuweather"), ("location", location)], &fields, current.epoch_time)
+}
+
+struct Exporter {
+    pending: Mutex<VecDeque<String>>,
+}
+
+static EXPORTER: OnceCell<Exporter> = OnceCell::new();
+
+fn queue(line: Option<String>) {
+    let (Some(exporter), Some(line)) = (EXPORTER.get(), line) else { return };
+    if let Ok(mut pending) = exporter.pending.lock() {
+        if pending.len() >= MAX_PENDING {
+            pending.pop_front();
+        }
+        pending.push_back(line);
+    }
+}
+
+/// Queues a stored report, when INFLUX_URL is set
+pub fn report(report: &WeatherReport) {
+    queue(report_point(report));
+}
+
+/// Queues current conditions the combo server cached for `location`, when INFLUX_URL is set
+pub fn conditions(location: &str, current: &CurrentCondition) {
+    queue(conditions_point(location, current));
+}
+
+/// Writes queued points until none are left or a write fails
+fn flush(config: &InfluxConfig, client: &reqwest::blocking::Client, exporter: &Exporter) {
+    loop {
+        let batch: Vec<String> = match exporter.pending.lock() {
+            Ok(mut pending) => {
+                let size = pending.len().min(MAX_BATCH);
+                pending.drain(..size).collect()
+            },
+            Err(_) => return,
+        };
+        if batch.is_empty() {
+            return;
+        }
+
+        let mut query = vec![("bucket", config.bucket.as_str()), ("precision", "s")];
+        if let Some(org) = &config.org {
+            query.push(("org", org.as_str()));
+        }
+        let mut request = client.post(format!("{}/api/v2/write", config.url)).query(&query).body(batch.join("\n"));
+        if let Some(token) = &config.token {
+            request = request.header(reqwest::header::AUTHORIZATION, format!("Token {}", token.expose()));
+        }
+        let result = request.send().and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            log::warn!("[influx] Failed to write {} point(s), retrying on the next flush: {}", batch.len(), e);
+            if let Ok(mut pending) = exporter.pending.lock() {
+                for line in batch.into_iter().rev() {
+                    if pending.len() < MAX_PENDING {
+                        pending.push_front(line);
+                    }
+                }
+            }
+            return;
+        }
+    }
+}
+
+/// Starts writing queued points every INFLUX_FLUSH_SECS until `shutdown_flag` is set, with a
+/// last write on the way out
+pub fn start(config: InfluxConfig, shutdown_flag: Arc<AtomicBool>) -> Option<JoinHandle<()>> {
+    if EXPORTER.set(Exporter { pending: Mutex::new(VecDeque::new()) }).is_err() {
+        return None;
+    }
+    log::info!("[influx] Writing reports and current conditions to {} bucket {}", config.url, config.bucket);
+
+    Some(std::thread::spawn(move || {
+        let Some(exporter) = EXPORTER.get() else { return };
+        let client = match reqwest::blocking::Client::builder().timeout(Duration::from_secs(30)).build() {
+            Ok(client) => client,
+            Err(e) => {
+                log::error!("[influx] Failed to create HTTP client, nothing will be written: {}", e);
+                return;
+            }
+        };
+        while !shutdown_flag.load(Ordering::Relaxed) {
+            // Sleep in short steps so shutdown isn't delayed
+            for _ in 0..config.flush_secs * 10 {
+                if shutdown_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            flush(&config, &client, exporter);
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_influx_config() {
+        let config = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            InfluxConfig::from_vars(|name| vars.get(name).cloned())
+        };
+        assert_eq!(config(&[]).unwrap(), None);
+        assert_eq!(config(&[("INFLUX_URL", "influx:8086")]).unwrap_err(), vec![
+            ("INFLUX_URL", "must be an http:// or https:// URL, got influx:8086".to_string()),
+            ("INFLUX_BUCKET", "is required with INFLUX_URL".to_string()),
+        ]);
+        let influx = config(&[("INFLUX_URL", "http://influx:8086/"), ("INFLUX_BUCKET", "weather")]).unwrap().unwrap();
+        assert_eq!((influx.url.as_str(), influx.flush_secs, influx.org), ("http://influx:8086", 10, None));
+    }
+
+    #[test]
+    fn test_points() {
+        let mut report = WeatherReport::new();
+        report.device_type = "outdoor".to_string();
+        report.device_id = Some("back yard".to_string());
+        report.timestamp = 1_700_000_000;
+        assert_eq!(report_point(&report), None, "no readings");
+
+        report.temperature = Some(21.0);
+        report.humidity = Some(40.5);
+        assert_eq!(report_point(&report).unwrap(),
+            "weather_report,device_type=outdoor,device_id=back\\ yard temperature=21.0,humidity=40.5 1700000000");
+
+        let mut current = CurrentCondition {
+            weather_text: "Mostly \"sunny\"".to_string(),
+            epoch_time: 1_700_000_000,
+            ..Default::default()
+        };
+        current.temperature.metric.value = 18.5;
+        assert_eq!(conditions_point("CA:V6B 1A1", &current).unwrap(), concat!(
+            "current_conditions,source=accuweather,location=CA:V6B\\ 1A1 ",
+            "temperature=18.5,weather_text=\"Mostly \\\"sunny\\\"\",is_day_time=false,has_precipitation=false 1700000000",
+        ));
+    }
+}
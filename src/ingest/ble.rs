@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::devices::DeviceRegistry;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::homebrew::{Config as HomebrewConfig, WeatherReport};
+
+// Decoded BLE advertisements forwarded by gateways such as Theengs Gateway or OpenMQTTGateway
+// (POST /api/ingest/ble), e.g. a Xiaomi LYWSD03MMC or SwitchBot meter:
+//   {"id":"A4:C1:38:12:34:56","model":"LYWSD03MMC","tempc":22.4,"hum":48.2,"batt":87,"rssi":-71}
+// The `id` field carries the MAC address, which is matched against the device registry.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BleAdvertisement {
+    #[serde(alias = "mac")]
+    pub id: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub tempc: Option<f64>,
+    #[serde(default)]
+    pub tempf: Option<f64>,
+    #[serde(default)]
+    pub hum: Option<f64>,
+    #[serde(default)]
+    pub pm25: Option<f64>,
+    #[serde(default)]
+    pub pm10: Option<f64>,
+    #[serde(default)]
+    pub co2: Option<f64>,
+    #[serde(default)]
+    pub batt: Option<f64>,    // percent
+    #[serde(default)]
+    pub volt: Option<f64>,
+    #[serde(default)]
+    pub rssi: Option<f64>,
+}
+
+impl BleAdvertisement {
+    pub fn to_report(&self) -> WeatherReport {
+        let mut report = WeatherReport::new();
+        report.temperature = self.tempc.or_else(|| self.tempf.map(|f| (f - 32.0) * 5.0 / 9.0));
+        report.humidity = self.hum;
+        report.pm25 = self.pm25;
+        report.pm10 = self.pm10;
+        report.co2 = self.co2;
+        report.battery_percent = self.batt;
+        report.battery_voltage = self.volt;
+        report.rssi = self.rssi;
+        report
+    }
+
+    fn has_readings(&self) -> bool {
+        self.tempc.is_some() || self.tempf.is_some() || self.hum.is_some()
+            || self.pm25.is_some() || self.pm10.is_some() || self.co2.is_some()
+    }
+}
+
+/// Accepts a single advertisement object or an array of them
+pub fn parse_payload(body: &str) -> JupiterResult<Vec<BleAdvertisement>> {
+    match serde_json::from_str::<Value>(body)? {
+        Value::Array(items) => items.into_iter()
+            .map(|item| serde_json::from_value(item).map_err(JupiterError::from))
+            .collect(),
+        item @ Value::Object(_) => Ok(vec![serde_json::from_value(item)?]),
+        _ => Err(JupiterError::ValidationError("Expected a JSON object or array".to_string())),
+    }
+}
+
+/// Stores an advertisement if its MAC is registered and it carries any readings.
+/// Returns `Ok(None)` for unknown beacons and presence-only advertisements.
+pub async fn ingest(advertisement: &BleAdvertisement, registry: &DeviceRegistry, homebrew: &HomebrewConfig) -> JupiterResult<Option<WeatherReport>> {
+    let device = match registry.find_mac(&advertisement.id) {
+        Some(device) => device,
+        None => {
+            log::debug!("[ble] Ignoring unregistered beacon {}", advertisement.id);
+            return Ok(None);
+        }
+    };
+    if !advertisement.has_readings() {
+        return Ok(None);
+    }
+
+    device.accepts_reports()?;
+
+    let mut report = advertisement.to_report();
+    report.device_type = device.device_type.clone();
+    report.device_id = Some(device.id.clone());
+    report.save(homebrew.clone()).await?;
+    Ok(Some(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gateway_payloads() {
+        let single = parse_payload(r#"{"id":"A4:C1:38:12:34:56","model":"LYWSD03MMC","tempc":22.4,"hum":48.2,"batt":87,"rssi":-71}"#).unwrap();
+        assert_eq!(single.len(), 1);
+        let report = single[0].to_report();
+        assert_eq!(report.temperature, Some(22.4));
+        assert_eq!(report.humidity, Some(48.2));
+        assert_eq!(report.battery_percent, Some(87.0));
+        assert_eq!(report.rssi, Some(-71.0));
+
+        let batch = parse_payload(r#"[
+            {"id":"C0:11:22:33:44:55","model":"WoSensorTH","tempf":68.0,"hum":40},
+            {"id":"C0:11:22:33:44:66","rssi":-90}
+        ]"#).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert!((batch[0].to_report().temperature.unwrap() - 20.0).abs() < 1e-9);
+        assert!(!batch[1].has_readings());
+
+        assert!(parse_payload("42").is_err());
+    }
+}
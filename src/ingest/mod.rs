@@ -0,0 +1,17 @@
+// Collectors that pull readings from local hardware and store them as homebrew WeatherReports.
+pub mod modbus;
+pub mod rtl433;
+pub mod ble;
+
+use serde::{Deserialize, Serialize};
+use crate::provider::homebrew::WeatherReport;
+
+/// Body of the HTTP ingest endpoints: the reports stored, how many readings matched no registered
+/// device, and why readings from paused or archived devices were refused
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestSummary {
+    pub stored: Vec<WeatherReport>,
+    pub ignored: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected: Vec<String>,
+}
@@ -0,0 +1,373 @@
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::homebrew::{Config as HomebrewConfig, WeatherReport};
+
+// Polls industrial sensors (anemometers, PT100 transmitters, ...) over Modbus TCP and stores
+// each poll as a homebrew WeatherReport. Devices and register maps are read from the JSON
+// file named by MODBUS_CONFIG, e.g.
+//
+// {
+//   "devices": [{
+//     "name": "roof-anemometer", "host": "192.168.1.50", "unit_id": 1,
+//     "interval_secs": 30, "device_type": "outdoor",
+//     "registers": [
+//       { "address": 0, "kind": "input", "scale": 0.1, "field": "wind_speed" },
+//       { "address": 1, "kind": "input", "field": "wind_direction" }
+//     ]
+//   }]
+// }
+
+const MODBUS_TCP_PORT: u16 = 502;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterKind {
+    #[default]
+    Holding,
+    Input,
+}
+
+impl RegisterKind {
+    fn function_code(&self) -> u8 {
+        match self {
+            RegisterKind::Holding => 0x03,
+            RegisterKind::Input => 0x04,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterDataType {
+    #[default]
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+}
+
+impl RegisterDataType {
+    fn register_count(&self) -> u16 {
+        match self {
+            RegisterDataType::U16 | RegisterDataType::I16 => 1,
+            _ => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportField {
+    Temperature,
+    Humidity,
+    Percipitation,
+    Pm10,
+    Pm25,
+    Co2,
+    Tvoc,
+    WindSpeed,
+    WindDirection,
+    BatteryVoltage,
+    BatteryPercent,
+}
+
+impl ReportField {
+    pub fn apply(&self, report: &mut WeatherReport, value: f64) {
+        match self {
+            ReportField::Temperature => report.temperature = Some(value),
+            ReportField::Humidity => report.humidity = Some(value),
+            ReportField::Percipitation => report.percipitation = Some(value),
+            ReportField::Pm10 => report.pm10 = Some(value),
+            ReportField::Pm25 => report.pm25 = Some(value),
+            ReportField::Co2 => report.co2 = Some(value),
+            ReportField::Tvoc => report.tvoc = Some(value),
+            ReportField::WindSpeed => report.wind_speed = Some(value),
+            ReportField::WindDirection => report.wind_direction = Some(value),
+            ReportField::BatteryVoltage => report.battery_voltage = Some(value),
+            ReportField::BatteryPercent => report.battery_percent = Some(value),
+        }
+    }
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterMapping {
+    pub address: u16,
+    #[serde(default)]
+    pub kind: RegisterKind,
+    #[serde(default)]
+    pub data_type: RegisterDataType,
+    #[serde(default)]
+    pub swap_words: bool,         // Low word first for 32-bit values
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub offset: f64,              // value = raw * scale + offset
+    pub field: ReportField,
+}
+
+impl RegisterMapping {
+    pub fn decode(&self, registers: &[u16]) -> Option<f64> {
+        let raw = match self.data_type {
+            RegisterDataType::U16 => *registers.first()? as f64,
+            RegisterDataType::I16 => *registers.first()? as i16 as f64,
+            _ => {
+                let (hi, lo) = match (registers.first()?, registers.get(1)?) {
+                    (a, b) if self.swap_words => (*b, *a),
+                    (a, b) => (*a, *b),
+                };
+                let bits = ((hi as u32) << 16) | lo as u32;
+                match self.data_type {
+                    RegisterDataType::U32 => bits as f64,
+                    RegisterDataType::I32 => bits as i32 as f64,
+                    _ => f32::from_bits(bits) as f64,
+                }
+            }
+        };
+        Some(raw * self.scale + self.offset)
+    }
+}
+
+fn default_port() -> u16 {
+    MODBUS_TCP_PORT
+}
+
+fn default_unit_id() -> u8 {
+    1
+}
+
+fn default_interval() -> u64 {
+    60
+}
+
+fn default_device_type() -> String {
+    "outdoor".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModbusDevice {
+    pub name: String,
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_unit_id")]
+    pub unit_id: u8,
+    #[serde(default = "default_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_device_type")]
+    pub device_type: String,
+    pub registers: Vec<RegisterMapping>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModbusConfig {
+    pub devices: Vec<ModbusDevice>,
+}
+
+impl ModbusConfig {
+    pub fn from_file(path: &str) -> JupiterResult<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let config: ModbusConfig = serde_json::from_str(&contents)?;
+        for device in &config.devices {
+            if device.registers.is_empty() {
+                return Err(JupiterError::ConfigurationError(format!("Modbus device {} has no registers", device.name)));
+            }
+            if device.interval_secs == 0 {
+                return Err(JupiterError::ConfigurationError(format!("Modbus device {} has a zero polling interval", device.name)));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Loads the file named by MODBUS_CONFIG, if set
+    pub fn from_env() -> JupiterResult<Option<Self>> {
+        match std::env::var("MODBUS_CONFIG") {
+            Ok(path) if !path.is_empty() => Ok(Some(Self::from_file(&path)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Minimal blocking Modbus TCP client supporting register reads (function codes 0x03/0x04)
+pub struct ModbusTcpClient {
+    stream: TcpStream,
+    unit_id: u8,
+    transaction_id: u16,
+}
+
+impl ModbusTcpClient {
+    pub fn connect(host: &str, port: u16, unit_id: u8, timeout: Duration) -> JupiterResult<Self> {
+        let addr = (host, port).to_socket_addrs()?
+            .next()
+            .ok_or_else(|| JupiterError::ConnectionError(format!("Could not resolve {}:{}", host, port)))?;
+        let stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        Ok(Self {
+            stream,
+            unit_id,
+            transaction_id: 0,
+        })
+    }
+
+    pub fn read_registers(&mut self, kind: RegisterKind, address: u16, count: u16) -> JupiterResult<Vec<u16>> {
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        let request = encode_read_request(self.transaction_id, self.unit_id, kind, address, count);
+        self.stream.write_all(&request)?;
+
+        // MBAP header: transaction id, protocol id, remaining length
+        let mut header = [0u8; 6];
+        self.stream.read_exact(&mut header)?;
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if !(2..=256).contains(&length) {
+            return Err(JupiterError::ConnectionError(format!("Invalid Modbus frame length {}", length)));
+        }
+        let mut body = vec![0u8; length];
+        self.stream.read_exact(&mut body)?;
+
+        let transaction_id = u16::from_be_bytes([header[0], header[1]]);
+        if transaction_id != self.transaction_id {
+            return Err(JupiterError::ConnectionError(format!(
+                "Modbus transaction mismatch: expected {}, got {}", self.transaction_id, transaction_id
+            )));
+        }
+
+        decode_read_response(kind, count, &body)
+    }
+}
+
+pub fn encode_read_request(transaction_id: u16, unit_id: u8, kind: RegisterKind, address: u16, count: u16) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(12);
+    frame.extend_from_slice(&transaction_id.to_be_bytes());
+    frame.extend_from_slice(&0u16.to_be_bytes());      // Protocol id (Modbus)
+    frame.extend_from_slice(&6u16.to_be_bytes());      // Unit id + PDU
+    frame.push(unit_id);
+    frame.push(kind.function_code());
+    frame.extend_from_slice(&address.to_be_bytes());
+    frame.extend_from_slice(&count.to_be_bytes());
+    frame
+}
+
+/// Decodes the unit id + PDU part of a read response
+pub fn decode_read_response(kind: RegisterKind, count: u16, body: &[u8]) -> JupiterResult<Vec<u16>> {
+    let function_code = *body.get(1)
+        .ok_or_else(|| JupiterError::ConnectionError("Truncated Modbus response".to_string()))?;
+
+    if function_code == kind.function_code() | 0x80 {
+        let exception = body.get(2).copied().unwrap_or(0);
+        return Err(JupiterError::ConnectionError(format!("Modbus exception code {}", exception)));
+    }
+    if function_code != kind.function_code() {
+        return Err(JupiterError::ConnectionError(format!("Unexpected Modbus function code {}", function_code)));
+    }
+
+    let byte_count = *body.get(2)
+        .ok_or_else(|| JupiterError::ConnectionError("Truncated Modbus response".to_string()))? as usize;
+    let data = body.get(3..3 + byte_count)
+        .filter(|d| d.len() == count as usize * 2)
+        .ok_or_else(|| JupiterError::ConnectionError("Modbus register count mismatch".to_string()))?;
+
+    Ok(data.chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect())
+}
+
+/// Reads every mapped register of a device into a new WeatherReport
+pub fn poll_device(device: &ModbusDevice) -> JupiterResult<WeatherReport> {
+    let mut client = ModbusTcpClient::connect(&device.host, device.port, device.unit_id, Duration::from_secs(5))?;
+    let mut report = WeatherReport::new();
+    report.device_type = device.device_type.clone();
+
+    for mapping in &device.registers {
+        let registers = client.read_registers(mapping.kind, mapping.address, mapping.data_type.register_count())?;
+        match mapping.decode(&registers) {
+            Some(value) => mapping.field.apply(&mut report, value),
+            None => log::warn!("[modbus] {}: could not decode register {}", device.name, mapping.address),
+        }
+    }
+
+    Ok(report)
+}
+
+/// Starts one polling thread per configured device. Threads exit once `shutdown_flag` is set.
+/// Must be called from within the process runtime, which stores the reports.
+pub fn start_pollers(config: ModbusConfig, homebrew: HomebrewConfig, shutdown_flag: Arc<AtomicBool>) -> Vec<JoinHandle<()>> {
+    let runtime = tokio::runtime::Handle::current();
+    config.devices.into_iter().map(|device| {
+        let homebrew = homebrew.clone();
+        let shutdown_flag = shutdown_flag.clone();
+        let runtime = runtime.clone();
+
+        thread::spawn(move || {
+            log::info!("[modbus] Polling {} at {}:{} every {}s", device.name, device.host, device.port, device.interval_secs);
+            let interval = Duration::from_secs(device.interval_secs);
+
+            while !shutdown_flag.load(Ordering::Relaxed) {
+                let started = Instant::now();
+                match poll_device(&device) {
+                    Ok(report) => {
+                        if let Err(e) = runtime.block_on(report.save(homebrew.clone())) {
+                            log::error!("[modbus] Failed to save report from {}: {}", device.name, e);
+                        }
+                    },
+                    Err(e) => log::warn!("[modbus] Failed to poll {}: {}", device.name, e),
+                }
+
+                // Sleep in short steps so shutdown isn't delayed by long intervals
+                while started.elapsed() < interval && !shutdown_flag.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+
+            log::info!("[modbus] Poller for {} stopped", device.name);
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_read_request() {
+        let frame = encode_read_request(1, 17, RegisterKind::Holding, 0x006B, 3);
+        assert_eq!(frame, vec![0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]);
+    }
+
+    #[test]
+    fn test_decode_read_response() {
+        let body = [0x11, 0x04, 0x04, 0x00, 0xFA, 0xFF, 0x38];
+        assert_eq!(decode_read_response(RegisterKind::Input, 2, &body).unwrap(), vec![250, 0xFF38]);
+
+        let exception = [0x11, 0x84, 0x02];
+        assert!(decode_read_response(RegisterKind::Input, 2, &exception).is_err());
+        assert!(decode_read_response(RegisterKind::Input, 3, &body).is_err());
+    }
+
+    #[test]
+    fn test_register_decoding() {
+        let pt100: RegisterMapping = serde_json::from_str(r#"{"address": 0, "data_type": "i16", "scale": 0.1, "field": "temperature"}"#).unwrap();
+        assert_eq!(pt100.decode(&[0xFF38]), Some(-20.0));
+
+        let float: RegisterMapping = serde_json::from_str(r#"{"address": 2, "data_type": "f32", "swap_words": true, "field": "wind_speed"}"#).unwrap();
+        // 12.5 = 0x41480000, low word first
+        assert_eq!(float.decode(&[0x0000, 0x4148]), Some(12.5));
+        assert_eq!(float.decode(&[0x0000]), None);
+
+        let mut report = WeatherReport::new();
+        float.field.apply(&mut report, 12.5);
+        assert_eq!(report.wind_speed, Some(12.5));
+    }
+}
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::BufRead;
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::devices::{value_to_string, DeviceRegistry};
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::homebrew::{Config as HomebrewConfig, WeatherReport};
+
+// Sink for rtl_433 (https://github.com/merbanan/rtl_433) JSON output. Each event is one JSON
+// object per line, e.g.
+//   {"time":"2024-01-05 07:12:44","model":"Acurite-Tower","id":1234,"channel":"A",
+//    "battery_ok":1,"temperature_C":-2.3,"humidity":87}
+// Events are matched against the device registry by model/channel/id and stored as homebrew
+// WeatherReports. Accepted over HTTP (POST /api/ingest/rtl433), stdin (`rtl_433 -F json | jupiter`
+// with RTL433_STDIN=true) or UDP (`rtl_433 -F syslog:host:port` with RTL433_UDP_LISTEN).
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rtl433Event {
+    pub model: String,
+    #[serde(default)]
+    pub id: Option<Value>,
+    #[serde(default)]
+    pub channel: Option<Value>,
+    #[serde(rename = "temperature_C", default)]
+    pub temperature_c: Option<f64>,
+    #[serde(rename = "temperature_F", default)]
+    pub temperature_f: Option<f64>,
+    #[serde(default)]
+    pub humidity: Option<f64>,
+    #[serde(default)]
+    pub rain_mm: Option<f64>,     // Running total kept by the sensor
+    #[serde(default)]
+    pub rain_in: Option<f64>,
+    #[serde(default)]
+    pub wind_avg_m_s: Option<f64>,
+    #[serde(default)]
+    pub wind_avg_km_h: Option<f64>,
+    #[serde(default)]
+    pub wind_avg_mi_h: Option<f64>,
+    #[serde(default)]
+    pub wind_dir_deg: Option<f64>,
+    #[serde(rename = "pm2_5_ug_m3", default)]
+    pub pm25: Option<f64>,
+    #[serde(rename = "pm10_ug_m3", default)]
+    pub pm10: Option<f64>,
+    #[serde(rename = "co2_ppm", default)]
+    pub co2: Option<f64>,
+    #[serde(default)]
+    pub battery_ok: Option<f64>,  // 0-1, newer rtl_433 versions report a fractional level
+    #[serde(rename = "battery_mV", default)]
+    pub battery_mv: Option<f64>,
+    #[serde(default)]
+    pub rssi: Option<f64>,        // Only present when rtl_433 runs with `-M level`
+}
+
+impl Rtl433Event {
+    pub fn parse(line: &str) -> JupiterResult<Self> {
+        // syslog output prefixes the JSON with an RFC 5424 header
+        let start = line.find('{')
+            .ok_or_else(|| JupiterError::ValidationError("No JSON object in rtl_433 event".to_string()))?;
+        Ok(serde_json::from_str(&line[start..])?)
+    }
+
+    pub fn channel(&self) -> Option<String> {
+        self.channel.as_ref().and_then(value_to_string)
+    }
+
+    pub fn sensor_id(&self) -> Option<String> {
+        self.id.as_ref().and_then(value_to_string)
+    }
+
+    /// Converts the event into a report, normalising units to those used by homebrew storage
+    pub fn to_report(&self) -> WeatherReport {
+        let mut report = WeatherReport::new();
+        report.temperature = self.temperature_c
+            .or_else(|| self.temperature_f.map(|f| (f - 32.0) * 5.0 / 9.0));
+        report.humidity = self.humidity;
+        report.percipitation = self.rain_mm.or_else(|| self.rain_in.map(|i| i * 25.4));
+        report.wind_speed = self.wind_avg_m_s
+            .or_else(|| self.wind_avg_km_h.map(|k| k / 3.6))
+            .or_else(|| self.wind_avg_mi_h.map(|m| m * 0.44704));
+        report.wind_direction = self.wind_dir_deg;
+        report.pm25 = self.pm25;
+        report.pm10 = self.pm10;
+        report.co2 = self.co2;
+        report.battery_percent = self.battery_ok.map(|b| (b * 100.0).clamp(0.0, 100.0));
+        report.battery_voltage = self.battery_mv.map(|mv| mv / 1000.0);
+        report.rssi = self.rssi;
+        report
+    }
+}
+
+/// Parses one rtl_433 line and stores it if the sensor is registered.
+/// Returns `Ok(None)` for sensors that aren't registered (e.g. a neighbour's).
+pub async fn ingest_line(line: &str, registry: &DeviceRegistry, homebrew: &HomebrewConfig) -> JupiterResult<Option<WeatherReport>> {
+    let event = Rtl433Event::parse(line)?;
+    let channel = event.channel();
+    let sensor_id = event.sensor_id();
+
+    let device = match registry.find_rtl433(&event.model, channel.as_deref(), sensor_id.as_deref()) {
+        Some(device) => device,
+        None => {
+            log::debug!("[rtl433] Ignoring unregistered sensor {} channel {:?} id {:?}", event.model, channel, sensor_id);
+            return Ok(None);
+        }
+    };
+
+    device.accepts_reports()?;
+
+    let mut report = event.to_report();
+    report.device_type = device.device_type.clone();
+    report.device_id = Some(device.id.clone());
+    report.save(homebrew.clone()).await?;
+    Ok(Some(report))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Rtl433ListenerConfig {
+    pub udp_listen: Option<String>,
+    pub stdin: bool,
+}
+
+impl Rtl433ListenerConfig {
+    pub fn from_env() -> Self {
+        Self {
+            udp_listen: std::env::var("RTL433_UDP_LISTEN").ok().filter(|a| !a.is_empty()),
+            stdin: std::env::var("RTL433_STDIN").map(|v| v == "true" || v == "1").unwrap_or(false),
+        }
+    }
+}
+
+fn log_ingest_result(result: JupiterResult<Option<WeatherReport>>) {
+    match result {
+        Ok(Some(report)) => log::debug!("[rtl433] Stored reading from {}", report.device_id.unwrap_or_default()),
+        Ok(None) => {},
+        Err(e) => log::warn!("[rtl433] Failed to ingest event: {}", e),
+    }
+}
+
+/// Starts the configured stdin/UDP listeners, matching events against the homebrew server's
+/// current device registry. The UDP listener exits once `shutdown_flag` is set; the stdin reader
+/// runs until stdin is closed. Must be called from within the process runtime, which stores the
+/// readings.
+pub fn start_listeners(config: Rtl433ListenerConfig, homebrew: HomebrewConfig, shutdown_flag: Arc<AtomicBool>) -> JupiterResult<Vec<JoinHandle<()>>> {
+    let mut handles = Vec::new();
+    let runtime = tokio::runtime::Handle::current();
+
+    if let Some(addr) = config.udp_listen {
+        let socket = UdpSocket::bind(&addr)?;
+        socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+        let homebrew = homebrew.clone();
+        let runtime = runtime.clone();
+        log::info!("[rtl433] Listening for UDP events on {}", addr);
+
+        handles.push(thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while !shutdown_flag.load(Ordering::Relaxed) {
+                match socket.recv_from(&mut buf) {
+                    Ok((len, _)) => {
+                        let line = String::from_utf8_lossy(&buf[..len]);
+                        log_ingest_result(runtime.block_on(ingest_line(&line, &homebrew.devices.snapshot(), &homebrew)));
+                    },
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {},
+                    Err(e) => log::error!("[rtl433] UDP receive error: {}", e),
+                }
+            }
+            log::info!("[rtl433] UDP listener stopped");
+        }));
+    }
+
+    if config.stdin {
+        log::info!("[rtl433] Reading events from stdin");
+        // Not joined on shutdown: the thread blocks on stdin and dies with the process
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line {
+                    Ok(line) if line.trim().is_empty() => {},
+                    Ok(line) => log_ingest_result(runtime.block_on(ingest_line(&line, &homebrew.devices.snapshot(), &homebrew))),
+                    Err(e) => {
+                        log::error!("[rtl433] Failed to read stdin: {}", e);
+                        break;
+                    }
+                }
+            }
+            log::info!("[rtl433] stdin closed");
+        });
+    }
+
+    Ok(handles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rtl433_event() {
+        let event = Rtl433Event::parse(r#"{"time":"2024-01-05 07:12:44","model":"Acurite-Tower","id":1234,"channel":"A","battery_ok":1,"temperature_C":-2.3,"humidity":87}"#).unwrap();
+        assert_eq!(event.model, "Acurite-Tower");
+        assert_eq!(event.sensor_id().as_deref(), Some("1234"));
+        assert_eq!(event.channel().as_deref(), Some("A"));
+
+        let report = event.to_report();
+        assert_eq!(report.temperature, Some(-2.3));
+        assert_eq!(report.humidity, Some(87.0));
+        assert_eq!(report.battery_percent, Some(100.0));
+    }
+
+    #[test]
+    fn test_parse_syslog_wrapped_event() {
+        let line = r#"<13>1 2024-01-05T07:12:44Z host rtl_433 - - - {"model":"Fineoffset-WH24","id":140,"temperature_F":50.0,"wind_avg_km_h":18.0,"wind_dir_deg":270,"rain_in":0.1}"#;
+        let report = Rtl433Event::parse(line).unwrap().to_report();
+        assert!((report.temperature.unwrap() - 10.0).abs() < 1e-9);
+        assert!((report.wind_speed.unwrap() - 5.0).abs() < 1e-9);
+        assert_eq!(report.wind_direction, Some(270.0));
+        assert!((report.percipitation.unwrap() - 2.54).abs() < 1e-9);
+
+        assert!(Rtl433Event::parse("not json").is_err());
+    }
+}
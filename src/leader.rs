@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use deadpool_postgres::ClientWrapper;
+
+use crate::db_pool::{get_combo_pool, get_homebrew_pool};
+
+// Leader election for background jobs when several instances share a database
+// (`"leader_election": true` in JOBS_CONFIG). Each job is led by one instance at a time: the one
+// holding the job's Postgres advisory lock on its election session, a connection taken out of
+// the combo pool (or the homebrew pool without a combo database) and kept for as long as it
+// works. Other instances skip the job's scheduled runs. Advisory locks belong to the session, so
+// when the leader exits, crashes or loses its connection Postgres releases them and another
+// instance takes the job over within CHECK_SECS. A leader that can't reach the database gives
+// up all its jobs at once rather than risk running them alongside the new leader.
+//
+// Manual runs (POST /api/admin/jobs/{name}/run) run on the instance asked, leader or not.
+
+/// How often followers try to take over jobs, and leaders check their session
+pub const CHECK_SECS: u64 = 10;
+
+// First key of every job's two-key advisory lock; the second is hashtext(job name)
+const LOCK_NAMESPACE: i32 = 0x6a75_7069;
+
+/// Which jobs this instance leads. Without election every instance leads every job.
+#[derive(Debug, Clone, Default)]
+pub struct Leadership {
+    elected: Option<Arc<RwLock<HashSet<String>>>>,
+}
+
+impl Leadership {
+    /// Every job runs here
+    pub fn everything() -> Self {
+        Self::default()
+    }
+
+    pub fn leads(&self, job: &str) -> bool {
+        match &self.elected {
+            None => true,
+            Some(led) => led.read().map(|led| led.contains(job)).unwrap_or(false),
+        }
+    }
+
+    fn set(&self, job: &str, leading: bool) {
+        if let Some(Ok(mut led)) = self.elected.as_ref().map(|led| led.write()) {
+            if leading {
+                led.insert(job.to_string());
+            } else {
+                led.remove(job);
+            }
+        }
+    }
+
+    fn clear(&self) {
+        if let Some(Ok(mut led)) = self.elected.as_ref().map(|led| led.write()) {
+            led.clear();
+        }
+    }
+
+    /// Starts electing a leader for each of `jobs` on the current runtime. Leadership ends, and
+    /// the locks are released, once `shutdown_flag` is set.
+    pub fn elect(jobs: Vec<String>, shutdown_flag: Arc<AtomicBool>) -> Self {
+        let leadership = Self { elected: Some(Arc::new(RwLock::new(HashSet::new()))) };
+        let election = leadership.clone();
+        tokio::spawn(async move {
+            let mut session: Option<ClientWrapper> = None;
+            while !shutdown_flag.load(Ordering::Relaxed) {
+                if session.is_none() {
+                    session = connect().await;
+                }
+                if let Some(client) = &session {
+                    if let Err(e) = election.campaign(client, &jobs).await {
+                        log::warn!("[leader] Lost the election session, giving up every job: {}", e);
+                        election.clear();
+                        session = None;
+                    }
+                }
+                // Sleep in short steps so shutdown isn't delayed
+                for _ in 0..CHECK_SECS * 10 {
+                    if shutdown_flag.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+            election.clear();
+            // Dropping the session closes it, which releases its locks
+            drop(session);
+        });
+        leadership
+    }
+
+    /// Confirms the session still works and tries to take over the jobs nobody leads
+    async fn campaign(&self, client: &ClientWrapper, jobs: &[String]) -> Result<(), tokio_postgres::Error> {
+        client.query_one("SELECT 1", &[]).await?;
+        for job in jobs.iter().filter(|job| !self.leads(job)) {
+            let acquired: bool = client.query_one("SELECT pg_try_advisory_lock($1, hashtext($2))", &[&LOCK_NAMESPACE, job]).await?
+                .get(0);
+            if acquired {
+                log::info!("[leader] This instance now leads job {}", job);
+                self.set(job, true);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A connection of its own, out of the pool so nothing else runs on it and its locks last
+/// exactly as long as it does
+async fn connect() -> Option<ClientWrapper> {
+    let Some(pool) = get_combo_pool().or_else(get_homebrew_pool) else {
+        log::warn!("[leader] No database to hold the election in, no jobs will run on a schedule");
+        return None;
+    };
+    match pool.get_connection_with_retry(3).await {
+        Ok(client) => Some(deadpool::managed::Object::take(client)),
+        Err(e) => {
+            log::warn!("[leader] Failed to open the election session: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leads() {
+        assert!(Leadership::everything().leads("rollup"));
+
+        let elected = Leadership { elected: Some(Arc::new(RwLock::new(HashSet::new()))) };
+        assert!(!elected.leads("rollup"), "followers until elected");
+        elected.set("rollup", true);
+        assert!(elected.leads("rollup"));
+        assert!(!elected.leads("chain_verify"));
+        elected.clear();
+        assert!(!elected.leads("rollup"));
+    }
+}
@@ -6,9 +6,60 @@ pub mod ssl_config;
 pub mod input_sanitizer;
 pub mod db_pool;
 pub mod pool_monitor;
+pub mod metrics;
+pub mod statsd;
+pub mod influx;
+pub mod slo;
+pub mod live;
 pub mod config;
 pub mod error;
 pub mod utils;
+pub mod units;
+pub mod analysis;
+pub mod response_format;
+pub mod response_cache;
+pub mod cache;
+pub mod coalesce;
+pub mod prefetch;
+pub mod query_cache;
+pub mod ingest;
+pub mod devices;
+pub mod groups;
+pub mod alert_rules;
+pub mod snmp;
+pub mod report_chain;
+pub mod rollup;
+pub mod summary;
+pub mod retention;
+pub mod usage;
+pub mod admin;
+pub mod widget;
+pub mod triggers;
+pub mod notifications;
+pub mod base_path;
+pub mod server;
+pub mod scheduler;
+pub mod leader;
+pub mod locale;
+pub mod logging;
+pub mod secret;
+pub mod archive;
+pub mod backfill;
+pub mod deploy;
+pub mod storage;
+pub mod timescale;
+pub mod migrations;
+pub mod schema;
+pub mod openapi;
+pub mod client;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+#[cfg(feature = "redis")]
+pub mod cluster;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(windows)]
+pub mod service;
 
 #[cfg(test)]
 mod tests;
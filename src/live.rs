@@ -0,0 +1,111 @@
+use axum::extract::ws::{Message, WebSocket};
+use once_cell::sync::Lazy;
+use serde_json::json;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::provider::combo::CachedWeatherData;
+use crate::provider::homebrew::WeatherReport;
+use crate::schema::Schema;
+
+// Live updates pushed to dashboards over the combo server's GET /api/stream WebSocket instead of
+// polling. Both servers run in one process, so stored homebrew reports and refreshed combo
+// conditions are published on a process-wide channel that every open stream subscribes to.
+
+/// Updates a stream may fall behind by before it skips ahead
+const CAPACITY: usize = 64;
+
+static UPDATES: Lazy<broadcast::Sender<LiveUpdate>> = Lazy::new(|| broadcast::channel(CAPACITY).0);
+
+#[derive(Debug, Clone)]
+pub enum LiveUpdate {
+    Report(WeatherReport),            // A homebrew report was stored, from any ingest path
+    Conditions(CachedWeatherData),    // The combo server refreshed its cached conditions
+}
+
+impl LiveUpdate {
+    fn kind(&self) -> &'static str {
+        match self {
+            LiveUpdate::Report(_) => "report",
+            LiveUpdate::Conditions(_) => "conditions",
+        }
+    }
+
+    fn schema(&self) -> Schema {
+        match self {
+            LiveUpdate::Report(_) => Schema::WeatherReport,
+            LiveUpdate::Conditions(_) => Schema::CachedConditions,
+        }
+    }
+
+    /// `{"type": "report", "schema": "weather_report/1", "data": {...}}`, with `data` shaped like
+    /// the response of the matching GET endpoint
+    pub fn to_json(&self) -> String {
+        let data = match self {
+            LiveUpdate::Report(report) => serde_json::to_value(report),
+            LiveUpdate::Conditions(conditions) => serde_json::to_value(conditions),
+        };
+        json!({
+            "type": self.kind(),
+            "schema": self.schema().header_value(),
+            "data": data.unwrap_or_default(),
+        }).to_string()
+    }
+}
+
+/// Sends `update` to every open stream; nothing happens without subscribers
+pub fn publish(update: LiveUpdate) {
+    let _ = UPDATES.send(update);
+}
+
+pub fn subscribe() -> broadcast::Receiver<LiveUpdate> {
+    UPDATES.subscribe()
+}
+
+/// Forwards updates to `socket` until the client goes away or the server shuts down. A client
+/// too slow to keep up misses the oldest updates rather than holding back the others.
+pub async fn forward(mut socket: WebSocket, mut shutdown: Option<broadcast::Receiver<()>>) {
+    let mut updates = subscribe();
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(update) => {
+                    if socket.send(Message::Text(update.to_json())).await.is_err() {
+                        break;
+                    }
+                },
+                Err(RecvError::Lagged(missed)) => log::warn!("Live stream fell behind, skipped {} updates", missed),
+                Err(RecvError::Closed) => break,
+            },
+            // Pings are answered by axum, anything else from the client is ignored
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {},
+            },
+            _ = async { shutdown.as_mut()?.recv().await.ok() }, if shutdown.is_some() => {
+                let _ = socket.send(Message::Close(None)).await;
+                break;
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_published_updates_reach_subscribers() {
+        let mut updates = subscribe();
+        let mut report = WeatherReport::new();
+        report.temperature = Some(18.5);
+        publish(LiveUpdate::Report(report));
+
+        let message: serde_json::Value = match updates.recv().await {
+            Ok(update) => serde_json::from_str(&update.to_json()).unwrap(),
+            Err(e) => panic!("no update: {}", e),
+        };
+        assert_eq!(message["type"], "report");
+        assert_eq!(message["schema"], "weather_report/1");
+        assert_eq!(message["data"]["temperature"], 18.5);
+    }
+}
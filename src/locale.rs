@@ -0,0 +1,226 @@
+use std::env;
+
+use crate::utils::time::civil_from_days;
+use crate::units::{Speed, Temperature};
+
+// Number, unit and date formatting for human-readable text: the spoken summary, the SVG widget
+// and `/api/value?format=text`. LOCALE takes a language-region tag ("en-US", "de-DE", "fr_CA")
+// and LOCALE_UNITS=metric|imperial overrides the units it implies. JSON, CBOR and the bare
+// `/api/value` numbers stay machine-readable and are never localised.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnitSystem {
+    Metric,
+    Imperial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateOrder {
+    DayMonthYear,
+    MonthDayYear,
+    YearMonthDay,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    pub tag: String,
+    pub decimal_separator: char,
+    pub units: UnitSystem,
+    pub date_order: DateOrder,
+    pub date_separator: char,
+}
+
+/// Metric units, '.' decimals and ISO dates, as text outputs looked before LOCALE existed
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            tag: "default".to_string(),
+            decimal_separator: '.',
+            units: UnitSystem::Metric,
+            date_order: DateOrder::YearMonthDay,
+            date_separator: '-',
+        }
+    }
+}
+
+// Languages writing decimals with a comma
+const DECIMAL_COMMA: &[&str] = &[
+    "de", "fr", "es", "it", "nl", "pt", "ru", "pl", "sv", "da", "nb", "nn", "no", "fi", "cs",
+    "sk", "tr", "el", "hu", "ro", "uk", "bg", "hr", "sl", "lt", "lv", "et", "id", "vi",
+];
+// Languages writing dates year first
+const YEAR_FIRST: &[&str] = &["zh", "ja", "ko", "hu", "lt", "sv"];
+// Languages separating day.month.year with dots
+const DOTTED_DATES: &[&str] = &["de", "ru", "pl", "cs", "sk", "fi", "nb", "nn", "no", "da", "tr", "uk", "bg", "ro", "hr", "sl", "lv", "et"];
+// Regions still using imperial units for weather
+const IMPERIAL_REGIONS: &[&str] = &["US", "LR", "MM"];
+
+impl Locale {
+    /// Locale for a language or language-region tag, e.g. "en-GB", "de", "fr_CA"
+    pub fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.trim().split('.').next().unwrap_or("");
+        let mut parts = tag.split(['-', '_']);
+        let language = parts.next()?.to_lowercase();
+        let region = parts.next().map(|r| r.to_uppercase());
+        if language.len() < 2 || language.len() > 3 || !language.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        if let Some(ref region) = region {
+            if region.len() != 2 || !region.chars().all(|c| c.is_ascii_alphabetic()) {
+                return None;
+            }
+        }
+
+        let language = language.as_str();
+        let region = region.as_deref();
+        let decimal_separator = if DECIMAL_COMMA.contains(&language) { ',' } else { '.' };
+        let units = match region {
+            Some(region) if IMPERIAL_REGIONS.contains(&region) => UnitSystem::Imperial,
+            _ => UnitSystem::Metric,
+        };
+        let (date_order, date_separator) = if YEAR_FIRST.contains(&language) {
+            (DateOrder::YearMonthDay, if language == "hu" { '.' } else { '-' })
+        } else if region == Some("US") {
+            (DateOrder::MonthDayYear, '/')
+        } else if DOTTED_DATES.contains(&language) {
+            (DateOrder::DayMonthYear, '.')
+        } else if language == "nl" {
+            (DateOrder::DayMonthYear, '-')
+        } else {
+            (DateOrder::DayMonthYear, '/')
+        };
+
+        Some(Self {
+            tag: match region {
+                Some(region) => format!("{}-{}", language, region),
+                None => language.to_string(),
+            },
+            decimal_separator,
+            units,
+            date_order,
+            date_separator,
+        })
+    }
+
+    /// LOCALE and LOCALE_UNITS; the default locale when LOCALE is unset
+    pub fn from_env() -> Result<Self, String> {
+        let mut locale = match env::var("LOCALE") {
+            Ok(tag) if !tag.trim().is_empty() => Self::parse(&tag)
+                .ok_or_else(|| format!("LOCALE must be a language-region tag like en-US or de-DE, got {}", tag))?,
+            _ => Self::default(),
+        };
+        match env::var("LOCALE_UNITS").map(|units| units.trim().to_lowercase()) {
+            Ok(units) if units == "metric" => locale.units = UnitSystem::Metric,
+            Ok(units) if units == "imperial" => locale.units = UnitSystem::Imperial,
+            Ok(units) if !units.is_empty() => return Err(format!("LOCALE_UNITS must be metric or imperial, got {}", units)),
+            _ => {},
+        }
+        Ok(locale)
+    }
+
+    /// `value` rounded to `decimals` places with the locale's decimal separator
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let text = format!("{:.*}", decimals, value);
+        // "-0" reads oddly in a sentence
+        let text = if text.trim_start_matches('-').chars().all(|c| c == '0' || c == '.') {
+            text.trim_start_matches('-').to_string()
+        } else {
+            text
+        };
+        if self.decimal_separator == '.' {
+            text
+        } else {
+            text.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+
+    /// Temperature in the locale's units from degrees Celsius
+    pub fn temperature(&self, celsius: f64) -> f64 {
+        match self.units {
+            UnitSystem::Metric => celsius,
+            UnitSystem::Imperial => Temperature::from_celsius(celsius).fahrenheit(),
+        }
+    }
+
+    pub fn temperature_unit(&self) -> &'static str {
+        match self.units {
+            UnitSystem::Metric => "°C",
+            UnitSystem::Imperial => "°F",
+        }
+    }
+
+    /// A report field converted from its stored unit, with the unit name to show after it
+    fn field_value(&self, field: &str, value: f64) -> (f64, usize, &'static str) {
+        let imperial = self.units == UnitSystem::Imperial;
+        match field {
+            "temperature" => (self.temperature(value), 1, self.temperature_unit()),
+            "humidity" | "battery_percent" => (value, 0, "%"),
+            "percipitation" | "precipitation" if imperial => (value / 25.4, 2, "in"),
+            "percipitation" | "precipitation" => (value, 1, "mm"),
+            "wind_speed" if imperial => (Speed::from_meters_per_second(value).mph(), 1, "mph"),
+            "wind_speed" => (Speed::from_meters_per_second(value).kmh(), 1, "km/h"),
+            "wind_direction" => (value, 0, "°"),
+            "pm10" | "pm25" => (value, 1, "µg/m³"),
+            "co2" => (value, 0, "ppm"),
+            "tvoc" => (value, 0, "ppb"),
+            "battery_voltage" => (value, 2, "V"),
+            "rssi" => (value, 0, "dBm"),
+            _ => (value, 1, ""),
+        }
+    }
+
+    /// A report field for people, e.g. "21,4 °C" or "9.8 mph"
+    pub fn format_value(&self, field: &str, value: f64) -> String {
+        let (value, decimals, unit) = self.field_value(field, value);
+        let number = self.number(value, decimals);
+        match unit {
+            "" => number,
+            "°" => format!("{}{}", number, unit),
+            // Comma-decimal languages also space the percent sign: "45 %"
+            "%" if self.decimal_separator == '.' => format!("{}{}", number, unit),
+            unit => format!("{} {}", number, unit),
+        }
+    }
+
+    /// Calendar date (UTC) of a unix timestamp in the locale's order
+    pub fn format_date(&self, timestamp: i64) -> String {
+        let (year, month, day) = civil_from_days(timestamp.div_euclid(86_400));
+        let sep = self.date_separator;
+        match self.date_order {
+            DateOrder::YearMonthDay => format!("{}{}{:02}{}{:02}", year, sep, month, sep, day),
+            DateOrder::MonthDayYear => format!("{:02}{}{:02}{}{}", month, sep, day, sep, year),
+            DateOrder::DayMonthYear => format!("{:02}{}{:02}{}{}", day, sep, month, sep, year),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_formatting() {
+        let default = Locale::default();
+        assert_eq!(default.format_value("temperature", 21.43), "21.4 °C");
+        assert_eq!(default.format_date(1_700_000_000), "2023-11-14");
+
+        let us = Locale::parse("en-US").unwrap();
+        assert_eq!(us.format_value("temperature", 20.0), "68.0 °F");
+        assert_eq!(us.format_value("wind_speed", 10.0), "22.4 mph");
+        assert_eq!(us.format_date(1_700_000_000), "11/14/2023");
+
+        let de = Locale::parse("de_DE.UTF-8").unwrap();
+        assert_eq!(de.tag, "de-DE");
+        assert_eq!(de.format_value("temperature", 21.43), "21,4 °C");
+        assert_eq!(de.format_value("humidity", 45.0), "45 %");
+        assert_eq!(de.format_date(1_700_000_000), "14.11.2023");
+
+        let gb = Locale::parse("en-GB").unwrap();
+        assert_eq!(gb.format_value("precipitation", 1.26), "1.3 mm");
+        assert_eq!(gb.format_date(1_700_000_000), "14/11/2023");
+        assert_eq!(gb.number(-0.2, 0), "0");
+
+        assert!(Locale::parse("english").is_none());
+        assert!(Locale::parse("en-USA").is_none());
+    }
+}
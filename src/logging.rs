@@ -0,0 +1,470 @@
+use axum::extract::Request;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Instrument, Subscriber};
+use tracing_subscriber::filter::{self, LevelFilter};
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, JsonFields, Writer};
+use tracing_subscriber::fmt::writer::{BoxMakeWriter, MakeWriter};
+use tracing_subscriber::fmt::{FmtContext, FormattedFields};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::utils::time::civil_from_days;
+
+// Process-wide `tracing` subscriber, replacing simple_logger. Records from the `log` macros are
+// forwarded to it, so they carry the spans they happen in: `request` (request_id, server,
+// method, path) for HTTP requests and `provider` (provider, operation) for provider calls.
+// Configured from the environment before anything else is loaded:
+//   LOG_FORMAT=plain|json               one line per record with its and its spans' fields,
+//                                        human-readable or JSON
+//   LOG_LEVEL=info,jupiter::db_pool=debug,hyper=warn
+//                                        default level plus per-module overrides
+//   LOG_FILE=/var/log/jupiter.log       write there instead of stdout, rotating by size
+//   LOG_MAX_SIZE_MB=10, LOG_MAX_FILES=5  rotate at this size, keeping jupiter.log.1 .. .5
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Plain,
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    pub level: LevelFilter,
+    /// (module prefix, level), most specific first
+    pub modules: Vec<(String, LevelFilter)>,
+    pub file: Option<PathBuf>,
+    pub max_size_bytes: u64,
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Plain,
+            level: LevelFilter::INFO,
+            modules: Vec::new(),
+            file: None,
+            max_size_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.trim().parse()
+        .map_err(|_| format!("unknown log level '{}' (off, error, warn, info, debug, trace)", level.trim()))
+}
+
+impl LogConfig {
+    /// Parses a LOG_LEVEL spec: a default level and/or `module=level` entries, comma separated
+    pub fn parse_levels(spec: &str) -> Result<(LevelFilter, Vec<(String, LevelFilter)>), String> {
+        let mut level = LevelFilter::INFO;
+        let mut modules = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((module, module_level)) => modules.push((module.trim().to_string(), parse_level(module_level)?)),
+                None => level = parse_level(entry)?,
+            }
+        }
+        // Longest prefix wins, so check the most specific modules first
+        modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Ok((level, modules))
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+        if let Ok(format) = env::var("LOG_FORMAT") {
+            config.format = match format.trim().to_lowercase().as_str() {
+                "" | "plain" | "text" => LogFormat::Plain,
+                "json" => LogFormat::Json,
+                other => return Err(format!("LOG_FORMAT must be plain or json, got {}", other)),
+            };
+        }
+        if let Ok(spec) = env::var("LOG_LEVEL") {
+            let (level, modules) = Self::parse_levels(&spec).map_err(|e| format!("LOG_LEVEL: {}", e))?;
+            config.level = level;
+            config.modules = modules;
+        }
+        config.file = env::var("LOG_FILE").ok()
+            .filter(|path| !path.trim().is_empty())
+            .map(PathBuf::from);
+        if let Ok(size) = env::var("LOG_MAX_SIZE_MB") {
+            let megabytes: u64 = size.trim().parse()
+                .ok().filter(|mb| *mb > 0)
+                .ok_or_else(|| format!("LOG_MAX_SIZE_MB must be a positive number, got {}", size))?;
+            config.max_size_bytes = megabytes * 1024 * 1024;
+        }
+        if let Ok(files) = env::var("LOG_MAX_FILES") {
+            config.max_files = files.trim().parse()
+                .map_err(|_| format!("LOG_MAX_FILES must be a number, got {}", files))?;
+        }
+        Ok(config)
+    }
+
+    /// Level that applies to records from `target` (a module path)
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules.iter()
+            .find(|(module, _)| target == module || target.strip_prefix(module.as_str()).is_some_and(|rest| rest.starts_with("::")))
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.level, std::cmp::max)
+    }
+}
+
+/// Log file that moves itself to `.1` (shifting older files up) once it reaches the size limit
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_size: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, file, size, max_size, max_files })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+            self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Shared handle to the log file. The formatter writes each record in a single call
+#[derive(Clone)]
+struct FileWriter(Arc<Mutex<RotatingFile>>);
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.0.lock().map_err(|e| io::Error::other(e.to_string()))
+            .and_then(|mut file| file.write_line(buf));
+        if let Err(e) = result {
+            // Don't lose the record when the disk is full or the file went away
+            eprint!("Failed to write log file ({}): {}", e, String::from_utf8_lossy(buf));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.lock() {
+            Ok(mut file) => file.file.flush(),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for FileWriter {
+    type Writer = FileWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// RFC 3339 UTC timestamp with milliseconds
+fn timestamp() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let seconds_of_day = secs.rem_euclid(86_400);
+    format!("{}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day,
+        seconds_of_day / 3600, seconds_of_day % 3600 / 60, seconds_of_day % 60, now.subsec_millis())
+}
+
+/// Fields of a record in the order they're written, with what the `log` bridge adds dropped
+/// except the original target
+#[derive(Default)]
+struct Fields {
+    target: Option<String>,
+    message: String,
+    values: Vec<(String, Value)>,
+}
+
+impl Fields {
+    fn insert(&mut self, name: &str, value: Value) {
+        match name {
+            "message" => self.message = match value {
+                Value::String(message) => message,
+                other => other.to_string(),
+            },
+            "log.target" => self.target = value.as_str().map(str::to_string),
+            name if name.starts_with("log.") => {},
+            name => {
+                // A field set again by an inner span or the record itself replaces the outer one
+                self.values.retain(|(existing, _)| existing != name);
+                self.values.push((name.to_string(), value));
+            },
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field.name(), format!("{:?}", value).into());
+    }
+}
+
+/// Writes records in the LOG_FORMAT layout: `timestamp level [target] message key=value ...`
+/// or a JSON object with the same keys. The keys are the record's fields after those of the
+/// spans it happened in, outermost first.
+struct LineFormat(LogFormat);
+
+impl<S, N> FormatEvent<S, N> for LineFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let mut fields = Fields::default();
+        // Span fields are stored formatted by JsonFields, see `init`
+        for span in ctx.event_scope().into_iter().flat_map(|scope| scope.from_root()) {
+            let extensions = span.extensions();
+            let stored = extensions.get::<FormattedFields<N>>()
+                .and_then(|stored| serde_json::from_str::<serde_json::Map<String, Value>>(stored).ok());
+            for (name, value) in stored.into_iter().flatten() {
+                fields.insert(&name, value);
+            }
+        }
+        event.record(&mut fields);
+        let target = fields.target.as_deref().unwrap_or(event.metadata().target());
+        let level = event.metadata().level().as_str();
+
+        match self.0 {
+            LogFormat::Plain => {
+                write!(writer, "{} {:<5} [{}] {}", timestamp(), level, target, fields.message)?;
+                for (name, value) in &fields.values {
+                    match value {
+                        // Unquoted, like the message
+                        Value::String(value) => write!(writer, " {}={}", name, value)?,
+                        value => write!(writer, " {}={}", name, value)?,
+                    }
+                }
+                writeln!(writer)
+            },
+            LogFormat::Json => {
+                let mut record = json!({
+                    "timestamp": timestamp(),
+                    "level": level,
+                    "target": target,
+                    "message": fields.message,
+                });
+                for (name, value) in fields.values {
+                    record[name.as_str()] = value;
+                }
+                writeln!(writer, "{}", record)
+            },
+        }
+    }
+}
+
+fn format_layer<S, W>(format: LogFormat, writer: W) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .fmt_fields(JsonFields::new())
+        .event_format(LineFormat(format))
+        .with_writer(writer)
+}
+
+/// Installs the subscriber and forwards `log` records to it; fails if the log file can't be
+/// opened or a logger is already set
+pub fn init(config: LogConfig) -> Result<(), String> {
+    let writer = match config.file {
+        Some(ref path) => BoxMakeWriter::new(FileWriter(Arc::new(Mutex::new(
+            RotatingFile::open(path.clone(), config.max_size_bytes, config.max_files)
+                .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?,
+        )))),
+        None => BoxMakeWriter::new(io::stdout),
+    };
+    let layer = format_layer(config.format, writer);
+    let max_level = config.max_level();
+    let levels = filter::filter_fn(move |metadata| *metadata.level() <= config.level_for(metadata.target()))
+        .with_max_level_hint(max_level);
+    tracing_subscriber::registry()
+        .with(layer.with_filter(levels))
+        .try_init()
+        .map_err(|e| e.to_string())
+}
+
+/// Logger from LOG_* variables, falling back to plain info-level stdout logging when they are invalid
+pub fn init_from_env() {
+    let config = LogConfig::from_env().unwrap_or_else(|e| {
+        eprintln!("Invalid logging configuration, using defaults: {}", e);
+        LogConfig::default()
+    });
+    if let Err(e) = init(config) {
+        eprintln!("Failed to initialize logger: {}", e);
+        let _ = init(LogConfig::default());
+    }
+}
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// ID for a request: the caller's X-Request-Id when it's a short token, so a request can be
+/// followed through a proxy, otherwise a new random one
+fn request_id(request: &Request) -> String {
+    request.headers().get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= 64
+            && id.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.')))
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()))
+}
+
+/// Runs a request in a `request` span and logs its status and latency once it's answered,
+/// returning its ID in the X-Request-Id response header
+pub async fn trace_http(server: &'static str, request: Request, next: Next) -> Response {
+    let request_id = request_id(&request);
+    let span = tracing::info_span!("request", %request_id, server, method = %request.method(), path = request.uri().path());
+    let start = Instant::now();
+    let mut response = next.run(request).instrument(span.clone()).await;
+    span.in_scope(|| tracing::info!(status = response.status().as_u16(), latency_ms = start.elapsed().as_millis() as u64, "request finished"));
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_levels() {
+        let (level, modules) = LogConfig::parse_levels("warn, jupiter=info, jupiter::db_pool=debug").unwrap();
+        let config = LogConfig { level, modules, ..LogConfig::default() };
+        assert_eq!(config.level_for("hyper::proto"), LevelFilter::WARN);
+        assert_eq!(config.level_for("jupiter::provider::combo"), LevelFilter::INFO);
+        assert_eq!(config.level_for("jupiter::db_pool"), LevelFilter::DEBUG);
+        assert_eq!(config.level_for("jupiter_extra"), LevelFilter::WARN);
+        assert_eq!(config.max_level(), LevelFilter::DEBUG);
+        assert!(LogConfig::parse_levels("loud").is_err());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = env::temp_dir().join(format!("jupiter-log-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("jupiter.log");
+        let mut file = RotatingFile::open(path.clone(), 20, 2).unwrap();
+        for line in ["first line 1234\n", "second line 123\n", "third line 1234\n", "fourth line 123\n"] {
+            file.write_line(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth line 123\n");
+        assert_eq!(fs::read_to_string(dir.join("jupiter.log.1")).unwrap(), "third line 1234\n");
+        assert_eq!(fs::read_to_string(dir.join("jupiter.log.2")).unwrap(), "second line 123\n");
+        assert!(!dir.join("jupiter.log.3").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_line_format() {
+        let path = env::temp_dir().join(format!("jupiter-log-format-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let file = FileWriter(Arc::new(Mutex::new(RotatingFile::open(path.clone(), 1024 * 1024, 0).unwrap())));
+        for format in [LogFormat::Json, LogFormat::Plain] {
+            let subscriber = tracing_subscriber::registry().with(format_layer(format, file.clone()));
+            tracing::subscriber::with_default(subscriber, || {
+                let _request = tracing::info_span!("request", request_id = "7f3a", path = "/api/current").entered();
+                let _provider = tracing::info_span!("provider", provider = "NWS").entered();
+                tracing::warn!(target: "jupiter::provider", latency_ms = 412u64, "provider call failed");
+            });
+        }
+
+        let written = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        let record: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record["level"], "WARN");
+        assert_eq!(record["target"], "jupiter::provider");
+        assert_eq!(record["message"], "provider call failed");
+        assert_eq!(record["request_id"], "7f3a");
+        assert_eq!(record["provider"], "NWS");
+        assert_eq!(record["latency_ms"], 412);
+        assert!(lines[1].ends_with(" WARN  [jupiter::provider] provider call failed path=/api/current request_id=7f3a provider=NWS latency_ms=412"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_request_id() {
+        let with_id = |id: &str| Request::builder().header(REQUEST_ID_HEADER, id).body(axum::body::Body::empty()).unwrap();
+        assert_eq!(request_id(&with_id("edge-7f3a.1")), "edge-7f3a.1");
+        let generated = request_id(&with_id("two words"));
+        assert_eq!(generated.len(), 16);
+        assert!(generated.bytes().all(|b| b.is_ascii_hexdigit()));
+        assert_ne!(request_id(&with_id("")), request_id(&with_id("")));
+    }
+}
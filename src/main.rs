@@ -5,21 +5,67 @@ use jupiter::provider::homebrew;
 use jupiter::provider::combo;
 use jupiter::db_pool;
 use jupiter::pool_monitor;
-use jupiter::config::Config;
+use jupiter::config::{self, Config};
+use jupiter::logging;
+use jupiter::ingest::{modbus, rtl433};
+use jupiter::devices::DeviceRegistry;
+use jupiter::snmp;
+use jupiter::statsd;
+use jupiter::influx;
+use jupiter::prefetch;
+use jupiter::timescale::TimescaleConfig;
+use jupiter::analysis::solar::SolarConfig;
+use jupiter::report_chain;
+use jupiter::rollup;
+use jupiter::summary;
+use jupiter::retention;
+use jupiter::archive;
+use jupiter::notifications;
+use jupiter::storage;
+use jupiter::error::JupiterError;
+use jupiter::scheduler::Scheduler;
+#[cfg(feature = "grpc")]
+use jupiter::grpc;
+#[cfg(windows)]
+use jupiter::service;
 use std::env;
+use std::future::Future;
+use std::time::Instant;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::signal;
 
 // store application version as a const
 const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
 
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Windows service management: `jupiter install`, `jupiter uninstall`, and `jupiter service`
+    // when launched by the service control manager
+    #[cfg(windows)]
+    match env::args().nth(1).as_deref() {
+        Some("install") => return service::install(),
+        Some("uninstall") => return service::uninstall(),
+        Some("service") => return service::run(run_service),
+        _ => {},
+    }
+
+    // Initialize logger (LOG_FORMAT, LOG_LEVEL, LOG_FILE); read .env first so it can set them
+    config::load_env_file();
+    logging::init_from_env();
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize logger
-    simple_logger::init_with_level(log::Level::Info).unwrap_or_else(|e| {
-        eprintln!("Failed to initialize logger: {}", e);
-    });
+    config::ResourceLimits::from_env().runtime()?.block_on(serve(shutdown_signal()))
+}
+
+/// Entry point under the Windows service control manager, which logs to the event log
+#[cfg(windows)]
+fn run_service(stop: tokio::sync::oneshot::Receiver<()>) -> Result<(), Box<dyn std::error::Error>> {
+    config::ResourceLimits::from_env().runtime()?.block_on(serve(async {
+        let _ = stop.await;
+    }))
+}
 
+/// Runs the servers until `shutdown` completes, then stops them gracefully
+async fn serve(shutdown: impl Future<Output = ()>) -> Result<(), Box<dyn std::error::Error>> {
     log::info!("Starting Jupiter Weather Server v{}", VERSION.unwrap_or("unknown"));
 
     // Load and validate configuration
@@ -39,26 +85,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         metric: None
     };
 
-    // Homebrew Weather Server configuration (if database config is available)
-    let mut homebrew_config = if let Some(ref db_config) = app_config.homebrew_database {
-        let pg = homebrew::PostgresServer::from_config(db_config);
+    // Registered sensors for ingest bridges
+    let device_registry = DeviceRegistry::from_env()
+        .map_err(|e| format!("Failed to load device registry: {}", e))?;
+
+    // Homebrew Weather Server configuration (if a database or SQLite file is configured)
+    let sqlite_path = storage::sqlite_path_from_env();
+    let mut homebrew_config = if app_config.homebrew_database.is_some() || sqlite_path.is_some() {
+        let pg = app_config.homebrew_database.as_ref()
+            .map(homebrew::PostgresServer::from_config)
+            .unwrap_or_default();
+        let store = storage::open(sqlite_path.as_deref())
+            .map_err(|e| format!("Failed to open homebrew storage: {}", e))?;
+        let timescale = TimescaleConfig::from_env()
+            .map_err(|problems| format!("Invalid TimescaleDB configuration: {:?}", problems))?;
         Some(homebrew::Config::new(
             app_config.weather.accu_key.clone(),
             pg,
-            9090
-        ))
+            app_config.servers.homebrew_port
+        )
+        .with_store(store)
+        .with_timescale(timescale)
+        .with_devices(device_registry)
+        .with_hash_chain(report_chain::enabled_from_env())
+        .with_base_path(app_config.base_path.clone())
+        .with_resource_limits(app_config.limits)
+        .with_locale(app_config.locale.clone())
+        .with_smoothing(app_config.servers.smoothing))
     } else {
         log::warn!("Homebrew database configuration not found, skipping homebrew server");
         None
     };
 
-    // Initialize homebrew server if configured
-    if let Some(ref mut hb_config) = homebrew_config {
-        hb_config.init().await
-            .map_err(|e| format!("Failed to initialize homebrew server: {}", e))?;
-        log::info!("Homebrew server initialized on port {}", hb_config.port);
-    }
-
     // Combo server configuration (if database config is available)
     let mut combo_config = if let Some(ref db_config) = app_config.combo_database {
         let pg = combo::PostgresServer::from_config(db_config);
@@ -66,56 +124,279 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             Some(accuweather_config),
             homebrew_config.clone(),
             app_config.weather.accu_key.clone(),
-            Some(3600),
+            Some(app_config.servers.cache_ttl_secs),
             pg,
-            9091,
+            app_config.servers.combo_port,
             app_config.weather.zip_code.clone()
-        ))
+        )
+        .with_marine_config(app_config.marine.clone())
+        .with_airfield(app_config.airfield.clone())
+        .with_solar(SolarConfig::from_env()
+            .map_err(|problems| format!("Invalid solar configuration: {:?}", problems))?)
+        .with_public_mode(combo::PublicModeConfig::from_env())
+        .with_base_path(app_config.base_path.clone())
+        .with_resource_limits(app_config.limits)
+        .with_accuweather_weight(app_config.servers.accuweather_weight)
+        .with_nws_weight(app_config.servers.nws_weight)
+        .with_tomorrow_io(app_config.weather.tomorrow_io_key.clone(), app_config.servers.tomorrow_io_weight)
+        .with_openweather_key(app_config.weather.openweather_key.clone())
+        .with_fusion_policy(app_config.servers.fusion_policy.clone())
+        .with_outlier_rejection(app_config.servers.outlier_rejection.clone())
+        .with_field_weights(app_config.servers.field_weights.clone())
+        .with_distance_scale(Some(app_config.servers.distance_scale_km))
+        .with_locale(app_config.locale.clone()))
+    } else if homebrew_config.is_some() {
+        log::warn!("Combo database configuration not found, skipping combo server");
+        None
     } else {
         log::error!("Combo database configuration not found - cannot start server");
         return Err("At least one database configuration (combo or homebrew) must be provided".into());
     };
 
-    // Initialize combo server
-    if let Some(ref mut config) = combo_config {
-        // Initialize the server
-        log::info!("Initializing combo server on port {}", config.port);
-        config.init().await
-            .map_err(|e| format!("Failed to initialize server: {}", e))?;
-        
+    // Rate limits and refreshes shared with other instances, when REDIS_URL is set
+    #[cfg(feature = "redis")]
+    timed("redis", jupiter::cluster::connect_from_env()).await;
+
+    // The servers use separate databases and pools, so bring them up concurrently
+    let startup = Instant::now();
+    let (homebrew_init, combo_init) = tokio::join!(
+        async {
+            match homebrew_config {
+                Some(ref mut hb_config) => timed("homebrew server", hb_config.init()).await,
+                None => Ok(()),
+            }
+        },
+        async {
+            match combo_config {
+                Some(ref mut config) => {
+                    log::info!("Initializing combo server on port {}", config.port);
+                    timed("combo server", config.init()).await
+                },
+                None => Ok(()),
+            }
+        },
+    );
+    homebrew_init.map_err(|e| format!("Failed to initialize homebrew server: {}", e))?;
+    combo_init.map_err(|e| format!("Failed to initialize server: {}", e))?;
+    if let Some(ref hb_config) = homebrew_config {
+        log::info!("Homebrew server initialized on port {}", hb_config.port);
+    }
+
+    if let Some(ref config) = combo_config {
         // Initialize pool monitors
-        pool_monitor::init_monitors().await;
-        
-        // Start monitoring task (check every 30 seconds)
-        pool_monitor::start_monitoring_task(30).await;
+        timed("pool monitors", pool_monitor::init_monitors()).await;
         
         log::info!("Server successfully initialized and listening on port {}", config.port);
-        log::info!("Pool metrics available at http://localhost:{}/metrics", config.port);
+        log::info!("Prometheus metrics available at http://localhost:{}/metrics", config.port);
+    }
+    log::info!("[startup] Servers ready in {} ms", startup.elapsed().as_millis());
+
+    // Start Modbus TCP pollers if configured
+    let background_shutdown = Arc::new(AtomicBool::new(false));
+    let mut background_threads = Vec::new();
+    match modbus::ModbusConfig::from_env() {
+        Ok(Some(modbus_config)) => match homebrew_config {
+            Some(ref hb_config) => {
+                log::info!("Starting Modbus pollers for {} device(s)", modbus_config.devices.len());
+                background_threads = modbus::start_pollers(modbus_config, hb_config.clone(), background_shutdown.clone());
+            },
+            None => log::warn!("MODBUS_CONFIG is set but no homebrew database is configured, skipping Modbus pollers"),
+        },
+        Ok(None) => {},
+        Err(e) => log::error!("Failed to load Modbus configuration: {}", e),
+    }
+
+    // Start rtl_433 stdin/UDP listeners if configured
+    let rtl433_config = rtl433::Rtl433ListenerConfig::from_env();
+    if rtl433_config.udp_listen.is_some() || rtl433_config.stdin {
+        match homebrew_config {
+            Some(ref hb_config) => {
+                match rtl433::start_listeners(rtl433_config, hb_config.clone(), background_shutdown.clone()) {
+                    Ok(handles) => background_threads.extend(handles),
+                    Err(e) => log::error!("Failed to start rtl_433 listeners: {}", e),
+                }
+            },
+            None => log::warn!("rtl_433 listeners are configured but no homebrew database is configured, skipping"),
+        }
+    }
+
+    // Push metrics to StatsD if configured
+    match statsd::StatsdConfig::from_env() {
+        Ok(Some(statsd_config)) => background_threads.extend(statsd::start(statsd_config, background_shutdown.clone())),
+        Ok(None) => {},
+        Err(problems) => log::error!("Invalid StatsD configuration: {:?}", problems),
+    }
+
+    // Write reports and cached conditions to InfluxDB if configured
+    match influx::InfluxConfig::from_env() {
+        Ok(Some(influx_config)) => background_threads.extend(influx::start(influx_config, background_shutdown.clone())),
+        Ok(None) => {},
+        Err(problems) => log::error!("Invalid InfluxDB configuration: {:?}", problems),
+    }
+
+    // Refresh cached conditions and forecasts ahead of expiry if configured
+    match prefetch::PrefetchConfig::from_env() {
+        Ok(prefetch_config) if prefetch_config.enabled => match combo_config {
+            Some(ref config) => background_threads.extend(prefetch::start(prefetch_config, config.clone(), background_shutdown.clone())),
+            None => log::warn!("PREFETCH is set but the combo server is not configured, skipping prefetching"),
+        },
+        Ok(_) => {},
+        Err(problems) => log::error!("Invalid prefetch configuration: {:?}", problems),
+    }
+
+    // Start SNMP agent if configured
+    match snmp::SnmpConfig::from_env() {
+        Ok(Some(snmp_config)) => match combo_config {
+            Some(ref config) => {
+                let providers = Arc::new(config.weather_providers());
+                match snmp::start(snmp_config, providers, config.zip_code.clone(), config.homebrew_config.clone(), background_shutdown.clone()) {
+                    Ok(handles) => background_threads.extend(handles),
+                    Err(e) => log::error!("Failed to start SNMP agent: {}", e),
+                }
+            },
+            None => log::warn!("SNMP_LISTEN is set but the combo server is not configured, skipping SNMP agent"),
+        },
+        Ok(None) => {},
+        Err(e) => log::error!("Failed to load SNMP configuration: {}", e),
+    }
+
+    // Start gRPC server if configured
+    #[cfg(feature = "grpc")]
+    match grpc::GrpcConfig::from_env() {
+        Ok(Some(grpc_config)) => match combo_config {
+            Some(ref config) => match grpc::start(grpc_config, config, background_shutdown.clone()) {
+                Ok(handles) => background_threads.extend(handles),
+                Err(e) => log::error!("Failed to start gRPC server: {}", e),
+            },
+            None => log::warn!("GRPC_LISTEN is set but the combo server is not configured, skipping gRPC server"),
+        },
+        Ok(None) => {},
+        Err(e) => log::error!("Failed to load gRPC configuration: {}", e),
+    }
+
+    // Start scheduled background jobs
+    let mut jobs = Scheduler::new(app_config.jobs.clone(), tokio::runtime::Handle::current());
+    match register_jobs(&mut jobs, combo_config.as_ref(), homebrew_config.as_ref()) {
+        Ok(()) => background_threads.extend(jobs.start(background_shutdown.clone())),
+        Err(e) => log::error!("Failed to schedule background jobs: {}", e),
     }
 
     // Wait for shutdown signal
-    shutdown_signal().await;
+    shutdown.await;
     
     log::info!("Shutdown signal received, gracefully shutting down...");
     
-    // Shutdown all servers gracefully
-    if let Some(ref mut config) = combo_config {
-        config.shutdown().await;
-    }
-    if let Some(ref mut hb_config) = homebrew_config {
-        hb_config.shutdown().await;
-    }
+    // Tell ingest pollers, listeners, the SNMP agent and the gRPC server to stop
+    background_shutdown.store(true, Ordering::Relaxed);
+
+    // Both servers stop accepting connections at once and drain their requests in parallel
+    tokio::join!(
+        async {
+            if let Some(ref mut config) = combo_config {
+                config.shutdown().await;
+            }
+        },
+        async {
+            if let Some(ref mut hb_config) = homebrew_config {
+                hb_config.shutdown().await;
+            }
+        },
+    );
+
+    // Then wait for the background threads, off the runtime's worker threads
+    let _ = tokio::task::spawn_blocking(move || {
+        for handle in background_threads {
+            let _ = handle.join();
+        }
+    }).await;
     
     // Shutdown database connection pools
     db_pool::shutdown_pools().await;
     
-    // Give the server threads a moment to finish current requests
-    tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-    
     log::info!("Server shutdown complete");
     Ok(())
 }
 
+/// Background jobs and their default schedules, which JOBS_CONFIG can override. Jobs run on
+/// scheduler threads and block on the process runtime for database work.
+fn register_jobs(scheduler: &mut Scheduler, combo_config: Option<&combo::Config>, homebrew_config: Option<&homebrew::Config>) -> Result<(), JupiterError> {
+    let runtime = scheduler.runtime();
+
+    scheduler.register("pool_health", "* * * * *", || {
+        pool_monitor::check_pools();
+        Ok(())
+    })?;
+
+    // Off by default: every refresh costs two AccuWeather calls, and requests refresh a stale cache anyway
+    if let Some(config) = combo_config {
+        let config = config.clone();
+        let runtime = runtime.clone();
+        scheduler.register("cache_refresh", "off", move || {
+            runtime.block_on(config.refresh_cached_conditions());
+            Ok(())
+        })?;
+    }
+
+    if combo_config.is_some() && archive::ARCHIVE_CONFIG.enabled() {
+        let runtime = runtime.clone();
+        scheduler.register("payload_prune", "30 3 * * *", move || runtime.block_on(archive::prune()))?;
+    }
+
+    if retention::RETENTION_CONFIG.enabled() {
+        let runtime = runtime.clone();
+        scheduler.register("retention", "0 4 * * *", move || {
+            runtime.block_on(retention::run(&retention::RETENTION_CONFIG)).map(|_| ())
+        })?;
+    }
+
+    // Forecasts, historical data and seasonal baselines read the rollups, which are Postgres tables
+    if homebrew_config.map(|config| config.store.uses_postgres()).unwrap_or(false) {
+        let runtime = runtime.clone();
+        scheduler.register("rollup", "*/15 * * * *", move || {
+            runtime.block_on(rollup::run()).map(|_| ())
+        })?;
+    }
+
+    // Plain Postgres tables read bucketed history from the summary views
+    if homebrew_config.map(|config| config.store.uses_postgres() && config.timescale.is_none()).unwrap_or(false) {
+        let runtime = runtime.clone();
+        scheduler.register("summary_refresh", "*/10 * * * *", move || {
+            runtime.block_on(summary::refresh())
+        })?;
+    }
+
+    // Alert rules need the combo server's providers, threshold and stale-sensor rules a homebrew database
+    if notifications::NOTIFICATIONS.enabled() {
+        let providers = combo_config.map(|config| (Arc::new(config.weather_providers()), config.zip_code.clone()));
+        let homebrew_config = homebrew_config.cloned();
+        let runtime = runtime.clone();
+        scheduler.register("notifications", "* * * * *", move || {
+            let providers = providers.as_ref().map(|(providers, location)| (providers.as_ref(), location.as_str()));
+            runtime.block_on(notifications::run(providers, homebrew_config.as_ref()))
+        })?;
+    }
+
+    if homebrew_config.map(|config| config.hash_chain).unwrap_or(false) {
+        scheduler.register("chain_verify", "0 3 * * *", move || {
+            let verification = runtime.block_on(report_chain::verify())?;
+            if verification.valid {
+                Ok(())
+            } else {
+                Err(JupiterError::ValidationError(format!("Report chain is broken: {}", verification.problems.join("; "))))
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// Runs one startup step, logging how long it took
+async fn timed<F: Future>(step: &str, future: F) -> F::Output {
+    let started = Instant::now();
+    let output = future.await;
+    log::info!("[startup] {} took {} ms", step, started.elapsed().as_millis());
+    output
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         if let Err(e) = signal::ctrl_c().await {
@@ -0,0 +1,366 @@
+use axum::extract::Request;
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// Process-wide metrics in the Prometheus text exposition format, served at GET /metrics on both
+// servers. Modules keep their counters and histograms in `Lazy` statics built with `counter` and
+// `histogram`, which register them here on first use; values only known at scrape time, like
+// database pool sizes, are registered as collectors. The same counters and gauges can be pushed
+// to StatsD as well (see `statsd`).
+
+/// Upper bounds in seconds of the latency histogram buckets
+pub const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+pub trait Metric: Send + Sync {
+    fn name(&self) -> &str;
+    /// Appends the metric's HELP, TYPE and sample lines
+    fn render(&self, out: &mut String);
+    /// Current values for exporters that push them; histograms push their observations instead
+    fn samples(&self) -> Vec<Sample> {
+        Vec::new()
+    }
+}
+
+/// One value of a counter or gauge with its labels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+static REGISTRY: Lazy<Mutex<Vec<Arc<dyn Metric>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+pub fn register(metric: Arc<dyn Metric>) {
+    match REGISTRY.lock() {
+        Ok(mut registry) => registry.push(metric),
+        Err(e) => log::error!("Metrics registry lock poisoned: {}", e),
+    }
+}
+
+/// Every registered metric, sorted by name
+pub fn render() -> String {
+    let mut metrics = match REGISTRY.lock() {
+        Ok(registry) => registry.clone(),
+        Err(e) => {
+            log::error!("Metrics registry lock poisoned: {}", e);
+            return String::new();
+        }
+    };
+    metrics.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let mut out = String::new();
+    for metric in metrics {
+        metric.render(&mut out);
+    }
+    out
+}
+
+/// Every registered counter and gauge value
+pub fn samples() -> Vec<Sample> {
+    let metrics = match REGISTRY.lock() {
+        Ok(registry) => registry.clone(),
+        Err(e) => {
+            log::error!("Metrics registry lock poisoned: {}", e);
+            return Vec::new();
+        }
+    };
+    metrics.iter().flat_map(|metric| metric.samples()).collect()
+}
+
+/// A registered counter with the given label names
+pub fn counter(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Arc<Counter> {
+    let counter = Arc::new(Counter::new(name, help, labels));
+    register(counter.clone());
+    counter
+}
+
+/// A registered histogram over LATENCY_BUCKETS with the given label names
+pub fn histogram(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Arc<Histogram> {
+    let histogram = Arc::new(Histogram::new(name, help, labels));
+    register(histogram.clone());
+    histogram
+}
+
+/// Registers values read at scrape time: `collect` returns the label values and value of each sample
+pub fn collector(kind: Kind, name: &'static str, help: &'static str, labels: &'static [&'static str], collect: fn() -> Vec<(Vec<String>, f64)>) {
+    register(Arc::new(Collector { kind, name, help, labels, collect }));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Counter,
+    Gauge,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help.replace('\\', "\\\\").replace('\n', "\\n"));
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// `{a="1",b="2"}`, or nothing without labels
+fn label_set(names: &[&str], values: &[String], extra: Option<(&str, &str)>) -> String {
+    let mut pairs: Vec<String> = names.iter().zip(values)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect();
+    if let Some((name, value)) = extra {
+        pairs.push(format!("{}=\"{}\"", name, escape(value)));
+    }
+    if pairs.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", pairs.join(","))
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn label_values(labels: &[&str]) -> Vec<String> {
+    labels.iter().map(|value| value.to_string()).collect()
+}
+
+fn sample(name: &'static str, kind: Kind, names: &'static [&'static str], values: &[String], value: f64) -> Sample {
+    Sample { name, kind, labels: names.iter().copied().zip(values.iter().cloned()).collect(), value }
+}
+
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, f64>>,
+}
+
+impl Counter {
+    pub fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        Counter { name, help, labels, values: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Adds one to the sample with these label values, in the order the label names were given
+    pub fn inc(&self, labels: &[&str]) {
+        self.add(labels, 1.0);
+    }
+
+    pub fn add(&self, labels: &[&str], amount: f64) {
+        if let Ok(mut values) = self.values.lock() {
+            *values.entry(label_values(labels)).or_insert(0.0) += amount;
+        }
+    }
+
+    pub fn get(&self, labels: &[&str]) -> f64 {
+        self.values.lock().ok()
+            .and_then(|values| values.get(&label_values(labels)).copied())
+            .unwrap_or(0.0)
+    }
+}
+
+impl Metric for Counter {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "counter");
+        if let Ok(values) = self.values.lock() {
+            for (labels, value) in values.iter() {
+                let _ = writeln!(out, "{}{} {}", self.name, label_set(self.labels, labels, None), format_value(*value));
+            }
+        }
+    }
+
+    fn samples(&self) -> Vec<Sample> {
+        match self.values.lock() {
+            Ok(values) => values.iter().map(|(labels, value)| sample(self.name, Kind::Counter, self.labels, labels, *value)).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Buckets {
+    counts: Vec<u64>,   // Per bucket, not cumulative
+    sum: f64,
+    count: u64,
+}
+
+pub struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, Buckets>>,
+}
+
+impl Histogram {
+    pub fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        Histogram { name, help, labels, values: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn observe(&self, labels: &[&str], value: f64) {
+        if let Ok(mut values) = self.values.lock() {
+            let buckets = values.entry(label_values(labels)).or_insert_with(|| Buckets {
+                counts: vec![0; LATENCY_BUCKETS.len()],
+                ..Buckets::default()
+            });
+            if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| value <= *bound) {
+                buckets.counts[bucket] += 1;
+            }
+            buckets.sum += value;
+            buckets.count += 1;
+        }
+        crate::statsd::timing(self.name, self.labels, labels, value);
+    }
+
+    /// Observes the seconds elapsed since `start`
+    pub fn observe_since(&self, labels: &[&str], start: Instant) {
+        self.observe(labels, start.elapsed().as_secs_f64());
+    }
+}
+
+impl Metric for Histogram {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, "histogram");
+        if let Ok(values) = self.values.lock() {
+            for (labels, buckets) in values.iter() {
+                let mut cumulative = 0;
+                for (bound, count) in LATENCY_BUCKETS.iter().zip(&buckets.counts) {
+                    cumulative += count;
+                    let _ = writeln!(out, "{}_bucket{} {}", self.name,
+                        label_set(self.labels, labels, Some(("le", &format_value(*bound)))), cumulative);
+                }
+                let _ = writeln!(out, "{}_bucket{} {}", self.name, label_set(self.labels, labels, Some(("le", "+Inf"))), buckets.count);
+                let _ = writeln!(out, "{}_sum{} {}", self.name, label_set(self.labels, labels, None), format_value(buckets.sum));
+                let _ = writeln!(out, "{}_count{} {}", self.name, label_set(self.labels, labels, None), buckets.count);
+            }
+        }
+    }
+}
+
+struct Collector {
+    kind: Kind,
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    collect: fn() -> Vec<(Vec<String>, f64)>,
+}
+
+impl Metric for Collector {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, self.name, self.help, self.kind.as_str());
+        for (labels, value) in (self.collect)() {
+            let _ = writeln!(out, "{}{} {}", self.name, label_set(self.labels, &labels, None), format_value(value));
+        }
+    }
+
+    fn samples(&self) -> Vec<Sample> {
+        (self.collect)().into_iter().map(|(labels, value)| sample(self.name, self.kind, self.labels, &labels, value)).collect()
+    }
+}
+
+static HTTP_RESPONSES: Lazy<Arc<Counter>> = Lazy::new(|| counter(
+    "jupiter_http_responses_total", "HTTP responses by server, method and status code", &["server", "method", "status"],
+));
+
+static HTTP_DURATION: Lazy<Arc<Histogram>> = Lazy::new(|| histogram(
+    "jupiter_http_request_duration_seconds", "Time to produce an HTTP response", &["server"],
+));
+
+static CACHE_LOOKUPS: Lazy<Arc<Counter>> = Lazy::new(|| counter(
+    "jupiter_cache_lookups_total", "Cache lookups by cache (provider, conditions, response or range) and result (hit or miss)", &["cache", "result"],
+));
+
+pub fn record_cache_lookup(cache: &str, hit: bool) {
+    CACHE_LOOKUPS.inc(&[cache, if hit { "hit" } else { "miss" }]);
+}
+
+/// GET /metrics
+pub async fn prometheus() -> Response {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")], render()).into_response()
+}
+
+/// Middleware counting responses by status code and timing requests, see `server::spawn`
+pub async fn track_http(server: &'static str, request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    HTTP_RESPONSES.inc(&[server, method.as_str(), response.status().as_str()]);
+    HTTP_DURATION.observe_since(&[server], start);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_exposition() {
+        let counter = Counter::new("test_requests_total", "Requests\nserved", &["provider", "outcome"]);
+        counter.inc(&["NWS", "ok"]);
+        counter.add(&["Acme \"Weather\"", "error"], 2.0);
+        counter.inc(&["NWS", "ok"]);
+        assert_eq!(counter.get(&["NWS", "ok"]), 2.0);
+
+        let mut out = String::new();
+        counter.render(&mut out);
+        assert_eq!(out, concat!(
+            "# HELP test_requests_total Requests\\nserved\n",
+            "# TYPE test_requests_total counter\n",
+            "test_requests_total{provider=\"Acme \\\"Weather\\\"\",outcome=\"error\"} 2\n",
+            "test_requests_total{provider=\"NWS\",outcome=\"ok\"} 2\n",
+        ));
+    }
+
+    #[test]
+    fn test_histogram_exposition() {
+        let histogram = Histogram::new("test_seconds", "Latency", &[]);
+        histogram.observe(&[], 0.00390625);
+        histogram.observe(&[], 0.25);
+        histogram.observe(&[], 32.0);
+
+        let mut out = String::new();
+        histogram.render(&mut out);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[1], "# TYPE test_seconds histogram");
+        assert!(lines.contains(&"test_seconds_bucket{le=\"0.005\"} 1"));
+        assert!(lines.contains(&"test_seconds_bucket{le=\"0.1\"} 1"));
+        assert!(lines.contains(&"test_seconds_bucket{le=\"0.25\"} 2"));
+        assert!(lines.contains(&"test_seconds_bucket{le=\"10\"} 2"));
+        assert!(lines.contains(&"test_seconds_bucket{le=\"+Inf\"} 3"));
+        assert!(lines.contains(&"test_seconds_sum 32.25390625"));
+        assert!(lines.contains(&"test_seconds_count 3"));
+    }
+}
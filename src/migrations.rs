@@ -0,0 +1,218 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::env;
+
+use crate::db_pool::DatabasePool;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::combo::CachedWeatherData;
+use crate::provider::homebrew::WeatherReport;
+use crate::provider::quota;
+use crate::{admin, alert_rules, archive, auth, backfill, cache, devices, groups, notifications, report_chain, rollup, scheduler, summary};
+
+// Versioned schema changes of the homebrew and combo databases. Each database records the
+// migrations applied to it in `schema_migrations`, per schema so both can share a database.
+// At startup the pending ones run in version order, each in a transaction together with its
+// record, and the first failure stops startup with the schema at the last version applied.
+// Instances starting at the same time take turns through an advisory lock.
+//
+// A schema change ships as a new migration at the end of `homebrew()` or `combo()`, with the
+// SQL that applies it and the SQL that reverts it. Applied migrations must not be edited; their
+// checksums are compared at every startup and a changed one is logged. To go back to an older
+// release, start this one once with HOMEBREW_SCHEMA_VERSION or COMBO_SCHEMA_VERSION set to the
+// version the older release expects: the migrations above it are reverted, newest first.
+//
+// The first versions are the tables as they were before migrations were versioned, written so
+// they also apply to databases created by those releases.
+
+// pg_advisory_lock key held while migrating
+const MIGRATION_LOCK_KEY: i64 = 0x006a_7570_6974_6573;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub up: String,
+    pub down: String,
+}
+
+impl Migration {
+    pub fn new(version: i32, name: &'static str, up: impl Into<String>, down: impl Into<String>) -> Self {
+        Self { version, name, up: up.into(), down: down.into() }
+    }
+
+    /// SHA-256 of `up`, recorded when applied
+    pub fn checksum(&self) -> String {
+        Sha256::digest(self.up.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Migrations of the homebrew database, oldest first
+pub fn homebrew() -> Vec<Migration> {
+    vec![
+        Migration::new(1, "devices", devices::sql_build_statement(), "DROP TABLE IF EXISTS devices;"),
+        // Doesn't restore the key hashes moved over from devices
+        Migration::new(2, "api_keys", [auth::sql_build_statement()].into_iter().chain(auth::sql_migrations()).collect::<Vec<_>>().join("\n"),
+            "DROP TABLE IF EXISTS api_keys;"),
+        Migration::new(3, "weather_reports", [WeatherReport::sql_build_statement()].into_iter().chain(WeatherReport::migrations()).collect::<Vec<_>>().join("\n"),
+            "DROP TABLE IF EXISTS weather_reports;"),
+        Migration::new(4, "erasure_log", admin::sql_build_statement(), "DROP TABLE IF EXISTS erasure_log;"),
+        Migration::new(5, "rollups", rollup::sql_build_statement(), "DROP TABLE IF EXISTS weather_reports_hourly, weather_reports_daily;"),
+        Migration::new(6, "report_chain", report_chain::sql_build_statement(), "DROP TABLE IF EXISTS report_chain;"),
+        Migration::new(7, "summary_views", summary::sql_build_statement(), summary::sql_drop_statement()),
+        Migration::new(8, "device_state", devices::sql_state_migration(), "ALTER TABLE devices DROP COLUMN IF EXISTS state;"),
+        Migration::new(9, "device_groups", groups::sql_build_statement(), "DROP TABLE IF EXISTS device_groups;"),
+        Migration::new(10, "alert_rules", alert_rules::sql_build_statement(), "DROP TABLE IF EXISTS alert_rules;"),
+    ]
+}
+
+/// Migrations of the combo database, oldest first
+pub fn combo() -> Vec<Migration> {
+    vec![
+        Migration::new(1, "cached_weather_data", CachedWeatherData::sql_build_statement(), "DROP TABLE IF EXISTS cached_weather_data;"),
+        Migration::new(2, "job_runs", scheduler::sql_build_statement(), "DROP TABLE IF EXISTS job_runs;"),
+        Migration::new(3, "provider_payloads", archive::sql_build_statement(), "DROP TABLE IF EXISTS provider_payloads;"),
+        Migration::new(4, "normalized_observations", backfill::sql_build_statement(), "DROP TABLE IF EXISTS normalized_observations;"),
+        Migration::new(5, "response_cache", cache::Postgres::sql_build_statement(), "DROP TABLE IF EXISTS response_cache;"),
+        Migration::new(6, "provider_calls", quota::sql_build_statement(), "DROP TABLE IF EXISTS provider_calls;"),
+        Migration::new(7, "notification_deliveries", notifications::sql_build_statement(), "DROP TABLE IF EXISTS notification_deliveries;"),
+    ]
+}
+
+/// Version to migrate a schema to from `setting` (HOMEBREW_SCHEMA_VERSION, COMBO_SCHEMA_VERSION),
+/// None for the latest
+pub fn target_from_env(setting: &str) -> Result<Option<i32>, String> {
+    match env::var(setting).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+        None => Ok(None),
+        Some(version) => version.parse::<i32>().ok().filter(|version| *version >= 0).map(Some)
+            .ok_or_else(|| format!("must be a migration version (0 or above), got '{}'", version)),
+    }
+}
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.schema_migrations (
+        schema varchar NOT NULL,
+        version INTEGER NOT NULL,
+        name varchar NOT NULL,
+        checksum varchar NOT NULL,
+        applied_at BIGINT DEFAULT 0,
+        CONSTRAINT schema_migrations_pkey PRIMARY KEY (schema, version));"
+}
+
+/// What migrating to `target` (the latest without one) takes, given the applied versions and
+/// their checksums
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Plan<'a> {
+    pub down: Vec<&'a Migration>,  // Newest first
+    pub up: Vec<&'a Migration>,    // Oldest first
+    pub changed: Vec<i32>,         // Applied, but edited since
+    pub unknown: Vec<i32>,         // Applied by a newer release
+}
+
+pub fn plan<'a>(migrations: &'a [Migration], applied: &BTreeMap<i32, String>, target: Option<i32>) -> Plan<'a> {
+    let target = target.unwrap_or(i32::MAX);
+    let mut sorted: Vec<&Migration> = migrations.iter().collect();
+    sorted.sort_by_key(|migration| migration.version);
+    Plan {
+        down: sorted.iter().rev().filter(|m| m.version > target && applied.contains_key(&m.version)).copied().collect(),
+        up: sorted.iter().filter(|m| m.version <= target && !applied.contains_key(&m.version)).copied().collect(),
+        changed: sorted.iter().filter(|m| applied.get(&m.version).is_some_and(|checksum| *checksum != m.checksum())).map(|m| m.version).collect(),
+        unknown: applied.keys().filter(|version| !migrations.iter().any(|m| m.version == **version)).copied().collect(),
+    }
+}
+
+fn db_error(e: tokio_postgres::Error) -> JupiterError {
+    JupiterError::DatabaseError(format!("Migration query failed: {}", e))
+}
+
+/// Applies the pending migrations of `schema` up to `target`, or reverts those above it
+pub async fn run(pool: &DatabasePool, schema: &str, migrations: &[Migration], target: Option<i32>) -> JupiterResult<()> {
+    let mut client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    client.execute("SELECT pg_advisory_lock($1)", &[&MIGRATION_LOCK_KEY]).await.map_err(db_error)?;
+    let result = match client.batch_execute(sql_build_statement()).await {
+        Ok(()) => migrate(&mut client, schema, migrations, target).await,
+        Err(e) => Err(db_error(e)),
+    };
+    if let Err(e) = client.execute("SELECT pg_advisory_unlock($1)", &[&MIGRATION_LOCK_KEY]).await {
+        log::error!("[migrations] Failed to release the migration lock: {}", e);
+    }
+    result
+}
+
+async fn migrate(client: &mut deadpool_postgres::Client, schema: &str, migrations: &[Migration], target: Option<i32>) -> JupiterResult<()> {
+    let applied: BTreeMap<i32, String> = client.query("SELECT version, checksum FROM schema_migrations WHERE schema = $1", &[&schema]).await
+        .map_err(db_error)?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect();
+    let plan = plan(migrations, &applied, target);
+    for version in &plan.changed {
+        log::warn!("[migrations] {} migration {} was edited after it was applied; add a new migration instead", schema, version);
+    }
+    if !plan.unknown.is_empty() {
+        log::warn!("[migrations] {} schema has migration(s) {:?} from a newer release, revert them with that release", schema, plan.unknown);
+    }
+
+    for migration in plan.down {
+        let transaction = client.transaction().await.map_err(db_error)?;
+        transaction.batch_execute(&migration.down).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Reverting {} migration {} ({}) failed: {}", schema, migration.version, migration.name, e)))?;
+        transaction.execute("DELETE FROM schema_migrations WHERE schema = $1 AND version = $2", &[&schema, &migration.version]).await.map_err(db_error)?;
+        transaction.commit().await.map_err(db_error)?;
+        log::info!("[migrations] Reverted {} migration {} ({})", schema, migration.version, migration.name);
+    }
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    for migration in plan.up {
+        let transaction = client.transaction().await.map_err(db_error)?;
+        transaction.batch_execute(&migration.up).await
+            .map_err(|e| JupiterError::DatabaseError(format!("{} migration {} ({}) failed: {}", schema, migration.version, migration.name, e)))?;
+        transaction.execute("INSERT INTO schema_migrations (schema, version, name, checksum, applied_at) VALUES ($1, $2, $3, $4, $5)",
+            &[&schema, &migration.version, &migration.name, &migration.checksum(), &now]).await.map_err(db_error)?;
+        transaction.commit().await.map_err(db_error)?;
+        log::info!("[migrations] Applied {} migration {} ({})", schema, migration.version, migration.name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        for migrations in [homebrew(), combo()] {
+            let versions: Vec<i32> = migrations.iter().map(|m| m.version).collect();
+            assert_eq!(versions, (1..=migrations.len() as i32).collect::<Vec<_>>(), "consecutive from 1");
+            assert!(migrations.iter().all(|m| !m.up.trim().is_empty() && !m.down.trim().is_empty()));
+            // Databases created before versioning already have the tables
+            assert!(migrations.iter().all(|m| !m.up.contains("CREATE TABLE public.")), "CREATE TABLE without IF NOT EXISTS");
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        let migrations = vec![
+            Migration::new(1, "one", "CREATE TABLE IF NOT EXISTS one (id INTEGER);", "DROP TABLE one;"),
+            Migration::new(2, "two", "CREATE TABLE IF NOT EXISTS two (id INTEGER);", "DROP TABLE two;"),
+            Migration::new(3, "three", "CREATE TABLE IF NOT EXISTS three (id INTEGER);", "DROP TABLE three;"),
+        ];
+        let versions = |list: &[&Migration]| list.iter().map(|m| m.version).collect::<Vec<_>>();
+
+        let fresh = plan(&migrations, &BTreeMap::new(), None);
+        assert_eq!((versions(&fresh.up), versions(&fresh.down)), (vec![1, 2, 3], vec![]));
+
+        let mut applied: BTreeMap<i32, String> = migrations[..2].iter().map(|m| (m.version, m.checksum())).collect();
+        assert_eq!(versions(&plan(&migrations, &applied, None).up), vec![3]);
+        assert_eq!(plan(&migrations, &applied, Some(2)), Plan::default(), "already at version 2");
+
+        applied.insert(3, migrations[2].checksum());
+        let rollback = plan(&migrations, &applied, Some(1));
+        assert_eq!((versions(&rollback.up), versions(&rollback.down)), (vec![], vec![3, 2]));
+
+        applied.insert(1, "edited".to_string());
+        applied.insert(4, "newer release".to_string());
+        let drifted = plan(&migrations, &applied, None);
+        assert_eq!((drifted.changed, drifted.unknown), (vec![1], vec![4]));
+    }
+}
@@ -0,0 +1,349 @@
+use reqwest::{header, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::email::{self, Email};
+use super::{template, Notification};
+use crate::provider::common::AlertSeverity;
+use crate::secret::Secret;
+use crate::utils::time::format_rfc3339;
+
+// Where a rule's notifications go, each formatted for its service: `webhook` POSTs the
+// notification as is to any URL, with optional extra headers (e.g. a token), `slack` a message
+// and `discord` an embed (coloured by severity) to an incoming webhook, and `matrix` sends a
+// message with an HTML formatted body to a room as the user of `access_token`:
+//
+// { "kind": "matrix", "homeserver": "https://matrix.example.org", "room": "!AbCdEf:example.org", "access_token": "..." }
+//
+// `email` sends mail through an SMTP server, see `email`. Webhook, Slack and Discord channels take
+// an optional `template` for the request body instead, see `template`.
+//
+// Webhook URLs usually carry their credentials, so they're kept as secrets like the access
+// token, and only the host a channel sends to is ever logged.
+
+/// Attempts per delivery, the first one included
+pub const MAX_ATTEMPTS: u32 = 4;
+
+/// Longest Retry-After honoured between attempts
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Channel {
+    Webhook {
+        url: Secret<String>,
+        #[serde(default)]
+        headers: HashMap<String, Secret<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+    Slack {
+        url: Secret<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+    Discord {
+        url: Secret<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        template: Option<String>,
+    },
+    Matrix {
+        homeserver: String,
+        room: String,       // Room id, e.g. !AbCdEf:example.org
+        access_token: Secret<String>,
+    },
+    Email(Email),
+}
+
+impl Channel {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Channel::Webhook { .. } => "webhook",
+            Channel::Slack { .. } => "slack",
+            Channel::Discord { .. } => "discord",
+            Channel::Matrix { .. } => "matrix",
+            Channel::Email(_) => "email",
+        }
+    }
+
+    /// The webhook URL, or the homeserver of a Matrix channel
+    fn base_url(&self) -> Option<Url> {
+        let url = match self {
+            Channel::Webhook { url, .. } | Channel::Slack { url, .. } | Channel::Discord { url, .. } => url.expose(),
+            Channel::Matrix { homeserver, .. } => homeserver,
+            Channel::Email(_) => return None,
+        };
+        Url::parse(url).ok().filter(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+    }
+
+    /// Host the channel sends to, the part of its URL safe to log
+    pub fn host(&self) -> String {
+        if let Channel::Email(email) = self {
+            return email.smtp_host.clone();
+        }
+        self.base_url().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Channel::Email(email) => email.validate(),
+            Channel::Matrix { .. } if self.base_url().is_none() => Err("matrix channel needs an http(s) homeserver".to_string()),
+            Channel::Matrix { room, .. } if !room.starts_with('!') || !room.contains(':') =>
+                Err(format!("matrix room must be a room id like !AbCdEf:example.org, got '{}'", room)),
+            Channel::Matrix { access_token, .. } if access_token.is_empty() => Err("matrix channel needs an access_token".to_string()),
+            _ if self.base_url().is_none() => Err(format!("{} channel needs an http(s) url", self.kind())),
+            _ => self.template().map_or(Ok(()), |template| {
+                template::validate(template).map_err(|message| format!("{} channel {}", self.kind(), message))
+            }),
+        }
+    }
+
+    fn template(&self) -> Option<&str> {
+        match self {
+            Channel::Webhook { template, .. } | Channel::Slack { template, .. } | Channel::Discord { template, .. } => template.as_deref(),
+            Channel::Matrix { .. } | Channel::Email(_) => None,
+        }
+    }
+
+    /// Request body for `notification`, rendered from the channel's template if it has one; the
+    /// subject and text of an email
+    pub fn body(&self, notification: &Notification) -> Result<Value, String> {
+        if let Some(template) = self.template() {
+            return template::render(template, notification);
+        }
+        Ok(match self {
+            Channel::Webhook { .. } => serde_json::to_value(notification).unwrap_or(Value::Null),
+            Channel::Slack { .. } => json!({ "text": format!("*{}*\n{}", notification.title, notification.message) }),
+            Channel::Discord { .. } => json!({
+                "embeds": [{
+                    "title": notification.title,
+                    "description": notification.message,
+                    "color": color(&notification.severity),
+                    "timestamp": format_rfc3339(notification.timestamp),
+                    "fields": [
+                        { "name": "Severity", "value": format!("{:?}", notification.severity), "inline": true },
+                        { "name": "Rule", "value": notification.rule, "inline": true },
+                    ],
+                }],
+            }),
+            Channel::Matrix { .. } => json!({
+                "msgtype": "m.text",
+                "body": format!("{}\n{}", notification.title, notification.message),
+                "format": "org.matrix.custom.html",
+                "formatted_body": format!("<strong>{}</strong><br>{}<br><em>{:?} · {}</em>",
+                    escape_html(&notification.title), escape_html(&notification.message), notification.severity, escape_html(&notification.rule)),
+            }),
+            Channel::Email(_) => json!({ "subject": email::subject(notification), "text": email::text(notification) }),
+        })
+    }
+
+    /// The request sending `body`; Matrix messages are PUT with a transaction id made from the
+    /// notification, so a retry of a message the homeserver did get isn't posted twice
+    fn request(&self, client: &reqwest::Client, notification: &Notification, body: &Value) -> Option<reqwest::RequestBuilder> {
+        let mut url = self.base_url()?;
+        let request = match self {
+            Channel::Webhook { headers, .. } => headers.iter().fold(client.post(url), |request, (name, value)| {
+                request.header(name.as_str(), value.expose().as_str())
+            }),
+            Channel::Slack { .. } | Channel::Discord { .. } => client.post(url),
+            Channel::Matrix { room, access_token, .. } => {
+                let digest = Sha256::digest(format!("{}\n{}\n{}", notification.rule, notification.key, notification.timestamp).as_bytes());
+                let transaction: String = digest.iter().take(12).map(|b| format!("{:02x}", b)).collect();
+                url.path_segments_mut().ok()?
+                    .pop_if_empty()
+                    .extend(["_matrix", "client", "v3", "rooms", room.as_str(), "send", "m.room.message", transaction.as_str()]);
+                client.put(url).bearer_auth(access_token.expose())
+            },
+            Channel::Email(_) => return None,
+        };
+        Some(request.json(body))
+    }
+}
+
+/// Discord embed colour of a severity
+fn color(severity: &AlertSeverity) -> u32 {
+    match severity {
+        AlertSeverity::Minor => 0x3498db,
+        AlertSeverity::Moderate => 0xf1c40f,
+        AlertSeverity::Severe => 0xe67e22,
+        AlertSeverity::Extreme => 0xe74c3c,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// How sending one notification to one channel went
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub attempts: u32,            // 0 when held back by a rate limit
+    pub error: Option<String>,    // Of the last attempt, None once delivered
+}
+
+/// Whether a failed attempt is worth repeating: rate limits and server errors are, other client
+/// errors (a deleted webhook, a malformed body) won't go away by themselves
+fn retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::REQUEST_TIMEOUT || status.is_server_error()
+}
+
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response.headers().get(header::RETRY_AFTER)?
+        .to_str().ok()?
+        .trim().parse::<f64>().ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(|secs| Duration::from_secs_f64(secs).min(MAX_RETRY_AFTER))
+}
+
+/// Sends `notification` to `channel`, retrying timeouts, connection failures, 429s and 5xx up to
+/// MAX_ATTEMPTS times. The wait starts at `backoff` and doubles after each attempt, or is the
+/// response's Retry-After if that's longer. Emails go through `email::deliver`.
+pub async fn deliver(client: &reqwest::Client, channel: &Channel, notification: &Notification, backoff: Duration) -> Delivery {
+    if let Channel::Email(email) = channel {
+        return email::deliver(email, notification, backoff).await;
+    }
+    // Templates are checked when the rules are loaded, so this only fails on unusual data
+    let body = match channel.body(notification) {
+        Ok(body) => body,
+        Err(error) => return Delivery { attempts: 1, error: Some(error) },
+    };
+    let mut wait = backoff;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let Some(request) = channel.request(client, notification, &body) else {
+            return Delivery { attempts, error: Some(format!("{} channel has no valid url", channel.kind())) };
+        };
+        let (error, retry, delay) = match request.send().await {
+            Ok(response) if response.status().is_success() => return Delivery { attempts, error: None },
+            Ok(response) => (format!("HTTP {}", response.status()), retryable(response.status()), retry_after(&response)),
+            // Without the URL, which would put the webhook's token in the log
+            Err(e) => (e.without_url().to_string(), true, None),
+        };
+        if !retry || attempts >= MAX_ATTEMPTS {
+            return Delivery { attempts, error: Some(error) };
+        }
+        tokio::time::sleep(delay.map_or(wait, |delay| delay.max(wait))).await;
+        wait *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::common::AlertSeverity;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn notification() -> Notification {
+        Notification {
+            rule: "bad-air".to_string(),
+            event: "threshold".to_string(),
+            key: "living-room".to_string(),
+            title: "pm25 above 35 at Living room".to_string(),
+            message: "pm25 is 48.2, above 35".to_string(),
+            severity: AlertSeverity::Moderate,
+            timestamp: 1_700_000_000,
+            data: json!({"device": "living-room", "value": 48.2}),
+        }
+    }
+
+    /// Answers each connection with the next of `statuses`, then stops listening
+    fn serve(statuses: Vec<u16>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for status in statuses {
+                let Ok((mut stream, _)) = listener.accept() else { return };
+                let mut buffer = [0u8; 4096];
+                let _ = stream.read(&mut buffer);
+                let _ = write!(stream, "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+            }
+        });
+        format!("http://{}/hooks/secret-token", address)
+    }
+
+    #[test]
+    fn test_channel_bodies() {
+        let channels: Vec<Channel> = serde_json::from_str(r#"[
+            {"kind": "webhook", "url": "https://example.com/hook", "headers": {"X-Token": "abc"}},
+            {"kind": "slack", "url": "https://hooks.slack.com/services/T0/B0/xyz"},
+            {"kind": "discord", "url": "https://discord.com/api/webhooks/1/xyz"}
+        ]"#).unwrap();
+        assert!(channels.iter().all(|channel| channel.validate().is_ok()));
+        assert_eq!(channels[1].host(), "hooks.slack.com");
+        assert!(!format!("{:?}", channels).contains("xyz"));
+
+        let notification = notification();
+        assert_eq!(channels[0].body(&notification).unwrap()["data"]["value"], 48.2);
+        assert_eq!(channels[1].body(&notification).unwrap()["text"], "*pm25 above 35 at Living room*\npm25 is 48.2, above 35");
+        let embed = &channels[2].body(&notification).unwrap()["embeds"][0];
+        assert_eq!(embed["title"], "pm25 above 35 at Living room");
+        assert_eq!(embed["color"], 0xf1c40f);
+        assert_eq!(embed["timestamp"], "2023-11-14T22:13:20Z");
+
+        let channel: Channel = serde_json::from_str(r#"{"kind": "slack", "url": "ftp://example.com"}"#).unwrap();
+        assert!(channel.validate().is_err());
+
+        let templated: Channel = serde_json::from_value(json!({
+            "kind": "slack", "url": "https://hooks.slack.com/services/T0/B0/xyz",
+            "template": "{\"blocks\": [{\"type\": \"section\", \"text\": {\"type\": \"mrkdwn\", \"text\": \"{{title}}: {{data.value}}\"}}]}",
+        })).unwrap();
+        assert_eq!(templated.validate(), Ok(()));
+        assert_eq!(templated.body(&notification).unwrap()["blocks"][0]["text"]["text"], "pm25 above 35 at Living room: 48.2");
+        let broken: Channel = serde_json::from_value(json!({"kind": "webhook", "url": "https://example.com/hook", "template": "{{title}}"})).unwrap();
+        assert!(broken.validate().unwrap_err().starts_with("webhook channel template didn't render JSON"));
+    }
+
+    #[test]
+    fn test_matrix_messages() {
+        let channel: Channel = serde_json::from_str(
+            r#"{"kind": "matrix", "homeserver": "https://matrix.example.org/", "room": "!AbCdEf:example.org", "access_token": "syt_token"}"#,
+        ).unwrap();
+        assert_eq!(channel.validate(), Ok(()));
+        assert_eq!(channel.host(), "matrix.example.org");
+
+        let mut notification = notification();
+        notification.message = "pm25 is 48.2 <above> 35".to_string();
+        let body = channel.body(&notification).unwrap();
+        assert_eq!(body["body"], "pm25 above 35 at Living room\npm25 is 48.2 <above> 35");
+        assert_eq!(body["formatted_body"], "<strong>pm25 above 35 at Living room</strong><br>pm25 is 48.2 &lt;above&gt; 35<br><em>Moderate · bad-air</em>");
+
+        let request = channel.request(&reqwest::Client::new(), &notification, &body).unwrap().build().unwrap();
+        assert_eq!(request.method(), reqwest::Method::PUT);
+        assert!(request.url().path().starts_with("/_matrix/client/v3/rooms/!AbCdEf:example.org/send/m.room.message/"));
+        assert_eq!(request.headers()[header::AUTHORIZATION], "Bearer syt_token");
+        // Retries reuse the transaction id
+        let again = channel.request(&reqwest::Client::new(), &notification, &body).unwrap().build().unwrap();
+        assert_eq!(again.url(), request.url());
+
+        let alias: Channel = serde_json::from_str(
+            r##"{"kind": "matrix", "homeserver": "https://matrix.example.org", "room": "#weather:example.org", "access_token": "syt_token"}"##,
+        ).unwrap();
+        assert!(alias.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_retries() {
+        let client = reqwest::Client::new();
+        let backoff = Duration::from_millis(10);
+
+        let channel = Channel::Slack { url: serve(vec![503, 429, 200]).into(), template: None };
+        assert_eq!(deliver(&client, &channel, &notification(), backoff).await, Delivery { attempts: 3, error: None });
+
+        // Client errors aren't retried
+        let channel = Channel::Discord { url: serve(vec![404, 200]).into(), template: None };
+        let delivery = deliver(&client, &channel, &notification(), backoff).await;
+        assert_eq!(delivery.attempts, 1);
+        assert!(delivery.error.unwrap().contains("404"));
+
+        // Nothing listening: every attempt fails, and the error doesn't give the URL away
+        let channel = Channel::Webhook { url: serve(vec![]).into(), headers: HashMap::new(), template: None };
+        let delivery = deliver(&client, &channel, &notification(), backoff).await;
+        assert_eq!(delivery.attempts, MAX_ATTEMPTS);
+        assert!(!delivery.error.unwrap().contains("secret-token"));
+    }
+}
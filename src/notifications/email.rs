@@ -0,0 +1,505 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::channels::{Delivery, MAX_ATTEMPTS};
+use super::Notification;
+use crate::secret::Secret;
+use crate::utils::time::{format_rfc2822, format_rfc3339};
+
+// The `email` channel sends notifications through an SMTP server to a list of recipients:
+//
+// { "kind": "email", "smtp_host": "smtp.example.com", "username": "alerts@example.com", "password": "...",
+//   "from": "Jupiter <alerts@example.com>", "to": ["ops@example.com", "facilities@example.com"] }
+//
+// `security` is "starttls" (the default, port 587), "tls" (port 465) or "none" (port 25, for a
+// relay on the local network); `smtp_port` overrides the port. Certificates are checked against
+// the roots of the TLS backend the build uses. Each channel sends at most `max_per_hour` emails;
+// notifications past that are held back (recorded as such in the delivery log), and the next
+// email that goes out says how many were.
+
+/// Connect, read and write timeout of the SMTP connection
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Name given in EHLO
+const CLIENT_NAME: &str = "jupiter";
+
+fn default_max_per_hour() -> u32 {
+    10
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    #[default]
+    Starttls,
+    Tls,
+    None,
+}
+
+impl Security {
+    fn default_port(&self) -> u16 {
+        match self {
+            Security::Starttls => 587,
+            Security::Tls => 465,
+            Security::None => 25,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Email {
+    pub smtp_host: String,
+    #[serde(default)]
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
+    pub security: Security,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<Secret<String>>,
+    pub from: String,       // Address, optionally with a name: "Jupiter <alerts@example.com>"
+    pub to: Vec<String>,
+    #[serde(default = "default_max_per_hour")]
+    pub max_per_hour: u32,
+}
+
+impl Email {
+    pub fn port(&self) -> u16 {
+        self.smtp_port.unwrap_or_else(|| self.security.default_port())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.smtp_host.is_empty() || self.smtp_host.contains(|c: char| c.is_whitespace() || c == '/' || c == ':') {
+            return Err(format!("email smtp_host must be a host name, got '{}'", self.smtp_host));
+        }
+        if self.from.contains(char::is_control) || !valid_address(address(&self.from)) {
+            return Err(format!("email from must be an address, got '{}'", self.from));
+        }
+        if self.to.is_empty() {
+            return Err("email channel needs at least one to address".to_string());
+        }
+        if let Some(to) = self.to.iter().find(|to| !valid_address(to)) {
+            return Err(format!("email to must be addresses, got '{}'", to));
+        }
+        if self.username.is_some() != self.password.is_some() {
+            return Err("email username and password go together".to_string());
+        }
+        if self.max_per_hour == 0 {
+            return Err("email max_per_hour must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// What the rate limit is counted per: a server and list of recipients
+    fn throttle_key(&self) -> String {
+        format!("{}:{}/{}", self.smtp_host, self.port(), self.to.join(","))
+    }
+}
+
+/// The address of a mailbox, "alerts@example.com" of "Jupiter <alerts@example.com>"
+fn address(mailbox: &str) -> &str {
+    mailbox.rsplit_once('<')
+        .and_then(|(_, rest)| rest.strip_suffix('>'))
+        .unwrap_or(mailbox)
+        .trim()
+}
+
+fn valid_address(address: &str) -> bool {
+    !address.contains(|c: char| c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | ','))
+        && matches!(address.split_once('@'), Some((local, domain)) if !local.is_empty() && !domain.is_empty())
+}
+
+pub fn subject(notification: &Notification) -> String {
+    format!("[{:?}] {}", notification.severity, notification.title)
+}
+
+pub fn text(notification: &Notification) -> String {
+    format!("{}\n\nSeverity: {:?}\nRule: {}\nTime: {}\n", notification.message, notification.severity,
+        notification.rule, format_rfc3339(notification.timestamp))
+}
+
+/// Standard base64 with padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk.iter().enumerate().fold(0u32, |triple, (i, byte)| triple | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            encoded.push(match i <= chunk.len() {
+                true => ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize] as char,
+                false => '=',
+            });
+        }
+    }
+    encoded
+}
+
+/// A header value as is if it's plain ASCII, else as an RFC 2047 encoded word
+fn encode_header(value: &str) -> String {
+    let value = value.replace(char::is_control, " ");
+    if value.is_ascii() {
+        value
+    } else {
+        format!("=?UTF-8?B?{}?=", base64(value.as_bytes()))
+    }
+}
+
+/// The message sent for `notification`, noting the `held` notifications held back before it.
+/// The body is base64 encoded, so no line of it needs dot-stuffing.
+pub fn message(email: &Email, notification: &Notification, held: u32) -> String {
+    let mut text = text(notification);
+    if held > 0 {
+        text.push_str(&format!("\n{} more notification(s) were held back since the last email, which are limited to {} an hour.\n",
+            held, email.max_per_hour));
+    }
+    let encoded = base64(text.replace('\n', "\r\n").as_bytes());
+    let body: Vec<&str> = encoded.as_bytes().chunks(76).map(|line| std::str::from_utf8(line).unwrap_or_default()).collect();
+    let headers = [
+        format!("From: {}", email.from),
+        format!("To: {}", email.to.join(", ")),
+        format!("Subject: {}", encode_header(&subject(notification))),
+        format!("Date: {}", format_rfc2822(notification.timestamp)),
+        "MIME-Version: 1.0".to_string(),
+        "Content-Type: text/plain; charset=utf-8".to_string(),
+        "Content-Transfer-Encoding: base64".to_string(),
+    ];
+    format!("{}\r\n\r\n{}\r\n", headers.join("\r\n"), body.join("\r\n"))
+}
+
+/// Emails sent per channel in the last hour, and how many were held back since the last one
+#[derive(Debug, Default)]
+pub struct Throttle {
+    channels: HashMap<String, (VecDeque<i64>, u32)>,
+}
+
+impl Throttle {
+    /// Whether another email may go out at `now`, and if so how many were held back before it
+    pub fn admit(&mut self, key: &str, max_per_hour: u32, now: i64) -> Option<u32> {
+        let (sent, held) = self.channels.entry(key.to_string()).or_default();
+        while sent.front().is_some_and(|at| now - at >= 3600) {
+            sent.pop_front();
+        }
+        if sent.len() >= max_per_hour as usize {
+            *held += 1;
+            return None;
+        }
+        sent.push_back(now);
+        Some(std::mem::take(held))
+    }
+}
+
+static THROTTLE: Lazy<Mutex<Throttle>> = Lazy::new(|| Mutex::new(Throttle::default()));
+
+#[derive(Debug)]
+enum SmtpError {
+    Io(io::Error),
+    Reply(u16, String),     // A reply other than the one expected
+    Tls(String),
+}
+
+impl SmtpError {
+    /// Dropped connections and 4xx replies (greylisting, a full queue) are worth retrying,
+    /// 5xx replies and TLS failures won't go away by themselves
+    fn retryable(&self) -> bool {
+        match self {
+            SmtpError::Io(_) => true,
+            SmtpError::Reply(code, _) => (400..500).contains(code),
+            SmtpError::Tls(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for SmtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmtpError::Io(e) => write!(f, "{}", e),
+            SmtpError::Reply(code, text) => write!(f, "SMTP {} {}", code, text.replace('\n', " ")),
+            SmtpError::Tls(message) => write!(f, "TLS: {}", message),
+        }
+    }
+}
+
+impl From<io::Error> for SmtpError {
+    fn from(e: io::Error) -> Self {
+        SmtpError::Io(e)
+    }
+}
+
+trait Transport: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Transport for T {}
+
+fn read_line(stream: &mut impl Read) -> io::Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    while line.last() != Some(&b'\n') {
+        if stream.read(&mut byte)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by the SMTP server"));
+        }
+        if line.len() >= 4096 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "SMTP reply line too long"));
+        }
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+/// Reads a reply, all lines of a multiline one, and checks its code is one of `expected`
+fn expect_reply(stream: &mut impl Read, expected: &[u16]) -> Result<String, SmtpError> {
+    let mut text = Vec::new();
+    loop {
+        let line = read_line(stream)?;
+        let code = line.get(..3).and_then(|code| code.parse::<u16>().ok())
+            .ok_or_else(|| SmtpError::Reply(0, format!("malformed reply '{}'", line)))?;
+        text.push(line.get(4..).unwrap_or_default().to_string());
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return match expected.contains(&code) {
+                true => Ok(text.join("\n")),
+                false => Err(SmtpError::Reply(code, text.join("\n"))),
+            };
+        }
+    }
+}
+
+fn command(stream: &mut (impl Read + Write), line: &str, expected: &[u16]) -> Result<String, SmtpError> {
+    stream.write_all(format!("{}\r\n", line).as_bytes())?;
+    stream.flush()?;
+    expect_reply(stream, expected)
+}
+
+#[cfg(not(feature = "rustls"))]
+fn tls_connect(host: &str, tcp: TcpStream) -> Result<Box<dyn Transport>, SmtpError> {
+    use openssl::ssl::{SslConnector, SslMethod};
+
+    let connector = SslConnector::builder(SslMethod::tls()).map_err(|e| SmtpError::Tls(e.to_string()))?.build();
+    let stream = connector.connect(host, tcp).map_err(|e| SmtpError::Tls(e.to_string()))?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(feature = "rustls")]
+fn tls_connect(host: &str, tcp: TcpStream) -> Result<Box<dyn Transport>, SmtpError> {
+    use rustls::{ClientConfig, ClientConnection, OwnedTrustAnchor, RootCertStore, ServerName, StreamOwned};
+    use std::sync::Arc;
+
+    let mut roots = RootCertStore::empty();
+    roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+    }));
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = ServerName::try_from(host).map_err(|e| SmtpError::Tls(e.to_string()))?;
+    let connection = ClientConnection::new(Arc::new(config), name).map_err(|e| SmtpError::Tls(e.to_string()))?;
+    Ok(Box::new(StreamOwned::new(connection, tcp)))
+}
+
+/// EHLO, login and the message, once the server has greeted and any TLS is up
+fn transaction(stream: &mut (impl Read + Write), email: &Email, message: &str) -> Result<(), SmtpError> {
+    command(stream, &format!("EHLO {}", CLIENT_NAME), &[250])?;
+    if let (Some(username), Some(password)) = (&email.username, &email.password) {
+        let credentials = base64(format!("\0{}\0{}", username, password.expose()).as_bytes());
+        command(stream, &format!("AUTH PLAIN {}", credentials), &[235])?;
+    }
+    command(stream, &format!("MAIL FROM:<{}>", address(&email.from)), &[250])?;
+    for to in &email.to {
+        command(stream, &format!("RCPT TO:<{}>", to), &[250, 251])?;
+    }
+    command(stream, "DATA", &[354])?;
+    stream.write_all(message.as_bytes())?;
+    command(stream, ".", &[250])?;
+    // The message is accepted; a server hanging up without answering QUIT doesn't matter
+    let _ = command(stream, "QUIT", &[221]);
+    Ok(())
+}
+
+/// Sends one message over a new connection
+fn send(email: &Email, message: &str) -> Result<(), SmtpError> {
+    let address = (email.smtp_host.as_str(), email.port()).to_socket_addrs()?.next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} didn't resolve", email.smtp_host)))?;
+    let mut tcp = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    tcp.set_read_timeout(Some(TIMEOUT))?;
+    tcp.set_write_timeout(Some(TIMEOUT))?;
+    match email.security {
+        Security::None => {
+            expect_reply(&mut tcp, &[220])?;
+            transaction(&mut tcp, email, message)
+        },
+        Security::Tls => {
+            let mut stream = tls_connect(&email.smtp_host, tcp)?;
+            expect_reply(&mut stream, &[220])?;
+            transaction(&mut stream, email, message)
+        },
+        Security::Starttls => {
+            expect_reply(&mut tcp, &[220])?;
+            let extensions = command(&mut tcp, &format!("EHLO {}", CLIENT_NAME), &[250])?;
+            if !extensions.lines().any(|line| line.trim().eq_ignore_ascii_case("STARTTLS")) {
+                return Err(SmtpError::Tls(format!("{} doesn't offer STARTTLS", email.smtp_host)));
+            }
+            command(&mut tcp, "STARTTLS", &[220])?;
+            let mut stream = tls_connect(&email.smtp_host, tcp)?;
+            transaction(&mut stream, email, message)
+        },
+    }
+}
+
+/// Emails `notification` unless the channel's hourly limit is used up, retrying dropped
+/// connections and 4xx replies like the other channels retry their requests
+pub async fn deliver(email: &Email, notification: &Notification, backoff: Duration) -> Delivery {
+    let admitted = THROTTLE.lock()
+        .map(|mut throttle| throttle.admit(&email.throttle_key(), email.max_per_hour, notification.timestamp))
+        .unwrap_or(Some(0));
+    let Some(held) = admitted else {
+        return Delivery { attempts: 0, error: Some(format!("held back, {} emails sent in the last hour", email.max_per_hour)) };
+    };
+    let message = message(email, notification, held);
+    let mut wait = backoff;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let (channel, body) = (email.clone(), message.clone());
+        let sent = tokio::task::spawn_blocking(move || send(&channel, &body)).await
+            .unwrap_or_else(|e| Err(SmtpError::Io(io::Error::other(e.to_string()))));
+        match sent {
+            Ok(()) => return Delivery { attempts, error: None },
+            Err(e) if !e.retryable() || attempts >= MAX_ATTEMPTS => return Delivery { attempts, error: Some(e.to_string()) },
+            Err(_) => {},
+        }
+        tokio::time::sleep(wait).await;
+        wait *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::common::AlertSeverity;
+    use serde_json::json;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    fn notification() -> Notification {
+        Notification {
+            rule: "bad-air".to_string(),
+            event: "threshold".to_string(),
+            key: "living-room".to_string(),
+            title: "CO2 above 1500 at Living room".to_string(),
+            message: "Living room co2 is 1720.0, above 1500".to_string(),
+            severity: AlertSeverity::Severe,
+            timestamp: 1_700_000_000,
+            data: json!({}),
+        }
+    }
+
+    fn email(json: &str) -> Email {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// An SMTP server for one connection, answering each command with the next of `replies`
+    /// after its greeting; sends back what the client wrote
+    fn serve(replies: Vec<&'static str>) -> (u16, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let Ok((mut stream, _)) = listener.accept() else { return };
+            let mut reader = io::BufReader::new(stream.try_clone().unwrap());
+            let mut received = String::new();
+            let _ = stream.write_all(b"220 mail.test ESMTP\r\n");
+            let mut in_data = false;
+            for reply in replies {
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                        let _ = tx.send(received);
+                        return;
+                    }
+                    received.push_str(&line);
+                    if !in_data || line == ".\r\n" {
+                        break;
+                    }
+                }
+                in_data = reply.starts_with("354");
+                let _ = stream.write_all(format!("{}\r\n", reply).as_bytes());
+            }
+            let _ = tx.send(received);
+        });
+        (port, rx)
+    }
+
+    #[test]
+    fn test_email_settings() {
+        let channel = email(r#"{"smtp_host": "smtp.example.com", "username": "alerts@example.com", "password": "hunter2",
+            "from": "Jupiter <alerts@example.com>", "to": ["ops@example.com"]}"#);
+        assert_eq!(channel.validate(), Ok(()));
+        assert_eq!((channel.security, channel.port(), channel.max_per_hour), (Security::Starttls, 587, 10));
+        assert_eq!(address(&channel.from), "alerts@example.com");
+        assert!(!format!("{:?}", channel).contains("hunter2"));
+
+        let relay = email(r#"{"smtp_host": "relay.lan", "security": "none", "from": "jupiter@lan", "to": ["me@lan"]}"#);
+        assert_eq!((relay.validate(), relay.port()), (Ok(()), 25));
+        assert!(Email { to: vec!["ops@example.com\r\nBcc: x@evil.test".to_string()], ..relay.clone() }.validate().is_err());
+        assert!(Email { to: Vec::new(), ..relay.clone() }.validate().is_err());
+        assert!(Email { username: Some("me".to_string()), ..relay.clone() }.validate().is_err());
+        assert!(Email { smtp_host: "https://smtp.example.com".to_string(), ..relay }.validate().is_err());
+    }
+
+    #[test]
+    fn test_message() {
+        assert_eq!(base64(b"\0user\0pass"), "AHVzZXIAcGFzcw==");
+        assert_eq!(base64(b"ab"), "YWI=");
+        assert_eq!(encode_header("Frost at 0°C"), "=?UTF-8?B?RnJvc3QgYXQgMMKwQw==?=");
+
+        let channel = email(r#"{"smtp_host": "relay.lan", "from": "Jupiter <jupiter@lan>", "to": ["a@lan", "b@lan"], "max_per_hour": 5}"#);
+        let message = message(&channel, &notification(), 3);
+        let (headers, body) = message.split_once("\r\n\r\n").unwrap();
+        assert!(headers.contains("To: a@lan, b@lan\r\nSubject: [Severe] CO2 above 1500 at Living room\r\nDate: Tue, 14 Nov 2023 22:13:20 +0000"));
+        assert!(body.lines().all(|line| line.len() <= 77));
+        assert!(body.starts_with(&base64(b"Living room co2 is 1720.0, above 1500\r\n")[..40]));
+        assert!(message.ends_with("\r\n"));
+    }
+
+    #[test]
+    fn test_throttle() {
+        let mut throttle = Throttle::default();
+        assert_eq!(throttle.admit("relay", 2, 1000), Some(0));
+        assert_eq!(throttle.admit("relay", 2, 1100), Some(0));
+        assert_eq!(throttle.admit("relay", 2, 1200), None);
+        assert_eq!(throttle.admit("relay", 2, 1300), None);
+        // Other channels have their own limit
+        assert_eq!(throttle.admit("other", 2, 1300), Some(0));
+        // An hour after the first email there is room again, and it counts the ones held back
+        assert_eq!(throttle.admit("relay", 2, 4600), Some(2));
+        assert_eq!(throttle.admit("relay", 2, 4650), None);
+    }
+
+    #[test]
+    fn test_smtp_conversation() {
+        let (port, received) = serve(vec!["250-mail.test\r\n250 AUTH PLAIN", "235 ok", "250 ok", "250 ok", "251 forwarding", "354 go ahead", "250 queued", "221 bye"]);
+        let channel = Email {
+            smtp_port: Some(port),
+            ..email(r#"{"smtp_host": "127.0.0.1", "security": "none", "username": "user", "password": "pass",
+                "from": "Jupiter <jupiter@lan>", "to": ["a@lan", "b@lan"]}"#)
+        };
+        let message = message(&channel, &notification(), 0);
+        send(&channel, &message).unwrap();
+        let received = received.recv().unwrap();
+        let expected = format!("EHLO jupiter\r\nAUTH PLAIN AHVzZXIAcGFzcw==\r\nMAIL FROM:<jupiter@lan>\r\nRCPT TO:<a@lan>\r\nRCPT TO:<b@lan>\r\nDATA\r\n{}.\r\nQUIT\r\n", message);
+        assert_eq!(received, expected);
+
+        // A permanent failure isn't retried; a temporary one is
+        let (port, _received) = serve(vec!["250 mail.test", "550 no such user"]);
+        let error = send(&Email { smtp_port: Some(port), username: None, password: None, ..channel.clone() }, &message).unwrap_err();
+        assert!(!error.retryable());
+        assert_eq!(error.to_string(), "SMTP 550 no such user");
+        let (port, _received) = serve(vec!["250 mail.test", "451 try again later"]);
+        assert!(send(&Email { smtp_port: Some(port), username: None, password: None, ..channel }, &message).unwrap_err().retryable());
+    }
+}
@@ -0,0 +1,634 @@
+// Notifications sent to webhooks when something needs attention. Rules are read from the JSON
+// file named by NOTIFICATIONS_CONFIG, e.g.
+//
+// {
+//   "rules": [
+//     { "name": "weather", "event": "alert", "min_severity": "Severe",
+//       "channels": [{ "kind": "slack", "url": "https://hooks.slack.com/services/..." },
+//                    { "kind": "email", "smtp_host": "smtp.example.com", "from": "alerts@example.com", "to": ["ops@example.com"] }] },
+//     { "name": "bad-air", "event": "threshold", "field": "pm25", "above": 35, "device": "indoor",
+//       "cooldown_secs": 3600, "channels": [{ "kind": "discord", "url": "https://discord.com/api/webhooks/..." }] },
+//     { "name": "offline", "event": "stale_sensor", "after_secs": 1800,
+//       "channels": [{ "kind": "webhook", "url": "https://example.com/hook", "headers": { "X-Token": "..." } }] }
+//   ]
+// }
+//
+// `alert` rules fire for each new weather alert from the providers, `threshold` rules when the
+// latest reading of a homebrew device crosses `above` or `below`, and `stale_sensor` rules when
+// a device that used to report hasn't for `after_secs`. `device` narrows a homebrew rule to one
+// device id or a placement (indoor, outdoor, ...); paused and archived devices are left out.
+//
+// The "notifications" job checks the rules every minute. A rule fires once when its condition
+// starts and again only after it has cleared, and not more often than every `cooldown_secs` for
+// the same alert or device. Each channel of the rule gets the notification, retried with backoff
+// (see `channels` for the kinds of channel), and every delivery is recorded in the combo database's
+// `notification_deliveries` table for DELIVERY_LOG_SECS (GET /api/admin/notifications). Which
+// conditions are active is kept in memory, so after a restart conditions still active fire again.
+pub mod channels;
+pub mod email;
+pub mod template;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::db_pool::{combo_client, get_combo_pool};
+use crate::devices::{self, Device, DeviceState, DeviceStatus};
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::metrics::{self, Counter};
+use crate::provider::combo_enhanced::ComboProvider;
+use crate::provider::common::{Alert, AlertSeverity, WeatherProvider};
+use crate::provider::homebrew::{self, WeatherReport};
+use crate::triggers;
+use crate::utils::time::safe_timestamp_with_fallback;
+use channels::{Channel, Delivery};
+
+const DELIVERY_LOG_SECS: i64 = 30 * 24 * 3600;
+
+/// First wait between delivery attempts, doubling after each
+const BACKOFF: Duration = Duration::from_secs(2);
+
+static NOTIFICATIONS_SENT: Lazy<Arc<Counter>> = Lazy::new(|| metrics::counter(
+    "jupiter_notifications_total", "Notification deliveries by channel kind and status (delivered, failed or held_back)", &["channel", "status"],
+));
+
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default()
+});
+
+fn default_severity() -> AlertSeverity {
+    AlertSeverity::Minor
+}
+
+fn default_stale_secs() -> i64 {
+    1800
+}
+
+/// What makes a rule fire
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Trigger {
+    Alert {
+        #[serde(default = "default_severity")]
+        min_severity: AlertSeverity,
+    },
+    Threshold {
+        field: String,
+        #[serde(default)]
+        above: Option<f64>,
+        #[serde(default)]
+        below: Option<f64>,
+        #[serde(default)]
+        device: Option<String>,
+        #[serde(default = "default_severity")]
+        severity: AlertSeverity,
+    },
+    StaleSensor {
+        #[serde(default = "default_stale_secs")]
+        after_secs: i64,
+        #[serde(default)]
+        device: Option<String>,
+    },
+}
+
+impl Trigger {
+    pub fn event(&self) -> &'static str {
+        match self {
+            Trigger::Alert { .. } => "alert",
+            Trigger::Threshold { .. } => "threshold",
+            Trigger::StaleSensor { .. } => "stale_sensor",
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Trigger::Alert { .. } => Ok(()),
+            Trigger::Threshold { field, above, below, .. } => {
+                if !WeatherReport::is_value_field(field) {
+                    return Err(format!("unknown report field '{}'", field));
+                }
+                match (above, below) {
+                    (None, None) => Err(format!("{} threshold needs above or below", field)),
+                    (Some(above), Some(below)) if above < below =>
+                        Err(format!("{} threshold would always fire: above {} is less than below {}", field, above, below)),
+                    _ => Ok(()),
+                }
+            },
+            Trigger::StaleSensor { after_secs, .. } if *after_secs < 60 => Err("after_secs must be at least 60".to_string()),
+            Trigger::StaleSensor { .. } => Ok(()),
+        }
+    }
+
+    fn uses_homebrew(&self) -> bool {
+        !matches!(self, Trigger::Alert { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(flatten)]
+    pub trigger: Trigger,
+    pub channels: Vec<Channel>,
+    #[serde(default)]
+    pub cooldown_secs: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl NotificationsConfig {
+    pub fn from_file(path: &str) -> JupiterResult<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Settings that are wrong, e.g. ("NOTIFICATIONS_CONFIG.rules.bad-air", "unknown report field 'pm'")
+    pub fn problems(&self) -> Vec<(String, String)> {
+        let mut problems = Vec::new();
+        let mut names = HashSet::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            let setting = format!("NOTIFICATIONS_CONFIG.rules.{}", if rule.name.is_empty() { index.to_string() } else { rule.name.clone() });
+            if rule.name.is_empty() {
+                problems.push((setting.clone(), "rule needs a name".to_string()));
+            } else if !names.insert(rule.name.as_str()) {
+                problems.push((setting.clone(), "rule names must be unique".to_string()));
+            }
+            if let Err(message) = rule.trigger.validate() {
+                problems.push((setting.clone(), message));
+            }
+            if rule.channels.is_empty() {
+                problems.push((setting.clone(), "rule needs at least one channel".to_string()));
+            }
+            for channel in &rule.channels {
+                if let Err(message) = channel.validate() {
+                    problems.push((setting.clone(), message));
+                }
+            }
+            if rule.cooldown_secs < 0 {
+                problems.push((setting, "cooldown_secs can't be negative".to_string()));
+            }
+        }
+        problems
+    }
+
+    /// Loads and checks the file named by NOTIFICATIONS_CONFIG; without it there are no rules
+    pub fn from_env() -> Result<Self, Vec<(String, String)>> {
+        let config = match env::var("NOTIFICATIONS_CONFIG") {
+            Ok(path) if !path.is_empty() => Self::from_file(&path)
+                .map_err(|e| vec![("NOTIFICATIONS_CONFIG".to_string(), format!("could not be loaded: {}", e))])?,
+            _ => Self::default(),
+        };
+        match config.problems() {
+            problems if problems.is_empty() => Ok(config),
+            problems => Err(problems),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    pub fn uses_alerts(&self) -> bool {
+        self.rules.iter().any(|rule| !rule.trigger.uses_homebrew())
+    }
+
+    pub fn uses_homebrew(&self) -> bool {
+        self.rules.iter().any(|rule| rule.trigger.uses_homebrew())
+    }
+}
+
+pub static NOTIFICATIONS: Lazy<NotificationsConfig> = Lazy::new(|| {
+    NotificationsConfig::from_env().unwrap_or_else(|problems| {
+        for (setting, message) in problems {
+            log::error!("Invalid {}, notifications disabled: {}", setting, message);
+        }
+        NotificationsConfig::default()
+    })
+});
+
+/// What is sent to a rule's channels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub rule: String,
+    pub event: String,
+    pub key: String,            // The alert or device the notification is about
+    pub title: String,
+    pub message: String,
+    pub severity: AlertSeverity,
+    pub timestamp: i64,
+    pub data: Value,
+}
+
+/// What the rules are checked against. `alerts` is None when they couldn't be fetched, which
+/// leaves alert rules as they were.
+#[derive(Debug, Clone, Default)]
+pub struct Observations {
+    pub alerts: Option<Vec<Alert>>,
+    pub readings: Vec<(Device, WeatherReport)>,   // Latest reading of each active device
+    pub statuses: Vec<DeviceStatus>,               // Of the active devices
+}
+
+/// A condition a rule fires for
+struct Condition {
+    title: String,
+    message: String,
+    severity: AlertSeverity,
+    data: Value,
+}
+
+fn device_matches(filter: &Option<String>, id: &str, device_type: &str) -> bool {
+    match filter {
+        Some(filter) if devices::PLACEMENTS.contains(&filter.as_str()) => device_type == filter,
+        Some(filter) => id == filter,
+        None => true,
+    }
+}
+
+/// The alerts or devices a rule could check, with the condition of each that holds
+struct Checked {
+    keys: Vec<(String, Option<Condition>)>,
+    complete: bool,     // Keys not listed have cleared (alerts no longer issued), rather than not been observed
+}
+
+impl Rule {
+    fn check(&self, observed: &Observations, now: i64) -> Option<Checked> {
+        match &self.trigger {
+            Trigger::Alert { min_severity } => {
+                let alerts = observed.alerts.as_ref()?;
+                let checked = alerts.iter()
+                    .filter(|alert| alert.severity >= *min_severity)
+                    .map(|alert| (triggers::alert_key(alert), Some(Condition {
+                        title: alert.title.clone(),
+                        message: alert.description.clone(),
+                        severity: alert.severity.clone(),
+                        data: json!(alert),
+                    })))
+                    .collect();
+                Some(Checked { keys: checked, complete: true })
+            },
+            Trigger::Threshold { field, above, below, device, severity } => {
+                let checked = observed.readings.iter()
+                    .filter(|(d, _)| device_matches(device, &d.id, &d.device_type))
+                    .filter_map(|(d, report)| {
+                        let value = report.value(field)?;
+                        let (side, limit) = match (above, below) {
+                            (Some(above), _) if value > *above => ("above", *above),
+                            (_, Some(below)) if value < *below => ("below", *below),
+                            _ => return Some((d.id.clone(), None)),
+                        };
+                        Some((d.id.clone(), Some(Condition {
+                            title: format!("{} {} {} at {}", field, side, limit, d.name),
+                            message: format!("{} {} is {:.1}, {} {}", d.name, field, value, side, limit),
+                            severity: severity.clone(),
+                            data: json!({"device": d.id, "name": d.name, "field": field, "value": value, side: limit, "timestamp": report.timestamp}),
+                        })))
+                    })
+                    .collect();
+                Some(Checked { keys: checked, complete: false })
+            },
+            Trigger::StaleSensor { after_secs, device } => {
+                let checked = observed.statuses.iter()
+                    .filter(|status| device_matches(device, &status.id, &status.device_type))
+                    .filter_map(|status| {
+                        // Devices that never reported aren't missing anything yet
+                        let last_seen = status.last_seen?;
+                        let silent = now - last_seen;
+                        Some((status.id.clone(), (silent >= *after_secs).then(|| Condition {
+                            title: format!("{} stopped reporting", status.name),
+                            message: format!("No readings from {} for {} minutes", status.name, silent / 60),
+                            severity: AlertSeverity::Moderate,
+                            data: json!({"device": status.id, "name": status.name, "last_seen": last_seen}),
+                        })))
+                    })
+                    .collect();
+                Some(Checked { keys: checked, complete: false })
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RuleState {
+    active: HashSet<String>,
+    last_sent: HashMap<String, i64>,
+}
+
+/// Which conditions each rule has already fired for
+#[derive(Debug, Default)]
+pub struct Notifier {
+    rules: HashMap<String, RuleState>,
+}
+
+impl Notifier {
+    /// Notifications for the conditions of `rules` that started since the last check
+    pub fn evaluate(&mut self, rules: &[Rule], observed: &Observations, now: i64) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        for rule in rules {
+            let Some(checked) = rule.check(observed, now) else { continue };
+            let state = self.rules.entry(rule.name.clone()).or_default();
+            if checked.complete {
+                let listed: HashSet<&String> = checked.keys.iter().map(|(key, _)| key).collect();
+                state.active.retain(|key| listed.contains(key));
+            }
+            for (key, condition) in checked.keys {
+                let Some(condition) = condition else {
+                    state.active.remove(&key);
+                    continue;
+                };
+                if !state.active.insert(key.clone()) {
+                    continue;
+                }
+                if state.last_sent.get(&key).is_some_and(|sent| now - sent < rule.cooldown_secs) {
+                    continue;
+                }
+                state.last_sent.insert(key.clone(), now);
+                notifications.push(Notification {
+                    rule: rule.name.clone(),
+                    event: rule.trigger.event().to_string(),
+                    key,
+                    title: condition.title,
+                    message: condition.message,
+                    severity: condition.severity,
+                    timestamp: now,
+                    data: condition.data,
+                });
+            }
+        }
+        notifications
+    }
+}
+
+static NOTIFIER: Lazy<Mutex<Notifier>> = Lazy::new(|| Mutex::new(Notifier::default()));
+
+/// Alerts, latest readings and statuses the configured rules need
+async fn observe(config: &NotificationsConfig, providers: Option<(&ComboProvider, &str)>, homebrew_config: Option<&homebrew::Config>, now: i64) -> Observations {
+    let mut observed = Observations::default();
+    if let (true, Some((providers, location))) = (config.uses_alerts(), providers) {
+        match providers.get_alerts(location).await {
+            Ok(alerts) => observed.alerts = Some(alerts),
+            Err(e) => log::warn!("[notifications] Failed to fetch alerts: {}", e),
+        }
+    }
+    if let (true, Some(homebrew_config)) = (config.uses_homebrew(), homebrew_config) {
+        let registry = homebrew_config.devices.snapshot();
+        let active: Vec<&Device> = registry.devices.iter().filter(|d| d.state == DeviceState::Active).collect();
+        for device in &active {
+            match homebrew::latest_reading(homebrew_config, &device.id, now).await {
+                Ok(Some(report)) => observed.readings.push(((*device).clone(), report)),
+                Ok(None) => {},
+                Err(e) => log::warn!("[notifications] Failed to read the latest reading of {}: {}", device.id, e),
+            }
+        }
+        match homebrew::statuses_of(homebrew_config, &active).await {
+            Ok(statuses) => observed.statuses = statuses,
+            Err(e) => log::warn!("[notifications] Failed to read device statuses: {}", e),
+        }
+    }
+    observed
+}
+
+/// Checks the rules and sends what they fire for; run every minute by the "notifications" job
+pub async fn run(providers: Option<(&ComboProvider, &str)>, homebrew_config: Option<&homebrew::Config>) -> JupiterResult<()> {
+    let config = &*NOTIFICATIONS;
+    let now = safe_timestamp_with_fallback();
+    let observed = observe(config, providers, homebrew_config, now).await;
+    let notifications = NOTIFIER.lock()
+        .map(|mut notifier| notifier.evaluate(&config.rules, &observed, now))
+        .map_err(|_| JupiterError::LockError("Notifier state poisoned".to_string()))?;
+
+    let sends = notifications.iter().flat_map(|notification| {
+        let rule = config.rules.iter().find(|rule| rule.name == notification.rule);
+        rule.into_iter().flat_map(|rule| rule.channels.iter()).map(move |channel| async move {
+            let delivery = channels::deliver(&CLIENT, channel, notification, BACKOFF).await;
+            (notification, channel, delivery)
+        })
+    });
+    let mut failed = 0;
+    for (notification, channel, delivery) in futures_util::future::join_all(sends).await {
+        let status = match (&delivery.error, delivery.attempts) {
+            (None, _) => "delivered",
+            (Some(_), 0) => "held_back",
+            (Some(_), _) => "failed",
+        };
+        NOTIFICATIONS_SENT.inc(&[channel.kind(), status]);
+        if delivery.attempts == 0 {
+            log::info!("[notifications] Held back '{}' to {} ({}): {}",
+                notification.title, channel.kind(), channel.host(), delivery.error.as_deref().unwrap_or_default());
+        } else if let Some(error) = &delivery.error {
+            failed += 1;
+            log::warn!("[notifications] Failed to send '{}' to {} ({}) after {} attempt(s): {}",
+                notification.title, channel.kind(), channel.host(), delivery.attempts, error);
+        }
+        if let Err(e) = record(notification, channel, &delivery).await {
+            log::warn!("[notifications] Failed to record a delivery: {}", e);
+        }
+    }
+    prune(now).await?;
+    match failed {
+        0 => Ok(()),
+        failed => Err(JupiterError::ConnectionError(format!("{} notification delivery(s) failed", failed))),
+    }
+}
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.notification_deliveries (
+        id serial NOT NULL,
+        rule varchar NOT NULL,
+        event varchar NOT NULL,
+        event_key varchar NOT NULL,
+        title TEXT NOT NULL,
+        channel varchar NOT NULL,
+        host varchar NOT NULL,
+        delivered BOOL DEFAULT false,
+        attempts INTEGER DEFAULT 0,
+        error TEXT NULL,
+        sent_at BIGINT DEFAULT 0,
+        CONSTRAINT notification_deliveries_pkey PRIMARY KEY (id));
+    CREATE INDEX IF NOT EXISTS notification_deliveries_sent_at_idx ON notification_deliveries (sent_at DESC);"
+}
+
+async fn record(notification: &Notification, channel: &Channel, delivery: &Delivery) -> JupiterResult<()> {
+    if get_combo_pool().is_none() {
+        return Ok(());
+    }
+    let attempts = delivery.attempts as i32;
+    combo_client().await?.execute(
+        "INSERT INTO notification_deliveries (rule, event, event_key, title, channel, host, delivered, attempts, error, sent_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        &[&notification.rule, &notification.event, &notification.key, &notification.title, &channel.kind(), &channel.host(),
+          &delivery.error.is_none(), &attempts, &delivery.error, &notification.timestamp],
+    ).await.map_err(|e| JupiterError::DatabaseError(format!("Insert failed: {}", e)))?;
+    Ok(())
+}
+
+async fn prune(now: i64) -> JupiterResult<()> {
+    if get_combo_pool().is_none() {
+        return Ok(());
+    }
+    combo_client().await?.execute("DELETE FROM notification_deliveries WHERE sent_at < $1", &[&(now - DELIVERY_LOG_SECS)]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Delete failed: {}", e)))?;
+    Ok(())
+}
+
+/// One row of the delivery log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    pub id: i32,
+    pub rule: String,
+    pub event: String,
+    pub key: String,
+    pub title: String,
+    pub channel: String,
+    pub host: String,
+    pub delivered: bool,
+    pub attempts: i32,
+    pub error: Option<String>,
+    pub sent_at: i64,
+}
+
+/// The newest `limit` deliveries, of one rule if given, only failed ones with `failed_only`
+pub async fn deliveries(rule: Option<&str>, failed_only: bool, limit: i64) -> JupiterResult<Vec<DeliveryRecord>> {
+    let rows = combo_client().await?.query(
+        "SELECT * FROM notification_deliveries WHERE ($1::varchar IS NULL OR rule = $1) AND (NOT $2 OR NOT delivered)
+         ORDER BY sent_at DESC, id DESC LIMIT $3",
+        &[&rule, &failed_only, &limit],
+    ).await.map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+    Ok(rows.iter().map(|row| DeliveryRecord {
+        id: row.get("id"),
+        rule: row.get("rule"),
+        event: row.get("event"),
+        key: row.get("event_key"),
+        title: row.get("title"),
+        channel: row.get("channel"),
+        host: row.get("host"),
+        delivered: row.get("delivered"),
+        attempts: row.get("attempts"),
+        error: row.get("error"),
+        sent_at: row.get("sent_at"),
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(id: &str, device_type: &str) -> Device {
+        serde_json::from_value(json!({"id": id, "name": id.replace('-', " "), "device_type": device_type})).unwrap()
+    }
+
+    fn reading(pm25: f64) -> WeatherReport {
+        let mut report = WeatherReport::new();
+        report.pm25 = Some(pm25);
+        report
+    }
+
+    fn alert(title: &str, severity: AlertSeverity) -> Alert {
+        Alert {
+            title: title.to_string(),
+            description: format!("{} in effect", title),
+            severity,
+            start: String::new(),
+            end: None,
+            regions: vec!["10001".to_string()],
+        }
+    }
+
+    fn config(json: &str) -> NotificationsConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_config_problems() {
+        let good = config(r#"{"rules": [
+            {"name": "weather", "event": "alert", "min_severity": "Severe", "channels": [{"kind": "slack", "url": "https://hooks.slack.com/services/x"}]},
+            {"name": "bad-air", "event": "threshold", "field": "pm25", "above": 35, "device": "indoor", "cooldown_secs": 3600,
+             "channels": [{"kind": "webhook", "url": "https://example.com/hook"}]},
+            {"name": "offline", "event": "stale_sensor", "channels": [{"kind": "discord", "url": "https://discord.com/api/webhooks/1/x"}]}
+        ]}"#);
+        assert_eq!(good.problems(), Vec::<(String, String)>::new());
+        assert_eq!(good.rules[0].trigger, Trigger::Alert { min_severity: AlertSeverity::Severe });
+        assert_eq!(good.rules[2].trigger, Trigger::StaleSensor { after_secs: 1800, device: None });
+        assert!(good.uses_alerts() && good.uses_homebrew());
+
+        let bad = config(r#"{"rules": [
+            {"name": "air", "event": "threshold", "field": "smoke", "above": 1, "channels": [{"kind": "slack", "url": "https://hooks.slack.com/x"}]},
+            {"name": "air", "event": "threshold", "field": "co2", "channels": []},
+            {"name": "", "event": "stale_sensor", "after_secs": 5, "channels": [{"kind": "webhook", "url": "not a url"}]}
+        ]}"#);
+        let problems = bad.problems();
+        assert!(problems.contains(&("NOTIFICATIONS_CONFIG.rules.air".to_string(), "unknown report field 'smoke'".to_string())));
+        assert!(problems.contains(&("NOTIFICATIONS_CONFIG.rules.air".to_string(), "rule names must be unique".to_string())));
+        assert!(problems.contains(&("NOTIFICATIONS_CONFIG.rules.air".to_string(), "co2 threshold needs above or below".to_string())));
+        assert!(problems.contains(&("NOTIFICATIONS_CONFIG.rules.air".to_string(), "rule needs at least one channel".to_string())));
+        assert!(problems.iter().filter(|(setting, _)| setting == "NOTIFICATIONS_CONFIG.rules.2").count() == 3);
+        assert!(serde_json::from_str::<NotificationsConfig>(r#"{"rules": [{"name": "x", "event": "earthquake", "channels": []}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_rules_fire_once_per_condition() {
+        let rules = config(r#"{"rules": [
+            {"name": "weather", "event": "alert", "min_severity": "Moderate", "channels": [{"kind": "slack", "url": "https://hooks.slack.com/x"}]},
+            {"name": "bad-air", "event": "threshold", "field": "pm25", "above": 35, "device": "indoor", "cooldown_secs": 600,
+             "channels": [{"kind": "slack", "url": "https://hooks.slack.com/x"}]}
+        ]}"#).rules;
+        let mut notifier = Notifier::default();
+        let observed = |alerts: Vec<Alert>, pm25: f64| Observations {
+            alerts: Some(alerts),
+            readings: vec![(device("living-room", "indoor"), reading(pm25)), (device("porch", "outdoor"), reading(90.0))],
+            statuses: Vec::new(),
+        };
+
+        let sent = notifier.evaluate(&rules, &observed(vec![alert("Heat Advisory", AlertSeverity::Moderate), alert("Frost", AlertSeverity::Minor)], 48.2), 1000);
+        let titles: Vec<&str> = sent.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["Heat Advisory", "pm25 above 35 at living room"]);
+        assert_eq!(sent[1].message, "living room pm25 is 48.2, above 35");
+
+        // Still active: nothing new
+        assert!(notifier.evaluate(&rules, &observed(vec![alert("Heat Advisory", AlertSeverity::Moderate)], 50.0), 1060).is_empty());
+        // A failed alert fetch changes nothing; the air clears
+        let unknown = Observations { alerts: None, ..observed(Vec::new(), 12.0) };
+        assert!(notifier.evaluate(&rules, &unknown, 1120).is_empty());
+        // The alert ends and is reissued; the air is bad again, but within the cooldown
+        assert!(notifier.evaluate(&rules, &observed(Vec::new(), 40.0), 1180).is_empty());
+        let sent = notifier.evaluate(&rules, &observed(vec![alert("Heat Advisory", AlertSeverity::Moderate)], 12.0), 1240);
+        assert_eq!(sent.len(), 1);
+        // After the cooldown the next crossing fires
+        let sent = notifier.evaluate(&rules, &observed(vec![alert("Heat Advisory", AlertSeverity::Moderate)], 60.0), 1700);
+        assert_eq!(sent.iter().map(|n| n.rule.as_str()).collect::<Vec<_>>(), vec!["bad-air"]);
+    }
+
+    #[test]
+    fn test_stale_sensors() {
+        let rules = config(r#"{"rules": [{"name": "offline", "event": "stale_sensor", "after_secs": 1800,
+            "channels": [{"kind": "slack", "url": "https://hooks.slack.com/x"}]}]}"#).rules;
+        let status = |id: &str, last_seen: Option<i64>| DeviceStatus {
+            id: id.to_string(),
+            name: id.to_string(),
+            device_type: "outdoor".to_string(),
+            room: None,
+            last_seen,
+            online: false,
+            battery_voltage: None,
+            battery_percent: None,
+            rssi: None,
+            low_battery: false,
+            weak_signal: false,
+        };
+        let mut notifier = Notifier::default();
+        // Devices that never reported are left alone
+        let observed = Observations { statuses: vec![status("garage", Some(1000)), status("new-sensor", None)], ..Default::default() };
+        assert!(notifier.evaluate(&rules, &observed, 2000).is_empty());
+        let sent = notifier.evaluate(&rules, &observed, 4000);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].message, "No readings from garage for 50 minutes");
+        assert!(notifier.evaluate(&rules, &observed, 5000).is_empty());
+    }
+}
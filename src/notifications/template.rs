@@ -0,0 +1,95 @@
+use handlebars::{handlebars_helper, Handlebars, Template};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use super::Notification;
+use crate::provider::common::AlertSeverity;
+use crate::utils::time::format_rfc3339;
+
+// Webhook, Slack and Discord channels can replace their request body with a Handlebars
+// `template`, for services that expect a particular JSON shape:
+//
+// { "kind": "webhook", "url": "https://events.example.com/v2/enqueue",
+//   "template": "{\"summary\": \"{{title}}\", \"severity\": \"{{lowercase severity}}\", \"source\": \"jupiter/{{rule}}\", \"custom_details\": {{{json data}}}}" }
+//
+// Templates see the notification's fields (rule, event, key, title, message, severity,
+// timestamp and data, e.g. `{{data.value}}`) and `time`, its timestamp in RFC 3339. Values
+// written with `{{...}}` are escaped for use inside a JSON string; `{{{json value}}}` writes a
+// value as JSON. The rendered text is sent as the JSON body, so it has to parse as JSON.
+
+handlebars_helper!(as_json: |value: Json| serde_json::to_string(value).unwrap_or_default());
+handlebars_helper!(lowercase: |text: str| text.to_lowercase());
+
+static TEMPLATES: Lazy<Handlebars<'static>> = Lazy::new(|| {
+    let mut handlebars = Handlebars::new();
+    // Everything a template writes ends up in a JSON document rather than HTML
+    handlebars.register_escape_fn(|text| {
+        let quoted = Value::String(text.to_string()).to_string();
+        quoted[1..quoted.len() - 1].to_string()
+    });
+    handlebars.register_helper("json", Box::new(as_json));
+    handlebars.register_helper("lowercase", Box::new(lowercase));
+    handlebars
+});
+
+/// The request body `template` renders for `notification`
+pub fn render(template: &str, notification: &Notification) -> Result<Value, String> {
+    let mut data = serde_json::to_value(notification).map_err(|e| e.to_string())?;
+    data["time"] = json!(format_rfc3339(notification.timestamp));
+    let rendered = TEMPLATES.render_template(template, &data)
+        .map_err(|e| format!("template failed to render: {}", e))?;
+    serde_json::from_str(&rendered).map_err(|e| format!("template didn't render JSON: {}", e))
+}
+
+/// Checks that `template` compiles and renders JSON for a typical notification
+pub fn validate(template: &str) -> Result<(), String> {
+    Template::compile(template).map_err(|e| format!("invalid template: {}", e))?;
+    let sample = Notification {
+        rule: "sample".to_string(),
+        event: "threshold".to_string(),
+        key: "living-room".to_string(),
+        title: "pm25 above 35 at Living room".to_string(),
+        message: "Living room pm25 is 48.2, above 35".to_string(),
+        severity: AlertSeverity::Moderate,
+        timestamp: 1_700_000_000,
+        data: json!({"device": "living-room", "name": "Living room", "field": "pm25", "value": 48.2, "above": 35.0}),
+    };
+    render(template, &sample).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification() -> Notification {
+        Notification {
+            rule: "weather".to_string(),
+            event: "alert".to_string(),
+            key: "heat-advisory".to_string(),
+            title: "Heat Advisory \"extreme\"".to_string(),
+            message: "Heat index up to 41°C\nStay hydrated".to_string(),
+            severity: AlertSeverity::Severe,
+            timestamp: 1_700_000_000,
+            data: json!({"regions": ["10001"], "start": "2023-11-14T22:00:00Z"}),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let template = r#"{"summary": "{{title}}", "text": "{{message}}", "severity": "{{lowercase severity}}",
+            "at": "{{time}}", "region": "{{data.regions.[0]}}", "details": {{{json data}}}}"#;
+        assert_eq!(validate(template), Ok(()));
+        let body = render(template, &notification()).unwrap();
+        assert_eq!(body, json!({
+            "summary": "Heat Advisory \"extreme\"",
+            "text": "Heat index up to 41°C\nStay hydrated",
+            "severity": "severe",
+            "at": "2023-11-14T22:13:20Z",
+            "region": "10001",
+            "details": {"regions": ["10001"], "start": "2023-11-14T22:00:00Z"},
+        }));
+
+        assert!(validate("{\"summary\": {{title}}}").unwrap_err().starts_with("template didn't render JSON"));
+        assert!(validate("{\"summary\": \"{{#if title}}\"}").unwrap_err().starts_with("invalid template"));
+    }
+}
@@ -0,0 +1,394 @@
+use serde_json::{json, Map, Value};
+
+use crate::schema::Schema;
+
+// OpenAPI 3 description of the homebrew and combo HTTP APIs, served by each at
+// GET /api/openapi.json, e.g. for the combo server:
+//   {"openapi": "3.0.3", "info": {"title": "Jupiter combo API", ...},
+//    "paths": {"/api/current": {"get": {"summary": "...", "responses": {"200": {...
+//        "$ref": "#/components/schemas/current_weather"}}}}}, ...}
+// Response schemas are built from the field shapes the contract tests pin to the serde models
+// (see `Schema::contract`), so they change with the models. Bodies without a schema version
+// are described as plain JSON objects.
+
+/// What a request or response carries
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Body {
+    Schema(Schema),
+    Json,                   // JSON without a schema version
+    Form,                   // An urlencoded `ReportForm`
+    Content(&'static str),  // Anything else, by content type
+    Empty,
+}
+
+/// A query parameter: name, JSON type and description
+type Param = (&'static str, &'static str, &'static str);
+
+struct Route {
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    query: &'static [Param],
+    request: Option<Body>,
+    response: Body,
+    public: bool,
+}
+
+impl Route {
+    fn new(method: &'static str, path: &'static str, summary: &'static str, response: Body) -> Self {
+        Self { method, path, summary, query: &[], request: None, response, public: false }
+    }
+
+    fn get(path: &'static str, summary: &'static str, response: Body) -> Self {
+        Self::new("get", path, summary, response)
+    }
+
+    fn post(path: &'static str, summary: &'static str, request: Body, response: Body) -> Self {
+        Self { request: Some(request), ..Self::new("post", path, summary, response) }
+    }
+
+    fn put(path: &'static str, summary: &'static str, response: Body) -> Self {
+        Self { request: Some(Body::Json), ..Self::new("put", path, summary, response) }
+    }
+
+    fn delete(path: &'static str, summary: &'static str) -> Self {
+        Self::new("delete", path, summary, Body::Empty)
+    }
+
+    fn query(self, query: &'static [Param]) -> Self {
+        Self { query, ..self }
+    }
+}
+
+const RAW: Param = ("raw", "boolean", "true skips SENSOR_SMOOTHING");
+const DEVICE: Param = ("device", "string", "Device id or device type");
+const LIMIT: Param = ("limit", "integer", "Maximum number of entries");
+const SINCE: Param = ("since", "integer", "Unix seconds");
+const UNTIL: Param = ("until", "integer", "Unix seconds");
+const INCLUDE_ARCHIVED: Param = ("include_archived", "boolean", "Also list archived devices");
+const TRIGGER_FORMAT: Param = ("format", "string", "ifttt (default) or zapier");
+
+/// The homebrew report routes, which the combo server also serves with a homebrew database
+fn report_routes() -> Vec<Route> {
+    vec![
+        Route::get("/api/weather_reports", "Latest report", Body::Schema(Schema::WeatherReport))
+            .query(&[RAW, ("format", "string", "json, cbor or msgpack; negotiated from Accept by default")]),
+        Route::post("/api/weather_reports", "Store a report", Body::Form, Body::Schema(Schema::WeatherReport)),
+        Route::get("/api/weather_reports/history", "Reports between two times, raw or averaged", Body::Schema(Schema::ReportRange)).query(&[
+            ("start", "integer", "Unix seconds, default 7 days before end"),
+            ("end", "integer", "Unix seconds, default now"),
+            ("device_type", "string", "Only reports of this device type"),
+            ("resolution", "string", "raw (default), 5min, hour or day"),
+            ("cursor", "string", "next_cursor of the previous page of raw readings"),
+            ("format", "string", "json (default) or ndjson to stream raw readings"),
+            RAW,
+        ]),
+        Route::get("/api/weather_reports/list", "Stored reports, newest first", Body::Schema(Schema::ReportPage)).query(&[
+            ("limit", "integer", "1-1000, default 100"),
+            ("cursor", "string", "next_cursor of the previous page"),
+            ("offset", "integer", "Deprecated, use cursor"),
+            DEVICE,
+        ]),
+        Route::get("/api/value/{field}", "Latest value of one field", Body::Content("text/plain"))
+            .query(&[DEVICE, ("format", "string", "text for the value with its unit in LOCALE"), RAW]),
+        Route::get("/api/history/{field}", "Evenly spaced series of one field", Body::Schema(Schema::History)).query(&[
+            ("hours", "integer", "1-336, default 24"),
+            ("step", "integer", "Seconds between points, 60-86400"),
+            ("max_gap", "integer", "Seconds without readings before a gap"),
+            ("interpolate", "boolean", "Fill gaps linearly"),
+            DEVICE,
+            RAW,
+        ]),
+    ]
+}
+
+fn homebrew_routes() -> Vec<Route> {
+    let mut routes = vec![
+        Route::post("/api/ingest/rtl433", "Ingest rtl_433 JSON output", Body::Json, Body::Json),
+        Route::post("/api/ingest/ble", "Ingest BLE advertisements", Body::Json, Body::Json),
+        Route::get("/api/ventilation", "Ventilation advice per room from CO2", Body::Schema(Schema::Ventilation))
+            .query(&[("hours", "integer", "Look-back window")]),
+        Route::get("/api/devices", "Registered devices", Body::Schema(Schema::Devices)).query(&[INCLUDE_ARCHIVED]),
+        Route::post("/api/devices", "Register a device and issue its ingest key", Body::Json, Body::Schema(Schema::DeviceKey)),
+        Route::get("/api/devices/status", "Last report, battery and signal per device", Body::Schema(Schema::DeviceStatus))
+            .query(&[INCLUDE_ARCHIVED]),
+        Route::get("/api/devices/{id}", "One device", Body::Schema(Schema::Device)),
+        Route::put("/api/devices/{id}", "Replace a registration", Body::Schema(Schema::Device)),
+        Route::delete("/api/devices/{id}", "Archive a device")
+            .query(&[("purge", "boolean", "Delete the registration and its keys instead")]),
+        Route::put("/api/devices/{id}/state", "Set a device active, disabled or archived", Body::Json),
+        Route::post("/api/devices/{id}/key", "Replace a device's keys with a new ingest key", Body::Empty, Body::Schema(Schema::DeviceKey)),
+        Route::get("/api/devices/{id}/config", "Settings for the device itself", Body::Schema(Schema::DeviceConfig)),
+        Route::get("/api/groups", "Device groups", Body::Schema(Schema::Groups)),
+        Route::post("/api/groups", "Create a group", Body::Json, Body::Schema(Schema::Group)),
+        Route::get("/api/groups/{id}", "One group", Body::Schema(Schema::Group)),
+        Route::put("/api/groups/{id}", "Replace a group", Body::Schema(Schema::Group)),
+        Route::delete("/api/groups/{id}", "Delete a group"),
+        Route::get("/api/groups/{id}/climate", "Climate summary of a group", Body::Schema(Schema::GroupClimate)),
+        Route::get("/api/groups/{id}/status", "Status of a group's devices", Body::Schema(Schema::DeviceStatus)),
+        Route::get("/api/alert_rules", "Alert rules", Body::Schema(Schema::AlertRules)),
+        Route::post("/api/alert_rules", "Create an alert rule", Body::Json, Body::Schema(Schema::AlertRule)),
+        Route::get("/api/alert_rules/{id}", "One alert rule", Body::Schema(Schema::AlertRule)),
+        Route::put("/api/alert_rules/{id}", "Replace an alert rule", Body::Schema(Schema::AlertRule)),
+        Route::delete("/api/alert_rules/{id}", "Delete an alert rule"),
+        Route::get("/api/admin/keys", "Stored API keys, without the keys themselves", Body::Schema(Schema::ApiKeys)),
+        Route::post("/api/admin/keys", "Issue an API key", Body::Json, Body::Schema(Schema::IssuedKey)),
+        Route::delete("/api/admin/keys/{id}", "Revoke an API key"),
+        Route::get("/api/admin/devices/{id}/export", "Everything stored about a device", Body::Json),
+        Route::post("/api/admin/devices/{id}/erase", "Erase a device's data", Body::Empty, Body::Json)
+            .query(&[("confirm", "string", "Token from the first, unconfirmed call")]),
+        Route::get("/api/admin/storage", "Storage used per table", Body::Schema(Schema::StorageUsage)),
+        Route::get("/api/audit/verify", "Verify the report hash chain", Body::Schema(Schema::AuditVerification)),
+        Route::get("/api/rooms", "Latest climate per room", Body::Schema(Schema::Rooms)),
+    ];
+    routes.extend(report_routes());
+    routes.push(Route::get("/metrics", "Prometheus metrics", Body::Content("text/plain")));
+    routes
+}
+
+fn combo_routes(reports: bool, public: bool) -> Vec<Route> {
+    let mut routes = Vec::new();
+    if reports {
+        routes.extend(report_routes());
+        routes.push(Route::get("/api/triggers/temperature_crossed", "Temperature crossings for IFTTT/Zapier", Body::Schema(Schema::TemperatureTrigger)).query(&[
+            ("threshold", "number", "°C"),
+            ("direction", "string", "above or below, default both"),
+            ("hours", "integer", "Look-back window, default 24"),
+            DEVICE,
+            TRIGGER_FORMAT,
+            LIMIT,
+        ]));
+    }
+    routes.extend([
+        Route::get("/", "Cached current conditions", Body::Schema(Schema::CachedConditions)),
+        Route::get("/api/current", "Combined current conditions with their sources", Body::Schema(Schema::CurrentWeather)),
+        Route::get("/api/capabilities", "Features the configured providers support", Body::Schema(Schema::Capabilities)),
+        Route::get("/api/stream", "Live readings over a WebSocket", Body::Empty),
+        Route::get("/api/pollen", "Pollen levels", Body::Schema(Schema::Pollen)),
+        Route::get("/api/uv/guidance", "Sun protection advice for the UV index", Body::Schema(Schema::UvGuidance))
+            .query(&[("skin_type", "integer", "Fitzpatrick skin type 1-6"), ("hour", "integer", "Hour of day of the forecast")]),
+        Route { public: true, ..Route::get("/api/widget.svg", "Current conditions as an SVG widget, without a key", Body::Content("image/svg+xml")) },
+        Route::get("/api/summary/spoken", "Current conditions as a sentence for voice assistants", Body::Content("text/plain")),
+        Route::get("/api/hvac/hints", "Heating, cooling and ventilation hints", Body::Schema(Schema::HvacHints)),
+        Route::get("/api/solar/forecast", "Hourly PV production estimate for the next 48 hours", Body::Schema(Schema::SolarForecast)),
+        Route::get("/api/marine", "Tides and marine forecast", Body::Schema(Schema::Marine)),
+        Route::get("/api/air_quality", "Air quality index and pollutants", Body::Schema(Schema::AirQuality)),
+        Route::get("/api/nowcast", "Precipitation in the next hour", Body::Schema(Schema::Nowcast)),
+        Route::get("/api/aviation", "TAF for the configured airfield", Body::Schema(Schema::Aviation)),
+        Route::get("/api/triggers/new_alert", "New weather alerts for IFTTT/Zapier", Body::Schema(Schema::AlertTrigger)).query(&[TRIGGER_FORMAT, LIMIT]),
+        Route::get("/api/admin/jobs", "Background jobs and their schedules", Body::Json),
+        Route::post("/api/admin/jobs/{name}/run", "Run a background job now", Body::Empty, Body::Json),
+        Route::get("/api/admin/jobs/{name}/runs", "Recent runs of a background job", Body::Json).query(&[LIMIT]),
+        Route::get("/api/admin/payloads", "Archived provider payloads", Body::Json).query(&[
+            ("provider", "string", "Provider name"),
+            ("endpoint", "string", "Provider endpoint"),
+            SINCE,
+            UNTIL,
+            LIMIT,
+        ]),
+        Route::get("/api/admin/notifications", "Notification delivery log", Body::Json)
+            .query(&[("rule", "string", "Only this rule"), ("failed", "boolean", "Only failed deliveries"), LIMIT]),
+        Route::post("/api/admin/backfill", "Replay archived payloads through the current normalization", Body::Empty, Body::Json)
+            .query(&[SINCE, UNTIL, ("provider", "string", "Provider name"), ("apply", "boolean", "Store the results, dry run otherwise")]),
+        Route::get("/api/admin/config", "Running configuration, secrets redacted", Body::Json),
+        Route::post("/api/admin/config", "Deploy a new configuration", Body::Json, Body::Json)
+            .query(&[("confirm", "string", "Token from the first, unconfirmed call")]),
+        Route::get("/api/admin/providers/health", "Provider success rates and latencies", Body::Json),
+        Route::get("/metrics", "Prometheus metrics", Body::Content("text/plain")),
+        Route::get("/metrics/pools", "Database pool metrics", Body::Json),
+    ]);
+    if public {
+        routes.extend([
+            Route { public: true, ..Route::get("/public/current", "Current conditions, without a key", Body::Schema(Schema::PublicCurrent)) },
+            Route { public: true, ..Route::get("/public/daily", "Today's forecast, without a key", Body::Schema(Schema::PublicDaily)) },
+            Route { public: true, ..Route::get("/public/widget.svg", "SVG widget, without a key", Body::Content("image/svg+xml")) },
+        ]);
+    }
+    routes
+}
+
+/// JSON Schema of the bodies whose field paths and types are `contract` ("path: type" lines
+/// as written by `schema::shape`)
+pub fn shape_schema(contract: &str) -> Value {
+    fn leaf(kind: &str) -> Value {
+        match kind {
+            "float" => json!({"type": "number"}),
+            "integer" => json!({"type": "integer"}),
+            "string" => json!({"type": "string"}),
+            "boolean" => json!({"type": "boolean"}),
+            "empty array" => json!({"type": "array", "items": {}}),
+            "empty object" => json!({"type": "object"}),
+            _ => json!({"nullable": true}),
+        }
+    }
+
+    fn ensure(node: &mut Value, kind: &str) {
+        if node.get("type").and_then(Value::as_str) != Some(kind) {
+            *node = match kind {
+                "array" => json!({"type": "array", "items": null}),
+                _ => json!({"type": "object", "properties": {}}),
+            };
+        }
+    }
+
+    fn insert(node: &mut Value, segments: &[&str], kind: &str) {
+        let Some((segment, rest)) = segments.split_first() else {
+            *node = leaf(kind);
+            return;
+        };
+        let name = segment.trim_end_matches("[]");
+        let mut node = node;
+        if !name.is_empty() {
+            ensure(node, "object");
+            node = node["properties"].as_object_mut().expect("object schema")
+                .entry(name).or_insert(Value::Null);
+        }
+        for _ in 0..(segment.len() - name.len()) / 2 {
+            ensure(node, "array");
+            node = &mut node["items"];
+        }
+        insert(node, rest, kind);
+    }
+
+    let mut root = Value::Null;
+    for line in contract.lines() {
+        if let Some((path, kind)) = line.split_once(": ") {
+            let segments: Vec<&str> = path.split('.').collect();
+            insert(&mut root, &segments, kind);
+        }
+    }
+    root
+}
+
+fn content(body: Body) -> Option<Value> {
+    let (content_type, schema) = match body {
+        Body::Schema(schema) => ("application/json", json!({"$ref": format!("#/components/schemas/{}", schema.name())})),
+        Body::Json => ("application/json", json!({"type": "object"})),
+        Body::Form => {
+            // The report fields the server doesn't assign
+            let mut form = shape_schema(Schema::WeatherReport.contract());
+            if let Some(properties) = form["properties"].as_object_mut() {
+                for assigned in ["id", "oid", "timestamp"] {
+                    properties.remove(assigned);
+                }
+            }
+            ("application/x-www-form-urlencoded", form)
+        },
+        Body::Content(content_type) => (content_type, json!({"type": "string"})),
+        Body::Empty => return None,
+    };
+    Some(json!({content_type: {"schema": schema}}))
+}
+
+fn operation(route: &Route) -> Value {
+    let mut parameters: Vec<Value> = route.path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| json!({"name": name, "in": "path", "required": true, "schema": {"type": "string"}}))
+        .collect();
+    parameters.extend(route.query.iter().map(|(name, kind, description)| {
+        json!({"name": name, "in": "query", "description": description, "schema": {"type": kind}})
+    }));
+
+    let response = match content(route.response) {
+        Some(content) => json!({"200": {"description": "OK", "content": content}}),
+        None if route.path == "/api/stream" => json!({"101": {"description": "Switching to WebSocket"}}),
+        None => json!({"204": {"description": "No Content"}}),
+    };
+    let mut operation = json!({"summary": route.summary, "responses": response});
+    if !parameters.is_empty() {
+        operation["parameters"] = Value::Array(parameters);
+    }
+    if let Some(content) = route.request.and_then(content) {
+        operation["requestBody"] = json!({"required": true, "content": content});
+    }
+    if route.public {
+        operation["security"] = json!([]);
+    }
+    operation
+}
+
+fn document(title: &str, base_path: Option<&str>, routes: Vec<Route>) -> Value {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    for route in &routes {
+        let item = paths.entry(route.path).or_insert_with(|| json!({}));
+        item[route.method] = operation(route);
+        if let Body::Schema(schema) = route.response {
+            schemas.entry(schema.name()).or_insert_with(|| shape_schema(schema.contract()));
+        }
+    }
+    paths.insert("/api/openapi.json".to_string(), json!({"get": {
+        "summary": "This document",
+        "responses": {"200": {"description": "OK", "content": {"application/json": {"schema": {"type": "object"}}}}},
+    }}));
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {"title": title, "version": env!("CARGO_PKG_VERSION")},
+        "servers": [{"url": base_path.unwrap_or("/")}],
+        "security": [{"apiKey": []}],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {"apiKey": {"type": "apiKey", "in": "header", "name": "Authorization"}},
+            "schemas": schemas,
+        },
+    })
+}
+
+/// The homebrew server's API
+pub fn homebrew(base_path: Option<&str>) -> Value {
+    document("Jupiter homebrew API", base_path, homebrew_routes())
+}
+
+/// The combo server's API; the report routes with a homebrew database and the /public pages in
+/// PUBLIC_MODE
+pub fn combo(base_path: Option<&str>, reports: bool, public: bool) -> Value {
+    document("Jupiter combo API", base_path, combo_routes(reports, public))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shape_schema() {
+        let schema = shape_schema("reports[].temperature: float\nreports[].device_id: null\ntruncated: boolean");
+        assert_eq!(schema["properties"]["reports"]["type"], "array");
+        assert_eq!(schema["properties"]["reports"]["items"]["properties"]["temperature"], json!({"type": "number"}));
+        assert_eq!(schema["properties"]["reports"]["items"]["properties"]["device_id"], json!({"nullable": true}));
+        assert_eq!(schema["properties"]["truncated"], json!({"type": "boolean"}));
+
+        let list = shape_schema(Schema::Devices.contract());
+        assert_eq!(list["type"], "array");
+        assert_eq!(list["items"]["properties"]["id"], json!({"type": "string"}));
+    }
+
+    #[test]
+    fn test_documents() {
+        let combo = combo(Some("/weather"), false, true);
+        assert_eq!(combo["servers"][0]["url"], "/weather");
+        assert!(combo["paths"]["/api/weather_reports"].is_null());
+        assert_eq!(combo["paths"]["/public/current"]["get"]["security"], json!([]));
+        assert_eq!(combo["paths"]["/api/current"]["get"]["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/current_weather");
+        assert!(combo["components"]["schemas"]["current_weather"]["properties"]["temperature"].is_object());
+
+        let homebrew = homebrew(None);
+        let device = &homebrew["paths"]["/api/devices/{id}"];
+        assert_eq!(device["delete"]["responses"]["204"]["description"], "No Content");
+        assert_eq!(device["get"]["parameters"][0]["name"], "id");
+        let form = &homebrew["paths"]["/api/weather_reports"]["post"]["requestBody"]["content"]["application/x-www-form-urlencoded"]["schema"];
+        assert!(form["properties"]["temperature"].is_object());
+        assert!(form["properties"]["oid"].is_null());
+
+        // Every versioned schema a route refers to is defined
+        for document in [combo, homebrew] {
+            let text = document.to_string();
+            for schema in Schema::ALL {
+                if text.contains(&format!("#/components/schemas/{}\"", schema.name())) {
+                    assert!(document["components"]["schemas"][schema.name()].is_object(), "{}", schema.name());
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::db_pool::{get_homebrew_pool, get_combo_pool};
+use crate::metrics::{self, Kind};
 use crate::utils::time::safe_timestamp_with_fallback;
 use serde::{Serialize, Deserialize};
 use std::sync::Arc;
@@ -97,6 +98,9 @@ pub async fn init_monitors() {
         Arc::new(PoolMonitor::new())
     }).await;
     
+    static COLLECTORS: std::sync::Once = std::sync::Once::new();
+    COLLECTORS.call_once(register_collectors);
+    
     info!("Pool monitors initialized");
 }
 
@@ -140,6 +144,49 @@ pub fn get_all_pool_metrics() -> Vec<PoolMetrics> {
     metrics
 }
 
+/// Pool sizes as read at scrape time, for GET /metrics
+fn register_collectors() {
+    fn per_pool(value: fn(&PoolMetrics) -> f64) -> Vec<(Vec<String>, f64)> {
+        get_all_pool_metrics().iter().map(|m| (vec![m.pool_name.clone()], value(m))).collect()
+    }
+    metrics::collector(Kind::Gauge, "jupiter_db_pool_connections", "Open connections per database pool", &["pool"],
+        || per_pool(|m| m.size as f64));
+    metrics::collector(Kind::Gauge, "jupiter_db_pool_available_connections", "Idle connections per database pool", &["pool"],
+        || per_pool(|m| m.available as f64));
+    metrics::collector(Kind::Gauge, "jupiter_db_pool_waiting", "Tasks waiting for a connection per database pool", &["pool"],
+        || per_pool(|m| m.waiting as f64));
+}
+
+// Logs the health of every pool; run by the scheduler as the pool_health job
+pub fn check_pools() {
+    let metrics = get_all_pool_metrics();
+    for metric in metrics {
+        if metric.available == 0 && metric.waiting > 0 {
+            warn!(
+                "[{}] Pool exhausted! Size: {}, Available: 0, Waiting: {}",
+                metric.pool_name, metric.size, metric.waiting
+            );
+        } else if (metric.available as f64) / (metric.size as f64) < 0.2 {
+            warn!(
+                "[{}] Pool running low! Size: {}, Available: {}, Waiting: {}",
+                metric.pool_name, metric.size, metric.available, metric.waiting
+            );
+        } else {
+            info!(
+                "[{}] Pool healthy - Size: {}, Available: {}, Waiting: {}",
+                metric.pool_name, metric.size, metric.available, metric.waiting
+            );
+        }
+        
+        if metric.total_connection_errors > 0 {
+            error!(
+                "[{}] Connection errors detected: {}",
+                metric.pool_name, metric.total_connection_errors
+            );
+        }
+    }
+}
+
 // Background monitoring task
 pub async fn start_monitoring_task(interval_seconds: u64) {
     let interval = Duration::from_secs(interval_seconds);
@@ -147,33 +194,7 @@ pub async fn start_monitoring_task(interval_seconds: u64) {
     tokio::spawn(async move {
         loop {
             tokio::time::sleep(interval).await;
-            
-            let metrics = get_all_pool_metrics();
-            for metric in metrics {
-                if metric.available == 0 && metric.waiting > 0 {
-                    warn!(
-                        "[{}] Pool exhausted! Size: {}, Available: 0, Waiting: {}",
-                        metric.pool_name, metric.size, metric.waiting
-                    );
-                } else if (metric.available as f64) / (metric.size as f64) < 0.2 {
-                    warn!(
-                        "[{}] Pool running low! Size: {}, Available: {}, Waiting: {}",
-                        metric.pool_name, metric.size, metric.available, metric.waiting
-                    );
-                } else {
-                    info!(
-                        "[{}] Pool healthy - Size: {}, Available: {}, Waiting: {}",
-                        metric.pool_name, metric.size, metric.available, metric.waiting
-                    );
-                }
-                
-                if metric.total_connection_errors > 0 {
-                    error!(
-                        "[{}] Connection errors detected: {}",
-                        metric.pool_name, metric.total_connection_errors
-                    );
-                }
-            }
+            check_pools();
         }
     });
 }
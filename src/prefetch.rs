@@ -0,0 +1,201 @@
+use once_cell::sync::Lazy;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::metrics::{self, Counter};
+use crate::provider::combo::Config as ComboConfig;
+use crate::provider::combo_enhanced::ComboProvider;
+use crate::provider::common::{LocationQuery, WeatherProvider};
+
+// Refreshes cached current conditions and forecasts before they expire, so requests find them
+// warm instead of waiting on the providers, and paid provider calls are spread evenly over
+// time instead of bursting whenever entries expire. With PREFETCH=true the combo server's
+// LOCATION is refreshed every CACHE_TTL_SECS / 2, together with the legacy conditions of GET /;
+// PREFETCH_LOCATIONS adds more locations, separated by `;` (coordinates contain commas), e.g.
+// `10001;51.5,-0.12`. Each cycle visits the locations one after another, evenly spaced.
+// PREFETCH_FORECAST_DAYS lists the forecast lengths to refresh (`1` by default, what
+// /public/daily asks for).
+//
+// Instances sharing Redis (see `cluster`) take turns, so each location is refreshed once per
+// slot across all of them.
+
+const DEFAULT_FORECAST_DAYS: u8 = 1;
+
+static PREFETCHES: Lazy<Arc<Counter>> = Lazy::new(|| metrics::counter(
+    "jupiter_prefetches_total", "Cache refreshes ahead of expiry by kind (current, forecast or conditions) and result (ok or error)", &["kind", "result"],
+));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrefetchConfig {
+    pub enabled: bool,
+    pub locations: Vec<String>,     // Besides LOCATION
+    pub forecast_days: Vec<u8>,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        Self { enabled: false, locations: Vec::new(), forecast_days: vec![DEFAULT_FORECAST_DAYS] }
+    }
+}
+
+impl PrefetchConfig {
+    /// Reads the settings through `var`; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let mut problems = Vec::new();
+        let mut config = Self::default();
+        if let Some(locations) = var("PREFETCH_LOCATIONS") {
+            for location in locations.split(';').map(str::trim).filter(|location| !location.is_empty()) {
+                match LocationQuery::parse(location) {
+                    Ok(_) => config.locations.push(location.to_string()),
+                    Err(message) => problems.push(("PREFETCH_LOCATIONS", message)),
+                }
+            }
+        }
+        config.enabled = !config.locations.is_empty() || var("PREFETCH").is_some_and(|v| v == "true" || v == "1");
+        if let Some(days) = var("PREFETCH_FORECAST_DAYS") {
+            let parsed: Option<Vec<u8>> = days.split(',')
+                .map(|days| days.trim().parse::<u8>().ok().filter(|days| (1..=15).contains(days)))
+                .collect();
+            match parsed {
+                Some(days) => config.forecast_days = days,
+                None => problems.push(("PREFETCH_FORECAST_DAYS", format!("must be forecast lengths of 1-15 days separated by commas, got '{}'", days))),
+            }
+        }
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// `primary` (LOCATION) and the other locations, each once
+    pub fn locations(&self, primary: &str) -> Vec<String> {
+        let mut locations = vec![primary.to_string()];
+        for location in &self.locations {
+            if !locations.contains(location) {
+                locations.push(location.clone());
+            }
+        }
+        locations
+    }
+}
+
+/// Refresh interval for entries living `cache_timeout` seconds
+pub fn interval(cache_timeout: i64) -> Duration {
+    Duration::from_secs((cache_timeout.max(2) / 2) as u64)
+}
+
+/// Whether this instance refreshes `location` in the current slot; another instance sharing
+/// Redis may have taken it
+async fn take_turn(location: &str, slot: Duration) -> bool {
+    #[cfg(feature = "redis")]
+    if let Some(cluster) = crate::cluster::get() {
+        // Held until it expires, so no other instance takes the same slot
+        return cluster.try_lock(&format!("prefetch:{}", location), slot.saturating_sub(Duration::from_secs(1))).await
+            .map(|lock| lock.is_some())
+            .unwrap_or(true);
+    }
+    let _ = (location, slot);
+    true
+}
+
+fn record(kind: &str, ok: bool) {
+    PREFETCHES.inc(&[kind, if ok { "ok" } else { "error" }]);
+}
+
+async fn prefetch(combo: &ComboConfig, providers: &ComboProvider, location: &str, forecast_days: &[u8]) {
+    let started = Instant::now();
+    let current = providers.get_current_weather(location).await;
+    if let Err(e) = &current {
+        log::warn!("[prefetch] Failed to refresh current conditions for {}: {}", location, e);
+    }
+    record("current", current.is_ok());
+    for days in forecast_days {
+        let forecast = providers.get_forecast(location, *days).await;
+        if let Err(e) = &forecast {
+            log::warn!("[prefetch] Failed to refresh the {} day forecast for {}: {}", days, location, e);
+        }
+        record("forecast", forecast.is_ok());
+    }
+    if location == combo.zip_code {
+        let conditions = combo.refresh_cached_conditions().await;
+        record("conditions", conditions.accuweather.is_some() || conditions.homebrew.is_some());
+    }
+    log::debug!("[prefetch] Refreshed {} in {} ms", location, started.elapsed().as_millis());
+}
+
+/// Starts refreshing the locations of `config` until `shutdown_flag` is set; None when
+/// prefetching is off or the combo server doesn't cache
+pub fn start(config: PrefetchConfig, combo: ComboConfig, shutdown_flag: Arc<AtomicBool>) -> Option<JoinHandle<()>> {
+    if !config.enabled {
+        return None;
+    }
+    let Some(cache_timeout) = combo.cache_timeout.filter(|timeout| *timeout > 0) else {
+        log::warn!("[prefetch] PREFETCH is set but the combo server doesn't cache, not prefetching");
+        return None;
+    };
+    let locations = config.locations(&combo.zip_code);
+    let interval = interval(cache_timeout);
+    let slot = interval / locations.len() as u32;
+    log::info!("[prefetch] Refreshing {} location(s) every {} s", locations.len(), interval.as_secs());
+
+    let runtime = tokio::runtime::Handle::current();
+    Some(thread::spawn(move || {
+        let providers = combo.weather_providers().set_prefetching(true);
+        let mut next = Instant::now();
+        'cycles: loop {
+            for location in &locations {
+                while Instant::now() < next {
+                    if shutdown_flag.load(Ordering::Relaxed) {
+                        break 'cycles;
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+                if shutdown_flag.load(Ordering::Relaxed) {
+                    break 'cycles;
+                }
+                if runtime.block_on(take_turn(location, slot)) {
+                    runtime.block_on(prefetch(&combo, &providers, location, &config.forecast_days));
+                }
+                // A slow refresh delays the next one rather than bunching them up
+                next = (next + slot).max(Instant::now());
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_prefetch_config() {
+        let config = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            PrefetchConfig::from_vars(|name| vars.get(name).cloned())
+        };
+        assert!(!config(&[]).unwrap().enabled);
+        assert!(config(&[("PREFETCH", "true")]).unwrap().enabled);
+
+        let prefetch = config(&[("PREFETCH_LOCATIONS", "10001; 51.5,-0.12;"), ("PREFETCH_FORECAST_DAYS", "1,2")]).unwrap();
+        assert!(prefetch.enabled);
+        assert_eq!(prefetch.forecast_days, vec![1, 2]);
+        assert_eq!(prefetch.locations("10001"), vec!["10001", "51.5,-0.12"]);
+        assert_eq!(prefetch.locations("94105"), vec!["94105", "10001", "51.5,-0.12"]);
+
+        assert_eq!(config(&[("PREFETCH_LOCATIONS", "1234")]).unwrap_err()[0].0, "PREFETCH_LOCATIONS");
+        assert_eq!(config(&[("PREFETCH_FORECAST_DAYS", "0")]).unwrap_err()[0].0, "PREFETCH_FORECAST_DAYS");
+
+        assert_eq!(interval(3600), Duration::from_secs(1800));
+        assert_eq!(interval(0), Duration::from_secs(1));
+    }
+}
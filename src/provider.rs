@@ -4,8 +4,17 @@ pub mod accuweather_enhanced;
 pub mod combo;
 pub mod combo_enhanced;
 pub mod homebrew;
+#[cfg(feature = "enhanced-providers")]
 pub mod homebrew_enhanced;
+#[cfg(feature = "enhanced-providers")]
 pub mod openweather;
+pub mod noaa;
+pub mod nws;
+pub mod tomorrow_io;
+pub mod aviation;
+pub mod air_quality;
+pub mod health;
+pub mod quota;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "enhanced-providers"))]
 mod tests;
\ No newline at end of file
@@ -1,963 +1,563 @@
-use serde_json::json;
-
 use serde::{Serialize, Deserialize};
-use std::convert::TryInto;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use crate::archive;
+use crate::secret::Secret;
+use crate::provider::common::{Location as CommonLocation, LocationQuery, PollenLevel, PollenReport, PollenType, RateLimiter, WeatherError};
+
+const BASE_URL: &str = "http://dataservice.accuweather.com";
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Config {
-    pub apikey: String,
+    pub apikey: Secret<String>,
     pub language: Option<String>,
     pub details: Option<bool>,
     pub metric: Option<bool>,
 }
-impl Config {
-    pub fn to_params(&self) -> String{
-        let mut params = format!("?apikey={}", self.apikey);
 
-        match &self.language {
-            Some(x) => {
-                params = format!("{}&language={}", params, x);
-            },
-            None => {}
+/// HTTP client and rate limiter for every AccuWeather call. Cloning shares both, so the cached
+/// combo conditions and `AccuWeatherProvider` draw on one hourly quota.
+#[derive(Clone)]
+pub struct AccuWeatherClient {
+    config: Config,
+    base_url: String,
+    http: reqwest::Client,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl AccuWeatherClient {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            base_url: BASE_URL.to_string(),
+            // Calls come from more than one tokio runtime, and pooled connections die with the
+            // runtime that opened them; at 50 calls an hour keep-alive isn't worth that
+            http: reqwest::Client::builder()
+                .pool_max_idle_per_host(0)
+                .build()
+                .unwrap_or_else(|_| reqwest::Client::new()),
+            rate_limiter: Arc::new(RateLimiter::new(50, 3600)), // 50 requests per hour for free tier
         }
+    }
 
-        match &self.details {
-            Some(x) => {
-                params = format!("{}&details={}", params, x);
-            },
-            None => {}
+    /// GET `path` below the API root with the key and configured language/details/metric
+    /// (overridden by `params`); `None` for 204 No Content. The raw body is archived under
+    /// `endpoint` and `location`.
+    pub async fn get_optional<T: DeserializeOwned>(&self, path: &str, params: &[(&str, &str)], endpoint: &str, location: &str) -> Result<Option<T>, WeatherError> {
+        if !self.rate_limiter.check_rate_limit() {
+            return Err(WeatherError::RateLimitExceeded);
+        }
+
+        let mut query: Vec<(&str, String)> = vec![("apikey", self.config.apikey.expose().clone())];
+        if let Some(ref language) = self.config.language {
+            query.push(("language", language.clone()));
+        }
+        if let Some(details) = self.config.details {
+            query.push(("details", details.to_string()));
+        }
+        if let Some(metric) = self.config.metric {
+            query.push(("metric", metric.to_string()));
+        }
+        for (key, value) in params {
+            query.retain(|(existing, _)| existing != key);
+            query.push((key, value.to_string()));
         }
 
-        match &self.metric {
-            Some(x) => {
-                params = format!("{}&metric={}", params, x);
-            },
-            None => {}
+        let response = self.http.get(format!("{}/{}", self.base_url, path))
+            .query(&query)
+            .send()
+            .await?;
+        match response.status().as_u16() {
+            401 => Err(WeatherError::InvalidApiKey),
+            204 => Ok(None),
+            _ => archive::json("accuweather", endpoint, location, response).await.map(Some),
         }
-        return params;
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str, params: &[(&str, &str)], endpoint: &str, location: &str) -> Result<T, WeatherError> {
+        self.get_optional(path, params, endpoint, location).await?
+            .ok_or_else(|| WeatherError::NotFound(format!("No AccuWeather data at {}", path)))
+    }
+}
+
+/// Location search path and `q` for a configured location: country-scoped searches keep e.g.
+/// a UK postcode from matching elsewhere
+pub fn search_path(query: &LocationQuery) -> (String, String) {
+    match query {
+        LocationQuery::PostalCode { country: Some(country), code } => (format!("locations/v1/postalcodes/{}/search", country), code.clone()),
+        LocationQuery::PostalCode { country: None, code } => ("locations/v1/postalcodes/search".to_string(), code.clone()),
+        LocationQuery::City { name, country: Some(country) } => (format!("locations/v1/cities/{}/search", country), name.clone()),
+        LocationQuery::City { name, country: None } => ("locations/v1/cities/search".to_string(), name.clone()),
+        LocationQuery::Coordinates { latitude, longitude } => ("locations/v1/cities/geoposition/search".to_string(), format!("{},{}", latitude, longitude)),
     }
 }
 
 
 pub type Locations = Vec<Location>;
 
+// The response models share a few building blocks: `UnitValue` for a reading in one unit,
+// `Measurement` for the same reading in metric and imperial, `Range` for a daily low and high
+// and `WindInfo` for wind and gusts. Fields AccuWeather only sends with details=true are
+// optional.
+
+/// A reading in one unit, e.g. {"Value": 12.6, "Unit": "km/h", "UnitType": 7}. RealFeel
+/// readings add a phrase such as "Pleasant".
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UnitValue {
+    pub value: f64,
+    pub unit: String,
+    pub unit_type: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phrase: Option<String>,
+}
+
+/// One reading in both unit systems, as current conditions and elevations come
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Measurement {
+    pub metric: UnitValue,
+    pub imperial: UnitValue,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Range {
+    pub minimum: UnitValue,
+    pub maximum: UnitValue,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Direction {
+    pub degrees: f64,
+    pub localized: String,
+    pub english: String,
+}
+
+/// Wind or gusts: forecasts give the speed as a `UnitValue` in the requested units, current
+/// conditions as a `Measurement`, and current-condition gusts have no direction
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WindInfo<S = UnitValue> {
+    pub speed: S,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub direction: Option<Direction>,
+}
 
 // http://dataservice.accuweather.com/locations/v1/postalcodes/search
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
 pub struct Location {
-    #[serde(rename = "Version")]
+    #[serde(default)]
     pub version: f64,
-    #[serde(rename = "Key")]
     pub key: String,
-    #[serde(rename = "Type")]
+    #[serde(rename = "Type", default)]
     pub type_field: String,
-    #[serde(rename = "Rank")]
+    #[serde(default)]
     pub rank: f64,
-    #[serde(rename = "LocalizedName")]
     pub localized_name: String,
-    #[serde(rename = "EnglishName")]
+    #[serde(default)]
     pub english_name: String,
-    #[serde(rename = "PrimaryPostalCode")]
-    pub primary_postal_code: String,
-    // #[serde(rename = "Region")]
-    // pub region: Region,
-    // #[serde(rename = "Country")]
-    // pub country: Country,
-    // #[serde(rename = "AdministrativeArea")]
-    // pub administrative_area: AdministrativeArea,
-    // #[serde(rename = "TimeZone")]
-    // pub time_zone: TimeZone,
-    // #[serde(rename = "GeoPosition")]
-    // pub geo_position: GeoPosition,
-    // #[serde(rename = "IsAlias")]
-    // pub is_alias: bool,
-    // #[serde(rename = "ParentCity")]
-    // pub parent_city: Option<ParentCity>,
-    // #[serde(rename = "SupplementalAdminAreas")]
-    // pub supplemental_admin_areas: Vec<SupplementalAdminArea>,
-    #[serde(rename = "DataSets")]
+    pub primary_postal_code: Option<String>,
+    pub region: Option<Area>,
+    pub country: Area,
+    pub administrative_area: Option<AdministrativeArea>,
+    pub time_zone: Option<TimeZone>,
+    pub geo_position: GeoPosition,
+    #[serde(default)]
+    pub is_alias: bool,
+    pub parent_city: Option<ParentCity>,
+    #[serde(default)]
+    pub supplemental_admin_areas: Vec<SupplementalAdminArea>,
+    #[serde(default)]
     pub data_sets: Vec<String>,
-    #[serde(rename = "Details")]
     pub details: Option<Details>,
 }
 impl Location {
 
-    // http://dataservice.accuweather.com/locations/v1/postalcodes/search
-    // apikey: string
-    // q: string
-    // language: string
-    // details: bool
-    pub fn search_by_zip(config: Config, q: String) -> Result<Option<Location>, reqwest::Error> {
-        let url = format!("http://dataservice.accuweather.com/locations/v1/postalcodes/search{}&q={}", config.to_params(), q);
-
-        let request = reqwest::blocking::Client::new().get(url).send();
-        match request {
-            Ok(req) => {
-                let json = req.json::<Locations>()?;
-                
-                // Check if we have any locations before accessing
-                if let Some(first) = json.first() {
-                    return Ok(Some(first.clone()));
-                } else {
-                    // Log empty result scenario
-                    eprintln!("[accuweather] Warning: No locations found for postal code: {}", q);
-                    // Return None to signal no data found
-                    return Ok(None);
-                }
-            },
-            Err(err) => {
-                return Err(err);
-            }
+    // http://dataservice.accuweather.com/locations/v1/postalcodes/{countryCode}/search
+    // http://dataservice.accuweather.com/locations/v1/cities/{countryCode}/search
+    // http://dataservice.accuweather.com/locations/v1/cities/geoposition/search
+    // Any configured location: US ZIP, country-qualified postal code, city or coordinates
+    pub async fn search(client: &AccuWeatherClient, query: &LocationQuery) -> Result<Option<Location>, WeatherError> {
+        let (path, q) = search_path(query);
+        let location = match query {
+            // The geoposition search returns a single location rather than a list
+            LocationQuery::Coordinates { .. } => client.get_optional::<Location>(&path, &[("q", &q)], "location_search", &q).await?,
+            _ => client.get_optional::<Locations>(&path, &[("q", &q)], "location_search", &q).await?
+                .and_then(|locations| locations.into_iter().next()),
+        };
+        if location.is_none() {
+            log::warn!("[accuweather] No locations found for {}", q);
         }
+        Ok(location)
+    }
 
+    // http://dataservice.accuweather.com/locations/v1/{location_id}
+    pub async fn get(client: &AccuWeatherClient, key: &str) -> Result<Location, WeatherError> {
+        client.get(&format!("locations/v1/{}", key), &[("details", "true")], "location", key).await
+    }
+
+    /// The location in the providers' common form
+    pub fn common(&self) -> CommonLocation {
+        CommonLocation {
+            latitude: self.geo_position.latitude,
+            longitude: self.geo_position.longitude,
+            name: self.localized_name.clone(),
+            country: Some(self.country.localized_name.clone()),
+            region: self.administrative_area.as_ref().map(|a| a.localized_name.clone()),
+            postal_code: self.primary_postal_code.clone(),
+        }
     }
 }
 
+/// A region or country
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Area {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub localized_name: String,
+    #[serde(default)]
+    pub english_name: String,
+}
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
+pub struct AdministrativeArea {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub localized_name: String,
+    pub english_name: String,
+    pub level: f64,
+    pub localized_type: String,
+    pub english_type: String,
+    #[serde(rename = "CountryID")]
+    pub country_id: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TimeZone {
+    pub code: String,
+    pub name: String,
+    pub gmt_offset: f64,
+    pub is_daylight_saving: bool,
+    pub next_offset_change: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GeoPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation: Option<Measurement>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParentCity {
+    pub key: String,
+    pub localized_name: String,
+    pub english_name: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SupplementalAdminArea {
+    pub level: f64,
+    pub localized_name: String,
+    pub english_name: String,
+}
+
+/// Station and zone codes of a location (details=true); most are empty outside the US
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct Details {
+    pub key: String,
+    pub station_code: String,
+    pub station_gmt_offset: f64,
+    pub band_map: String,
+    pub climo: String,
+    pub local_radar: String,
+    pub media_region: Option<String>,
+    pub metar: String,
+    #[serde(rename = "NXMetro")]
+    pub nxmetro: String,
+    #[serde(rename = "NXState")]
+    pub nxstate: String,
+    pub population: Option<f64>,
+    pub primary_warning_county_code: String,
+    pub primary_warning_zone_code: String,
+    pub satellite: String,
+    pub synoptic: String,
+    pub marine_station: Option<String>,
+    #[serde(rename = "MarineStationGMTOffset")]
+    pub marine_station_gmt_offset: Option<f64>,
+    pub video_code: String,
+    pub location_stem: String,
+    #[serde(rename = "DMA")]
+    pub dma: Option<Dma>,
+    pub sources: Vec<Source>,
+    pub canonical_postal_code: String,
+    pub canonical_location_key: String,
+}
+
+/// US designated market area
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Dma {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub english_name: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Source {
+    pub data_type: String,
+    pub source: String,
+    pub source_id: f64,
+}
+
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
 pub struct Forecast {
-    #[serde(rename = "Headline")]
     pub headline: Headline,
-    #[serde(rename = "DailyForecasts")]
     pub daily_forecasts: Vec<DailyForecast>,
 }
 impl Forecast {
 
     // http://dataservice.accuweather.com/forecasts/v1/daily/1day/{location_id}
-    // apikey: string
-    // language: string
-    // details: bool
-    // metric: bool
-    pub fn get_daily(config: Config, location: Location) -> Result<Forecast, reqwest::Error> {
-        let mut url = format!("http://dataservice.accuweather.com/forecasts/v1/daily/1day/{}{}", location.key, config.to_params());
-
-        let request = reqwest::blocking::Client::new().get(url).send();
-        match request {
-            Ok(req) => {
-                let json = req.json::<Forecast>()?;
-                return Ok(json);
-            },
-            Err(err) => {
-                return Err(err);
-            }
-        }
-
+    pub async fn get_daily(client: &AccuWeatherClient, location: &Location) -> Result<Forecast, WeatherError> {
+        client.get(&format!("forecasts/v1/daily/1day/{}", location.key), &[], "daily_1day", &location.key).await
     }
 }
 
 pub type CurrentConditions = Vec<CurrentCondition>;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
 pub struct CurrentCondition {
-    #[serde(rename = "LocalObservationDateTime")]
     pub local_observation_date_time: String,
-    #[serde(rename = "EpochTime")]
     pub epoch_time: i64,
-    #[serde(rename = "WeatherText")]
     pub weather_text: String,
-    #[serde(rename = "WeatherIcon")]
     pub weather_icon: i64,
-    #[serde(rename = "HasPrecipitation")]
     pub has_precipitation: bool,
-    #[serde(rename = "PrecipitationType")]
     pub precipitation_type: Option<String>,
-    #[serde(rename = "IsDayTime")]
     pub is_day_time: bool,
-    #[serde(rename = "Temperature")]
-    pub temperature: Temperature2,
-    #[serde(rename = "MobileLink")]
+    pub temperature: Measurement,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub real_feel_temperature: Option<Measurement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_humidity: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind: Option<WindInfo<Measurement>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_gust: Option<WindInfo<Measurement>>,
+    #[serde(rename = "UVIndex", default, skip_serializing_if = "Option::is_none")]
+    pub uv_index: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Measurement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<Measurement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precipitation_summary: Option<PrecipitationSummary>,
+    #[serde(default)]
     pub mobile_link: String,
-    #[serde(rename = "Link")]
+    #[serde(default)]
     pub link: String,
 }
 impl CurrentCondition {
 
     // http://dataservice.accuweather.com/currentconditions/v1/{location_id}
-    // apikey: string
-    // language: string
-    // details: bool
-    pub fn get(config: Config, location: Location) -> Result<Option<CurrentCondition>, reqwest::Error> {
-        let mut url = format!("http://dataservice.accuweather.com/currentconditions/v1/{}{}", location.key, config.to_params());
-
-        let request = reqwest::blocking::Client::new().get(url).send();
-        match request {
-            Ok(req) => {
-                let json = req.json::<CurrentConditions>()?;
-                
-                // Check if we have any conditions before accessing
-                if let Some(first) = json.first() {
-                    return Ok(Some(first.clone()));
-                } else {
-                    // Log empty result scenario
-                    eprintln!("[accuweather] Warning: No current conditions found for location: {}", location.key);
-                    // Return None to signal no data found
-                    return Ok(None);
-                }
-            },
-            Err(err) => {
-                return Err(err);
-            }
+    pub async fn get(client: &AccuWeatherClient, location: &Location) -> Result<Option<CurrentCondition>, WeatherError> {
+        let conditions: CurrentConditions = client.get(&format!("currentconditions/v1/{}", location.key), &[], "current", &location.key).await?;
+        if conditions.is_empty() {
+            log::warn!("[accuweather] No current conditions found for location: {}", location.key);
         }
-
+        Ok(conditions.into_iter().next())
     }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
+pub struct PrecipitationSummary {
+    pub precipitation: Option<Measurement>,
+    pub past_hour: Option<Measurement>,
+    #[serde(rename = "Past24Hours")]
+    pub past_24_hours: Option<Measurement>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
 pub struct Headline {
-    #[serde(rename = "EffectiveDate")]
     pub effective_date: String,
-    #[serde(rename = "EffectiveEpochDate")]
     pub effective_epoch_date: f64,
-    #[serde(rename = "Severity")]
     pub severity: f64,
-    #[serde(rename = "Text")]
     pub text: String,
-    #[serde(rename = "Category")]
     pub category: String,
-    #[serde(rename = "EndDate")]
-    pub end_date: String,
-    #[serde(rename = "EndEpochDate")]
-    pub end_epoch_date: f64,
-    #[serde(rename = "MobileLink")]
+    // Both null for a headline without an end
+    pub end_date: Option<String>,
+    pub end_epoch_date: Option<f64>,
+    #[serde(default)]
     pub mobile_link: String,
-    #[serde(rename = "Link")]
+    #[serde(default)]
     pub link: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
 pub struct DailyForecast {
-    #[serde(rename = "Date")]
     pub date: String,
-    #[serde(rename = "EpochDate")]
     pub epoch_date: f64,
-    #[serde(rename = "Sun")]
     pub sun: Option<Sun>,
-    #[serde(rename = "Moon")]
     pub moon: Option<Moon>,
-    #[serde(rename = "Temperature")]
-    pub temperature: Temperature,
-    #[serde(rename = "RealFeelTemperature")]
-    pub real_feel_temperature: Option<RealFeelTemperature>,
-    #[serde(rename = "RealFeelTemperatureShade")]
-    pub real_feel_temperature_shade: Option<RealFeelTemperatureShade>,
-    #[serde(rename = "HoursOfSun")]
+    pub temperature: Range,
+    pub real_feel_temperature: Option<Range>,
+    pub real_feel_temperature_shade: Option<Range>,
     pub hours_of_sun: Option<f64>,
-    #[serde(rename = "DegreeDaySummary")]
     pub degree_day_summary: Option<DegreeDaySummary>,
-    #[serde(rename = "AirAndPollen")]
     pub air_and_pollen: Option<Vec<AirAndPollen>>,
-    // #[serde(rename = "Day")]
-    // pub day: Day,
-    // #[serde(rename = "Night")]
-    // pub night: Night,
-    // #[serde(rename = "Sources")]
-    // pub sources: Vec<String>,
-    #[serde(rename = "MobileLink")]
+    pub day: DayPart,
+    pub night: DayPart,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
     pub mobile_link: String,
-    #[serde(rename = "Link")]
+    #[serde(default)]
     pub link: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
 pub struct Sun {
-    #[serde(rename = "Rise")]
     pub rise: String,
-    #[serde(rename = "EpochRise")]
     pub epoch_rise: f64,
-    #[serde(rename = "Set")]
     pub set: String,
-    #[serde(rename = "EpochSet")]
     pub epoch_set: f64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
 pub struct Moon {
-    #[serde(rename = "Rise")]
-    pub rise: String,
-    #[serde(rename = "EpochRise")]
-    pub epoch_rise: f64,
-    #[serde(rename = "Set")]
-    pub set: String,
-    #[serde(rename = "EpochSet")]
-    pub epoch_set: f64,
-    #[serde(rename = "Phase")]
+    pub rise: Option<String>,
+    pub epoch_rise: Option<f64>,
+    pub set: Option<String>,
+    pub epoch_set: Option<f64>,
     pub phase: String,
-    #[serde(rename = "Age")]
     pub age: f64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Temperature {
-    #[serde(rename = "Minimum")]
-    pub minimum: Minimum,
-    #[serde(rename = "Maximum")]
-    pub maximum: Maximum,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Temperature2 {
-    #[serde(rename = "Metric")]
-    pub metric: Metric,
-    #[serde(rename = "Imperial")]
-    pub imperial: Imperial,
-}
-
-
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Minimum {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Maximum {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct RealFeelTemperature {
-    #[serde(rename = "Minimum")]
-    pub minimum: Minimum2,
-    #[serde(rename = "Maximum")]
-    pub maximum: Maximum2,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Minimum2 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-    #[serde(rename = "Phrase")]
-    pub phrase: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Maximum2 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-    #[serde(rename = "Phrase")]
-    pub phrase: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct RealFeelTemperatureShade {
-    #[serde(rename = "Minimum")]
-    pub minimum: Minimum3,
-    #[serde(rename = "Maximum")]
-    pub maximum: Maximum3,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Minimum3 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-    #[serde(rename = "Phrase")]
-    pub phrase: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Maximum3 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-    #[serde(rename = "Phrase")]
-    pub phrase: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
 pub struct DegreeDaySummary {
-    #[serde(rename = "Heating")]
-    pub heating: Heating,
-    #[serde(rename = "Cooling")]
-    pub cooling: Cooling,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Heating {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Cooling {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
+    pub heating: UnitValue,
+    pub cooling: UnitValue,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "PascalCase")]
 pub struct AirAndPollen {
-    #[serde(rename = "Name")]
     pub name: String,
-    #[serde(rename = "Value")]
     pub value: f64,
-    #[serde(rename = "Category")]
     pub category: String,
-    #[serde(rename = "CategoryValue")]
     pub category_value: f64,
     #[serde(rename = "Type")]
     pub type_field: Option<String>,
 }
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Day {
-    #[serde(rename = "Icon")]
-    pub icon: f64,
-    #[serde(rename = "IconPhrase")]
-    pub icon_phrase: String,
-    #[serde(rename = "HasPrecipitation")]
-    pub has_precipitation: bool,
-    #[serde(rename = "ShortPhrase")]
-    pub short_phrase: Option<String>,
-    #[serde(rename = "LongPhrase")]
-    pub long_phrase: Option<String>,
-    #[serde(rename = "PrecipitationProbability")]
-    pub precipitation_probability: Option<f64>,
-    #[serde(rename = "ThunderstormProbability")]
-    pub thunderstorm_probability: Option<f64>,
-    #[serde(rename = "RainProbability")]
-    pub rain_probability: Option<f64>,
-    #[serde(rename = "SnowProbability")]
-    pub snow_probability: Option<f64>,
-    #[serde(rename = "IceProbability")]
-    pub ice_probability: Option<f64>,
-    #[serde(rename = "Wind")]
-    pub wind: Wind,
-    #[serde(rename = "WindGust")]
-    pub wind_gust: WindGust,
-    #[serde(rename = "TotalLiquid")]
-    pub total_liquid: TotalLiquid,
-    #[serde(rename = "Rain")]
-    pub rain: Rain,
-    #[serde(rename = "Snow")]
-    pub snow: Snow,
-    #[serde(rename = "Ice")]
-    pub ice: Ice,
-    #[serde(rename = "HoursOfPrecipitation")]
-    pub hours_of_precipitation: f64,
-    #[serde(rename = "HoursOfRain")]
-    pub hours_of_rain: f64,
-    #[serde(rename = "HoursOfSnow")]
-    pub hours_of_snow: f64,
-    #[serde(rename = "HoursOfIce")]
-    pub hours_of_ice: f64,
-    #[serde(rename = "CloudCover")]
-    pub cloud_cover: f64,
-    #[serde(rename = "Evapotranspiration")]
-    pub evapotranspiration: Evapotranspiration,
-    #[serde(rename = "SolarIrradiance")]
-    pub solar_irradiance: SolarIrradiance,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Wind {
-    #[serde(rename = "Speed")]
-    pub speed: Speed,
-    #[serde(rename = "Direction")]
-    pub direction: Direction,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Speed {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Direction {
-    #[serde(rename = "Degrees")]
-    pub degrees: f64,
-    #[serde(rename = "Localized")]
-    pub localized: String,
-    #[serde(rename = "English")]
-    pub english: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct WindGust {
-    #[serde(rename = "Speed")]
-    pub speed: Speed2,
-    #[serde(rename = "Direction")]
-    pub direction: Direction2,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Speed2 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Direction2 {
-    #[serde(rename = "Degrees")]
-    pub degrees: f64,
-    #[serde(rename = "Localized")]
-    pub localized: String,
-    #[serde(rename = "English")]
-    pub english: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TotalLiquid {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Rain {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Snow {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
+impl AirAndPollen {
+
+    // AccuWeather reports pollen and mold alongside AirQuality and UVIndex in the same list;
+    // only the allergen entries map to a pollen level.
+    pub fn pollen_level(&self) -> Option<(PollenType, PollenLevel)> {
+        let pollen_type = PollenType::from_name(&self.name)?;
+        Some((pollen_type, PollenLevel {
+            value: self.value,
+            category: self.category.clone(),
+            category_value: self.category_value,
+        }))
+    }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Ice {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
+impl DailyForecast {
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Evapotranspiration {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SolarIrradiance {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
+    // Normalizes the AirAndPollen entries of a daily forecast (requires details=true)
+    pub fn pollen_report(&self, location: CommonLocation) -> PollenReport {
+        let mut report = PollenReport::new(location, "AccuWeather".to_string(), self.date.clone());
+        if let Some(entries) = &self.air_and_pollen {
+            for entry in entries {
+                if let Some((pollen_type, level)) = entry.pollen_level() {
+                    report.set_level(pollen_type, level);
+                }
+            }
+        }
+        report
+    }
 }
 
+/// The Day or Night half of a daily forecast
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Night {
-    #[serde(rename = "Icon")]
-    pub icon: f64,
-    #[serde(rename = "IconPhrase")]
+#[serde(rename_all = "PascalCase")]
+pub struct DayPart {
+    pub icon: i64,
     pub icon_phrase: String,
-    #[serde(rename = "HasPrecipitation")]
     pub has_precipitation: bool,
-    #[serde(rename = "ShortPhrase")]
+    pub precipitation_type: Option<String>,
+    pub precipitation_intensity: Option<String>,
     pub short_phrase: Option<String>,
-    #[serde(rename = "LongPhrase")]
     pub long_phrase: Option<String>,
-    #[serde(rename = "PrecipitationProbability")]
     pub precipitation_probability: Option<f64>,
-    #[serde(rename = "ThunderstormProbability")]
     pub thunderstorm_probability: Option<f64>,
-    #[serde(rename = "RainProbability")]
     pub rain_probability: Option<f64>,
-    #[serde(rename = "SnowProbability")]
     pub snow_probability: Option<f64>,
-    #[serde(rename = "IceProbability")]
     pub ice_probability: Option<f64>,
-    #[serde(rename = "Wind")]
-    pub wind: Wind2,
-    #[serde(rename = "WindGust")]
-    pub wind_gust: WindGust2,
-    #[serde(rename = "TotalLiquid")]
-    pub total_liquid: TotalLiquid2,
-    #[serde(rename = "Rain")]
-    pub rain: Rain2,
-    #[serde(rename = "Snow")]
-    pub snow: Snow2,
-    #[serde(rename = "Ice")]
-    pub ice: Ice2,
-    #[serde(rename = "HoursOfPrecipitation")]
-    pub hours_of_precipitation: f64,
-    #[serde(rename = "HoursOfRain")]
-    pub hours_of_rain: f64,
-    #[serde(rename = "HoursOfSnow")]
-    pub hours_of_snow: f64,
-    #[serde(rename = "HoursOfIce")]
-    pub hours_of_ice: f64,
-    #[serde(rename = "CloudCover")]
-    pub cloud_cover: f64,
-    #[serde(rename = "Evapotranspiration")]
-    pub evapotranspiration: Evapotranspiration,
-    #[serde(rename = "SolarIrradiance")]
-    pub solar_irradiance: SolarIrradiance,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Wind2 {
-    #[serde(rename = "Speed")]
-    pub speed: Speed3,
-    #[serde(rename = "Direction")]
-    pub direction: Direction3,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Speed3 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Direction3 {
-    #[serde(rename = "Degrees")]
-    pub degrees: f64,
-    #[serde(rename = "Localized")]
-    pub localized: String,
-    #[serde(rename = "English")]
-    pub english: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct WindGust2 {
-    #[serde(rename = "Speed")]
-    pub speed: Speed4,
-    #[serde(rename = "Direction")]
-    pub direction: Direction4,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Speed4 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Direction4 {
-    #[serde(rename = "Degrees")]
-    pub degrees: f64,
-    #[serde(rename = "Localized")]
-    pub localized: String,
-    #[serde(rename = "English")]
-    pub english: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TotalLiquid2 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Rain2 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Snow2 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Ice2 {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Region {
-    #[serde(rename = "ID")]
-    pub id: String,
-    #[serde(rename = "LocalizedName")]
-    pub localized_name: String,
-    #[serde(rename = "EnglishName")]
-    pub english_name: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Country {
-    #[serde(rename = "ID")]
-    pub id: String,
-    #[serde(rename = "LocalizedName")]
-    pub localized_name: String,
-    #[serde(rename = "EnglishName")]
-    pub english_name: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct AdministrativeArea {
-    #[serde(rename = "ID")]
-    pub id: String,
-    #[serde(rename = "LocalizedName")]
-    pub localized_name: String,
-    #[serde(rename = "EnglishName")]
-    pub english_name: String,
-    #[serde(rename = "Level")]
-    pub level: f64,
-    #[serde(rename = "LocalizedType")]
-    pub localized_type: String,
-    #[serde(rename = "EnglishType")]
-    pub english_type: String,
-    #[serde(rename = "CountryID")]
-    pub country_id: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TimeZone {
-    #[serde(rename = "Code")]
-    pub code: String,
-    #[serde(rename = "Name")]
-    pub name: String,
-    #[serde(rename = "GmtOffset")]
-    pub gmt_offset: f64,
-    #[serde(rename = "IsDaylightSaving")]
-    pub is_daylight_saving: bool,
-    #[serde(rename = "NextOffsetChange")]
-    pub next_offset_change: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct GeoPosition {
-    #[serde(rename = "Latitude")]
-    pub latitude: f64,
-    #[serde(rename = "Longitude")]
-    pub longitude: f64,
-    #[serde(rename = "Elevation")]
-    pub elevation: Elevation,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Elevation {
-    #[serde(rename = "Metric")]
-    pub metric: Metric,
-    #[serde(rename = "Imperial")]
-    pub imperial: Imperial,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Metric {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Imperial {
-    #[serde(rename = "Value")]
-    pub value: f64,
-    #[serde(rename = "Unit")]
-    pub unit: String,
-    #[serde(rename = "UnitType")]
-    pub unit_type: f64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ParentCity {
-    #[serde(rename = "Key")]
-    pub key: String,
-    #[serde(rename = "LocalizedName")]
-    pub localized_name: String,
-    #[serde(rename = "EnglishName")]
-    pub english_name: String,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SupplementalAdminArea {
-    #[serde(rename = "Level")]
-    pub level: f64,
-    #[serde(rename = "LocalizedName")]
-    pub localized_name: String,
-    #[serde(rename = "EnglishName")]
-    pub english_name: String,
+    pub wind: Option<WindInfo>,
+    pub wind_gust: Option<WindInfo>,
+    pub total_liquid: Option<UnitValue>,
+    pub rain: Option<UnitValue>,
+    pub snow: Option<UnitValue>,
+    pub ice: Option<UnitValue>,
+    pub hours_of_precipitation: Option<f64>,
+    pub hours_of_rain: Option<f64>,
+    pub hours_of_snow: Option<f64>,
+    pub hours_of_ice: Option<f64>,
+    pub cloud_cover: Option<f64>,
+    pub evapotranspiration: Option<UnitValue>,
+    pub solar_irradiance: Option<UnitValue>,
+}
+
+// http://dataservice.accuweather.com/forecasts/v1/hourly/12hour/{location_id}
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HourlyForecast {
+    pub date_time: String,
+    #[serde(default)]
+    pub epoch_date_time: i64,
+    pub weather_icon: i64,
+    pub icon_phrase: String,
+    #[serde(default)]
+    pub has_precipitation: bool,
+    #[serde(default)]
+    pub is_daylight: bool,
+    pub temperature: UnitValue,
+    pub real_feel_temperature: Option<UnitValue>,
+    pub wind: Option<WindInfo>,
+    pub wind_gust: Option<WindInfo>,
+    pub relative_humidity: Option<f64>,
+    pub precipitation_probability: f64,
+    pub total_liquid: Option<UnitValue>,
+    pub rain: Option<UnitValue>,
+    pub snow: Option<UnitValue>,
+    pub ice: Option<UnitValue>,
+    pub cloud_cover: Option<f64>,
+    #[serde(rename = "UVIndex")]
+    pub uv_index: Option<f64>,
+    pub solar_irradiance: Option<UnitValue>,
+    #[serde(default)]
+    pub mobile_link: String,
+    #[serde(default)]
+    pub link: String,
 }
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Details {
-    #[serde(rename = "Key")]
-    pub key: String,
-    #[serde(rename = "StationCode")]
-    pub station_code: String,
-    #[serde(rename = "StationGmtOffset")]
-    pub station_gmt_offset: f64,
-    #[serde(rename = "BandMap")]
-    pub band_map: String,
-    #[serde(rename = "Climo")]
-    pub climo: String,
-    #[serde(rename = "LocalRadar")]
-    pub local_radar: String,
-    #[serde(rename = "MediaRegion")]
-    pub media_region: Option<String>,
-    #[serde(rename = "Metar")]
-    pub metar: String,
-    #[serde(rename = "NXMetro")]
-    pub nxmetro: String,
-    #[serde(rename = "NXState")]
-    pub nxstate: String,
-    // #[serde(rename = "Population")]
-    // pub population: Value,
-    #[serde(rename = "PrimaryWarningCountyCode")]
-    pub primary_warning_county_code: String,
-    #[serde(rename = "PrimaryWarningZoneCode")]
-    pub primary_warning_zone_code: String,
-    #[serde(rename = "Satellite")]
-    pub satellite: String,
-    #[serde(rename = "Synoptic")]
-    pub synoptic: String,
-    #[serde(rename = "MarineStation")]
-    pub marine_station: String,
-    // #[serde(rename = "MarineStationGMTOffset")]
-    // pub marine_station_gmtoffset: Value,
-    #[serde(rename = "VideoCode")]
-    pub video_code: String,
-    #[serde(rename = "LocationStem")]
-    pub location_stem: String,
-    // #[serde(rename = "DMA")]
-    // pub dma: Option<Dma>,
-    // #[serde(rename = "PartnerID")]
-    // pub partner_id: Value,
-    // #[serde(rename = "Sources")]
-    // pub sources: Vec<Source>,
-    #[serde(rename = "CanonicalPostalCode")]
-    pub canonical_postal_code: String,
-    #[serde(rename = "CanonicalLocationKey")]
-    pub canonical_location_key: String,
-}
\ No newline at end of file
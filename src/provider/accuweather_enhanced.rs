@@ -2,11 +2,20 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use super::common::{
     Weather, WeatherError, WeatherProvider, Forecast, Alert, Location, 
-    DailyForecast, HourlyForecast, AlertSeverity, WeatherFeature, 
-    HistoricalData, RateLimiter
+    DailyForecast, DayPartForecast, HourlyForecast, AlertSeverity, WeatherFeature, 
+    PollenReport, MarineForecast, LocationQuery
 };
-use std::sync::Arc;
+use super::accuweather::{self, AccuWeatherClient, Config as AccuConfig};
+use super::noaa::NoaaMarineClient;
+use crate::config::MarineConfig;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::secret::Secret;
+use crate::analysis::comfort::comfort;
+use crate::analysis::conditions::ConditionCode;
+use crate::analysis::road::estimate_road_condition;
 use crate::utils::time::safe_timestamp_with_fallback;
+use crate::units::{Pressure, Speed, Temperature};
 
 // Helper function to safely get current timestamp
 fn get_current_timestamp() -> Result<i64, WeatherError> {
@@ -17,163 +26,196 @@ fn get_current_timestamp() -> Result<i64, WeatherError> {
 }
 
 pub struct AccuWeatherProvider {
-    api_key: String,
-    base_url: String,
-    rate_limiter: Arc<RateLimiter>,
-    client: reqwest::Client,
+    client: AccuWeatherClient,
+    marine: Option<MarineConfig>,
+    noaa: NoaaMarineClient,
 }
 
 impl AccuWeatherProvider {
-    pub fn new(api_key: String) -> Self {
+    pub fn new(api_key: impl Into<Secret<String>>) -> Self {
+        Self::from_client(AccuWeatherClient::new(AccuConfig {
+            apikey: api_key.into(),
+            ..AccuConfig::default()
+        }))
+    }
+
+    /// Shares `client`'s connection pool and rate limit with the legacy accuweather functions
+    pub fn from_client(client: AccuWeatherClient) -> Self {
         Self {
-            api_key,
-            base_url: "http://dataservice.accuweather.com".to_string(),
-            rate_limiter: Arc::new(RateLimiter::new(50, 3600)), // 50 requests per hour for free tier
-            client: reqwest::Client::new(),
+            client,
+            marine: None,
+            noaa: NoaaMarineClient::new(),
         }
     }
     
+    // AccuWeather has no wave or tide data of its own; marine forecasts pair its
+    // MarineStation zone with the configured NOAA stations.
+    pub fn with_marine_stations(mut self, marine: MarineConfig) -> Self {
+        self.marine = Some(marine);
+        self
+    }
+    
     async fn get_location_key(&self, location: &str) -> Result<String, WeatherError> {
-        if !self.rate_limiter.check_rate_limit() {
-            return Err(WeatherError::RateLimitExceeded);
-        }
-        
-        let url = if location.chars().all(|c| c.is_digit(10)) {
-            format!("{}/locations/v1/postalcodes/search?apikey={}&q={}", 
-                self.base_url, self.api_key, location)
-        } else {
-            format!("{}/locations/v1/cities/search?apikey={}&q={}", 
-                self.base_url, self.api_key, location)
-        };
-        
-        let response = self.client.get(&url)
-            .send()
-            .await?;
-            
-        if response.status() == 401 {
-            return Err(WeatherError::InvalidApiKey);
-        }
-        
-        let locations: Vec<AccuLocation> = response.json().await?;
-        
-        locations.first()
-            .map(|l| l.key.clone())
+        let query = LocationQuery::parse(location).map_err(WeatherError::ConfigurationError)?;
+        accuweather::Location::search(&self.client, &query).await?
+            .map(|l| l.key)
             .ok_or_else(|| WeatherError::NotFound(format!("Location not found: {}", location)))
     }
     
-    async fn get_5day_forecast(&self, location_key: &str) -> Result<Vec<AccuDailyForecast>, WeatherError> {
-        if !self.rate_limiter.check_rate_limit() {
-            return Err(WeatherError::RateLimitExceeded);
-        }
-        
-        let url = format!("{}/forecasts/v1/daily/5day/{}?apikey={}&metric=true", 
-            self.base_url, location_key, self.api_key);
-            
-        let response = self.client.get(&url)
-            .send()
-            .await?;
-            
-        let forecast: AccuForecastResponse = response.json().await?;
+    async fn get_5day_forecast(&self, location_key: &str) -> Result<Vec<accuweather::DailyForecast>, WeatherError> {
+        // Day and Night only carry precipitation, wind, cloud cover and solar irradiance with details
+        let forecast: accuweather::Forecast = self.client.get(&format!("forecasts/v1/daily/5day/{}", location_key),
+            &[("metric", "true"), ("details", "true")], "daily_5day", location_key).await?;
         Ok(forecast.daily_forecasts)
     }
     
-    async fn get_hourly_forecast(&self, location_key: &str) -> Result<Vec<AccuHourlyForecast>, WeatherError> {
-        if !self.rate_limiter.check_rate_limit() {
-            return Err(WeatherError::RateLimitExceeded);
-        }
-        
-        let url = format!("{}/forecasts/v1/hourly/12hour/{}?apikey={}&metric=true", 
-            self.base_url, location_key, self.api_key);
-            
-        let response = self.client.get(&url)
-            .send()
-            .await?;
-            
-        response.json().await.map_err(|e| e.into())
+    async fn get_hourly_forecast(&self, location_key: &str) -> Result<Vec<accuweather::HourlyForecast>, WeatherError> {
+        // Cloud cover and solar irradiance are only sent with details
+        self.client.get(&format!("forecasts/v1/hourly/12hour/{}", location_key),
+            &[("metric", "true"), ("details", "true")], "hourly_12hour", location_key).await
     }
     
     async fn get_weather_alerts(&self, location_key: &str) -> Result<Vec<AccuAlert>, WeatherError> {
-        if !self.rate_limiter.check_rate_limit() {
-            return Err(WeatherError::RateLimitExceeded);
-        }
-        
-        let url = format!("{}/alerts/v1/{}?apikey={}", 
-            self.base_url, location_key, self.api_key);
-            
-        let response = self.client.get(&url)
-            .send()
-            .await?;
-            
-        if response.status() == 204 {
-            return Ok(Vec::new());
-        }
-        
-        response.json().await.map_err(|e| e.into())
+        // 204 No Content when there are no alerts
+        let alerts = self.client.get_optional(&format!("alerts/v1/{}", location_key),
+            &[], "alerts", location_key).await?;
+        Ok(alerts.unwrap_or_default())
     }
     
-    async fn get_location_details(&self, location_key: &str) -> Result<AccuLocation, WeatherError> {
-        if !self.rate_limiter.check_rate_limit() {
-            return Err(WeatherError::RateLimitExceeded);
-        }
-        
-        let url = format!("{}/locations/v1/{}?apikey={}", 
-            self.base_url, location_key, self.api_key);
-            
-        let response = self.client.get(&url)
-            .send()
-            .await?;
-            
-        response.json().await.map_err(|e| e.into())
+    async fn get_1day_forecast_details(&self, location_key: &str) -> Result<Vec<accuweather::DailyForecast>, WeatherError> {
+        // AirAndPollen is only included when details=true
+        let forecast: accuweather::Forecast = self.client.get(&format!("forecasts/v1/daily/1day/{}", location_key),
+            &[("details", "true"), ("metric", "true")], "daily_1day", location_key).await?;
+        Ok(forecast.daily_forecasts)
+    }
+    
+    async fn get_location_details(&self, location_key: &str) -> Result<accuweather::Location, WeatherError> {
+        accuweather::Location::get(&self.client, location_key).await
+    }
+}
+
+/// Weather from one current-conditions entry, for live requests and archive replays alike
+fn current_weather(condition: &accuweather::CurrentCondition, location: Location, timestamp: i64) -> Weather {
+    // Metric values are in °C, km/h and mb (hPa)
+    let temperature = Temperature::from_celsius(condition.temperature.metric.value);
+    let wind_speed = condition.wind.as_ref().map(|w| Speed::from_kmh(w.speed.metric.value));
+    Weather {
+        temperature,
+        feels_like: condition.real_feel_temperature.as_ref().map(|t| Temperature::from_celsius(t.metric.value)),
+        humidity: condition.relative_humidity,
+        pressure: condition.pressure.as_ref().map(|p| Pressure::from_hpa(p.metric.value)),
+        wind_speed,
+        wind_direction: condition.wind.as_ref().and_then(|w| w.direction.as_ref()).map(|d| d.degrees),
+        description: condition.weather_text.clone(),
+        condition: ConditionCode::from_description(&condition.weather_text),
+        icon: Some(condition.weather_icon.to_string()),
+        precipitation: condition.precipitation_summary.as_ref()
+            .and_then(|p| p.precipitation.as_ref())
+            .map(|p| p.metric.value),
+        visibility: condition.visibility.as_ref().map(|v| v.metric.value),
+        uv_index: condition.uv_index,
+        road_condition: Some(estimate_road_condition(
+            temperature,
+            condition.relative_humidity,
+            condition.precipitation_summary.as_ref()
+                .and_then(|p| p.precipitation.as_ref())
+                .map(|p| p.metric.value),
+            None,
+        )),
+        comfort: Some(comfort(
+            temperature,
+            condition.relative_humidity,
+            wind_speed,
+            condition.uv_index,
+        )),
+        provider: "AccuWeather".to_string(),
+        location,
+        timestamp,
+        sources: Vec::new(),
+        spread: BTreeMap::new(),
+    }
+}
+
+/// Re-normalizes an archived `current` payload; `location_details` is the archived `location`
+/// payload for the same location key, when there is one
+fn day_part(part: &accuweather::DayPart) -> DayPartForecast {
+    DayPartForecast {
+        description: part.icon_phrase.clone(),
+        condition: ConditionCode::from_description(&part.icon_phrase),
+        icon: Some(part.icon.to_string()),
+        precipitation_probability: part.precipitation_probability,
+        precipitation_amount: part.total_liquid.as_ref().map(|t| t.value),
+        cloud_cover: part.cloud_cover,
+        wind_speed: part.wind.as_ref().map(|w| Speed::from_kmh(w.speed.value)),
+        wind_direction: part.wind.as_ref().and_then(|w| w.direction.as_ref()).map(|d| d.degrees),
+        solar_irradiance: part.solar_irradiance.as_ref().map(|s| s.value),
+    }
+}
+
+/// Combines a value of the day and night halves, or takes whichever one is known
+fn whole_day(day: Option<f64>, night: Option<f64>, combine: fn(f64, f64) -> f64) -> Option<f64> {
+    match (day, night) {
+        (Some(day), Some(night)) => Some(combine(day, night)),
+        (day, night) => day.or(night),
+    }
+}
+
+/// Common daily forecast from an AccuWeather day (metric, with details): the description and
+/// wind are the daytime ones, the chance of precipitation the higher of the two halves, and
+/// precipitation and solar energy add up over both
+pub fn daily_forecast(d: &accuweather::DailyForecast) -> DailyForecast {
+    let (day, night) = (day_part(&d.day), day_part(&d.night));
+    DailyForecast {
+        date: d.date.clone(),
+        temperature_min: Temperature::from_celsius(d.temperature.minimum.value),
+        temperature_max: Temperature::from_celsius(d.temperature.maximum.value),
+        humidity: None,
+        precipitation_probability: whole_day(day.precipitation_probability, night.precipitation_probability, f64::max),
+        precipitation_amount: whole_day(day.precipitation_amount, night.precipitation_amount, |a, b| a + b),
+        wind_speed: day.wind_speed,
+        wind_direction: day.wind_direction,
+        description: day.description.clone(),
+        condition: day.condition,
+        icon: day.icon.clone(),
+        sunrise: d.sun.as_ref().map(|s| s.rise.clone()),
+        sunset: d.sun.as_ref().map(|s| s.set.clone()),
+        solar_irradiance: whole_day(day.solar_irradiance, night.solar_irradiance, |a, b| a + b),
+        day: Some(day),
+        night: Some(night),
     }
 }
 
+pub fn normalize_current(payload: &serde_json::Value, location_details: Option<&serde_json::Value>, location_key: &str, timestamp: i64) -> Result<Weather, WeatherError> {
+    let conditions: accuweather::CurrentConditions = serde_json::from_value(payload.clone())?;
+    let condition = conditions.first()
+        .ok_or_else(|| WeatherError::NotFound("No current conditions in payload".to_string()))?;
+    let location = match location_details {
+        Some(details) => serde_json::from_value::<accuweather::Location>(details.clone())?.common(),
+        None => Location {
+            latitude: 0.0,
+            longitude: 0.0,
+            name: location_key.to_string(),
+            country: None,
+            region: None,
+            postal_code: None,
+        },
+    };
+    Ok(current_weather(condition, location, timestamp))
+}
+
 #[async_trait]
 impl WeatherProvider for AccuWeatherProvider {
     async fn get_current_weather(&self, location: &str) -> Result<Weather, WeatherError> {
         let location_key = self.get_location_key(location).await?;
         
-        if !self.rate_limiter.check_rate_limit() {
-            return Err(WeatherError::RateLimitExceeded);
-        }
-        
-        let url = format!("{}/currentconditions/v1/{}?apikey={}&details=true", 
-            self.base_url, location_key, self.api_key);
-            
-        let response = self.client.get(&url)
-            .send()
-            .await?;
-            
-        let conditions: Vec<AccuCurrentCondition> = response.json().await?;
+        let conditions: accuweather::CurrentConditions = self.client.get(&format!("currentconditions/v1/{}", location_key),
+            &[("details", "true")], "current", &location_key).await?;
         let condition = conditions.first()
             .ok_or_else(|| WeatherError::NotFound("No current conditions available".to_string()))?;
         
         let location_details = self.get_location_details(&location_key).await?;
         
-        Ok(Weather {
-            temperature: condition.temperature.metric.value,
-            feels_like: condition.real_feel_temperature.as_ref().map(|t| t.metric.value),
-            humidity: condition.relative_humidity,
-            pressure: condition.pressure.as_ref().map(|p| p.metric.value),
-            wind_speed: condition.wind.as_ref().map(|w| w.speed.metric.value),
-            wind_direction: condition.wind.as_ref().map(|w| w.direction.degrees),
-            description: condition.weather_text.clone(),
-            icon: Some(condition.weather_icon.to_string()),
-            precipitation: condition.precipitation_summary.as_ref()
-                .and_then(|p| p.precipitation.as_ref())
-                .map(|p| p.metric.value),
-            visibility: condition.visibility.as_ref().map(|v| v.metric.value),
-            uv_index: condition.uv_index.map(|u| u as f64),
-            provider: "AccuWeather".to_string(),
-            location: Location {
-                latitude: location_details.geo_position.latitude,
-                longitude: location_details.geo_position.longitude,
-                name: location_details.localized_name,
-                country: Some(location_details.country.localized_name),
-                region: location_details.administrative_area.as_ref().map(|a| a.localized_name.clone()),
-                postal_code: location_details.primary_postal_code,
-            },
-            timestamp: safe_timestamp_with_fallback(),
-        })
+        Ok(current_weather(condition, location_details.common(), safe_timestamp_with_fallback()))
     }
     
     async fn get_forecast(&self, location: &str, days: u8) -> Result<Forecast, WeatherError> {
@@ -185,46 +227,29 @@ impl WeatherProvider for AccuWeatherProvider {
         
         let daily = daily_forecasts.iter()
             .take(days as usize)
-            .map(|d| DailyForecast {
-                date: d.date.clone(),
-                temperature_min: d.temperature.minimum.value,
-                temperature_max: d.temperature.maximum.value,
-                humidity: None,
-                precipitation_probability: d.day.precipitation_probability,
-                precipitation_amount: d.day.total_liquid.as_ref().map(|t| t.value),
-                wind_speed: d.day.wind.as_ref().map(|w| w.speed.value),
-                wind_direction: d.day.wind.as_ref().map(|w| w.direction.degrees),
-                description: d.day.icon_phrase.clone(),
-                icon: Some(d.day.icon.to_string()),
-                sunrise: d.sun.as_ref().map(|s| s.rise.clone()),
-                sunset: d.sun.as_ref().map(|s| s.set.clone()),
-            })
+            .map(daily_forecast)
             .collect();
         
         let hourly = Some(hourly_forecasts.iter()
             .map(|h| HourlyForecast {
                 datetime: h.date_time.clone(),
-                temperature: h.temperature.value,
-                feels_like: h.real_feel_temperature.as_ref().map(|t| t.value),
+                temperature: Temperature::from_celsius(h.temperature.value),
+                feels_like: h.real_feel_temperature.as_ref().map(|t| Temperature::from_celsius(t.value)),
                 humidity: h.relative_humidity,
-                precipitation_probability: Some(h.precipitation_probability as f64),
+                precipitation_probability: Some(h.precipitation_probability),
                 precipitation_amount: h.total_liquid.as_ref().map(|t| t.value),
-                wind_speed: h.wind.as_ref().map(|w| w.speed.value),
-                wind_direction: h.wind.as_ref().map(|w| w.direction.degrees),
+                wind_speed: h.wind.as_ref().map(|w| Speed::from_kmh(w.speed.value)),
+                wind_direction: h.wind.as_ref().and_then(|w| w.direction.as_ref()).map(|d| d.degrees),
                 description: h.icon_phrase.clone(),
+                condition: ConditionCode::from_description(&h.icon_phrase),
                 icon: Some(h.weather_icon.to_string()),
+                cloud_cover: h.cloud_cover,
+                solar_irradiance: h.solar_irradiance.as_ref().map(|s| s.value),
             })
             .collect());
         
         Ok(Forecast {
-            location: Location {
-                latitude: location_details.geo_position.latitude,
-                longitude: location_details.geo_position.longitude,
-                name: location_details.localized_name,
-                country: Some(location_details.country.localized_name),
-                region: location_details.administrative_area.as_ref().map(|a| a.localized_name.clone()),
-                postal_code: location_details.primary_postal_code,
-            },
+            location: location_details.common(),
             provider: "AccuWeather".to_string(),
             daily,
             hourly,
@@ -252,6 +277,77 @@ impl WeatherProvider for AccuWeatherProvider {
             .collect())
     }
     
+    async fn get_pollen(&self, location: &str) -> Result<PollenReport, WeatherError> {
+        let location_key = self.get_location_key(location).await?;
+        let location_details = self.get_location_details(&location_key).await?;
+        let daily_forecasts = self.get_1day_forecast_details(&location_key).await?;
+        
+        let today = daily_forecasts.first()
+            .ok_or_else(|| WeatherError::NotFound("No daily forecast available".to_string()))?;
+        
+        let mut report = PollenReport::new(
+            location_details.common(),
+            "AccuWeather".to_string(),
+            today.date.clone(),
+        );
+        
+        for entry in today.air_and_pollen.iter().flatten() {
+            if let Some((pollen_type, level)) = entry.pollen_level() {
+                report.set_level(pollen_type, level);
+            }
+        }
+        
+        if report.is_empty() {
+            return Err(WeatherError::NotFound(format!("No pollen data available for {}", location)));
+        }
+        
+        Ok(report)
+    }
+    
+    async fn get_marine(&self, location: &str) -> Result<MarineForecast, WeatherError> {
+        let marine = self.marine.as_ref()
+            .ok_or_else(|| WeatherError::NotFound("Marine data requires NOAA_TIDE_STATION or NOAA_BUOY_STATION".to_string()))?;
+        
+        let location_key = self.get_location_key(location).await?;
+        let location_details = self.get_location_details(&location_key).await?;
+        
+        let mut forecast = MarineForecast {
+            location: location_details.common(),
+            provider: "AccuWeather".to_string(),
+            marine_zone: location_details.details.as_ref().and_then(|d| d.marine_station.clone()),
+            tide_station: marine.tide_station.clone(),
+            buoy_station: marine.buoy_station.clone(),
+            wave_height: None,
+            wave_period: None,
+            swell_height: None,
+            swell_period: None,
+            swell_direction: None,
+            water_temperature: None,
+            tides: Vec::new(),
+            timestamp: safe_timestamp_with_fallback(),
+        };
+        
+        if let Some(station) = &marine.buoy_station {
+            let buoy = self.noaa.get_buoy_observation(station).await?;
+            forecast.wave_height = buoy.wave_height;
+            forecast.wave_period = buoy.wave_period;
+            forecast.swell_height = buoy.swell_height;
+            forecast.swell_period = buoy.swell_period;
+            forecast.swell_direction = buoy.swell_direction;
+            forecast.water_temperature = buoy.water_temperature;
+        }
+        
+        if let Some(station) = &marine.tide_station {
+            forecast.tides = self.noaa.get_tide_predictions(station).await?;
+            // Prefer the tide gauge's sensor, it sits closer to shore than the buoy
+            if let Some(temp) = self.noaa.get_water_temperature(station).await? {
+                forecast.water_temperature = Some(temp);
+            }
+        }
+        
+        Ok(forecast)
+    }
+    
     fn name(&self) -> &str {
         "AccuWeather"
     }
@@ -264,162 +360,14 @@ impl WeatherProvider for AccuWeatherProvider {
             WeatherFeature::HourlyForecast => true,
             WeatherFeature::UvIndex => true,
             WeatherFeature::AirQuality => true,
+            WeatherFeature::Pollen => true,
+            WeatherFeature::Marine => self.marine.is_some(),
+            WeatherFeature::MinutelyForecast => false,
             WeatherFeature::HistoricalData => false,
         }
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuLocation {
-    key: String,
-    localized_name: String,
-    country: AccuCountry,
-    administrative_area: Option<AccuAdminArea>,
-    geo_position: AccuGeoPosition,
-    primary_postal_code: Option<String>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuCountry {
-    #[serde(rename = "ID")]
-    id: String,
-    localized_name: String,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuAdminArea {
-    localized_name: String,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuGeoPosition {
-    latitude: f64,
-    longitude: f64,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuCurrentCondition {
-    weather_text: String,
-    weather_icon: i32,
-    temperature: AccuTemperature,
-    real_feel_temperature: Option<AccuTemperature>,
-    relative_humidity: Option<f64>,
-    wind: Option<AccuWind>,
-    #[serde(rename = "UVIndex")]
-    uv_index: Option<i32>,
-    visibility: Option<AccuMeasurement>,
-    pressure: Option<AccuMeasurement>,
-    precipitation_summary: Option<AccuPrecipitationSummary>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuTemperature {
-    metric: AccuUnit,
-    imperial: AccuUnit,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuUnit {
-    value: f64,
-    unit: String,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuWind {
-    direction: AccuWindDirection,
-    speed: AccuMeasurement,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuWindDirection {
-    degrees: f64,
-    localized: String,
-    english: String,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuMeasurement {
-    metric: AccuUnit,
-    imperial: AccuUnit,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuPrecipitationSummary {
-    precipitation: Option<AccuMeasurement>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuForecastResponse {
-    daily_forecasts: Vec<AccuDailyForecast>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuDailyForecast {
-    date: String,
-    temperature: AccuTempRange,
-    day: AccuDayNight,
-    night: AccuDayNight,
-    sun: Option<AccuSun>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuTempRange {
-    minimum: AccuUnit,
-    maximum: AccuUnit,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuDayNight {
-    icon: i32,
-    icon_phrase: String,
-    precipitation_probability: Option<f64>,
-    total_liquid: Option<AccuUnit>,
-    wind: Option<AccuWindForecast>,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuWindForecast {
-    speed: AccuUnit,
-    direction: AccuWindDirection,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuSun {
-    rise: String,
-    set: String,
-}
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct AccuHourlyForecast {
-    date_time: String,
-    weather_icon: i32,
-    icon_phrase: String,
-    temperature: AccuUnit,
-    real_feel_temperature: Option<AccuUnit>,
-    relative_humidity: Option<f64>,
-    precipitation_probability: i32,
-    total_liquid: Option<AccuUnit>,
-    wind: Option<AccuWindForecast>,
-}
-
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 struct AccuAlert {
@@ -0,0 +1,289 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use super::common::{LocationQuery, RateLimiter, WeatherError};
+use crate::analysis::aqi::{air_quality_index, AirQualityIndex, Pollutant};
+use crate::archive;
+use crate::db_pool::get_homebrew_pool;
+use crate::provider::homebrew::{FilterParams, WeatherReport};
+use crate::secret::Secret;
+use crate::utils::time::safe_timestamp_with_fallback;
+
+// Pollutant concentrations from the OpenWeather Air Pollution API and homebrew PM2.5/PM10
+// sensors, combined into one report with US EPA and European AQI (GET /api/air_quality).
+
+// Homebrew particulate readings older than this don't describe the air right now
+const HOMEBREW_MAX_AGE_SECS: i64 = 3 * 3600;
+
+/// Concentrations in µg/m³; `None` where the provider doesn't measure a pollutant
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AirQualityReading {
+    pub pm25: Option<f64>,
+    pub pm10: Option<f64>,
+    pub o3: Option<f64>,
+    pub no2: Option<f64>,
+    pub so2: Option<f64>,
+    pub co: Option<f64>,
+    pub timestamp: i64,
+}
+
+impl AirQualityReading {
+    pub fn get(&self, pollutant: Pollutant) -> Option<f64> {
+        match pollutant {
+            Pollutant::Pm25 => self.pm25,
+            Pollutant::Pm10 => self.pm10,
+            Pollutant::O3 => self.o3,
+            Pollutant::No2 => self.no2,
+            Pollutant::So2 => self.so2,
+            Pollutant::Co => self.co,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AirQualityReport {
+    pub location: String,
+    pub providers: Vec<String>,      // Providers that contributed at least one pollutant
+    #[serde(flatten)]
+    pub index: AirQualityIndex,
+    pub timestamp: i64,
+}
+
+#[async_trait]
+pub trait AirQualityProvider: Send + Sync {
+    async fn get_air_quality(&self, location: &str) -> Result<AirQualityReading, WeatherError>;
+
+    fn name(&self) -> &str;
+}
+
+/// Each pollutant comes from the first provider in `readings` that measured it, so local
+/// sensors listed first take precedence over modelled values
+pub fn combine(location: &str, readings: &[(String, AirQualityReading)]) -> Result<AirQualityReport, WeatherError> {
+    let mut providers: Vec<String> = Vec::new();
+    let mut concentrations = Vec::new();
+    for pollutant in Pollutant::all() {
+        if let Some((provider, value)) = readings.iter().find_map(|(name, r)| r.get(pollutant).map(|v| (name, v))) {
+            concentrations.push((pollutant, value));
+            if !providers.contains(provider) {
+                providers.push(provider.clone());
+            }
+        }
+    }
+    if concentrations.is_empty() {
+        return Err(WeatherError::NotFound("No air quality data available from any provider".to_string()));
+    }
+
+    Ok(AirQualityReport {
+        location: location.to_string(),
+        timestamp: readings.iter()
+            .filter(|(name, _)| providers.contains(name))
+            .map(|(_, r)| r.timestamp)
+            .max()
+            .unwrap_or_else(safe_timestamp_with_fallback),
+        providers,
+        index: air_quality_index(&concentrations),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenWeatherAirPollution {
+    list: Vec<OpenWeatherAirSample>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenWeatherAirSample {
+    dt: i64,
+    components: OpenWeatherComponents,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenWeatherComponents {
+    co: Option<f64>,
+    no2: Option<f64>,
+    o3: Option<f64>,
+    so2: Option<f64>,
+    pm2_5: Option<f64>,
+    pm10: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OpenWeatherPlace {
+    lat: f64,
+    lon: f64,
+}
+
+/// Parses an OpenWeather Air Pollution API response
+pub fn parse_air_pollution(body: &str) -> Result<AirQualityReading, WeatherError> {
+    air_pollution_reading(serde_json::from_str(body)?)
+}
+
+fn air_pollution_reading(response: OpenWeatherAirPollution) -> Result<AirQualityReading, WeatherError> {
+    let sample = response.list.into_iter().next()
+        .ok_or_else(|| WeatherError::NotFound("OpenWeather returned no air pollution data".to_string()))?;
+    let components = sample.components;
+    Ok(AirQualityReading {
+        pm25: components.pm2_5,
+        pm10: components.pm10,
+        o3: components.o3,
+        no2: components.no2,
+        so2: components.so2,
+        co: components.co,
+        timestamp: sample.dt,
+    })
+}
+
+/// Cloning shares the rate limit
+#[derive(Clone)]
+pub struct OpenWeatherAirQuality {
+    api_key: Secret<String>,
+    base_url: String,
+    rate_limiter: Arc<RateLimiter>,
+    client: reqwest::Client,
+}
+
+impl OpenWeatherAirQuality {
+    pub fn new(api_key: impl Into<Secret<String>>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: "https://api.openweathermap.org".to_string(),
+            rate_limiter: Arc::new(RateLimiter::new(60, 60)), // 60 requests per minute for free tier
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get(&self, path: &str, params: &[(&str, String)]) -> Result<reqwest::Response, WeatherError> {
+        if !self.rate_limiter.check_rate_limit() {
+            return Err(WeatherError::RateLimitExceeded);
+        }
+        let response = self.client.get(format!("{}{}", self.base_url, path))
+            .query(params)
+            .query(&[("appid", self.api_key.expose().as_str())])
+            .send()
+            .await?;
+        match response.status().as_u16() {
+            401 => Err(WeatherError::InvalidApiKey),
+            429 => Err(WeatherError::RateLimitExceeded),
+            404 => Err(WeatherError::NotFound("OpenWeather location not found".to_string())),
+            _ => Ok(response),
+        }
+    }
+
+    // The Air Pollution API only takes coordinates
+    async fn coordinates(&self, location: &str) -> Result<(f64, f64), WeatherError> {
+        let place: OpenWeatherPlace = match LocationQuery::parse(location).map_err(WeatherError::ConfigurationError)? {
+            LocationQuery::Coordinates { latitude, longitude } => return Ok((latitude, longitude)),
+            LocationQuery::PostalCode { country, code } => self.get("/geo/1.0/zip",
+                &[("zip", format!("{},{}", code, country.as_deref().unwrap_or("US")))]).await?.json().await?,
+            LocationQuery::City { name, country } => {
+                let q = match country {
+                    Some(country) => format!("{},{}", name, country),
+                    None => name,
+                };
+                let places: Vec<OpenWeatherPlace> = self.get("/geo/1.0/direct", &[("q", q), ("limit", "1".to_string())]).await?.json().await?;
+                places.into_iter().next()
+                    .ok_or_else(|| WeatherError::NotFound(format!("Location not found: {}", location)))?
+            },
+        };
+        Ok((place.lat, place.lon))
+    }
+}
+
+#[async_trait]
+impl AirQualityProvider for OpenWeatherAirQuality {
+    async fn get_air_quality(&self, location: &str) -> Result<AirQualityReading, WeatherError> {
+        let (lat, lon) = self.coordinates(location).await?;
+        let response = self.get("/data/2.5/air_pollution", &[("lat", lat.to_string()), ("lon", lon.to_string())]).await?;
+        air_pollution_reading(archive::json("openweather", "air_pollution", location, response).await?)
+    }
+
+    fn name(&self) -> &str {
+        "OpenWeather"
+    }
+}
+
+/// Latest outdoor PM2.5/PM10 readings from the homebrew database. Indoor sensors are left
+/// out, they say nothing about the air outside.
+pub struct HomebrewAirQuality;
+
+/// Particulates of the newest report among `reports` (newest first) that has any, if it is
+/// recent enough at `now`
+pub fn latest_particulates(reports: &[WeatherReport], now: i64) -> Option<AirQualityReading> {
+    reports.iter()
+        .find(|r| r.pm25.is_some() || r.pm10.is_some())
+        .filter(|r| now - r.timestamp <= HOMEBREW_MAX_AGE_SECS)
+        .map(|r| AirQualityReading {
+            pm25: r.pm25,
+            pm10: r.pm10,
+            timestamp: r.timestamp,
+            ..Default::default()
+        })
+}
+
+#[async_trait]
+impl AirQualityProvider for HomebrewAirQuality {
+    async fn get_air_quality(&self, _location: &str) -> Result<AirQualityReading, WeatherError> {
+        let pool = get_homebrew_pool()
+            .ok_or_else(|| WeatherError::DatabaseError("Homebrew database pool not initialized".to_string()))?;
+        let client = pool.get_connection_with_retry(3).await
+            .map_err(WeatherError::DatabaseError)?;
+
+        let filter = FilterParams { oid: None, device_type: Some("outdoor".to_string()), device_id: None };
+        let (query, params) = WeatherReport::select_query(Some(50), None, Some("timestamp".to_string()), Some(filter));
+        let query_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params.iter()
+            .map(|p| p as &(dyn tokio_postgres::types::ToSql + Sync))
+            .collect();
+        let rows = client.query(&query, &query_params).await
+            .map_err(|e| WeatherError::DatabaseError(format!("Query failed: {}", e)))?;
+        let reports = rows.iter()
+            .map(WeatherReport::from_row)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| WeatherError::DatabaseError(e.to_string()))?;
+
+        latest_particulates(&reports, safe_timestamp_with_fallback())
+            .ok_or_else(|| WeatherError::NotFound("No recent outdoor particulate readings".to_string()))
+    }
+
+    fn name(&self) -> &str {
+        "Homebrew"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine_prefers_earlier_providers() {
+        let homebrew = AirQualityReading { pm25: Some(4.0), timestamp: 1_700_000_300, ..Default::default() };
+        let openweather = parse_air_pollution(r#"{"coord": {"lon": -122.33, "lat": 47.61}, "list": [{
+            "main": {"aqi": 2},
+            "components": {"co": 201.9, "no": 0.0, "no2": 0.77, "o3": 68.66, "so2": 0.64, "pm2_5": 12.5, "pm10": 18.0, "nh3": 0.12},
+            "dt": 1700000000
+        }]}"#).unwrap();
+        assert_eq!(openweather.o3, Some(68.66));
+
+        let report = combine("98101", &[
+            ("Homebrew".to_string(), homebrew),
+            ("OpenWeather".to_string(), openweather),
+        ]).unwrap();
+        assert_eq!(report.providers, vec!["Homebrew", "OpenWeather"]);
+        let pm25 = report.index.pollutants.iter().find(|p| p.pollutant == Pollutant::Pm25).unwrap();
+        assert_eq!(pm25.concentration, 4.0);
+        assert_eq!(report.index.pollutants.len(), 6);
+        assert_eq!(report.index.dominant_pollutant, Some(Pollutant::O3));
+        assert_eq!(report.timestamp, 1_700_000_300);
+
+        assert!(matches!(combine("98101", &[]), Err(WeatherError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_homebrew_particulates_expire() {
+        let mut report = WeatherReport::new();
+        report.pm25 = Some(8.0);
+        report.timestamp = 1_700_000_000;
+        let reports = vec![WeatherReport::new(), report];
+
+        assert_eq!(latest_particulates(&reports, 1_700_000_600).and_then(|r| r.pm25), Some(8.0));
+        assert!(latest_particulates(&reports, 1_700_000_000 + HOMEBREW_MAX_AGE_SECS + 1).is_none());
+    }
+}
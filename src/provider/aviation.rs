@@ -0,0 +1,406 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use super::common::{RateLimiter, WeatherError};
+use crate::archive;
+
+// Terminal Aerodrome Forecasts (TAF) for a configured airfield, fetched from the
+// aviationweather.gov data API and decoded into structured forecast periods.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TafTime {
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TafTime {
+    // DDHHMM (issue time without 'Z', FM groups) or DDHH (validity groups)
+    fn parse(value: &str) -> Option<TafTime> {
+        if !value.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let number = |range: std::ops::Range<usize>| value.get(range).and_then(|v| v.parse::<u8>().ok());
+        match value.len() {
+            4 => Some(TafTime { day: number(0..2)?, hour: number(2..4)?, minute: 0 }),
+            6 => Some(TafTime { day: number(0..2)?, hour: number(2..4)?, minute: number(4..6)? }),
+            _ => None,
+        }
+    }
+
+    fn parse_range(value: &str) -> Option<(TafTime, TafTime)> {
+        let (from, to) = value.split_once('/')?;
+        Some((TafTime::parse(from)?, TafTime::parse(to)?))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TafChange {
+    Base,
+    From,
+    Becoming,
+    Temporary,
+    Probability(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TafWind {
+    pub direction: Option<u16>,       // Degrees true, None when variable
+    pub speed_kt: u16,
+    pub gust_kt: Option<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudLayer {
+    pub cover: String,                // FEW, SCT, BKN, OVC or VV (vertical visibility)
+    pub base_ft: u32,                 // Feet above ground level
+    pub cloud_type: Option<String>,   // CB or TCU
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TafPeriod {
+    pub change: TafChange,
+    pub start: Option<TafTime>,
+    pub end: Option<TafTime>,
+    pub wind: Option<TafWind>,
+    pub visibility_sm: Option<f64>,   // Statute miles; "P6SM" is reported as 6.0
+    pub ceiling_ft: Option<u32>,      // Lowest broken/overcast layer or vertical visibility
+    pub clouds: Vec<CloudLayer>,
+    pub conditions: Vec<String>,
+    pub raw: String,
+}
+
+impl TafPeriod {
+    fn new(change: TafChange) -> Self {
+        Self {
+            change,
+            start: None,
+            end: None,
+            wind: None,
+            visibility_sm: None,
+            ceiling_ft: None,
+            clouds: Vec::new(),
+            conditions: Vec::new(),
+            raw: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TafReport {
+    pub station: String,
+    pub issued: Option<TafTime>,
+    pub valid_from: Option<TafTime>,
+    pub valid_to: Option<TafTime>,
+    pub amended: bool,
+    pub periods: Vec<TafPeriod>,
+    pub raw: String,
+}
+
+pub struct AviationWeatherClient {
+    base_url: String,
+    rate_limiter: Arc<RateLimiter>,
+    client: reqwest::Client,
+}
+
+impl AviationWeatherClient {
+    pub fn new() -> Self {
+        Self {
+            base_url: "https://aviationweather.gov/api/data".to_string(),
+            rate_limiter: Arc::new(RateLimiter::new(100, 60)),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn get_taf(&self, station: &str) -> Result<TafReport, WeatherError> {
+        if !self.rate_limiter.check_rate_limit() {
+            return Err(WeatherError::RateLimitExceeded);
+        }
+
+        let url = format!("{}/taf?ids={}&format=raw", self.base_url, station);
+        let response = self.client.get(&url)
+            .send()
+            .await?;
+
+        let text = response.text().await?;
+        if text.trim().is_empty() {
+            return Err(WeatherError::NotFound(format!("No TAF available for {}", station)));
+        }
+        archive::store("aviation", "taf", station, text.as_bytes()).await;
+
+        decode_taf(&text)
+    }
+}
+
+impl Default for AviationWeatherClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decodes a raw TAF (continuation lines allowed) into structured forecast periods
+pub fn decode_taf(raw: &str) -> Result<TafReport, WeatherError> {
+    let raw = raw.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut tokens = raw.trim_end_matches('=').split(' ').peekable();
+
+    let mut amended = false;
+    let mut station = None;
+    for token in tokens.by_ref() {
+        match token {
+            "TAF" | "COR" => {}
+            "AMD" => amended = true,
+            _ => {
+                station = Some(token.to_string());
+                break;
+            }
+        }
+    }
+    let station = station
+        .filter(|s| s.len() == 4 && s.chars().all(|c| c.is_ascii_alphanumeric()))
+        .ok_or_else(|| WeatherError::ParseError(format!("Missing station identifier in TAF: {}", raw)))?;
+
+    let mut report = TafReport {
+        station,
+        issued: None,
+        valid_from: None,
+        valid_to: None,
+        amended,
+        periods: Vec::new(),
+        raw: raw.clone(),
+    };
+
+    if let Some(token) = tokens.peek() {
+        if token.ends_with('Z') {
+            report.issued = TafTime::parse(token.trim_end_matches('Z'));
+            tokens.next();
+        }
+    }
+    if let Some(token) = tokens.peek() {
+        if let Some((from, to)) = TafTime::parse_range(token) {
+            report.valid_from = Some(from);
+            report.valid_to = Some(to);
+            tokens.next();
+        }
+    }
+
+    let mut period = TafPeriod::new(TafChange::Base);
+    period.start = report.valid_from;
+    period.end = report.valid_to;
+    let mut raw_tokens: Vec<&str> = Vec::new();
+    // Whole statute miles preceding a fraction, e.g. "1 1/2SM"
+    let mut whole_miles: Option<f64> = None;
+
+    while let Some(token) = tokens.next() {
+        let next_change = if let Some(time) = token.strip_prefix("FM") {
+            TafTime::parse(time).map(|start| {
+                let mut next = TafPeriod::new(TafChange::From);
+                next.start = Some(start);
+                next
+            })
+        } else if token == "BECMG" || token == "TEMPO" {
+            Some(TafPeriod::new(if token == "BECMG" { TafChange::Becoming } else { TafChange::Temporary }))
+        } else if let Some(probability) = token.strip_prefix("PROB").and_then(|p| p.parse::<u8>().ok()) {
+            // PROB30 TEMPO is folded into a single probabilistic period
+            if tokens.peek() == Some(&"TEMPO") {
+                tokens.next();
+            }
+            Some(TafPeriod::new(TafChange::Probability(probability)))
+        } else {
+            None
+        };
+
+        if let Some(mut next) = next_change {
+            period.raw = raw_tokens.join(" ");
+            report.periods.push(period);
+            raw_tokens = vec![token];
+
+            if next.start.is_none() {
+                if let Some((from, to)) = tokens.peek().and_then(|t| TafTime::parse_range(t)) {
+                    next.start = Some(from);
+                    next.end = Some(to);
+                    raw_tokens.push(tokens.next().unwrap_or_default());
+                }
+            }
+            period = next;
+            whole_miles = None;
+            continue;
+        }
+
+        raw_tokens.push(token);
+
+        if let Some(wind) = decode_wind(token) {
+            period.wind = Some(wind);
+        } else if let Some(visibility) = decode_visibility(token) {
+            period.visibility_sm = Some(visibility + whole_miles.take().unwrap_or(0.0));
+        } else if token.len() == 1 && token.chars().all(|c| c.is_ascii_digit())
+            && tokens.peek().is_some_and(|t| t.contains('/') && t.ends_with("SM")) {
+            whole_miles = token.parse::<f64>().ok();
+        } else if let Some(layer) = decode_cloud_layer(token) {
+            period.clouds.push(layer);
+        } else if let Some(condition) = decode_condition(token) {
+            period.conditions.push(condition);
+        }
+    }
+
+    period.raw = raw_tokens.join(" ");
+    report.periods.push(period);
+
+    for period in report.periods.iter_mut() {
+        period.ceiling_ft = period.clouds.iter()
+            .filter(|c| c.cover == "BKN" || c.cover == "OVC" || c.cover == "VV")
+            .map(|c| c.base_ft)
+            .min();
+    }
+
+    Ok(report)
+}
+
+fn decode_wind(token: &str) -> Option<TafWind> {
+    let (body, to_knots) = if let Some(body) = token.strip_suffix("KT") {
+        (body, 1.0)
+    } else if let Some(body) = token.strip_suffix("MPS") {
+        (body, 1.943_844)
+    } else {
+        return None;
+    };
+    if body.len() < 5 {
+        return None;
+    }
+
+    let direction = match &body[..3] {
+        "VRB" => None,
+        dir => Some(dir.parse::<u16>().ok()?),
+    };
+    let (speed, gust) = match body[3..].split_once('G') {
+        Some((speed, gust)) => (speed, Some(gust)),
+        None => (&body[3..], None),
+    };
+    let convert = |value: &str| value.parse::<f64>().ok().map(|v| (v * to_knots).round() as u16);
+
+    Some(TafWind {
+        direction,
+        speed_kt: convert(speed)?,
+        gust_kt: match gust {
+            Some(gust) => Some(convert(gust)?),
+            None => None,
+        },
+    })
+}
+
+fn decode_visibility(token: &str) -> Option<f64> {
+    if let Some(miles) = token.strip_suffix("SM") {
+        let miles = miles.trim_start_matches('P').trim_start_matches('M');
+        return match miles.split_once('/') {
+            Some((numerator, denominator)) => {
+                let numerator = numerator.parse::<f64>().ok()?;
+                let denominator = denominator.parse::<f64>().ok()?;
+                if denominator == 0.0 { None } else { Some(numerator / denominator) }
+            }
+            None => miles.parse::<f64>().ok(),
+        };
+    }
+
+    // ICAO visibility in meters, 9999 meaning 10 km or more
+    if token.len() == 4 && token.chars().all(|c| c.is_ascii_digit()) {
+        let meters = token.parse::<f64>().ok()?;
+        return Some((meters / 1609.344 * 10.0).round() / 10.0);
+    }
+    if token == "CAVOK" {
+        return Some(6.0);
+    }
+
+    None
+}
+
+fn decode_cloud_layer(token: &str) -> Option<CloudLayer> {
+    let (cover, rest) = if token.starts_with("VV") {
+        token.split_at(2)
+    } else if ["FEW", "SCT", "BKN", "OVC"].iter().any(|c| token.starts_with(c)) {
+        token.split_at(3)
+    } else {
+        return None;
+    };
+    if rest.len() < 3 {
+        return None;
+    }
+
+    let (height, cloud_type) = rest.split_at(3);
+    let base_ft = height.parse::<u32>().ok()? * 100;
+
+    Some(CloudLayer {
+        cover: cover.to_string(),
+        base_ft,
+        cloud_type: match cloud_type {
+            "CB" | "TCU" => Some(cloud_type.to_string()),
+            "" => None,
+            _ => return None,
+        },
+    })
+}
+
+const DESCRIPTORS: &[(&str, &str)] = &[
+    ("MI", "shallow"), ("BC", "patches of"), ("PR", "partial"), ("DR", "low drifting"),
+    ("BL", "blowing"), ("SH", "showers"), ("TS", "thunderstorm"), ("FZ", "freezing"),
+];
+
+const PHENOMENA: &[(&str, &str)] = &[
+    ("DZ", "drizzle"), ("RA", "rain"), ("SN", "snow"), ("SG", "snow grains"),
+    ("IC", "ice crystals"), ("PL", "ice pellets"), ("GR", "hail"), ("GS", "small hail"),
+    ("UP", "unknown precipitation"), ("BR", "mist"), ("FG", "fog"), ("FU", "smoke"),
+    ("VA", "volcanic ash"), ("DU", "widespread dust"), ("SA", "sand"), ("HZ", "haze"),
+    ("PY", "spray"), ("PO", "dust whirls"), ("SQ", "squalls"), ("FC", "funnel cloud"),
+    ("SS", "sandstorm"), ("DS", "duststorm"),
+];
+
+// Decodes a present weather group such as "-SHRA", "+TSRA" or "VCFG" into plain English
+fn decode_condition(token: &str) -> Option<String> {
+    match token {
+        "NSW" => return Some("no significant weather".to_string()),
+        "SKC" | "CLR" | "NSC" => return Some("sky clear".to_string()),
+        _ => {}
+    }
+
+    let (intensity, mut rest) = if let Some(rest) = token.strip_prefix('-') {
+        (Some("light"), rest)
+    } else if let Some(rest) = token.strip_prefix('+') {
+        (Some("heavy"), rest)
+    } else {
+        (None, token)
+    };
+    let vicinity = rest.starts_with("VC");
+    if vicinity {
+        rest = &rest[2..];
+    }
+    if rest.is_empty() || rest.len() % 2 != 0 || !rest.is_char_boundary(2) {
+        return None;
+    }
+
+    let lookup = |table: &[(&str, &'static str)], code: &str| {
+        table.iter().find(|(c, _)| *c == code).map(|(_, w)| *w)
+    };
+    let descriptor = lookup(DESCRIPTORS, &rest[..2]);
+    if descriptor.is_some() {
+        rest = &rest[2..];
+    }
+    let mut phenomena = Vec::new();
+    for i in (0..rest.len()).step_by(2) {
+        phenomena.push(lookup(PHENOMENA, rest.get(i..i + 2)?)?);
+    }
+    let phenomena = phenomena.join(" and ");
+
+    let mut description = match (descriptor, phenomena.is_empty()) {
+        (None, true) => return None,
+        (None, false) => phenomena,
+        (Some(descriptor), true) => descriptor.to_string(),
+        (Some("showers"), false) => format!("{} showers", phenomena),
+        (Some("thunderstorm"), false) => format!("thunderstorm with {}", phenomena),
+        (Some(descriptor), false) => format!("{} {}", descriptor, phenomena),
+    };
+    if let Some(intensity) = intensity {
+        description = format!("{} {}", intensity, description);
+    }
+    if vicinity {
+        description.push_str(" in the vicinity");
+    }
+
+    Some(description)
+}
@@ -2,35 +2,79 @@ use serde_json::json;
 
 use serde::{Serialize, Deserialize};
 use std::convert::TryInto;
+use std::collections::HashMap;
 use std::env;
-use std::thread;
+use std::net::SocketAddr;
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
-use rouille::Request;
-use rouille::Response;
-use rouille::post_input;
-use rouille::session;
-use rouille::try_or_400;
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::auth::{validate_auth_header, RateLimiter};
+use axum::extract::{ConnectInfo, Form, FromRef, Path, Query, State};
+use axum::extract::rejection::FormRejection;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware;
+use axum::response::{IntoResponse, Json, Response};
+use axum::Extension;
+use axum::routing::{any, get, post};
+use axum::Router;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use crate::deploy;
+use crate::influx;
+use crate::slo;
+use crate::provider::health as provider_health;
+use crate::provider::quota;
+use crate::auth::{require_api_key, ApiKeyAuth, AuthenticatedDevice, RateLimiter};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread::JoinHandle;
 use tokio::sync::{broadcast, Mutex as AsyncMutex};
-use std::time::Duration;
+use tracing::Instrument;
 
 use tokio_postgres::{Error, Row};
 use crate::error::{JupiterError, Result as JupiterResult};
-use crate::ssl_config::{create_combo_connector, SslConfig};
+use crate::units::{Pressure, Speed, Temperature};
 use crate::input_sanitizer::{InputSanitizer, DatabaseInputValidator, ValidationError};
-use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
-use postgres_openssl::MakeTlsConnector;
-use crate::db_pool::{DatabasePool, init_combo_pool, get_combo_pool};
+use crate::db_pool::{init_combo_pool, get_combo_pool, split_address};
 use crate::db_pool::DatabaseConfig as DbPoolConfig;
-use crate::config::{ConfigError, DatabaseConfig};
+use crate::config::{ConfigError, DatabaseConfig, ResourceLimits, MarineConfig};
+use crate::provider::common::{DailyForecast, LocationQuery, Weather, WeatherError, WeatherProvider};
+use crate::provider::combo_enhanced::{self, CacheFreshness, ComboProvider, FieldWeights, FusionPolicy, OutlierRejection};
+#[cfg(feature = "enhanced-providers")]
+use crate::provider::homebrew_enhanced::HomebrewProvider;
+use crate::provider::accuweather_enhanced::AccuWeatherProvider;
+use crate::provider::accuweather::{AccuWeatherClient, CurrentCondition, Location as AccuLocation};
+use crate::provider::aviation::AviationWeatherClient;
+use crate::provider::nws::NwsProvider;
+use crate::provider::tomorrow_io::TomorrowIoProvider;
+use crate::provider::air_quality::{HomebrewAirQuality, OpenWeatherAirQuality};
+use crate::analysis::{hvac, nowcast, solar, summary, uv};
+use crate::analysis::solar::SolarConfig;
+use crate::analysis::smoothing::Smoothing;
+use crate::base_path;
+use crate::live::{self, LiveUpdate};
+use crate::metrics;
+use crate::cache::{self, Lookup};
+use crate::coalesce::Coalescer;
+use crate::response_cache::ResponseCache;
+use crate::response_format::{self, ResponseFormat};
+use crate::scheduler;
+use crate::migrations;
+use crate::openapi;
+use crate::schema::{with_schema, Schema};
+use crate::archive;
+use crate::backfill;
+use crate::widget;
+use crate::triggers;
+use crate::notifications;
+use crate::locale::Locale;
+use crate::secret::Secret;
+use crate::server;
+use crate::provider::homebrew::{self, ReportForm};
+use crate::utils::time::safe_timestamp_with_fallback;
 
 // Ability to combine, average, and cache final values between all configured providers.
 
+// Refreshes of the cached current conditions, so concurrent requests finding them expired
+// wait for one refresh instead of each calling AccuWeather
+static CONDITIONS_REFRESH: once_cell::sync::Lazy<Coalescer<CachedWeatherData>> = once_cell::sync::Lazy::new(|| Coalescer::new("conditions"));
+
 // Secure filter parameters for database queries
 #[derive(Debug, Clone)]
 pub struct FilterParams {
@@ -38,20 +82,261 @@ pub struct FilterParams {
     // Add more filter fields as needed
 }
 
+// Unauthenticated, read-only /public/* endpoints for sharing a station (PUBLIC_MODE=true)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicModeConfig {
+    pub requests_per_minute: usize,
+}
+
+impl PublicModeConfig {
+    pub fn from_env() -> Option<Self> {
+        let enabled = env::var("PUBLIC_MODE").map(|v| v == "true" || v == "1").unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+        Some(Self {
+            requests_per_minute: env::var("PUBLIC_RATE_LIMIT").ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(6),
+        })
+    }
+}
+
+/// Requests per minute each IP may make for /api/widget.svg, which needs no key (WIDGET_RATE_LIMIT)
+fn widget_rate_limit() -> usize {
+    env::var("WIDGET_RATE_LIMIT").ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(30)
+}
+
+/// How long embedding pages may cache the widget when the conditions behind it aren't cached
+const WIDGET_MAX_AGE_SECS: u64 = 60;
+
+/// Lets browsers, CDNs and reverse proxies reuse a response while the cache entry behind it is fresh.
+/// max-age is the entry's lifetime and Age the time already spent, so downstream caches expire the
+/// response together with ours. Responses differ per API key and negotiated format.
+fn with_cache_headers(mut response: Response, freshness: Option<CacheFreshness>) -> Response {
+    let headers = response.headers_mut();
+    match freshness {
+        Some(freshness) if freshness.remaining() > 0 => {
+            if let Ok(cache_control) = HeaderValue::from_str(&format!("public, max-age={}", freshness.ttl)) {
+                headers.insert(header::CACHE_CONTROL, cache_control);
+            }
+            headers.insert(header::AGE, HeaderValue::from(freshness.age));
+            headers.insert(header::VARY, HeaderValue::from_static("Authorization, Accept"));
+            headers.remove(header::EXPIRES);
+            headers.remove(header::PRAGMA);
+        },
+        _ => {
+            headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache, no-store, must-revalidate"));
+            headers.insert(header::EXPIRES, HeaderValue::from_static("0"));
+            headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+        },
+    }
+    response
+}
+
+/// Serializes cached conditions for `format` and keeps the bytes for later requests
+fn cached_response(cache: &ResponseCache, format: ResponseFormat, data: &CachedWeatherData) -> Response {
+    match response_format::encode(format, data) {
+        Ok(body) => cache.insert(format, body, data.timestamp).response(),
+        Err(e) => {
+            log::error!("Failed to serialize cached weather data: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error").into_response()
+        }
+    }
+}
+
+/// Plain-text 404 for missing data and 502 for any other provider failure
+fn provider_error(context: &str, error: WeatherError) -> Response {
+    match error {
+        WeatherError::NotFound(msg) => (StatusCode::NOT_FOUND, msg).into_response(),
+        e => {
+            log::error!("Failed to {}: {}", context, e);
+            (StatusCode::BAD_GATEWAY, "Upstream provider error").into_response()
+        }
+    }
+}
+
+/// Serves /public/current, /public/daily and /public/widget.svg. Only a curated subset of fields is exposed,
+/// and responses may be cached by browsers and proxies for as long as the underlying data is fresh.
+async fn public_response(page: &str, state: &ServerState) -> Response {
+    let location = state.config.zip_code.as_str();
+    let providers = &state.providers;
+    let (cache_key, schema) = match page {
+        "widget.svg" => return widget_response(state).await,
+        "current" => (format!("current:{}", location), Schema::PublicCurrent),
+        "daily" => (format!("forecast:{}:1", location), Schema::PublicDaily),
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let response = match page {
+        "daily" => providers.get_forecast(location, 1).await.and_then(|forecast| {
+            forecast.daily.first()
+                .map(|day| json!(public_daily(day)))
+                .ok_or_else(|| WeatherError::NotFound("No daily summary available".to_string()))
+        }),
+        _ => providers.get_current_weather(location).await.map(|weather| json!(public_current(&weather))),
+    };
+
+    match response {
+        Ok(body) => with_cache_headers(with_schema(Json(body).into_response(), schema), providers.cache_freshness(&cache_key).await),
+        Err(e) => provider_error(&format!("serve public /public/{}", page), e),
+    }
+}
+
+/// Body of /public/current, a curated subset of the current conditions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicCurrent {
+    pub temperature: f64,
+    pub feels_like: Option<f64>,
+    pub humidity: Option<f64>,
+    pub pressure: Option<f64>,
+    pub wind_speed: Option<f64>,
+    pub wind_direction: Option<f64>,
+    pub precipitation: Option<f64>,
+    pub uv_index: Option<f64>,
+    pub description: String,
+    pub timestamp: i64,
+}
+
+/// Body of /public/daily, a curated subset of today's forecast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicDaily {
+    pub date: String,
+    pub temperature_min: f64,
+    pub temperature_max: f64,
+    pub humidity: Option<f64>,
+    pub precipitation_probability: Option<f64>,
+    pub precipitation_amount: Option<f64>,
+    pub wind_speed: Option<f64>,
+    pub description: String,
+}
+
+pub fn public_current(weather: &Weather) -> PublicCurrent {
+    PublicCurrent {
+        temperature: weather.temperature.celsius(),
+        feels_like: weather.feels_like.map(Temperature::celsius),
+        humidity: weather.humidity,
+        pressure: weather.pressure.map(Pressure::hpa),
+        wind_speed: weather.wind_speed.map(Speed::kmh),
+        wind_direction: weather.wind_direction,
+        precipitation: weather.precipitation,
+        uv_index: weather.uv_index,
+        description: weather.description.clone(),
+        timestamp: weather.timestamp,
+    }
+}
+
+pub fn public_daily(day: &DailyForecast) -> PublicDaily {
+    PublicDaily {
+        date: day.date.clone(),
+        temperature_min: day.temperature_min.celsius(),
+        temperature_max: day.temperature_max.celsius(),
+        humidity: day.humidity,
+        precipitation_probability: day.precipitation_probability,
+        precipitation_amount: day.precipitation_amount,
+        wind_speed: day.wind_speed.map(Speed::kmh),
+        description: day.description.clone(),
+    }
+}
+
+/// Current-conditions SVG card, with AQI from homebrew particulate sensors when available
+async fn widget_response(state: &ServerState) -> Response {
+    let config = &state.config;
+    let aqi = match &config.homebrew_config {
+        Some(homebrew) => homebrew::latest_aqi(homebrew).await.unwrap_or_else(|e| {
+            log::warn!("Failed to read homebrew air quality for widget: {}", e);
+            None
+        }),
+        None => None,
+    };
+
+    match state.providers.get_current_weather(&config.zip_code).await {
+        Ok(weather) => {
+            let response = ([(header::CONTENT_TYPE, "image/svg+xml")], widget::render_svg(&weather, aqi, &config.locale)).into_response();
+            // Embedded in other pages, so cacheable even when the conditions were fetched just now
+            match state.providers.cache_freshness(&format!("current:{}", config.zip_code)).await {
+                Some(freshness) if freshness.remaining() > 0 => with_cache_headers(response, Some(freshness)),
+                _ => {
+                    let mut response = response;
+                    if let Ok(cache_control) = HeaderValue::from_str(&format!("public, max-age={}", WIDGET_MAX_AGE_SECS)) {
+                        response.headers_mut().insert(header::CACHE_CONTROL, cache_control);
+                    }
+                    response
+                },
+            }
+        },
+        Err(e) => provider_error("fetch weather for widget", e),
+    }
+}
+
 // Lives in memory, no SQL
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_distance_scale() -> Option<f64> {
+    Some(combo_enhanced::DISTANCE_SCALE_KM)
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     pub accu_config: Option<crate::provider::accuweather::Config>,
     pub homebrew_config: Option<crate::provider::homebrew::Config>,
-    pub apikey: String,
+    pub apikey: Secret<String>,
     pub cache_timeout: Option<i64>,
     pub pg: PostgresServer,
     pub port: u16,
     pub zip_code: String,
+    #[serde(default)]
+    pub marine_config: Option<MarineConfig>,
+    #[serde(default)]
+    pub airfield: Option<String>,
+    /// PV system of /api/solar/forecast, see `analysis::solar`
+    #[serde(default)]
+    pub solar: Option<SolarConfig>,
+    #[serde(default)]
+    pub public_mode: Option<PublicModeConfig>,
+    #[serde(default)]
+    pub base_path: Option<String>,
+    #[serde(skip)]
+    pub limits: ResourceLimits,
+    #[serde(default = "default_weight")]
+    pub accuweather_weight: f64,
+    #[serde(default)]
+    pub nws_weight: f64,
+    /// Built by `with_nws_weight`, so requests share its grid point cache
+    #[serde(skip)]
+    pub nws: Option<NwsProvider>,
+    #[serde(default = "default_weight")]
+    pub tomorrow_io_weight: f64,
+    /// Set by `with_tomorrow_io`, which also enables /api/nowcast
+    #[serde(skip)]
+    pub tomorrow_io: Option<TomorrowIoProvider>,
+    /// How homebrew sensors and regional providers are combined, see `with_fusion_policy`
+    #[serde(skip)]
+    pub fusion_policy: FusionPolicy,
+    /// Provider values left out of combined conditions, see `with_outlier_rejection`
     #[serde(skip)]
-    pub server_handle: Option<Arc<AsyncMutex<Option<JoinHandle<()>>>>>,
+    pub outlier_rejection: OutlierRejection,
+    /// Per-field multipliers of provider weights, see `with_field_weights`
     #[serde(skip)]
-    pub shutdown_flag: Arc<AtomicBool>,
+    pub field_weights: FieldWeights,
+    /// Inverse-distance weighting of sources when LOCATION is coordinates, see `with_distance_scale`
+    #[serde(default = "default_distance_scale")]
+    pub distance_scale_km: Option<f64>,
+    /// Set by `with_openweather_key`; homebrew particulate sensors are used without it
+    #[serde(skip)]
+    pub openweather_air_quality: Option<OpenWeatherAirQuality>,
+    #[serde(skip)]
+    pub locale: Locale,
+    /// Built from `accu_config`; shared by the cached conditions and `weather_providers()`
+    #[serde(skip)]
+    pub accu_client: Option<AccuWeatherClient>,
+    #[serde(skip)]
+    pub server_handle: Option<Arc<AsyncMutex<Option<server::ServerTask>>>>,
     #[serde(skip)]
     pub shutdown_tx: Option<broadcast::Sender<()>>
 }
@@ -70,45 +355,298 @@ impl std::fmt::Debug for Config {
 impl Config {
     pub fn new(accu_config: Option<crate::provider::accuweather::Config>,
                homebrew_config: Option<crate::provider::homebrew::Config>,
-               apikey: String,
+               apikey: impl Into<Secret<String>>,
                cache_timeout: Option<i64>,
                pg: PostgresServer,
                port: u16,
                zip_code: String) -> Self {
         let (shutdown_tx, _) = broadcast::channel(1);
+        let accu_client = accu_config.clone().map(AccuWeatherClient::new);
         Config {
             accu_config,
             homebrew_config,
-            apikey,
+            apikey: apikey.into(),
             cache_timeout,
             pg,
             port,
             zip_code,
+            marine_config: None,
+            airfield: None,
+            solar: None,
+            public_mode: None,
+            base_path: None,
+            limits: ResourceLimits::default(),
+            accuweather_weight: default_weight(),
+            nws_weight: 0.0,
+            nws: None,
+            tomorrow_io_weight: default_weight(),
+            tomorrow_io: None,
+            fusion_policy: FusionPolicy::default(),
+            outlier_rejection: OutlierRejection::Off,
+            field_weights: FieldWeights::default(),
+            distance_scale_km: default_distance_scale(),
+            openweather_air_quality: None,
+            locale: Locale::default(),
+            accu_client,
             server_handle: Some(Arc::new(AsyncMutex::new(None))),
-            shutdown_flag: Arc::new(AtomicBool::new(false)),
             shutdown_tx: Some(shutdown_tx),
         }
     }
 
+    pub fn with_marine_config(mut self, marine_config: Option<MarineConfig>) -> Self {
+        self.marine_config = marine_config;
+        self
+    }
+
+    pub fn with_airfield(mut self, airfield: Option<String>) -> Self {
+        self.airfield = airfield;
+        self
+    }
+
+    pub fn with_solar(mut self, solar: Option<SolarConfig>) -> Self {
+        self.solar = solar;
+        self
+    }
+
+    pub fn with_public_mode(mut self, public_mode: Option<PublicModeConfig>) -> Self {
+        self.public_mode = public_mode;
+        self
+    }
+
+    /// Serve all routes below `base_path` (e.g. "/weather") for hosting behind a reverse proxy
+    pub fn with_base_path(mut self, base_path: Option<String>) -> Self {
+        self.base_path = base_path;
+        self
+    }
+
+    /// Database pool size and concurrent request limit
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Number, unit and date formatting for the spoken summary, widget and `/api/value?format=text`
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Weight of AccuWeather when averaging providers
+    pub fn with_accuweather_weight(mut self, weight: f64) -> Self {
+        self.accuweather_weight = weight;
+        self
+    }
+
+    /// Weight of the National Weather Service when averaging providers; 0 leaves it out
+    pub fn with_nws_weight(mut self, weight: f64) -> Self {
+        self.nws_weight = weight;
+        self.nws = (weight > 0.0).then(NwsProvider::new);
+        self
+    }
+
+    /// Tomorrow.io as an averaged provider and the source of minute-level precipitation; left
+    /// out without a key
+    pub fn with_tomorrow_io(mut self, api_key: Option<Secret<String>>, weight: f64) -> Self {
+        self.tomorrow_io_weight = weight;
+        self.tomorrow_io = api_key.map(TomorrowIoProvider::new);
+        self
+    }
+
+    /// Which of homebrew sensors and regional providers each field of current conditions
+    /// prefers; hyperlocal fields default to the sensors
+    pub fn with_fusion_policy(mut self, policy: FusionPolicy) -> Self {
+        self.fusion_policy = policy;
+        self
+    }
+
+    /// How provider values far off from the others are left out of combined conditions; off by
+    /// default
+    pub fn with_outlier_rejection(mut self, outliers: OutlierRejection) -> Self {
+        self.outlier_rejection = outliers;
+        self
+    }
+
+    /// Weights of providers for single fields, multiplying their configured weight
+    pub fn with_field_weights(mut self, weights: FieldWeights) -> Self {
+        self.field_weights = weights;
+        self
+    }
+
+    /// Distance in km at which a provider station or homebrew sensor keeps half its weight,
+    /// relative to LOCATION given as coordinates; None or 0 turns distance weighting off
+    pub fn with_distance_scale(mut self, scale_km: Option<f64>) -> Self {
+        self.distance_scale_km = scale_km.filter(|scale| *scale > 0.0);
+        self
+    }
+
+    /// OpenWeather Air Pollution API as a source of gas and particulate concentrations for
+    /// /api/air_quality
+    pub fn with_openweather_key(mut self, api_key: Option<Secret<String>>) -> Self {
+        self.openweather_air_quality = api_key.map(OpenWeatherAirQuality::new);
+        self
+    }
+
+    /// Fetches current conditions from AccuWeather and the latest homebrew report, and stores
+    /// them as the newest cached_weather_data row
+    pub async fn refresh_cached_conditions(&self) -> CachedWeatherData {
+        let mut resp = CachedWeatherData::new();
+
+        if let Some(client) = &self.accu_client {
+            match self.fetch_accuweather_conditions(client).await {
+                Ok(Some(current)) => {
+                    influx::conditions(&self.zip_code, &current);
+                    let j = match serde_json::to_string(&current) {
+                        Ok(json) => json,
+                        Err(e) => {
+                            log::error!("Failed to serialize AccuWeather data: {}", e);
+                            String::new()
+                        }
+                    };
+                    resp.accuweather = Some(j);
+                },
+                Ok(None) => {
+                    log::warn!("[combo] No current conditions available from AccuWeather for {}", self.zip_code);
+                },
+                Err(e) => {
+                    log::error!("[combo] Error fetching current conditions from AccuWeather: {}", e);
+                }
+            }
+        }
+
+
+        if let Some(cfg) = self.homebrew_config.clone() {
+            let objects = match crate::provider::homebrew::WeatherReport::select(cfg.clone(), Some(1), None, Some("timestamp DESC".to_string()), None).await {
+                Ok(objs) => objs,
+                Err(e) => {
+                    log::error!("Failed to select homebrew data for combo: {}", e);
+                    vec![]
+                }
+            };
+
+            // Use safe array access to prevent panic on empty results
+            if let Some(first) = objects.into_iter().next() {
+                let first = homebrew::smoothed(&cfg, first).await;
+                let j = match serde_json::to_string(&first) {
+                    Ok(json) => json,
+                    Err(e) => {
+                        log::error!("Failed to serialize homebrew data: {}", e);
+                        String::new()
+                    }
+                };
+                resp.homebrew = Some(j);
+            } else {
+                log::warn!("[combo] No homebrew data available for caching");
+            }
+            // If no data, resp.homebrew remains None which is acceptable
+        }
+
+        // Keep the last good row rather than caching an outage
+        if resp.accuweather.is_none() && resp.homebrew.is_none() {
+            log::warn!("No provider returned current conditions, not caching an empty result");
+            cache::shared().release(&self.conditions_key());
+            return resp;
+        }
+        // The row records the refresh for instances waiting on it, the cache serves requests
+        if let Err(e) = resp.save(self.clone()).await {
+            log::error!("Failed to save cached weather data: {}", e);
+        }
+        match serde_json::to_value(&resp) {
+            Ok(value) => cache::shared().set(&self.conditions_key(), value).await,
+            Err(e) => log::error!("Failed to serialize cached weather data: {}", e),
+        }
+        live::publish(LiveUpdate::Conditions(resp.clone()));
+        resp
+    }
+
+    async fn fetch_accuweather_conditions(&self, client: &AccuWeatherClient) -> Result<Option<CurrentCondition>, WeatherError> {
+        // The location was validated at startup
+        let query = match LocationQuery::parse(&self.zip_code) {
+            Ok(query) => query,
+            Err(_) => return Ok(None),
+        };
+        combo_enhanced::provider_available("AccuWeather")?;
+        async {
+            let start = Instant::now();
+            let conditions = match AccuLocation::search(client, &query).await {
+                Ok(Some(location)) => CurrentCondition::get(client, &location).await,
+                Ok(None) => Ok(None),
+                Err(e) => Err(e),
+            };
+            combo_enhanced::record_provider_request("AccuWeather", start, &conditions);
+            conditions
+        }.instrument(combo_enhanced::provider_span("AccuWeather", "current")).await
+    }
+
+    // Async providers backing the /api/* endpoints that go beyond cached current conditions.
+    pub fn weather_providers(&self) -> ComboProvider {
+        let mut providers = ComboProvider::new();
+        if let Some(client) = &self.accu_client {
+            let mut accuweather = AccuWeatherProvider::from_client(client.clone());
+            if let Some(marine) = &self.marine_config {
+                accuweather = accuweather.with_marine_stations(marine.clone());
+            }
+            providers = providers.add_provider(Box::new(accuweather), self.accuweather_weight);
+        }
+        if let Some(nws) = &self.nws {
+            providers = providers.add_provider(Box::new(nws.clone()), self.nws_weight);
+        }
+        if let Some(tomorrow_io) = &self.tomorrow_io {
+            providers = providers.add_provider(Box::new(tomorrow_io.clone()), self.tomorrow_io_weight);
+        }
+        // Outdoor sensors as the local station for the fusion policy; added last so the
+        // combined location comes from a regional provider
+        #[cfg(feature = "enhanced-providers")]
+        if let Some(homebrew) = &self.homebrew_config {
+            // Unplaced (0,0) without coordinates on any outdoor device
+            let (latitude, longitude) = homebrew.devices.snapshot().outdoor_position().unwrap_or((0.0, 0.0));
+            let mut station = HomebrewProvider::new(homebrew.clone());
+            station.add_location_mapping(self.zip_code.clone(), latitude, longitude, "Homebrew".to_string(), vec!["outdoor".to_string()]);
+            providers = providers.add_local_provider(Box::new(station), default_weight());
+        }
+        providers = providers
+            .set_fusion_policy(self.fusion_policy.clone())
+            .set_outlier_rejection(self.outlier_rejection.clone())
+            .set_field_weights(self.field_weights.clone())
+            .set_distance_scale(self.distance_scale_km);
+        // Local sensors first, so their particulate readings win over OpenWeather's model
+        if self.homebrew_config.is_some() {
+            providers = providers.add_air_quality_provider(Box::new(HomebrewAirQuality));
+        }
+        if let Some(openweather) = &self.openweather_air_quality {
+            providers = providers.add_air_quality_provider(Box::new(openweather.clone()));
+        }
+        if let Some(timeout) = self.cache_timeout {
+            providers = providers.set_cache_duration(timeout.max(0) as u64);
+        }
+        providers.set_cache(cache::shared())
+    }
+
+    /// Key of the current conditions in the shared cache
+    fn conditions_key(&self) -> String {
+        format!("conditions:{}", self.zip_code)
+    }
+
     pub async fn init(&mut self) -> JupiterResult<()> {
         // Initialize connection pool
+        let (host, port) = split_address(&self.pg.address);
         let db_config = DbPoolConfig {
             db_name: self.pg.db_name.clone(),
             username: self.pg.username.clone(),
             password: self.pg.password.clone(),
-            host: self.pg.address.clone(),
+            host,
             address: self.pg.address.clone(),  // For backward compatibility
-            port: Some(5432),
-            pool_size: Some(20),
+            port: Some(port),
+            pool_size: Some(self.limits.db_pool_size),
             connection_timeout: Some(std::time::Duration::from_secs(5)),
             idle_timeout: Some(std::time::Duration::from_secs(600)),
             max_lifetime: Some(std::time::Duration::from_secs(1800)),
             use_ssl: true,
         };
         
+        let started = Instant::now();
         match init_combo_pool(db_config).await {
             Ok(pool) => {
-                log::info!("[combo] Database connection pool initialized successfully");
+                log::info!("[combo] Database connection pool initialized in {} ms", started.elapsed().as_millis());
                 // Log initial pool status
                 let status = pool.status();
                 status.log("combo");
@@ -119,283 +657,39 @@ impl Config {
             }
         }
 
+        let started = Instant::now();
         self.build_tables().await?;
+        log::info!("[combo] Tables built in {} ms", started.elapsed().as_millis());
+        if let Err(e) = quota::load().await {
+            log::warn!("[combo] Failed to load provider call counts, budgets start from zero: {}", e);
+        }
 
-        let config = self.clone();
-        let shutdown_flag = self.shutdown_flag.clone();
-        let _shutdown_rx = self.shutdown_tx.as_ref()
+        let shutdown_rx = self.shutdown_tx.as_ref()
             .ok_or_else(|| JupiterError::ConfigurationError("Shutdown channel not initialized".into()))?
             .subscribe();
-        let server_port = config.port;
-        
-        let handle = thread::spawn(move || {
-            // Create rate limiter: max 10 attempts per minute per IP
-            let rate_limiter = Arc::new(RateLimiter::new(10, 60));
-            
-            let server = rouille::Server::new(format!("0.0.0.0:{}", server_port).as_str(), move |request| {
-    
-                // Validate authentication with rate limiting
-                if let Err(response) = validate_auth_header(request, &config.apikey, Some(&rate_limiter)) {
-                    return response;
-                }
-    
-
-                match config.homebrew_config.clone(){
-                    Some(cfg) => {
-                        if request.url() == "/api/weather_reports" {
-                            if request.method() == "POST" {
-        
-                                // Collect input params from post request
-                                let input = try_or_400!(post_input!(request, {
-                                    temperature: Option<f64>,
-                                    humidity: Option<f64>,
-                                    percipitation: Option<f64>,
-                                    pm10: Option<f64>,
-                                    pm25: Option<f64>,
-                                    co2: Option<f64>,
-                                    tvoc: Option<f64>,
-                                    device_type: String,
-                                }));
-        
-                                let mut obj = crate::provider::homebrew::WeatherReport::new();
-                                obj.temperature = input.temperature;
-                                obj.humidity = input.humidity;
-                                obj.percipitation = input.percipitation;
-                                obj.pm10 = input.pm10;
-                                obj.pm25 = input.pm25;
-                                obj.co2 = input.co2;
-                                obj.tvoc = input.tvoc;
-                                obj.device_type = input.device_type.to_string();
-                                obj.save(cfg.clone());
-                                return Response::json(&obj);
-                            }
-                            if request.method() == "GET" {
-                                let objects = match crate::provider::homebrew::WeatherReport::select(cfg.clone(), Some(1), None, Some(format!("timestamp DESC")), None) {
-                                    Ok(objs) => objs,
-                                    Err(e) => {
-                                        log::error!("Failed to select homebrew weather reports: {}", e);
-                                        return Response::text("Database error").with_status_code(500);
-                                    }
-                                };
-                                
-                                // Check if we have any results before accessing
-                                if let Some(first) = objects.first() {
-                                    return Response::json(&first.clone());
-                                } else {
-                                    eprintln!("[combo/homebrew] Warning: No weather data found in homebrew database");
-                                    return Response::text("No homebrew weather data available").with_status_code(404);
-                                }
-                            }
-                        }
-                    },
-                    None => {}
-                }
-
-
-  
-                // Return a cached response if one exists within the timeout window
-                // Otherwise check configured providers for current weather conditions and cache the results
-                if request.method() == "GET" {
-
-                    match config.cache_timeout.clone(){
-                        Some(timeout) => {
-                            let objects = match CachedWeatherData::select(config.clone(), Some(1), None, Some(format!("timestamp DESC")), None) {
-                                Ok(objs) => objs,
-                                Err(e) => {
-                                    log::error!("Failed to select cached weather data: {}", e);
-                                    // Continue without cache
-                                    vec![]
-                                }
-                            };
-                            
-                            // Use safe array access with .first()
-                            if let Some(first) = objects.first() {
-                                let current_timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
-                                    Ok(duration) => duration.as_secs() as i64,
-                                    Err(e) => {
-                                        log::error!("System time error: {}", e);
-                                        0i64
-                                    }
-                                };
-                                let x = current_timestamp - first.timestamp;
-                                if x < timeout {
-                                    return Response::json(&first.clone());
-                                }
-                            } else {
-                                eprintln!("[combo] Warning: No cached weather data found in database");
-                            }
-                        },
-                        None => {}
-                    }
-
-                    let mut resp = CachedWeatherData::new();
-
-                    match config.accu_config.clone(){
-                        Some(cfg) => {
-                            // Handle Option return from search_by_zip
-                            match crate::provider::accuweather::Location::search_by_zip(cfg.clone(), config.zip_code.clone()) {
-                                Ok(Some(location)) => {
-                                    // Handle Option return from get
-                                    match crate::provider::accuweather::CurrentCondition::get(cfg, location.clone()) {
-                                        Ok(Some(current)) => {
-                                            let j = match serde_json::to_string(&current) {
-                                                Ok(json) => json,
-                                                Err(e) => {
-                                                    log::error!("Failed to serialize AccuWeather data: {}", e);
-                                                    String::new()
-                                                }
-                                            };
-                                            resp.accuweather = Some(j);
-                                        },
-                                        Ok(None) => {
-                                            eprintln!("[combo] No current conditions available from AccuWeather");
-                                        },
-                                        Err(e) => {
-                                            eprintln!("[combo] Error fetching current conditions from AccuWeather: {}", e);
-                                        }
-                                    }
-                                },
-                                Ok(None) => {
-                                    eprintln!("[combo] No location found for zip code: {}", config.zip_code);
-                                },
-                                Err(e) => {
-                                    eprintln!("[combo] Error searching location by zip: {}", e);
-                                }
-                            }
-                        },
-                        None => {}
-                    }
-         
-
-                    match config.homebrew_config.clone(){
-                        Some(cfg) => {
-                            let objects = match crate::provider::homebrew::WeatherReport::select(cfg.clone(), Some(1), None, Some(format!("timestamp DESC")), None) {
-                                Ok(objs) => objs,
-                                Err(e) => {
-                                    log::error!("Failed to select homebrew data for combo: {}", e);
-                                    vec![]
-                                }
-                            };
-                            
-                            // Use safe array access to prevent panic on empty results
-                            if let Some(first) = objects.first() {
-                                let j = match serde_json::to_string(&first.clone()) {
-                                    Ok(json) => json,
-                                    Err(e) => {
-                                        log::error!("Failed to serialize homebrew data: {}", e);
-                                        String::new()
-                                    }
-                                };
-                                resp.homebrew = Some(j);
-                            } else {
-                                eprintln!("[combo] Warning: No homebrew data available for caching");
-                            }
-                            // If no data, resp.homebrew remains None which is acceptable
-                        },
-                        None => {}
-                    }
-
-                    resp.save(config.clone());
-
-                    return Response::json(&resp);
-                }
-                
-                // Add metrics endpoint
-                if request.url() == "/metrics" {
-                    if request.method() == "GET" {
-                        let metrics_json = crate::pool_monitor::handle_metrics_endpoint();
-                        return Response::text(metrics_json)
-                            .with_additional_header("Content-Type", "application/json");
-                    }
-                }
-    
-    
-                let mut response = Response::text("hello world");
-
-                return response;
-            }).unwrap_or_else(|e| {
-                log::error!("Failed to create server: {}", e);
-                panic!("Failed to create server: {}", e);
-            });
-            
-            log::info!("Combo server started on port {}", server_port);
-            
-            // Run server with shutdown support
-            while !shutdown_flag.load(Ordering::Relaxed) {
-                server.poll_timeout(std::time::Duration::from_millis(100));
-            }
-            
-            log::info!("Combo server shutting down...");
-        });
-        
-        // Store the handle in the async mutex - need to spawn a task for this
+        let handle = server::spawn("Combo", self.port, router(self.clone()), &self.limits, shutdown_rx).await?;
         if let Some(handle_mutex) = &self.server_handle {
-            let handle_mutex_clone = handle_mutex.clone();
-            tokio::spawn(async move {
-                match tokio::time::timeout(Duration::from_secs(5), handle_mutex_clone.lock()).await {
-                    Ok(mut handle_guard) => {
-                        *handle_guard = Some(handle);
-                    },
-                    Err(_) => {
-                        log::error!("Failed to acquire server handle lock within timeout");
-                    }
-                }
-            });
+            *handle_mutex.lock().await = Some(handle);
         }
         
         Ok(())
     }
 
     pub async fn shutdown(&mut self) {
-        self.shutdown_with_timeout(std::time::Duration::from_secs(10)).await;
+        self.shutdown_with_timeout(self.limits.shutdown_timeout).await;
     }
 
     pub async fn shutdown_with_timeout(&mut self, timeout: std::time::Duration) {
         log::info!("Initiating graceful shutdown of combo server...");
         
-        // Signal the server thread to stop
-        self.shutdown_flag.store(true, Ordering::Relaxed);
-        
-        // Send shutdown signal via broadcast channel
+        // Stop accepting connections; requests in flight are finished first
         if let Some(tx) = &self.shutdown_tx {
             let _ = tx.send(());
         }
         
-        // Wait for the server thread to finish with timeout
         if let Some(handle_mutex) = &self.server_handle {
-            let handle_mutex_clone = handle_mutex.clone();
-            
-            // Try to join with timeout using async mutex
-            let join_result = tokio::time::timeout(timeout, async move {
-                // First acquire lock with timeout to prevent deadlock
-                match tokio::time::timeout(Duration::from_secs(2), handle_mutex_clone.lock()).await {
-                    Ok(mut handle_guard) => {
-                        if let Some(handle) = handle_guard.take() {
-                            // Since we can't directly join std::thread in async context,
-                            // we'll use a different approach
-                            let _ = tokio::task::spawn_blocking(move || {
-                                handle.join()
-                            }).await;
-                        }
-                    },
-                    Err(_) => {
-                        log::error!("Failed to acquire lock for shutdown within timeout");
-                    }
-                }
-            }).await;
-            
-            match join_result {
-                Ok(_) => log::info!("Combo server thread joined successfully"),
-                Err(_) => {
-                    log::warn!("Combo server shutdown timed out after {:?}", timeout);
-                    // Force cleanup if needed with timeout
-                    if let Ok(mut handle_guard) = tokio::time::timeout(
-                        Duration::from_secs(1),
-                        handle_mutex.lock()
-                    ).await {
-                        handle_guard.take(); // Drop the handle
-                    }
-                }
+            if let Some(handle) = handle_mutex.lock().await.take() {
+                server::join("Combo", handle, timeout).await;
             }
         }
         
@@ -406,31 +700,644 @@ impl Config {
         // Get connection from pool
         let pool = get_combo_pool()
             .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
-        
-        let client = pool.get_connection_with_retry(3).await
-            .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
-    
-        // Build CachedWeatherData Table
-        // ---------------------------------------------------------------
-        let db = client.batch_execute(CachedWeatherData::sql_build_statement()).await;
-        match db {
-            Ok(_v) => log::info!("POSTGRES: CREATED CachedWeatherData Table"),
-            Err(e) => log::error!("POSTGRES: {:?}", e),
-        }
-        let db_migrations = CachedWeatherData::migrations();
-        for migration in db_migrations {
-            let migrations_db = client.batch_execute(migration).await;
-            match migrations_db {
-                Ok(_v) => log::info!("POSTGRES: Migration Successful"),
-                Err(e) => log::error!("POSTGRES: {:?}", e),
-            }
-        }
+
+        let target = migrations::target_from_env("COMBO_SCHEMA_VERSION")
+            .map_err(|message| JupiterError::ConfigurationError(format!("COMBO_SCHEMA_VERSION {}", message)))?;
+        migrations::run(&pool, "combo", &migrations::combo(), target).await?;
 
         return Ok(());
     }    
 
 }
 
+/// Shared by every request to the combo server
+struct ServerState {
+    config: Config,
+    providers: ComboProvider,
+    aviation: AviationWeatherClient,
+    // Serialized current conditions, per response format
+    response_cache: ResponseCache,
+    public_rate_limiter: Option<RateLimiter>,
+    widget_rate_limiter: RateLimiter,
+}
+
+impl ServerState {
+    fn new(config: Config) -> Self {
+        Self {
+            providers: config.weather_providers(),
+            aviation: AviationWeatherClient::new(),
+            response_cache: ResponseCache::new(),
+            public_rate_limiter: config.public_mode.as_ref()
+                .map(|public| RateLimiter::new(public.requests_per_minute, 60).shared("public")),
+            widget_rate_limiter: RateLimiter::new(widget_rate_limit(), 60).shared("widget"),
+            config,
+        }
+    }
+}
+
+/// The running ServerState, replaced as a whole when settings are deployed (see `deploy`).
+/// Handlers extract the state current when their request arrived.
+#[derive(Clone)]
+struct LiveState {
+    current: Arc<std::sync::RwLock<Arc<ServerState>>>,
+}
+
+impl LiveState {
+    fn snapshot(&self) -> Arc<ServerState> {
+        match self.current.read() {
+            Ok(current) => current.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+impl FromRef<LiveState> for Arc<ServerState> {
+    fn from_ref(live: &LiveState) -> Self {
+        live.snapshot()
+    }
+}
+
+type Shared = State<Arc<ServerState>>;
+
+/// Routes of the combo server. Everything except /api/widget.svg and the /public/* pages of
+/// PUBLIC_MODE needs the API key or a stored homebrew key whose scope permits the route, and any
+/// other GET returns the cached current conditions.
+pub fn router(config: Config) -> Router {
+    let mut auth = ApiKeyAuth::new(config.apikey.clone());
+    if let Some(homebrew_config) = &config.homebrew_config {
+        auth = auth.with_keys(homebrew_config.api_keys.clone());
+    }
+    let auth = Arc::new(auth);
+    let base_path = config.base_path.clone();
+    let state = Arc::new(ServerState::new(config));
+
+    let mut api = Router::new();
+    if state.config.homebrew_config.is_some() {
+        api = api
+            .route("/api/weather_reports", get(latest_report).post(create_report))
+            .route("/api/weather_reports/history", get(report_range))
+            .route("/api/weather_reports/list", get(report_list))
+            .route("/api/value/:field", get(value))
+            .route("/api/history/:field", get(field_history))
+            .route("/api/triggers/temperature_crossed", get(temperature_trigger));
+    }
+    let api = api
+        .route("/api/current", get(current_weather))
+        .route("/api/capabilities", get(capabilities))
+        .route("/api/openapi.json", get(openapi_document))
+        .route("/api/stream", get(stream))
+        .route("/api/pollen", get(pollen))
+        .route("/api/uv/guidance", get(uv_guidance))
+        .route("/api/summary/spoken", get(spoken_summary))
+        .route("/api/hvac/hints", get(hvac_hints))
+        .route("/api/solar/forecast", get(solar_forecast))
+        .route("/api/marine", get(marine))
+        .route("/api/air_quality", get(air_quality))
+        .route("/api/nowcast", get(minute_nowcast))
+        .route("/api/aviation", get(aviation_forecast))
+        .route("/api/triggers/new_alert", get(alert_trigger))
+        .route("/api/admin/jobs", get(jobs))
+        .route("/api/admin/jobs/:name/run", post(run_job))
+        .route("/api/admin/jobs/:name/runs", get(job_runs))
+        .route("/api/admin/payloads", get(payloads))
+        .route("/api/admin/notifications", get(notification_deliveries))
+        // Replays archived payloads through the current normalization; dry run unless apply=true
+        .route("/api/admin/backfill", post(run_backfill))
+        .route("/api/admin/config", get(running_config).post(deploy_config))
+        .route("/api/admin/providers/health", get(provider_health))
+        .route("/metrics", get(metrics::prometheus))
+        .route("/metrics/pools", get(pool_metrics))
+        .fallback(current_conditions)
+        .layer(middleware::from_fn_with_state(auth, require_api_key));
+
+    // The widget is embedded in pages that can't hold a key, and the public read-only subset is
+    // served without authentication
+    let api = Router::new().route("/api/widget.svg", get(widget)).merge(api);
+    let routes = match state.public_rate_limiter {
+        Some(_) => Router::new().route("/public/*page", any(public)).merge(api),
+        None => api,
+    };
+    let live = LiveState { current: Arc::new(std::sync::RwLock::new(state)) };
+    base_path::nest(routes.with_state(live), base_path.as_deref())
+}
+
+async fn public(State(state): Shared, ConnectInfo(remote_addr): ConnectInfo<SocketAddr>, method: Method, Path(page): Path<String>) -> Response {
+    if method != Method::GET {
+        return (StatusCode::METHOD_NOT_ALLOWED, "Method Not Allowed").into_response();
+    }
+    if let Some(public_limiter) = &state.public_rate_limiter {
+        if !public_limiter.allow(&remote_addr.ip().to_string()).await {
+            return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "60")], "Too Many Requests").into_response();
+        }
+    }
+    public_response(&page, &state).await
+}
+
+async fn create_report(State(state): Shared, device: Option<Extension<AuthenticatedDevice>>, form: Result<Form<ReportForm>, FormRejection>) -> Response {
+    match &state.config.homebrew_config {
+        Some(cfg) => homebrew::save_form(cfg, device.map(|Extension(device)| device), form).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// `?raw=true` skips SENSOR_SMOOTHING
+async fn latest_report(State(state): Shared, format: ResponseFormat, Query(params): Query<HashMap<String, String>>) -> Response {
+    let cfg = match &state.config.homebrew_config {
+        Some(cfg) => cfg,
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let objects = match homebrew::WeatherReport::select(cfg.clone(), Some(1), None, Some("timestamp DESC".to_string()), None).await {
+        Ok(objs) => objs,
+        Err(e) => {
+            log::error!("Failed to select homebrew weather reports: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    };
+
+    // Check if we have any results before accessing
+    match objects.into_iter().next() {
+        Some(first) if homebrew::raw_requested(&params) => with_schema(response_format::respond(format, &first), Schema::WeatherReport),
+        Some(first) => with_schema(response_format::respond(format, &homebrew::smoothed(cfg, first).await), Schema::WeatherReport),
+        None => {
+            log::warn!("[combo/homebrew] No weather data found in homebrew database");
+            (StatusCode::NOT_FOUND, "No homebrew weather data available").into_response()
+        }
+    }
+}
+
+async fn report_range(State(state): Shared, Query(params): Query<HashMap<String, String>>) -> Response {
+    match &state.config.homebrew_config {
+        Some(cfg) => homebrew::range_response(cfg, &params).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn report_list(State(state): Shared, Query(params): Query<HashMap<String, String>>) -> Response {
+    match &state.config.homebrew_config {
+        Some(cfg) => homebrew::list_response(cfg, &params).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn field_history(State(state): Shared, Path(field): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    match &state.config.homebrew_config {
+        Some(cfg) => homebrew::history_response(cfg, &field, &params).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn value(State(state): Shared, Path(field): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    match &state.config.homebrew_config {
+        Some(cfg) => {
+            let locale = Some(&state.config.locale).filter(|_| params.get("format").map(String::as_str) == Some("text"));
+            let smoothing = if homebrew::raw_requested(&params) { Smoothing::Off } else { cfg.smoothing };
+            homebrew::value_response(cfg, &field, params.get("device").cloned(), locale, smoothing).await
+        },
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn temperature_trigger(State(state): Shared, Query(params): Query<HashMap<String, String>>) -> Response {
+    match &state.config.homebrew_config {
+        Some(cfg) => homebrew::crossings_response(cfg, "temperature", &params).await,
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// IFTTT/Zapier polling feed of the active weather alerts, each dated when first seen
+async fn alert_trigger(State(state): Shared, Query(params): Query<HashMap<String, String>>) -> Response {
+    let (format, limit) = match triggers::options(&params) {
+        Ok(options) => options,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match state.providers.get_alerts(&state.config.zip_code).await {
+        Ok(alerts) => with_schema(Json(triggers::render(triggers::alert_events(&alerts, safe_timestamp_with_fallback()), format, limit)).into_response(), Schema::AlertTrigger),
+        Err(e) => provider_error("fetch alerts for trigger", e),
+    }
+}
+
+async fn pollen(State(state): Shared) -> Response {
+    let zip_code = &state.config.zip_code;
+    match state.providers.get_pollen(zip_code).await {
+        Ok(report) => with_cache_headers(with_schema(Json(&report).into_response(), Schema::Pollen),
+            state.providers.cache_freshness(&format!("pollen:{}", zip_code)).await),
+        Err(e) => provider_error("fetch pollen data", e),
+    }
+}
+
+async fn uv_guidance(State(state): Shared, Query(params): Query<HashMap<String, String>>) -> Response {
+    let skin_type = match params.get("skin_type") {
+        Some(value) => match uv::SkinType::from_param(value) {
+            Ok(skin_type) => skin_type,
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        },
+        None => uv::SkinType::II,
+    };
+    let hour = match params.get("hour") {
+        Some(value) => match uv::parse_hour(value) {
+            Ok(hour) => Some(hour),
+            Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        },
+        None => None,
+    };
+
+    let zip_code = &state.config.zip_code;
+    match state.providers.get_current_weather(zip_code).await {
+        Ok(weather) => match weather.uv_index {
+            Some(uv_index) => with_cache_headers(with_schema(Json(&uv::guidance(uv_index, skin_type, hour)).into_response(), Schema::UvGuidance),
+                state.providers.cache_freshness(&format!("current:{}", zip_code)).await),
+            None => (StatusCode::NOT_FOUND, "No UV index available from configured providers").into_response(),
+        },
+        Err(e) => provider_error("fetch UV index", e),
+    }
+}
+
+async fn widget(State(state): Shared, ConnectInfo(remote_addr): ConnectInfo<SocketAddr>) -> Response {
+    if !state.widget_rate_limiter.allow(&remote_addr.ip().to_string()).await {
+        return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "60")], "Too Many Requests").into_response();
+    }
+    widget_response(&state).await
+}
+
+async fn spoken_summary(State(state): Shared) -> Response {
+    let zip_code = &state.config.zip_code;
+    match state.providers.get_current_weather(zip_code).await {
+        Ok(weather) => with_cache_headers(summary::spoken_summary(&weather, &state.config.locale).into_response(),
+            state.providers.cache_freshness(&format!("current:{}", zip_code)).await),
+        Err(e) => provider_error("fetch weather for spoken summary", e),
+    }
+}
+
+async fn hvac_hints(State(state): Shared) -> Response {
+    // Latest indoor homebrew reading, if a homebrew database is configured
+    let indoor = match &state.config.homebrew_config {
+        Some(cfg) => {
+            let filter = homebrew::FilterParams {
+                oid: None,
+                device_type: Some("indoor".to_string()),
+                device_id: None,
+            };
+            match homebrew::WeatherReport::select(cfg.clone(), Some(1), None, Some("timestamp DESC".to_string()), Some(filter)).await {
+                Ok(reports) => reports.into_iter().next(),
+                Err(e) => {
+                    log::error!("Failed to select indoor homebrew readings: {}", e);
+                    None
+                }
+            }
+        },
+        None => None,
+    };
+
+    match state.providers.get_forecast(&state.config.zip_code, 2).await {
+        Ok(forecast) => match forecast.hourly {
+            Some(hourly) => with_schema(Json(&hvac::hvac_hints(
+                &hourly,
+                indoor.as_ref().and_then(|r| r.temperature),
+                indoor.as_ref().and_then(|r| r.humidity),
+            )).into_response(), Schema::HvacHints),
+            None => (StatusCode::NOT_FOUND, "No hourly forecast available from configured providers").into_response(),
+        },
+        Err(e) => provider_error("fetch forecast for HVAC hints", e),
+    }
+}
+
+async fn solar_forecast(State(state): Shared) -> Response {
+    let Some(system) = &state.config.solar else {
+        return (StatusCode::NOT_FOUND, "No PV system configured, set SOLAR_KWP").into_response();
+    };
+    match state.providers.get_forecast(&state.config.zip_code, 3).await {
+        Ok(forecast) => with_schema(Json(&solar::solar_forecast(
+            system,
+            forecast.location.latitude,
+            forecast.location.longitude,
+            forecast.hourly.as_deref().unwrap_or_default(),
+            &forecast.daily,
+            safe_timestamp_with_fallback(),
+        )).into_response(), Schema::SolarForecast),
+        Err(e) => provider_error("fetch forecast for solar production", e),
+    }
+}
+
+async fn marine(State(state): Shared) -> Response {
+    let zip_code = &state.config.zip_code;
+    match state.providers.get_marine(zip_code).await {
+        Ok(forecast) => with_cache_headers(with_schema(Json(&forecast).into_response(), Schema::Marine),
+            state.providers.cache_freshness(&format!("marine:{}", zip_code)).await),
+        Err(e) => provider_error("fetch marine data", e),
+    }
+}
+
+/// Averaged current conditions, with each provider's raw values, weight and data age under `sources`
+async fn current_weather(State(state): Shared) -> Response {
+    let zip_code = &state.config.zip_code;
+    match state.providers.get_current_weather(zip_code).await {
+        Ok(weather) => with_cache_headers(with_schema(Json(&weather).into_response(), Schema::CurrentWeather),
+            state.providers.cache_freshness(&format!("current:{}", zip_code)).await),
+        Err(e) => provider_error("fetch current weather", e),
+    }
+}
+
+/// WebSocket pushing stored homebrew reports and refreshed cached conditions as they happen
+async fn stream(State(state): Shared, upgrade: WebSocketUpgrade) -> Response {
+    let shutdown = state.config.shutdown_tx.as_ref().map(|tx| tx.subscribe());
+    upgrade.on_upgrade(move |socket| live::forward(socket, shutdown))
+}
+
+/// OpenAPI description of the routes this server was configured with
+async fn openapi_document(State(state): Shared) -> Response {
+    let reports = state.config.homebrew_config.is_some();
+    Json(openapi::combo(state.config.base_path.as_deref(), reports, state.public_rate_limiter.is_some())).into_response()
+}
+
+/// Features the configured providers support, for clients to show only what they can get
+async fn capabilities(State(state): Shared) -> Response {
+    with_schema(Json(&state.providers.capabilities()).into_response(), Schema::Capabilities)
+}
+
+async fn air_quality(State(state): Shared) -> Response {
+    let zip_code = &state.config.zip_code;
+    match state.providers.get_air_quality(zip_code).await {
+        Ok(report) => with_cache_headers(with_schema(Json(&report).into_response(), Schema::AirQuality),
+            state.providers.cache_freshness(&format!("air_quality:{}", zip_code)).await),
+        Err(e) => provider_error("fetch air quality", e),
+    }
+}
+
+async fn minute_nowcast(State(state): Shared) -> Response {
+    let zip_code = &state.config.zip_code;
+    match state.providers.get_minutely(zip_code).await {
+        Ok(forecast) => with_cache_headers(with_schema(Json(&nowcast::nowcast(forecast, safe_timestamp_with_fallback())).into_response(), Schema::Nowcast),
+            state.providers.cache_freshness(&format!("minutely:{}", zip_code)).await),
+        Err(e) => provider_error("fetch minute-level forecast", e),
+    }
+}
+
+async fn aviation_forecast(State(state): Shared) -> Response {
+    let airfield = match &state.config.airfield {
+        Some(airfield) => airfield,
+        None => return (StatusCode::NOT_FOUND, "No airfield configured, set AVIATION_STATION").into_response(),
+    };
+    match state.aviation.get_taf(airfield).await {
+        Ok(taf) => with_schema(Json(&taf).into_response(), Schema::Aviation),
+        Err(e) => provider_error(&format!("fetch TAF for {}", airfield), e),
+    }
+}
+
+async fn jobs() -> Response {
+    Json(&scheduler::statuses()).into_response()
+}
+
+async fn run_job(Path(name): Path<String>) -> Response {
+    match scheduler::trigger(&name) {
+        Some(true) => (StatusCode::ACCEPTED, Json(json!({ "job": name, "started": true }))).into_response(),
+        Some(false) => (StatusCode::CONFLICT, format!("Job {} is already running", name)).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("Unknown job '{}'", name)).into_response(),
+    }
+}
+
+async fn job_runs(Path(name): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let limit = params.get("limit")
+        .and_then(|l| l.parse::<i64>().ok())
+        .unwrap_or(20)
+        .clamp(1, 500);
+    match scheduler::recent_runs(&name, limit).await {
+        Ok(runs) => Json(&runs).into_response(),
+        Err(e) => {
+            log::error!("Failed to select runs of job {}: {}", name, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+async fn payloads(Query(params): Query<HashMap<String, String>>) -> Response {
+    let filter = archive::PayloadFilter {
+        provider: params.get("provider").cloned(),
+        endpoint: params.get("endpoint").cloned(),
+        since: params.get("since").and_then(|s| s.parse().ok()),
+        until: params.get("until").and_then(|s| s.parse().ok()),
+    };
+    let limit = params.get("limit")
+        .and_then(|l| l.parse::<i64>().ok())
+        .unwrap_or(100)
+        .clamp(1, 1000);
+    match archive::payloads(&filter, limit).await {
+        Ok(payloads) => Json(&payloads).into_response(),
+        Err(e) => {
+            log::error!("Failed to select archived payloads: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// Delivery log of NOTIFICATIONS_CONFIG rules, newest first; `?failed=true` for failures only
+async fn notification_deliveries(Query(params): Query<HashMap<String, String>>) -> Response {
+    let limit = params.get("limit")
+        .and_then(|l| l.parse::<i64>().ok())
+        .unwrap_or(100)
+        .clamp(1, 1000);
+    let failed_only = params.get("failed").map(String::as_str) == Some("true");
+    match notifications::deliveries(params.get("rule").map(String::as_str), failed_only, limit).await {
+        Ok(deliveries) => Json(&deliveries).into_response(),
+        Err(e) => {
+            log::error!("Failed to select notification deliveries: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+async fn run_backfill(Query(params): Query<HashMap<String, String>>) -> Response {
+    let (since, until) = match (params.get("since").and_then(|s| s.parse().ok()), params.get("until").and_then(|s| s.parse().ok())) {
+        (Some(since), Some(until)) if since <= until => (since, until),
+        _ => return (StatusCode::BAD_REQUEST, "since and until (unix timestamps, since <= until) are required").into_response(),
+    };
+    let backfill_request = backfill::BackfillRequest {
+        provider: params.get("provider").cloned(),
+        since,
+        until,
+        apply: params.get("apply").map(String::as_str) == Some("true"),
+    };
+    match backfill::run(&backfill_request).await {
+        Ok(report) => Json(&report).into_response(),
+        Err(e) => {
+            log::error!("Backfill failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+async fn running_config(State(state): Shared) -> Response {
+    Json(&deploy::Running::of(&state.config)).into_response()
+}
+
+/// Latency and error rate of each provider over the SLO window against its targets
+async fn provider_health() -> Response {
+    let mut health = slo::health();
+    for provider in &mut health {
+        provider.circuit = provider_health::status(&provider.provider);
+        provider.budgets = quota::status(&provider.provider);
+    }
+    Json(health).into_response()
+}
+
+/// A candidate in the body previews it; `?confirm=` with the preview's token applies it
+async fn deploy_config(State(live): State<LiveState>, Query(params): Query<HashMap<String, String>>, candidate: Option<Json<deploy::Candidate>>) -> Response {
+    let token = match params.get("confirm") {
+        Some(token) => token,
+        None => {
+            let Some(Json(candidate)) = candidate else {
+                return (StatusCode::BAD_REQUEST, "Expected a JSON candidate configuration").into_response();
+            };
+            return match deploy::preview(&live.snapshot().config, &candidate) {
+                Ok(preview) if preview.valid => Json(&preview).into_response(),
+                Ok(preview) => (StatusCode::UNPROCESSABLE_ENTITY, Json(&preview)).into_response(),
+                Err(e) => {
+                    log::error!("Failed to preview configuration: {}", e);
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response()
+                }
+            };
+        },
+    };
+    let (base, settings) = match deploy::take_confirmation(token) {
+        Ok(pending) => pending,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    // Blue/green: build the new state beside the running one, then swap it in under the lock
+    // so a concurrent deployment can't interleave
+    let mut current = match live.current.write() {
+        Ok(current) => current,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if deploy::Settings::of(&current.config) != base {
+        return (StatusCode::CONFLICT, "The running configuration changed since the preview, preview again").into_response();
+    }
+    let next = Arc::new(ServerState::new(settings.apply(current.config.clone())));
+    let deployed = deploy::Deployed::new(deploy::Running::of(&next.config), deploy::diff(&base, &settings));
+    *current = next;
+    drop(current);
+
+    log::warn!("[combo] Deployed configuration changes: {}",
+        deployed.changes.iter().map(|change| format!("{} {} -> {}", change.setting, change.from, change.to)).collect::<Vec<_>>().join(", "));
+    Json(&deployed).into_response()
+}
+
+async fn pool_metrics() -> Response {
+    ([(header::CONTENT_TYPE, "application/json")], crate::pool_monitor::handle_metrics_endpoint()).into_response()
+}
+
+// Return a cached response if one exists within the timeout window
+// Otherwise check configured providers for current weather conditions and cache the results
+async fn current_conditions(State(state): Shared, method: Method, format: Result<ResponseFormat, Response>) -> Response {
+    if method != Method::GET {
+        return "hello world".into_response();
+    }
+    let format = match format {
+        Ok(format) => format,
+        Err(response) => return response,
+    };
+    let config = &state.config;
+    let response_cache = &state.response_cache;
+
+    // Expired cached conditions, served if every provider fails on refresh
+    let mut stale = None;
+    if let Some(timeout) = config.cache_timeout {
+        let current_timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() as i64,
+            Err(e) => {
+                log::error!("System time error: {}", e);
+                0i64
+            }
+        };
+
+        // Hot path: bytes already serialized for this format
+        if let Some(cached) = response_cache.get(format, current_timestamp, timeout) {
+            let age = (current_timestamp - cached.timestamp).max(0) as u64;
+            return with_cache_headers(with_schema(cached.response(), Schema::CachedConditions), Some(CacheFreshness { age, ttl: timeout as u64 }));
+        }
+
+        let conditions = |entry: cache::CacheEntry| serde_json::from_value::<CachedWeatherData>(entry.value)
+            .map_err(|e| log::error!("Failed to parse cached weather data: {}", e))
+            .ok();
+        let lookup = cache::shared().lookup(&config.conditions_key(), timeout.max(0) as u64).await;
+        metrics::record_cache_lookup("conditions", matches!(lookup, Lookup::Fresh(_) | Lookup::Stale(_)));
+        match lookup {
+            Lookup::Fresh(entry) => if let Some(data) = conditions(entry) {
+                let age = (current_timestamp - data.timestamp).max(0) as u64;
+                return with_cache_headers(with_schema(cached_response(response_cache, format, &data), Schema::CachedConditions),
+                    Some(CacheFreshness { age, ttl: timeout as u64 }));
+            },
+            // Another request is refreshing them
+            Lookup::Stale(entry) => if let Some(data) = conditions(entry) {
+                return with_cache_headers(with_schema(response_format::respond(format, &data), Schema::CachedConditions), None);
+            },
+            Lookup::Revalidate(entry) => stale = entry.and_then(conditions),
+        }
+    }
+
+    let stale_timestamp = stale.as_ref().map(|stale| stale.timestamp);
+    let resp = CONDITIONS_REFRESH.run(&config.conditions_key(), || refresh_conditions(config, stale_timestamp)).await;
+    if resp.accuweather.is_none() && resp.homebrew.is_none() {
+        if let Some(stale) = stale {
+            log::warn!("Refreshing current conditions failed, serving cached conditions from {}", stale.timestamp);
+            return with_cache_headers(with_schema(response_format::respond(format, &stale), Schema::CachedConditions), None);
+        }
+    }
+
+    match config.cache_timeout.filter(|timeout| *timeout > 0) {
+        Some(timeout) => with_cache_headers(with_schema(cached_response(response_cache, format, &resp), Schema::CachedConditions),
+            Some(CacheFreshness { age: 0, ttl: timeout as u64 })),
+        None => with_cache_headers(with_schema(response_format::respond(format, &resp), Schema::CachedConditions), None),
+    }
+}
+
+/// Refreshes expired current conditions. Instances sharing Redis (see `cluster`) take turns:
+/// one refreshes while the others wait for the row it stores, newer than `stale_timestamp`, and
+/// after a refresh that got nothing none of them retries for a while. An empty result means
+/// the stale conditions should be served.
+#[cfg_attr(not(feature = "redis"), allow(unused_variables))]
+async fn refresh_conditions(config: &Config, stale_timestamp: Option<i64>) -> CachedWeatherData {
+    #[cfg(feature = "redis")]
+    if let (Some(cluster), Some(_)) = (crate::cluster::get(), config.cache_timeout) {
+        use crate::cluster::REFRESH_WAIT_SECS;
+        use std::time::Duration;
+
+        if cluster.failed_recently("conditions").await {
+            return CachedWeatherData::new();
+        }
+        match cluster.try_lock("conditions", Duration::from_secs(REFRESH_WAIT_SECS)).await {
+            Some(Some(lock)) => {
+                let resp = config.refresh_cached_conditions().await;
+                if resp.accuweather.is_none() && resp.homebrew.is_none() {
+                    cluster.mark_failed("conditions").await;
+                }
+                cluster.unlock(lock).await;
+                return resp;
+            },
+            Some(None) => {
+                let waiting = Instant::now();
+                while waiting.elapsed() < Duration::from_secs(REFRESH_WAIT_SECS) {
+                    tokio::time::sleep(Duration::from_millis(250)).await;
+                    if cluster.failed_recently("conditions").await {
+                        return CachedWeatherData::new();
+                    }
+                    let newest = CachedWeatherData::select(config.clone(), Some(1), None, Some("timestamp DESC".to_string()), None).await
+                        .ok()
+                        .and_then(|rows| rows.into_iter().next());
+                    if let Some(row) = newest.filter(|row| Some(row.timestamp) > stale_timestamp) {
+                        if let Ok(value) = serde_json::to_value(&row) {
+                            cache::shared().set(&config.conditions_key(), value).await;
+                        }
+                        return row;
+                    }
+                }
+                log::warn!("Another instance didn't refresh current conditions within {}s, refreshing here", REFRESH_WAIT_SECS);
+            },
+            None => {},
+        }
+    }
+    config.refresh_cached_conditions().await
+}
+
 // Stored in SQL in cache_timeout is set
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CachedWeatherData {
@@ -464,7 +1371,7 @@ impl CachedWeatherData {
         return format!("cached_weather_data")
     }
     pub fn sql_build_statement() -> &'static str {
-        "CREATE TABLE public.cached_weather_data (
+        "CREATE TABLE IF NOT EXISTS public.cached_weather_data (
             id serial NOT NULL,
             oid varchar NOT NULL UNIQUE,
             accuweather VARCHAR NULL,
@@ -473,64 +1380,53 @@ impl CachedWeatherData {
             timestamp BIGINT DEFAULT 0,
             CONSTRAINT cached_weather_data_pkey PRIMARY KEY (id));"
     }
-    pub fn migrations() -> Vec<&'static str> {
-        vec![
-            "",
-        ]
-    }
-    pub fn save(&self, config: Config) -> JupiterResult<&Self> {
-        // Use async runtime to get connection from pool
-        let runtime = tokio::runtime::Runtime::new()
-            .map_err(|e| JupiterError::DatabaseError(format!("Failed to create runtime: {}", e)))?;
-        let mut client = runtime.block_on(async {
-            let pool = get_combo_pool()
-                .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
-            
-            pool.get_connection_with_retry(3).await
-                .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))
-        })?;
+    pub async fn save(&self, config: Config) -> JupiterResult<&Self> {
+        let pool = get_combo_pool()
+            .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+        let client = pool.get_connection_with_retry(3).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
 
         // Search for OID matches using secure parameterized query
         let rows = Self::select_by_oid(
             config.clone(),
             &self.oid
-        )?;
+        ).await?;
 
         if rows.len() == 0 {
-            runtime.block_on(client.execute("INSERT INTO cached_weather_data (oid, timestamp) VALUES ($1, $2)",
+            client.execute("INSERT INTO cached_weather_data (oid, timestamp) VALUES ($1, $2)",
                 &[&self.oid.clone(),
                 &self.timestamp]
-            ))?;
+            ).await?;
         } 
 
         if self.accuweather.is_some() {
-            runtime.block_on(client.execute("UPDATE cached_weather_data SET accuweather = $1 WHERE oid = $2;", 
+            client.execute("UPDATE cached_weather_data SET accuweather = $1 WHERE oid = $2;", 
             &[
                 &self.accuweather,
                 &self.oid
-            ]))?;
+            ]).await?;
         }
 
         if self.homebrew.is_some() {
-            runtime.block_on(client.execute("UPDATE cached_weather_data SET homebrew = $1 WHERE oid = $2;", 
+            client.execute("UPDATE cached_weather_data SET homebrew = $1 WHERE oid = $2;", 
             &[
                 &self.homebrew,
                 &self.oid
-            ]))?;
+            ]).await?;
         }
 
         if self.openweathermap.is_some() {
-            runtime.block_on(client.execute("UPDATE cached_weather_data SET openweathermap = $1 WHERE oid = $2;", 
+            client.execute("UPDATE cached_weather_data SET openweathermap = $1 WHERE oid = $2;", 
             &[
                 &self.openweathermap,
                 &self.oid
-            ]))?;
+            ]).await?;
         }
 
         return Ok(self);
     }
     // Secure method to select by OID using parameterized query
-    pub fn select_by_oid(config: Config, oid: &str) -> JupiterResult<Vec<Self>> {
+    pub async fn select_by_oid(_config: Config, oid: &str) -> JupiterResult<Vec<Self>> {
         // Validate OID input before using in query
         if !InputSanitizer::validate_oid(oid) {
             log::error!("Invalid OID format detected: {}", oid);
@@ -540,32 +1436,27 @@ impl CachedWeatherData {
             log::error!("Potential SQL injection detected in OID: {}", oid);
         }
         
-        // Use async runtime to get connection from pool
-        let runtime = tokio::runtime::Runtime::new()
-            .map_err(|e| JupiterError::DatabaseError(format!("Failed to create runtime: {}", e)))?;
-        runtime.block_on(async {
-            let pool = get_combo_pool()
-                .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
-            
-            let client = pool.get_connection_with_retry(3).await
-                .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
-            
-            let query = "SELECT * FROM cached_weather_data WHERE oid = $1 ORDER BY id DESC";
-            let rows = client.query(query, &[&oid]).await
-                .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
-            
-            let mut parsed_rows: Vec<Self> = Vec::new();
-            for row in rows {
-                parsed_rows.push(Self::from_row(&row)
-                    .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e)))?);
-            }
-            
-            Ok(parsed_rows)
-        })
+        let pool = get_combo_pool()
+            .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+        
+        let client = pool.get_connection_with_retry(3).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+        
+        let query = "SELECT * FROM cached_weather_data WHERE oid = $1 ORDER BY id DESC";
+        let rows = client.query(query, &[&oid]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+        
+        let mut parsed_rows: Vec<Self> = Vec::new();
+        for row in rows {
+            parsed_rows.push(Self::from_row(&row)
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e)))?);
+        }
+        
+        Ok(parsed_rows)
     }
     
     // Secure select method with parameterized queries
-    pub fn select(config: Config, limit: Option<usize>, offset: Option<usize>, order_column: Option<String>, filter_params: Option<FilterParams>) -> JupiterResult<Vec<Self>> {
+    pub async fn select(_config: Config, limit: Option<usize>, offset: Option<usize>, order_column: Option<String>, filter_params: Option<FilterParams>) -> JupiterResult<Vec<Self>> {
         // Build secure query with parameterized placeholders
         let mut query = String::from("SELECT * FROM cached_weather_data");
         let mut param_count = 0;
@@ -597,38 +1488,33 @@ impl CachedWeatherData {
             query.push_str(&format!(" OFFSET {}", offset_val));
         }
         
-        // Use async runtime to get connection from pool
-        let runtime = tokio::runtime::Runtime::new()
-            .map_err(|e| JupiterError::DatabaseError(format!("Failed to create runtime: {}", e)))?;
-        runtime.block_on(async {
-            let pool = get_combo_pool()
-                .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
-            
-            let client = pool.get_connection_with_retry(3).await
-                .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
-            
-            // Execute query with appropriate parameters
-            let rows = if let Some(ref filters) = filter_params {
-                if let Some(ref oid) = filters.oid {
-                    client.query(&query, &[oid]).await
-                        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
-                } else {
-                    client.query(&query, &[]).await
-                        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
-                }
+        let pool = get_combo_pool()
+            .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+        
+        let client = pool.get_connection_with_retry(3).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+        
+        // Execute query with appropriate parameters
+        let rows = if let Some(ref filters) = filter_params {
+            if let Some(ref oid) = filters.oid {
+                client.query(&query, &[oid]).await
+                    .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
             } else {
                 client.query(&query, &[]).await
                     .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
-            };
-            
-            let mut parsed_rows: Vec<Self> = Vec::new();
-            for row in rows {
-                parsed_rows.push(Self::from_row(&row)
-                    .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e)))?);
             }
-            
-            Ok(parsed_rows)
-        })
+        } else {
+            client.query(&query, &[]).await
+                .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
+        };
+        
+        let mut parsed_rows: Vec<Self> = Vec::new();
+        for row in rows {
+            parsed_rows.push(Self::from_row(&row)
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e)))?);
+        }
+        
+        Ok(parsed_rows)
     }
     fn from_row(row: &Row) -> JupiterResult<Self> {
         return Ok(Self {
@@ -649,7 +1535,7 @@ impl CachedWeatherData {
 pub struct PostgresServer {
 	pub db_name: String,
     pub username: String,
-    pub password: String,
+    pub password: Secret<String>,
 	pub address: String
 }
 impl PostgresServer {
@@ -3,12 +3,31 @@ use serde::{Deserialize, Serialize};
 use super::common::{
     Weather, WeatherError, WeatherProvider, Forecast, Alert, Location, 
     DailyForecast, HourlyForecast, AlertSeverity, WeatherFeature, 
-    HistoricalData
+    HistoricalData, PollenLevel, PollenReport, PollenType, MarineForecast, MinutelyForecast,
+    SourceContribution, LocationQuery, distance_km
 };
 use std::sync::Arc;
+use super::air_quality::{self, AirQualityProvider, AirQualityReport};
+use super::{health, quota};
 use crate::utils::time::safe_timestamp_with_fallback;
-use tokio::sync::RwLock;
-use std::collections::HashMap;
+use crate::analysis::comfort::comfort;
+use crate::analysis::conditions::ConditionCode;
+use crate::analysis::road::estimate_road_condition;
+use crate::metrics::{self, Counter, Histogram};
+use crate::cache::{Cache, Lookup};
+use crate::coalesce::Coalescer;
+use crate::units::{Pressure, Speed, Temperature};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use once_cell::sync::Lazy;
+use tracing::Instrument;
+
+// Minute-level forecasts go stale within minutes, whatever the configured cache duration
+const MINUTELY_CACHE_SECS: u64 = 300;
+
+// When every provider fails, conditions and forecasts up to this old are served from the
+// cache instead of an error
+const STALE_CACHE_SECS: u64 = 86_400;
 
 // Helper function to safely get current timestamp
 fn get_current_timestamp() -> Result<i64, WeatherError> {
@@ -25,32 +44,531 @@ fn get_current_timestamp_u64() -> Result<u64, WeatherError> {
         .map_err(|e| WeatherError::ConfigurationError(format!("Failed to get system time: {}", e)))
 }
 
+static PROVIDER_REQUESTS: Lazy<Arc<Counter>> = Lazy::new(|| metrics::counter(
+    "jupiter_provider_requests_total", "Weather provider requests by provider and outcome (ok, error, skipped or over_budget)", &["provider", "outcome"],
+));
+
+static PROVIDER_DURATION: Lazy<Arc<Histogram>> = Lazy::new(|| metrics::histogram(
+    "jupiter_provider_request_duration_seconds", "Time weather providers take to answer", &["provider"],
+));
+
+/// Span for a call to `provider`, so what is logged during it carries the provider and operation
+pub(crate) fn provider_span(provider: &str, operation: &'static str) -> tracing::Span {
+    tracing::info_span!("provider", provider, operation)
+}
+
+/// Counts a provider request started at `start` in the provider metrics and logs its latency
+pub(crate) fn record_provider_request<T>(provider: &str, start: Instant, result: &Result<T, WeatherError>) {
+    let latency_ms = start.elapsed().as_millis() as u64;
+    match result {
+        Ok(_) => tracing::info!(provider, latency_ms, "provider call finished"),
+        Err(e) => tracing::warn!(provider, latency_ms, error = %e, "provider call failed"),
+    }
+    PROVIDER_REQUESTS.inc(&[provider, if result.is_ok() { "ok" } else { "error" }]);
+    PROVIDER_DURATION.observe_since(&[provider], start);
+    crate::slo::record(provider, start.elapsed(), result.is_ok());
+    health::record(provider, start.elapsed(), result.as_ref().err());
+}
+
+/// Whether to call `provider`: not while its circuit is open (counted as skipped) or once a
+/// budget of its calls is used up (counted as over_budget), see `health` and `quota`
+pub(crate) fn provider_available(provider: &str) -> Result<(), WeatherError> {
+    if !health::allow(provider) {
+        PROVIDER_REQUESTS.inc(&[provider, "skipped"]);
+        return Err(WeatherError::NetworkError(format!("{} skipped after repeated failures", provider)));
+    }
+    if !quota::allow(provider) {
+        PROVIDER_REQUESTS.inc(&[provider, "over_budget"]);
+        return Err(WeatherError::RateLimitExceeded);
+    }
+    Ok(())
+}
+
+/// Wraps a provider so every call is counted and timed in the provider metrics, and skipped
+/// while the provider's circuit is open or its budget is used up
+struct MeteredProvider {
+    inner: Box<dyn WeatherProvider>,
+}
+
+impl MeteredProvider {
+    async fn call<T>(&self, operation: &'static str, request: impl std::future::Future<Output = Result<T, WeatherError>>) -> Result<T, WeatherError> {
+        provider_available(self.inner.name())?;
+        async {
+            let start = Instant::now();
+            let result = request.await;
+            record_provider_request(self.inner.name(), start, &result);
+            result
+        }.instrument(provider_span(self.inner.name(), operation)).await
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for MeteredProvider {
+    async fn get_current_weather(&self, location: &str) -> Result<Weather, WeatherError> {
+        self.call("current", self.inner.get_current_weather(location)).await
+    }
+
+    async fn get_forecast(&self, location: &str, days: u8) -> Result<Forecast, WeatherError> {
+        self.call("forecast", self.inner.get_forecast(location, days)).await
+    }
+
+    async fn get_alerts(&self, location: &str) -> Result<Vec<Alert>, WeatherError> {
+        self.call("alerts", self.inner.get_alerts(location)).await
+    }
+
+    async fn get_historical(&self, location: &str, date: &str) -> Result<HistoricalData, WeatherError> {
+        self.call("historical", self.inner.get_historical(location, date)).await
+    }
+
+    async fn get_pollen(&self, location: &str) -> Result<PollenReport, WeatherError> {
+        self.call("pollen", self.inner.get_pollen(location)).await
+    }
+
+    async fn get_marine(&self, location: &str) -> Result<MarineForecast, WeatherError> {
+        self.call("marine", self.inner.get_marine(location)).await
+    }
+
+    async fn get_minutely(&self, location: &str) -> Result<MinutelyForecast, WeatherError> {
+        self.call("minutely", self.inner.get_minutely(location)).await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn supports_feature(&self, feature: WeatherFeature) -> bool {
+        self.inner.supports_feature(feature)
+    }
+}
+
+/// Distance at which a source keeps half its weight when blending for a query by coordinates
+pub const DISTANCE_SCALE_KM: f64 = 1.0;
+
+/// Inverse-distance weighting: a source `distance` km from the query point keeps
+/// 1 / (1 + (distance / scale)²) of its weight, so all of it on site and, with a 1 km scale,
+/// 1/401 of it 20 km away
+fn distance_factor(distance: f64, scale_km: f64) -> f64 {
+    1.0 / (1.0 + (distance / scale_km).powi(2))
+}
+
+/// What `weather` from `provider` fed into a combined reading taken at `now`
+fn source_contribution(provider: &str, weight: f64, weather: &Weather, now: i64) -> SourceContribution {
+    // In the API's units, °C, km/h and hPa
+    let fields = [
+        ("temperature", Some(weather.temperature.celsius())),
+        ("feels_like", weather.feels_like.map(Temperature::celsius)),
+        ("humidity", weather.humidity),
+        ("pressure", weather.pressure.map(Pressure::hpa)),
+        ("wind_speed", weather.wind_speed.map(Speed::kmh)),
+        ("wind_direction", weather.wind_direction),
+        ("precipitation", weather.precipitation),
+        ("visibility", weather.visibility),
+        ("uv_index", weather.uv_index),
+    ];
+    SourceContribution {
+        provider: provider.to_string(),
+        local: false,
+        weight,
+        distance_km: None,
+        age_secs: (now - weather.timestamp).max(0),
+        values: fields.into_iter()
+            .filter_map(|(field, value)| value.map(|value| (field.to_string(), value)))
+            .collect(),
+        rejected: Vec::new(),
+    }
+}
+
+/// Weighted standard deviation of the values of `field` from `sources` when two or more
+/// reported it, e.g. 1.2 for a combined temperature of ±1.2°C
+fn spread(field: &str, sources: &[SourceContribution]) -> Option<f64> {
+    let values: Vec<(f64, f64)> = sources.iter()
+        .filter_map(|source| Some((*source.values.get(field)?, source.weight)))
+        .collect();
+    let total_weight: f64 = values.iter().map(|(_, weight)| weight).sum();
+    if values.len() < 2 || total_weight <= 0.0 {
+        return None;
+    }
+    let mean = values.iter().map(|(value, weight)| value * weight).sum::<f64>() / total_weight;
+    let variance = values.iter().map(|(value, weight)| weight * (value - mean).powi(2)).sum::<f64>() / total_weight;
+    Some(variance.sqrt())
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+/// Which values of a field are left out of the combined reading as outliers, so one provider
+/// returning a bogus -40°C doesn't drag the average with it. Needs three or more sources
+/// reporting the field: with two there's no telling which one is off.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OutlierRejection {
+    #[default]
+    Off,
+    /// Values farther than this many standard deviations of the other sources' values from
+    /// their median ("stddev:3"). The deviation is at least the field's `noise_floor`, so
+    /// sources agreeing within sensor precision aren't rejected.
+    StdDevs(f64),
+    /// Values farther from the median of all sources than the field's delta, in the field's
+    /// unit ("delta:temperature=10,humidity=25"); other fields aren't checked
+    Delta(BTreeMap<String, f64>),
+}
+
+impl OutlierRejection {
+    /// Spread of the other sources below which a value isn't judged against it, in the
+    /// field's unit
+    pub fn noise_floor(field: &str) -> f64 {
+        match field {
+            "temperature" | "feels_like" => 1.0,    // °C
+            "humidity" => 5.0,                      // %
+            "pressure" => 2.0,                      // hPa
+            "wind_speed" => 3.6,                    // km/h
+            "precipitation" => 0.5,                 // mm
+            "visibility" => 1.0,                    // km
+            "uv_index" => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Indexes of the outliers among `values` of `field`. Wind direction is never checked
+    /// since angles wrap around, and every value is kept if all of them would be rejected.
+    pub fn outliers(&self, field: &str, values: &[f64]) -> Vec<usize> {
+        if values.len() < 3 || field == "wind_direction" {
+            return Vec::new();
+        }
+        let outliers: Vec<usize> = match self {
+            OutlierRejection::Off => Vec::new(),
+            OutlierRejection::StdDevs(limit) => (0..values.len())
+                .filter(|&i| {
+                    let mut others: Vec<f64> = values.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, v)| *v).collect();
+                    let mean = others.iter().sum::<f64>() / others.len() as f64;
+                    let deviation = (others.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / others.len() as f64).sqrt();
+                    (values[i] - median(&mut others)).abs() > limit * deviation.max(Self::noise_floor(field))
+                })
+                .collect(),
+            OutlierRejection::Delta(deltas) => match deltas.get(field) {
+                Some(delta) => {
+                    let middle = median(&mut values.to_vec());
+                    (0..values.len()).filter(|&i| (values[i] - middle).abs() > *delta).collect()
+                },
+                None => Vec::new(),
+            },
+        };
+        if outliers.len() == values.len() { Vec::new() } else { outliers }
+    }
+}
+
+impl std::str::FromStr for OutlierRejection {
+    type Err = String;
+
+    /// "off", "stddev:N" or "delta:field=max,..." (OUTLIER_REJECTION)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() || s.eq_ignore_ascii_case("off") {
+            return Ok(OutlierRejection::Off);
+        }
+        let number = |value: &str, what: &str| value.trim().parse::<f64>().ok()
+            .filter(|value| value.is_finite() && *value > 0.0)
+            .ok_or_else(|| format!("{} must be a number above 0, got '{}'", what, value.trim()));
+        match s.split_once(':') {
+            Some((mode, limit)) if mode.trim().eq_ignore_ascii_case("stddev") => Ok(OutlierRejection::StdDevs(number(limit, "the number of standard deviations")?)),
+            Some((mode, deltas)) if mode.trim().eq_ignore_ascii_case("delta") => {
+                let mut parsed = BTreeMap::new();
+                for entry in deltas.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+                    let (field, delta) = entry.split_once('=')
+                        .ok_or_else(|| format!("expected field=delta, got '{}'", entry))?;
+                    let field = field.trim();
+                    if !FusionPolicy::FIELDS.contains(&field) || field == "wind_direction" {
+                        return Err(format!("unknown field '{}', expected one of {}", field,
+                            FusionPolicy::FIELDS.iter().filter(|f| **f != "wind_direction").copied().collect::<Vec<_>>().join(", ")));
+                    }
+                    parsed.insert(field.to_string(), number(delta, field)?);
+                }
+                if parsed.is_empty() {
+                    return Err("delta needs at least one field=delta".to_string());
+                }
+                Ok(OutlierRejection::Delta(parsed))
+            },
+            _ => Err(format!("expected off, stddev:N or delta:field=max,..., got '{}'", s)),
+        }
+    }
+}
+
+/// Weights of providers for single fields, multiplying their configured weight, e.g. to trust
+/// NWS for pressure and AccuWeather for UV. Parsed from "provider.field=weight" pairs
+/// ("nws.pressure=2,accuweather.uv_index=3", FIELD_WEIGHTS) with providers matched by name
+/// regardless of case; 0 leaves a provider out of a field.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FieldWeights {
+    weights: BTreeMap<(String, String), f64>,   // (lowercase provider, field)
+}
+
+impl FieldWeights {
+    pub fn parse(weights: &str) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        for entry in weights.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (key, weight) = entry.split_once('=')
+                .ok_or_else(|| format!("expected provider.field=weight, got '{}'", entry))?;
+            // Provider names like Tomorrow.io have dots of their own
+            let (provider, field) = key.trim().rsplit_once('.')
+                .ok_or_else(|| format!("expected provider.field=weight, got '{}'", entry))?;
+            if !FusionPolicy::FIELDS.contains(&field) {
+                return Err(format!("unknown field '{}', expected one of {}", field, FusionPolicy::FIELDS.join(", ")));
+            }
+            let weight = weight.trim().parse::<f64>().ok()
+                .filter(|weight| weight.is_finite() && (0.0..=100.0).contains(weight))
+                .ok_or_else(|| format!("weight of {} must be between 0 and 100, got '{}'", key.trim(), weight.trim()))?;
+            parsed = parsed.with(provider, field, weight);
+        }
+        Ok(parsed)
+    }
+
+    pub fn with(mut self, provider: &str, field: &str, weight: f64) -> Self {
+        self.weights.insert((provider.trim().to_lowercase(), field.to_string()), weight);
+        self
+    }
+
+    /// Multiplier of `provider`'s weight for `field`, 1 unless set
+    pub fn weight(&self, provider: &str, field: &str) -> f64 {
+        self.weights.get(&(provider.to_lowercase(), field.to_string())).copied().unwrap_or(1.0)
+    }
+
+    /// Providers named in the weights, lowercase
+    pub fn providers(&self) -> impl Iterator<Item = &str> {
+        self.weights.keys().map(|(provider, _)| provider.as_str())
+    }
+}
+
+/// Where a combined field comes from when nearby sensors and regional providers both report it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldPreference {
+    Local,      // Homebrew sensors, falling back to providers
+    Regional,   // Providers, falling back to homebrew sensors
+    Blend,      // Weighted average of both
+}
+
+impl FieldPreference {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldPreference::Local => "local",
+            FieldPreference::Regional => "regional",
+            FieldPreference::Blend => "blend",
+        }
+    }
+}
+
+impl std::str::FromStr for FieldPreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "local" => Ok(FieldPreference::Local),
+            "regional" => Ok(FieldPreference::Regional),
+            "blend" => Ok(FieldPreference::Blend),
+            other => Err(format!("unknown source preference '{}', expected local, regional or blend", other)),
+        }
+    }
+}
+
+/// Per-field fusion of local sensors and regional providers for current conditions. Hyperlocal
+/// fields (temperature, humidity, precipitation) prefer local sensors and everything else, like
+/// pressure, prefers providers. Forecasts always come from providers since local stations don't
+/// forecast, and alerts from both are merged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FusionPolicy {
+    preferences: HashMap<String, FieldPreference>,
+}
+
+impl Default for FusionPolicy {
+    fn default() -> Self {
+        let preferences = ["temperature", "feels_like", "humidity", "precipitation"].iter()
+            .map(|field| (field.to_string(), FieldPreference::Local))
+            .collect();
+        Self { preferences }
+    }
+}
+
+impl FusionPolicy {
+    /// Fields of current conditions the policy applies to
+    pub const FIELDS: [&'static str; 9] = [
+        "temperature", "feels_like", "humidity", "pressure", "wind_speed",
+        "wind_direction", "precipitation", "visibility", "uv_index",
+    ];
+
+    /// Flat weighted averaging of every source, as without local sensors
+    pub fn blend() -> Self {
+        let preferences = Self::FIELDS.iter()
+            .map(|field| (field.to_string(), FieldPreference::Blend))
+            .collect();
+        Self { preferences }
+    }
+
+    /// The default policy with overrides like "humidity=blend,wind_speed=local" (FUSION_POLICY)
+    pub fn parse(overrides: &str) -> Result<Self, String> {
+        let mut policy = Self::default();
+        for entry in overrides.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (field, preference) = entry.split_once('=')
+                .ok_or_else(|| format!("expected field=preference, got '{}'", entry))?;
+            let field = field.trim();
+            if !Self::FIELDS.contains(&field) {
+                return Err(format!("unknown field '{}', expected one of {}", field, Self::FIELDS.join(", ")));
+            }
+            policy = policy.with(field, preference.parse()?);
+        }
+        Ok(policy)
+    }
+
+    pub fn with(mut self, field: &str, preference: FieldPreference) -> Self {
+        self.preferences.insert(field.to_string(), preference);
+        self
+    }
+
+    pub fn preference(&self, field: &str) -> FieldPreference {
+        self.preferences.get(field).copied().unwrap_or(FieldPreference::Regional)
+    }
+
+    /// Weighted average of `field` over the preferred sources that reported it, or over every
+    /// source that did when none of the preferred ones did
+    fn fuse(&self, field: &str, sources: &[SourceContribution]) -> Option<f64> {
+        let reporting: Vec<&SourceContribution> = sources.iter()
+            .filter(|source| source.values.contains_key(field))
+            .collect();
+        let preferred: Vec<&SourceContribution> = match self.preference(field) {
+            FieldPreference::Local => reporting.iter().copied().filter(|source| source.local).collect(),
+            FieldPreference::Regional => reporting.iter().copied().filter(|source| !source.local).collect(),
+            FieldPreference::Blend => Vec::new(),
+        };
+        let chosen = if preferred.is_empty() { reporting } else { preferred };
+
+        let total_weight: f64 = chosen.iter().map(|source| source.weight).sum();
+        if total_weight <= 0.0 {
+            return None;
+        }
+        Some(chosen.iter().map(|source| source.values[field] * source.weight).sum::<f64>() / total_weight)
+    }
+}
+
+/// Whether the configured providers can serve a feature, and which of them do
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureSupport {
+    pub supported: bool,
+    pub providers: Vec<String>,
+}
+
+/// Features the combo server can serve with its providers, for GET /api/capabilities
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub providers: Vec<String>,
+    pub features: BTreeMap<String, FeatureSupport>,
+}
+
 pub struct ComboProvider {
     providers: Vec<Box<dyn WeatherProvider>>,
+    air_quality: Vec<Box<dyn AirQualityProvider>>,
     weights: HashMap<String, f64>,
-    cache: Arc<RwLock<WeatherCache>>,
+    local: HashSet<String>,
+    fusion: FusionPolicy,
+    outliers: OutlierRejection,
+    field_weights: FieldWeights,
+    distance_scale_km: Option<f64>,
+    cache: Arc<Cache>,
     cache_duration_secs: u64,
     fallback_enabled: bool,
+    prefetching: bool,
+    in_flight: Coalescer<Result<serde_json::Value, WeatherError>>,    // Fetches by cache key
 }
 
 impl ComboProvider {
     pub fn new() -> Self {
         Self {
             providers: Vec::new(),
+            air_quality: Vec::new(),
             weights: HashMap::new(),
-            cache: Arc::new(RwLock::new(WeatherCache::new())),
+            local: HashSet::new(),
+            fusion: FusionPolicy::default(),
+            outliers: OutlierRejection::Off,
+            field_weights: FieldWeights::default(),
+            distance_scale_km: Some(DISTANCE_SCALE_KM),
+            cache: Arc::new(Cache::memory(0)),
             cache_duration_secs: 300,
             fallback_enabled: true,
+            prefetching: false,
+            in_flight: Coalescer::new("provider"),
         }
     }
     
     pub fn add_provider(mut self, provider: Box<dyn WeatherProvider>, weight: f64) -> Self {
+        #[cfg(feature = "fault-injection")]
+        let provider: Box<dyn WeatherProvider> = Box::new(crate::fault::FaultyProvider::new(provider));
+        let provider = Box::new(MeteredProvider { inner: provider });
         let name = provider.name().to_string();
         self.providers.push(provider);
         self.weights.insert(name, weight);
         self
     }
     
+    /// A nearby sensor station, preferred over regional providers for the fields the fusion
+    /// policy marks local
+    pub fn add_local_provider(mut self, provider: Box<dyn WeatherProvider>, weight: f64) -> Self {
+        self.local.insert(provider.name().to_string());
+        self.add_provider(provider, weight)
+    }
+    
+    pub fn set_fusion_policy(mut self, policy: FusionPolicy) -> Self {
+        self.fusion = policy;
+        self
+    }
+    
+    pub fn set_outlier_rejection(mut self, outliers: OutlierRejection) -> Self {
+        self.outliers = outliers;
+        self
+    }
+    
+    pub fn set_field_weights(mut self, weights: FieldWeights) -> Self {
+        self.field_weights = weights;
+        self
+    }
+    
+    /// Air quality sources in order of precedence, see `air_quality::combine`
+    pub fn add_air_quality_provider(mut self, provider: Box<dyn AirQualityProvider>) -> Self {
+        self.air_quality.push(provider);
+        self
+    }
+    
+    /// Each feature with the providers supporting it. Air quality is read from the air quality
+    /// providers rather than the weather providers, so those are what it lists.
+    pub fn capabilities(&self) -> Capabilities {
+        let features = WeatherFeature::ALL.iter().map(|feature| {
+            let providers: Vec<String> = match feature {
+                WeatherFeature::AirQuality => self.air_quality.iter().map(|p| p.name().to_string()).collect(),
+                _ => self.providers.iter().filter(|p| p.supports_feature(*feature)).map(|p| p.name().to_string()).collect(),
+            };
+            (feature.name().to_string(), FeatureSupport { supported: !providers.is_empty(), providers })
+        }).collect();
+        Capabilities {
+            providers: self.providers.iter().map(|p| p.name().to_string()).collect(),
+            features,
+        }
+    }
+    
+    /// Scale of the inverse-distance weighting of sources for queries by coordinates, see
+    /// `distance_factor`; None weighs sources by their configured weight alone
+    pub fn set_distance_scale(mut self, scale_km: Option<f64>) -> Self {
+        self.distance_scale_km = scale_km.filter(|scale| *scale > 0.0);
+        self
+    }
+    
+    /// Where results are cached, in memory of this provider by default; see `cache`
+    pub fn set_cache(mut self, cache: Arc<Cache>) -> Self {
+        self.cache = cache;
+        self
+    }
+    
     pub fn set_cache_duration(mut self, seconds: u64) -> Self {
         self.cache_duration_secs = seconds;
         self
@@ -61,114 +579,216 @@ impl ComboProvider {
         self
     }
     
+    /// Always asks the providers instead of reading the cache, storing results for the
+    /// providers sharing it; see `prefetch`
+    pub fn set_prefetching(mut self, prefetching: bool) -> Self {
+        self.prefetching = prefetching;
+        self
+    }
+    
+    /// Age and lifetime of the cached result for `key` ("current:{location}", "forecast:{location}:{days}",
+    /// "alerts:{location}", "pollen:{location}", "marine:{location}", "minutely:{location}" or
+    /// "air_quality:{location}"), if it is still fresh
+    pub async fn cache_freshness(&self, key: &str) -> Option<CacheFreshness> {
+        let ttl = self.cache_ttl(key);
+        let entry = self.cache.get(key, ttl).await?;
+        Some(CacheFreshness { age: entry.age(safe_timestamp_with_fallback()), ttl })
+            .filter(|freshness| freshness.remaining() > 0)
+    }
+    
+    fn cache_ttl(&self, key: &str) -> u64 {
+        if key.starts_with("minutely:") {
+            self.cache_duration_secs.min(MINUTELY_CACHE_SECS)
+        } else {
+            self.cache_duration_secs
+        }
+    }
+    
+    /// The cached result for `key`, or None when this call should fetch it; stale within
+    /// CACHE_STALE_SECS while another call fetches it
     async fn get_from_cache(&self, key: &str) -> Option<serde_json::Value> {
-        let cache = self.cache.read().await;
-        cache.get(key, self.cache_duration_secs)
+        if self.prefetching {
+            return None;
+        }
+        let value = match self.cache.lookup(key, self.cache_ttl(key)).await {
+            Lookup::Fresh(entry) | Lookup::Stale(entry) => Some(entry.value),
+            Lookup::Revalidate(_) => None,
+        };
+        metrics::record_cache_lookup("provider", value.is_some());
+        value
+    }
+    
+    /// Expired cache entry for `key` as a last resort, if it is younger than STALE_CACHE_SECS
+    async fn get_stale<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.cache.release(key);
+        let max_age = STALE_CACHE_SECS.max(self.cache_duration_secs);
+        let entry = self.cache.get(key, max_age).await
+            .filter(|entry| entry.age(safe_timestamp_with_fallback()) < max_age)?;
+        log::warn!("All providers failed, serving stale {}", key);
+        serde_json::from_value(entry.value).ok()
     }
     
     async fn store_in_cache(&self, key: &str, value: serde_json::Value) {
-        let mut cache = self.cache.write().await;
-        cache.set(key.to_string(), value);
+        self.cache.set(key, value).await;
     }
     
-    fn average_weather(&self, weathers: Vec<(String, Weather)>) -> Result<Weather, WeatherError> {
-        if weathers.is_empty() {
-            return Err(WeatherError::NotFound("No weather data available from any provider".to_string()));
+    /// Runs `fetch` for the cache entry `key` unless the same fetch is already under way, in
+    /// which case its result is shared; see `coalesce`
+    async fn coalesced<T>(&self, key: &str, fetch: impl std::future::Future<Output = Result<T, WeatherError>>) -> Result<T, WeatherError>
+    where
+        T: Serialize + serde::de::DeserializeOwned,
+    {
+        let mut fetched = None;
+        let shared = self.in_flight.run(key, || async {
+            let result = fetch.await;
+            let shared = match &result {
+                Ok(value) => serde_json::to_value(value).map_err(WeatherError::from),
+                Err(e) => Err(e.clone()),
+            };
+            fetched = Some(result);
+            shared
+        }).await;
+        match fetched {
+            Some(result) => result,
+            None => Ok(serde_json::from_value(shared?)?),
         }
+    }
+    
+    /// Pollutant concentrations from every air quality provider, combined and indexed
+    pub async fn get_air_quality(&self, location: &str) -> Result<AirQualityReport, WeatherError> {
+        let cache_key = format!("air_quality:{}", location);
         
-        let total_weight: f64 = weathers.iter()
-            .map(|(name, _)| self.weights.get(name).unwrap_or(&1.0))
-            .sum();
-        
-        let mut avg_temp = 0.0;
-        let mut avg_feels_like = 0.0;
-        let mut avg_humidity = 0.0;
-        let mut avg_pressure = 0.0;
-        let mut avg_wind_speed = 0.0;
-        let mut avg_wind_direction = 0.0;
-        let mut avg_precipitation = 0.0;
-        let mut avg_visibility = 0.0;
-        let mut avg_uv = 0.0;
-        
-        let mut feels_like_count = 0.0;
-        let mut humidity_count = 0.0;
-        let mut pressure_count = 0.0;
-        let mut wind_speed_count = 0.0;
-        let mut wind_direction_count = 0.0;
-        let mut precipitation_count = 0.0;
-        let mut visibility_count = 0.0;
-        let mut uv_count = 0.0;
-        
-        let mut descriptions = Vec::new();
-        let mut location = None;
-        
-        for (name, weather) in &weathers {
-            let weight = self.weights.get(name).unwrap_or(&1.0);
-            
-            avg_temp += weather.temperature * weight;
-            
-            if let Some(val) = weather.feels_like {
-                avg_feels_like += val * weight;
-                feels_like_count += weight;
-            }
-            if let Some(val) = weather.humidity {
-                avg_humidity += val * weight;
-                humidity_count += weight;
-            }
-            if let Some(val) = weather.pressure {
-                avg_pressure += val * weight;
-                pressure_count += weight;
-            }
-            if let Some(val) = weather.wind_speed {
-                avg_wind_speed += val * weight;
-                wind_speed_count += weight;
-            }
-            if let Some(val) = weather.wind_direction {
-                avg_wind_direction += val * weight;
-                wind_direction_count += weight;
-            }
-            if let Some(val) = weather.precipitation {
-                avg_precipitation += val * weight;
-                precipitation_count += weight;
-            }
-            if let Some(val) = weather.visibility {
-                avg_visibility += val * weight;
-                visibility_count += weight;
+        if let Some(cached) = self.get_from_cache(&cache_key).await {
+            if let Ok(report) = serde_json::from_value::<AirQualityReport>(cached) {
+                return Ok(report);
             }
-            if let Some(val) = weather.uv_index {
-                avg_uv += val * weight;
-                uv_count += weight;
+        }
+        
+        self.coalesced(&cache_key, async {
+            let mut readings = Vec::new();
+            for provider in &self.air_quality {
+                if let Err(e) = provider_available(provider.name()) {
+                    if !self.fallback_enabled {
+                        return Err(e);
+                    }
+                    continue;
+                }
+                let reading = async {
+                    let start = Instant::now();
+                    let reading = provider.get_air_quality(location).await;
+                    record_provider_request(provider.name(), start, &reading);
+                    reading
+                }.instrument(provider_span(provider.name(), "air_quality")).await;
+                match reading {
+                    Ok(reading) => readings.push((provider.name().to_string(), reading)),
+                    Err(e) => {
+                        log::error!("Air quality provider {} failed: {:?}", provider.name(), e);
+                        if !self.fallback_enabled {
+                            return Err(e);
+                        }
+                    }
+                }
             }
             
-            descriptions.push(format!("{}: {}", name, weather.description));
-            
-            if location.is_none() {
-                location = Some(weather.location.clone());
+            let report = air_quality::combine(location, &readings)?;
+            if let Ok(json_value) = serde_json::to_value(&report) {
+                self.store_in_cache(&cache_key, json_value).await;
             }
+            Ok(report)
+        }).await
+    }
+    
+    /// The sources counting towards `field`: those that reported it and weren't rejected as
+    /// outliers, weighted for the field
+    fn field_sources(&self, field: &str, sources: &[SourceContribution]) -> Vec<SourceContribution> {
+        sources.iter()
+            .filter(|source| source.values.contains_key(field) && !source.rejected.iter().any(|rejected| rejected == field))
+            .map(|source| SourceContribution {
+                weight: source.weight * self.field_weights.weight(&source.provider, field),
+                ..source.clone()
+            })
+            .collect()
+    }
+    
+    /// Combines current conditions field by field following the fusion policy: each field is
+    /// the weighted average of the preferred sources (local sensors or regional providers) that
+    /// reported it, or of every source that did when none of the preferred ones did. Values
+    /// rejected as outliers are listed under each source's `rejected` and left out.
+    fn average_weather(&self, location: &str, weathers: Vec<(String, Weather)>) -> Result<Weather, WeatherError> {
+        if weathers.is_empty() {
+            return Err(WeatherError::NotFound("No weather data available from any provider".to_string()));
         }
         
+        let query = match LocationQuery::parse(location) {
+            Ok(LocationQuery::Coordinates { latitude, longitude }) => Some((latitude, longitude)),
+            _ => None,
+        };
+        let distances: Vec<Option<f64>> = weathers.iter()
+            .map(|(_, weather)| Some(distance_km(query?, weather.location.position()?)))
+            .collect();
+        // Only when every source was placed, otherwise the unplaced ones would count as on site
+        let distance_scale = self.distance_scale_km.filter(|_| distances.iter().all(Option::is_some));
+        
+        let now = safe_timestamp_with_fallback();
+        let mut sources: Vec<SourceContribution> = weathers.iter().zip(&distances)
+            .map(|((name, weather), distance)| {
+                let mut weight = *self.weights.get(name).unwrap_or(&1.0);
+                if let (Some(scale), Some(distance)) = (distance_scale, distance) {
+                    weight *= distance_factor(*distance, scale);
+                }
+                let mut source = source_contribution(name, weight, weather, now);
+                source.local = self.local.contains(name);
+                source.distance_km = *distance;
+                source
+            })
+            .collect();
+        for field in FusionPolicy::FIELDS {
+            let reporting: Vec<usize> = (0..sources.len()).filter(|&i| sources[i].values.contains_key(field)).collect();
+            let values: Vec<f64> = reporting.iter().map(|&i| sources[i].values[field]).collect();
+            for outlier in self.outliers.outliers(field, &values) {
+                let source = &mut sources[reporting[outlier]];
+                log::warn!("Leaving out {} {} of {} as an outlier", field, values[outlier], source.provider);
+                source.rejected.push(field.to_string());
+            }
+        }
+        let fused = |field: &str| self.fusion.fuse(field, &self.field_sources(field, &sources));
+        
+        let temperature = fused("temperature").map(Temperature::from_celsius)
+            .ok_or_else(|| WeatherError::NotFound("No temperature available from any provider".to_string()))?;
+        let humidity = fused("humidity");
+        let precipitation = fused("precipitation");
+        let wind_speed = fused("wind_speed").map(Speed::from_kmh);
+        let uv_index = fused("uv_index");
+        
+        let descriptions: Vec<String> = weathers.iter()
+            .map(|(name, weather)| format!("{}: {}", name, weather.description))
+            .collect();
+        
         Ok(Weather {
-            temperature: avg_temp / total_weight,
-            feels_like: if feels_like_count > 0.0 { Some(avg_feels_like / feels_like_count) } else { None },
-            humidity: if humidity_count > 0.0 { Some(avg_humidity / humidity_count) } else { None },
-            pressure: if pressure_count > 0.0 { Some(avg_pressure / pressure_count) } else { None },
-            wind_speed: if wind_speed_count > 0.0 { Some(avg_wind_speed / wind_speed_count) } else { None },
-            wind_direction: if wind_direction_count > 0.0 { Some(avg_wind_direction / wind_direction_count) } else { None },
+            temperature,
+            feels_like: fused("feels_like").map(Temperature::from_celsius),
+            humidity,
+            pressure: fused("pressure").map(Pressure::from_hpa),
+            wind_speed,
+            wind_direction: fused("wind_direction"),
             description: format!("Combined: {}", descriptions.join(" | ")),
+            condition: ConditionCode::consensus(weathers.iter().zip(&sources)
+                .map(|((_, weather), source)| (weather.condition, source.weight))),
             icon: None,
-            precipitation: if precipitation_count > 0.0 { Some(avg_precipitation / precipitation_count) } else { None },
-            visibility: if visibility_count > 0.0 { Some(avg_visibility / visibility_count) } else { None },
-            uv_index: if uv_count > 0.0 { Some(avg_uv / uv_count) } else { None },
+            precipitation,
+            visibility: fused("visibility"),
+            uv_index,
+            road_condition: Some(estimate_road_condition(temperature, humidity, precipitation, None)),
+            comfort: Some(comfort(temperature, humidity, wind_speed, uv_index)),
             provider: "Combo".to_string(),
-            location: location.unwrap_or_else(|| Location {
-                latitude: 0.0,
-                longitude: 0.0,
-                name: "Unknown".to_string(),
-                country: None,
-                region: None,
-                postal_code: None,
-            }),
-            timestamp: safe_timestamp_with_fallback(),
+            location: weathers[0].1.location.clone(),
+            timestamp: now,
+            // Angles wrap around, so wind direction has no spread
+            spread: FusionPolicy::FIELDS.iter()
+                .filter(|field| **field != "wind_direction")
+                .filter_map(|field| Some((field.to_string(), spread(field, &self.field_sources(field, &sources))?)))
+                .collect(),
+            sources,
         })
     }
     
@@ -209,19 +829,26 @@ impl ComboProvider {
                 
                 let mut avg = DailyForecast {
                     date,
-                    temperature_min: 0.0,
-                    temperature_max: 0.0,
+                    temperature_min: Temperature::default(),
+                    temperature_max: Temperature::default(),
                     humidity: None,
                     precipitation_probability: None,
                     precipitation_amount: None,
                     wind_speed: None,
                     wind_direction: None,
                     description: String::new(),
+                    condition: ConditionCode::consensus(provider_forecasts.iter()
+                        .map(|(name, forecast)| (forecast.condition, *self.weights.get(name).unwrap_or(&1.0)))),
                     icon: None,
                     sunrise: None,
                     sunset: None,
+                    solar_irradiance: None,
+                    day: None,
+                    night: None,
                 };
                 
+                let mut temperature_min_sum = 0.0;
+                let mut temperature_max_sum = 0.0;
                 let mut humidity_sum = 0.0;
                 let mut humidity_count = 0.0;
                 let mut precip_prob_sum = 0.0;
@@ -232,12 +859,14 @@ impl ComboProvider {
                 let mut wind_speed_count = 0.0;
                 let mut wind_dir_sum = 0.0;
                 let mut wind_dir_count = 0.0;
+                let mut solar_sum = 0.0;
+                let mut solar_count = 0.0;
                 
                 for (name, forecast) in &provider_forecasts {
                     let weight = self.weights.get(name).unwrap_or(&1.0);
                     
-                    avg.temperature_min += forecast.temperature_min * weight;
-                    avg.temperature_max += forecast.temperature_max * weight;
+                    temperature_min_sum += forecast.temperature_min.celsius() * weight;
+                    temperature_max_sum += forecast.temperature_max.celsius() * weight;
                     
                     if let Some(val) = forecast.humidity {
                         humidity_sum += val * weight;
@@ -252,13 +881,17 @@ impl ComboProvider {
                         precip_amt_count += weight;
                     }
                     if let Some(val) = forecast.wind_speed {
-                        wind_speed_sum += val * weight;
+                        wind_speed_sum += val.kmh() * weight;
                         wind_speed_count += weight;
                     }
                     if let Some(val) = forecast.wind_direction {
                         wind_dir_sum += val * weight;
                         wind_dir_count += weight;
                     }
+                    if let Some(val) = forecast.solar_irradiance {
+                        solar_sum += val * weight;
+                        solar_count += weight;
+                    }
                     
                     if avg.sunrise.is_none() {
                         avg.sunrise = forecast.sunrise.clone();
@@ -266,14 +899,20 @@ impl ComboProvider {
                     if avg.sunset.is_none() {
                         avg.sunset = forecast.sunset.clone();
                     }
+                    // Halves aren't averaged, they come from one provider
+                    if avg.day.is_none() && avg.night.is_none() {
+                        avg.day = forecast.day.clone();
+                        avg.night = forecast.night.clone();
+                    }
                 }
                 
-                avg.temperature_min /= total_weight;
-                avg.temperature_max /= total_weight;
+                avg.temperature_min = Temperature::from_celsius(temperature_min_sum / total_weight);
+                avg.temperature_max = Temperature::from_celsius(temperature_max_sum / total_weight);
                 avg.humidity = if humidity_count > 0.0 { Some(humidity_sum / humidity_count) } else { None };
                 avg.precipitation_probability = if precip_prob_count > 0.0 { Some(precip_prob_sum / precip_prob_count) } else { None };
                 avg.precipitation_amount = if precip_amt_count > 0.0 { Some(precip_amt_sum / precip_amt_count) } else { None };
-                avg.wind_speed = if wind_speed_count > 0.0 { Some(wind_speed_sum / wind_speed_count) } else { None };
+                avg.wind_speed = if wind_speed_count > 0.0 { Some(Speed::from_kmh(wind_speed_sum / wind_speed_count)) } else { None };
+                avg.solar_irradiance = if solar_count > 0.0 { Some(solar_sum / solar_count) } else { None };
                 avg.wind_direction = if wind_dir_count > 0.0 { Some(wind_dir_sum / wind_dir_count) } else { None };
                 avg.description = "Combined forecast".to_string();
                 
@@ -292,7 +931,7 @@ impl ComboProvider {
                     
                     let mut avg = HourlyForecast {
                         datetime,
-                        temperature: 0.0,
+                        temperature: Temperature::default(),
                         feels_like: None,
                         humidity: None,
                         precipitation_probability: None,
@@ -300,14 +939,27 @@ impl ComboProvider {
                         wind_speed: None,
                         wind_direction: None,
                         description: "Combined".to_string(),
+                        condition: ConditionCode::consensus(provider_forecasts.iter()
+                            .map(|(name, forecast)| (forecast.condition, *self.weights.get(name).unwrap_or(&1.0)))),
                         icon: None,
+                        cloud_cover: None,
+                        solar_irradiance: None,
                     };
                     
-                    for (name, forecast) in &provider_forecasts {
-                        let weight = self.weights.get(name).unwrap_or(&1.0);
-                        avg.temperature += forecast.temperature * weight;
-                    }
-                    avg.temperature /= total_weight;
+                    let temperature_sum: f64 = provider_forecasts.iter()
+                        .map(|(name, forecast)| forecast.temperature.celsius() * self.weights.get(name).unwrap_or(&1.0))
+                        .sum();
+                    avg.temperature = Temperature::from_celsius(temperature_sum / total_weight);
+                    
+                    // Weighted over the providers that have them
+                    let average = |value: fn(&HourlyForecast) -> Option<f64>| {
+                        let (sum, weight) = provider_forecasts.iter()
+                            .filter_map(|(name, forecast)| value(forecast).map(|v| (v, *self.weights.get(name).unwrap_or(&1.0))))
+                            .fold((0.0, 0.0), |(sum, total), (v, weight)| (sum + v * weight, total + weight));
+                        if weight > 0.0 { Some(sum / weight) } else { None }
+                    };
+                    avg.cloud_cover = average(|h| h.cloud_cover);
+                    avg.solar_irradiance = average(|h| h.solar_irradiance);
                     
                     avg
                 })
@@ -334,6 +986,43 @@ impl ComboProvider {
         })
     }
     
+    // Concentrations are weight-averaged, while the category follows the worst reporting
+    // provider so allergy sufferers are never told a day is milder than one source claims.
+    fn merge_pollen(&self, reports: Vec<(String, PollenReport)>) -> Result<PollenReport, WeatherError> {
+        let first = reports.first()
+            .ok_or_else(|| WeatherError::NotFound("No pollen data available from any provider".to_string()))?;
+        
+        let mut merged = PollenReport::new(first.1.location.clone(), "Combo".to_string(), first.1.date.clone());
+        
+        for pollen_type in PollenType::all().iter() {
+            let mut value_sum = 0.0;
+            let mut weight_sum = 0.0;
+            let mut worst: Option<&PollenLevel> = None;
+            
+            for (name, report) in &reports {
+                if let Some(level) = report.level(*pollen_type) {
+                    let weight = self.weights.get(name).unwrap_or(&1.0);
+                    value_sum += level.value * weight;
+                    weight_sum += weight;
+                    
+                    if worst.is_none_or(|w| level.category_value > w.category_value) {
+                        worst = Some(level);
+                    }
+                }
+            }
+            
+            if let Some(worst) = worst {
+                merged.set_level(*pollen_type, PollenLevel {
+                    value: if weight_sum > 0.0 { value_sum / weight_sum } else { worst.value },
+                    category: worst.category.clone(),
+                    category_value: worst.category_value,
+                });
+            }
+        }
+        
+        Ok(merged)
+    }
+    
     fn merge_alerts(&self, alerts_list: Vec<(String, Vec<Alert>)>) -> Vec<Alert> {
         let mut all_alerts = Vec::new();
         let mut seen_alerts = std::collections::HashSet::new();
@@ -365,29 +1054,34 @@ impl WeatherProvider for ComboProvider {
             }
         }
         
-        let mut results = Vec::new();
-        for provider in &self.providers {
-            let provider_name = provider.name().to_string();
-            match provider.get_current_weather(location).await {
-                Ok(data) => {
-                    results.push((provider_name, data));
-                    if !self.fallback_enabled {
-                        break;
+        self.coalesced(&cache_key, async {
+            let mut results = Vec::new();
+            for provider in &self.providers {
+                let provider_name = provider.name().to_string();
+                match provider.get_current_weather(location).await {
+                    Ok(data) => {
+                        results.push((provider_name, data));
+                        if !self.fallback_enabled {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        log::error!("Provider {} failed: {:?}", provider_name, e);
                     }
-                }
-                Err(e) => {
-                    log::error!("Provider {} failed: {:?}", provider_name, e);
                 }
             }
-        }
-        
-        let weather = self.average_weather(results)?;
-        
-        if let Ok(json_value) = serde_json::to_value(&weather) {
-            self.store_in_cache(&cache_key, json_value).await;
-        }
-        
-        Ok(weather)
+            
+            let weather = match self.average_weather(location, results) {
+                Ok(weather) => weather,
+                Err(e) => return self.get_stale(&cache_key).await.ok_or(e),
+            };
+            
+            if let Ok(json_value) = serde_json::to_value(&weather) {
+                self.store_in_cache(&cache_key, json_value).await;
+            }
+            
+            Ok(weather)
+        }).await
     }
     
     async fn get_forecast(&self, location: &str, days: u8) -> Result<Forecast, WeatherError> {
@@ -399,31 +1093,36 @@ impl WeatherProvider for ComboProvider {
             }
         }
         
-        let mut results = Vec::new();
-        for provider in &self.providers {
-            if provider.supports_feature(WeatherFeature::Forecast) {
-                let provider_name = provider.name().to_string();
-                match provider.get_forecast(location, days).await {
-                    Ok(data) => {
-                        results.push((provider_name, data));
-                        if !self.fallback_enabled {
-                            break;
+        self.coalesced(&cache_key, async {
+            let mut results = Vec::new();
+            for provider in &self.providers {
+                if provider.supports_feature(WeatherFeature::Forecast) {
+                    let provider_name = provider.name().to_string();
+                    match provider.get_forecast(location, days).await {
+                        Ok(data) => {
+                            results.push((provider_name, data));
+                            if !self.fallback_enabled {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Provider {} failed: {:?}", provider_name, e);
                         }
-                    }
-                    Err(e) => {
-                        log::error!("Provider {} failed: {:?}", provider_name, e);
                     }
                 }
             }
-        }
-        
-        let forecast = self.combine_forecasts(results)?;
-        
-        if let Ok(json_value) = serde_json::to_value(&forecast) {
-            self.store_in_cache(&cache_key, json_value).await;
-        }
-        
-        Ok(forecast)
+            
+            let forecast = match self.combine_forecasts(results) {
+                Ok(forecast) => forecast,
+                Err(e) => return self.get_stale(&cache_key).await.ok_or(e),
+            };
+            
+            if let Ok(json_value) = serde_json::to_value(&forecast) {
+                self.store_in_cache(&cache_key, json_value).await;
+            }
+            
+            Ok(forecast)
+        }).await
     }
     
     async fn get_alerts(&self, location: &str) -> Result<Vec<Alert>, WeatherError> {
@@ -435,28 +1134,30 @@ impl WeatherProvider for ComboProvider {
             }
         }
         
-        let mut results = Vec::new();
-        for provider in &self.providers {
-            if provider.supports_feature(WeatherFeature::Alerts) {
-                let provider_name = provider.name().to_string();
-                match provider.get_alerts(location).await {
-                    Ok(data) => {
-                        results.push((provider_name, data));
-                    }
-                    Err(e) => {
-                        log::error!("Provider {} failed: {:?}", provider_name, e);
+        self.coalesced(&cache_key, async {
+            let mut results = Vec::new();
+            for provider in &self.providers {
+                if provider.supports_feature(WeatherFeature::Alerts) {
+                    let provider_name = provider.name().to_string();
+                    match provider.get_alerts(location).await {
+                        Ok(data) => {
+                            results.push((provider_name, data));
+                        }
+                        Err(e) => {
+                            log::error!("Provider {} failed: {:?}", provider_name, e);
+                        }
                     }
                 }
             }
-        }
-        
-        let alerts = self.merge_alerts(results);
-        
-        if let Ok(json_value) = serde_json::to_value(&alerts) {
-            self.store_in_cache(&cache_key, json_value).await;
-        }
-        
-        Ok(alerts)
+            
+            let alerts = self.merge_alerts(results);
+            
+            if let Ok(json_value) = serde_json::to_value(&alerts) {
+                self.store_in_cache(&cache_key, json_value).await;
+            }
+            
+            Ok(alerts)
+        }).await
     }
     
     async fn get_historical(&self, location: &str, date: &str) -> Result<HistoricalData, WeatherError> {
@@ -487,6 +1188,112 @@ impl WeatherProvider for ComboProvider {
         Ok(first.1.clone())
     }
     
+    async fn get_pollen(&self, location: &str) -> Result<PollenReport, WeatherError> {
+        let cache_key = format!("pollen:{}", location);
+        
+        if let Some(cached) = self.get_from_cache(&cache_key).await {
+            if let Ok(report) = serde_json::from_value::<PollenReport>(cached) {
+                return Ok(report);
+            }
+        }
+        
+        self.coalesced(&cache_key, async {
+            let mut results = Vec::new();
+            for provider in &self.providers {
+                if provider.supports_feature(WeatherFeature::Pollen) {
+                    let provider_name = provider.name().to_string();
+                    match provider.get_pollen(location).await {
+                        Ok(data) => {
+                            results.push((provider_name, data));
+                            if !self.fallback_enabled {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!("Provider {} failed: {:?}", provider_name, e);
+                        }
+                    }
+                }
+            }
+            
+            let report = self.merge_pollen(results)?;
+            
+            if let Ok(json_value) = serde_json::to_value(&report) {
+                self.store_in_cache(&cache_key, json_value).await;
+            }
+            
+            Ok(report)
+        }).await
+    }
+    
+    // Marine data is tied to specific stations, so the first provider that has it wins
+    async fn get_marine(&self, location: &str) -> Result<MarineForecast, WeatherError> {
+        let cache_key = format!("marine:{}", location);
+        
+        if let Some(cached) = self.get_from_cache(&cache_key).await {
+            if let Ok(forecast) = serde_json::from_value::<MarineForecast>(cached) {
+                return Ok(forecast);
+            }
+        }
+        
+        self.coalesced(&cache_key, async {
+            for provider in &self.providers {
+                if provider.supports_feature(WeatherFeature::Marine) {
+                    match provider.get_marine(location).await {
+                        Ok(forecast) => {
+                            if let Ok(json_value) = serde_json::to_value(&forecast) {
+                                self.store_in_cache(&cache_key, json_value).await;
+                            }
+                            return Ok(forecast);
+                        }
+                        Err(e) => {
+                            log::error!("Provider {} failed: {:?}", provider.name(), e);
+                            if !self.fallback_enabled {
+                                return Err(e);
+                            }
+                        }
+                    }
+                }
+            }
+            
+            Err(WeatherError::NotFound("No marine data available from any provider".to_string()))
+        }).await
+    }
+    
+    // Minute-level series from different models don't line up, so the first provider wins
+    async fn get_minutely(&self, location: &str) -> Result<MinutelyForecast, WeatherError> {
+        let cache_key = format!("minutely:{}", location);
+        
+        if let Some(cached) = self.get_from_cache(&cache_key).await {
+            if let Ok(forecast) = serde_json::from_value::<MinutelyForecast>(cached) {
+                return Ok(forecast);
+            }
+        }
+        
+        self.coalesced(&cache_key, async {
+            for provider in &self.providers {
+                if provider.supports_feature(WeatherFeature::MinutelyForecast) {
+                    match provider.get_minutely(location).await {
+                        Ok(forecast) => {
+                            if let Ok(json_value) = serde_json::to_value(&forecast) {
+                                self.store_in_cache(&cache_key, json_value).await;
+                            }
+                            return Ok(forecast);
+                        }
+                        Err(e) => {
+                            log::error!("Provider {} failed: {:?}", provider.name(), e);
+                            if !self.fallback_enabled {
+                                return Err(e);
+                            }
+                        }
+                    }
+                }
+            }
+            
+            Err(WeatherError::NotFound("No minute-level forecast available from any provider".to_string()))
+        }).await
+    }
+    
     fn name(&self) -> &str {
         "Combo"
     }
@@ -497,36 +1304,14 @@ impl WeatherProvider for ComboProvider {
 }
 
 
-struct WeatherCache {
-    data: HashMap<String, CacheEntry>,
-}
-
-struct CacheEntry {
-    value: serde_json::Value,
-    timestamp: u64,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheFreshness {
+    pub age: u64,       // Seconds since the entry was stored
+    pub ttl: u64,       // Lifetime of the entry in seconds
 }
 
-impl WeatherCache {
-    fn new() -> Self {
-        Self {
-            data: HashMap::new(),
-        }
+impl CacheFreshness {
+    pub fn remaining(&self) -> u64 {
+        self.ttl.saturating_sub(self.age)
     }
-    
-    fn get(&self, key: &str, ttl_secs: u64) -> Option<serde_json::Value> {
-        let now = safe_timestamp_with_fallback() as u64;
-        
-        self.data.get(key).and_then(|entry| {
-            if now - entry.timestamp < ttl_secs {
-                Some(entry.value.clone())
-            } else {
-                None
-            }
-        })
-    }
-    
-    fn set(&mut self, key: String, value: serde_json::Value) {
-        let timestamp = safe_timestamp_with_fallback() as u64;
-        self.data.insert(key, CacheEntry { value, timestamp });
-    }
-}
\ No newline at end of file
+}
@@ -1,9 +1,14 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
+use crate::analysis::comfort::Comfort;
+use crate::analysis::conditions::ConditionCode;
+use crate::analysis::road::RoadCondition;
+use crate::units::{Pressure, Speed, Temperature};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum WeatherError {
     NetworkError(String),
     ParseError(String),
@@ -32,7 +37,8 @@ impl Error for WeatherError {}
 
 impl From<reqwest::Error> for WeatherError {
     fn from(err: reqwest::Error) -> Self {
-        WeatherError::NetworkError(err.to_string())
+        // The URL carries the API key in its query string
+        WeatherError::NetworkError(err.without_url().to_string())
     }
 }
 
@@ -44,20 +50,46 @@ impl From<serde_json::Error> for WeatherError {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Weather {
-    pub temperature: f64,
-    pub feels_like: Option<f64>,
+    pub temperature: Temperature,
+    pub feels_like: Option<Temperature>,
     pub humidity: Option<f64>,
-    pub pressure: Option<f64>,
-    pub wind_speed: Option<f64>,
+    pub pressure: Option<Pressure>,
+    pub wind_speed: Option<Speed>,
     pub wind_direction: Option<f64>,
     pub description: String,
+    #[serde(default)]
+    pub condition: ConditionCode,                // Normalised code for `description`
     pub icon: Option<String>,
     pub precipitation: Option<f64>,
     pub visibility: Option<f64>,
     pub uv_index: Option<f64>,
+    #[serde(default)]
+    pub road_condition: Option<RoadCondition>,   // Estimated frost/black-ice risk on nearby roads
+    #[serde(default)]
+    pub comfort: Option<Comfort>,                // Comfort score and clothing recommendation
     pub provider: String,
     pub location: Location,
     pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<SourceContribution>,        // Provider readings a combined value was averaged from
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub spread: BTreeMap<String, f64>,           // Weighted standard deviation per field with 2+ sources
+}
+
+/// One provider's part in a combined reading: its raw value per field ("temperature",
+/// "humidity", ...), its averaging weight and how old its data was when combined
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourceContribution {
+    pub provider: String,
+    #[serde(default)]
+    pub local: bool,        // A nearby homebrew sensor station rather than a regional provider
+    pub weight: f64,        // Configured weight, scaled down with distance when that's known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance_km: Option<f64>,   // From the queried coordinates to where the data was measured
+    pub age_secs: i64,
+    pub values: BTreeMap<String, f64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rejected: Vec<String>,  // Fields whose value was left out as an outlier
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,6 +102,99 @@ pub struct Location {
     pub postal_code: Option<String>,
 }
 
+impl Location {
+    /// Latitude and longitude, unless the provider left them at 0,0 for unknown
+    pub fn position(&self) -> Option<(f64, f64)> {
+        (self.latitude != 0.0 || self.longitude != 0.0).then_some((self.latitude, self.longitude))
+    }
+}
+
+/// Great-circle distance in km between two latitude,longitude points
+pub fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6371.0;
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.1 - from.1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// A configured location (LOCATION, or the older ZIP_CODE) as understood by provider geocoding:
+/// "12345" (US ZIP), "CA:V6B1A1" (postal code with ISO country), "Vancouver" or
+/// "Vancouver,CA" (city with optional country), or "49.28,-123.12" (latitude,longitude)
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocationQuery {
+    PostalCode { country: Option<String>, code: String },
+    City { name: String, country: Option<String> },
+    Coordinates { latitude: f64, longitude: f64 },
+}
+
+fn country_code(code: &str) -> Option<String> {
+    let code = code.trim();
+    if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(code.to_ascii_uppercase())
+    } else {
+        None
+    }
+}
+
+impl LocationQuery {
+    pub fn parse(location: &str) -> Result<Self, String> {
+        let location = location.trim();
+
+        if let Some((lat, lon)) = location.split_once(',') {
+            if let (Ok(latitude), Ok(longitude)) = (lat.trim().parse::<f64>(), lon.trim().parse::<f64>()) {
+                if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                    return Err(format!("coordinates out of range: {}", location));
+                }
+                return Ok(LocationQuery::Coordinates { latitude, longitude });
+            }
+        }
+
+        if let Some((country, code)) = location.split_once(':') {
+            let country = country_code(country)
+                .ok_or_else(|| format!("expected a two-letter country code before ':', got {}", location))?;
+            let code = code.trim();
+            let valid = (2..=10).contains(&code.len())
+                && code.chars().any(|c| c.is_ascii_digit() || c.is_ascii_alphabetic())
+                && code.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-');
+            if !valid {
+                return Err(format!("invalid postal code for {}: {}", country, code));
+            }
+            return Ok(LocationQuery::PostalCode { country: Some(country), code: code.to_ascii_uppercase() });
+        }
+
+        if location.chars().all(|c| c.is_ascii_digit()) {
+            if location.len() != 5 {
+                return Err(format!("a postal code without a country must be a 5-digit US ZIP code (use e.g. CA:V6B1A1 elsewhere), got {}", location));
+            }
+            return Ok(LocationQuery::PostalCode { country: None, code: location.to_string() });
+        }
+
+        let (name, country) = match location.rsplit_once(',') {
+            Some((name, country)) => match country_code(country) {
+                Some(country) => (name.trim(), Some(country)),
+                None => return Err(format!("expected a two-letter country code after ',', got {}", location)),
+            },
+            None => (location, None),
+        };
+        let valid = name.chars().any(|c| c.is_alphabetic())
+            && name.chars().all(|c| c.is_alphabetic() || " -'.".contains(c));
+        if !valid {
+            return Err(format!("not a ZIP code, country:postal code, city or latitude,longitude: {}", location));
+        }
+        Ok(LocationQuery::City { name: name.to_string(), country })
+    }
+
+    /// ISO 3166 alpha-2 country, if the location names one
+    pub fn country(&self) -> Option<&str> {
+        match self {
+            LocationQuery::PostalCode { country, .. } | LocationQuery::City { country, .. } => country.as_deref(),
+            LocationQuery::Coordinates { .. } => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Forecast {
     pub location: Location,
@@ -81,31 +206,66 @@ pub struct Forecast {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DailyForecast {
     pub date: String,
-    pub temperature_min: f64,
-    pub temperature_max: f64,
+    pub temperature_min: Temperature,
+    pub temperature_max: Temperature,
     pub humidity: Option<f64>,
     pub precipitation_probability: Option<f64>,
     pub precipitation_amount: Option<f64>,
-    pub wind_speed: Option<f64>,
+    pub wind_speed: Option<Speed>,
     pub wind_direction: Option<f64>,
     pub description: String,
+    #[serde(default)]
+    pub condition: ConditionCode,
     pub icon: Option<String>,
     pub sunrise: Option<String>,
     pub sunset: Option<String>,
+    /// Solar energy reaching the ground over the day in Wh/m², for evapotranspiration and PV
+    /// estimates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solar_irradiance: Option<f64>,
+    /// Daytime and overnight halves, from providers that forecast them separately
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<DayPartForecast>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub night: Option<DayPartForecast>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DayPartForecast {
+    pub description: String,
+    #[serde(default)]
+    pub condition: ConditionCode,
+    pub icon: Option<String>,
+    pub precipitation_probability: Option<f64>,
+    pub precipitation_amount: Option<f64>,
+    /// Percent
+    pub cloud_cover: Option<f64>,
+    pub wind_speed: Option<Speed>,
+    pub wind_direction: Option<f64>,
+    /// Wh/m² over the half
+    pub solar_irradiance: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HourlyForecast {
     pub datetime: String,
-    pub temperature: f64,
-    pub feels_like: Option<f64>,
+    pub temperature: Temperature,
+    pub feels_like: Option<Temperature>,
     pub humidity: Option<f64>,
     pub precipitation_probability: Option<f64>,
     pub precipitation_amount: Option<f64>,
-    pub wind_speed: Option<f64>,
+    pub wind_speed: Option<Speed>,
     pub wind_direction: Option<f64>,
     pub description: String,
+    #[serde(default)]
+    pub condition: ConditionCode,
     pub icon: Option<String>,
+    /// Percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloud_cover: Option<f64>,
+    /// Average global horizontal irradiance over the hour in W/m²
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub solar_irradiance: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -139,6 +299,141 @@ pub struct HistoricalData {
     pub wind_speed_avg: Option<f64>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PollenType {
+    Tree,
+    Grass,
+    Ragweed,
+    Mold,
+}
+
+impl PollenType {
+    pub fn all() -> [PollenType; 4] {
+        [PollenType::Tree, PollenType::Grass, PollenType::Ragweed, PollenType::Mold]
+    }
+
+    /// Maps a provider allergen name (e.g. AccuWeather's "Tree", "Ragweed") to a pollen type
+    pub fn from_name(name: &str) -> Option<PollenType> {
+        match name.trim().to_lowercase().as_str() {
+            "tree" | "trees" => Some(PollenType::Tree),
+            "grass" | "grasses" => Some(PollenType::Grass),
+            "ragweed" | "weed" | "weeds" => Some(PollenType::Ragweed),
+            "mold" | "mould" => Some(PollenType::Mold),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PollenLevel {
+    pub value: f64,              // Concentration as reported by the provider (grains or spores per m³)
+    pub category: String,        // Human readable category, e.g. "Low", "High"
+    pub category_value: f64,     // Ordinal category, 1 (low) to 6 (extreme)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollenReport {
+    pub location: Location,
+    pub provider: String,
+    pub date: String,
+    pub tree: Option<PollenLevel>,
+    pub grass: Option<PollenLevel>,
+    pub ragweed: Option<PollenLevel>,
+    pub mold: Option<PollenLevel>,
+}
+
+impl PollenReport {
+    pub fn new(location: Location, provider: String, date: String) -> Self {
+        Self {
+            location,
+            provider,
+            date,
+            tree: None,
+            grass: None,
+            ragweed: None,
+            mold: None,
+        }
+    }
+
+    pub fn level(&self, pollen_type: PollenType) -> Option<&PollenLevel> {
+        match pollen_type {
+            PollenType::Tree => self.tree.as_ref(),
+            PollenType::Grass => self.grass.as_ref(),
+            PollenType::Ragweed => self.ragweed.as_ref(),
+            PollenType::Mold => self.mold.as_ref(),
+        }
+    }
+
+    pub fn set_level(&mut self, pollen_type: PollenType, level: PollenLevel) {
+        match pollen_type {
+            PollenType::Tree => self.tree = Some(level),
+            PollenType::Grass => self.grass = Some(level),
+            PollenType::Ragweed => self.ragweed = Some(level),
+            PollenType::Mold => self.mold = Some(level),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        PollenType::all().iter().all(|t| self.level(*t).is_none())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TideType {
+    High,
+    Low,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TidePrediction {
+    pub time: String,            // Local station time, e.g. "2024-05-01 04:12"
+    pub height: f64,             // Height in meters above MLLW
+    pub tide_type: TideType,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarineForecast {
+    pub location: Location,
+    pub provider: String,
+    pub marine_zone: Option<String>,         // Provider marine zone/station code, e.g. AccuWeather's MarineStation
+    pub tide_station: Option<String>,        // NOAA CO-OPS station id
+    pub buoy_station: Option<String>,        // NOAA NDBC buoy id
+    pub wave_height: Option<f64>,            // Significant wave height in meters
+    pub wave_period: Option<f64>,            // Dominant wave period in seconds
+    pub swell_height: Option<f64>,           // Swell height in meters
+    pub swell_period: Option<f64>,           // Swell period in seconds
+    pub swell_direction: Option<f64>,        // Direction swell is coming from in degrees
+    pub water_temperature: Option<f64>,      // Water temperature in Celsius
+    pub tides: Vec<TidePrediction>,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrecipitationType {
+    Rain,
+    Snow,
+    Sleet,
+    FreezingRain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinutePrecipitation {
+    pub timestamp: i64,
+    pub intensity: f64,                              // mm/h, all precipitation types together
+    pub probability: Option<f64>,                    // Percent
+    pub precipitation_type: Option<PrecipitationType>,  // None when dry
+}
+
+/// Precipitation for the next hour or so, one entry per minute
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinutelyForecast {
+    pub location: Location,
+    pub provider: String,
+    pub minutes: Vec<MinutePrecipitation>,
+    pub timestamp: i64,
+}
+
 #[async_trait]
 pub trait WeatherProvider: Send + Sync {
     async fn get_current_weather(&self, location: &str) -> Result<Weather, WeatherError>;
@@ -151,6 +446,18 @@ pub trait WeatherProvider: Send + Sync {
         Err(WeatherError::NotFound("Historical data not supported by this provider".to_string()))
     }
     
+    async fn get_pollen(&self, _location: &str) -> Result<PollenReport, WeatherError> {
+        Err(WeatherError::NotFound("Pollen data not supported by this provider".to_string()))
+    }
+    
+    async fn get_marine(&self, _location: &str) -> Result<MarineForecast, WeatherError> {
+        Err(WeatherError::NotFound("Marine data not supported by this provider".to_string()))
+    }
+    
+    async fn get_minutely(&self, _location: &str) -> Result<MinutelyForecast, WeatherError> {
+        Err(WeatherError::NotFound("Minute-level forecasts not supported by this provider".to_string()))
+    }
+    
     fn name(&self) -> &str;
     
     fn supports_feature(&self, feature: WeatherFeature) -> bool;
@@ -165,6 +472,40 @@ pub enum WeatherFeature {
     HourlyForecast,
     UvIndex,
     AirQuality,
+    Pollen,
+    Marine,
+    MinutelyForecast,
+}
+
+impl WeatherFeature {
+    pub const ALL: [WeatherFeature; 10] = [
+        WeatherFeature::CurrentWeather,
+        WeatherFeature::Forecast,
+        WeatherFeature::HourlyForecast,
+        WeatherFeature::MinutelyForecast,
+        WeatherFeature::Alerts,
+        WeatherFeature::HistoricalData,
+        WeatherFeature::UvIndex,
+        WeatherFeature::AirQuality,
+        WeatherFeature::Pollen,
+        WeatherFeature::Marine,
+    ];
+
+    /// Name in GET /api/capabilities
+    pub fn name(&self) -> &'static str {
+        match self {
+            WeatherFeature::CurrentWeather => "current",
+            WeatherFeature::Forecast => "forecast",
+            WeatherFeature::HourlyForecast => "hourly_forecast",
+            WeatherFeature::MinutelyForecast => "minutely_forecast",
+            WeatherFeature::Alerts => "alerts",
+            WeatherFeature::HistoricalData => "historical",
+            WeatherFeature::UvIndex => "uv_index",
+            WeatherFeature::AirQuality => "air_quality",
+            WeatherFeature::Pollen => "pollen",
+            WeatherFeature::Marine => "marine",
+        }
+    }
 }
 
 pub struct RateLimiter {
@@ -0,0 +1,275 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::common::WeatherError;
+use crate::metrics::{self, Kind};
+
+// Circuit breakers for weather providers. Every provider call's outcome and latency is
+// recorded; after CIRCUIT_FAILURES failures in a row (5 by default) the provider's circuit opens
+// and ComboProvider skips it, combining the other providers, for CIRCUIT_BACKOFF_SECS (30 by
+// default). Then a single trial call goes through: an answer closes the circuit, another
+// failure opens it for twice as long, up to CIRCUIT_MAX_BACKOFF_SECS (600 by default).
+// CIRCUIT_FAILURES=0 turns the breakers off.
+//
+// Network, parse, rate limit, API key and database errors are failures. A provider that
+// doesn't know a location or support a feature still answered, so that counts as a success.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakerConfig {
+    pub failures: u32,             // In a row before the circuit opens, 0 for never
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self { failures: 5, backoff: Duration::from_secs(30), max_backoff: Duration::from_secs(600) }
+    }
+}
+
+impl BreakerConfig {
+    /// Reads the settings through `var`; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let defaults = Self::default();
+        let mut problems = Vec::new();
+        let mut number = |setting: &'static str, default: u64, min: u64| match var(setting).map(|v| v.parse::<u64>()) {
+            None => default,
+            Some(Ok(value)) if value >= min => value,
+            Some(_) => {
+                problems.push((setting, format!("must be a whole number, at least {}", min)));
+                default
+            },
+        };
+        let config = Self {
+            failures: number("CIRCUIT_FAILURES", defaults.failures.into(), 0).min(u32::MAX.into()) as u32,
+            backoff: Duration::from_secs(number("CIRCUIT_BACKOFF_SECS", defaults.backoff.as_secs(), 1)),
+            max_backoff: Duration::from_secs(number("CIRCUIT_MAX_BACKOFF_SECS", defaults.max_backoff.as_secs(), 1)),
+        };
+        if problems.is_empty() && config.max_backoff < config.backoff {
+            problems.push(("CIRCUIT_MAX_BACKOFF_SECS", format!("must be at least CIRCUIT_BACKOFF_SECS ({})", config.backoff.as_secs())));
+        }
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.failures > 0
+    }
+}
+
+pub static BREAKER_CONFIG: Lazy<BreakerConfig> = Lazy::new(|| {
+    BreakerConfig::from_env().unwrap_or_else(|problems| {
+        for (setting, message) in problems {
+            log::error!("Invalid {}, using the default circuit breakers: {}", setting, message);
+        }
+        BreakerConfig::default()
+    })
+});
+
+/// Whether `error` means the provider is unhealthy rather than unable to answer the request
+pub fn is_failure(error: &WeatherError) -> bool {
+    !matches!(error, WeatherError::NotFound(_) | WeatherError::ConfigurationError(_))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,      // Backoff over, waiting for the trial call
+}
+
+/// One provider's calls since startup and its circuit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CircuitStatus {
+    pub state: CircuitState,
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub skipped: u64,
+    pub last_latency_ms: Option<u64>,
+    pub last_error: Option<String>,
+    pub retry_in_secs: Option<u64>,    // While open
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    successes: u64,
+    failures: u64,
+    consecutive_failures: u32,
+    skipped: u64,
+    last_latency: Option<Duration>,
+    last_error: Option<String>,
+    open_until: Option<Instant>,
+    backoff: Duration,
+    trial: bool,       // The trial call of a half-open circuit is under way
+}
+
+impl Breaker {
+    fn state(&self, now: Instant) -> CircuitState {
+        match self.open_until {
+            None => CircuitState::Closed,
+            Some(until) if now < until => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Whether a call may go through, letting one through per half-open circuit
+    fn allow(&mut self, now: Instant) -> bool {
+        let allowed = match self.state(now) {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => !std::mem::replace(&mut self.trial, true),
+        };
+        if !allowed {
+            self.skipped += 1;
+        }
+        allowed
+    }
+
+    /// Records a call; returns the new state when the circuit opened or closed
+    fn record(&mut self, latency: Duration, error: Option<&WeatherError>, config: &BreakerConfig, now: Instant) -> Option<CircuitState> {
+        self.last_latency = Some(latency);
+        let Some(error) = error.filter(|error| is_failure(error)) else {
+            self.successes += 1;
+            self.consecutive_failures = 0;
+            self.trial = false;
+            self.backoff = Duration::ZERO;
+            return self.open_until.take().map(|_| CircuitState::Closed);
+        };
+        self.failures += 1;
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.last_error = Some(error.to_string());
+        let backoff = match self.state(now) {
+            CircuitState::Closed if config.enabled() && self.consecutive_failures >= config.failures => config.backoff,
+            CircuitState::HalfOpen if self.trial => (self.backoff * 2).min(config.max_backoff),
+            // Calls that started before the circuit opened
+            _ => return None,
+        };
+        self.trial = false;
+        self.backoff = backoff;
+        self.open_until = Some(now + backoff);
+        Some(CircuitState::Open)
+    }
+
+    fn status(&self, now: Instant) -> CircuitStatus {
+        let state = self.state(now);
+        CircuitStatus {
+            state,
+            successes: self.successes,
+            failures: self.failures,
+            consecutive_failures: self.consecutive_failures,
+            skipped: self.skipped,
+            last_latency_ms: self.last_latency.map(|latency| latency.as_millis() as u64),
+            last_error: self.last_error.clone(),
+            retry_in_secs: self.open_until.filter(|_| state == CircuitState::Open)
+                .map(|until| until.saturating_duration_since(now).as_secs()),
+        }
+    }
+}
+
+static BREAKERS: Lazy<Mutex<HashMap<String, Breaker>>> = Lazy::new(|| {
+    metrics::collector(Kind::Gauge, "jupiter_provider_circuit_open", "1 while a provider is skipped after repeated failures", &["provider"], || {
+        let now = Instant::now();
+        BREAKERS.lock().map(|breakers| breakers.iter()
+            .map(|(provider, breaker)| (vec![provider.clone()], if breaker.state(now) == CircuitState::Closed { 0.0 } else { 1.0 }))
+            .collect())
+            .unwrap_or_default()
+    });
+    Mutex::new(HashMap::new())
+});
+
+/// Whether to call `provider` now; false while its circuit is open
+pub fn allow(provider: &str) -> bool {
+    if !BREAKER_CONFIG.enabled() {
+        return true;
+    }
+    let Ok(mut breakers) = BREAKERS.lock() else { return true };
+    breakers.entry(provider.to_string()).or_default().allow(Instant::now())
+}
+
+/// Records a call to `provider` that took `latency`, logging when its circuit opens or closes
+pub fn record(provider: &str, latency: Duration, error: Option<&WeatherError>) {
+    let config = &*BREAKER_CONFIG;
+    let Ok(mut breakers) = BREAKERS.lock() else { return };
+    let breaker = breakers.entry(provider.to_string()).or_default();
+    match breaker.record(latency, error, config, Instant::now()) {
+        Some(CircuitState::Open) => log::warn!("[health] {} failed {} time(s) in a row ({}), skipping it for {} s",
+            provider, breaker.consecutive_failures, breaker.last_error.as_deref().unwrap_or_default(), breaker.backoff.as_secs()),
+        Some(_) => log::warn!("[health] {} answered again, calling it again", provider),
+        None => {},
+    }
+}
+
+/// The circuit of `provider`, if it was called since startup
+pub fn status(provider: &str) -> Option<CircuitStatus> {
+    BREAKERS.lock().ok()?.get(provider).map(|breaker| breaker.status(Instant::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap as Vars;
+
+    #[test]
+    fn test_breaker_config() {
+        let config = |vars: &[(&str, &str)]| {
+            let vars: Vars<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            BreakerConfig::from_vars(|name| vars.get(name).cloned())
+        };
+        assert_eq!(config(&[]).unwrap(), BreakerConfig::default());
+        assert!(!config(&[("CIRCUIT_FAILURES", "0")]).unwrap().enabled());
+        assert_eq!(config(&[("CIRCUIT_BACKOFF_SECS", "0")]).unwrap_err()[0].0, "CIRCUIT_BACKOFF_SECS");
+        assert_eq!(config(&[("CIRCUIT_BACKOFF_SECS", "900")]).unwrap_err()[0].0, "CIRCUIT_MAX_BACKOFF_SECS");
+    }
+
+    #[test]
+    fn test_breaker() {
+        let config = BreakerConfig { failures: 3, backoff: Duration::from_secs(30), max_backoff: Duration::from_secs(50) };
+        let timeout = WeatherError::NetworkError("timed out".to_string());
+        let latency = Duration::from_millis(100);
+        let start = Instant::now();
+        let mut breaker = Breaker::default();
+
+        // Unknown locations aren't failures
+        assert_eq!(breaker.record(latency, Some(&WeatherError::NotFound("location".to_string())), &config, start), None);
+        assert_eq!(breaker.record(latency, Some(&timeout), &config, start), None);
+        assert_eq!(breaker.record(latency, Some(&timeout), &config, start), None);
+        assert_eq!(breaker.record(latency, Some(&timeout), &config, start), Some(CircuitState::Open));
+        assert!(!breaker.allow(start + Duration::from_secs(29)));
+        let status = breaker.status(start + Duration::from_secs(10));
+        assert_eq!((status.state, status.retry_in_secs, status.skipped), (CircuitState::Open, Some(20), 1));
+        assert_eq!((status.successes, status.failures), (1, 3));
+
+        // One trial call once the backoff is over; failing it doubles the backoff, up to the max
+        let retry = start + Duration::from_secs(30);
+        assert!(breaker.allow(retry));
+        assert!(!breaker.allow(retry), "a single trial call");
+        assert_eq!(breaker.record(latency, Some(&timeout), &config, retry), Some(CircuitState::Open));
+        assert_eq!(breaker.state(retry + Duration::from_secs(49)), CircuitState::Open);
+        let retry = retry + Duration::from_secs(50);
+        assert_eq!(breaker.state(retry), CircuitState::HalfOpen);
+        assert!(breaker.allow(retry));
+        assert_eq!(breaker.record(latency, None, &config, retry), Some(CircuitState::Closed));
+        assert!(breaker.allow(retry));
+        assert_eq!(breaker.status(retry).consecutive_failures, 0);
+
+        let mut off = Breaker::default();
+        let disabled = BreakerConfig { failures: 0, ..config };
+        for _ in 0..10 {
+            assert_eq!(off.record(latency, Some(&timeout), &disabled, start), None);
+        }
+    }
+}
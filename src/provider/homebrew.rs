@@ -1,37 +1,61 @@
-use serde_json::json;
-
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::env;
-use std::thread;
 use rand::{thread_rng, Rng};
 use rand::distributions::Alphanumeric;
-use rouille::Request;
-use rouille::Response;
-use rouille::post_input;
-use rouille::session;
-use rouille::try_or_400;
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::auth::{validate_auth_header, RateLimiter};
+use axum::extract::{DefaultBodyLimit, Extension, Form, Path, Query, State};
+use axum::extract::rejection::{FormRejection, JsonRejection};
+use axum::http::{header, StatusCode};
+use axum::middleware;
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use crate::auth::{self, require_api_key, ApiKey, ApiKeyAuth, AuthenticatedDevice, IssuedKey, KeyStore, NewApiKey, Scope};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::thread::JoinHandle;
 use tokio::sync::{broadcast, Mutex as AsyncMutex};
-use std::time::Duration;
 
 use tokio_postgres::{Error, Row};
 use crate::error::{JupiterError, Result as JupiterResult};
-use crate::ssl_config::{create_homebrew_connector, SslConfig};
 use crate::input_sanitizer::{InputSanitizer, DatabaseInputValidator, ValidationError};
-use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
-use postgres_openssl::MakeTlsConnector;
-use crate::db_pool::{DatabasePool, init_homebrew_pool, get_homebrew_pool};
+use crate::db_pool::{init_homebrew_pool, get_homebrew_pool, split_address};
 use crate::db_pool::DatabaseConfig as DbPoolConfig;
-use crate::config::{ConfigError, DatabaseConfig};
+use crate::config::{ConfigError, DatabaseConfig, ResourceLimits};
+use crate::base_path;
+use crate::live::{self, LiveUpdate};
+use crate::metrics::{self, Counter};
+use once_cell::sync::Lazy;
+use crate::query_cache::{self, RangeKey};
+use crate::response_format::{self, ResponseFormat};
+use crate::openapi;
+use crate::schema::{with_schema, Schema};
+use crate::server;
+use crate::devices::{self, Device, DeviceKey, DeviceRegistry, DeviceState, DeviceStatus, SharedRegistry, StateChange};
+use crate::groups::{self, Group, GroupClimate};
+use crate::alert_rules::{self, AlertRule};
+use crate::ingest::{ble, rtl433, IngestSummary};
+use crate::analysis::{aqi, history, rooms, ventilation};
+use crate::analysis::baseline::DayExtremes;
+use crate::analysis::history::HistoryOptions;
+use crate::analysis::rooms::{RoomClimate, RoomDifferential};
+use crate::analysis::smoothing::{self, Smoothing};
+use crate::analysis::ventilation::VentilationEstimate;
+use crate::report_chain;
+use crate::admin;
+use crate::locale::Locale;
+use crate::secret::Secret;
+use crate::timescale::TimescaleConfig;
+use crate::migrations;
+use crate::summary;
+use crate::storage::{self, WeatherStore};
+use crate::influx;
+use crate::usage;
+use crate::triggers;
 
 // Can have multiple homebrew instruments
 // Support temperature humidity, windspeed, wind direction, percipitation, PM2.5, PM10, C02, TVOC, etc.
-// Must select storage location for homebrew instruments (local, postgres, etc.)
+// Reports are stored in Postgres, or a local SQLite file (see `storage`)
 // Multiple instruments can form an inside/outside average
 // Instrument can be inside or outside
 // Instruments POST to homebrew API using an API key
@@ -40,24 +64,111 @@ use crate::config::{ConfigError, DatabaseConfig};
 
 
 
+static REPORTS_SAVED: Lazy<Arc<Counter>> = Lazy::new(|| metrics::counter(
+    "jupiter_reports_saved_total", "Homebrew reports stored, posted or ingested, by device type", &["device_type"],
+));
+
 // Secure filter parameters for database queries
 #[derive(Debug, Clone)]
 pub struct FilterParams {
     pub oid: Option<String>,
+    pub device_type: Option<String>,
+    pub device_id: Option<String>,
     // Add more filter fields as needed
 }
 
+/// Bucket size of `WeatherReport::select_range`; buckets start on whole UTC intervals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    Raw,
+    #[serde(rename = "5min")]
+    FiveMinutes,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Raw => "raw",
+            Resolution::FiveMinutes => "5min",
+            Resolution::Hour => "hour",
+            Resolution::Day => "day",
+        }
+    }
+
+    pub fn bucket_secs(&self) -> Option<i64> {
+        match self {
+            Resolution::Raw => None,
+            Resolution::FiveMinutes => Some(5 * 60),
+            Resolution::Hour => Some(3600),
+            Resolution::Day => Some(86_400),
+        }
+    }
+}
+
+impl std::str::FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(Resolution::Raw),
+            "5min" => Ok(Resolution::FiveMinutes),
+            "hour" => Ok(Resolution::Hour),
+            "day" => Ok(Resolution::Day),
+            other => Err(format!("unknown resolution '{}', expected raw, 5min, hour or day", other)),
+        }
+    }
+}
+
+/// Body of GET /api/weather_reports/history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportRange {
+    pub start: i64,
+    pub end: i64,
+    pub device_type: Option<String>,
+    pub resolution: Resolution,
+    pub truncated: bool,             // More raw readings than RANGE_LIMIT; the newest were left out
+    #[serde(default)]
+    pub next_cursor: Option<String>, // `?cursor=` for the readings left out
+    pub reports: Vec<WeatherReport>, // Oldest first
+}
+
+/// Body of GET /api/weather_reports/list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportPage {
+    pub reports: Vec<WeatherReport>, // Newest first
+    pub next_cursor: Option<String>, // `?cursor=` for the next page, None on the last one
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
-    pub apikey: String,
+    pub apikey: Secret<String>,
     pub pg: PostgresServer,
     pub port: u16,
     #[serde(skip)]
-    pub server_handle: Option<Arc<AsyncMutex<Option<JoinHandle<()>>>>>,
+    pub server_handle: Option<Arc<AsyncMutex<Option<server::ServerTask>>>>,
+    #[serde(skip)]
+    pub shutdown_tx: Option<broadcast::Sender<()>>,
+    #[serde(skip)]
+    pub devices: SharedRegistry,
+    #[serde(skip)]
+    pub api_keys: KeyStore,
+    #[serde(skip)]
+    pub hash_chain: bool,
     #[serde(skip)]
-    pub shutdown_flag: Arc<AtomicBool>,
+    pub base_path: Option<String>,
     #[serde(skip)]
-    pub shutdown_tx: Option<broadcast::Sender<()>>
+    pub limits: ResourceLimits,
+    #[serde(skip)]
+    pub locale: Locale,
+    #[serde(skip)]
+    pub smoothing: Smoothing,
+    #[serde(skip, default = "storage::postgres")]
+    pub store: Arc<dyn WeatherStore>,
+    #[serde(skip)]
+    pub timescale: Option<TimescaleConfig>,
 }
 impl std::fmt::Debug for Config {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -65,41 +176,125 @@ impl std::fmt::Debug for Config {
             .field("apikey", &self.apikey)
             .field("pg", &self.pg)
             .field("port", &self.port)
+            .field("store", &self.store.name())
             .finish()
     }
 }
 
 impl Config {
-    pub fn new(apikey: String, pg: PostgresServer, port: u16) -> Self {
+    pub fn new(apikey: impl Into<Secret<String>>, pg: PostgresServer, port: u16) -> Self {
         let (shutdown_tx, _) = broadcast::channel(1);
         Config {
-            apikey,
+            apikey: apikey.into(),
             pg,
             port,
             server_handle: Some(Arc::new(AsyncMutex::new(None))),
-            shutdown_flag: Arc::new(AtomicBool::new(false)),
             shutdown_tx: Some(shutdown_tx),
+            devices: SharedRegistry::default(),
+            api_keys: KeyStore::default(),
+            hash_chain: false,
+            base_path: None,
+            limits: ResourceLimits::default(),
+            locale: Locale::default(),
+            smoothing: Smoothing::Off,
+            store: storage::postgres(),
+            timescale: None,
         }
     }
 
+    /// Devices to register on startup (DEVICE_REGISTRY_FILE) and the settings defaults served
+    /// to firmware
+    pub fn with_devices(mut self, devices: DeviceRegistry) -> Self {
+        self.devices = SharedRegistry::new(devices);
+        self
+    }
+
+    /// Record every saved report in the tamper-evident `report_chain` table
+    pub fn with_hash_chain(mut self, enabled: bool) -> Self {
+        self.hash_chain = enabled;
+        self
+    }
+
+    /// Serve all routes below `base_path` (e.g. "/weather") for hosting behind a reverse proxy
+    pub fn with_base_path(mut self, base_path: Option<String>) -> Self {
+        self.base_path = base_path;
+        self
+    }
+
+    /// Database pool size and concurrent request limit
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Number and unit formatting for `/api/value?format=text`
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Smoothing of sensor series (SENSOR_SMOOTHING) for the latest report, values, rooms and
+    /// combined conditions; stored reports stay raw
+    pub fn with_smoothing(mut self, smoothing: Smoothing) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Where reports are kept (HOMEBREW_SQLITE_PATH), the homebrew Postgres database by default
+    pub fn with_store(mut self, store: Arc<dyn WeatherStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Keep weather_reports as a compressed TimescaleDB hypertable (TIMESCALE_ENABLED)
+    pub fn with_timescale(mut self, timescale: Option<TimescaleConfig>) -> Self {
+        self.timescale = timescale;
+        self
+    }
+
     pub async fn init(&mut self) -> JupiterResult<()> {
+        if self.store.uses_postgres() {
+            self.init_postgres().await?;
+        } else {
+            // Devices stay as registered in DEVICE_REGISTRY_FILE and only the API key is accepted
+            let started = Instant::now();
+            self.store.build().await?;
+            log::info!("[homebrew] Storing reports in {}, opened in {} ms", self.store.name(), started.elapsed().as_millis());
+        }
+        alert_rules::load(self).await?;
+
+        let shutdown_rx = self.shutdown_tx.as_ref()
+            .ok_or_else(|| JupiterError::ConfigurationError("Shutdown channel not initialized".into()))?
+            .subscribe();
+        let handle = server::spawn("Homebrew", self.port, router(self.clone()), &self.limits, shutdown_rx).await?;
+        if let Some(handle_mutex) = &self.server_handle {
+            *handle_mutex.lock().await = Some(handle);
+        }
+        
+        Ok(())
+    }
+
+    async fn init_postgres(&mut self) -> JupiterResult<()> {
         // Initialize connection pool
+        let (host, port) = split_address(&self.pg.address);
         let db_config = DbPoolConfig {
             db_name: self.pg.db_name.clone(),
             username: self.pg.username.clone(),
             password: self.pg.password.clone(),
-            host: self.pg.address.clone(),
-            port: Some(5432),
-            pool_size: Some(20),
+            host,
+            address: self.pg.address.clone(),  // For backward compatibility
+            port: Some(port),
+            pool_size: Some(self.limits.db_pool_size),
             connection_timeout: Some(std::time::Duration::from_secs(5)),
             idle_timeout: Some(std::time::Duration::from_secs(600)),
             max_lifetime: Some(std::time::Duration::from_secs(1800)),
             use_ssl: true,
         };
         
+        let started = Instant::now();
         match init_homebrew_pool(db_config).await {
             Ok(pool) => {
-                log::info!("[homebrew] Database connection pool initialized successfully");
+                log::info!("[homebrew] Database connection pool initialized in {} ms", started.elapsed().as_millis());
                 // Log initial pool status
                 let status = pool.status();
                 status.log("homebrew");
@@ -111,195 +306,1380 @@ impl Config {
         }
 
         self.build_tables().await?;
-
-        let config = self.clone();
-        let shutdown_flag = self.shutdown_flag.clone();
-        let _shutdown_rx = self.shutdown_tx.as_ref()
-            .ok_or_else(|| JupiterError::ConfigurationError("Shutdown channel not initialized".into()))?
-            .subscribe();
-        let server_port = config.port;
-        
-        let handle = thread::spawn(move || {
-            // Create rate limiter: max 10 attempts per minute per IP
-            let rate_limiter = Arc::new(RateLimiter::new(10, 60));
-            
-            let server = rouille::Server::new(format!("0.0.0.0:{}", server_port).as_str(), move |request| {
-    
-                // Validate authentication with rate limiting
-                if let Err(response) = validate_auth_header(request, &config.apikey, Some(&rate_limiter)) {
-                    return response;
-                }
-    
-                if request.url() == "/api/weather_reports" {
-                    if request.method() == "POST" {
-
-                        // Collect input params from post request
-                        let input = try_or_400!(post_input!(request, {
-                            temperature: Option<f64>,
-                            humidity: Option<f64>,
-                            percipitation: Option<f64>,
-                            pm10: Option<f64>,
-                            pm25: Option<f64>,
-                            co2: Option<f64>,
-                            tvoc: Option<f64>,
-                            device_type: String,
-                        }));
-
-                        let mut obj = WeatherReport::new();
-                        obj.temperature = input.temperature;
-                        obj.humidity = input.humidity;
-                        obj.percipitation = input.percipitation;
-                        obj.pm10 = input.pm10;
-                        obj.pm25 = input.pm25;
-                        obj.co2 = input.co2;
-                        obj.tvoc = input.tvoc;
-                        obj.device_type = input.device_type.to_string();
-                        obj.save(config.clone());
-                        return Response::json(&obj);
-                    }
-                    if request.method() == "GET" {
-                        let objects = match WeatherReport::select(config.clone(), Some(1), None, Some(format!("timestamp DESC")), None) {
-                            Ok(objs) => objs,
-                            Err(e) => {
-                                log::error!("Failed to select weather reports: {}", e);
-                                return Response::text("Database error").with_status_code(500);
-                            }
-                        };
-                        
-                        // Check if we have any results before accessing
-                        if let Some(first) = objects.first() {
-                            return Response::json(&first.clone());
-                        } else {
-                            // Log empty result scenario
-                            eprintln!("[homebrew] Warning: No weather data found in database for GET request");
-                            // Return a proper error response when no data is available
-                            return Response::text("No weather data available").with_status_code(404);
-                        }
-                    }
-                }
-    
-    
-                let mut response = Response::text("hello world");
-
-                return response;
-            }).unwrap_or_else(|e| {
-                log::error!("Failed to create server: {}", e);
-                panic!("Failed to create server: {}", e);
-            });
-            
-            log::info!("Homebrew server started on port {}", server_port);
-            
-            // Run server with shutdown support
-            while !shutdown_flag.load(Ordering::Relaxed) {
-                server.poll_timeout(std::time::Duration::from_millis(100));
-            }
-            
-            log::info!("Homebrew server shutting down...");
-        });
-        
-        // Store the handle in the async mutex - need to spawn a task for this
-        if let Some(handle_mutex) = &self.server_handle {
-            let handle_mutex_clone = handle_mutex.clone();
-            tokio::spawn(async move {
-                match tokio::time::timeout(Duration::from_secs(5), handle_mutex_clone.lock()).await {
-                    Ok(mut handle_guard) => {
-                        *handle_guard = Some(handle);
-                    },
-                    Err(_) => {
-                        log::error!("Failed to acquire server handle lock within timeout");
-                    }
-                }
-            });
-        }
-        
-        Ok(())
+        self.load_devices().await?;
+        self.api_keys.reload().await
     }
 
     pub async fn shutdown(&mut self) {
-        self.shutdown_with_timeout(std::time::Duration::from_secs(10)).await;
+        self.shutdown_with_timeout(self.limits.shutdown_timeout).await;
     }
 
     pub async fn shutdown_with_timeout(&mut self, timeout: std::time::Duration) {
         log::info!("Initiating graceful shutdown of homebrew server...");
         
-        // Signal the server thread to stop
-        self.shutdown_flag.store(true, Ordering::Relaxed);
-        
-        // Send shutdown signal via broadcast channel
+        // Stop accepting connections; requests in flight are finished first
         if let Some(tx) = &self.shutdown_tx {
             let _ = tx.send(());
         }
         
-        // Wait for the server thread to finish with timeout
         if let Some(handle_mutex) = &self.server_handle {
-            let handle_mutex_clone = handle_mutex.clone();
-            
-            // Try to join with timeout using async mutex
-            let join_result = tokio::time::timeout(timeout, async move {
-                // First acquire lock with timeout to prevent deadlock
-                match tokio::time::timeout(Duration::from_secs(2), handle_mutex_clone.lock()).await {
-                    Ok(mut handle_guard) => {
-                        if let Some(handle) = handle_guard.take() {
-                            // Since we can't directly join std::thread in async context,
-                            // we'll use a different approach
-                            let _ = tokio::task::spawn_blocking(move || {
-                                handle.join()
-                            }).await;
-                        }
-                    },
-                    Err(_) => {
-                        log::error!("Failed to acquire lock for shutdown within timeout");
-                    }
-                }
-            }).await;
-            
-            match join_result {
-                Ok(_) => log::info!("Homebrew server thread joined successfully"),
-                Err(_) => {
-                    log::warn!("Homebrew server shutdown timed out after {:?}", timeout);
-                    // Force cleanup if needed with timeout
-                    if let Ok(mut handle_guard) = tokio::time::timeout(
-                        Duration::from_secs(1),
-                        handle_mutex.lock()
-                    ).await {
-                        handle_guard.take(); // Drop the handle
-                    }
-                }
+            if let Some(handle) = handle_mutex.lock().await.take() {
+                server::join("Homebrew", handle, timeout).await;
             }
         }
         
         log::info!("Homebrew server shutdown complete");
     }
 
+    /// Adds devices from DEVICE_REGISTRY_FILE the table doesn't have yet, then serves the
+    /// registry from the table
+    pub async fn load_devices(&self) -> JupiterResult<()> {
+        let registry = self.devices.snapshot();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        for device in &registry.devices {
+            if devices::insert(device, now).await? {
+                log::info!("[homebrew] Registered device {} from the device registry file", device.id);
+            }
+        }
+        self.reload_devices().await?;
+        log::info!("[homebrew] {} registered device(s)", self.devices.snapshot().devices.len());
+        Ok(())
+    }
+
+    /// Serves the registry as it is in the table, after a change through /api/devices
+    pub async fn reload_devices(&self) -> JupiterResult<()> {
+        let defaults = self.devices.snapshot().defaults.clone();
+        self.devices.replace(DeviceRegistry { defaults, devices: devices::select_all().await? });
+        Ok(())
+    }
+
     pub async fn build_tables(&self) -> JupiterResult<()> {
         // Get connection from pool
         let pool = get_homebrew_pool()
             .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
-        
-        let client = pool.get_connection_with_retry(3).await
-            .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
-    
-        // Build WeatherReport Table
-        // ---------------------------------------------------------------
-        let db = client.batch_execute(WeatherReport::sql_build_statement()).await;
-        match db {
-            Ok(_v) => log::info!("POSTGRES: CREATED WeatherReport Table"),
-            Err(e) => log::error!("POSTGRES: {:?}", e),
-        }
-        let db_migrations = WeatherReport::migrations();
-        for migration in db_migrations {
-            let migrations_db = client.batch_execute(migration).await;
-            match migrations_db {
-                Ok(_v) => log::info!("POSTGRES: Migration Successful"),
-                Err(e) => log::error!("POSTGRES: {:?}", e),
+        let started = Instant::now();
+
+        let target = migrations::target_from_env("HOMEBREW_SCHEMA_VERSION")
+            .map_err(|message| JupiterError::ConfigurationError(format!("HOMEBREW_SCHEMA_VERSION {}", message)))?;
+        migrations::run(&pool, "homebrew", &migrations::homebrew(), target).await?;
+
+        // Hypertables aggregate by chunk, plain tables read the summary views
+        summary::enable(self.timescale.is_none());
+
+        // weather_reports becomes a hypertable once its migrations ran
+        if let Some(timescale) = &self.timescale {
+            let client = pool.get_connection_with_retry(3).await
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+            let mut failed = false;
+            for statement in timescale.statements() {
+                if let Err(e) = client.batch_execute(&statement).await {
+                    log::error!("POSTGRES: TimescaleDB setup failed, weather_reports may stay a plain table: {:?}", e);
+                    failed = true;
+                    break;
+                }
+            }
+            if !failed {
+                log::info!("POSTGRES: weather_reports is a hypertable in {}-day chunks", timescale.chunk_days);
             }
         }
 
+        log::info!("[homebrew] Tables built in {} ms", started.elapsed().as_millis());
         return Ok(());
     }    
 
 }
 
+/// Routes of the homebrew server, every one of them behind the API key or a stored key whose
+/// scope permits the route
+pub fn router(config: Config) -> Router {
+    let auth = Arc::new(ApiKeyAuth::new(config.apikey.clone()).with_keys(config.api_keys.clone()));
+    let base_path = config.base_path.clone();
+    let routes = Router::new()
+        .route("/api/ingest/rtl433", post(ingest_rtl433))
+        .route("/api/ingest/ble", post(ingest_ble))
+        // Ingest bridges post small JSON payloads
+        .layer(DefaultBodyLimit::max(1024 * 1024))
+        .route("/api/value/:field", get(value))
+        .route("/api/history/:field", get(field_history))
+        .route("/api/ventilation", get(room_ventilation))
+        .route("/api/devices", get(list_devices).post(register_device))
+        .route("/api/devices/status", get(devices_status))
+        .route("/api/devices/:id", get(get_device).put(update_device).delete(unregister_device))
+        .route("/api/devices/:id/state", axum::routing::put(set_device_state))
+        .route("/api/devices/:id/key", post(issue_device_key))
+        .route("/api/devices/:id/config", get(device_config))
+        .route("/api/groups", get(list_groups).post(create_group))
+        .route("/api/groups/:id", get(get_group).put(update_group).delete(delete_group))
+        .route("/api/groups/:id/climate", get(group_climate_summary))
+        .route("/api/groups/:id/status", get(group_status))
+        .route("/api/alert_rules", get(list_alert_rules).post(create_alert_rule))
+        .route("/api/alert_rules/:id", get(get_alert_rule).put(update_alert_rule).delete(delete_alert_rule))
+        .route("/api/admin/keys", get(list_keys).post(create_key))
+        .route("/api/admin/keys/:id", axum::routing::delete(revoke_key))
+        .route("/api/admin/devices/:id/export", get(export_device))
+        .route("/api/admin/devices/:id/erase", post(erase_device))
+        .route("/api/admin/storage", get(storage_usage))
+        .route("/api/audit/verify", get(verify_chain))
+        .route("/api/rooms", get(room_climate))
+        .route("/api/openapi.json", get(openapi_document))
+        .route("/api/weather_reports", get(latest_report).post(create_report))
+        .route("/api/weather_reports/history", get(report_range))
+        .route("/api/weather_reports/list", get(report_list))
+        .route("/metrics", get(metrics::prometheus))
+        .fallback(|| async { "hello world" })
+        .layer(middleware::from_fn_with_state(auth, require_api_key))
+        .with_state(Arc::new(config));
+    base_path::nest(routes, base_path.as_deref())
+}
+
+/// Fields of a report posted as an urlencoded form to /api/weather_reports, on either server
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReportForm {
+    pub temperature: Option<f64>,
+    pub humidity: Option<f64>,
+    pub percipitation: Option<f64>,
+    pub pm10: Option<f64>,
+    pub pm25: Option<f64>,
+    pub co2: Option<f64>,
+    pub tvoc: Option<f64>,
+    pub wind_speed: Option<f64>,
+    pub wind_direction: Option<f64>,
+    pub battery_voltage: Option<f64>,
+    pub battery_percent: Option<f64>,
+    pub rssi: Option<f64>,
+    #[serde(default)]
+    pub device_type: String,    // Taken from the registration when the report names a device
+    #[serde(default)]
+    pub device_id: Option<String>,
+}
+
+impl ReportForm {
+    pub fn into_report(self) -> WeatherReport {
+        let mut obj = WeatherReport::new();
+        obj.temperature = self.temperature;
+        obj.humidity = self.humidity;
+        obj.percipitation = self.percipitation;
+        obj.pm10 = self.pm10;
+        obj.pm25 = self.pm25;
+        obj.co2 = self.co2;
+        obj.tvoc = self.tvoc;
+        obj.wind_speed = self.wind_speed;
+        obj.wind_direction = self.wind_direction;
+        obj.battery_voltage = self.battery_voltage;
+        obj.battery_percent = self.battery_percent;
+        obj.rssi = self.rssi;
+        obj.device_type = self.device_type;
+        obj.device_id = self.device_id;
+        obj
+    }
+}
+
+/// Stores a posted report and echoes it back. A device authenticated by its own key reports as
+/// itself; otherwise the form may name a registered device.
+pub async fn save_form(config: &Config, device: Option<AuthenticatedDevice>, form: Result<Form<ReportForm>, FormRejection>) -> Response {
+    let report = match form {
+        Ok(Form(form)) => form.into_report(),
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    match submit_report(config, device, report).await {
+        Ok(report) => with_schema(Json(&report).into_response(), Schema::WeatherReport),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Attributes `report` to its device and stores it; the status and message it was turned away
+/// with otherwise. Shared by the HTTP and gRPC ingest endpoints.
+pub async fn submit_report(config: &Config, device: Option<AuthenticatedDevice>, mut report: WeatherReport) -> Result<WeatherReport, (StatusCode, String)> {
+    if let Some(AuthenticatedDevice(id)) = device {
+        report.device_id = Some(id);
+    }
+    if let Some(id) = &report.device_id {
+        match config.devices.snapshot().get(id) {
+            Some(device) => {
+                device.accepts_reports().map_err(|e| (StatusCode::CONFLICT, e.to_string()))?;
+                report.device_type = device.device_type.clone();
+            },
+            None => return Err((StatusCode::BAD_REQUEST, format!("Unknown device '{}'", id))),
+        }
+    }
+    if report.device_type.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Missing field `device_type`".to_string()));
+    }
+    match report.save(config.clone()).await {
+        Ok(_) => Ok(report),
+        Err(e @ JupiterError::QuotaExceeded(_)) => Err((StatusCode::INSUFFICIENT_STORAGE, e.to_string())),
+        Err(e) => {
+            log::error!("[homebrew] Failed to save weather report: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, "Database error".to_string()))
+        }
+    }
+}
+
+async fn create_report(State(config): State<Arc<Config>>, device: Option<Extension<AuthenticatedDevice>>, form: Result<Form<ReportForm>, FormRejection>) -> Response {
+    save_form(&config, device.map(|Extension(device)| device), form).await
+}
+
+/// `?raw=true` skips SENSOR_SMOOTHING
+async fn latest_report(State(config): State<Arc<Config>>, format: ResponseFormat, Query(params): Query<HashMap<String, String>>) -> Response {
+    let latest = config.store.latest().await;
+    let latest = match latest {
+        Ok(Some(report)) if !raw_requested(&params) => Ok(Some(smoothed(&config, report).await)),
+        latest => latest,
+    };
+
+    match latest {
+        Ok(Some(report)) if format == ResponseFormat::Json => with_schema(latest_json_response(&report), Schema::WeatherReport),
+        Ok(Some(report)) => with_schema(response_format::respond(format, &report), Schema::WeatherReport),
+        Ok(None) => {
+            log::warn!("[homebrew] No weather data found in database for GET request");
+            (StatusCode::NOT_FOUND, "No weather data available").into_response()
+        },
+        Err(e) => {
+            log::error!("Failed to select latest weather report: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// A bridge batch keeps going past readings from paused or archived devices; each reason is
+/// listed once in the summary
+fn reject(rejected: &mut Vec<String>, e: JupiterError) {
+    let reason = e.to_string();
+    if !rejected.contains(&reason) {
+        rejected.push(reason);
+    }
+}
+
+/// Body is one rtl_433 JSON event, or several as JSON lines
+async fn ingest_rtl433(State(config): State<Arc<Config>>, body: String) -> Response {
+    let mut stored = Vec::new();
+    let mut ignored = 0;
+    let mut rejected = Vec::new();
+    for line in body.lines().filter(|l| !l.trim().is_empty()) {
+        match rtl433::ingest_line(line, &config.devices.snapshot(), &config).await {
+            Ok(Some(report)) => stored.push(report),
+            Ok(None) => ignored += 1,
+            Err(e @ JupiterError::DeviceInactive(_)) => reject(&mut rejected, e),
+            Err(e @ (JupiterError::SerializationError(_) | JupiterError::ValidationError(_))) => {
+                return (StatusCode::BAD_REQUEST, format!("Invalid rtl_433 event: {}", e)).into_response();
+            },
+            Err(e @ JupiterError::QuotaExceeded(_)) => return (StatusCode::INSUFFICIENT_STORAGE, e.to_string()).into_response(),
+            Err(e) => {
+                log::error!("[homebrew] Failed to store rtl_433 event: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+            }
+        }
+    }
+
+    Json(&IngestSummary { stored, ignored, rejected }).into_response()
+}
+
+async fn ingest_ble(State(config): State<Arc<Config>>, body: String) -> Response {
+    let advertisements = match ble::parse_payload(&body) {
+        Ok(advertisements) => advertisements,
+        Err(e) => return (StatusCode::BAD_REQUEST, format!("Invalid BLE payload: {}", e)).into_response(),
+    };
+
+    let mut stored = Vec::new();
+    let mut ignored = 0;
+    let mut rejected = Vec::new();
+    for advertisement in &advertisements {
+        match ble::ingest(advertisement, &config.devices.snapshot(), &config).await {
+            Ok(Some(report)) => stored.push(report),
+            Ok(None) => ignored += 1,
+            Err(e @ JupiterError::DeviceInactive(_)) => reject(&mut rejected, e),
+            Err(e @ JupiterError::QuotaExceeded(_)) => return (StatusCode::INSUFFICIENT_STORAGE, e.to_string()).into_response(),
+            Err(e) => {
+                log::error!("[homebrew] Failed to store BLE reading: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+            }
+        }
+    }
+
+    Json(&IngestSummary { stored, ignored, rejected }).into_response()
+}
+
+async fn value(State(config): State<Arc<Config>>, Path(field): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let locale = Some(&config.locale).filter(|_| params.get("format").map(String::as_str) == Some("text"));
+    let smoothing = if raw_requested(&params) { Smoothing::Off } else { config.smoothing };
+    value_response(&config, &field, params.get("device").cloned(), locale, smoothing).await
+}
+
+/// `?raw=true`: readings as they were stored, without SENSOR_SMOOTHING
+pub fn raw_requested(params: &HashMap<String, String>) -> bool {
+    params.get("raw").map(String::as_str) == Some("true")
+}
+
+async fn report_range(State(config): State<Arc<Config>>, Query(params): Query<HashMap<String, String>>) -> Response {
+    range_response(&config, &params).await
+}
+
+async fn report_list(State(config): State<Arc<Config>>, Query(params): Query<HashMap<String, String>>) -> Response {
+    list_response(&config, &params).await
+}
+
+async fn field_history(State(config): State<Arc<Config>>, Path(field): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    history_response(&config, &field, &params).await
+}
+
+async fn room_ventilation(State(config): State<Arc<Config>>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let hours = match params.get("hours").map(|h| h.parse::<i64>()) {
+        Some(Ok(hours)) if (1..=24 * 14).contains(&hours) => hours,
+        Some(_) => return (StatusCode::BAD_REQUEST, "hours must be between 1 and 336").into_response(),
+        None => 24,
+    };
+    match room_co2_series(&config, hours).await {
+        Ok(rooms) => with_schema(Json(&ventilation_body(hours, &rooms)).into_response(), Schema::Ventilation),
+        Err(e) => {
+            log::error!("[homebrew] Failed to select CO2 readings: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// `?include_archived=true` lists archived devices too
+fn include_archived(params: &HashMap<String, String>) -> bool {
+    params.get("include_archived").is_some_and(|v| v == "true")
+}
+
+async fn devices_status(State(config): State<Arc<Config>>, Query(params): Query<HashMap<String, String>>) -> Response {
+    match device_statuses(&config, include_archived(&params)).await {
+        Ok(statuses) => with_schema(Json(&statuses).into_response(), Schema::DeviceStatus),
+        Err(e) => {
+            log::error!("[homebrew] Failed to select device status: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+fn device_store_error(action: &str, e: JupiterError) -> Response {
+    log::error!("[homebrew] Failed to {}: {}", action, e);
+    (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+}
+
+async fn list_devices(State(config): State<Arc<Config>>, Query(params): Query<HashMap<String, String>>) -> Response {
+    let registry = config.devices.snapshot();
+    let devices: Vec<&Device> = if include_archived(&params) {
+        registry.devices.iter().collect()
+    } else {
+        registry.listed().collect()
+    };
+    with_schema(Json(&devices).into_response(), Schema::Devices)
+}
+
+async fn get_device(State(config): State<Arc<Config>>, Path(id): Path<String>) -> Response {
+    match config.devices.snapshot().get(&id) {
+        Some(device) => with_schema(Json(device).into_response(), Schema::Device),
+        None => (StatusCode::NOT_FOUND, format!("Unknown device '{}'", id)).into_response(),
+    }
+}
+
+/// Issues `device` an ingest key and stores it
+async fn issue_ingest_key(config: &Config, device: &Device, now: i64) -> JupiterResult<String> {
+    let (key, api_key) = ApiKey::issue(&format!("{} device key", device.name), Scope::Ingest, Some(device.id.clone()), None, now);
+    auth::insert_key(&key).await?;
+    config.api_keys.reload().await?;
+    Ok(api_key)
+}
+
+/// Registers a device and issues its ingest key, which is only ever shown in this response
+async fn register_device(State(config): State<Arc<Config>>, device: Result<Json<Device>, JsonRejection>) -> Response {
+    let device = match device {
+        Ok(Json(device)) => device,
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    if let Err(problem) = device.validate() {
+        return (StatusCode::BAD_REQUEST, problem).into_response();
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    match devices::insert(&device, now).await {
+        Ok(true) => {},
+        Ok(false) => return (StatusCode::CONFLICT, format!("Device '{}' is already registered", device.id)).into_response(),
+        Err(e) => return device_store_error("register device", e),
+    }
+    if let Err(e) = config.reload_devices().await {
+        return device_store_error("reload devices", e);
+    }
+    let api_key = match issue_ingest_key(&config, &device, now).await {
+        Ok(api_key) => api_key,
+        Err(e) => return device_store_error("issue device key", e),
+    };
+    (StatusCode::CREATED, with_schema(Json(&DeviceKey { device, api_key }).into_response(), Schema::DeviceKey)).into_response()
+}
+
+/// Replaces a registration except for its id and state; its keys keep working
+async fn update_device(State(config): State<Arc<Config>>, Path(id): Path<String>, device: Result<Json<Device>, JsonRejection>) -> Response {
+    let state = config.devices.snapshot().get(&id).map(|device| device.state).unwrap_or_default();
+    let device = match device {
+        Ok(Json(device)) => Device { id, state, ..device },
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    if let Err(problem) = device.validate() {
+        return (StatusCode::BAD_REQUEST, problem).into_response();
+    }
+    match devices::update(&device).await {
+        Ok(true) => {},
+        Ok(false) => return (StatusCode::NOT_FOUND, format!("Unknown device '{}'", device.id)).into_response(),
+        Err(e) => return device_store_error("update device", e),
+    }
+    if let Err(e) = config.reload_devices().await {
+        return device_store_error("reload devices", e);
+    }
+    with_schema(Json(&device).into_response(), Schema::Device)
+}
+
+/// Pauses, archives or reactivates a device
+async fn set_device_state(State(config): State<Arc<Config>>, Path(id): Path<String>, change: Result<Json<StateChange>, JsonRejection>) -> Response {
+    let state = match change {
+        Ok(Json(StateChange { state })) => state,
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    match devices::set_state(&id, state).await {
+        Ok(true) => {},
+        Ok(false) => return (StatusCode::NOT_FOUND, format!("Unknown device '{}'", id)).into_response(),
+        Err(e) => return device_store_error("change device state", e),
+    }
+    if let Err(e) = config.reload_devices().await {
+        return device_store_error("reload devices", e);
+    }
+    match config.devices.snapshot().get(&id) {
+        Some(device) => with_schema(Json(device).into_response(), Schema::Device),
+        None => (StatusCode::NOT_FOUND, format!("Unknown device '{}'", id)).into_response(),
+    }
+}
+
+/// Archives the device, keeping its reports and keys. With `?purge=true` the registration is
+/// deleted instead: its keys are deleted and its reports kept, no longer attributed to it;
+/// /api/admin/devices/{id}/erase deletes them
+async fn unregister_device(State(config): State<Arc<Config>>, Path(id): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    if params.get("purge").map(String::as_str) != Some("true") {
+        return match devices::set_state(&id, DeviceState::Archived).await {
+            Ok(true) => match config.reload_devices().await {
+                Ok(()) => StatusCode::NO_CONTENT.into_response(),
+                Err(e) => device_store_error("reload devices", e),
+            },
+            Ok(false) => (StatusCode::NOT_FOUND, format!("Unknown device '{}'", id)).into_response(),
+            Err(e) => device_store_error("archive device", e),
+        };
+    }
+    match devices::delete(&id).await {
+        Ok(true) => {},
+        Ok(false) => return (StatusCode::NOT_FOUND, format!("Unknown device '{}'", id)).into_response(),
+        Err(e) => return device_store_error("unregister device", e),
+    }
+    if let Err(e) = config.api_keys.reload().await {
+        return device_store_error("reload keys", e);
+    }
+    match config.reload_devices().await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => device_store_error("reload devices", e),
+    }
+}
+
+/// Replaces a device's keys with a new ingest key, e.g. after a lost or leaked one; the old
+/// keys stop working and every other key is left alone
+async fn issue_device_key(State(config): State<Arc<Config>>, Path(id): Path<String>) -> Response {
+    let device = match config.devices.snapshot().get(&id) {
+        Some(device) => device.clone(),
+        None => return (StatusCode::NOT_FOUND, format!("Unknown device '{}'", id)).into_response(),
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    if let Err(e) = auth::revoke_device_keys(&id, now).await {
+        return device_store_error("revoke device keys", e);
+    }
+    match issue_ingest_key(&config, &device, now).await {
+        Ok(api_key) => with_schema(Json(&DeviceKey { device, api_key }).into_response(), Schema::DeviceKey),
+        Err(e) => device_store_error("issue device key", e),
+    }
+}
+
+async fn list_groups() -> Response {
+    match groups::select_all().await {
+        Ok(groups) => with_schema(Json(&groups).into_response(), Schema::Groups),
+        Err(e) => device_store_error("select groups", e),
+    }
+}
+
+/// The group named by the path, or the response to send instead; with every group for context
+async fn find_group(id: &str) -> Result<(Vec<Group>, Group), Response> {
+    let all = groups::select_all().await.map_err(|e| device_store_error("select groups", e))?;
+    match all.iter().find(|g| g.id == id).cloned() {
+        Some(group) => Ok((all, group)),
+        None => Err((StatusCode::NOT_FOUND, format!("Unknown group '{}'", id)).into_response()),
+    }
+}
+
+async fn get_group(Path(id): Path<String>) -> Response {
+    match find_group(&id).await {
+        Ok((_, group)) => with_schema(Json(&group).into_response(), Schema::Group),
+        Err(response) => response,
+    }
+}
+
+async fn create_group(State(config): State<Arc<Config>>, group: Result<Json<Group>, JsonRejection>) -> Response {
+    let group = match group {
+        Ok(Json(group)) => group,
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    let all = match groups::select_all().await {
+        Ok(all) => all,
+        Err(e) => return device_store_error("select groups", e),
+    };
+    if let Err(problem) = group.validate(&all, &config.devices.snapshot()) {
+        return (StatusCode::BAD_REQUEST, problem).into_response();
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    match groups::insert(&group, now).await {
+        Ok(true) => with_schema((StatusCode::CREATED, Json(&group)).into_response(), Schema::Group),
+        Ok(false) => (StatusCode::CONFLICT, format!("Group '{}' already exists", group.id)).into_response(),
+        Err(e) => device_store_error("create group", e),
+    }
+}
+
+/// Replaces a group except for its id
+async fn update_group(State(config): State<Arc<Config>>, Path(id): Path<String>, group: Result<Json<Group>, JsonRejection>) -> Response {
+    let group = match group {
+        Ok(Json(group)) => Group { id, ..group },
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    let all = match find_group(&group.id).await {
+        Ok((all, _)) => all,
+        Err(response) => return response,
+    };
+    if let Err(problem) = group.validate(&all, &config.devices.snapshot()) {
+        return (StatusCode::BAD_REQUEST, problem).into_response();
+    }
+    match groups::update(&group).await {
+        Ok(true) => with_schema(Json(&group).into_response(), Schema::Group),
+        Ok(false) => (StatusCode::NOT_FOUND, format!("Unknown group '{}'", group.id)).into_response(),
+        Err(e) => device_store_error("update group", e),
+    }
+}
+
+/// Deletes a group without subgroups; its devices are left alone
+async fn delete_group(Path(id): Path<String>) -> Response {
+    let all = match find_group(&id).await {
+        Ok((all, _)) => all,
+        Err(response) => return response,
+    };
+    if let Some(child) = all.iter().find(|g| g.parent.as_deref() == Some(id.as_str())) {
+        return (StatusCode::CONFLICT, format!("Group '{}' contains '{}'; move or delete its subgroups first", id, child.id)).into_response();
+    }
+    match groups::delete(&id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, format!("Unknown group '{}'", id)).into_response(),
+        Err(e) => device_store_error("delete group", e),
+    }
+}
+
+async fn group_climate_summary(State(config): State<Arc<Config>>, Path(id): Path<String>) -> Response {
+    let (all, group) = match find_group(&id).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    match group_climate(&config, &all, &group).await {
+        Ok(climate) => with_schema(Json(&climate).into_response(), Schema::GroupClimate),
+        Err(e) => device_store_error("summarize group", e),
+    }
+}
+
+async fn group_status(State(config): State<Arc<Config>>, Path(id): Path<String>) -> Response {
+    let (all, group) = match find_group(&id).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    let registry = config.devices.snapshot();
+    match statuses_of(&config, &groups::members(&all, &group, &registry)).await {
+        Ok(statuses) => with_schema(Json(&statuses).into_response(), Schema::DeviceStatus),
+        Err(e) => device_store_error("select device status", e),
+    }
+}
+
+async fn list_alert_rules() -> Response {
+    match alert_rules::select_all().await {
+        Ok(rules) => with_schema(Json(&rules).into_response(), Schema::AlertRules),
+        Err(e) => device_store_error("select alert rules", e),
+    }
+}
+
+async fn get_alert_rule(Path(id): Path<String>) -> Response {
+    match alert_rules::select_all().await {
+        Ok(rules) => match rules.into_iter().find(|rule| rule.id == id) {
+            Some(rule) => with_schema(Json(&rule).into_response(), Schema::AlertRule),
+            None => (StatusCode::NOT_FOUND, format!("Unknown alert rule '{}'", id)).into_response(),
+        },
+        Err(e) => device_store_error("select alert rules", e),
+    }
+}
+
+/// Applies a change through /api/alert_rules to the rules checked on ingest
+async fn reload_alert_rules() {
+    if let Err(e) = alert_rules::reload().await {
+        log::error!("[homebrew] Failed to reload alert rules: {}", e);
+    }
+}
+
+async fn create_alert_rule(rule: Result<Json<AlertRule>, JsonRejection>) -> Response {
+    let rule = match rule {
+        Ok(Json(rule)) => rule,
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    if let Err(problem) = rule.validate() {
+        return (StatusCode::BAD_REQUEST, problem).into_response();
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    match alert_rules::insert(&rule, now).await {
+        Ok(true) => {
+            reload_alert_rules().await;
+            with_schema((StatusCode::CREATED, Json(&rule)).into_response(), Schema::AlertRule)
+        },
+        Ok(false) => (StatusCode::CONFLICT, format!("Alert rule '{}' already exists", rule.id)).into_response(),
+        Err(e) => device_store_error("create alert rule", e),
+    }
+}
+
+/// Replaces a rule except for its id; devices meeting it start over
+async fn update_alert_rule(Path(id): Path<String>, rule: Result<Json<AlertRule>, JsonRejection>) -> Response {
+    let rule = match rule {
+        Ok(Json(rule)) => AlertRule { id, ..rule },
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    if let Err(problem) = rule.validate() {
+        return (StatusCode::BAD_REQUEST, problem).into_response();
+    }
+    match alert_rules::update(&rule).await {
+        Ok(true) => {
+            reload_alert_rules().await;
+            with_schema(Json(&rule).into_response(), Schema::AlertRule)
+        },
+        Ok(false) => (StatusCode::NOT_FOUND, format!("Unknown alert rule '{}'", rule.id)).into_response(),
+        Err(e) => device_store_error("update alert rule", e),
+    }
+}
+
+async fn delete_alert_rule(Path(id): Path<String>) -> Response {
+    match alert_rules::delete(&id).await {
+        Ok(true) => {
+            reload_alert_rules().await;
+            StatusCode::NO_CONTENT.into_response()
+        },
+        Ok(false) => (StatusCode::NOT_FOUND, format!("Unknown alert rule '{}'", id)).into_response(),
+        Err(e) => device_store_error("delete alert rule", e),
+    }
+}
+
+/// Every stored key, revoked and expired ones included, without the keys themselves
+async fn list_keys(State(config): State<Arc<Config>>) -> Response {
+    with_schema(Json(&*config.api_keys.snapshot()).into_response(), Schema::ApiKeys)
+}
+
+/// Issues a key; the response is the only time it is shown
+async fn create_key(State(config): State<Arc<Config>>, new_key: Result<Json<NewApiKey>, JsonRejection>) -> Response {
+    let new_key = match new_key {
+        Ok(Json(new_key)) => new_key,
+        Err(rejection) => return (StatusCode::BAD_REQUEST, rejection.body_text()).into_response(),
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    if new_key.name.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "name cannot be empty").into_response();
+    }
+    if new_key.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return (StatusCode::BAD_REQUEST, "expires_at must be in the future").into_response();
+    }
+    if let Some(id) = &new_key.device_id {
+        if config.devices.snapshot().get(id).is_none() {
+            return (StatusCode::BAD_REQUEST, format!("Unknown device '{}'", id)).into_response();
+        }
+    }
+
+    let (key, api_key) = ApiKey::issue(new_key.name.trim(), new_key.scope, new_key.device_id, new_key.expires_at, now);
+    if let Err(e) = auth::insert_key(&key).await {
+        return device_store_error("store key", e);
+    }
+    if let Err(e) = config.api_keys.reload().await {
+        return device_store_error("reload keys", e);
+    }
+    with_schema((StatusCode::CREATED, Json(&IssuedKey { key, api_key })).into_response(), Schema::IssuedKey)
+}
+
+/// The key stops working at once; its row is kept
+async fn revoke_key(State(config): State<Arc<Config>>, Path(id): Path<String>) -> Response {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    match auth::revoke_key(&id, now).await {
+        Ok(true) => {},
+        Ok(false) => return (StatusCode::NOT_FOUND, format!("Unknown or already revoked key '{}'", id)).into_response(),
+        Err(e) => return device_store_error("revoke key", e),
+    }
+    match config.api_keys.reload().await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => device_store_error("reload keys", e),
+    }
+}
+
+async fn device_config(State(config): State<Arc<Config>>, Path(id): Path<String>) -> Response {
+    match config.devices.snapshot().device_config(&id) {
+        Some(device_config) => with_schema(Json(&device_config).into_response(), Schema::DeviceConfig),
+        None => (StatusCode::NOT_FOUND, format!("Unknown device '{}'", id)).into_response(),
+    }
+}
+
+// RFC 5987 file name, so unusual device ids can't break the header
+fn attachment(filename: &str) -> String {
+    let encoded: String = filename.bytes()
+        .map(|b| if b.is_ascii_alphanumeric() || b"-._~".contains(&b) { (b as char).to_string() } else { format!("%{:02X}", b) })
+        .collect();
+    format!("attachment; filename*=UTF-8''{}", encoded)
+}
+
+async fn export_device(State(config): State<Arc<Config>>, Path(id): Path<String>) -> Response {
+    match admin::export_device(&id, config.devices.snapshot().get(&id).cloned()).await {
+        Ok(export) => (
+            [(header::CONTENT_DISPOSITION, attachment(&format!("jupiter-export-{}.json", id)))],
+            Json(&export),
+        ).into_response(),
+        Err(e) => {
+            log::error!("[homebrew] Failed to export device {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+async fn erase_device(Path(id): Path<String>, Query(params): Query<HashMap<String, String>>) -> Response {
+    // Without a token, issue one; the caller confirms by repeating the request with it
+    let token = match params.get("confirm") {
+        Some(token) => token,
+        None => return match admin::request_erasure(&id) {
+            Ok(confirmation) => (StatusCode::ACCEPTED, Json(&confirmation)).into_response(),
+            Err(e) => {
+                log::error!("[homebrew] Failed to issue erasure confirmation: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal error").into_response()
+            }
+        },
+    };
+    match admin::erase_device(&id, token).await {
+        Ok(result) => Json(&result).into_response(),
+        Err(e @ JupiterError::ValidationError(_)) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+        Err(e) => {
+            log::error!("[homebrew] Failed to erase device {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+async fn storage_usage(State(config): State<Arc<Config>>) -> Response {
+    if !config.store.uses_postgres() {
+        return (StatusCode::NOT_FOUND, "Storage usage is only reported for the Postgres store").into_response();
+    }
+    match usage::report().await {
+        Ok(usage) => with_schema(Json(&usage).into_response(), Schema::StorageUsage),
+        Err(e) => {
+            log::error!("[homebrew] Failed to report storage usage: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+async fn verify_chain(State(config): State<Arc<Config>>) -> Response {
+    if !config.hash_chain {
+        return (StatusCode::NOT_FOUND, "Report hash chain is not enabled, set REPORT_HASH_CHAIN=true").into_response();
+    }
+    match report_chain::verify().await {
+        Ok(verification) => with_schema(Json(&verification).into_response(), Schema::AuditVerification),
+        Err(e) => {
+            log::error!("[homebrew] Failed to verify report chain: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+async fn openapi_document(State(config): State<Arc<Config>>) -> Response {
+    Json(openapi::homebrew(config.base_path.as_deref())).into_response()
+}
+
+async fn room_climate(State(config): State<Arc<Config>>) -> Response {
+    match room_readings(&config).await {
+        Ok(readings) => with_schema(Json(&rooms_body(&readings)).into_response(), Schema::Rooms),
+        Err(e) => {
+            log::error!("[homebrew] Failed to select room readings: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// Reports of a registered device id or of every device with a placement (indoor/outdoor/...)
+fn device_filter(device: String) -> FilterParams {
+    if devices::PLACEMENTS.contains(&device.as_str()) {
+        FilterParams { oid: None, device_type: Some(device), device_id: None }
+    } else {
+        FilterParams { oid: None, device_type: None, device_id: Some(device) }
+    }
+}
+
+/// Readings read back for one history series, enough for two weeks at one a minute
+const HISTORY_LIMIT: usize = 24 * 60 * 14;
+
+/// Most reports one range query returns, raw readings or buckets
+pub const RANGE_LIMIT: usize = 10_000;
+
+/// Averages of a bucket of reports, named after the report columns; wind direction is the
+/// circular mean, from -180 to 180 degrees
+pub const BUCKET_AVERAGES: &str = "AVG(temperature) AS temperature, AVG(humidity) AS humidity, AVG(percipitation) AS percipitation, \
+    AVG(pm10) AS pm10, AVG(pm25) AS pm25, AVG(co2) AS co2, AVG(tvoc) AS tvoc, AVG(wind_speed) AS wind_speed, \
+    DEGREES(ATAN2(AVG(SIN(RADIANS(wind_direction))), AVG(COS(RADIANS(wind_direction))))) AS wind_direction, \
+    AVG(battery_voltage) AS battery_voltage, AVG(battery_percent) AS battery_percent, AVG(rssi) AS rssi";
+
+/// Most raw readings one streamed range query sends before handing out a cursor
+pub const STREAM_LIMIT: usize = 1_000_000;
+
+/// Readings read from the store at a time while streaming
+const STREAM_PAGE: usize = 1_000;
+
+/// Reports shown per page of /api/weather_reports/list unless `?limit=` says otherwise
+const LIST_PAGE: usize = 100;
+
+/// Where a page of reports continues: the (timestamp, id) of the last report sent, which the
+/// next page's query seeks past instead of counting rows with OFFSET. Handed to clients as an
+/// opaque token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub timestamp: i64,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn after(report: &WeatherReport) -> Self {
+        Self { timestamp: report.timestamp, id: report.id }
+    }
+
+    pub fn encode(&self) -> String {
+        format!("{:016x}{:08x}", self.timestamp as u64, self.id as u32)
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        if token.len() != 24 || !token.is_ascii() {
+            return None;
+        }
+        let timestamp = u64::from_str_radix(&token[..16], 16).ok()? as i64;
+        let id = u32::from_str_radix(&token[16..], 16).ok()? as i32;
+        Some(Self { timestamp, id })
+    }
+}
+
+/// `?cursor=` of a request, Ok(None) without one
+fn cursor_param(params: &HashMap<String, String>) -> Result<Option<Cursor>, &'static str> {
+    match params.get("cursor").map(|token| Cursor::decode(token)) {
+        None => Ok(None),
+        Some(Some(cursor)) => Ok(Some(cursor)),
+        Some(None) => Err("cursor must be a next_cursor of an earlier response"),
+    }
+}
+
+/// Raw reports of a range as newline-delimited JSON, read a page at a time as the client takes
+/// them. After `limit` reports, with more left, the last line is `{"next_cursor": "..."}` to
+/// pass as `?cursor=` for the rest; a failed query ends the stream with `{"error": "..."}`.
+pub fn stream_range(store: Arc<dyn WeatherStore>, start: i64, end: i64, device_type: Option<String>, after: Option<Cursor>, limit: usize) -> Response {
+    struct Progress {
+        after: Option<Cursor>,
+        sent: usize,
+        done: bool,
+    }
+
+    let pages = futures_util::stream::unfold(Progress { after, sent: 0, done: false }, move |mut progress| {
+        let store = store.clone();
+        let device_type = device_type.clone();
+        async move {
+            if progress.done {
+                return None;
+            }
+            let page = if progress.sent >= limit { 1 } else { STREAM_PAGE.min(limit - progress.sent) };
+            let reports = match store.select_page(start, end, device_type, progress.after, page).await {
+                Ok(reports) => reports,
+                Err(e) => {
+                    log::error!("Failed to stream weather reports between {} and {}: {}", start, end, e);
+                    progress.done = true;
+                    return Some((Ok::<_, std::convert::Infallible>("{\"error\":\"Database error\"}\n".to_string()), progress));
+                },
+            };
+            if reports.is_empty() {
+                return None;
+            }
+            if progress.sent >= limit {
+                // Only checked there is more
+                progress.done = true;
+                let cursor = progress.after?;
+                return Some((Ok(format!("{{\"next_cursor\":\"{}\"}}\n", cursor.encode())), progress));
+            }
+
+            let mut lines = String::with_capacity(reports.len() * 256);
+            for report in &reports {
+                match serde_json::to_string(report) {
+                    Ok(json) => {
+                        lines.push_str(&json);
+                        lines.push('\n');
+                    },
+                    Err(e) => log::error!("Failed to serialize weather report {}: {}", report.oid, e),
+                }
+            }
+            progress.done = reports.len() < page;
+            progress.after = reports.last().map(Cursor::after);
+            progress.sent += reports.len();
+            Some((Ok(lines), progress))
+        }
+    });
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], axum::body::Body::from_stream(pages)).into_response()
+}
+
+/// Reports between `?start=` and `?end=` (Unix seconds, default the last 7 days) of one
+/// `?device_type=`, at `?resolution=` raw (default), 5min, hour or day. Raw readings are smoothed
+/// like other reads unless `?raw=true`; buckets average the readings as stored. Raw readings
+/// past RANGE_LIMIT continue at `?cursor=` with the response's `next_cursor`. With
+/// `?format=ndjson` raw readings are streamed as stored instead, see `stream_range`.
+pub async fn range_response(config: &Config, params: &HashMap<String, String>) -> Response {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let timestamp = |name: &str, default: i64| match params.get(name).map(|v| v.parse::<i64>()) {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(_)) => Err(format!("{} must be a Unix timestamp", name)),
+        None => Ok(default),
+    };
+    let resolution = match params.get("resolution").map(|r| r.parse::<Resolution>()).transpose() {
+        Ok(resolution) => resolution.unwrap_or(Resolution::Raw),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    // Bucketed ranges end at the next bucket boundary by default, so repeated polls of the
+    // latest window ask for the same range and share its cached result
+    let default_end = resolution.bucket_secs().map_or(now, |secs| (now / secs + 1) * secs);
+    let end = match timestamp("end", default_end) {
+        Ok(end) => end,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let start = match timestamp("start", end - 7 * 86_400) {
+        Ok(start) => start,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if start >= end {
+        return (StatusCode::BAD_REQUEST, "start must be before end").into_response();
+    }
+    if let Some(secs) = resolution.bucket_secs() {
+        if (end - start) / secs > RANGE_LIMIT as i64 {
+            return (StatusCode::BAD_REQUEST, format!("More than {} buckets, use a coarser resolution or a shorter range", RANGE_LIMIT)).into_response();
+        }
+    }
+    let device_type = params.get("device_type").cloned();
+    let after = match cursor_param(params) {
+        Ok(Some(_)) if resolution != Resolution::Raw => return (StatusCode::BAD_REQUEST, "cursor only applies to raw readings").into_response(),
+        Ok(after) => after,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+
+    match params.get("format").map(String::as_str) {
+        None | Some("json") => {},
+        Some("ndjson") if resolution == Resolution::Raw => {
+            return stream_range(config.store.clone(), start, end, device_type, after, STREAM_LIMIT);
+        },
+        Some("ndjson") => return (StatusCode::BAD_REQUEST, "Only raw readings can be streamed, use resolution=raw").into_response(),
+        Some(other) => return (StatusCode::BAD_REQUEST, format!("Unsupported format '{}', expected json or ndjson", other)).into_response(),
+    }
+
+    let selected = match resolution {
+        Resolution::Raw => config.store.select_page(start, end, device_type.clone(), after, RANGE_LIMIT + 1).await,
+        _ => WeatherReport::select_range(config.clone(), start, end, device_type.clone(), resolution).await,
+    };
+    match selected {
+        Ok(mut reports) => {
+            let truncated = reports.len() > RANGE_LIMIT;
+            reports.truncate(RANGE_LIMIT);
+            let next_cursor = reports.last().filter(|_| truncated && resolution == Resolution::Raw).map(|last| Cursor::after(last).encode());
+            if resolution == Resolution::Raw && !raw_requested(params) {
+                config.smoothing.smooth_reports(&mut reports);
+            }
+            let range = ReportRange { start, end, device_type, resolution, truncated, next_cursor, reports };
+            with_schema(Json(&range).into_response(), Schema::ReportRange)
+        },
+        Err(e) => {
+            log::error!("Failed to select weather reports between {} and {}: {}", start, end, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// Stored reports newest first as stored, `?limit=` (1-1000, default 100) per page, optionally of
+/// a `?device=` id or placement. The next page is at `?cursor=` with the response's
+/// `next_cursor`. `?offset=` still skips reports but is deprecated: the database counts past
+/// every earlier report for each page.
+pub async fn list_response(config: &Config, params: &HashMap<String, String>) -> Response {
+    let limit = match params.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => LIST_PAGE,
+        Some(Ok(limit)) if InputSanitizer::validate_limit(limit).is_ok() => limit,
+        Some(_) => return (StatusCode::BAD_REQUEST, "limit must be between 1 and 1000").into_response(),
+    };
+    let before = match cursor_param(params) {
+        Ok(before) => before,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let offset = match params.get("offset").map(|offset| offset.parse::<usize>()) {
+        None => None,
+        Some(_) if before.is_some() => return (StatusCode::BAD_REQUEST, "offset can't be combined with cursor").into_response(),
+        Some(Ok(offset)) if InputSanitizer::validate_offset(offset).is_ok() => Some(offset),
+        Some(_) => return (StatusCode::BAD_REQUEST, "offset must be between 0 and 100000").into_response(),
+    };
+
+    match config.store.list(params.get("device").cloned().map(device_filter), before, offset, limit + 1).await {
+        Ok(mut reports) => {
+            let more = reports.len() > limit;
+            reports.truncate(limit);
+            let next_cursor = reports.last().filter(|_| more).map(|last| Cursor::after(last).encode());
+            let mut response = with_schema(Json(&ReportPage { reports, next_cursor }).into_response(), Schema::ReportPage);
+            if offset.is_some() {
+                response.headers_mut().insert("Deprecation", header::HeaderValue::from_static("true"));
+            }
+            response
+        },
+        Err(e) => {
+            log::error!("Failed to list weather reports: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// Series of one report field for charts with offline periods marked, from `?device=`,
+/// `?hours=` (1-336, default 24), `?step=` seconds (60-86400, default 300), `?max_gap=` seconds
+/// (default 900), `?interpolate=true` and `?raw=true`
+pub async fn history_response(config: &Config, field: &str, params: &HashMap<String, String>) -> Response {
+    if !WeatherReport::is_value_field(field) {
+        return (StatusCode::NOT_FOUND, format!("Unknown field '{}'", field)).into_response();
+    }
+    if field == "wind_direction" {
+        return (StatusCode::BAD_REQUEST, "wind_direction can't be averaged into a series").into_response();
+    }
+    fn param(params: &HashMap<String, String>, name: &str, default: i64, range: std::ops::RangeInclusive<i64>) -> Result<i64, String> {
+        match params.get(name).map(|v| v.parse::<i64>()) {
+            Some(Ok(value)) if range.contains(&value) => Ok(value),
+            Some(_) => Err(format!("{} must be between {} and {}", name, range.start(), range.end())),
+            None => Ok(default),
+        }
+    }
+    let (hours, step_secs, max_gap_secs) = match (
+        param(params, "hours", 24, 1..=24 * 14),
+        param(params, "step", history::STEP_SECS, 60..=86_400),
+        param(params, "max_gap", history::MAX_GAP_SECS, 0..=86_400 * 14),
+    ) {
+        (Ok(hours), Ok(step), Ok(max_gap)) => (hours, step, max_gap),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let smoothing = if raw_requested(params) { Smoothing::Off } else { config.smoothing };
+    let device = params.get("device").cloned();
+
+    let end = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let start = end - hours * 3600;
+    match WeatherReport::select(config.clone(), Some(HISTORY_LIMIT), None, Some("timestamp".to_string()), device.clone().map(device_filter)).await {
+        Ok(mut reports) => {
+            smoothing.smooth_reports(&mut reports);
+            let readings: Vec<(i64, f64)> = reports.iter()
+                .filter(|r| r.timestamp >= start)
+                .filter_map(|r| r.value(field).map(|v| (r.timestamp, v)))
+                .collect();
+            let options = HistoryOptions {
+                start,
+                end,
+                step_secs,
+                max_gap_secs,
+                interpolate: params.get("interpolate").map(String::as_str) == Some("true"),
+            };
+            with_schema(Json(&history::series(field, device, &readings, options)).into_response(), Schema::History)
+        },
+        Err(e) => {
+            log::error!("Failed to select weather reports for {} history: {}", field, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// IFTTT/Zapier polling feed of readings of `field` crossing `threshold` (in stored units, so °C
+/// for temperature) over the last `hours` (default 24), optionally only `direction` above or below
+/// and one `device` id or device type
+pub async fn crossings_response(config: &Config, field: &str, params: &HashMap<String, String>) -> Response {
+    let (format, limit) = match triggers::options(params) {
+        Ok(options) => options,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let threshold = match params.get("threshold").map(|v| v.parse::<f64>()) {
+        Some(Ok(threshold)) if threshold.is_finite() => threshold,
+        _ => return (StatusCode::BAD_REQUEST, "threshold must be a number").into_response(),
+    };
+    let direction = match params.get("direction").map(String::as_str) {
+        None | Some("either") => None,
+        Some(value) => match triggers::Direction::parse(value) {
+            Some(direction) => Some(direction),
+            None => return (StatusCode::BAD_REQUEST, "direction must be above, below or either").into_response(),
+        },
+    };
+    let hours = match params.get("hours").map(|v| v.parse::<i64>()) {
+        Some(Ok(hours)) if (1..=24 * 7).contains(&hours) => hours,
+        Some(_) => return (StatusCode::BAD_REQUEST, "hours must be between 1 and 168").into_response(),
+        None => 24,
+    };
+    let smoothing = if raw_requested(params) { Smoothing::Off } else { config.smoothing };
+
+    let start = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0) - hours * 3600;
+    match WeatherReport::select(config.clone(), Some(HISTORY_LIMIT), None, Some("timestamp".to_string()), params.get("device").cloned().map(device_filter)).await {
+        Ok(mut reports) => {
+            smoothing.smooth_reports(&mut reports);
+            reports.retain(|r| r.timestamp >= start);
+            with_schema(Json(triggers::render(triggers::crossings(&reports, field, threshold, direction), format, limit)).into_response(), Schema::TemperatureTrigger)
+        },
+        Err(e) => {
+            log::error!("Failed to select weather reports for {} crossings: {}", field, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// Latest value of one report field as a bare number, for Home Assistant REST sensors and
+/// Node-RED flows. `device` is a registered device id or a device type (indoor/outdoor/greenhouse/other).
+/// With a `locale` the value is converted and formatted for people instead, e.g. "21,4 °C".
+pub async fn value_response(config: &Config, field: &str, device: Option<String>, locale: Option<&Locale>, smoothing: Smoothing) -> Response {
+    if !WeatherReport::is_value_field(field) {
+        return (StatusCode::NOT_FOUND, format!("Unknown field '{}'", field)).into_response();
+    }
+
+    let filter = device.map(device_filter);
+
+    // Not every report carries every field, so look back a little for the latest one that does
+    match WeatherReport::select(config.clone(), Some(50), None, Some("timestamp".to_string()), filter).await {
+        Ok(mut reports) => {
+            smoothing.smooth_reports(&mut reports);
+            match reports.iter().find_map(|r| r.value(field)) {
+                Some(value) => match locale {
+                    Some(locale) => locale.format_value(field, value),
+                    None => value.to_string(),
+                }.into_response(),
+                None => (StatusCode::NOT_FOUND, "No data").into_response(),
+            }
+        },
+        Err(e) => {
+            log::error!("Failed to select weather reports for {}: {}", field, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+/// `report` with SENSOR_SMOOTHING applied over its device's recent readings; unsmoothed if
+/// those can't be read
+pub async fn smoothed(config: &Config, report: WeatherReport) -> WeatherReport {
+    if config.smoothing.is_off() {
+        return report;
+    }
+    let filter = match &report.device_id {
+        Some(id) => FilterParams { oid: None, device_type: None, device_id: Some(id.clone()) },
+        None => FilterParams { oid: None, device_type: Some(report.device_type.clone()), device_id: None },
+    };
+    match WeatherReport::select(config.clone(), Some(smoothing::WINDOW), None, Some("timestamp".to_string()), Some(filter)).await {
+        Ok(mut recent) => {
+            // Reports of a device type include every unregistered device of that type
+            recent.retain(|r| r.device_id == report.device_id);
+            config.smoothing.smooth_reports(&mut recent);
+            recent.into_iter().find(|r| r.oid == report.oid).unwrap_or(report)
+        },
+        Err(e) => {
+            log::warn!("[homebrew] Failed to select readings to smooth: {}", e);
+            report
+        }
+    }
+}
+
+/// US AQI from the most recent report carrying particulate readings
+pub async fn latest_aqi(config: &Config) -> JupiterResult<Option<u32>> {
+    let reports = WeatherReport::select(config.clone(), Some(50), None, Some("timestamp".to_string()), None).await?;
+    Ok(reports.iter()
+        .find(|r| r.pm25.is_some() || r.pm10.is_some())
+        .and_then(|r| aqi::us_aqi(r.pm25, r.pm10)))
+}
+
+/// Battery, signal and last-seen status for every registered device, archived ones only with
+/// `include_archived`
+pub async fn device_statuses(config: &Config, include_archived: bool) -> JupiterResult<Vec<DeviceStatus>> {
+    let registry = config.devices.snapshot();
+    let devices: Vec<&Device> = registry.devices.iter().filter(|d| include_archived || d.state != DeviceState::Archived).collect();
+    statuses_of(config, &devices).await
+}
+
+/// Battery, signal and last-seen status of `devices`
+pub async fn statuses_of(config: &Config, devices: &[&Device]) -> JupiterResult<Vec<DeviceStatus>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    let mut statuses = Vec::new();
+    for device in devices {
+        let filter = FilterParams { oid: None, device_type: None, device_id: Some(device.id.clone()) };
+        let reports = WeatherReport::select(config.clone(), Some(20), None, Some("timestamp".to_string()), Some(filter)).await?;
+        statuses.push(DeviceStatus::new(device, &reports, now));
+    }
+    Ok(statuses)
+}
+
+thread_local! {
+    // Serialization buffer reused by each runtime worker thread
+    static JSON_BUFFER: std::cell::RefCell<Vec<u8>> = std::cell::RefCell::new(Vec::with_capacity(512));
+}
+
+/// JSON response for the latest report, serialized into the worker's reusable buffer
+fn latest_json_response(report: &WeatherReport) -> Response {
+    JSON_BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        match report.write_json(&mut buffer) {
+            Ok(()) => response_format::body_response(ResponseFormat::Json, buffer.clone()),
+            Err(e) => {
+                log::error!("Failed to serialize weather report: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error").into_response()
+            }
+        }
+    })
+}
+
+/// Body of /api/ventilation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VentilationReport {
+    pub hours: i64,
+    pub rooms: Vec<RoomVentilation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomVentilation {
+    pub room: String,
+    pub ventilation: VentilationEstimate,
+}
+
+/// Body of /api/rooms
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomsReport {
+    pub rooms: Vec<RoomClimate>,
+    pub differentials: Vec<RoomDifferential>,
+}
+
+/// Ventilation estimate per room from its CO2 series
+pub fn ventilation_body(hours: i64, rooms: &[(String, Vec<Co2Series>)]) -> VentilationReport {
+    VentilationReport {
+        hours,
+        rooms: rooms.iter().map(|(room, series)| RoomVentilation {
+            room: room.clone(),
+            ventilation: ventilation::estimate_ventilation(series),
+        }).collect(),
+    }
+}
+
+/// Room climate and cross-room differentials from each room's latest readings
+pub fn rooms_body(readings: &[(String, WeatherReport)]) -> RoomsReport {
+    let rooms = rooms::summarize_rooms(readings);
+    let differentials = rooms::room_differentials(&rooms);
+    RoomsReport { rooms, differentials }
+}
+
+/// Latest reading of every registered indoor device with a room, skipping devices
+/// that haven't reported within the last hour
+pub async fn room_readings(config: &Config) -> JupiterResult<Vec<(String, WeatherReport)>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    let mut readings = Vec::new();
+    let registry = config.devices.snapshot();
+    for (room, device) in registry.room_devices() {
+        if let Some(report) = latest_reading(config, &device.id, now).await? {
+            readings.push((room.to_string(), report));
+        }
+    }
+    Ok(readings)
+}
+
+/// Latest reading of a device, smoothed as configured, unless it is more than an hour old
+pub async fn latest_reading(config: &Config, device_id: &str, now: i64) -> JupiterResult<Option<WeatherReport>> {
+    let window = if config.smoothing.is_off() { 1 } else { smoothing::WINDOW };
+    let filter = FilterParams { oid: None, device_type: None, device_id: Some(device_id.to_string()) };
+    let mut latest = WeatherReport::select(config.clone(), Some(window), None, Some("timestamp".to_string()), Some(filter)).await?;
+    config.smoothing.smooth_reports(&mut latest);
+    Ok(latest.into_iter().next().filter(|r| now - r.timestamp < 3600))
+}
+
+/// Summary of the latest readings of the members of `group`, one of `all`
+pub async fn group_climate(config: &Config, all: &[Group], group: &Group) -> JupiterResult<GroupClimate> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let registry = config.devices.snapshot();
+    let members = groups::members(all, group, &registry);
+    let mut readings = Vec::new();
+    for device in &members {
+        readings.extend(latest_reading(config, &device.id, now).await?);
+    }
+    Ok(GroupClimate::new(group, &members, &readings))
+}
+
+/// Groups with notification rules and their current climate
+pub async fn group_climates(config: &Config) -> JupiterResult<Vec<(Group, GroupClimate)>> {
+    let all = groups::select_all().await?;
+    let mut climates = Vec::new();
+    for group in all.iter().filter(|g| !g.rules.is_empty()) {
+        climates.push((group.clone(), group_climate(config, &all, group).await?));
+    }
+    Ok(climates)
+}
+
+/// Lowest and highest temperature of every UTC day on record for one device type, oldest first,
+/// from the daily rollup
+pub async fn daily_temperature_extremes(device_type: &str) -> JupiterResult<Vec<DayExtremes>> {
+    let pool = get_homebrew_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    let rows = client.query(
+        "SELECT bucket / 86400 AS day, MIN(temperature_min) AS low, MAX(temperature_max) AS high FROM weather_reports_daily \
+            WHERE device_type = $1 AND temperature_count > 0 GROUP BY 1 ORDER BY 1",
+        &[&device_type],
+    ).await.map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+    Ok(rows.iter().map(|row| DayExtremes { day: row.get("day"), low: row.get("low"), high: row.get("high") }).collect())
+}
+
+/// CO2 samples (timestamp, ppm) from one device
+pub type Co2Series = Vec<(i64, f64)>;
+
+/// CO2 samples (timestamp, ppm) per room over the last `hours`, one series per device.
+/// Without any rooms in the registry all indoor devices are treated as a single "indoor" room.
+pub async fn room_co2_series(config: &Config, hours: i64) -> JupiterResult<Vec<(String, Vec<Co2Series>)>> {
+    let since = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0) - hours * 3600;
+    let select_co2 = move |filter: FilterParams| async move {
+        let reports = WeatherReport::select(config.clone(), Some(5000), None, Some("timestamp".to_string()), Some(filter)).await?;
+        JupiterResult::Ok(reports.into_iter().filter(|r| r.timestamp >= since && r.co2.is_some()).collect::<Vec<_>>())
+    };
+
+    let mut rooms: Vec<(String, Vec<Co2Series>)> = Vec::new();
+    let registry = config.devices.snapshot();
+    for (room, device) in registry.room_devices() {
+        let reports = select_co2(FilterParams { oid: None, device_type: None, device_id: Some(device.id.clone()) }).await?;
+        let series: Co2Series = reports.iter().filter_map(|r| r.co2.map(|c| (r.timestamp, c))).collect();
+        match rooms.iter_mut().find(|(r, _)| r == room) {
+            Some((_, room_series)) => room_series.push(series),
+            None => rooms.push((room.to_string(), vec![series])),
+        }
+    }
+
+    if rooms.is_empty() {
+        let reports = select_co2(FilterParams { oid: None, device_type: Some("indoor".to_string()), device_id: None }).await?;
+        let mut by_device: Vec<(Option<String>, Co2Series)> = Vec::new();
+        for report in &reports {
+            let sample = (report.timestamp, report.co2.unwrap_or_default());
+            match by_device.iter_mut().find(|(id, _)| *id == report.device_id) {
+                Some((_, series)) => series.push(sample),
+                None => by_device.push((report.device_id.clone(), vec![sample])),
+            }
+        }
+        rooms.push(("indoor".to_string(), by_device.into_iter().map(|(_, series)| series).collect()));
+    }
+    Ok(rooms)
+}
+
 // Stored in SQL in cache_timeout is set
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WeatherReport {
@@ -312,9 +1692,20 @@ pub struct WeatherReport {
     pub pm25: Option<f64>,
     pub co2: Option<f64>,
     pub tvoc: Option<f64>,
+    pub wind_speed: Option<f64>, // m/s
+    pub wind_direction: Option<f64>, // degrees
+    pub battery_voltage: Option<f64>, // volts
+    pub battery_percent: Option<f64>,
+    pub rssi: Option<f64>, // dBm, signal strength at the receiving gateway
     pub device_type: String, // indoor, outdoor, other
+    pub device_id: Option<String>, // Registered device the reading came from, if known
     pub timestamp: i64
 }
+// Single-row query behind the latest-report fast path; explicit columns so rows are read by position
+pub const LATEST_REPORT_QUERY: &str = "SELECT id, oid, temperature, humidity, percipitation, pm10, pm25, co2, tvoc, \
+    wind_speed, wind_direction, battery_voltage, battery_percent, rssi, device_type, device_id, timestamp \
+    FROM weather_reports ORDER BY timestamp DESC, id DESC LIMIT 1";
+
 impl WeatherReport {
     pub fn new() -> WeatherReport {
         let oid: String = thread_rng().sample_iter(&Alphanumeric).take(15).map(char::from).collect();
@@ -335,15 +1726,63 @@ impl WeatherReport {
             pm25: None,
             co2: None,
             tvoc: None,
+            wind_speed: None,
+            wind_direction: None,
+            battery_voltage: None,
+            battery_percent: None,
+            rssi: None,
             device_type: String::from("other"),
+            device_id: None,
             timestamp: timestamp
         }
     }
+    pub fn is_value_field(field: &str) -> bool {
+        matches!(field, "temperature" | "humidity" | "percipitation" | "precipitation" | "pm10" | "pm25"
+            | "co2" | "tvoc" | "wind_speed" | "wind_direction" | "battery_voltage" | "battery_percent" | "rssi")
+    }
+
+    /// Reading for a field accepted by `is_value_field`
+    pub fn value(&self, field: &str) -> Option<f64> {
+        match field {
+            "temperature" => self.temperature,
+            "humidity" => self.humidity,
+            "percipitation" | "precipitation" => self.percipitation,
+            "pm10" => self.pm10,
+            "pm25" => self.pm25,
+            "co2" => self.co2,
+            "tvoc" => self.tvoc,
+            "wind_speed" => self.wind_speed,
+            "wind_direction" => self.wind_direction,
+            "battery_voltage" => self.battery_voltage,
+            "battery_percent" => self.battery_percent,
+            "rssi" => self.rssi,
+            _ => None,
+        }
+    }
+
+    /// Mutable reading for a field accepted by `is_value_field`
+    pub fn value_mut(&mut self, field: &str) -> Option<&mut Option<f64>> {
+        match field {
+            "temperature" => Some(&mut self.temperature),
+            "humidity" => Some(&mut self.humidity),
+            "percipitation" | "precipitation" => Some(&mut self.percipitation),
+            "pm10" => Some(&mut self.pm10),
+            "pm25" => Some(&mut self.pm25),
+            "co2" => Some(&mut self.co2),
+            "tvoc" => Some(&mut self.tvoc),
+            "wind_speed" => Some(&mut self.wind_speed),
+            "wind_direction" => Some(&mut self.wind_direction),
+            "battery_voltage" => Some(&mut self.battery_voltage),
+            "battery_percent" => Some(&mut self.battery_percent),
+            "rssi" => Some(&mut self.rssi),
+            _ => None,
+        }
+    }
     pub fn sql_table_name() -> String {
         return format!("weather_reports")
     }
     pub fn sql_build_statement() -> &'static str {
-        "CREATE TABLE public.weather_reports (
+        "CREATE TABLE IF NOT EXISTS public.weather_reports (
             id serial NOT NULL,
             oid varchar NOT NULL UNIQUE,
             temperature DOUBLE PRECISION NULL,
@@ -353,124 +1792,65 @@ impl WeatherReport {
             pm25 DOUBLE PRECISION NULL,
             co2 DOUBLE PRECISION NULL,
             tvoc DOUBLE PRECISION NULL,
+            wind_speed DOUBLE PRECISION NULL,
+            wind_direction DOUBLE PRECISION NULL,
+            battery_voltage DOUBLE PRECISION NULL,
+            battery_percent DOUBLE PRECISION NULL,
+            rssi DOUBLE PRECISION NULL,
             device_type VARCHAR NULL,
+            device_id VARCHAR NULL,
             timestamp BIGINT DEFAULT 0,
             CONSTRAINT weather_reports_pkey PRIMARY KEY (id));"
     }
+    /// Upgrades of tables created by earlier releases, part of homebrew migration 3; later
+    /// schema changes are new migrations in `migrations::homebrew`
     pub fn migrations() -> Vec<&'static str> {
         vec![
-            "",
+            "ALTER TABLE weather_reports ADD COLUMN IF NOT EXISTS wind_speed DOUBLE PRECISION NULL;",
+            "ALTER TABLE weather_reports ADD COLUMN IF NOT EXISTS wind_direction DOUBLE PRECISION NULL;",
+            "ALTER TABLE weather_reports ADD COLUMN IF NOT EXISTS device_id VARCHAR NULL;",
+            "ALTER TABLE weather_reports ADD COLUMN IF NOT EXISTS battery_voltage DOUBLE PRECISION NULL;",
+            "ALTER TABLE weather_reports ADD COLUMN IF NOT EXISTS battery_percent DOUBLE PRECISION NULL;",
+            "ALTER TABLE weather_reports ADD COLUMN IF NOT EXISTS rssi DOUBLE PRECISION NULL;",
+            "CREATE INDEX IF NOT EXISTS weather_reports_timestamp_idx ON weather_reports (timestamp DESC, id DESC);",
+            "CREATE INDEX IF NOT EXISTS weather_reports_device_id_idx ON weather_reports (device_id);",
+            // Enforced for new reports only: older ones may name devices that were never in the table
+            "DO $$ BEGIN
+                IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'weather_reports_device_id_fkey') THEN
+                    ALTER TABLE weather_reports ADD CONSTRAINT weather_reports_device_id_fkey
+                        FOREIGN KEY (device_id) REFERENCES devices (id) ON DELETE SET NULL NOT VALID;
+                END IF;
+            END $$;",
         ]
     }
-    pub fn save(&self, config: Config) -> JupiterResult<&Self> {
-        // Use async runtime to get connection from pool
-        let runtime = tokio::runtime::Runtime::new()
-            .map_err(|e| {
-                log::error!("Failed to create tokio runtime: {}", e);
-                JupiterError::RuntimeError(format!("Failed to create runtime: {}", e))
-            })?;
-        
-        let mut client = runtime.block_on(async {
-            let pool = get_homebrew_pool()
-                .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".into()))?;
-            
-            pool.get_connection_with_retry(3).await
-                .map_err(|e| {
-                    log::error!("Failed to get database connection: {}", e);
-                    JupiterError::DatabaseError(format!("Connection pool exhausted: {}", e))
-                })
-        })?;
-
-        // Search for OID matches using secure parameterized query
-        let rows = Self::select_by_oid(
-            config.clone(),
-            &self.oid
-        )?;
-
-        if rows.len() == 0 {
-            runtime.block_on(async {
-                client.execute("INSERT INTO weather_reports (oid, device_type, timestamp) VALUES ($1, $2, $3)",
-                    &[&self.oid as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.device_type as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.timestamp as &(dyn tokio_postgres::types::ToSql + Sync)]
-                ).await
-            })?;
-        } 
-
-        if self.temperature.is_some() {
-            runtime.block_on(async {
-                client.execute("UPDATE weather_reports SET temperature = $1 WHERE oid = $2;", 
-                &[
-                    &self.temperature as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.oid as &(dyn tokio_postgres::types::ToSql + Sync)
-                ]).await
-            })?;
-        }
-
-        if self.humidity.is_some() {
-            runtime.block_on(async {
-                client.execute("UPDATE weather_reports SET humidity = $1 WHERE oid = $2;", 
-                &[
-                    &self.humidity as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.oid as &(dyn tokio_postgres::types::ToSql + Sync)
-                ]).await
-            })?;
-        }
-
-        if self.percipitation.is_some() {
-            runtime.block_on(async {
-                client.execute("UPDATE weather_reports SET percipitation = $1 WHERE oid = $2;", 
-                &[
-                    &self.percipitation as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.oid as &(dyn tokio_postgres::types::ToSql + Sync)
-                ]).await
-            })?;
-        }
-
-        if self.pm10.is_some() {
-            runtime.block_on(async {
-                client.execute("UPDATE weather_reports SET pm10 = $1 WHERE oid = $2;", 
-                &[
-                    &self.pm10 as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.oid as &(dyn tokio_postgres::types::ToSql + Sync)
-                ]).await
-            })?;
-        }
-
-        if self.pm25.is_some() {
-            runtime.block_on(async {
-                client.execute("UPDATE weather_reports SET pm25 = $1 WHERE oid = $2;", 
-                &[
-                    &self.pm25 as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.oid as &(dyn tokio_postgres::types::ToSql + Sync)
-                ]).await
-            })?;
-        }
-
-        if self.co2.is_some() {
-            runtime.block_on(async {
-                client.execute("UPDATE weather_reports SET co2 = $1 WHERE oid = $2;", 
-                &[
-                    &self.co2 as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.oid as &(dyn tokio_postgres::types::ToSql + Sync)
-                ]).await
-            })?;
-        }
-
-        if self.tvoc.is_some() {
-            runtime.block_on(async {
-                client.execute("UPDATE weather_reports SET tvoc = $1 WHERE oid = $2;", 
-                &[
-                    &self.tvoc as &(dyn tokio_postgres::types::ToSql + Sync),
-                    &self.oid as &(dyn tokio_postgres::types::ToSql + Sync)
-                ]).await
-            })?;
+    pub async fn save(&self, config: Config) -> JupiterResult<&Self> {
+        // Usage is counted in the Postgres tables
+        if config.store.uses_postgres() {
+            usage::check(self.device_id.as_deref()).await?;
+        }
+        config.store.save(self).await?;
+        usage::record(self.device_id.as_deref());
+        query_cache::RANGE_CACHE.invalidate(&self.device_type, self.timestamp);
+        REPORTS_SAVED.inc(&[&self.device_type]);
+        influx::report(self);
+        alert_rules::observe(self);
+
+        if config.hash_chain {
+            // Chain the row as stored, which may merge earlier saves of the same report
+            if let Some(stored) = Self::select_by_oid(config.clone(), &self.oid).await?.into_iter().next() {
+                let pool = get_homebrew_pool()
+                    .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".into()))?;
+                let mut client = pool.get_connection_with_retry(3).await
+                    .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+                report_chain::append(&mut client, &stored).await?;
+            }
         }
 
+        live::publish(LiveUpdate::Report(self.clone()));
         return Ok(self);
     }
     // Secure method to select by OID using parameterized query
-    pub fn select_by_oid(config: Config, oid: &str) -> JupiterResult<Vec<Self>> {
+    pub async fn select_by_oid(_config: Config, oid: &str) -> JupiterResult<Vec<Self>> {
         // Validate OID input before using in query
         if !InputSanitizer::validate_oid(oid) {
             log::error!("Invalid OID format detected: {}", oid);
@@ -480,42 +1860,101 @@ impl WeatherReport {
             log::error!("Potential SQL injection detected in OID: {}", oid);
         }
         
-        // Use async runtime to get connection from pool
-        let runtime = tokio::runtime::Runtime::new()
-            .map_err(|e| JupiterError::DatabaseError(format!("Failed to create runtime: {}", e)))?;
-        runtime.block_on(async {
-            let pool = get_homebrew_pool()
-                .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
-            
-            let client = pool.get_connection_with_retry(3).await
-                .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
-            
-            let query = "SELECT * FROM weather_reports WHERE oid = $1 ORDER BY id DESC";
-            let rows = client.query(query, &[&oid]).await
-                .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
-            
-            let mut parsed_rows: Vec<Self> = Vec::new();
-            for row in rows {
-                parsed_rows.push(Self::from_row(&row)
-                    .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e)))?);
-            }
-            
-            Ok(parsed_rows)
-        })
+        let pool = get_homebrew_pool()
+            .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+        
+        let client = pool.get_connection_with_retry(3).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+        
+        let query = "SELECT * FROM weather_reports WHERE oid = $1 ORDER BY id DESC";
+        let rows = client.query(query, &[&oid]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+        
+        let mut parsed_rows: Vec<Self> = Vec::new();
+        for row in rows {
+            parsed_rows.push(Self::from_row(&row)
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e)))?);
+        }
+        
+        Ok(parsed_rows)
     }
     
     // Secure select method with parameterized queries
-    pub fn select(config: Config, limit: Option<usize>, offset: Option<usize>, order_column: Option<String>, filter_params: Option<FilterParams>) -> JupiterResult<Vec<Self>> {
+    pub async fn select(config: Config, limit: Option<usize>, offset: Option<usize>, order_column: Option<String>, filter_params: Option<FilterParams>) -> JupiterResult<Vec<Self>> {
+        config.store.select(limit, offset, order_column, filter_params).await
+    }
+    /// Reports with `start_ts <= timestamp < end_ts`, oldest first, of one device type or all.
+    /// Bucketed reports average each field over the bucket (wind direction as a circular mean),
+    /// start at the bucket's timestamp and have no id, oid or device id. At most RANGE_LIMIT + 1
+    /// are returned so callers can tell when raw readings were cut off. Bucketed results are
+    /// cached until a report is saved in their range, see `query_cache`.
+    pub async fn select_range(config: Config, start_ts: i64, end_ts: i64, device_type: Option<String>, resolution: Resolution) -> JupiterResult<Vec<Self>> {
+        let cache = &query_cache::RANGE_CACHE;
+        let key = match resolution.bucket_secs() {
+            Some(bucket_secs) if cache.enabled() => RangeKey { start: start_ts, end: end_ts, device_type: device_type.clone(), bucket_secs },
+            _ => return config.store.select_range(start_ts, end_ts, device_type, resolution).await,
+        };
+        if let Some(reports) = cache.get(&key) {
+            return Ok(reports.as_ref().clone());
+        }
+        let generation = cache.generation();
+        let reports = config.store.select_range(start_ts, end_ts, device_type, resolution).await?;
+        cache.insert(key, reports.clone(), generation);
+        Ok(reports)
+    }
+    /// SQL for `select_range`, taking $1 start, $2 end and $3 an optional device type
+    pub fn range_query(resolution: Resolution) -> String {
+        let filter = "timestamp >= $1 AND timestamp < $2 AND ($3::varchar IS NULL OR device_type = $3)";
+        match resolution.bucket_secs() {
+            None => format!("SELECT * FROM weather_reports WHERE {} ORDER BY timestamp ASC, id ASC LIMIT {}", filter, RANGE_LIMIT + 1),
+            Some(secs) => format!(
+                "SELECT 0 AS id, '' AS oid, (timestamp / {secs}) * {secs} AS timestamp, device_type, NULL::varchar AS device_id, {averages} \
+                FROM weather_reports WHERE {filter} \
+                GROUP BY 3, device_type ORDER BY 3 ASC, device_type ASC LIMIT {limit}",
+                secs = secs, averages = BUCKET_AVERAGES, filter = filter, limit = RANGE_LIMIT + 1,
+            ),
+        }
+    }
+    /// Conditions of `filter_params` with their positional parameters
+    fn filter_conditions(filter_params: Option<FilterParams>) -> (Vec<String>, Vec<String>) {
+        let mut params: Vec<String> = Vec::new();
+        let mut conditions: Vec<String> = Vec::new();
+        if let Some(ref filters) = filter_params {
+            for (column, value) in [("oid", &filters.oid), ("device_type", &filters.device_type), ("device_id", &filters.device_id)] {
+                if let Some(value) = value {
+                    params.push(value.clone());
+                    conditions.push(format!("{} = ${}", column, params.len()));
+                }
+            }
+        }
+        (conditions, params)
+    }
+    /// SQL and positional parameters for `WeatherStore::list`; the cursor's timestamp and id
+    /// are integers written into the query, filters are bound
+    pub fn list_query(filter_params: Option<FilterParams>, before: Option<Cursor>, offset: Option<usize>, limit: usize) -> (String, Vec<String>) {
+        let (mut conditions, params) = Self::filter_conditions(filter_params);
+        if let Some(before) = before {
+            conditions.push(format!("(timestamp, id) < ({}, {})", before.timestamp, before.id));
+        }
+        let mut query = String::from("SELECT * FROM weather_reports");
+        if !conditions.is_empty() {
+            query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        query.push_str(&format!(" ORDER BY timestamp DESC, id DESC LIMIT {}", limit));
+        if let Some(offset) = offset {
+            query.push_str(&format!(" OFFSET {}", offset));
+        }
+        (query, params)
+    }
+    /// SQL and positional parameters for `select`
+    pub fn select_query(limit: Option<usize>, offset: Option<usize>, order_column: Option<String>, filter_params: Option<FilterParams>) -> (String, Vec<String>) {
         // Build secure query with parameterized placeholders
         let mut query = String::from("SELECT * FROM weather_reports");
-        let mut param_count = 0;
         
         // Add WHERE clause if filter parameters provided
-        if let Some(ref filters) = filter_params {
-            if let Some(ref oid) = filters.oid {
-                param_count += 1;
-                query.push_str(&format!(" WHERE oid = ${}", param_count));
-            }
+        let (conditions, params) = Self::filter_conditions(filter_params);
+        if !conditions.is_empty() {
+            query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
         }
         
         // Add ORDER BY clause (validate column name against whitelist)
@@ -537,40 +1976,45 @@ impl WeatherReport {
             query.push_str(&format!(" OFFSET {}", offset_val));
         }
         
-        // Use async runtime to get connection from pool
-        let runtime = tokio::runtime::Runtime::new()
-            .map_err(|e| JupiterError::DatabaseError(format!("Failed to create runtime: {}", e)))?;
-        runtime.block_on(async {
-            let pool = get_homebrew_pool()
-                .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
-            
-            let client = pool.get_connection_with_retry(3).await
-                .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
-            
-            // Execute query with appropriate parameters
-            let rows = if let Some(ref filters) = filter_params {
-                if let Some(ref oid) = filters.oid {
-                    client.query(&query, &[oid]).await
-                        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
-                } else {
-                    client.query(&query, &[]).await
-                        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
-                }
-            } else {
-                client.query(&query, &[]).await
-                    .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
-            };
-            
-            let mut parsed_rows: Vec<Self> = Vec::new();
-            for row in rows {
-                parsed_rows.push(Self::from_row(&row)
-                    .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e)))?);
-            }
-
-            Ok(parsed_rows)
-        })
+        (query, params)
+    }
+    /// Newest report using the prepared LATEST_REPORT_QUERY, for the GET /api/weather_reports hot path
+    pub async fn latest(client: &deadpool_postgres::Client) -> JupiterResult<Option<Self>> {
+        let statement = client.prepare_cached(LATEST_REPORT_QUERY).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to prepare latest report query: {}", e)))?;
+        let row = client.query_opt(&statement, &[]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+        Ok(row.map(|row| Self::from_latest_row(&row)))
+    }
+    // Columns by position, in LATEST_REPORT_QUERY order
+    fn from_latest_row(row: &Row) -> Self {
+        Self {
+            id: row.get(0),
+            oid: row.get(1),
+            temperature: row.get(2),
+            humidity: row.get(3),
+            percipitation: row.get(4),
+            pm10: row.get(5),
+            pm25: row.get(6),
+            co2: row.get(7),
+            tvoc: row.get(8),
+            wind_speed: row.get(9),
+            wind_direction: row.get(10),
+            battery_voltage: row.get(11),
+            battery_percent: row.get(12),
+            rssi: row.get(13),
+            device_type: row.get(14),
+            device_id: row.get(15),
+            timestamp: row.get(16),
+        }
     }
-    fn from_row(row: &Row) -> JupiterResult<Self> {
+    /// Serializes as JSON into `out`, reusing its capacity
+    pub fn write_json(&self, out: &mut Vec<u8>) -> JupiterResult<()> {
+        out.clear();
+        serde_json::to_writer(out, self)?;
+        Ok(())
+    }
+    pub(crate) fn from_row(row: &Row) -> JupiterResult<Self> {
         return Ok(Self {
             id: row.get("id"),
             oid: row.get("oid"),
@@ -581,18 +2025,24 @@ impl WeatherReport {
             pm25: row.get("pm25"),
             co2: row.get("co2"),
             tvoc: row.get("tvoc"),
+            wind_speed: row.get("wind_speed"),
+            wind_direction: row.get("wind_direction"),
+            battery_voltage: row.get("battery_voltage"),
+            battery_percent: row.get("battery_percent"),
+            rssi: row.get("rssi"),
             device_type: row.get("device_type"),
+            device_id: row.get("device_id"),
             timestamp: row.get("timestamp"),
         });
     }
 }
 
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct PostgresServer {
 	pub db_name: String,
     pub username: String,
-    pub password: String,
+    pub password: Secret<String>,
 	pub address: String
 }
 impl PostgresServer {
@@ -6,9 +6,19 @@ use super::common::{
     HistoricalData, RateLimiter
 };
 use std::sync::Arc;
-use crate::provider::homebrew::{Config, WeatherReport, PostgresServer};
+use crate::provider::homebrew::{daily_temperature_extremes, device_statuses, group_climates, room_readings, Config, WeatherReport};
 use crate::utils::time::safe_timestamp_with_fallback;
-use std::collections::HashMap;
+use crate::analysis::baseline::{self, DayExtremes};
+use crate::rollup::{self, DailySummary};
+use crate::analysis::comfort::comfort;
+use crate::analysis::conditions::ConditionCode;
+use crate::analysis::road::{estimate_road_condition, temperature_trend};
+use crate::analysis::rooms::{room_differentials, summarize_rooms};
+use crate::analysis::smoothing;
+use crate::alert_rules;
+use crate::units::{Speed, Temperature};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // Helper function to safely get current timestamp
 fn get_current_timestamp() -> Result<i64, WeatherError> {
@@ -68,19 +78,23 @@ impl HomebrewProvider {
     async fn get_latest_reports(&self, device_type: Option<&str>, limit: usize) -> Result<Vec<WeatherReport>, WeatherError> {
         let filter = device_type.map(|dt| crate::provider::homebrew::FilterParams {
             oid: None,
+            device_type: Some(dt.to_string()),
+            device_id: None,
         });
         
-        WeatherReport::select(self.config.clone(), Some(limit), None, Some("timestamp".to_string()), filter)
+        WeatherReport::select(self.config.clone(), Some(limit), None, Some("timestamp".to_string()), filter).await
             .map_err(|e| WeatherError::DatabaseError(e.to_string()))
     }
     
     async fn get_aggregated_data(&self, device_types: &[String]) -> Result<AggregatedData, WeatherError> {
         let mut all_reports = Vec::new();
+        let limit = if self.config.smoothing.is_off() { 10 } else { smoothing::WINDOW };
         
         for device_type in device_types {
-            let reports = self.get_latest_reports(Some(device_type), 10).await?;
+            let reports = self.get_latest_reports(Some(device_type), limit).await?;
             all_reports.extend(reports);
         }
+        self.config.smoothing.smooth_reports(&mut all_reports);
         
         if all_reports.is_empty() {
             return Err(WeatherError::NotFound("No data available".to_string()));
@@ -101,6 +115,10 @@ impl HomebrewProvider {
             .filter_map(|r| r.temperature)
             .collect();
         
+        let temperature_samples: Vec<(i64, f64)> = recent_reports.iter()
+            .filter_map(|r| r.temperature.map(|t| (r.timestamp, t)))
+            .collect();
+        
         let humidities: Vec<f64> = recent_reports.iter()
             .filter_map(|r| r.humidity)
             .collect();
@@ -125,9 +143,20 @@ impl HomebrewProvider {
             .filter_map(|r| r.tvoc)
             .collect();
         
+        let wind_speeds: Vec<f64> = recent_reports.iter()
+            .filter_map(|r| r.wind_speed)
+            .collect();
+        
+        // Directions are averaged as unit vectors so 350° and 10° average to 0°, not 180°
+        let (wind_x, wind_y, wind_direction_count) = recent_reports.iter()
+            .filter_map(|r| r.wind_direction)
+            .fold((0.0, 0.0, 0usize), |(x, y, n), deg| {
+                (x + deg.to_radians().sin(), y + deg.to_radians().cos(), n + 1)
+            });
+        
         Ok(AggregatedData {
             temperature: if temperatures.is_empty() { None } else {
-                Some(temperatures.iter().sum::<f64>() / temperatures.len() as f64)
+                Some(Temperature::from_celsius(temperatures.iter().sum::<f64>() / temperatures.len() as f64))
             },
             humidity: if humidities.is_empty() { None } else {
                 Some(humidities.iter().sum::<f64>() / humidities.len() as f64)
@@ -147,64 +176,24 @@ impl HomebrewProvider {
             tvoc: if tvocs.is_empty() { None } else {
                 Some(tvocs.iter().sum::<f64>() / tvocs.len() as f64)
             },
+            // Reports store wind speeds in m/s
+            wind_speed: if wind_speeds.is_empty() { None } else {
+                Some(Speed::from_meters_per_second(wind_speeds.iter().sum::<f64>() / wind_speeds.len() as f64))
+            },
+            wind_direction: if wind_direction_count == 0 { None } else {
+                Some((wind_x.atan2(wind_y).to_degrees() + 360.0) % 360.0)
+            },
+            temperature_trend: temperature_trend(&temperature_samples),
             count: recent_reports.len(),
         })
     }
     
-    async fn get_historical_aggregated(&self, device_types: &[String], days: u8) -> Result<Vec<DailyAggregatedData>, WeatherError> {
-        let mut daily_data = HashMap::new();
+    /// Days of the last `days` from the daily rollup, rather than aggregating raw reports
+    async fn get_historical_aggregated(&self, device_types: &[String], days: u8) -> Result<Vec<DailySummary>, WeatherError> {
         let now = safe_timestamp_with_fallback();
-        let start_time = now - (days as i64 * 86400);
-        
-        for device_type in device_types {
-            let reports = self.get_latest_reports(Some(device_type), 1000).await?;
-            
-            for report in reports {
-                if report.timestamp >= start_time {
-                    let day = report.timestamp / 86400;
-                    let entry = daily_data.entry(day).or_insert_with(|| DailyAggregatedData {
-                        date: format_timestamp(day * 86400),
-                        temperatures: Vec::new(),
-                        humidities: Vec::new(),
-                        precipitations: Vec::new(),
-                        pm25s: Vec::new(),
-                        pm10s: Vec::new(),
-                        co2s: Vec::new(),
-                        tvocs: Vec::new(),
-                    });
-                    
-                    if let Some(temp) = report.temperature {
-                        entry.temperatures.push(temp);
-                    }
-                    if let Some(hum) = report.humidity {
-                        entry.humidities.push(hum);
-                    }
-                    if let Some(precip) = report.percipitation {
-                        entry.precipitations.push(precip);
-                    }
-                    if let Some(pm25) = report.pm25 {
-                        entry.pm25s.push(pm25);
-                    }
-                    if let Some(pm10) = report.pm10 {
-                        entry.pm10s.push(pm10);
-                    }
-                    if let Some(co2) = report.co2 {
-                        entry.co2s.push(co2);
-                    }
-                    if let Some(tvoc) = report.tvoc {
-                        entry.tvocs.push(tvoc);
-                    }
-                }
-            }
-        }
-        
-        let mut result: Vec<_> = daily_data.into_iter()
-            .map(|(_, data)| data)
-            .collect();
-        
-        result.sort_by(|a, b| a.date.cmp(&b.date));
-        
-        Ok(result)
+        let since_day = (now - days as i64 * 86400).div_euclid(86400);
+        rollup::daily_summaries(device_types, since_day..now.div_euclid(86400) + 1).await
+            .map_err(|e| WeatherError::DatabaseError(e.to_string()))
     }
 }
 
@@ -213,6 +202,9 @@ impl WeatherProvider for HomebrewProvider {
     async fn get_current_weather(&self, location: &str) -> Result<Weather, WeatherError> {
         let location_info = self.get_location_info(location)?;
         let aggregated = self.get_aggregated_data(&location_info.device_types).await?;
+        // Rather than a made-up 0°C that would win sensor fusion for temperature
+        let temperature = aggregated.temperature
+            .ok_or_else(|| WeatherError::NotFound("No recent temperature readings".to_string()))?;
         
         let description = format!(
             "Homebrew Weather Station - {} sensors reporting",
@@ -240,17 +232,29 @@ impl WeatherProvider for HomebrewProvider {
         };
         
         Ok(Weather {
-            temperature: aggregated.temperature.unwrap_or(0.0),
+            temperature,
             feels_like: None,
             humidity: aggregated.humidity,
             pressure: None,
-            wind_speed: None,
-            wind_direction: None,
+            wind_speed: aggregated.wind_speed,
+            wind_direction: aggregated.wind_direction,
             description: full_description,
+            // A station can only tell that it's raining, not what the sky looks like
+            condition: match aggregated.precipitation {
+                Some(p) if p > 0.0 => ConditionCode::Rain,
+                _ => ConditionCode::Unknown,
+            },
             icon: None,
             precipitation: aggregated.precipitation,
             visibility: None,
             uv_index: None,
+            road_condition: Some(estimate_road_condition(
+                temperature,
+                aggregated.humidity,
+                aggregated.precipitation,
+                aggregated.temperature_trend,
+            )),
+            comfort: Some(comfort(temperature, aggregated.humidity, aggregated.wind_speed, None)),
             provider: "Homebrew".to_string(),
             location: Location {
                 latitude: location_info.latitude,
@@ -261,37 +265,43 @@ impl WeatherProvider for HomebrewProvider {
                 postal_code: None,
             },
             timestamp: safe_timestamp_with_fallback(),
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
         })
     }
     
     async fn get_forecast(&self, location: &str, days: u8) -> Result<Forecast, WeatherError> {
         let location_info = self.get_location_info(location)?;
         let historical = self.get_historical_aggregated(&location_info.device_types, days).await?;
+        // Days that stand out from earlier years are called out; the summaries are served without
+        // them if the records can't be read
+        let extremes = daily_temperature_extremes("outdoor").await.unwrap_or_else(|e| {
+            log::warn!("Failed to read daily temperature extremes: {}", e);
+            Vec::new()
+        });
         
         let daily = historical.iter()
             .map(|day| {
-                let temp_min = day.temperatures.iter().cloned().fold(f64::INFINITY, f64::min);
-                let temp_max = day.temperatures.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-                let humidity_avg = if day.humidities.is_empty() { None } else {
-                    Some(day.humidities.iter().sum::<f64>() / day.humidities.len() as f64)
-                };
-                let precipitation_total = if day.precipitations.is_empty() { None } else {
-                    Some(day.precipitations.iter().sum())
-                };
-                
                 DailyForecast {
-                    date: day.date.clone(),
-                    temperature_min: if temp_min.is_finite() { temp_min } else { 0.0 },
-                    temperature_max: if temp_max.is_finite() { temp_max } else { 0.0 },
-                    humidity: humidity_avg,
+                    date: format_timestamp(day.day * 86400),
+                    temperature_min: Temperature::from_celsius(day.temperature_min.unwrap_or(0.0)),
+                    temperature_max: Temperature::from_celsius(day.temperature_max.unwrap_or(0.0)),
+                    humidity: day.humidity_avg,
                     precipitation_probability: None,
-                    precipitation_amount: precipitation_total,
+                    precipitation_amount: day.precipitation_total,
                     wind_speed: None,
                     wind_direction: None,
-                    description: "Homebrew historical data".to_string(),
+                    description: match unusual_messages(&extremes, day.day) {
+                        messages if messages.is_empty() => "Homebrew historical data".to_string(),
+                        messages => format!("Homebrew historical data. {}", messages.join(". ")),
+                    },
+                    condition: ConditionCode::Unknown,
                     icon: None,
                     sunrise: None,
                     sunset: None,
+                    solar_irradiance: None,
+                    day: None,
+                    night: None,
                 }
             })
             .collect();
@@ -313,17 +323,25 @@ impl WeatherProvider for HomebrewProvider {
     
     async fn get_alerts(&self, _location: &str) -> Result<Vec<Alert>, WeatherError> {
         let outdoor_data = self.get_aggregated_data(&vec!["outdoor".to_string()]).await.ok();
-        let indoor_data = self.get_aggregated_data(&vec!["indoor".to_string()]).await.ok();
         
         let mut alerts = Vec::new();
         
+        // PM2.5, CO2, TVOC and other thresholds, see alert_rules
+        alerts.extend(alert_rules::active_alerts());
+        
         if let Some(data) = &outdoor_data {
-            if let Some(pm25) = data.pm25 {
-                if pm25 > 35.0 {
+            if let Some(temperature) = data.temperature {
+                let road = estimate_road_condition(temperature, data.humidity, data.precipitation, data.temperature_trend);
+                if road.frost_risk {
                     alerts.push(Alert {
-                        title: "Poor Air Quality (PM2.5)".to_string(),
-                        description: format!("PM2.5 levels are elevated at {:.1} µg/m³", pm25),
-                        severity: if pm25 > 55.0 { AlertSeverity::Severe } else { AlertSeverity::Moderate },
+                        title: "Road Frost / Black Ice Risk".to_string(),
+                        description: format!(
+                            "Road surfaces estimated at {:.1}°C ({:.0}% confidence): {}",
+                            road.estimated_surface_temperature,
+                            road.confidence * 100.0,
+                            road.factors.join("; ")
+                        ),
+                        severity: if road.confidence >= 0.8 { AlertSeverity::Severe } else { AlertSeverity::Moderate },
                         start: format_timestamp(safe_timestamp_with_fallback()),
                         end: None,
                         regions: vec!["Outdoor".to_string()],
@@ -332,32 +350,88 @@ impl WeatherProvider for HomebrewProvider {
             }
         }
         
-        if let Some(data) = &indoor_data {
-            if let Some(co2) = data.co2 {
-                if co2 > 1000.0 {
+        match room_readings(&self.config).await {
+            Ok(readings) => {
+                for differential in room_differentials(&summarize_rooms(&readings)) {
                     alerts.push(Alert {
-                        title: "High CO2 Levels".to_string(),
-                        description: format!("Indoor CO2 levels are elevated at {:.0} ppm", co2),
-                        severity: if co2 > 2000.0 { AlertSeverity::Severe } else { AlertSeverity::Moderate },
+                        title: "Room Climate Differential".to_string(),
+                        description: differential.message,
+                        severity: AlertSeverity::Minor,
                         start: format_timestamp(safe_timestamp_with_fallback()),
                         end: None,
-                        regions: vec!["Indoor".to_string()],
+                        regions: vec![differential.room, differential.reference_room],
                     });
                 }
-            }
-            
-            if let Some(tvoc) = data.tvoc {
-                if tvoc > 500.0 {
+            },
+            Err(e) => log::warn!("Failed to read room climate: {}", e),
+        }
+        
+        match device_statuses(&self.config, false).await {
+            Ok(statuses) => {
+                for status in statuses {
+                    if status.low_battery {
+                        let level = match (status.battery_percent, status.battery_voltage) {
+                            (Some(percent), _) => format!("{:.0}%", percent),
+                            (None, Some(voltage)) => format!("{:.2} V", voltage),
+                            (None, None) => "unknown".to_string(),
+                        };
+                        alerts.push(Alert {
+                            title: "Low Battery".to_string(),
+                            description: format!("{} battery is low ({})", status.name, level),
+                            severity: if status.battery_percent.is_some_and(|p| p < 10.0) { AlertSeverity::Moderate } else { AlertSeverity::Minor },
+                            start: format_timestamp(safe_timestamp_with_fallback()),
+                            end: None,
+                            regions: vec![status.id.clone()],
+                        });
+                    }
+                    if status.weak_signal {
+                        alerts.push(Alert {
+                            title: "Weak Signal".to_string(),
+                            description: format!("{} signal is weak ({:.0} dBm)", status.name, status.rssi.unwrap_or_default()),
+                            severity: AlertSeverity::Minor,
+                            start: format_timestamp(safe_timestamp_with_fallback()),
+                            end: None,
+                            regions: vec![status.id.clone()],
+                        });
+                    }
+                }
+            },
+            Err(e) => log::warn!("Failed to read device status: {}", e),
+        }
+        
+        match group_climates(&self.config).await {
+            Ok(climates) => {
+                for (group, climate) in climates {
+                    for (message, severity) in climate.breaches(&group) {
+                        alerts.push(Alert {
+                            title: "Group Rule".to_string(),
+                            description: message,
+                            severity,
+                            start: format_timestamp(safe_timestamp_with_fallback()),
+                            end: None,
+                            regions: vec![group.id.clone()],
+                        });
+                    }
+                }
+            },
+            Err(e) => log::warn!("Failed to read group climate: {}", e),
+        }
+        
+        match daily_temperature_extremes("outdoor").await {
+            Ok(extremes) => {
+                let today = safe_timestamp_with_fallback().div_euclid(86400);
+                for unusual in extremes.iter().filter(|d| d.day == today).flat_map(|d| baseline::unusual(&extremes, d)) {
                     alerts.push(Alert {
-                        title: "High TVOC Levels".to_string(),
-                        description: format!("Indoor TVOC levels are elevated at {:.0} ppb", tvoc),
-                        severity: if tvoc > 1000.0 { AlertSeverity::Severe } else { AlertSeverity::Moderate },
+                        title: if unusual.record { "Weather Record" } else { "Unusual Weather" }.to_string(),
+                        description: unusual.message,
+                        severity: AlertSeverity::Minor,
                         start: format_timestamp(safe_timestamp_with_fallback()),
                         end: None,
-                        regions: vec!["Indoor".to_string()],
+                        regions: vec!["Outdoor".to_string()],
                     });
                 }
-            }
+            },
+            Err(e) => log::warn!("Failed to read daily temperature extremes: {}", e),
         }
         
         Ok(alerts)
@@ -368,34 +442,12 @@ impl WeatherProvider for HomebrewProvider {
         let timestamp = parse_date_to_timestamp(date)
             .ok_or_else(|| WeatherError::ParseError("Invalid date format".to_string()))?;
         
-        let start_time = timestamp;
-        let end_time = timestamp + 86400;
-        
-        let mut temperatures = Vec::new();
-        let mut humidities = Vec::new();
-        let mut precipitations = Vec::new();
-        
-        for device_type in &location_info.device_types {
-            let reports = self.get_latest_reports(Some(device_type), 1000).await?;
-            
-            for report in reports {
-                if report.timestamp >= start_time && report.timestamp < end_time {
-                    if let Some(temp) = report.temperature {
-                        temperatures.push(temp);
-                    }
-                    if let Some(hum) = report.humidity {
-                        humidities.push(hum);
-                    }
-                    if let Some(precip) = report.percipitation {
-                        precipitations.push(precip);
-                    }
-                }
-            }
-        }
-        
-        if temperatures.is_empty() {
-            return Err(WeatherError::NotFound(format!("No data available for date: {}", date)));
-        }
+        let day = timestamp.div_euclid(86400);
+        let summary = rollup::daily_summaries(&location_info.device_types, day..day + 1).await
+            .map_err(|e| WeatherError::DatabaseError(e.to_string()))?
+            .into_iter()
+            .find(|summary| summary.temperature_avg.is_some())
+            .ok_or_else(|| WeatherError::NotFound(format!("No data available for date: {}", date)))?;
         
         Ok(HistoricalData {
             location: Location {
@@ -408,15 +460,11 @@ impl WeatherProvider for HomebrewProvider {
             },
             provider: "Homebrew".to_string(),
             date: date.to_string(),
-            temperature_min: temperatures.iter().cloned().fold(f64::INFINITY, f64::min),
-            temperature_max: temperatures.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
-            temperature_avg: temperatures.iter().sum::<f64>() / temperatures.len() as f64,
-            humidity_avg: if humidities.is_empty() { None } else {
-                Some(humidities.iter().sum::<f64>() / humidities.len() as f64)
-            },
-            precipitation_total: if precipitations.is_empty() { None } else {
-                Some(precipitations.iter().sum())
-            },
+            temperature_min: summary.temperature_min.unwrap_or_default(),
+            temperature_max: summary.temperature_max.unwrap_or_default(),
+            temperature_avg: summary.temperature_avg.unwrap_or_default(),
+            humidity_avg: summary.humidity_avg,
+            precipitation_total: summary.precipitation_total,
             wind_speed_avg: None,
         })
     }
@@ -434,30 +482,32 @@ impl WeatherProvider for HomebrewProvider {
             WeatherFeature::HourlyForecast => false,
             WeatherFeature::UvIndex => false,
             WeatherFeature::AirQuality => true,
+            WeatherFeature::Pollen => false,
+            WeatherFeature::Marine => false,
+            WeatherFeature::MinutelyForecast => false,
         }
     }
 }
 
 struct AggregatedData {
-    temperature: Option<f64>,
+    temperature: Option<Temperature>,
     humidity: Option<f64>,
     precipitation: Option<f64>,
     pm25: Option<f64>,
     pm10: Option<f64>,
     co2: Option<f64>,
     tvoc: Option<f64>,
+    wind_speed: Option<Speed>,
+    wind_direction: Option<f64>,
+    temperature_trend: Option<f64>,   // °C per hour over the recent window
     count: usize,
 }
 
-struct DailyAggregatedData {
-    date: String,
-    temperatures: Vec<f64>,
-    humidities: Vec<f64>,
-    precipitations: Vec<f64>,
-    pm25s: Vec<f64>,
-    pm10s: Vec<f64>,
-    co2s: Vec<f64>,
-    tvocs: Vec<f64>,
+/// How the outdoor temperatures of `day` stand out from earlier years, if at all
+fn unusual_messages(extremes: &[DayExtremes], day: i64) -> Vec<String> {
+    extremes.iter().find(|d| d.day == day)
+        .map(|today| baseline::unusual(extremes, today).into_iter().map(|unusual| unusual.message).collect())
+        .unwrap_or_default()
 }
 
 fn format_timestamp(ts: i64) -> String {
@@ -491,13 +541,13 @@ pub async fn create_weather_report(
     report.tvoc = tvoc;
     report.device_type = device_type;
     
-    report.save(config)
+    report.save(config).await
         .map_err(|e| WeatherError::DatabaseError(e.to_string()))?;
     Ok(report)
 }
 
 pub async fn get_latest_weather_report(config: Config) -> Result<Option<WeatherReport>, WeatherError> {
-    WeatherReport::select(config, Some(1), None, Some("timestamp".to_string()), None)
+    WeatherReport::select(config, Some(1), None, Some("timestamp".to_string()), None).await
         .map(|reports| reports.into_iter().next())
         .map_err(|e| WeatherError::DatabaseError(e.to_string()))
 }
@@ -509,8 +559,10 @@ pub async fn get_weather_reports_by_device(
 ) -> Result<Vec<WeatherReport>, WeatherError> {
     let filter = crate::provider::homebrew::FilterParams {
         oid: None,
+        device_type: Some(device_type),
+        device_id: None,
     };
     
-    WeatherReport::select(config, Some(limit), None, Some("timestamp".to_string()), Some(filter))
+    WeatherReport::select(config, Some(limit), None, Some("timestamp".to_string()), Some(filter)).await
         .map_err(|e| WeatherError::DatabaseError(e.to_string()))
 }
\ No newline at end of file
@@ -0,0 +1,188 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use super::common::{RateLimiter, TidePrediction, TideType, WeatherError};
+
+// NOAA marine data (US only): tide predictions and water temperature from CO-OPS
+// (Tides & Currents) and wave/swell observations from NDBC buoys. Neither service requires
+// an API key, so stations are configured by id (see NOAA_TIDE_STATION / NOAA_BUOY_STATION).
+
+#[derive(Debug, Clone, Default)]
+pub struct BuoyObservation {
+    pub wave_height: Option<f64>,
+    pub wave_period: Option<f64>,
+    pub wave_direction: Option<f64>,
+    pub swell_height: Option<f64>,
+    pub swell_period: Option<f64>,
+    pub swell_direction: Option<f64>,
+    pub water_temperature: Option<f64>,
+}
+
+pub struct NoaaMarineClient {
+    tides_url: String,
+    buoy_url: String,
+    rate_limiter: Arc<RateLimiter>,
+    client: reqwest::Client,
+}
+
+impl NoaaMarineClient {
+    pub fn new() -> Self {
+        Self {
+            tides_url: "https://api.tidesandcurrents.noaa.gov/api/prod/datagetter".to_string(),
+            buoy_url: "https://www.ndbc.noaa.gov/data/realtime2".to_string(),
+            rate_limiter: Arc::new(RateLimiter::new(60, 60)),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get_text(&self, url: &str) -> Result<String, WeatherError> {
+        if !self.rate_limiter.check_rate_limit() {
+            return Err(WeatherError::RateLimitExceeded);
+        }
+
+        let response = self.client.get(url)
+            .send()
+            .await?;
+
+        if response.status() == 404 {
+            return Err(WeatherError::NotFound(format!("NOAA resource not found: {}", url)));
+        }
+
+        Ok(response.text().await?)
+    }
+
+    /// High/low tide predictions for the next 48 hours
+    pub async fn get_tide_predictions(&self, station: &str) -> Result<Vec<TidePrediction>, WeatherError> {
+        let url = format!("{}?product=predictions&application=jupiter&date=today&range=48&datum=MLLW&station={}&time_zone=lst_ldt&units=metric&interval=hilo&format=json",
+            self.tides_url, station);
+        parse_tide_predictions(&self.get_text(&url).await?)
+    }
+
+    /// Latest water temperature reading in Celsius, if the station has a sensor
+    pub async fn get_water_temperature(&self, station: &str) -> Result<Option<f64>, WeatherError> {
+        let url = format!("{}?product=water_temperature&application=jupiter&date=latest&station={}&time_zone=lst_ldt&units=metric&format=json",
+            self.tides_url, station);
+        parse_water_temperature(&self.get_text(&url).await?)
+    }
+
+    /// Latest standard meteorological and spectral wave observations from an NDBC buoy
+    pub async fn get_buoy_observation(&self, station: &str) -> Result<BuoyObservation, WeatherError> {
+        let standard = parse_ndbc_latest(&self.get_text(&format!("{}/{}.txt", self.buoy_url, station)).await?)?;
+
+        // Not every buoy publishes spectral summaries; swell stays empty in that case
+        let spectral = match self.get_text(&format!("{}/{}.spec", self.buoy_url, station)).await {
+            Ok(text) => parse_ndbc_latest(&text).unwrap_or_default(),
+            Err(WeatherError::NotFound(_)) => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(BuoyObservation {
+            wave_height: ndbc_value(&standard, "WVHT"),
+            wave_period: ndbc_value(&standard, "DPD"),
+            wave_direction: ndbc_value(&standard, "MWD"),
+            swell_height: ndbc_value(&spectral, "SwH"),
+            swell_period: ndbc_value(&spectral, "SwP"),
+            swell_direction: ndbc_value(&spectral, "SwD")
+                .or_else(|| ndbc_value(&standard, "MWD")),
+            water_temperature: ndbc_value(&standard, "WTMP"),
+        })
+    }
+}
+
+impl Default for NoaaMarineClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CoopsPredictionsResponse {
+    predictions: Option<Vec<CoopsPrediction>>,
+    error: Option<CoopsError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoopsPrediction {
+    t: String,
+    v: String,
+    #[serde(rename = "type")]
+    tide_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoopsDataResponse {
+    data: Option<Vec<CoopsObservation>>,
+    error: Option<CoopsError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoopsObservation {
+    v: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CoopsError {
+    message: String,
+}
+
+pub fn parse_tide_predictions(json: &str) -> Result<Vec<TidePrediction>, WeatherError> {
+    let response: CoopsPredictionsResponse = serde_json::from_str(json)?;
+    if let Some(error) = response.error {
+        return Err(WeatherError::NotFound(format!("NOAA CO-OPS: {}", error.message)));
+    }
+
+    let mut tides = Vec::new();
+    for prediction in response.predictions.unwrap_or_default() {
+        let height = prediction.v.trim().parse::<f64>()
+            .map_err(|_| WeatherError::ParseError(format!("Invalid tide height: {}", prediction.v)))?;
+        let tide_type = match prediction.tide_type.as_str() {
+            "H" | "HH" => TideType::High,
+            "L" | "LL" => TideType::Low,
+            other => return Err(WeatherError::ParseError(format!("Unknown tide type: {}", other))),
+        };
+        tides.push(TidePrediction {
+            time: prediction.t,
+            height,
+            tide_type,
+        });
+    }
+
+    Ok(tides)
+}
+
+pub fn parse_water_temperature(json: &str) -> Result<Option<f64>, WeatherError> {
+    let response: CoopsDataResponse = serde_json::from_str(json)?;
+    if response.error.is_some() {
+        // Stations without a temperature sensor report an error rather than an empty list
+        return Ok(None);
+    }
+
+    Ok(response.data
+        .and_then(|data| data.into_iter().last())
+        .and_then(|obs| obs.v.trim().parse::<f64>().ok()))
+}
+
+/// Parses the most recent row of an NDBC realtime2 table into a column -> value map.
+/// The first header line names the columns (prefixed with '#'), the second gives units.
+pub fn parse_ndbc_latest(text: &str) -> Result<HashMap<String, String>, WeatherError> {
+    let mut lines = text.lines();
+    let header = lines.next()
+        .ok_or_else(|| WeatherError::ParseError("Empty NDBC response".to_string()))?;
+    let columns: Vec<&str> = header.trim_start_matches('#').split_whitespace().collect();
+
+    let row = lines
+        .find(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .ok_or_else(|| WeatherError::NotFound("No NDBC observations available".to_string()))?;
+
+    Ok(columns.iter()
+        .zip(row.split_whitespace())
+        .map(|(column, value)| (column.to_string(), value.to_string()))
+        .collect())
+}
+
+// NDBC marks missing values as "MM"
+fn ndbc_value(row: &HashMap<String, String>, column: &str) -> Option<f64> {
+    row.get(column)
+        .filter(|v| v.as_str() != "MM")
+        .and_then(|v| v.parse::<f64>().ok())
+}
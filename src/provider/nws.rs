@@ -0,0 +1,528 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use super::common::{
+    Alert, AlertSeverity, DailyForecast, DayPartForecast, Forecast, HourlyForecast, Location, LocationQuery,
+    RateLimiter, Weather, WeatherError, WeatherFeature, WeatherProvider,
+};
+use crate::analysis::comfort::comfort;
+use crate::analysis::conditions::ConditionCode;
+use crate::analysis::road::estimate_road_condition;
+use crate::archive;
+use crate::units::{Pressure, Speed, Temperature};
+use crate::utils::time::{parse_rfc3339, safe_timestamp_with_fallback};
+
+// US National Weather Service forecasts, observations and alerts from api.weather.gov. The
+// API is free and keyless but has no geocoder: locations must be "latitude,longitude", which
+// a /points lookup resolves to the forecast grid and nearby observation stations.
+
+// api.weather.gov rejects requests without an identifying User-Agent
+const USER_AGENT: &str = "jupiter (https://github.com/ktheindifferent/jupiter)";
+
+#[derive(Debug, Deserialize)]
+struct NwsPointResponse {
+    properties: NwsPoint,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NwsPoint {
+    forecast: String,
+    forecast_hourly: String,
+    observation_stations: String,
+    relative_location: Option<NwsRelativeLocation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct NwsRelativeLocation {
+    properties: NwsPlace,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct NwsPlace {
+    city: String,
+    state: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NwsForecastResponse {
+    properties: NwsPeriods,
+}
+
+#[derive(Debug, Deserialize)]
+struct NwsPeriods {
+    periods: Vec<NwsPeriod>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NwsPeriod {
+    pub start_time: String,
+    pub is_daytime: bool,
+    pub temperature: f64,
+    #[serde(default)]
+    pub temperature_unit: String,
+    #[serde(default)]
+    pub probability_of_precipitation: Option<NwsValue>,
+    #[serde(default)]
+    pub relative_humidity: Option<NwsValue>,
+    #[serde(default)]
+    pub wind_speed: Option<String>,
+    #[serde(default)]
+    pub wind_direction: Option<String>,
+    pub icon: Option<String>,
+    pub short_forecast: String,
+}
+
+/// A measurement with its WMO unit code, e.g. {"unitCode": "wmoUnit:degC", "value": 21.1};
+/// `value` is null when the station didn't report it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NwsValue {
+    #[serde(default)]
+    pub unit_code: String,
+    pub value: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NwsStationsResponse {
+    features: Vec<NwsStation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NwsStation {
+    properties: NwsStationProperties,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NwsStationProperties {
+    station_identifier: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NwsObservationResponse {
+    geometry: Option<NwsGeometry>,
+    properties: NwsObservation,
+}
+
+#[derive(Debug, Deserialize)]
+struct NwsGeometry {
+    coordinates: Vec<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NwsObservation {
+    timestamp: String,
+    #[serde(default)]
+    text_description: String,
+    icon: Option<String>,
+    temperature: Option<NwsValue>,
+    wind_direction: Option<NwsValue>,
+    wind_speed: Option<NwsValue>,
+    barometric_pressure: Option<NwsValue>,
+    visibility: Option<NwsValue>,
+    relative_humidity: Option<NwsValue>,
+    wind_chill: Option<NwsValue>,
+    heat_index: Option<NwsValue>,
+    precipitation_last_hour: Option<NwsValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NwsAlertsResponse {
+    features: Vec<NwsAlert>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NwsAlert {
+    properties: NwsAlertProperties,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NwsAlertProperties {
+    event: String,
+    headline: Option<String>,
+    #[serde(default)]
+    description: String,
+    severity: String,
+    onset: Option<String>,
+    effective: Option<String>,
+    ends: Option<String>,
+    expires: Option<String>,
+    #[serde(default)]
+    area_desc: String,
+}
+
+/// Cloning shares the rate limit and the grid point cache
+#[derive(Clone)]
+pub struct NwsProvider {
+    base_url: String,
+    rate_limiter: Arc<RateLimiter>,
+    client: reqwest::Client,
+    // Grid points never move, so each location is looked up once
+    points: Arc<Mutex<HashMap<String, NwsPoint>>>,
+}
+
+impl NwsProvider {
+    pub fn new() -> Self {
+        Self {
+            base_url: "https://api.weather.gov".to_string(),
+            rate_limiter: Arc::new(RateLimiter::new(60, 60)),
+            client: reqwest::Client::new(),
+            points: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: &str, endpoint: &str, location: &str) -> Result<T, WeatherError> {
+        if !self.rate_limiter.check_rate_limit() {
+            return Err(WeatherError::RateLimitExceeded);
+        }
+
+        let response = self.client.get(url)
+            .header("User-Agent", USER_AGENT)
+            .header("Accept", "application/geo+json")
+            .send()
+            .await?;
+
+        if response.status() == 404 {
+            // /points answers 404 for locations outside the US
+            return Err(WeatherError::NotFound(format!("NWS has no data for {}", location)));
+        }
+
+        archive::json("nws", endpoint, location, response).await
+    }
+
+    fn coordinates(location: &str) -> Result<(f64, f64), WeatherError> {
+        match LocationQuery::parse(location).map_err(WeatherError::ConfigurationError)? {
+            LocationQuery::Coordinates { latitude, longitude } => Ok((latitude, longitude)),
+            _ => Err(WeatherError::ConfigurationError(format!(
+                "NWS needs the location as latitude,longitude, got {}", location))),
+        }
+    }
+
+    async fn get_point(&self, location: &str) -> Result<(Location, NwsPoint), WeatherError> {
+        let (latitude, longitude) = Self::coordinates(location)?;
+        let cached = self.points.lock()
+            .map_err(|_| WeatherError::ConfigurationError("NWS point cache poisoned".to_string()))?
+            .get(location)
+            .cloned();
+        let point = match cached {
+            Some(point) => point,
+            None => {
+                // More than four decimal places is answered with a redirect
+                let url = format!("{}/points/{:.4},{:.4}", self.base_url, latitude, longitude);
+                let response: NwsPointResponse = self.get(&url, "points", location).await?;
+                if let Ok(mut points) = self.points.lock() {
+                    points.insert(location.to_string(), response.properties.clone());
+                }
+                response.properties
+            },
+        };
+
+        let place = point.relative_location.as_ref().map(|r| &r.properties);
+        Ok((Location {
+            latitude,
+            longitude,
+            name: place.map(|p| p.city.clone()).unwrap_or_else(|| location.to_string()),
+            country: Some("US".to_string()),
+            region: place.map(|p| p.state.clone()),
+            postal_code: None,
+        }, point))
+    }
+
+    async fn get_periods(&self, url: &str, endpoint: &str, location: &str) -> Result<Vec<NwsPeriod>, WeatherError> {
+        // SI units: Celsius and km/h, like the other providers
+        let response: NwsForecastResponse = self.get(&format!("{}?units=si", url), endpoint, location).await?;
+        Ok(response.properties.periods)
+    }
+}
+
+impl Default for NwsProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Value and WMO unit code of an NWS measurement, e.g. (3.2, "m_s-1")
+fn reading(value: &Option<NwsValue>) -> Option<(f64, &str)> {
+    let value = value.as_ref()?;
+    Some((value.value?, value.unit_code.trim_start_matches("wmoUnit:")))
+}
+
+fn temperature(value: &Option<NwsValue>) -> Option<Temperature> {
+    Some(match reading(value)? {
+        (v, "degF") => Temperature::from_fahrenheit(v),
+        (v, _) => Temperature::from_celsius(v),
+    })
+}
+
+fn speed(value: &Option<NwsValue>) -> Option<Speed> {
+    Some(match reading(value)? {
+        (v, "m_s-1") => Speed::from_meters_per_second(v),
+        (v, "kt") => Speed::from_knots(v),
+        (v, _) => Speed::from_kmh(v),
+    })
+}
+
+fn pressure(value: &Option<NwsValue>) -> Option<Pressure> {
+    Some(match reading(value)? {
+        (v, "Pa") => Pressure::from_pascals(v),
+        (v, _) => Pressure::from_hpa(v),
+    })
+}
+
+/// Length of an NWS measurement in the units the other providers use: mm, or km for distances
+/// given in m
+fn length(value: &Option<NwsValue>) -> Option<f64> {
+    Some(match reading(value)? {
+        (v, "m") => v / 1000.0,
+        (v, _) => v,
+    })
+}
+
+/// Upper end of an NWS wind speed such as "10 to 15 km/h" or "5 mph"
+pub fn parse_wind_speed(text: &str) -> Option<Speed> {
+    let speed = text.split_whitespace()
+        .filter_map(|word| word.parse::<f64>().ok())
+        .fold(None, |max: Option<f64>, v| Some(max.map_or(v, |m| m.max(v))))?;
+    Some(if text.contains("mph") { Speed::from_mph(speed) } else { Speed::from_kmh(speed) })
+}
+
+/// Degrees of a 16-point compass direction, e.g. "NW" -> 315
+pub fn compass_degrees(direction: &str) -> Option<f64> {
+    const POINTS: [&str; 16] = ["N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW", "NNW"];
+    POINTS.iter()
+        .position(|p| p.eq_ignore_ascii_case(direction.trim()))
+        .map(|i| i as f64 * 22.5)
+}
+
+fn period_temperature(period: &NwsPeriod) -> Temperature {
+    if period.temperature_unit == "F" {
+        Temperature::from_fahrenheit(period.temperature)
+    } else {
+        Temperature::from_celsius(period.temperature)
+    }
+}
+
+fn hourly_forecast(period: &NwsPeriod) -> HourlyForecast {
+    HourlyForecast {
+        datetime: period.start_time.clone(),
+        temperature: period_temperature(period),
+        feels_like: None,
+        humidity: period.relative_humidity.as_ref().and_then(|h| h.value),
+        precipitation_probability: period.probability_of_precipitation.as_ref().and_then(|p| p.value),
+        precipitation_amount: None,
+        wind_speed: period.wind_speed.as_deref().and_then(parse_wind_speed),
+        wind_direction: period.wind_direction.as_deref().and_then(compass_degrees),
+        description: period.short_forecast.clone(),
+        condition: ConditionCode::from_description(&period.short_forecast),
+        icon: period.icon.clone(),
+        cloud_cover: None,
+        solar_irradiance: None,
+    }
+}
+
+/// Folds the 12-hour day and night periods of an NWS forecast into one entry per date: the
+/// high comes from the daytime period, the low from the night, and a date with only one of
+/// them (e.g. a forecast issued in the evening starts with "Tonight") uses it for both. Each
+/// period is also kept as the date's day or night half.
+pub fn daily_forecasts(periods: &[NwsPeriod]) -> Vec<DailyForecast> {
+    let mut daily: Vec<DailyForecast> = Vec::new();
+    for period in periods {
+        let date = period.start_time.get(..10).unwrap_or(&period.start_time).to_string();
+        let temperature = period_temperature(period);
+        let precipitation = period.probability_of_precipitation.as_ref().and_then(|p| p.value);
+        let part = DayPartForecast {
+            description: period.short_forecast.clone(),
+            condition: ConditionCode::from_description(&period.short_forecast),
+            icon: period.icon.clone(),
+            precipitation_probability: precipitation,
+            wind_speed: period.wind_speed.as_deref().and_then(parse_wind_speed),
+            wind_direction: period.wind_direction.as_deref().and_then(compass_degrees),
+            ..DayPartForecast::default()
+        };
+
+        match daily.iter_mut().find(|d| d.date == date) {
+            Some(day) => {
+                if period.is_daytime {
+                    day.temperature_max = temperature;
+                    day.description = period.short_forecast.clone();
+                    day.condition = ConditionCode::from_description(&period.short_forecast);
+                    day.icon = period.icon.clone();
+                    day.day = Some(part);
+                } else {
+                    day.temperature_min = temperature;
+                    day.night = Some(part);
+                }
+                if day.temperature_max < day.temperature_min {
+                    day.temperature_min = day.temperature_max;
+                }
+                day.precipitation_probability = match (day.precipitation_probability, precipitation) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    (a, b) => a.or(b),
+                };
+            },
+            None => daily.push(DailyForecast {
+                date,
+                temperature_min: temperature,
+                temperature_max: temperature,
+                humidity: None,
+                precipitation_probability: precipitation,
+                precipitation_amount: None,
+                wind_speed: part.wind_speed,
+                wind_direction: part.wind_direction,
+                description: part.description.clone(),
+                condition: part.condition,
+                icon: part.icon.clone(),
+                sunrise: None,
+                sunset: None,
+                solar_irradiance: None,
+                day: period.is_daytime.then(|| part.clone()),
+                night: (!period.is_daytime).then_some(part),
+            }),
+        }
+    }
+    daily
+}
+
+fn alert_severity(severity: &str) -> AlertSeverity {
+    match severity {
+        "Extreme" => AlertSeverity::Extreme,
+        "Severe" => AlertSeverity::Severe,
+        "Moderate" => AlertSeverity::Moderate,
+        _ => AlertSeverity::Minor,
+    }
+}
+
+fn observation_weather(observation: &NwsObservation, location: Location) -> Result<Weather, WeatherError> {
+    let temperature = temperature(&observation.temperature)
+        .ok_or_else(|| WeatherError::NotFound("Latest NWS observation has no temperature".to_string()))?;
+    let humidity = observation.relative_humidity.as_ref().and_then(|h| h.value);
+    let wind_speed = speed(&observation.wind_speed);
+    let precipitation = length(&observation.precipitation_last_hour);
+    let visibility = length(&observation.visibility);
+
+    Ok(Weather {
+        temperature,
+        feels_like: self::temperature(&observation.wind_chill).or_else(|| self::temperature(&observation.heat_index)),
+        humidity,
+        pressure: pressure(&observation.barometric_pressure),
+        wind_speed,
+        wind_direction: observation.wind_direction.as_ref().and_then(|d| d.value),
+        description: observation.text_description.clone(),
+        condition: ConditionCode::from_description(&observation.text_description),
+        icon: observation.icon.clone(),
+        precipitation,
+        visibility,
+        uv_index: None,
+        road_condition: Some(estimate_road_condition(temperature, humidity, precipitation, None)),
+        comfort: Some(comfort(temperature, humidity, wind_speed, None)),
+        provider: "NWS".to_string(),
+        location,
+        timestamp: parse_rfc3339(&observation.timestamp).unwrap_or_else(safe_timestamp_with_fallback),
+        sources: Vec::new(),
+        spread: BTreeMap::new(),
+    })
+}
+
+/// Re-normalizes an archived `current` payload (a latest-observation response); `location` is
+/// the configured location it was fetched for
+pub fn normalize_current(payload: &serde_json::Value, location: &str) -> Result<Weather, WeatherError> {
+    let response: NwsObservationResponse = serde_json::from_value(payload.clone())?;
+    // GeoJSON coordinates are [longitude, latitude]
+    let (longitude, latitude) = match response.geometry.as_ref().map(|g| g.coordinates.as_slice()) {
+        Some([longitude, latitude, ..]) => (*longitude, *latitude),
+        _ => (0.0, 0.0),
+    };
+    observation_weather(&response.properties, Location {
+        latitude,
+        longitude,
+        name: location.to_string(),
+        country: Some("US".to_string()),
+        region: None,
+        postal_code: None,
+    })
+}
+
+#[async_trait]
+impl WeatherProvider for NwsProvider {
+    async fn get_current_weather(&self, location: &str) -> Result<Weather, WeatherError> {
+        let (place, point) = self.get_point(location).await?;
+
+        // Stations are listed nearest first
+        let stations: NwsStationsResponse = self.get(&point.observation_stations, "stations", location).await?;
+        let station = stations.features.first()
+            .map(|s| s.properties.station_identifier.clone())
+            .ok_or_else(|| WeatherError::NotFound(format!("No NWS observation stations near {}", location)))?;
+
+        let url = format!("{}/stations/{}/observations/latest", self.base_url, station);
+        let observation: NwsObservationResponse = self.get(&url, "current", location).await?;
+        observation_weather(&observation.properties, place)
+    }
+
+    async fn get_forecast(&self, location: &str, days: u8) -> Result<Forecast, WeatherError> {
+        let (place, point) = self.get_point(location).await?;
+
+        let periods = self.get_periods(&point.forecast, "forecast", location).await?;
+        let mut daily = daily_forecasts(&periods);
+        daily.truncate(days as usize);
+
+        let hourly = self.get_periods(&point.forecast_hourly, "forecast_hourly", location).await?
+            .iter()
+            .take(48)
+            .map(hourly_forecast)
+            .collect();
+
+        Ok(Forecast {
+            location: place,
+            provider: "NWS".to_string(),
+            daily,
+            hourly: Some(hourly),
+        })
+    }
+
+    async fn get_alerts(&self, location: &str) -> Result<Vec<Alert>, WeatherError> {
+        let (latitude, longitude) = Self::coordinates(location)?;
+        let url = format!("{}/alerts/active?point={:.4},{:.4}", self.base_url, latitude, longitude);
+        let response: NwsAlertsResponse = self.get(&url, "alerts", location).await?;
+
+        Ok(response.features.into_iter()
+            .map(|alert| {
+                let alert = alert.properties;
+                Alert {
+                    title: alert.headline.unwrap_or(alert.event),
+                    description: alert.description,
+                    severity: alert_severity(&alert.severity),
+                    start: alert.onset.or(alert.effective).unwrap_or_default(),
+                    end: alert.ends.or(alert.expires),
+                    regions: alert.area_desc.split(';')
+                        .map(|area| area.trim().to_string())
+                        .filter(|area| !area.is_empty())
+                        .collect(),
+                }
+            })
+            .collect())
+    }
+
+    fn name(&self) -> &str {
+        "NWS"
+    }
+
+    fn supports_feature(&self, feature: WeatherFeature) -> bool {
+        match feature {
+            WeatherFeature::CurrentWeather => true,
+            WeatherFeature::Forecast => true,
+            WeatherFeature::Alerts => true,
+            WeatherFeature::HourlyForecast => true,
+            WeatherFeature::UvIndex => false,
+            WeatherFeature::AirQuality => false,
+            WeatherFeature::Pollen => false,
+            WeatherFeature::Marine => false,
+            WeatherFeature::MinutelyForecast => false,
+            WeatherFeature::HistoricalData => false,
+        }
+    }
+}
@@ -3,22 +3,29 @@ use serde::{Deserialize, Serialize};
 use super::common::{
     Weather, WeatherError, WeatherProvider, Forecast, Alert, Location, 
     DailyForecast, HourlyForecast, AlertSeverity, WeatherFeature, 
-    HistoricalData, RateLimiter
+    HistoricalData, RateLimiter, LocationQuery
 };
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
+use crate::archive;
+use crate::secret::Secret;
+use crate::analysis::comfort::comfort;
+use crate::analysis::conditions::ConditionCode;
+use crate::analysis::road::estimate_road_condition;
+use crate::units::{Pressure, Speed, Temperature};
 
 pub struct OpenWeatherProvider {
-    api_key: String,
+    api_key: Secret<String>,
     base_url: String,
     rate_limiter: Arc<RateLimiter>,
     client: reqwest::Client,
 }
 
 impl OpenWeatherProvider {
-    pub fn new(api_key: String) -> Self {
+    pub fn new(api_key: impl Into<Secret<String>>) -> Self {
         Self {
-            api_key,
+            api_key: api_key.into(),
             base_url: "https://api.openweathermap.org".to_string(),
             rate_limiter: Arc::new(RateLimiter::new(60, 60)), // 60 requests per minute for free tier
             client: reqwest::Client::new(),
@@ -30,15 +37,23 @@ impl OpenWeatherProvider {
             return Err(WeatherError::RateLimitExceeded);
         }
         
-        let url = if location.chars().all(|c| c.is_digit(10)) {
-            format!("{}/geo/1.0/zip?zip={}&appid={}", 
-                self.base_url, location, self.api_key)
-        } else {
-            format!("{}/geo/1.0/direct?q={}&limit=1&appid={}", 
-                self.base_url, location, self.api_key)
+        let query = LocationQuery::parse(location).map_err(WeatherError::ConfigurationError)?;
+        // OpenWeather assumes the US for postal codes without a country
+        let (url, params) = match &query {
+            LocationQuery::PostalCode { country, code } => (format!("{}/geo/1.0/zip", self.base_url),
+                vec![("zip", format!("{},{}", code, country.as_deref().unwrap_or("US")))]),
+            LocationQuery::City { name, country } => (format!("{}/geo/1.0/direct", self.base_url),
+                vec![("q", match country {
+                    Some(country) => format!("{},{}", name, country),
+                    None => name.clone(),
+                }), ("limit", "1".to_string())]),
+            LocationQuery::Coordinates { latitude, longitude } => (format!("{}/geo/1.0/reverse", self.base_url),
+                vec![("lat", latitude.to_string()), ("lon", longitude.to_string()), ("limit", "1".to_string())]),
         };
         
         let response = self.client.get(&url)
+            .query(&params)
+            .query(&[("appid", self.api_key.expose().as_str())])
             .send()
             .await?;
             
@@ -48,7 +63,12 @@ impl OpenWeatherProvider {
         
         let text = response.text().await?;
         
-        if location.chars().all(|c| c.is_digit(10)) {
+        if let LocationQuery::Coordinates { latitude, longitude } = query {
+            // Keep the configured coordinates, only the place name comes from the lookup
+            let geos: Vec<OpenWeatherGeo> = serde_json::from_str(&text)?;
+            let name = geos.first().map(|geo| geo.name.clone()).unwrap_or_else(|| location.to_string());
+            Ok((latitude, longitude, name))
+        } else if let LocationQuery::PostalCode { .. } = query {
             let geo: OpenWeatherZipGeo = serde_json::from_str(&text)?;
             Ok((geo.lat, geo.lon, geo.name))
         } else {
@@ -67,13 +87,13 @@ impl OpenWeatherProvider {
         }
         
         let url = format!("{}/data/2.5/forecast?lat={}&lon={}&appid={}&units=metric", 
-            self.base_url, lat, lon, self.api_key);
+            self.base_url, lat, lon, self.api_key.expose());
             
         let response = self.client.get(&url)
             .send()
             .await?;
             
-        let forecast: OpenWeather5Day = response.json().await?;
+        let forecast: OpenWeather5Day = archive::json("openweather", "forecast_5day", location, response).await?;
         
         let mut daily_map = std::collections::HashMap::new();
         
@@ -112,21 +132,27 @@ impl OpenWeatherProvider {
         let mut daily: Vec<DailyForecast> = daily_map.into_iter()
             .map(|(_, data)| DailyForecast {
                 date: data.date,
-                temperature_min: data.temps.iter().cloned().fold(f64::INFINITY, f64::min),
-                temperature_max: data.temps.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+                temperature_min: Temperature::from_celsius(data.temps.iter().cloned().fold(f64::INFINITY, f64::min)),
+                temperature_max: Temperature::from_celsius(data.temps.iter().cloned().fold(f64::NEG_INFINITY, f64::max)),
                 humidity: Some(data.humidity.iter().sum::<f64>() / data.humidity.len() as f64),
                 precipitation_probability: Some(data.pop.iter().cloned().fold(0.0, f64::max)),
                 precipitation_amount: if data.rain.is_empty() { None } else {
                     Some(data.rain.iter().sum())
                 },
-                wind_speed: Some(data.wind_speed.iter().sum::<f64>() / data.wind_speed.len() as f64),
+                wind_speed: Some(Speed::from_meters_per_second(data.wind_speed.iter().sum::<f64>() / data.wind_speed.len() as f64)),
                 wind_direction: if data.wind_deg.is_empty() { None } else {
                     Some(data.wind_deg.iter().sum::<f64>() / data.wind_deg.len() as f64)
                 },
                 description: data.descriptions.first().cloned().unwrap_or_default(),
+                condition: data.descriptions.first()
+                    .map(|d| ConditionCode::from_description(d))
+                    .unwrap_or_default(),
                 icon: data.icons.first().cloned(),
                 sunrise: None,
                 sunset: None,
+                solar_irradiance: None,
+                day: None,
+                night: None,
             })
             .collect();
         
@@ -137,19 +163,24 @@ impl OpenWeatherProvider {
             .take(40)
             .map(|h| HourlyForecast {
                 datetime: format_timestamp(h.dt),
-                temperature: h.main.temp,
-                feels_like: Some(h.main.feels_like),
+                temperature: Temperature::from_celsius(h.main.temp),
+                feels_like: Some(Temperature::from_celsius(h.main.feels_like)),
                 humidity: Some(h.main.humidity),
                 precipitation_probability: Some(h.pop * 100.0),
                 precipitation_amount: h.rain.as_ref()
                     .and_then(|r| r.three_h)
                     .or_else(|| h.snow.as_ref().and_then(|s| s.three_h)),
-                wind_speed: Some(h.wind.speed),
+                wind_speed: Some(Speed::from_meters_per_second(h.wind.speed)),
                 wind_direction: h.wind.deg,
                 description: h.weather.first()
                     .map(|w| w.description.clone())
                     .unwrap_or_default(),
+                condition: h.weather.first()
+                    .map(|w| ConditionCode::from_description(&w.description))
+                    .unwrap_or_default(),
                 icon: h.weather.first().map(|w| w.icon.clone()),
+                cloud_cover: h.clouds.as_ref().map(|c| c.all),
+                solar_irradiance: None,
             })
             .collect());
         
@@ -169,6 +200,60 @@ impl OpenWeatherProvider {
     }
 }
 
+/// Weather from a current-conditions response, for live requests and archive replays alike
+fn current_weather(current: &OpenWeatherCurrent, latitude: f64, longitude: f64, name: String) -> Weather {
+    // units=metric gives °C, hPa and wind in m/s
+    let temperature = Temperature::from_celsius(current.main.temp);
+    let wind_speed = Speed::from_meters_per_second(current.wind.speed);
+    Weather {
+        temperature,
+        feels_like: Some(Temperature::from_celsius(current.main.feels_like)),
+        humidity: Some(current.main.humidity),
+        pressure: Some(Pressure::from_hpa(current.main.pressure)),
+        wind_speed: Some(wind_speed),
+        wind_direction: current.wind.deg,
+        description: current.weather.first()
+            .map(|w| w.description.clone())
+            .unwrap_or_default(),
+        condition: current.weather.first()
+            .map(|w| ConditionCode::from_description(&w.description))
+            .unwrap_or_default(),
+        icon: current.weather.first().map(|w| w.icon.clone()),
+        precipitation: current.rain.as_ref().map(|r| r.one_h.unwrap_or(0.0))
+            .or_else(|| current.snow.as_ref().map(|s| s.one_h.unwrap_or(0.0))),
+        visibility: current.visibility.map(|v| v as f64),
+        uv_index: None,
+        road_condition: Some(estimate_road_condition(
+            temperature,
+            Some(current.main.humidity),
+            current.rain.as_ref().and_then(|r| r.one_h)
+                .or_else(|| current.snow.as_ref().and_then(|s| s.one_h)),
+            None,
+        )),
+        comfort: Some(comfort(temperature, Some(current.main.humidity), Some(wind_speed), None)),
+        provider: "OpenWeather".to_string(),
+        location: Location {
+            latitude,
+            longitude,
+            name,
+            country: Some(current.sys.country.clone()),
+            region: None,
+            postal_code: None,
+        },
+        timestamp: current.dt,
+        sources: Vec::new(),
+        spread: BTreeMap::new(),
+    }
+}
+
+/// Re-normalizes an archived `current` payload; `location` is the configured location it was fetched for
+pub fn normalize_current(payload: &serde_json::Value, location: &str) -> Result<Weather, WeatherError> {
+    let current: OpenWeatherCurrent = serde_json::from_value(payload.clone())?;
+    let (latitude, longitude) = current.coord.as_ref().map(|c| (c.lat, c.lon)).unwrap_or((0.0, 0.0));
+    let name = current.name.clone().filter(|n| !n.is_empty()).unwrap_or_else(|| location.to_string());
+    Ok(current_weather(&current, latitude, longitude, name))
+}
+
 #[async_trait]
 impl WeatherProvider for OpenWeatherProvider {
     async fn get_current_weather(&self, location: &str) -> Result<Weather, WeatherError> {
@@ -179,40 +264,14 @@ impl WeatherProvider for OpenWeatherProvider {
         }
         
         let url = format!("{}/data/2.5/weather?lat={}&lon={}&appid={}&units=metric", 
-            self.base_url, lat, lon, self.api_key);
+            self.base_url, lat, lon, self.api_key.expose());
             
         let response = self.client.get(&url)
             .send()
             .await?;
             
-        let current: OpenWeatherCurrent = response.json().await?;
-        
-        Ok(Weather {
-            temperature: current.main.temp,
-            feels_like: Some(current.main.feels_like),
-            humidity: Some(current.main.humidity),
-            pressure: Some(current.main.pressure),
-            wind_speed: Some(current.wind.speed),
-            wind_direction: current.wind.deg,
-            description: current.weather.first()
-                .map(|w| w.description.clone())
-                .unwrap_or_default(),
-            icon: current.weather.first().map(|w| w.icon.clone()),
-            precipitation: current.rain.as_ref().map(|r| r.one_h.unwrap_or(0.0))
-                .or_else(|| current.snow.as_ref().map(|s| s.one_h.unwrap_or(0.0))),
-            visibility: current.visibility.map(|v| v as f64),
-            uv_index: None,
-            provider: "OpenWeather".to_string(),
-            location: Location {
-                latitude: lat,
-                longitude: lon,
-                name: name.clone(),
-                country: Some(current.sys.country.clone()),
-                region: None,
-                postal_code: None,
-            },
-            timestamp: current.dt as i64,
-        })
+        let current: OpenWeatherCurrent = archive::json("openweather", "current", location, response).await?;
+        Ok(current_weather(&current, lat, lon, name))
     }
     
     async fn get_forecast(&self, location: &str, days: u8) -> Result<Forecast, WeatherError> {
@@ -223,7 +282,7 @@ impl WeatherProvider for OpenWeatherProvider {
         }
         
         let url = format!("{}/data/3.0/onecall?lat={}&lon={}&exclude=minutely,alerts&appid={}&units=metric", 
-            self.base_url, lat, lon, self.api_key);
+            self.base_url, lat, lon, self.api_key.expose());
             
         let response = self.client.get(&url)
             .send()
@@ -234,25 +293,31 @@ impl WeatherProvider for OpenWeatherProvider {
             return self.get_5day_forecast_internal(location, days).await;
         }
         
-        let forecast: OpenWeatherOneCall = response.json().await?;
+        let forecast: OpenWeatherOneCall = archive::json("openweather", "onecall_forecast", location, response).await?;
         
         let daily = forecast.daily.iter()
             .take(days as usize)
             .map(|d| DailyForecast {
                 date: format_timestamp(d.dt),
-                temperature_min: d.temp.min,
-                temperature_max: d.temp.max,
+                temperature_min: Temperature::from_celsius(d.temp.min),
+                temperature_max: Temperature::from_celsius(d.temp.max),
                 humidity: Some(d.humidity),
                 precipitation_probability: Some(d.pop * 100.0),
                 precipitation_amount: d.rain.or(d.snow),
-                wind_speed: Some(d.wind_speed),
+                wind_speed: Some(Speed::from_meters_per_second(d.wind_speed)),
                 wind_direction: Some(d.wind_deg),
                 description: d.weather.first()
                     .map(|w| w.description.clone())
                     .unwrap_or_default(),
+                condition: d.weather.first()
+                    .map(|w| ConditionCode::from_description(&w.description))
+                    .unwrap_or_default(),
                 icon: d.weather.first().map(|w| w.icon.clone()),
                 sunrise: Some(format_timestamp(d.sunrise)),
                 sunset: Some(format_timestamp(d.sunset)),
+                solar_irradiance: None,
+                day: None,
+                night: None,
             })
             .collect();
         
@@ -260,18 +325,23 @@ impl WeatherProvider for OpenWeatherProvider {
             .take(48)
             .map(|h| HourlyForecast {
                 datetime: format_timestamp(h.dt),
-                temperature: h.temp,
-                feels_like: Some(h.feels_like),
+                temperature: Temperature::from_celsius(h.temp),
+                feels_like: Some(Temperature::from_celsius(h.feels_like)),
                 humidity: Some(h.humidity),
                 precipitation_probability: Some(h.pop * 100.0),
                 precipitation_amount: h.rain.as_ref().map(|r| r.one_h.unwrap_or(0.0))
                     .or_else(|| h.snow.as_ref().map(|s| s.one_h.unwrap_or(0.0))),
-                wind_speed: Some(h.wind_speed),
+                wind_speed: Some(Speed::from_meters_per_second(h.wind_speed)),
                 wind_direction: Some(h.wind_deg),
                 description: h.weather.first()
                     .map(|w| w.description.clone())
                     .unwrap_or_default(),
+                condition: h.weather.first()
+                    .map(|w| ConditionCode::from_description(&w.description))
+                    .unwrap_or_default(),
                 icon: h.weather.first().map(|w| w.icon.clone()),
+                cloud_cover: h.clouds,
+                solar_irradiance: None,
             })
             .collect());
         
@@ -298,7 +368,7 @@ impl WeatherProvider for OpenWeatherProvider {
         }
         
         let url = format!("{}/data/3.0/onecall?lat={}&lon={}&exclude=current,minutely,hourly,daily&appid={}", 
-            self.base_url, lat, lon, self.api_key);
+            self.base_url, lat, lon, self.api_key.expose());
             
         let response = self.client.get(&url)
             .send()
@@ -308,7 +378,7 @@ impl WeatherProvider for OpenWeatherProvider {
             return Ok(Vec::new());
         }
         
-        let data: serde_json::Value = response.json().await?;
+        let data: serde_json::Value = archive::json("openweather", "onecall_alerts", location, response).await?;
         
         if let Some(alerts) = data.get("alerts").and_then(|a| a.as_array()) {
             Ok(alerts.iter()
@@ -344,7 +414,7 @@ impl WeatherProvider for OpenWeatherProvider {
         }
         
         let url = format!("{}/data/3.0/onecall/timemachine?lat={}&lon={}&dt={}&appid={}&units=metric", 
-            self.base_url, lat, lon, timestamp, self.api_key);
+            self.base_url, lat, lon, timestamp, self.api_key.expose());
             
         let response = self.client.get(&url)
             .send()
@@ -354,7 +424,7 @@ impl WeatherProvider for OpenWeatherProvider {
             return Err(WeatherError::NotFound("Historical data requires subscription".to_string()));
         }
         
-        let data: OpenWeatherHistorical = response.json().await?;
+        let data: OpenWeatherHistorical = archive::json("openweather", "timemachine", location, response).await?;
         
         let temps: Vec<f64> = data.data.iter().map(|h| h.temp).collect();
         let humidities: Vec<f64> = data.data.iter().map(|h| h.humidity).collect();
@@ -392,6 +462,9 @@ impl WeatherProvider for OpenWeatherProvider {
             WeatherFeature::HourlyForecast => true,
             WeatherFeature::UvIndex => true,
             WeatherFeature::AirQuality => true,
+            WeatherFeature::Pollen => false,
+            WeatherFeature::Marine => false,
+            WeatherFeature::MinutelyForecast => false,
             WeatherFeature::HistoricalData => true,
         }
     }
@@ -416,6 +489,8 @@ struct OpenWeatherZipGeo {
 #[derive(Debug, Deserialize)]
 struct OpenWeatherCurrent {
     dt: i64,
+    coord: Option<OpenWeatherCoord>,
+    name: Option<String>,
     main: OpenWeatherMain,
     weather: Vec<OpenWeatherWeatherInfo>,
     wind: OpenWeatherWind,
@@ -453,6 +528,12 @@ struct OpenWeatherPrecip {
     three_h: Option<f64>,
 }
 
+#[derive(Debug, Deserialize)]
+struct OpenWeatherCoord {
+    lat: f64,
+    lon: f64,
+}
+
 #[derive(Debug, Deserialize)]
 struct OpenWeatherSys {
     country: String,
@@ -493,6 +574,7 @@ struct OpenWeatherHourly {
     wind_speed: f64,
     wind_deg: f64,
     pop: f64,
+    clouds: Option<f64>,
     weather: Vec<OpenWeatherWeatherInfo>,
     rain: Option<OpenWeatherPrecip>,
     snow: Option<OpenWeatherPrecip>,
@@ -534,10 +616,16 @@ struct OpenWeather5DayItem {
     weather: Vec<OpenWeatherWeatherInfo>,
     wind: OpenWeatherWind,
     pop: f64,
+    clouds: Option<OpenWeatherClouds>,
     rain: Option<OpenWeatherPrecip>,
     snow: Option<OpenWeatherPrecip>,
 }
 
+#[derive(Debug, Deserialize)]
+struct OpenWeatherClouds {
+    all: f64,
+}
+
 #[derive(Debug, Deserialize)]
 struct OpenWeatherHistorical {
     data: Vec<OpenWeatherHistoricalHour>,
@@ -564,9 +652,7 @@ struct DailyData {
 }
 
 fn format_timestamp(ts: i64) -> String {
-    use std::time::{Duration, UNIX_EPOCH};
-    let d = UNIX_EPOCH + Duration::from_secs(ts as u64);
-    format!("{:?}", d)
+    crate::utils::time::format_rfc3339(ts)
 }
 
 fn format_date_only(ts: i64) -> String {
@@ -0,0 +1,339 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::Mutex;
+
+use crate::db_pool::{combo_client, get_combo_pool};
+use crate::error::Result as JupiterResult;
+use crate::metrics::{self, Kind};
+use crate::utils::time::safe_timestamp_with_fallback;
+
+// Call budgets for paid weather providers. PROVIDER_BUDGETS lists `provider=calls/period` pairs
+// separated by commas, the period being `hour` or `day` (UTC windows), e.g.
+// `accuweather=50/day,tomorrow.io=25/hour,tomorrow.io=500/day` for the free tiers. Provider
+// names are matched ignoring case. Once any budget of a provider is used up ComboProvider skips
+// it until the window resets, combining the other (free) providers instead.
+//
+// Every call let through counts, whatever its outcome; calls skipped by the circuit breakers
+// (see `health`) don't. Counts are kept in the combo database's `provider_calls` table so
+// they survive restarts and are shared by instances using the same database, each adding its
+// calls and picking up the others' totals as it writes.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    Hour,
+    Day,
+}
+
+impl Period {
+    pub fn secs(&self) -> i64 {
+        match self {
+            Period::Hour => 3600,
+            Period::Day => 86_400,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Period::Hour => "hour",
+            Period::Day => "day",
+        }
+    }
+
+    pub fn parse(period: &str) -> Option<Self> {
+        match period {
+            "hour" => Some(Period::Hour),
+            "day" => Some(Period::Day),
+            _ => None,
+        }
+    }
+
+    /// Start of the window `now` falls in
+    pub fn window(&self, now: i64) -> i64 {
+        now - now.rem_euclid(self.secs())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    pub calls: u64,
+    pub period: Period,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BudgetConfig {
+    pub budgets: BTreeMap<String, Vec<Budget>>,   // Lowercase provider name -> its budgets
+}
+
+impl BudgetConfig {
+    /// Parses `provider=calls/period` pairs separated by commas
+    pub fn parse(budgets: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for pair in budgets.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let parsed = pair.split_once('=').and_then(|(provider, budget)| {
+                let (calls, period) = budget.split_once('/')?;
+                let provider = provider.trim().to_lowercase();
+                Some((provider, calls.trim().parse::<u64>().ok()?, Period::parse(period.trim())?))
+                    .filter(|(provider, _, _)| !provider.is_empty())
+            });
+            let Some((provider, calls, period)) = parsed else {
+                return Err(format!("expected provider=calls/hour or provider=calls/day, got '{}'", pair));
+            };
+            let budgets = config.budgets.entry(provider).or_default();
+            if budgets.iter().any(|budget| budget.period == period) {
+                return Err(format!("more than one {} budget in '{}'", period.as_str(), pair));
+            }
+            budgets.push(Budget { calls, period });
+        }
+        Ok(config)
+    }
+
+    /// Reads the settings through `var`; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<(&'static str, String)>> {
+        match var("PROVIDER_BUDGETS") {
+            Some(budgets) => Self::parse(&budgets).map_err(|message| vec![("PROVIDER_BUDGETS", message)]),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    pub fn budgets(&self, provider: &str) -> &[Budget] {
+        self.budgets.get(&provider.to_lowercase()).map_or(&[], Vec::as_slice)
+    }
+}
+
+pub static BUDGET_CONFIG: Lazy<BudgetConfig> = Lazy::new(|| {
+    BudgetConfig::from_env().unwrap_or_else(|problems| {
+        for (setting, message) in problems {
+            log::error!("Invalid {}, not limiting provider calls: {}", setting, message);
+        }
+        BudgetConfig::default()
+    })
+});
+
+/// A provider's use of one budget in the current window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetStatus {
+    pub period: Period,
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    pub resets_at: i64,
+}
+
+/// Calls made in the current windows, by (lowercase provider, period)
+#[derive(Debug, Default)]
+struct Counters {
+    windows: HashMap<(String, Period), (i64, u64)>,    // Window start, calls
+}
+
+impl Counters {
+    fn used(&self, provider: &str, period: Period, now: i64) -> u64 {
+        match self.windows.get(&(provider.to_string(), period)) {
+            Some((start, calls)) if *start == period.window(now) => *calls,
+            _ => 0,
+        }
+    }
+
+    /// Counts a call against every budget unless one of them is used up; returns the
+    /// exhausted budget otherwise
+    fn take(&mut self, provider: &str, budgets: &[Budget], now: i64) -> Result<(), Budget> {
+        if let Some(budget) = budgets.iter().find(|budget| self.used(provider, budget.period, now) >= budget.calls) {
+            return Err(*budget);
+        }
+        for budget in budgets {
+            let start = budget.period.window(now);
+            let window = self.windows.entry((provider.to_string(), budget.period)).or_insert((start, 0));
+            if window.0 != start {
+                *window = (start, 0);
+            }
+            window.1 += 1;
+        }
+        Ok(())
+    }
+
+    /// Takes the stored total of a window when it is ahead, e.g. counting other instances' calls
+    fn merge(&mut self, provider: &str, period: Period, start: i64, calls: u64) {
+        let window = self.windows.entry((provider.to_string(), period)).or_insert((start, 0));
+        if window.0 < start {
+            *window = (start, calls);
+        } else if window.0 == start {
+            window.1 = window.1.max(calls);
+        }
+    }
+
+    fn status(&self, provider: &str, budgets: &[Budget], now: i64) -> Vec<BudgetStatus> {
+        budgets.iter().map(|budget| {
+            let used = self.used(provider, budget.period, now);
+            BudgetStatus {
+                period: budget.period,
+                limit: budget.calls,
+                used,
+                remaining: budget.calls.saturating_sub(used),
+                resets_at: budget.period.window(now) + budget.period.secs(),
+            }
+        }).collect()
+    }
+}
+
+static COUNTERS: Lazy<Mutex<Counters>> = Lazy::new(|| {
+    metrics::collector(Kind::Gauge, "jupiter_provider_budget_remaining", "Calls left in each provider budget's current window", &["provider", "period"], || {
+        let now = safe_timestamp_with_fallback();
+        let Ok(counters) = COUNTERS.lock() else { return Vec::new() };
+        BUDGET_CONFIG.budgets.iter()
+            .flat_map(|(provider, budgets)| counters.status(provider, budgets, now).into_iter()
+                .map(move |status| (vec![provider.clone(), status.period.as_str().to_string()], status.remaining as f64)))
+            .collect()
+    });
+    Mutex::new(Counters::default())
+});
+
+/// Whether `provider` is within its budgets, counting the call it is about to make
+pub fn allow(provider: &str) -> bool {
+    let budgets = BUDGET_CONFIG.budgets(provider);
+    if budgets.is_empty() {
+        return true;
+    }
+    let provider = provider.to_lowercase();
+    let now = safe_timestamp_with_fallback();
+    let taken = match COUNTERS.lock() {
+        Ok(mut counters) => counters.take(&provider, budgets, now),
+        Err(_) => return true,
+    };
+    match taken {
+        Ok(()) => {
+            // Stored from a task so the call isn't held up by the database
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let periods: Vec<Period> = budgets.iter().map(|budget| budget.period).collect();
+                runtime.spawn(async move {
+                    if let Err(e) = persist(&provider, &periods, now).await {
+                        log::warn!("[quota] Failed to store the call count of {}: {}", provider, e);
+                    }
+                });
+            }
+            true
+        },
+        Err(budget) => {
+            log::debug!("[quota] Skipping {}, its {} calls per {} are used up", provider, budget.calls, budget.period.as_str());
+            false
+        }
+    }
+}
+
+/// The budgets of `provider` and their use, empty when it has none
+pub fn status(provider: &str) -> Vec<BudgetStatus> {
+    let budgets = BUDGET_CONFIG.budgets(provider);
+    if budgets.is_empty() {
+        return Vec::new();
+    }
+    COUNTERS.lock().map(|counters| counters.status(&provider.to_lowercase(), budgets, safe_timestamp_with_fallback())).unwrap_or_default()
+}
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.provider_calls (
+        provider varchar NOT NULL,
+        period varchar NOT NULL,
+        window_start BIGINT NOT NULL,
+        calls BIGINT NOT NULL DEFAULT 0,
+        CONSTRAINT provider_calls_pkey PRIMARY KEY (provider, period, window_start));"
+}
+
+/// Adds a call to the stored windows of `provider` and takes their totals
+async fn persist(provider: &str, periods: &[Period], now: i64) -> JupiterResult<()> {
+    if get_combo_pool().is_none() {
+        return Ok(());
+    }
+    let client = combo_client().await?;
+    for period in periods {
+        let start = period.window(now);
+        let row = client.query_one(
+            "INSERT INTO provider_calls (provider, period, window_start, calls) VALUES ($1, $2, $3, 1)
+             ON CONFLICT (provider, period, window_start) DO UPDATE SET calls = provider_calls.calls + 1
+             RETURNING calls",
+            &[&provider, &period.as_str(), &start],
+        ).await?;
+        let calls: i64 = row.try_get("calls")?;
+        if let Ok(mut counters) = COUNTERS.lock() {
+            counters.merge(provider, *period, start, calls.max(0) as u64);
+        }
+    }
+    Ok(())
+}
+
+/// Picks up the calls counted in the current windows before a restart, and deletes windows
+/// older than a week
+pub async fn load() -> JupiterResult<()> {
+    if BUDGET_CONFIG.budgets.is_empty() {
+        return Ok(());
+    }
+    let now = safe_timestamp_with_fallback();
+    let client = combo_client().await?;
+    client.execute("DELETE FROM provider_calls WHERE window_start < $1", &[&(now - 7 * 86_400)]).await?;
+    let rows = client.query("SELECT provider, period, window_start, calls FROM provider_calls WHERE window_start >= $1",
+        &[&Period::Day.window(now)]).await?;
+    let Ok(mut counters) = COUNTERS.lock() else { return Ok(()) };
+    for row in &rows {
+        let period: String = row.try_get("period")?;
+        let Some(period) = Period::parse(&period) else { continue };
+        let provider: String = row.try_get("provider")?;
+        let calls: i64 = row.try_get("calls")?;
+        counters.merge(&provider, period, row.try_get("window_start")?, calls.max(0) as u64);
+    }
+    for (provider, budgets) in &BUDGET_CONFIG.budgets {
+        for status in counters.status(provider, budgets, now) {
+            log::info!("[quota] {} has used {} of its {} calls this {}", provider, status.used, status.limit, status.period.as_str());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_config() {
+        let config = BudgetConfig::parse("AccuWeather=50/day, tomorrow.io=25/hour,tomorrow.io=500/day").unwrap();
+        assert_eq!(config.budgets("accuweather"), &[Budget { calls: 50, period: Period::Day }]);
+        assert_eq!(config.budgets("Tomorrow.io").len(), 2);
+        assert!(config.budgets("NWS").is_empty());
+        assert!(BudgetConfig::parse("").unwrap().budgets.is_empty());
+
+        assert!(BudgetConfig::parse("accuweather=50").is_err());
+        assert!(BudgetConfig::parse("accuweather=50/week").is_err());
+        assert!(BudgetConfig::parse("accuweather=-1/day").is_err());
+        assert!(BudgetConfig::parse("accuweather=50/day,accuweather=40/day").is_err());
+        assert_eq!(BudgetConfig::from_vars(|_| Some("=5/day".to_string())).unwrap_err()[0].0, "PROVIDER_BUDGETS");
+    }
+
+    #[test]
+    fn test_budget_counting() {
+        let budgets = [Budget { calls: 2, period: Period::Hour }, Budget { calls: 3, period: Period::Day }];
+        let mut counters = Counters::default();
+        let noon = 19_000 * 86_400 + 12 * 3600;
+
+        assert!(counters.take("tomorrow.io", &budgets, noon).is_ok());
+        assert!(counters.take("tomorrow.io", &budgets, noon + 60).is_ok());
+        assert_eq!(counters.take("tomorrow.io", &budgets, noon + 120), Err(budgets[0]));
+        // A new hour, but only one call left today
+        assert!(counters.take("tomorrow.io", &budgets, noon + 3600).is_ok());
+        assert_eq!(counters.take("tomorrow.io", &budgets, noon + 3660), Err(budgets[1]));
+
+        let status = counters.status("tomorrow.io", &budgets, noon + 3660);
+        assert_eq!((status[0].used, status[0].remaining, status[0].resets_at), (1, 1, noon + 7200));
+        assert_eq!((status[1].used, status[1].remaining, status[1].resets_at), (3, 0, noon + 12 * 3600));
+
+        // Tomorrow the budgets start over
+        assert!(counters.take("tomorrow.io", &budgets, noon + 86_400).is_ok());
+
+        // Another instance's calls count too, stale windows don't
+        counters.merge("accuweather", Period::Day, Period::Day.window(noon), 40);
+        counters.merge("accuweather", Period::Day, Period::Day.window(noon) - 86_400, 50);
+        assert_eq!(counters.used("accuweather", Period::Day, noon), 40);
+    }
+}
@@ -1,13 +1,16 @@
 #[cfg(test)]
 mod tests {
     use super::super::common::*;
+    use crate::analysis::conditions::ConditionCode;
     use super::super::accuweather_enhanced::AccuWeatherProvider;
     use super::super::openweather::OpenWeatherProvider;
     use super::super::homebrew_enhanced::HomebrewProvider;
     use super::super::combo_enhanced::ComboProvider;
     use super::super::homebrew::{Config as HomebrewConfig, PostgresServer};
+    use crate::units::{Pressure, Speed, Temperature};
+    use std::collections::BTreeMap;
     
-    fn create_test_location() -> Location {
+    pub(super) fn create_test_location() -> Location {
         Location {
             latitude: 40.7128,
             longitude: -74.0060,
@@ -55,23 +58,28 @@ mod tests {
     #[test]
     fn test_weather_struct_creation() {
         let weather = Weather {
-            temperature: 20.5,
-            feels_like: Some(19.0),
+            temperature: Temperature::from_celsius(20.5),
+            feels_like: Some(Temperature::from_celsius(19.0)),
             humidity: Some(65.0),
-            pressure: Some(1013.25),
-            wind_speed: Some(5.5),
+            pressure: Some(Pressure::from_hpa(1013.25)),
+            wind_speed: Some(Speed::from_kmh(5.5)),
             wind_direction: Some(180.0),
             description: "Partly cloudy".to_string(),
+            condition: ConditionCode::PartlyCloudy,
             icon: Some("02d".to_string()),
             precipitation: Some(0.0),
             visibility: Some(10000.0),
             uv_index: Some(3.0),
+            road_condition: None,
+            comfort: None,
             provider: "Test".to_string(),
             location: create_test_location(),
             timestamp: 1234567890,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
         };
         
-        assert_eq!(weather.temperature, 20.5);
+        assert_eq!(weather.temperature.celsius(), 20.5);
         assert_eq!(weather.provider, "Test");
         assert_eq!(weather.location.name, "New York");
     }
@@ -81,17 +89,21 @@ mod tests {
         let daily = vec![
             DailyForecast {
                 date: "2024-01-01".to_string(),
-                temperature_min: 10.0,
-                temperature_max: 20.0,
+                temperature_min: Temperature::from_celsius(10.0),
+                temperature_max: Temperature::from_celsius(20.0),
                 humidity: Some(70.0),
                 precipitation_probability: Some(30.0),
                 precipitation_amount: Some(2.5),
-                wind_speed: Some(10.0),
+                wind_speed: Some(Speed::from_kmh(10.0)),
                 wind_direction: Some(270.0),
                 description: "Rain".to_string(),
+                condition: ConditionCode::Rain,
                 icon: Some("10d".to_string()),
                 sunrise: Some("06:30".to_string()),
                 sunset: Some("18:45".to_string()),
+                solar_irradiance: None,
+                day: None,
+                night: None,
             },
         ];
         
@@ -103,7 +115,7 @@ mod tests {
         };
         
         assert_eq!(forecast.daily.len(), 1);
-        assert_eq!(forecast.daily[0].temperature_max, 20.0);
+        assert_eq!(forecast.daily[0].temperature_max.celsius(), 20.0);
     }
     
     #[test]
@@ -145,7 +157,33 @@ mod tests {
         let combo = ComboProvider::new();
         assert_eq!(combo.name(), "Combo");
     }
-    
+
+    #[test]
+    fn test_accuweather_search_path() {
+        use super::super::accuweather::search_path;
+
+        let path = |location: &str| search_path(&LocationQuery::parse(location).unwrap());
+        assert_eq!(path("98101"), ("locations/v1/postalcodes/search".to_string(), "98101".to_string()));
+        assert_eq!(path("47.6,-122.3"), ("locations/v1/cities/geoposition/search".to_string(), "47.6,-122.3".to_string()));
+    }
+
+    #[test]
+    fn test_combo_capabilities() {
+        let combo = ComboProvider::new()
+            .add_provider(Box::new(AccuWeatherProvider::new("test_key".to_string())), 1.0)
+            .add_provider(Box::new(OpenWeatherProvider::new("test_key".to_string())), 1.0);
+        let capabilities = combo.capabilities();
+        assert_eq!(capabilities.providers, vec!["AccuWeather", "OpenWeather"]);
+        assert_eq!(capabilities.features.len(), WeatherFeature::ALL.len());
+        assert_eq!(capabilities.features["alerts"].providers, vec!["AccuWeather", "OpenWeather"]);
+        assert_eq!(capabilities.features["historical"].providers, vec!["OpenWeather"]);
+        assert!(capabilities.features["pollen"].supported);
+        // AccuWeather without a marine configuration, and no air quality providers
+        assert!(!capabilities.features["marine"].supported);
+        assert!(!capabilities.features["air_quality"].supported);
+        assert!(ComboProvider::new().capabilities().features.values().all(|feature| !feature.supported));
+    }
+
     #[tokio::test]
     async fn test_combo_provider_builder() {
         let combo = ComboProvider::new()
@@ -177,7 +215,387 @@ mod tests {
 #[cfg(test)]
 mod integration_tests {
     use super::super::common::*;
+    use crate::units::{Pressure, Speed};
+    
+    #[test]
+    fn test_pollen_normalization() {
+        use super::super::accuweather::AirAndPollen;
+        
+        let json = r#"[
+            {"Name": "AirQuality", "Value": 0.0, "Category": "Good", "CategoryValue": 1.0, "Type": "Ozone"},
+            {"Name": "Grass", "Value": 12.0, "Category": "Moderate", "CategoryValue": 2.0},
+            {"Name": "Mold", "Value": 6500.0, "Category": "High", "CategoryValue": 3.0},
+            {"Name": "Ragweed", "Value": 0.0, "Category": "Low", "CategoryValue": 1.0},
+            {"Name": "Tree", "Value": 40.0, "Category": "Moderate", "CategoryValue": 2.0}
+        ]"#;
+        let entries: Vec<AirAndPollen> = serde_json::from_str(json).unwrap();
+        
+        let mut report = PollenReport::new(super::tests::create_test_location(), "AccuWeather".to_string(), "2024-05-01".to_string());
+        assert!(report.is_empty());
+        for (pollen_type, level) in entries.iter().filter_map(|e| e.pollen_level()) {
+            report.set_level(pollen_type, level);
+        }
+        
+        assert!(!report.is_empty());
+        assert_eq!(report.level(PollenType::Grass).unwrap().value, 12.0);
+        assert_eq!(report.level(PollenType::Mold).unwrap().category, "High");
+        assert_eq!(report.level(PollenType::Tree).unwrap().category_value, 2.0);
+        assert!(report.ragweed.is_some());
+        
+        assert_eq!(PollenType::from_name("Weeds"), Some(PollenType::Ragweed));
+        assert_eq!(PollenType::from_name("AirQuality"), None);
+    }
+    
+    #[test]
+    fn test_accuweather_fixtures() {
+        use super::super::accuweather::{CurrentCondition, CurrentConditions, Forecast, HourlyForecast, Location};
+        use super::super::accuweather_enhanced::normalize_current;
+
+        // Hand-written after AccuWeather's responses for New York (349727) with details=true&metric=true,
+        // not live captures
+        let location_json = include_str!("../../tests/fixtures/accuweather/location.json");
+        let location: Location = serde_json::from_str(location_json).unwrap();
+        assert_eq!(location.region.as_ref().map(|r| r.id.as_str()), Some("NAM"));
+        assert_eq!(location.country.id, "US");
+        assert_eq!(location.time_zone.as_ref().map(|t| t.name.as_str()), Some("America/New_York"));
+        assert_eq!(location.geo_position.elevation.as_ref().map(|e| e.metric.value), Some(9.0));
+        assert_eq!(location.details.as_ref().and_then(|d| d.marine_station.as_deref()), Some("ANZ338"));
+        let common = location.common();
+        assert_eq!(common.name, "New York");
+        assert_eq!(common.region.as_deref(), Some("New York"));
+        assert_eq!(common.postal_code.as_deref(), Some("10007"));
+
+        let current_json = include_str!("../../tests/fixtures/accuweather/current_conditions.json");
+        let conditions: CurrentConditions = serde_json::from_str(current_json).unwrap();
+        let current = &conditions[0];
+        assert_eq!(current.wind.as_ref().and_then(|w| w.direction.as_ref()).map(|d| d.degrees), Some(68.0));
+        assert!(current.wind_gust.as_ref().is_some_and(|g| g.direction.is_none()));
+        assert_eq!(current.uv_index, Some(1.0));
+        assert_eq!(current.pressure.as_ref().map(|p| p.metric.value), Some(1016.3));
+
+        let weather = normalize_current(
+            &serde_json::from_str(current_json).unwrap(),
+            Some(&serde_json::from_str(location_json).unwrap()),
+            "349727",
+            1714587900,
+        ).unwrap();
+        assert_eq!(weather.temperature.celsius(), 15.6);
+        assert_eq!(weather.wind_speed, Some(Speed::from_kmh(14.8)));
+        assert_eq!(weather.location.name, "New York");
+
+        // Conditions fetched without details leave the details fields out when re-serialized
+        let basic = serde_json::to_value(CurrentCondition::default()).unwrap();
+        assert!(basic.get("Wind").is_none() && basic.get("UVIndex").is_none());
+
+        let forecast: Forecast = serde_json::from_str(include_str!("../../tests/fixtures/accuweather/daily_1day.json")).unwrap();
+        assert!(forecast.headline.end_date.is_none());
+        let day = &forecast.daily_forecasts[0];
+        assert_eq!(day.day.solar_irradiance.as_ref().map(|s| s.value), Some(2437.6));
+        assert_eq!(day.day.evapotranspiration.as_ref().map(|e| e.value), Some(1.3));
+        assert!(!day.night.icon_phrase.is_empty());
+        assert_eq!(day.pollen_report(common.clone()).level(PollenType::Tree).unwrap().category, "High");
+
+        let daily = super::super::accuweather_enhanced::daily_forecast(day);
+        assert_eq!(daily.precipitation_probability, Some(75.0));
+        assert_eq!(daily.precipitation_amount, Some(3.3));
+        assert!((daily.solar_irradiance.unwrap() - 2525.0).abs() < 1e-9);
+        assert_eq!(daily.condition, crate::analysis::conditions::ConditionCode::Rain);
+        let night = daily.night.as_ref().unwrap();
+        assert_eq!((night.description.as_str(), night.cloud_cover, night.solar_irradiance), ("Cloudy", Some(99.0), Some(87.4)));
+
+        // The 12-hour forecast trimmed to two hours, the second with a gust missing its direction
+        let hourly: Vec<HourlyForecast> = serde_json::from_str(include_str!("../../tests/fixtures/accuweather/hourly_12hour.json")).unwrap();
+        assert_eq!(hourly.len(), 2);
+        assert!(hourly[1].wind_gust.as_ref().is_some_and(|g| g.direction.is_none()));
+        assert_eq!(hourly[1].solar_irradiance.as_ref().map(|s| s.value), Some(96.4));
+    }
+
+    #[test]
+    fn test_noaa_marine_parsing() {
+        use super::super::noaa::{parse_ndbc_latest, parse_tide_predictions, parse_water_temperature};
+        
+        let tides = parse_tide_predictions(r#"{"predictions": [
+            {"t": "2024-05-01 04:12", "v": "1.402", "type": "H"},
+            {"t": "2024-05-01 10:31", "v": "-0.051", "type": "L"}
+        ]}"#).unwrap();
+        assert_eq!(tides.len(), 2);
+        assert_eq!(tides[0].tide_type, TideType::High);
+        assert_eq!(tides[1].height, -0.051);
+        
+        assert!(matches!(
+            parse_tide_predictions(r#"{"error": {"message": "No Predictions data was found."}}"#),
+            Err(WeatherError::NotFound(_))
+        ));
+        assert_eq!(parse_water_temperature(r#"{"data": [{"t": "2024-05-01 12:00", "v": "12.4", "f": "0,0,0"}]}"#).unwrap(), Some(12.4));
+        
+        let buoy = "#YY  MM DD hh mm WDIR WSPD GST  WVHT   DPD   APD MWD   PRES  ATMP  WTMP  DEWP  VIS PTDY  TIDE\n\
+                    #yr  mo dy hr mn degT m/s  m/s     m   sec   sec degT   hPa  degC  degC  degC  nmi  hPa    ft\n\
+                    2024 05 01 12 40 200  5.0  6.0   1.2    8   5.6 210 1015.2  14.1  12.3   MM   MM   MM    MM\n\
+                    2024 05 01 12 30 190  4.0  5.0   1.1    8   5.4 205 1015.0  14.0  12.2   MM   MM   MM    MM";
+        let latest = parse_ndbc_latest(buoy).unwrap();
+        assert_eq!(latest.get("WVHT").map(String::as_str), Some("1.2"));
+        assert_eq!(latest.get("WTMP").map(String::as_str), Some("12.3"));
+        assert_eq!(latest.get("DEWP").map(String::as_str), Some("MM"));
+    }
+    
+    #[test]
+    fn test_nws_parsing() {
+        use super::super::nws::{compass_degrees, daily_forecasts, normalize_current, parse_wind_speed, NwsPeriod};
+        
+        assert_eq!(parse_wind_speed("10 to 20 km/h"), Some(Speed::from_kmh(20.0)));
+        assert!((parse_wind_speed("5 mph").unwrap().kmh() - 8.05).abs() < 0.01);
+        assert_eq!(compass_degrees("NW"), Some(315.0));
+        assert_eq!(compass_degrees("variable"), None);
+        
+        // An evening forecast: tonight, then a full day and night
+        let periods: Vec<NwsPeriod> = serde_json::from_str(r#"[
+            {"startTime": "2024-05-01T18:00:00-04:00", "isDaytime": false, "temperature": 11, "temperatureUnit": "C",
+             "probabilityOfPrecipitation": {"unitCode": "wmoUnit:percent", "value": 20}, "windSpeed": "10 km/h", "windDirection": "S",
+             "icon": null, "shortForecast": "Partly Cloudy"},
+            {"startTime": "2024-05-02T06:00:00-04:00", "isDaytime": true, "temperature": 22, "temperatureUnit": "C",
+             "probabilityOfPrecipitation": {"unitCode": "wmoUnit:percent", "value": null}, "windSpeed": "10 to 15 km/h", "windDirection": "SW",
+             "icon": null, "shortForecast": "Sunny"},
+            {"startTime": "2024-05-02T18:00:00-04:00", "isDaytime": false, "temperature": 55, "temperatureUnit": "F",
+             "probabilityOfPrecipitation": {"unitCode": "wmoUnit:percent", "value": 60}, "windSpeed": "5 km/h", "windDirection": "W",
+             "icon": null, "shortForecast": "Chance Showers"}
+        ]"#).unwrap();
+        let daily = daily_forecasts(&periods);
+        assert_eq!(daily.len(), 2);
+        assert_eq!((daily[0].date.as_str(), daily[0].temperature_min.celsius(), daily[0].temperature_max.celsius()), ("2024-05-01", 11.0, 11.0));
+        assert_eq!(daily[1].temperature_max.celsius(), 22.0);
+        assert!((daily[1].temperature_min.celsius() - 12.78).abs() < 0.01);
+        assert_eq!(daily[1].description, "Sunny");
+        assert_eq!(daily[1].precipitation_probability, Some(60.0));
+        assert!(daily[0].day.is_none());
+        assert_eq!(daily[0].night.as_ref().map(|n| n.precipitation_probability), Some(Some(20.0)));
+        assert_eq!(daily[1].day.as_ref().map(|d| d.wind_direction), Some(Some(225.0)));
+        assert_eq!(daily[1].night.as_ref().map(|n| n.description.as_str()), Some("Chance Showers"));
+        
+        let weather = normalize_current(&serde_json::json!({
+            "geometry": {"type": "Point", "coordinates": [-122.31, 47.45]},
+            "properties": {
+                "timestamp": "2023-11-14T22:13:20+00:00",
+                "textDescription": "Light Rain",
+                "icon": null,
+                "temperature": {"unitCode": "wmoUnit:degC", "value": 8.3},
+                "windDirection": {"unitCode": "wmoUnit:degree_(angle)", "value": 180},
+                "windSpeed": {"unitCode": "wmoUnit:km_h-1", "value": 14.8},
+                "barometricPressure": {"unitCode": "wmoUnit:Pa", "value": 101320},
+                "visibility": {"unitCode": "wmoUnit:m", "value": 16090},
+                "relativeHumidity": {"unitCode": "wmoUnit:percent", "value": 93.2},
+                "windChill": {"unitCode": "wmoUnit:degC", "value": null},
+                "heatIndex": {"unitCode": "wmoUnit:degC", "value": null},
+                "precipitationLastHour": {"unitCode": "wmoUnit:mm", "value": 0.8}
+            }
+        }), "47.45,-122.31").unwrap();
+        assert_eq!(weather.temperature.celsius(), 8.3);
+        assert_eq!(weather.pressure.map(Pressure::hpa), Some(1013.2));
+        assert_eq!(weather.visibility, Some(16.09));
+        assert_eq!(weather.feels_like, None);
+        assert_eq!((weather.location.latitude, weather.location.longitude), (47.45, -122.31));
+        assert_eq!(weather.timestamp, 1700000000);
+    }
+
+    #[test]
+    fn test_tomorrow_io_parsing() {
+        use crate::analysis::conditions::ConditionCode;
+        use super::super::tomorrow_io::{minute_precipitation, normalize_current, weather_description, TomorrowIoInterval};
+
+        assert_eq!(weather_description(4200), "Light Rain");
+        assert_eq!(weather_description(42), "Unknown");
+
+        let minute: TomorrowIoInterval = serde_json::from_str(r#"{
+            "time": "2023-11-14T22:14:00Z",
+            "values": {"precipitationProbability": 65, "rainIntensity": 0.4, "snowIntensity": 1.1, "sleetIntensity": 0, "freezingRainIntensity": 0}
+        }"#).unwrap();
+        let minute = minute_precipitation(&minute);
+        assert_eq!(minute.timestamp, 1700000040);
+        assert!((minute.intensity - 1.5).abs() < 1e-9);
+        assert_eq!(minute.probability, Some(65.0));
+        assert_eq!(minute.precipitation_type, Some(PrecipitationType::Snow));
+
+        let weather = normalize_current(&serde_json::json!({
+            "data": {
+                "time": "2023-11-14T22:13:20Z",
+                "values": {
+                    "temperature": 8.3, "temperatureApparent": 6.1, "humidity": 93, "pressureSurfaceLevel": 1013.2,
+                    "windSpeed": 5, "windDirection": 180, "visibility": 16.09, "uvIndex": 0, "weatherCode": 4200,
+                    "rainIntensity": 0.8, "snowIntensity": 0, "sleetIntensity": 0, "freezingRainIntensity": 0
+                }
+            },
+            "location": {"lat": 47.45, "lon": -122.31, "name": "Seattle, King County, Washington, United States"}
+        }), "47.45,-122.31").unwrap();
+        assert_eq!(weather.temperature.celsius(), 8.3);
+        assert!((weather.wind_speed.unwrap().kmh() - 18.0).abs() < 1e-9);
+        assert_eq!(weather.precipitation, Some(0.8));
+        assert_eq!(weather.description, "Light Rain");
+        assert_eq!(weather.condition, ConditionCode::Rain);
+        assert_eq!((weather.location.latitude, weather.location.longitude), (47.45, -122.31));
+        assert_eq!(weather.timestamp, 1700000000);
+    }
+
+    #[test]
+    fn test_taf_decoding() {
+        use super::super::aviation::{decode_taf, TafChange};
+        
+        let taf = decode_taf("TAF AMD KJFK 011130Z 0112/0218 18010G20KT P6SM FEW030 SCT250
+              FM011800 20015KT 3SM -SHRA BKN015 OVC030
+              TEMPO 0120/0123 1 1/2SM +TSRA BKN008CB
+              PROB30 0203/0206 VRB03KT 1/4SM FG VV002=").unwrap();
+        
+        assert_eq!(taf.station, "KJFK");
+        assert!(taf.amended);
+        assert_eq!(taf.issued.unwrap().hour, 11);
+        assert_eq!(taf.valid_to.unwrap().day, 2);
+        assert_eq!(taf.periods.len(), 4);
+        
+        let base = &taf.periods[0];
+        assert_eq!(base.change, TafChange::Base);
+        assert_eq!(base.wind.as_ref().unwrap().gust_kt, Some(20));
+        assert_eq!(base.visibility_sm, Some(6.0));
+        assert_eq!(base.ceiling_ft, None);
+        
+        let from = &taf.periods[1];
+        assert_eq!(from.change, TafChange::From);
+        assert_eq!(from.start.unwrap().hour, 18);
+        assert_eq!(from.ceiling_ft, Some(1500));
+        assert_eq!(from.conditions, vec!["light rain showers".to_string()]);
+        
+        let tempo = &taf.periods[2];
+        assert_eq!(tempo.change, TafChange::Temporary);
+        assert_eq!(tempo.visibility_sm, Some(1.5));
+        assert_eq!(tempo.clouds[0].cloud_type.as_deref(), Some("CB"));
+        assert_eq!(tempo.conditions, vec!["heavy thunderstorm with rain".to_string()]);
+        
+        let prob = &taf.periods[3];
+        assert_eq!(prob.change, TafChange::Probability(30));
+        assert_eq!(prob.wind.as_ref().unwrap().direction, None);
+        assert_eq!(prob.ceiling_ft, Some(200));
+        assert_eq!(prob.end.unwrap().hour, 6);
+    }
+    
+    #[test]
+    fn test_weather_report_value_fields() {
+        use super::super::homebrew::WeatherReport;
+        
+        let mut report = WeatherReport::new();
+        report.pm25 = Some(12.5);
+        report.percipitation = Some(0.4);
+        
+        assert_eq!(report.value("pm25"), Some(12.5));
+        assert_eq!(report.value("precipitation"), Some(0.4));
+        assert_eq!(report.value("temperature"), None);
+        assert!(WeatherReport::is_value_field("wind_speed"));
+        assert!(!WeatherReport::is_value_field("oid"));
+    }
+
+    #[test]
+    fn test_report_range_query() {
+        use super::super::homebrew::{Resolution, WeatherReport, RANGE_LIMIT};
+
+        assert_eq!("5min".parse(), Ok(Resolution::FiveMinutes));
+        assert!("week".parse::<Resolution>().is_err());
+        for resolution in [Resolution::Raw, Resolution::FiveMinutes, Resolution::Hour, Resolution::Day] {
+            assert_eq!(resolution.as_str().parse(), Ok(resolution));
+            assert_eq!(serde_json::to_value(resolution).unwrap(), resolution.as_str());
+        }
+
+        let raw = WeatherReport::range_query(Resolution::Raw);
+        assert!(raw.contains("ORDER BY timestamp ASC"));
+        assert!(raw.ends_with(&format!("LIMIT {}", RANGE_LIMIT + 1)));
+        let hourly = WeatherReport::range_query(Resolution::Hour);
+        assert!(hourly.contains("(timestamp / 3600) * 3600 AS timestamp"));
+        assert!(hourly.contains("AVG(temperature) AS temperature"));
+        assert!(hourly.contains("GROUP BY 3, device_type"));
+    }
     
+    #[tokio::test]
+    async fn test_stream_range() {
+        use super::super::homebrew::{stream_range, Cursor, FilterParams, Resolution, WeatherReport};
+        use crate::error::Result as JupiterResult;
+        use crate::storage::WeatherStore;
+        use std::sync::Arc;
+
+        // Pages of raw reports, the way the Postgres and SQLite stores read them
+        struct Reports(Vec<WeatherReport>);
+
+        #[async_trait::async_trait]
+        impl WeatherStore for Reports {
+            fn name(&self) -> String {
+                "memory".to_string()
+            }
+            async fn build(&self) -> JupiterResult<()> {
+                Ok(())
+            }
+            async fn save(&self, _report: &WeatherReport) -> JupiterResult<()> {
+                Ok(())
+            }
+            async fn latest(&self) -> JupiterResult<Option<WeatherReport>> {
+                Ok(self.0.last().cloned())
+            }
+            async fn select(&self, _limit: Option<usize>, _offset: Option<usize>, _order_column: Option<String>, _filter_params: Option<FilterParams>) -> JupiterResult<Vec<WeatherReport>> {
+                Ok(Vec::new())
+            }
+            async fn select_range(&self, _start_ts: i64, _end_ts: i64, _device_type: Option<String>, _resolution: Resolution) -> JupiterResult<Vec<WeatherReport>> {
+                Ok(Vec::new())
+            }
+            async fn select_page(&self, start_ts: i64, end_ts: i64, _device_type: Option<String>, after: Option<Cursor>, limit: usize) -> JupiterResult<Vec<WeatherReport>> {
+                Ok(self.0.iter()
+                    .filter(|r| r.timestamp >= start_ts && r.timestamp < end_ts && after.is_none_or(|after| (r.timestamp, r.id) > (after.timestamp, after.id)))
+                    .take(limit)
+                    .cloned()
+                    .collect())
+            }
+            async fn list(&self, _filter_params: Option<FilterParams>, _before: Option<Cursor>, _offset: Option<usize>, _limit: usize) -> JupiterResult<Vec<WeatherReport>> {
+                Ok(Vec::new())
+            }
+        }
+
+        let store: Arc<dyn WeatherStore> = Arc::new(Reports((0..5).map(|i| {
+            let mut report = WeatherReport::new();
+            report.id = i + 1;
+            report.timestamp = 1_000 + i as i64 * 60;
+            report
+        }).collect()));
+        let lines = |response: axum::response::Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            String::from_utf8(body.to_vec()).unwrap().lines().map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()).collect::<Vec<_>>()
+        };
+
+        let first = lines(stream_range(store.clone(), 0, 2_000, None, None, 3)).await;
+        assert_eq!(first.iter().filter_map(|line| line["id"].as_i64()).collect::<Vec<_>>(), vec![1, 2, 3]);
+        let cursor = Cursor::decode(first.last().unwrap()["next_cursor"].as_str().unwrap());
+        assert_eq!(cursor, Some(Cursor { timestamp: 1_120, id: 3 }));
+
+        let rest = lines(stream_range(store.clone(), 0, 2_000, None, cursor, 3)).await;
+        assert_eq!(rest.iter().filter_map(|line| line["id"].as_i64()).collect::<Vec<_>>(), vec![4, 5]);
+        assert!(rest.iter().all(|line| line.get("next_cursor").is_none()), "nothing left");
+
+        let exact = lines(stream_range(store, 0, 2_000, None, None, 5)).await;
+        assert_eq!(exact.len(), 5, "no cursor when the limit is met exactly");
+    }
+
+    #[test]
+    fn test_report_cursor() {
+        use super::super::homebrew::{Cursor, FilterParams, WeatherReport};
+
+        for cursor in [Cursor { timestamp: 1_700_000_000, id: 42 }, Cursor { timestamp: -1, id: i32::MAX }] {
+            let token = cursor.encode();
+            assert_eq!(token.len(), 24);
+            assert_eq!(Cursor::decode(&token), Some(cursor));
+        }
+        assert_eq!(Cursor::decode("1700000000:42"), None);
+        assert_eq!(Cursor::decode("zz0000000000000000000000"), None);
+
+        let before = Cursor { timestamp: 1_700_000_000, id: 42 };
+        let filter = FilterParams { oid: None, device_type: Some("outdoor".to_string()), device_id: None };
+        let (query, params) = WeatherReport::list_query(Some(filter), Some(before), None, 101);
+        assert_eq!(query, "SELECT * FROM weather_reports WHERE device_type = $1 AND (timestamp, id) < (1700000000, 42) ORDER BY timestamp DESC, id DESC LIMIT 101");
+        assert_eq!(params, vec!["outdoor".to_string()]);
+        let (deprecated, _) = WeatherReport::list_query(None, None, Some(200), 101);
+        assert!(deprecated.ends_with("LIMIT 101 OFFSET 200"));
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_accuweather_integration() {
@@ -186,7 +604,7 @@ mod integration_tests {
         
         match provider.get_current_weather("10001").await {
             Ok(weather) => {
-                assert!(weather.temperature != 0.0);
+                assert!(weather.temperature.celsius() != 0.0);
                 assert_eq!(weather.provider, "AccuWeather");
             }
             Err(WeatherError::InvalidApiKey) => {
@@ -206,7 +624,7 @@ mod integration_tests {
         
         match provider.get_current_weather("New York").await {
             Ok(weather) => {
-                assert!(weather.temperature != 0.0);
+                assert!(weather.temperature.celsius() != 0.0);
                 assert_eq!(weather.provider, "OpenWeather");
             }
             Err(WeatherError::InvalidApiKey) => {
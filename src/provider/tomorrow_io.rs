@@ -0,0 +1,353 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use super::common::{
+    Alert, DailyForecast, Forecast, HourlyForecast, Location, LocationQuery, MinutePrecipitation,
+    MinutelyForecast, PrecipitationType, RateLimiter, Weather, WeatherError, WeatherFeature, WeatherProvider,
+};
+use crate::analysis::comfort::comfort;
+use crate::analysis::conditions::ConditionCode;
+use crate::analysis::road::estimate_road_condition;
+use crate::archive;
+use crate::units::{Pressure, Speed, Temperature};
+use crate::secret::Secret;
+use crate::utils::time::{parse_rfc3339, safe_timestamp_with_fallback};
+
+// Tomorrow.io realtime conditions and forecasts, including minute-by-minute precipitation for
+// the next hour that backs the combo /api/nowcast endpoint. Locations are passed through to the
+// API, which geocodes coordinates, postal codes and place names itself.
+
+#[derive(Debug, Deserialize)]
+struct TomorrowIoRealtime {
+    data: TomorrowIoInterval,
+    location: Option<TomorrowIoLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TomorrowIoForecastResponse {
+    timelines: TomorrowIoTimelines,
+    location: Option<TomorrowIoLocation>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TomorrowIoTimelines {
+    #[serde(default)]
+    minutely: Vec<TomorrowIoInterval>,
+    #[serde(default)]
+    hourly: Vec<TomorrowIoInterval>,
+    #[serde(default)]
+    daily: Vec<TomorrowIoInterval>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TomorrowIoLocation {
+    lat: f64,
+    lon: f64,
+    name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TomorrowIoInterval {
+    pub time: String,
+    pub values: TomorrowIoValues,
+}
+
+/// One timestep of data fields; which ones are present depends on the endpoint and timestep
+/// (daily intervals carry `temperatureMax`, `windSpeedAvg` and so on instead)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TomorrowIoValues {
+    pub temperature: Option<f64>,
+    pub temperature_apparent: Option<f64>,
+    pub temperature_max: Option<f64>,
+    pub temperature_min: Option<f64>,
+    pub humidity: Option<f64>,
+    pub humidity_avg: Option<f64>,
+    pub pressure_surface_level: Option<f64>,
+    pub wind_speed: Option<f64>,
+    pub wind_speed_avg: Option<f64>,
+    pub wind_direction: Option<f64>,
+    pub wind_direction_avg: Option<f64>,
+    pub visibility: Option<f64>,
+    pub uv_index: Option<f64>,
+    pub cloud_cover: Option<f64>,
+    pub weather_code: Option<u32>,
+    pub weather_code_max: Option<u32>,
+    pub precipitation_probability: Option<f64>,
+    pub precipitation_probability_max: Option<f64>,
+    pub rain_intensity: Option<f64>,
+    pub snow_intensity: Option<f64>,
+    pub sleet_intensity: Option<f64>,
+    pub freezing_rain_intensity: Option<f64>,
+    pub rain_accumulation: Option<f64>,
+    pub rain_accumulation_sum: Option<f64>,
+    pub sunrise_time: Option<String>,
+    pub sunset_time: Option<String>,
+}
+
+/// Cloning shares the rate limit
+#[derive(Clone)]
+pub struct TomorrowIoProvider {
+    api_key: Secret<String>,
+    base_url: String,
+    rate_limiter: Arc<RateLimiter>,
+    client: reqwest::Client,
+}
+
+impl TomorrowIoProvider {
+    pub fn new(api_key: impl Into<Secret<String>>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            base_url: "https://api.tomorrow.io/v4".to_string(),
+            rate_limiter: Arc::new(RateLimiter::new(25, 3600)), // Free tier: 25 requests per hour
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The `location` parameter as Tomorrow.io expects it, e.g. "10001 US" for a ZIP code
+    fn location_param(location: &str) -> Result<String, WeatherError> {
+        Ok(match LocationQuery::parse(location).map_err(WeatherError::ConfigurationError)? {
+            LocationQuery::Coordinates { latitude, longitude } => format!("{},{}", latitude, longitude),
+            LocationQuery::PostalCode { country, code } => format!("{} {}", code, country.as_deref().unwrap_or("US")),
+            LocationQuery::City { name, country: Some(country) } => format!("{}, {}", name, country),
+            LocationQuery::City { name, country: None } => name,
+        })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str, params: &[(&str, &str)], endpoint: &str, location: &str) -> Result<T, WeatherError> {
+        if !self.rate_limiter.check_rate_limit() {
+            return Err(WeatherError::RateLimitExceeded);
+        }
+
+        let response = self.client.get(format!("{}{}", self.base_url, path))
+            .query(&[("location", Self::location_param(location)?.as_str()), ("units", "metric")])
+            .query(params)
+            .query(&[("apikey", self.api_key.expose().as_str())])
+            .send()
+            .await?;
+
+        match response.status().as_u16() {
+            401 | 403 => return Err(WeatherError::InvalidApiKey),
+            429 => return Err(WeatherError::RateLimitExceeded),
+            // Unresolvable locations are answered with 400
+            400 => return Err(WeatherError::NotFound(format!("Tomorrow.io has no data for {}", location))),
+            _ => {},
+        }
+
+        archive::json("tomorrow_io", endpoint, location, response).await
+    }
+
+    async fn get_timelines(&self, timesteps: &str, endpoint: &str, location: &str) -> Result<(Location, TomorrowIoTimelines), WeatherError> {
+        let response: TomorrowIoForecastResponse = self.get("/weather/forecast", &[("timesteps", timesteps)], endpoint, location).await?;
+        Ok((place(response.location.as_ref(), location), response.timelines))
+    }
+}
+
+fn place(reported: Option<&TomorrowIoLocation>, location: &str) -> Location {
+    Location {
+        latitude: reported.map(|l| l.lat).unwrap_or(0.0),
+        longitude: reported.map(|l| l.lon).unwrap_or(0.0),
+        name: reported.and_then(|l| l.name.clone()).unwrap_or_else(|| location.to_string()),
+        country: None,
+        region: None,
+        postal_code: None,
+    }
+}
+
+/// Description of a Tomorrow.io weather code
+pub fn weather_description(code: u32) -> &'static str {
+    match code {
+        1000 => "Clear",
+        1100 => "Mostly Clear",
+        1101 => "Partly Cloudy",
+        1102 => "Mostly Cloudy",
+        1001 => "Cloudy",
+        2000 => "Fog",
+        2100 => "Light Fog",
+        4000 => "Drizzle",
+        4001 => "Rain",
+        4200 => "Light Rain",
+        4201 => "Heavy Rain",
+        5000 => "Snow",
+        5001 => "Flurries",
+        5100 => "Light Snow",
+        5101 => "Heavy Snow",
+        6000 => "Freezing Drizzle",
+        6001 => "Freezing Rain",
+        6200 => "Light Freezing Rain",
+        6201 => "Heavy Freezing Rain",
+        7000 => "Ice Pellets",
+        7101 => "Heavy Ice Pellets",
+        7102 => "Light Ice Pellets",
+        8000 => "Thunderstorm",
+        _ => "Unknown",
+    }
+}
+
+fn describe(code: Option<u32>) -> (String, ConditionCode) {
+    let description = code.map(weather_description).unwrap_or("Unknown");
+    (description.to_string(), ConditionCode::from_description(description))
+}
+
+/// Total precipitation intensity of an interval and its dominant type
+pub fn minute_precipitation(interval: &TomorrowIoInterval) -> MinutePrecipitation {
+    let values = &interval.values;
+    let types = [
+        (PrecipitationType::Rain, values.rain_intensity),
+        (PrecipitationType::Snow, values.snow_intensity),
+        (PrecipitationType::Sleet, values.sleet_intensity),
+        (PrecipitationType::FreezingRain, values.freezing_rain_intensity),
+    ];
+    let intensity = types.iter().filter_map(|(_, i)| *i).sum();
+    let precipitation_type = types.iter()
+        .filter_map(|(kind, i)| i.filter(|i| *i > 0.0).map(|i| (*kind, i)))
+        .fold(None, |best: Option<(PrecipitationType, f64)>, (kind, i)| match best {
+            Some((_, best_i)) if best_i >= i => best,
+            _ => Some((kind, i)),
+        })
+        .map(|(kind, _)| kind);
+
+    MinutePrecipitation {
+        timestamp: parse_rfc3339(&interval.time).unwrap_or_else(safe_timestamp_with_fallback),
+        intensity,
+        probability: values.precipitation_probability,
+        precipitation_type,
+    }
+}
+
+fn hourly_forecast(interval: &TomorrowIoInterval) -> HourlyForecast {
+    let values = &interval.values;
+    let (description, condition) = describe(values.weather_code);
+    HourlyForecast {
+        datetime: interval.time.clone(),
+        temperature: Temperature::from_celsius(values.temperature.unwrap_or_default()),
+        feels_like: values.temperature_apparent.map(Temperature::from_celsius),
+        humidity: values.humidity,
+        precipitation_probability: values.precipitation_probability,
+        precipitation_amount: values.rain_accumulation,
+        // Wind speeds come in m/s even with units=metric
+        wind_speed: values.wind_speed.map(Speed::from_meters_per_second),
+        wind_direction: values.wind_direction,
+        description,
+        condition,
+        icon: None,
+        cloud_cover: values.cloud_cover,
+        solar_irradiance: None,
+    }
+}
+
+fn daily_forecast(interval: &TomorrowIoInterval) -> DailyForecast {
+    let values = &interval.values;
+    let (description, condition) = describe(values.weather_code_max.or(values.weather_code));
+    DailyForecast {
+        date: interval.time.get(..10).unwrap_or(&interval.time).to_string(),
+        temperature_min: Temperature::from_celsius(values.temperature_min.unwrap_or_default()),
+        temperature_max: Temperature::from_celsius(values.temperature_max.unwrap_or_default()),
+        humidity: values.humidity_avg,
+        precipitation_probability: values.precipitation_probability_max,
+        precipitation_amount: values.rain_accumulation_sum,
+        wind_speed: values.wind_speed_avg.map(Speed::from_meters_per_second),
+        wind_direction: values.wind_direction_avg,
+        description,
+        condition,
+        icon: None,
+        sunrise: values.sunrise_time.clone(),
+        sunset: values.sunset_time.clone(),
+        solar_irradiance: None,
+        day: None,
+        night: None,
+    }
+}
+
+fn realtime_weather(response: &TomorrowIoRealtime, location: &str) -> Result<Weather, WeatherError> {
+    let values = &response.data.values;
+    let temperature = values.temperature.map(Temperature::from_celsius)
+        .ok_or_else(|| WeatherError::NotFound("Tomorrow.io realtime data has no temperature".to_string()))?;
+    let wind_speed = values.wind_speed.map(Speed::from_meters_per_second);
+    let precipitation = Some(minute_precipitation(&response.data).intensity);
+    let (description, condition) = describe(values.weather_code);
+
+    Ok(Weather {
+        temperature,
+        feels_like: values.temperature_apparent.map(Temperature::from_celsius),
+        humidity: values.humidity,
+        pressure: values.pressure_surface_level.map(Pressure::from_hpa),
+        wind_speed,
+        wind_direction: values.wind_direction,
+        description,
+        condition,
+        icon: None,
+        precipitation,
+        visibility: values.visibility,
+        uv_index: values.uv_index,
+        road_condition: Some(estimate_road_condition(temperature, values.humidity, precipitation, None)),
+        comfort: Some(comfort(temperature, values.humidity, wind_speed, values.uv_index)),
+        provider: "Tomorrow.io".to_string(),
+        location: place(response.location.as_ref(), location),
+        timestamp: parse_rfc3339(&response.data.time).unwrap_or_else(safe_timestamp_with_fallback),
+        sources: Vec::new(),
+        spread: BTreeMap::new(),
+    })
+}
+
+/// Re-normalizes an archived `current` payload (a realtime response)
+pub fn normalize_current(payload: &serde_json::Value, location: &str) -> Result<Weather, WeatherError> {
+    let response: TomorrowIoRealtime = serde_json::from_value(payload.clone())?;
+    realtime_weather(&response, location)
+}
+
+#[async_trait]
+impl WeatherProvider for TomorrowIoProvider {
+    async fn get_current_weather(&self, location: &str) -> Result<Weather, WeatherError> {
+        let response: TomorrowIoRealtime = self.get("/weather/realtime", &[], "current", location).await?;
+        realtime_weather(&response, location)
+    }
+
+    async fn get_forecast(&self, location: &str, days: u8) -> Result<Forecast, WeatherError> {
+        let (place, timelines) = self.get_timelines("1h,1d", "forecast", location).await?;
+        Ok(Forecast {
+            location: place,
+            provider: "Tomorrow.io".to_string(),
+            daily: timelines.daily.iter().take(days as usize).map(daily_forecast).collect(),
+            hourly: Some(timelines.hourly.iter().take(48).map(hourly_forecast).collect()),
+        })
+    }
+
+    async fn get_alerts(&self, _location: &str) -> Result<Vec<Alert>, WeatherError> {
+        // Alerts need a paid plan
+        Ok(Vec::new())
+    }
+
+    async fn get_minutely(&self, location: &str) -> Result<MinutelyForecast, WeatherError> {
+        let (place, timelines) = self.get_timelines("1m", "minutely", location).await?;
+        if timelines.minutely.is_empty() {
+            return Err(WeatherError::NotFound(format!("Tomorrow.io has no minute-level forecast for {}", location)));
+        }
+        Ok(MinutelyForecast {
+            location: place,
+            provider: "Tomorrow.io".to_string(),
+            minutes: timelines.minutely.iter().map(minute_precipitation).collect(),
+            timestamp: safe_timestamp_with_fallback(),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "Tomorrow.io"
+    }
+
+    fn supports_feature(&self, feature: WeatherFeature) -> bool {
+        match feature {
+            WeatherFeature::CurrentWeather => true,
+            WeatherFeature::Forecast => true,
+            WeatherFeature::Alerts => false,
+            WeatherFeature::HourlyForecast => true,
+            WeatherFeature::UvIndex => true,
+            WeatherFeature::AirQuality => false,
+            WeatherFeature::Pollen => false,
+            WeatherFeature::Marine => false,
+            WeatherFeature::MinutelyForecast => true,
+            WeatherFeature::HistoricalData => false,
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use once_cell::sync::Lazy;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+use crate::provider::homebrew::WeatherReport;
+
+// Results of the bucketed history queries (`resolution=5min|hour|day`), which average every
+// report of the range with a GROUP BY. Dashboards poll the same window, usually the last day of
+// a chart, so repeat requests are served from here until a report saved in that window (of that
+// device type) drops them. Entries also expire after QUERY_CACHE_TTL_SECS (60 by default, 0
+// turns the cache off) in case other instances write to the same database.
+//
+// Erasures and retention delete reports from any window and clear the whole cache.
+
+const DEFAULT_TTL_SECS: u64 = 60;
+// Bucketed reports kept across all entries before the oldest entries are dropped
+const MAX_REPORTS: usize = 50_000;
+
+/// A bucketed range query
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RangeKey {
+    pub start: i64,
+    pub end: i64,
+    pub device_type: Option<String>,
+    pub bucket_secs: i64,
+}
+
+impl RangeKey {
+    /// Whether a report saved by `device_type` at `timestamp` changes this result
+    fn covers(&self, device_type: &str, timestamp: i64) -> bool {
+        timestamp >= self.start && timestamp < self.end
+            && self.device_type.as_deref().is_none_or(|filter| filter == device_type)
+    }
+}
+
+struct Entry {
+    key: RangeKey,
+    reports: Arc<Vec<WeatherReport>>,
+    stored: Instant,
+}
+
+pub struct QueryCache {
+    ttl: Duration,
+    max_reports: usize,
+    entries: Mutex<Vec<Entry>>,  // Oldest first
+    generation: AtomicU64,       // Bumped by every invalidation
+}
+
+impl QueryCache {
+    pub fn new(ttl: Duration, max_reports: usize) -> Self {
+        Self { ttl, max_reports, entries: Mutex::new(Vec::new()), generation: AtomicU64::new(0) }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// To pass to `insert` for results queried after this call
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn get(&self, key: &RangeKey) -> Option<Arc<Vec<WeatherReport>>> {
+        let mut entries = self.entries.lock().ok()?;
+        entries.retain(|entry| entry.stored.elapsed() < self.ttl);
+        let reports = entries.iter().find(|entry| entry.key == *key).map(|entry| entry.reports.clone());
+        metrics::record_cache_lookup("range", reports.is_some());
+        reports
+    }
+
+    /// Stores the result of a query started at `generation`, unless a report was saved since:
+    /// the query may not have seen it
+    pub fn insert(&self, key: RangeKey, reports: Vec<WeatherReport>, generation: u64) {
+        if !self.enabled() || reports.len() > self.max_reports {
+            return;
+        }
+        let Ok(mut entries) = self.entries.lock() else { return };
+        if self.generation() != generation {
+            return;
+        }
+        entries.retain(|entry| entry.key != key);
+        entries.push(Entry { key, reports: Arc::new(reports), stored: Instant::now() });
+        let mut total: usize = entries.iter().map(|entry| entry.reports.len()).sum();
+        while total > self.max_reports {
+            total -= entries.remove(0).reports.len();
+        }
+    }
+
+    /// Drops the results a report saved by `device_type` at `timestamp` changes
+    pub fn invalidate(&self, device_type: &str, timestamp: i64) {
+        if let Ok(mut entries) = self.entries.lock() {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            entries.retain(|entry| !entry.key.covers(device_type, timestamp));
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            entries.clear();
+        }
+    }
+}
+
+/// QUERY_CACHE_TTL_SECS, 0 to turn the cache off
+pub fn ttl_from_env() -> Result<Duration, String> {
+    match env::var("QUERY_CACHE_TTL_SECS").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+        None => Ok(Duration::from_secs(DEFAULT_TTL_SECS)),
+        Some(secs) => secs.parse::<u64>().map(Duration::from_secs)
+            .map_err(|_| format!("must be a number of seconds (0 turns the cache off), got '{}'", secs)),
+    }
+}
+
+pub static RANGE_CACHE: Lazy<QueryCache> = Lazy::new(|| {
+    let ttl = ttl_from_env().unwrap_or_else(|message| {
+        log::error!("Invalid QUERY_CACHE_TTL_SECS, history query cache disabled: {}", message);
+        Duration::ZERO
+    });
+    QueryCache::new(ttl, MAX_REPORTS)
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(start: i64, end: i64, device_type: Option<&str>) -> RangeKey {
+        RangeKey { start, end, device_type: device_type.map(str::to_string), bucket_secs: 3600 }
+    }
+
+    fn reports(count: usize) -> Vec<WeatherReport> {
+        (0..count).map(|i| WeatherReport { timestamp: i as i64 * 3600, ..WeatherReport::new() }).collect()
+    }
+
+    #[test]
+    fn test_query_cache() {
+        let cache = QueryCache::new(Duration::from_secs(60), 10);
+        let day = key(0, 86_400, None);
+        let outdoor = key(0, 86_400, Some("outdoor"));
+        assert!(cache.get(&day).is_none());
+
+        cache.insert(day.clone(), reports(4), cache.generation());
+        cache.insert(outdoor.clone(), reports(4), cache.generation());
+        assert_eq!(cache.get(&day).unwrap().len(), 4);
+
+        // Only windows containing the report, of its device type, are dropped
+        cache.invalidate("indoor", 100_000);
+        cache.invalidate("indoor", 3_600);
+        assert!(cache.get(&day).is_none());
+        assert!(cache.get(&outdoor).is_some());
+
+        // A result queried before a save isn't stored
+        let generation = cache.generation();
+        cache.invalidate("outdoor", 7_200);
+        cache.insert(day.clone(), reports(4), generation);
+        assert!(cache.get(&day).is_none());
+
+        // The oldest entries go once the cache holds too many reports
+        cache.insert(day.clone(), reports(6), cache.generation());
+        cache.insert(key(86_400, 172_800, None), reports(6), cache.generation());
+        assert!(cache.get(&day).is_none());
+        assert!(cache.get(&key(86_400, 172_800, None)).is_some());
+
+        cache.clear();
+        assert!(cache.get(&key(86_400, 172_800, None)).is_none());
+
+        let disabled = QueryCache::new(Duration::ZERO, 10);
+        disabled.insert(day.clone(), reports(1), disabled.generation());
+        assert!(disabled.get(&day).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+
+use crate::db_pool::get_homebrew_pool;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::homebrew::WeatherReport;
+
+// Optional tamper-evident audit trail for stored reports (REPORT_HASH_CHAIN=true).
+// Every time a report is saved its stored row is appended to `report_chain` as JSON together
+// with SHA-256(previous hash || payload), so editing or deleting a historical entry, or editing
+// the report row it describes, breaks the chain. GET /api/audit/verify walks the chain.
+// Erasing a device's data (see `admin`) blanks the payloads of its entries but keeps their
+// hashes, so the rest of the chain stays verifiable.
+
+/// Hash the first entry links to
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// pg_advisory_xact_lock key serialising appends, so two writers can't fork the chain
+const CHAIN_LOCK_KEY: i64 = 0x6a_7570_6974_6572;
+
+pub fn enabled_from_env() -> bool {
+    std::env::var("REPORT_HASH_CHAIN").map(|v| v == "true" || v == "1").unwrap_or(false)
+}
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.report_chain (
+        id serial NOT NULL,
+        report_oid varchar NOT NULL,
+        payload TEXT NOT NULL,
+        prev_hash varchar NOT NULL,
+        hash varchar NOT NULL,
+        timestamp BIGINT DEFAULT 0,
+        CONSTRAINT report_chain_pkey PRIMARY KEY (id));
+    CREATE INDEX IF NOT EXISTS report_chain_report_oid_idx ON report_chain (report_oid);"
+}
+
+pub fn chain_hash(prev_hash: &str, payload: &str) -> String {
+    let mut input = Vec::with_capacity(prev_hash.len() + payload.len());
+    input.extend_from_slice(prev_hash.as_bytes());
+    input.extend_from_slice(payload.as_bytes());
+    Sha256::digest(&input).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainEntry {
+    pub id: i32,
+    pub report_oid: String,
+    pub payload: String,
+    pub prev_hash: String,
+    pub hash: String,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainVerification {
+    pub valid: bool,
+    pub entries: usize,
+    pub erased: usize,             // Entries whose payload was blanked by a data erasure
+    pub head: Option<String>,      // Hash of the newest entry, worth recording externally
+    pub problems: Vec<String>,
+}
+
+/// Checks linkage and hashes of `entries` (in id order), and that the latest entry for each
+/// report still matches its stored row. `current` maps report oids to their stored rows as JSON;
+/// fields added to reports after an entry was written are ignored.
+pub fn verify_entries(entries: &[ChainEntry], current: &HashMap<String, Value>) -> ChainVerification {
+    let mut problems = Vec::new();
+    let mut prev_hash = GENESIS_HASH.to_string();
+    let mut latest: HashMap<&str, &ChainEntry> = HashMap::new();
+    let mut erased = 0;
+
+    for entry in entries {
+        if entry.prev_hash != prev_hash {
+            problems.push(format!("Entry {} does not link to the previous entry (missing or reordered entries)", entry.id));
+        }
+        prev_hash = entry.hash.clone();
+        if entry.payload.is_empty() {
+            erased += 1;
+            continue;
+        }
+        if chain_hash(&entry.prev_hash, &entry.payload) != entry.hash {
+            problems.push(format!("Entry {} payload does not match its hash", entry.id));
+        }
+        latest.insert(&entry.report_oid, entry);
+    }
+
+    let mut oids: Vec<&&str> = latest.keys().collect();
+    oids.sort();
+    for oid in oids {
+        let entry = latest[*oid];
+        let recorded: Value = match serde_json::from_str(&entry.payload) {
+            Ok(value) => value,
+            Err(_) => continue, // Already reported as a hash mismatch unless the writer was broken
+        };
+        match (recorded.as_object(), current.get(*oid)) {
+            (Some(recorded), Some(row)) => {
+                let changed: Vec<&String> = recorded.iter()
+                    .filter(|(key, value)| row.get(key.as_str()) != Some(value))
+                    .map(|(key, _)| key)
+                    .collect();
+                if !changed.is_empty() {
+                    problems.push(format!("Report {} was modified after entry {} ({})", oid, entry.id,
+                        changed.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(", ")));
+                }
+            },
+            (_, None) => problems.push(format!("Report {} recorded in entry {} no longer exists", oid, entry.id)),
+            (None, _) => {},
+        }
+    }
+
+    ChainVerification {
+        valid: problems.is_empty(),
+        entries: entries.len(),
+        erased,
+        head: entries.last().map(|e| e.hash.clone()),
+        problems,
+    }
+}
+
+/// Appends the stored row of `report` to the chain
+pub async fn append(client: &mut deadpool_postgres::Client, report: &WeatherReport) -> JupiterResult<ChainEntry> {
+    let payload = serde_json::to_string(report)?;
+    let db_error = |e: tokio_postgres::Error| JupiterError::DatabaseError(format!("Failed to append to report chain: {}", e));
+
+    let transaction = client.transaction().await.map_err(db_error)?;
+    transaction.execute("SELECT pg_advisory_xact_lock($1)", &[&CHAIN_LOCK_KEY]).await.map_err(db_error)?;
+    let prev_hash: String = transaction.query_opt("SELECT hash FROM report_chain ORDER BY id DESC LIMIT 1", &[]).await
+        .map_err(db_error)?
+        .map(|row| row.get("hash"))
+        .unwrap_or_else(|| GENESIS_HASH.to_string());
+    let hash = chain_hash(&prev_hash, &payload);
+    let row = transaction.query_one(
+        "INSERT INTO report_chain (report_oid, payload, prev_hash, hash, timestamp) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        &[&report.oid, &payload, &prev_hash, &hash, &report.timestamp],
+    ).await.map_err(db_error)?;
+    transaction.commit().await.map_err(db_error)?;
+
+    Ok(ChainEntry {
+        id: row.get("id"),
+        report_oid: report.oid.clone(),
+        payload,
+        prev_hash,
+        hash,
+        timestamp: report.timestamp,
+    })
+}
+
+/// Verifies the whole chain against the current weather_reports table
+pub async fn verify() -> JupiterResult<ChainVerification> {
+    let pool = get_homebrew_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+
+    let rows = client.query("SELECT * FROM report_chain ORDER BY id", &[]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+    let entries: Vec<ChainEntry> = rows.iter().map(|row| ChainEntry {
+        id: row.get("id"),
+        report_oid: row.get("report_oid"),
+        payload: row.get("payload"),
+        prev_hash: row.get("prev_hash"),
+        hash: row.get("hash"),
+        timestamp: row.get("timestamp"),
+    }).collect();
+
+    let oids: Vec<String> = entries.iter().map(|e| e.report_oid.clone()).collect();
+    let rows = client.query("SELECT * FROM weather_reports WHERE oid = ANY($1)", &[&oids]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+    let mut current = HashMap::new();
+    for row in rows {
+        let report = WeatherReport::from_row(&row)?;
+        current.insert(report.oid.clone(), serde_json::to_value(&report)?);
+    }
+
+    Ok(verify_entries(&entries, &current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(payloads: &[(&str, &str)]) -> Vec<ChainEntry> {
+        let mut prev_hash = GENESIS_HASH.to_string();
+        payloads.iter().enumerate().map(|(i, (oid, payload))| {
+            let hash = chain_hash(&prev_hash, payload);
+            let entry = ChainEntry {
+                id: i as i32 + 1,
+                report_oid: oid.to_string(),
+                payload: payload.to_string(),
+                prev_hash: prev_hash.clone(),
+                hash: hash.clone(),
+                timestamp: 0,
+            };
+            prev_hash = hash;
+            entry
+        }).collect()
+    }
+
+    #[test]
+    fn test_chain_hash() {
+        assert_eq!(chain_hash("", "abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_verify_detects_tampering() {
+        let entries = chain(&[("a", r#"{"oid":"a","temperature":20.5}"#), ("b", r#"{"oid":"b","temperature":21.0}"#)]);
+        let mut current = HashMap::new();
+        current.insert("a".to_string(), serde_json::json!({"oid": "a", "temperature": 20.5, "rssi": null}));
+        current.insert("b".to_string(), serde_json::json!({"oid": "b", "temperature": 21.0}));
+        assert!(verify_entries(&entries, &current).valid);
+
+        // Edited report row
+        current.insert("b".to_string(), serde_json::json!({"oid": "b", "temperature": 25.0}));
+        let result = verify_entries(&entries, &current);
+        assert!(!result.valid);
+        assert_eq!(result.problems, vec!["Report b was modified after entry 2 (temperature)".to_string()]);
+
+        // Rewritten chain entry
+        current.insert("b".to_string(), serde_json::json!({"oid": "b", "temperature": 21.0}));
+        let mut tampered = entries.clone();
+        tampered[0].payload = r#"{"oid":"a","temperature":10.0}"#.to_string();
+        assert!(!verify_entries(&tampered, &current).valid);
+
+        // Deleted entry
+        assert!(!verify_entries(&entries[1..], &current).valid);
+
+        // Erased entries keep the chain intact
+        let mut erased = entries.clone();
+        erased[0].payload = String::new();
+        current.remove("a");
+        let result = verify_entries(&erased, &current);
+        assert!(result.valid);
+        assert_eq!(result.erased, 1);
+    }
+}
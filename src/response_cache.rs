@@ -0,0 +1,79 @@
+use axum::response::Response;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::metrics;
+use crate::response_format::{self, ResponseFormat};
+
+// Serialized bodies of the combo server's cached current conditions, one per response format.
+// Repeat requests within the cache timeout copy these bytes instead of reading the cache row
+// back from Postgres and serializing it again.
+
+#[derive(Debug, Clone)]
+pub struct CachedBody {
+    pub format: ResponseFormat,
+    pub body: Arc<Vec<u8>>,
+    pub timestamp: i64,         // Timestamp of the data the body was serialized from
+}
+
+impl CachedBody {
+    pub fn response(&self) -> Response {
+        response_format::body_response(self.format, self.body.as_ref().clone())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    entries: RwLock<HashMap<ResponseFormat, CachedBody>>,
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The body for `format` if its data is less than `ttl` seconds old at `now`
+    pub fn get(&self, format: ResponseFormat, now: i64, ttl: i64) -> Option<CachedBody> {
+        let entries = self.entries.read().ok()?;
+        let entry = entries.get(&format)
+            .filter(|entry| now - entry.timestamp < ttl)
+            .cloned();
+        metrics::record_cache_lookup("response", entry.is_some());
+        entry
+    }
+
+    /// Stores the body serialized from data fetched at `timestamp`. Bodies of older data in
+    /// other formats are dropped so no format keeps serving superseded conditions.
+    pub fn insert(&self, format: ResponseFormat, body: Vec<u8>, timestamp: i64) -> CachedBody {
+        let entry = CachedBody { format, body: Arc::new(body), timestamp };
+        match self.entries.write() {
+            Ok(mut entries) => {
+                entries.retain(|_, existing| existing.timestamp >= timestamp);
+                entries.insert(format, entry.clone());
+            },
+            Err(e) => log::error!("Response cache lock poisoned: {}", e),
+        }
+        entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_cache() {
+        let cache = ResponseCache::new();
+        assert!(cache.get(ResponseFormat::Json, 100, 60).is_none());
+
+        cache.insert(ResponseFormat::Json, b"{\"a\":1}".to_vec(), 100);
+        cache.insert(ResponseFormat::Cbor, vec![0xa1], 100);
+        assert_eq!(cache.get(ResponseFormat::Json, 150, 60).unwrap().body.as_slice(), b"{\"a\":1}");
+        assert!(cache.get(ResponseFormat::Json, 160, 60).is_none());
+
+        // Newer data supersedes every format serialized from older data
+        cache.insert(ResponseFormat::Json, b"{\"a\":2}".to_vec(), 130);
+        assert!(cache.get(ResponseFormat::Cbor, 140, 60).is_none());
+        assert_eq!(cache.get(ResponseFormat::Json, 140, 60).unwrap().timestamp, 130);
+    }
+}
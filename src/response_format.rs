@@ -0,0 +1,317 @@
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use serde::Serialize;
+use serde_json::Value;
+
+// Compact binary encodings for constrained clients (e.g. ESP32 displays polling for updates).
+// Requested with `?format=cbor` / `?format=msgpack` or a matching Accept header; JSON otherwise.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResponseFormat {
+    Json,
+    Cbor,
+    MessagePack,
+}
+
+impl ResponseFormat {
+    pub fn from_name(name: &str) -> Option<ResponseFormat> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Some(ResponseFormat::Json),
+            "cbor" => Some(ResponseFormat::Cbor),
+            "msgpack" | "messagepack" => Some(ResponseFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "application/json; charset=utf-8",
+            ResponseFormat::Cbor => "application/cbor",
+            ResponseFormat::MessagePack => "application/msgpack",
+        }
+    }
+
+    /// The `format` query parameter takes precedence over the Accept header
+    pub fn negotiate(format: Option<&str>, accept: &str) -> Result<ResponseFormat, String> {
+        if let Some(format) = format {
+            return ResponseFormat::from_name(format)
+                .ok_or_else(|| format!("Unsupported format '{}', expected json, cbor or msgpack", format));
+        }
+
+        if accept.contains("application/cbor") {
+            Ok(ResponseFormat::Cbor)
+        } else if accept.contains("application/msgpack") || accept.contains("application/x-msgpack") {
+            Ok(ResponseFormat::MessagePack)
+        } else {
+            Ok(ResponseFormat::Json)
+        }
+    }
+}
+
+/// Negotiated from the request; unsupported `format` values are rejected with a 400
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ResponseFormat {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let params = Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+            .map(|Query(params)| params)
+            .unwrap_or_default();
+        let accept = parts.headers.get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .unwrap_or("");
+        ResponseFormat::negotiate(params.get("format").map(String::as_str), accept)
+            .map_err(|message| (StatusCode::BAD_REQUEST, message).into_response())
+    }
+}
+
+/// Raw body with the content type of `format`
+pub fn body_response(format: ResponseFormat, body: Vec<u8>) -> Response {
+    ([(header::CONTENT_TYPE, format.content_type())], body).into_response()
+}
+
+/// Serializes `value` to a response body in `format`
+pub fn encode<T: Serialize>(format: ResponseFormat, value: &T) -> Result<Vec<u8>, serde_json::Error> {
+    match format {
+        ResponseFormat::Json => serde_json::to_vec(value),
+        ResponseFormat::Cbor => Ok(to_cbor(&serde_json::to_value(value)?)),
+        ResponseFormat::MessagePack => Ok(to_msgpack(&serde_json::to_value(value)?)),
+    }
+}
+
+/// Serializes `value` in the format requested by the client
+pub fn respond<T: Serialize>(format: ResponseFormat, value: &T) -> Response {
+    match encode(format, value) {
+        Ok(body) => body_response(format, body),
+        Err(e) => {
+            log::error!("Failed to serialize response: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Serialization error").into_response()
+        }
+    }
+}
+
+// Floats that survive a round trip through f32 are sent as f32 to save four bytes each
+fn as_f32(f: f64) -> Option<f32> {
+    let narrow = f as f32;
+    if narrow as f64 == f { Some(narrow) } else { None }
+}
+
+/// Encodes a JSON value as CBOR (RFC 8949)
+pub fn to_cbor(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_cbor(value, &mut out);
+    out
+}
+
+fn write_cbor_head(major: u8, len: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    if len < 24 {
+        out.push(major | len as u8);
+    } else if len <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(len as u8);
+    } else if len <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else if len <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&len.to_be_bytes());
+    }
+}
+
+fn write_cbor(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                write_cbor_head(0, u, out);
+            } else if let Some(i) = n.as_i64() {
+                // Negative integers are encoded as -1 - n
+                write_cbor_head(1, (-1 - i) as u64, out);
+            } else {
+                let f = n.as_f64().unwrap_or(0.0);
+                match as_f32(f) {
+                    Some(narrow) => {
+                        out.push(0xfa);
+                        out.extend_from_slice(&narrow.to_be_bytes());
+                    }
+                    None => {
+                        out.push(0xfb);
+                        out.extend_from_slice(&f.to_be_bytes());
+                    }
+                }
+            }
+        }
+        Value::String(s) => {
+            write_cbor_head(3, s.len() as u64, out);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(items) => {
+            write_cbor_head(4, items.len() as u64, out);
+            for item in items {
+                write_cbor(item, out);
+            }
+        }
+        Value::Object(map) => {
+            write_cbor_head(5, map.len() as u64, out);
+            for (key, item) in map {
+                write_cbor_head(3, key.len() as u64, out);
+                out.extend_from_slice(key.as_bytes());
+                write_cbor(item, out);
+            }
+        }
+    }
+}
+
+/// Encodes a JSON value as MessagePack
+pub fn to_msgpack(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_msgpack(value, &mut out);
+    out
+}
+
+fn write_msgpack_str(s: &str, out: &mut Vec<u8>) {
+    let len = s.len();
+    if len < 32 {
+        out.push(0xa0 | len as u8);
+    } else if len <= u8::MAX as usize {
+        out.push(0xd9);
+        out.push(len as u8);
+    } else if len <= u16::MAX as usize {
+        out.push(0xda);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(0xdb);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_msgpack_container(len: usize, fix: u8, marker16: u8, out: &mut Vec<u8>) {
+    if len < 16 {
+        out.push(fix | len as u8);
+    } else if len <= u16::MAX as usize {
+        out.push(marker16);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(marker16 + 1);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+fn write_msgpack(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                if u < 128 {
+                    out.push(u as u8);
+                } else if u <= u8::MAX as u64 {
+                    out.push(0xcc);
+                    out.push(u as u8);
+                } else if u <= u16::MAX as u64 {
+                    out.push(0xcd);
+                    out.extend_from_slice(&(u as u16).to_be_bytes());
+                } else if u <= u32::MAX as u64 {
+                    out.push(0xce);
+                    out.extend_from_slice(&(u as u32).to_be_bytes());
+                } else {
+                    out.push(0xcf);
+                    out.extend_from_slice(&u.to_be_bytes());
+                }
+            } else if let Some(i) = n.as_i64() {
+                if i >= -32 {
+                    out.push(i as i8 as u8);
+                } else if i >= i8::MIN as i64 {
+                    out.push(0xd0);
+                    out.push(i as i8 as u8);
+                } else if i >= i16::MIN as i64 {
+                    out.push(0xd1);
+                    out.extend_from_slice(&(i as i16).to_be_bytes());
+                } else if i >= i32::MIN as i64 {
+                    out.push(0xd2);
+                    out.extend_from_slice(&(i as i32).to_be_bytes());
+                } else {
+                    out.push(0xd3);
+                    out.extend_from_slice(&i.to_be_bytes());
+                }
+            } else {
+                let f = n.as_f64().unwrap_or(0.0);
+                match as_f32(f) {
+                    Some(narrow) => {
+                        out.push(0xca);
+                        out.extend_from_slice(&narrow.to_be_bytes());
+                    }
+                    None => {
+                        out.push(0xcb);
+                        out.extend_from_slice(&f.to_be_bytes());
+                    }
+                }
+            }
+        }
+        Value::String(s) => write_msgpack_str(s, out),
+        Value::Array(items) => {
+            write_msgpack_container(items.len(), 0x90, 0xdc, out);
+            for item in items {
+                write_msgpack(item, out);
+            }
+        }
+        Value::Object(map) => {
+            write_msgpack_container(map.len(), 0x80, 0xde, out);
+            for (key, item) in map {
+                write_msgpack_str(key, out);
+                write_msgpack(item, out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!(ResponseFormat::from_name("CBOR"), Some(ResponseFormat::Cbor));
+        assert_eq!(ResponseFormat::from_name("msgpack"), Some(ResponseFormat::MessagePack));
+        assert_eq!(ResponseFormat::from_name("xml"), None);
+        assert_eq!(ResponseFormat::negotiate(Some("cbor"), "application/msgpack"), Ok(ResponseFormat::Cbor));
+        assert_eq!(ResponseFormat::negotiate(None, "application/x-msgpack"), Ok(ResponseFormat::MessagePack));
+        assert_eq!(ResponseFormat::negotiate(None, "*/*"), Ok(ResponseFormat::Json));
+        assert!(ResponseFormat::negotiate(Some("text"), "").is_err());
+    }
+
+    #[test]
+    fn test_cbor_encoding() {
+        // Examples from RFC 8949 Appendix A
+        assert_eq!(to_cbor(&json!(0)), vec![0x00]);
+        assert_eq!(to_cbor(&json!(500)), vec![0x19, 0x01, 0xf4]);
+        assert_eq!(to_cbor(&json!(-10)), vec![0x29]);
+        assert_eq!(to_cbor(&json!(1.5)), vec![0xfa, 0x3f, 0xc0, 0x00, 0x00]);
+        assert_eq!(to_cbor(&json!(1.1)), vec![0xfb, 0x3f, 0xf1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a]);
+        assert_eq!(to_cbor(&json!(null)), vec![0xf6]);
+        assert_eq!(to_cbor(&json!({"a": [1, "b"]})), vec![0xa1, 0x61, 0x61, 0x82, 0x01, 0x61, 0x62]);
+    }
+
+    #[test]
+    fn test_msgpack_encoding() {
+        assert_eq!(to_msgpack(&json!(5)), vec![0x05]);
+        assert_eq!(to_msgpack(&json!(-1)), vec![0xff]);
+        assert_eq!(to_msgpack(&json!(-100)), vec![0xd0, 0x9c]);
+        assert_eq!(to_msgpack(&json!(300)), vec![0xcd, 0x01, 0x2c]);
+        assert_eq!(to_msgpack(&json!(true)), vec![0xc3]);
+        assert_eq!(to_msgpack(&json!({"t": 21.5})), vec![0x81, 0xa1, 0x74, 0xca, 0x41, 0xac, 0x00, 0x00]);
+    }
+}
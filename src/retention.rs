@@ -0,0 +1,181 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+use crate::db_pool::{get_combo_pool, get_homebrew_pool};
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::query_cache;
+use crate::utils::time::safe_timestamp_with_fallback;
+
+// Data retention: how many days of each growing table to keep, after which the `retention` job
+// (daily at 04:00 UTC by default) deletes older rows. Each table has its own setting, in days,
+// with 0 or unset keeping everything:
+//
+//   RETAIN_REPORTS_DAYS   raw homebrew reports (weather_reports)
+//   RETAIN_HOURLY_DAYS    hourly rollups (weather_reports_hourly)
+//   RETAIN_DAILY_DAYS     daily rollups (weather_reports_daily)
+//   RETAIN_CACHE_DAYS     cached combo conditions (cached_weather_data)
+//
+// e.g. 30 days of raw reports, a year of hourly rollups and daily ones forever. Each rollup run
+// recomputes its last buckets from the table below it (see `rollup`), so raw reports and hourly
+// rollups have to outlive that window. Pruned reports have their audit chain entries blanked
+// like erased ones, so the chain stays verifiable.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Store {
+    Homebrew,
+    Combo,
+}
+
+/// What a setting prunes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Table {
+    pub setting: &'static str,
+    pub name: &'static str,
+    pub column: &'static str,      // Unix seconds
+    pub store: Store,
+    pub min_days: u32,             // Shortest retention that keeps the rollups correct
+}
+
+pub const TABLES: [Table; 4] = [
+    Table { setting: "RETAIN_REPORTS_DAYS", name: "weather_reports", column: "timestamp", store: Store::Homebrew, min_days: 1 },
+    Table { setting: "RETAIN_HOURLY_DAYS", name: "weather_reports_hourly", column: "bucket", store: Store::Homebrew, min_days: 2 },
+    Table { setting: "RETAIN_DAILY_DAYS", name: "weather_reports_daily", column: "bucket", store: Store::Homebrew, min_days: 1 },
+    Table { setting: "RETAIN_CACHE_DAYS", name: "cached_weather_data", column: "timestamp", store: Store::Combo, min_days: 1 },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    pub table: Table,
+    pub days: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionConfig {
+    pub policies: Vec<Policy>,     // Tables that are pruned
+}
+
+impl RetentionConfig {
+    /// Reads the settings through `var`; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<(&'static str, String)>> {
+        let mut policies = Vec::new();
+        let mut problems = Vec::new();
+        for table in TABLES {
+            let days: u32 = match var(table.setting) {
+                Some(days) if !days.trim().is_empty() => match days.trim().parse() {
+                    Ok(days) => days,
+                    Err(_) => {
+                        problems.push((table.setting, format!("must be a number of days, got {}", days)));
+                        continue;
+                    },
+                },
+                _ => 0,
+            };
+            if days == 0 {
+                continue;
+            }
+            if days < table.min_days {
+                problems.push((table.setting, format!("must be at least {} days or 0 to keep everything, got {}", table.min_days, days)));
+                continue;
+            }
+            policies.push(Policy { table, days });
+        }
+        if problems.is_empty() {
+            Ok(Self { policies })
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.policies.is_empty()
+    }
+}
+
+pub static RETENTION_CONFIG: Lazy<RetentionConfig> = Lazy::new(|| {
+    RetentionConfig::from_env().unwrap_or_else(|problems| {
+        for (setting, message) in problems {
+            log::error!("Invalid {}, data retention disabled: {}", setting, message);
+        }
+        RetentionConfig::default()
+    })
+});
+
+/// Rows deleted from one table by a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pruned {
+    pub table: String,
+    pub cutoff: i64,
+    pub deleted: u64,
+}
+
+/// Deletes every row older than its table's retention; tables whose database isn't
+/// configured are left alone
+pub async fn run(config: &RetentionConfig) -> JupiterResult<Vec<Pruned>> {
+    let mut pruned = Vec::new();
+    for policy in &config.policies {
+        let pool = match policy.table.store {
+            Store::Homebrew => get_homebrew_pool(),
+            Store::Combo => get_combo_pool(),
+        };
+        let Some(pool) = pool else { continue };
+        let mut client = pool.get_connection_with_retry(3).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+        let db_error = |e: tokio_postgres::Error| JupiterError::DatabaseError(format!("Failed to prune {}: {}", policy.table.name, e));
+
+        let cutoff = safe_timestamp_with_fallback() - i64::from(policy.days) * 86_400;
+        let transaction = client.transaction().await.map_err(db_error)?;
+        if policy.table.name == "weather_reports" {
+            let chain_exists: bool = transaction.query_one("SELECT to_regclass('public.report_chain') IS NOT NULL", &[]).await
+                .map_err(db_error)?
+                .get(0);
+            if chain_exists {
+                transaction.execute(
+                    "UPDATE report_chain SET payload = '' WHERE report_oid IN (SELECT oid FROM weather_reports WHERE timestamp < $1)",
+                    &[&cutoff],
+                ).await.map_err(db_error)?;
+            }
+        }
+        let deleted = transaction.execute(&format!("DELETE FROM {} WHERE {} < $1", policy.table.name, policy.table.column), &[&cutoff]).await
+            .map_err(db_error)?;
+        transaction.commit().await.map_err(db_error)?;
+        if policy.table.name == "weather_reports" {
+            query_cache::RANGE_CACHE.clear();
+        }
+
+        log::info!("[retention] Pruned {} row(s) older than {} days from {}", deleted, policy.days, policy.table.name);
+        pruned.push(Pruned { table: policy.table.name.to_string(), cutoff, deleted });
+    }
+    Ok(pruned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Result<RetentionConfig, Vec<(&'static str, String)>> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        RetentionConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_retention_config() {
+        assert!(!config(&[]).unwrap().enabled());
+
+        let retention = config(&[("RETAIN_REPORTS_DAYS", "30"), ("RETAIN_HOURLY_DAYS", "365"), ("RETAIN_DAILY_DAYS", "0")]).unwrap();
+        let days: Vec<(&str, u32)> = retention.policies.iter().map(|p| (p.table.name, p.days)).collect();
+        assert_eq!(days, vec![("weather_reports", 30), ("weather_reports_hourly", 365)]);
+
+        let problems = config(&[("RETAIN_CACHE_DAYS", "a week"), ("RETAIN_HOURLY_DAYS", "1")]).unwrap_err();
+        assert_eq!(problems, vec![
+            ("RETAIN_HOURLY_DAYS", "must be at least 2 days or 0 to keep everything, got 1".to_string()),
+            ("RETAIN_CACHE_DAYS", "must be a number of days, got a week".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+
+use crate::db_pool::get_homebrew_pool;
+use crate::error::{JupiterError, Result as JupiterResult};
+
+// Continuous aggregation of homebrew reports, so daily summaries and long-range reads don't
+// scan raw readings. The "rollup" scheduler job (every 15 minutes by default, see JOBS_CONFIG)
+// rolls `weather_reports` into `weather_reports_hourly`, then the hourly rows into
+// `weather_reports_daily`: per bucket, device type and device id, the min, max, sum and count of
+// every metric. Sums and counts rather than averages, so buckets merge exactly across devices
+// and from hours into days.
+//
+// Each run recomputes the buckets from the newest one it already has, less LATE_SECS, and
+// upserts them, so late reports within that window are picked up and a run can be repeated
+// safely. Reports without a device id are rolled up under device id ''. Erasing a device (see
+// `admin`) deletes its rollups along with its reports.
+
+/// Report columns that are rolled up; wind direction is circular and left out
+pub const METRICS: [&str; 11] = [
+    "temperature", "humidity", "percipitation", "pm10", "pm25", "co2", "tvoc", "wind_speed",
+    "battery_voltage", "battery_percent", "rssi",
+];
+
+/// How far before its newest bucket each run starts recomputing, for reports that arrive late
+pub const LATE_SECS: i64 = 6 * 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rollup {
+    Hourly,
+    Daily,
+}
+
+impl Rollup {
+    pub fn table(&self) -> &'static str {
+        match self {
+            Rollup::Hourly => "weather_reports_hourly",
+            Rollup::Daily => "weather_reports_daily",
+        }
+    }
+
+    pub fn bucket_secs(&self) -> i64 {
+        match self {
+            Rollup::Hourly => 3600,
+            Rollup::Daily => 86_400,
+        }
+    }
+
+    /// Upsert of every bucket from $1 on. Hourly buckets are computed from raw reports and
+    /// daily ones from the hourly buckets.
+    pub fn upsert_query(&self) -> String {
+        let secs = self.bucket_secs();
+        let mut columns = vec!["bucket".to_string(), "device_type".to_string(), "device_id".to_string(), "readings".to_string()];
+        let mut values = Vec::new();
+        let source = match self {
+            Rollup::Hourly => {
+                values.extend([
+                    format!("(timestamp / {secs}) * {secs}", secs = secs),
+                    "COALESCE(device_type, '')".to_string(),
+                    "COALESCE(device_id, '')".to_string(),
+                    "COUNT(*)".to_string(),
+                ]);
+                for metric in METRICS {
+                    values.extend([
+                        format!("MIN({})", metric),
+                        format!("MAX({})", metric),
+                        format!("SUM({})", metric),
+                        format!("COUNT({})", metric),
+                    ]);
+                }
+                "weather_reports WHERE timestamp >= $1"
+            },
+            Rollup::Daily => {
+                values.extend([
+                    format!("(bucket / {secs}) * {secs}", secs = secs),
+                    "device_type".to_string(),
+                    "device_id".to_string(),
+                    "SUM(readings)::BIGINT".to_string(),
+                ]);
+                for metric in METRICS {
+                    values.extend([
+                        format!("MIN({}_min)", metric),
+                        format!("MAX({}_max)", metric),
+                        format!("SUM({}_sum)", metric),
+                        format!("SUM({}_count)::BIGINT", metric),
+                    ]);
+                }
+                "weather_reports_hourly WHERE bucket >= $1"
+            },
+        };
+        for metric in METRICS {
+            columns.extend(["min", "max", "sum", "count"].map(|stat| format!("{}_{}", metric, stat)));
+        }
+        let updates: Vec<String> = columns[3..].iter().map(|column| format!("{column} = EXCLUDED.{column}", column = column)).collect();
+        format!(
+            "INSERT INTO {table} ({columns}) SELECT {values} FROM {source} GROUP BY 1, 2, 3 \
+                ON CONFLICT (bucket, device_type, device_id) DO UPDATE SET {updates}",
+            table = self.table(),
+            columns = columns.join(", "),
+            values = values.join(", "),
+            source = source,
+            updates = updates.join(", "),
+        )
+    }
+}
+
+/// Both rollup tables
+pub fn sql_build_statement() -> String {
+    let metric_columns: String = METRICS.iter()
+        .map(|metric| format!(
+            "{m}_min DOUBLE PRECISION NULL, {m}_max DOUBLE PRECISION NULL, {m}_sum DOUBLE PRECISION NULL, {m}_count BIGINT NOT NULL DEFAULT 0,",
+            m = metric
+        ))
+        .collect::<Vec<_>>()
+        .join("\n        ");
+    [Rollup::Hourly, Rollup::Daily].iter()
+        .map(|rollup| format!(
+            "CREATE TABLE IF NOT EXISTS public.{table} (
+        bucket BIGINT NOT NULL,
+        device_type VARCHAR NOT NULL,
+        device_id VARCHAR NOT NULL,
+        readings BIGINT NOT NULL,
+        {metric_columns}
+        CONSTRAINT {table}_pkey PRIMARY KEY (bucket, device_type, device_id));",
+            table = rollup.table(),
+            metric_columns = metric_columns,
+        ))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Rolls up everything new since the last run; returns how many hourly and daily buckets
+/// were written
+pub async fn run() -> JupiterResult<(u64, u64)> {
+    let pool = get_homebrew_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+
+    let mut written = Vec::new();
+    for rollup in [Rollup::Hourly, Rollup::Daily] {
+        // The daily rollup rereads the day before its newest one, since that may have had late hours
+        let lookback = LATE_SECS.max(rollup.bucket_secs());
+        let newest: Option<i64> = client.query_one(&format!("SELECT MAX(bucket) FROM {}", rollup.table()), &[]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
+            .get(0);
+        let since = newest.map_or(0, |bucket| bucket - lookback);
+        written.push(client.execute(&rollup.upsert_query(), &[&since]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to roll up {}: {}", rollup.table(), e)))?);
+    }
+    log::info!("[rollup] Wrote {} hourly and {} daily buckets", written[0], written[1]);
+    Ok((written[0], written[1]))
+}
+
+/// One day across a set of device types, from the daily rollup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailySummary {
+    pub day: i64,                            // Days since 1970-01-01
+    pub readings: i64,
+    pub temperature_min: Option<f64>,
+    pub temperature_max: Option<f64>,
+    pub temperature_avg: Option<f64>,
+    pub humidity_avg: Option<f64>,
+    pub precipitation_total: Option<f64>,
+}
+
+/// Days in `days` (days since 1970-01-01) with reports from any of `device_types`, oldest first
+pub async fn daily_summaries(device_types: &[String], days: std::ops::Range<i64>) -> JupiterResult<Vec<DailySummary>> {
+    let pool = get_homebrew_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    let rows = client.query(
+        "SELECT bucket / 86400 AS day, SUM(readings)::BIGINT AS readings, \
+            MIN(temperature_min) AS temperature_min, MAX(temperature_max) AS temperature_max, \
+            SUM(temperature_sum) / NULLIF(SUM(temperature_count), 0)::DOUBLE PRECISION AS temperature_avg, \
+            SUM(humidity_sum) / NULLIF(SUM(humidity_count), 0)::DOUBLE PRECISION AS humidity_avg, \
+            SUM(percipitation_sum) AS precipitation_total \
+        FROM weather_reports_daily WHERE device_type = ANY($1) AND bucket >= $2 AND bucket < $3 GROUP BY 1 ORDER BY 1",
+        &[&device_types, &(days.start * 86_400), &(days.end * 86_400)],
+    ).await.map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+    Ok(rows.iter().map(|row| DailySummary {
+        day: row.get("day"),
+        readings: row.get("readings"),
+        temperature_min: row.get("temperature_min"),
+        temperature_max: row.get("temperature_max"),
+        temperature_avg: row.get("temperature_avg"),
+        humidity_avg: row.get("humidity_avg"),
+        precipitation_total: row.get("precipitation_total"),
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upsert_queries() {
+        let hourly = Rollup::Hourly.upsert_query();
+        assert!(hourly.starts_with("INSERT INTO weather_reports_hourly (bucket, device_type, device_id, readings, temperature_min,"));
+        assert!(hourly.contains("SELECT (timestamp / 3600) * 3600, COALESCE(device_type, ''), COALESCE(device_id, ''), COUNT(*), MIN(temperature),"));
+        assert!(hourly.contains("FROM weather_reports WHERE timestamp >= $1 GROUP BY 1, 2, 3"));
+        assert!(hourly.ends_with("rssi_count = EXCLUDED.rssi_count"));
+        assert!(!hourly.contains("bucket = EXCLUDED"), "the key isn't updated");
+
+        let daily = Rollup::Daily.upsert_query();
+        assert!(daily.contains("SELECT (bucket / 86400) * 86400, device_type, device_id, SUM(readings)::BIGINT, MIN(temperature_min), MAX(temperature_max), SUM(temperature_sum), SUM(temperature_count)::BIGINT"));
+        assert!(daily.contains("FROM weather_reports_hourly WHERE bucket >= $1"));
+
+        // Every column gets a value
+        let columns = hourly.split_once(" (").unwrap().1.split_once(')').unwrap().0.split(", ").count();
+        assert_eq!(columns, 4 + METRICS.len() * 4);
+    }
+
+    #[test]
+    fn test_tables() {
+        let statement = sql_build_statement();
+        assert!(statement.contains("CREATE TABLE IF NOT EXISTS public.weather_reports_hourly ("));
+        assert!(statement.contains("CONSTRAINT weather_reports_daily_pkey PRIMARY KEY (bucket, device_type, device_id)"));
+        assert!(statement.contains("co2_count BIGINT NOT NULL DEFAULT 0,"));
+    }
+}
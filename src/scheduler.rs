@@ -0,0 +1,524 @@
+use once_cell::sync::Lazy;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+
+use crate::db_pool::get_combo_pool;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::leader::Leadership;
+use crate::utils::time::{civil_from_days, safe_timestamp_with_fallback};
+
+// Runs background jobs (cache refresh, pool health checks, audit chain verification, ...) on
+// cron schedules. Schedules are read from the JSON file named by JOBS_CONFIG, e.g.
+//
+// {
+//   "jitter_secs": 30,
+//   "jobs": { "cache_refresh": "*/30 * * * *", "chain_verify": "off" }
+// }
+//
+// Expressions use five UTC fields (minute hour day-of-month month day-of-week) or @hourly,
+// @daily, @weekly and @monthly; "off" disables a job and jobs not listed keep their default.
+// Each job runs on its own thread, delayed by a random 0..jitter_secs so jobs sharing a
+// schedule don't hit the database together. A run still in progress when the job is due again
+// is skipped rather than overlapped. GET /api/admin/jobs reports every job's status.
+//
+// Every finished run is recorded in the combo database's `job_runs` table (GET
+// /api/admin/jobs/{name}/runs), and POST /api/admin/jobs/{name}/run starts a run on demand,
+// e.g. to retry a failed job without waiting for its next slot or restarting. Runs are kept
+// for RUN_HISTORY_SECS.
+//
+// With `"leader_election": true`, instances sharing a database run each job's schedule on one
+// of them only (see `leader`); the others report `"leader": false` and skip it.
+
+const RUN_HISTORY_SECS: i64 = 30 * 24 * 3600;
+
+/// A parsed five-field cron expression, evaluated in UTC at minute resolution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Standard cron: if both day fields are restricted, either one matching is enough
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+/// Bitmask of the values matched by one field: "*", "5", "1-5", "*/15", "10-40/10", "1,3,5"
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse().ok()?, end.parse().ok()?)
+        } else {
+            let value: u32 = range.parse().ok()?;
+            // "5/15" means every 15 starting at 5
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> JupiterResult<Self> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let invalid = |reason: &str| JupiterError::ConfigurationError(format!("Invalid cron expression '{}': {}", expression, reason));
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(invalid("expected 5 fields (minute hour day-of-month month day-of-week)"));
+        }
+        let minutes = parse_field(fields[0], 0, 59).ok_or_else(|| invalid("bad minute field"))?;
+        let hours = parse_field(fields[1], 0, 23).ok_or_else(|| invalid("bad hour field"))?;
+        let days = parse_field(fields[2], 1, 31).ok_or_else(|| invalid("bad day-of-month field"))?;
+        let months = parse_field(fields[3], 1, 12).ok_or_else(|| invalid("bad month field"))?;
+        // 0 and 7 are both Sunday
+        let weekdays = parse_field(fields[4], 0, 7).ok_or_else(|| invalid("bad day-of-week field"))?;
+        let weekdays = (weekdays | (weekdays >> 7)) & 0x7f;
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            days_restricted: !fields[2].starts_with('*'),
+            weekdays_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    fn matches_day(&self, days_since_epoch: i64) -> bool {
+        let (_, month, day) = civil_from_days(days_since_epoch);
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        // 1970-01-01 was a Thursday
+        let weekday = (days_since_epoch + 4).rem_euclid(7);
+        let day_match = self.days & (1 << day) != 0;
+        let weekday_match = self.weekdays & (1 << weekday) != 0;
+        if self.days_restricted && self.weekdays_restricted {
+            day_match || weekday_match
+        } else {
+            day_match && weekday_match
+        }
+    }
+
+    /// First matching minute strictly after `timestamp`, within the next five years
+    pub fn next_after(&self, timestamp: i64) -> Option<i64> {
+        let start = (timestamp.div_euclid(60) + 1) * 60;
+        let first_day = start.div_euclid(86_400);
+        for day in first_day..first_day + 5 * 366 {
+            if !self.matches_day(day) {
+                continue;
+            }
+            for hour in (0..24i64).filter(|h| self.hours & (1 << *h) != 0) {
+                for minute in (0..60i64).filter(|m| self.minutes & (1 << *m) != 0) {
+                    let candidate = day * 86_400 + hour * 3600 + minute * 60;
+                    if candidate >= start {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JobsConfig {
+    #[serde(default)]
+    pub jitter_secs: u64,
+    #[serde(default)]
+    pub jobs: HashMap<String, String>,   // Job name -> cron expression or "off"
+    #[serde(default)]
+    pub leader_election: bool,
+}
+
+impl JobsConfig {
+    pub fn from_file(path: &str) -> JupiterResult<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    /// Job names whose expression doesn't parse, with the reason
+    pub fn invalid_schedules(&self) -> Vec<(String, String)> {
+        let mut invalid: Vec<(String, String)> = self.jobs.iter()
+            .filter(|(_, expression)| expression.as_str() != "off")
+            .filter_map(|(name, expression)| match CronSchedule::parse(expression) {
+                Err(JupiterError::ConfigurationError(message)) => Some((name.clone(), message)),
+                Err(e) => Some((name.clone(), e.to_string())),
+                Ok(_) => None,
+            })
+            .collect();
+        invalid.sort();
+        invalid
+    }
+
+    /// Loads the file named by JOBS_CONFIG; without it every job keeps its default schedule
+    pub fn from_env() -> JupiterResult<Self> {
+        match std::env::var("JOBS_CONFIG") {
+            Ok(path) if !path.is_empty() => Self::from_file(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub name: String,
+    pub schedule: String,
+    pub running: bool,
+    pub next_run: Option<i64>,
+    pub last_started: Option<i64>,
+    pub last_duration_ms: Option<u64>,
+    pub last_error: Option<String>,    // None if the last run succeeded
+    pub runs: u64,
+    pub failures: u64,
+    pub skipped: u64,                  // Due while the previous run was still in progress
+    pub leader: bool,                  // Scheduled runs happen on this instance
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobTrigger {
+    Schedule,
+    Manual,    // POST /api/admin/jobs/{name}/run
+}
+
+impl JobTrigger {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobTrigger::Schedule => "schedule",
+            JobTrigger::Manual => "manual",
+        }
+    }
+}
+
+/// One finished run, as stored in `job_runs`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRun {
+    pub id: i32,
+    pub job: String,
+    pub trigger: JobTrigger,
+    pub started: i64,
+    pub duration_ms: i64,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+pub fn sql_build_statement() -> &'static str {
+    "CREATE TABLE IF NOT EXISTS public.job_runs (
+        id serial NOT NULL,
+        job varchar NOT NULL,
+        trigger varchar NOT NULL,
+        started BIGINT DEFAULT 0,
+        duration_ms BIGINT DEFAULT 0,
+        success BOOL DEFAULT false,
+        error TEXT NULL,
+        CONSTRAINT job_runs_pkey PRIMARY KEY (id));
+    CREATE INDEX IF NOT EXISTS job_runs_job_started_idx ON job_runs (job, started DESC);"
+}
+
+async fn record_run(run: &JobRun) -> JupiterResult<()> {
+    let pool = get_combo_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    client.execute("INSERT INTO job_runs (job, trigger, started, duration_ms, success, error) VALUES ($1, $2, $3, $4, $5, $6)",
+        &[&run.job, &run.trigger.as_str(), &run.started, &run.duration_ms, &run.success, &run.error]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Insert failed: {}", e)))?;
+    // pool_health alone adds a row a minute
+    client.execute("DELETE FROM job_runs WHERE started < $1", &[&(run.started - RUN_HISTORY_SECS)]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Delete failed: {}", e)))?;
+    Ok(())
+}
+
+/// The newest `limit` runs of a job
+pub async fn recent_runs(job: &str, limit: i64) -> JupiterResult<Vec<JobRun>> {
+    let pool = get_combo_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    let rows = client.query("SELECT * FROM job_runs WHERE job = $1 ORDER BY started DESC, id DESC LIMIT $2", &[&job, &limit]).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+    Ok(rows.iter().map(|row| {
+        let trigger: String = row.get("trigger");
+        JobRun {
+            id: row.get("id"),
+            job: row.get("job"),
+            trigger: if trigger == "manual" { JobTrigger::Manual } else { JobTrigger::Schedule },
+            started: row.get("started"),
+            duration_ms: row.get("duration_ms"),
+            success: row.get("success"),
+            error: row.get("error"),
+        }
+    }).collect())
+}
+
+type JobFn = Box<dyn Fn() -> JupiterResult<()> + Send + Sync>;
+
+pub struct Job {
+    name: String,
+    schedule: CronSchedule,
+    run: JobFn,
+    running: AtomicBool,
+    status: Mutex<JobStatus>,
+    runtime: Handle,
+}
+
+impl Job {
+    fn update(&self, f: impl FnOnce(&mut JobStatus)) {
+        match self.status.lock() {
+            Ok(mut status) => f(&mut status),
+            Err(e) => log::error!("Job status lock poisoned: {}", e),
+        }
+    }
+
+    pub fn status(&self) -> Option<JobStatus> {
+        self.status.lock().ok().map(|status| status.clone())
+    }
+
+    /// Marks the job as running; false if a run is already in progress
+    fn claim(&self) -> bool {
+        self.running.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_ok()
+    }
+
+    /// Runs a claimed job, then records the outcome in its status and in `job_runs`
+    fn run_claimed(&self, trigger: JobTrigger) {
+        let started_at = safe_timestamp_with_fallback();
+        self.update(|status| {
+            status.running = true;
+            status.last_started = Some(started_at);
+        });
+
+        let started = Instant::now();
+        let result = (self.run)();
+        let duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(()) => log::info!("[scheduler] Job {} ({}) finished in {} ms", self.name, trigger.as_str(), duration_ms),
+            Err(e) => log::error!("[scheduler] Job {} ({}) failed after {} ms: {}", self.name, trigger.as_str(), duration_ms, e),
+        }
+        let error = result.err().map(|e| e.to_string());
+
+        self.update(|status| {
+            status.running = false;
+            status.last_duration_ms = Some(duration_ms);
+            status.last_error = error.clone();
+            status.runs += 1;
+            if error.is_some() {
+                status.failures += 1;
+            }
+        });
+        self.running.store(false, Ordering::Release);
+
+        let run = JobRun {
+            id: 0,
+            job: self.name.clone(),
+            trigger,
+            started: started_at,
+            duration_ms: duration_ms as i64,
+            success: error.is_none(),
+            error,
+        };
+        if let Err(e) = self.runtime.block_on(record_run(&run)) {
+            log::warn!("[scheduler] Failed to record run of job {}: {}", self.name, e);
+        }
+    }
+
+    /// Runs the job unless a run is already in progress; false if it was skipped
+    pub fn try_run(&self, trigger: JobTrigger) -> bool {
+        if !self.claim() {
+            self.update(|status| status.skipped += 1);
+            return false;
+        }
+        self.run_claimed(trigger);
+        true
+    }
+}
+
+// Jobs of the running scheduler, for the status and trigger endpoints
+static JOBS: Lazy<RwLock<Vec<Arc<Job>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+static LEADERSHIP: Lazy<RwLock<Leadership>> = Lazy::new(|| RwLock::new(Leadership::everything()));
+
+/// Status of every scheduled job
+pub fn statuses() -> Vec<JobStatus> {
+    let leadership = LEADERSHIP.read().map(|leadership| leadership.clone()).unwrap_or_default();
+    match JOBS.read() {
+        Ok(jobs) => jobs.iter()
+            .filter_map(|job| job.status())
+            .map(|status| JobStatus { leader: leadership.leads(&status.name), ..status })
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Starts a run of `name` in the background. None if there is no such job, Some(false) if it
+/// is already running.
+pub fn trigger(name: &str) -> Option<bool> {
+    let job = JOBS.read().ok()?.iter().find(|job| job.name == name).cloned()?;
+    if !job.claim() {
+        return Some(false);
+    }
+    thread::spawn(move || job.run_claimed(JobTrigger::Manual));
+    Some(true)
+}
+
+pub struct Scheduler {
+    config: JobsConfig,
+    jobs: Vec<Arc<Job>>,
+    runtime: Handle,
+}
+
+impl Scheduler {
+    /// Job threads block on `runtime` to record their runs
+    pub fn new(config: JobsConfig, runtime: Handle) -> Self {
+        Self { config, jobs: Vec::new(), runtime }
+    }
+
+    /// The process runtime, for jobs to block on rather than creating their own
+    pub fn runtime(&self) -> Handle {
+        self.runtime.clone()
+    }
+
+    /// Adds a job running on its configured schedule, or `default_schedule` if none is configured
+    pub fn register(&mut self, name: &str, default_schedule: &str, run: impl Fn() -> JupiterResult<()> + Send + Sync + 'static) -> JupiterResult<()> {
+        let expression = self.config.jobs.get(name).map(String::as_str).unwrap_or(default_schedule);
+        if expression == "off" {
+            log::info!("[scheduler] Job {} is disabled", name);
+            return Ok(());
+        }
+        let schedule = CronSchedule::parse(expression)?;
+        self.jobs.push(Arc::new(Job {
+            name: name.to_string(),
+            status: Mutex::new(JobStatus {
+                name: name.to_string(),
+                schedule: schedule.expression().to_string(),
+                running: false,
+                next_run: None,
+                last_started: None,
+                last_duration_ms: None,
+                last_error: None,
+                runs: 0,
+                failures: 0,
+                skipped: 0,
+                leader: true,
+            }),
+            schedule,
+            run: Box::new(run),
+            running: AtomicBool::new(false),
+            runtime: self.runtime.clone(),
+        }));
+        Ok(())
+    }
+
+    /// Starts one thread per job; they exit once `shutdown_flag` is set. Leader election, if
+    /// configured, runs on the current tokio runtime.
+    pub fn start(self, shutdown_flag: Arc<AtomicBool>) -> Vec<JoinHandle<()>> {
+        for name in self.config.jobs.keys() {
+            if !self.jobs.iter().any(|job| &job.name == name) {
+                log::warn!("[scheduler] JOBS_CONFIG schedules unknown or unavailable job {}", name);
+            }
+        }
+        if let Ok(mut jobs) = JOBS.write() {
+            *jobs = self.jobs.clone();
+        }
+        let leadership = if self.config.leader_election {
+            Leadership::elect(self.jobs.iter().map(|job| job.name.clone()).collect(), shutdown_flag.clone())
+        } else {
+            Leadership::everything()
+        };
+        if let Ok(mut current) = LEADERSHIP.write() {
+            *current = leadership.clone();
+        }
+
+        let jitter_secs = self.config.jitter_secs;
+        self.jobs.into_iter().map(|job| {
+            let shutdown_flag = shutdown_flag.clone();
+            let leadership = leadership.clone();
+            thread::spawn(move || {
+                while !shutdown_flag.load(Ordering::Relaxed) {
+                    let next_run = match job.schedule.next_after(safe_timestamp_with_fallback()) {
+                        Some(next_run) => next_run,
+                        None => break,
+                    };
+                    let jitter = if jitter_secs > 0 { thread_rng().gen_range(0..=jitter_secs) as i64 } else { 0 };
+                    job.update(|status| status.next_run = Some(next_run));
+
+                    // Sleep in short steps so shutdown isn't delayed by long intervals
+                    while safe_timestamp_with_fallback() < next_run + jitter && !shutdown_flag.load(Ordering::Relaxed) {
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    if shutdown_flag.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if leadership.leads(&job.name) {
+                        job.try_run(JobTrigger::Schedule);
+                    } else {
+                        log::debug!("[scheduler] Job {} is led by another instance, skipping", job.name);
+                    }
+                }
+            })
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cron_parse() {
+        assert!(CronSchedule::parse("*/15 * * * *").is_ok());
+        assert!(CronSchedule::parse("0 3 * * 1-5").is_ok());
+        assert!(CronSchedule::parse("@daily").is_ok());
+        assert!(CronSchedule::parse("0 24 * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("0 * * *").is_err());
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        // 2024-07-01 14:07:30 UTC, a Monday
+        let t = 1_719_842_850;
+        let at = |h: i64, m: i64| 1_719_792_000 + h * 3600 + m * 60;
+
+        assert_eq!(CronSchedule::parse("*/15 * * * *").unwrap().next_after(t), Some(at(14, 15)));
+        assert_eq!(CronSchedule::parse("@hourly").unwrap().next_after(t), Some(at(15, 0)));
+        assert_eq!(CronSchedule::parse("0 3 * * *").unwrap().next_after(t), Some(at(24 + 3, 0)));
+        // Next Sunday (7 is Sunday too) is 2024-07-07
+        assert_eq!(CronSchedule::parse("30 2 * * 7").unwrap().next_after(t), Some(at(6 * 24 + 2, 30)));
+        assert_eq!(CronSchedule::parse("0 0 1 * *").unwrap().next_after(t), Some(1_722_470_400));
+        // Both day fields restricted: the 3rd (a Wednesday) or any Sunday
+        assert_eq!(CronSchedule::parse("0 12 3 * 0").unwrap().next_after(t), Some(at(2 * 24 + 12, 0)));
+    }
+}
@@ -0,0 +1,285 @@
+use axum::http::HeaderValue;
+use axum::response::Response;
+use serde_json::Value;
+
+// Versions of the JSON bodies served by the homebrew and combo servers. Successful responses
+// carry `X-Schema-Version: <name>/<version>` so clients can detect incompatible changes.
+// Renaming or removing a field, or changing its type or unit, needs a version bump and a new golden
+// file in tests/contracts (included by `Schema::contract`); adding a field is compatible but still shows up in the contract
+// tests, so the golden file is updated in the same change.
+//
+// Version 2 of current_weather, public_current and public_daily reports `wind_speed` in km/h
+// for every provider; version 1 passed OpenWeather's m/s through unconverted.
+
+pub const HEADER: &str = "X-Schema-Version";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schema {
+    CachedConditions,    // combo GET (cached AccuWeather and homebrew conditions)
+    WeatherReport,       // GET /api/weather_reports, both servers
+    PublicCurrent,       // GET /public/current
+    PublicDaily,         // GET /public/daily
+    Pollen,              // GET /api/pollen
+    UvGuidance,          // GET /api/uv/guidance
+    HvacHints,           // GET /api/hvac/hints
+    SolarForecast,       // GET /api/solar/forecast
+    Marine,              // GET /api/marine
+    Aviation,            // GET /api/aviation
+    Ventilation,         // GET /api/ventilation
+    DeviceStatus,        // GET /api/devices/status
+    DeviceConfig,        // GET /api/devices/{id}/config
+    AuditVerification,   // GET /api/audit/verify
+    Rooms,               // GET /api/rooms
+    Nowcast,             // GET /api/nowcast
+    AirQuality,          // GET /api/air_quality
+    CurrentWeather,      // GET /api/current (combined conditions with their sources)
+    Devices,             // GET /api/devices
+    Device,              // GET/PUT /api/devices/{id}
+    DeviceKey,           // POST /api/devices, POST /api/devices/{id}/key
+    History,             // GET /api/history/{field}, both servers
+    ReportRange,         // GET /api/weather_reports/history, both servers
+    ReportPage,          // GET /api/weather_reports/list, both servers
+    Capabilities,        // GET /api/capabilities
+    Groups,              // GET /api/groups
+    Group,               // GET/PUT /api/groups/{id}, POST /api/groups
+    GroupClimate,        // GET /api/groups/{id}/climate
+    TemperatureTrigger,  // GET /api/triggers/temperature_crossed, as IFTTT gets it (Zapier: the items alone)
+    AlertTrigger,        // GET /api/triggers/new_alert, likewise
+    AlertRules,          // GET /api/alert_rules
+    AlertRule,           // GET/PUT /api/alert_rules/{id}, POST /api/alert_rules
+    StorageUsage,        // GET /api/admin/storage
+    ApiKeys,             // GET /api/admin/keys
+    IssuedKey,           // POST /api/admin/keys
+}
+
+impl Schema {
+    pub const ALL: [Schema; 35] = [
+        Schema::CachedConditions,
+        Schema::WeatherReport,
+        Schema::PublicCurrent,
+        Schema::PublicDaily,
+        Schema::Pollen,
+        Schema::UvGuidance,
+        Schema::HvacHints,
+        Schema::SolarForecast,
+        Schema::Marine,
+        Schema::Aviation,
+        Schema::Ventilation,
+        Schema::DeviceStatus,
+        Schema::DeviceConfig,
+        Schema::AuditVerification,
+        Schema::Rooms,
+        Schema::Nowcast,
+        Schema::AirQuality,
+        Schema::CurrentWeather,
+        Schema::Devices,
+        Schema::Device,
+        Schema::DeviceKey,
+        Schema::History,
+        Schema::ReportRange,
+        Schema::ReportPage,
+        Schema::Capabilities,
+        Schema::Groups,
+        Schema::Group,
+        Schema::GroupClimate,
+        Schema::TemperatureTrigger,
+        Schema::AlertTrigger,
+        Schema::AlertRules,
+        Schema::AlertRule,
+        Schema::StorageUsage,
+        Schema::ApiKeys,
+        Schema::IssuedKey,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Schema::CachedConditions => "cached_conditions",
+            Schema::WeatherReport => "weather_report",
+            Schema::PublicCurrent => "public_current",
+            Schema::PublicDaily => "public_daily",
+            Schema::Pollen => "pollen",
+            Schema::UvGuidance => "uv_guidance",
+            Schema::HvacHints => "hvac_hints",
+            Schema::SolarForecast => "solar_forecast",
+            Schema::Marine => "marine",
+            Schema::Aviation => "aviation",
+            Schema::Ventilation => "ventilation",
+            Schema::DeviceStatus => "device_status",
+            Schema::DeviceConfig => "device_config",
+            Schema::AuditVerification => "audit_verification",
+            Schema::Rooms => "rooms",
+            Schema::Nowcast => "nowcast",
+            Schema::AirQuality => "air_quality",
+            Schema::CurrentWeather => "current_weather",
+            Schema::Devices => "devices",
+            Schema::Device => "device",
+            Schema::DeviceKey => "device_key",
+            Schema::History => "history",
+            Schema::ReportRange => "report_range",
+            Schema::ReportPage => "report_page",
+            Schema::Capabilities => "capabilities",
+            Schema::Groups => "groups",
+            Schema::Group => "group",
+            Schema::GroupClimate => "group_climate",
+            Schema::TemperatureTrigger => "temperature_trigger",
+            Schema::AlertTrigger => "alert_trigger",
+            Schema::AlertRules => "alert_rules",
+            Schema::AlertRule => "alert_rule",
+            Schema::StorageUsage => "storage_usage",
+            Schema::ApiKeys => "api_keys",
+            Schema::IssuedKey => "issued_key",
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            Schema::CachedConditions => 1,
+            Schema::WeatherReport => 1,
+            Schema::PublicCurrent => 2,
+            Schema::PublicDaily => 2,
+            Schema::Pollen => 1,
+            Schema::UvGuidance => 1,
+            Schema::HvacHints => 1,
+            Schema::SolarForecast => 1,
+            Schema::Marine => 1,
+            Schema::Aviation => 1,
+            Schema::Ventilation => 1,
+            Schema::DeviceStatus => 1,
+            Schema::DeviceConfig => 1,
+            Schema::AuditVerification => 1,
+            Schema::Rooms => 1,
+            Schema::Nowcast => 1,
+            Schema::AirQuality => 1,
+            Schema::CurrentWeather => 2,
+            Schema::Devices => 1,
+            Schema::Device => 1,
+            Schema::DeviceKey => 1,
+            Schema::History => 1,
+            Schema::ReportRange => 1,
+            Schema::ReportPage => 1,
+            Schema::Capabilities => 1,
+            Schema::Groups => 1,
+            Schema::Group => 1,
+            Schema::GroupClimate => 1,
+            Schema::TemperatureTrigger => 1,
+            Schema::AlertTrigger => 1,
+            Schema::AlertRules => 1,
+            Schema::AlertRule => 1,
+            Schema::StorageUsage => 1,
+            Schema::ApiKeys => 1,
+            Schema::IssuedKey => 1,
+        }
+    }
+
+    /// The golden file of the current version, i.e. `shape` of a body of this schema; what
+    /// `openapi` describes responses with
+    pub fn contract(&self) -> &'static str {
+        match self {
+            Schema::CachedConditions => include_str!("../tests/contracts/cached_conditions.v1.txt"),
+            Schema::WeatherReport => include_str!("../tests/contracts/weather_report.v1.txt"),
+            Schema::PublicCurrent => include_str!("../tests/contracts/public_current.v2.txt"),
+            Schema::PublicDaily => include_str!("../tests/contracts/public_daily.v2.txt"),
+            Schema::Pollen => include_str!("../tests/contracts/pollen.v1.txt"),
+            Schema::UvGuidance => include_str!("../tests/contracts/uv_guidance.v1.txt"),
+            Schema::HvacHints => include_str!("../tests/contracts/hvac_hints.v1.txt"),
+            Schema::SolarForecast => include_str!("../tests/contracts/solar_forecast.v1.txt"),
+            Schema::Marine => include_str!("../tests/contracts/marine.v1.txt"),
+            Schema::Aviation => include_str!("../tests/contracts/aviation.v1.txt"),
+            Schema::Ventilation => include_str!("../tests/contracts/ventilation.v1.txt"),
+            Schema::DeviceStatus => include_str!("../tests/contracts/device_status.v1.txt"),
+            Schema::DeviceConfig => include_str!("../tests/contracts/device_config.v1.txt"),
+            Schema::AuditVerification => include_str!("../tests/contracts/audit_verification.v1.txt"),
+            Schema::Rooms => include_str!("../tests/contracts/rooms.v1.txt"),
+            Schema::Nowcast => include_str!("../tests/contracts/nowcast.v1.txt"),
+            Schema::AirQuality => include_str!("../tests/contracts/air_quality.v1.txt"),
+            Schema::CurrentWeather => include_str!("../tests/contracts/current_weather.v2.txt"),
+            Schema::Devices => include_str!("../tests/contracts/devices.v1.txt"),
+            Schema::Device => include_str!("../tests/contracts/device.v1.txt"),
+            Schema::DeviceKey => include_str!("../tests/contracts/device_key.v1.txt"),
+            Schema::History => include_str!("../tests/contracts/history.v1.txt"),
+            Schema::ReportRange => include_str!("../tests/contracts/report_range.v1.txt"),
+            Schema::ReportPage => include_str!("../tests/contracts/report_page.v1.txt"),
+            Schema::Capabilities => include_str!("../tests/contracts/capabilities.v1.txt"),
+            Schema::Groups => include_str!("../tests/contracts/groups.v1.txt"),
+            Schema::Group => include_str!("../tests/contracts/group.v1.txt"),
+            Schema::GroupClimate => include_str!("../tests/contracts/group_climate.v1.txt"),
+            Schema::TemperatureTrigger => include_str!("../tests/contracts/temperature_trigger.v1.txt"),
+            Schema::AlertTrigger => include_str!("../tests/contracts/alert_trigger.v1.txt"),
+            Schema::AlertRules => include_str!("../tests/contracts/alert_rules.v1.txt"),
+            Schema::AlertRule => include_str!("../tests/contracts/alert_rule.v1.txt"),
+            Schema::StorageUsage => include_str!("../tests/contracts/storage_usage.v1.txt"),
+            Schema::ApiKeys => include_str!("../tests/contracts/api_keys.v1.txt"),
+            Schema::IssuedKey => include_str!("../tests/contracts/issued_key.v1.txt"),
+        }
+    }
+
+    /// e.g. "weather_report/1"
+    pub fn header_value(&self) -> String {
+        format!("{}/{}", self.name(), self.version())
+    }
+}
+
+/// Tags a successful response with its schema version; errors are plain text and left alone
+pub fn with_schema(mut response: Response, schema: Schema) -> Response {
+    if response.status().is_success() {
+        if let Ok(value) = HeaderValue::from_str(&schema.header_value()) {
+            response.headers_mut().insert(HEADER, value);
+        }
+    }
+    response
+}
+
+/// Field paths and JSON types of a response body, one "path: type" line each, sorted.
+/// Array elements appear under "path[]" (described by the first element); numbers are
+/// "integer" or "float" so an i64 turning into an f64 counts as a change.
+pub fn shape(value: &Value) -> Vec<String> {
+    fn walk(path: &str, value: &Value, lines: &mut Vec<String>) {
+        let kind = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(n) if n.is_f64() => "float",
+            Value::Number(_) => "integer",
+            Value::String(_) => "string",
+            Value::Array(items) => {
+                match items.first() {
+                    Some(first) => walk(&format!("{}[]", path), first, lines),
+                    None => lines.push(format!("{}: empty array", path)),
+                }
+                return;
+            },
+            Value::Object(fields) if fields.is_empty() => "empty object",
+            Value::Object(fields) => {
+                for (key, field) in fields {
+                    let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                    walk(&child, field, lines);
+                }
+                return;
+            },
+        };
+        lines.push(format!("{}: {}", path, kind));
+    }
+
+    let mut lines = Vec::new();
+    walk("", value, &mut lines);
+    lines.sort();
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_shape() {
+        let value = json!({"temperature": 21.5, "timestamp": 1700000000, "tags": [{"name": "a"}], "device_id": null, "empty": []});
+        assert_eq!(shape(&value), vec![
+            "device_id: null",
+            "empty: empty array",
+            "tags[].name: string",
+            "temperature: float",
+            "timestamp: integer",
+        ]);
+        assert_eq!(Schema::WeatherReport.header_value(), "weather_report/1");
+    }
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+// Wrapper for credentials (API keys, database passwords, SNMP communities) so they can't leak
+// through logs, Debug output or serialized configs: Display, Debug and Serialize all print
+// `[REDACTED]`. Deserialize reads the real value so secrets can still come from config files.
+// Call `expose()` only where the value is actually sent somewhere.
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+
+    /// The wrapped value; keep it out of log and error messages
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl Secret<String> {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for Secret<String> {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Secret(value.to_string())
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Credentials {
+        user: String,
+        password: Secret<String>,
+    }
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let credentials: Credentials = serde_json::from_str(r#"{"user":"jupiter","password":"hunter2"}"#).unwrap();
+        assert_eq!(credentials.password.expose(), "hunter2");
+        assert_eq!(format!("{}", credentials.password), "[REDACTED]");
+        assert!(!format!("{:?}", credentials).contains("hunter2"));
+        assert_eq!(serde_json::to_string(&credentials).unwrap(), r#"{"user":"jupiter","password":"[REDACTED]"}"#);
+    }
+}
@@ -0,0 +1,162 @@
+use axum::extract::Request;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{middleware, Router};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinHandle;
+use tower::limit::GlobalConcurrencyLimitLayer;
+
+use crate::config::ResourceLimits;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::metrics::Kind;
+use crate::{logging, metrics};
+
+// HTTP serving shared by the homebrew and combo servers. Both are axum routers running as tasks
+// on the process runtime, so handlers await the database pools and providers directly.
+//
+// Shutdown drains a server: it stops accepting connections, lets the requests in flight finish
+// for up to SHUTDOWN_TIMEOUT_SECS and then cuts off the rest, logging how many of each there were.
+// Cutting off drops the handlers of the remaining requests, which are answered with a 503, so
+// their work stops rather than carrying on in axum's connection tasks.
+
+/// How long cut-off connections get to send their 503 and close before the server is aborted
+const CUT_OFF_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct RequestCounts {
+    in_flight: usize,
+    completed: u64,
+}
+
+static REQUESTS: Lazy<Mutex<HashMap<&'static str, RequestCounts>>> = Lazy::new(|| {
+    metrics::collector(Kind::Gauge, "jupiter_http_requests_in_flight", "HTTP requests being handled by server", &["server"], || {
+        REQUESTS.lock().map(|servers| servers.iter()
+            .map(|(server, counts)| (vec![server.to_string()], counts.in_flight as f64))
+            .collect())
+            .unwrap_or_default()
+    });
+    Mutex::new(HashMap::new())
+});
+
+fn request_counts(server: &'static str) -> RequestCounts {
+    REQUESTS.lock().ok().and_then(|servers| servers.get(server).copied()).unwrap_or_default()
+}
+
+/// Counts a request as in flight until it's answered or dropped, when its server is cut off
+struct InFlight(&'static str);
+
+impl InFlight {
+    fn start(server: &'static str) -> Self {
+        if let Ok(mut servers) = REQUESTS.lock() {
+            servers.entry(server).or_default().in_flight += 1;
+        }
+        Self(server)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Ok(mut servers) = REQUESTS.lock() {
+            let counts = servers.entry(self.0).or_default();
+            counts.in_flight = counts.in_flight.saturating_sub(1);
+            counts.completed += 1;
+        }
+    }
+}
+
+async fn track_in_flight(server: &'static str, mut cut_off: watch::Receiver<bool>, request: Request, next: Next) -> Response {
+    let _in_flight = InFlight::start(server);
+    tokio::select! {
+        response = next.run(request) => response,
+        Ok(_) = cut_off.wait_for(|cut_off| *cut_off) => (StatusCode::SERVICE_UNAVAILABLE, "Server shutting down").into_response(),
+    }
+}
+
+/// A running server, see `spawn` and `join`
+pub struct ServerTask {
+    handle: JoinHandle<()>,
+    cut_off: watch::Sender<bool>,
+}
+
+/// Binds `port` and serves `router` on a new task until a message arrives on `shutdown`, then
+/// drains it, see `join`. HTTP_WORKERS caps how many requests are handled at once, every
+/// response is counted in the `jupiter_http_*` metrics under `name`, and every request runs in
+/// a logging span with its request ID.
+pub async fn spawn(name: &'static str, port: u16, router: Router, limits: &ResourceLimits, mut shutdown: broadcast::Receiver<()>) -> JupiterResult<ServerTask> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await
+        .map_err(|e| JupiterError::ServerError(format!("Failed to bind port {}: {}", port, e)))?;
+    let router = router.layer(middleware::from_fn(move |request, next| metrics::track_http(name, request, next)))
+        .layer(middleware::from_fn(move |request, next| logging::trace_http(name, request, next)));
+    let router = match limits.http_workers {
+        Some(workers) => router.layer(GlobalConcurrencyLimitLayer::new(workers)),
+        None => router,
+    };
+    // Outermost, so requests waiting for a worker count as in flight too
+    let (cut_off, cut_off_rx) = watch::channel(false);
+    let router = router.layer(middleware::from_fn(move |request, next| track_in_flight(name, cut_off_rx.clone(), request, next)));
+    log::info!("{} server started on port {}", name, port);
+
+    let handle = tokio::spawn(async move {
+        let served = axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move {
+                let _ = shutdown.recv().await;
+                log::info!("{} server no longer accepting connections", name);
+            })
+            .await;
+        if let Err(e) = served {
+            log::error!("{} server failed: {}", name, e);
+        }
+    });
+    Ok(ServerTask { handle, cut_off })
+}
+
+/// Waits up to `timeout` for a server to finish its in-flight requests, then cuts off the rest.
+/// Call after signalling shutdown, so the server no longer accepts connections.
+pub async fn join(name: &'static str, task: ServerTask, timeout: Duration) {
+    let ServerTask { mut handle, cut_off } = task;
+    let start = Instant::now();
+    let before = request_counts(name);
+    log::info!("{} server draining {} in-flight requests (timeout {:?})", name, before.in_flight, timeout);
+    if tokio::time::timeout(timeout, &mut handle).await.is_ok() {
+        let (drained, _) = drain_stats(before, request_counts(name));
+        log::info!("{} server stopped after {:?}: {} requests drained", name, start.elapsed(), drained);
+        return;
+    }
+    let (drained, cut_off_requests) = drain_stats(before, request_counts(name));
+    log::warn!("{} server shutdown timed out after {:?}: {} requests drained, {} cut off", name, timeout, drained, cut_off_requests);
+    let _ = cut_off.send(true);
+    // Connections close once their cut-off requests are answered; ones still open after that are dropped
+    if tokio::time::timeout(CUT_OFF_GRACE, &mut handle).await.is_err() {
+        handle.abort();
+    }
+}
+
+/// Requests that finished between two counts and those still in flight at the second
+fn drain_stats(before: RequestCounts, after: RequestCounts) -> (u64, usize) {
+    (after.completed.saturating_sub(before.completed), after.in_flight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_flight_counts() {
+        let before = request_counts("Test");
+        let first = InFlight::start("Test");
+        let second = InFlight::start("Test");
+        assert_eq!(request_counts("Test").in_flight, before.in_flight + 2);
+        drop(first);
+        let (drained, cut_off) = drain_stats(before, request_counts("Test"));
+        assert_eq!((drained, cut_off), (1, before.in_flight + 1));
+        drop(second);
+        assert_eq!(request_counts("Test"), RequestCounts { in_flight: before.in_flight, completed: before.completed + 2 });
+    }
+}
@@ -0,0 +1,130 @@
+use once_cell::sync::OnceCell;
+use std::error::Error as StdError;
+use std::ffi::OsString;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use windows_service::service::{
+    ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+// Windows service integration, the counterpart of the Unix signal handling in main.rs.
+// `jupiter install` registers the service (auto start, launched as `jupiter service`) and its
+// event log source; under the service control manager Stop and Shutdown trigger the same
+// graceful shutdown as SIGTERM, and logs go to the Windows event log.
+
+pub const SERVICE_NAME: &str = "jupiter";
+const DISPLAY_NAME: &str = "Jupiter Weather Server";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+/// Runs the servers until the receiver fires
+pub type ServiceMain = fn(oneshot::Receiver<()>) -> Result<(), Box<dyn StdError>>;
+
+static SERVICE_MAIN: OnceCell<ServiceMain> = OnceCell::new();
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// Hands the process to the service control manager; blocks until the service stops
+pub fn run(main: ServiceMain) -> Result<(), Box<dyn StdError>> {
+    let _ = SERVICE_MAIN.set(main);
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)?;
+    Ok(())
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = eventlog::init(DISPLAY_NAME, log::Level::Info) {
+        eprintln!("Failed to initialize event log: {}", e);
+    }
+
+    // Services start in System32; read .env and relative paths next to the executable instead
+    if let Some(dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(|p| p.to_path_buf())) {
+        if let Err(e) = std::env::set_current_dir(&dir) {
+            log::warn!("Failed to change to {}: {}", dir.display(), e);
+        }
+    }
+
+    if let Err(e) = run_service() {
+        log::error!("Service failed: {}", e);
+    }
+}
+
+fn run_service() -> Result<(), Box<dyn StdError>> {
+    let (stop_tx, stop_rx) = oneshot::channel();
+    let stop_tx = Mutex::new(Some(stop_tx));
+
+    let status_handle = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            log::info!("Received service {:?} request", control);
+            if let Some(tx) = stop_tx.lock().ok().and_then(|mut tx| tx.take()) {
+                let _ = tx.send(());
+            }
+            ServiceControlHandlerResult::NoError
+        },
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+
+    status_handle.set_service_status(status(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        ServiceExitCode::Win32(0),
+    ))?;
+
+    let result = match SERVICE_MAIN.get() {
+        Some(main) => main(stop_rx),
+        None => Err("Service entry point not set".into()),
+    };
+    let exit_code = match result {
+        Ok(()) => ServiceExitCode::Win32(0),
+        Err(_) => ServiceExitCode::ServiceSpecific(1),
+    };
+
+    status_handle.set_service_status(status(ServiceState::Stopped, ServiceControlAccept::empty(), exit_code))?;
+    result
+}
+
+fn status(state: ServiceState, controls_accepted: ServiceControlAccept, exit_code: ServiceExitCode) -> ServiceStatus {
+    ServiceStatus {
+        service_type: SERVICE_TYPE,
+        current_state: state,
+        controls_accepted,
+        exit_code,
+        checkpoint: 0,
+        wait_hint: Duration::default(),
+        process_id: None,
+    }
+}
+
+/// Registers the service and its event log source; needs an elevated prompt
+pub fn install() -> Result<(), Box<dyn StdError>> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)?;
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(DISPLAY_NAME),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments: vec![OsString::from("service")],
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)?;
+    service.set_description("Homebrew and combo weather APIs")?;
+    eventlog::register(DISPLAY_NAME)?;
+    Ok(())
+}
+
+/// Removes the service and its event log source
+pub fn uninstall() -> Result<(), Box<dyn StdError>> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::DELETE)?;
+    service.delete()?;
+    eventlog::deregister(DISPLAY_NAME)?;
+    Ok(())
+}
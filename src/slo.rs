@@ -0,0 +1,275 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::metrics::{self, Kind};
+use crate::provider::health::CircuitStatus;
+use crate::provider::quota::BudgetStatus;
+
+// Latency and error service level objectives per weather provider. Every provider call is kept
+// for SLO_WINDOW_SECS (15 minutes by default); over that window each provider's 95th percentile
+// latency and error rate are compared with its target from PROVIDER_SLO, `provider=p95_ms/error_percent`
+// pairs separated by commas with `*` for every other provider, e.g. `*=3000/10,NWS=5000/20`.
+// The default is 3000 ms and 10%. A provider is judged once it made SLO_MIN_REQUESTS calls in
+// the window (5 by default), so a single slow call doesn't count as a breach.
+//
+// A breach, and the recovery from it, is logged as a warning once per transition, exported as
+// the jupiter_provider_slo_breached gauge (and so through StatsD) next to the p95 and error
+// rate gauges, and listed at GET /api/admin/providers/health.
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SloTarget {
+    pub p95_ms: u64,
+    pub error_rate: f64,           // 0-1
+}
+
+impl Default for SloTarget {
+    fn default() -> Self {
+        Self { p95_ms: 3000, error_rate: 0.1 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SloConfig {
+    pub default: SloTarget,
+    pub providers: BTreeMap<String, SloTarget>,
+    pub window_secs: u64,
+    pub min_requests: usize,
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        Self { default: SloTarget::default(), providers: BTreeMap::new(), window_secs: 900, min_requests: 5 }
+    }
+}
+
+impl SloConfig {
+    /// Targets like "*=3000/10,NWS=5000/20" (PROVIDER_SLO) over the defaults
+    pub fn parse_targets(mut self, targets: &str) -> Result<Self, String> {
+        for entry in targets.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let parsed = entry.split_once('=').and_then(|(provider, target)| {
+                let (p95_ms, error_percent) = target.split_once('/')?;
+                let p95_ms = p95_ms.trim().trim_end_matches("ms").parse::<u64>().ok().filter(|ms| *ms > 0)?;
+                let error_percent = error_percent.trim().trim_end_matches('%').parse::<f64>().ok().filter(|p| (0.0..=100.0).contains(p))?;
+                Some((provider.trim(), SloTarget { p95_ms, error_rate: error_percent / 100.0 }))
+            });
+            match parsed {
+                Some(("*", target)) => self.default = target,
+                Some((provider, target)) if !provider.is_empty() => {
+                    self.providers.insert(provider.to_string(), target);
+                },
+                _ => return Err(format!("expected provider=p95_ms/error_percent (e.g. NWS=5000/20), got '{}'", entry)),
+            }
+        }
+        Ok(self)
+    }
+
+    /// Reads the settings through `var`; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let mut problems = Vec::new();
+        let mut config = Self::default();
+        if let Some(targets) = var("PROVIDER_SLO") {
+            match config.clone().parse_targets(&targets) {
+                Ok(parsed) => config = parsed,
+                Err(message) => problems.push(("PROVIDER_SLO", message)),
+            }
+        }
+        let mut positive = |setting: &'static str, default: u64| match var(setting).map(|v| v.parse::<u64>()) {
+            None => default,
+            Some(Ok(value)) if value > 0 => value,
+            Some(_) => {
+                problems.push((setting, "must be a whole number above 0".to_string()));
+                default
+            },
+        };
+        config.window_secs = positive("SLO_WINDOW_SECS", config.window_secs);
+        config.min_requests = positive("SLO_MIN_REQUESTS", config.min_requests as u64) as usize;
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    pub fn target(&self, provider: &str) -> SloTarget {
+        self.providers.get(provider).copied().unwrap_or(self.default)
+    }
+}
+
+pub static SLO_CONFIG: Lazy<SloConfig> = Lazy::new(|| {
+    SloConfig::from_env().unwrap_or_else(|problems| {
+        for (setting, message) in problems {
+            log::error!("Invalid {}, using the default provider SLOs: {}", setting, message);
+        }
+        SloConfig::default()
+    })
+});
+
+#[derive(Debug, Clone, Copy)]
+struct Call {
+    at: Instant,
+    duration: Duration,
+    ok: bool,
+}
+
+#[derive(Default)]
+struct Window {
+    calls: VecDeque<Call>,
+    breached: bool,
+}
+
+static WINDOWS: Lazy<Mutex<HashMap<String, Window>>> = Lazy::new(|| {
+    register_collectors();
+    Mutex::new(HashMap::new())
+});
+
+/// One provider's calls in the window against its target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub requests: usize,
+    pub errors: usize,
+    pub error_rate: f64,
+    pub p95_ms: Option<u64>,
+    pub target: SloTarget,
+    pub breaches: Vec<String>,     // e.g. "p95 4200 ms above 3000 ms"; empty when met or too few requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit: Option<CircuitStatus>,    // Circuit breaker, see `provider::health`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub budgets: Vec<BudgetStatus>,        // Call budgets, see `provider::quota`
+}
+
+impl ProviderHealth {
+    pub fn breached(&self) -> bool {
+        !self.breaches.is_empty()
+    }
+}
+
+/// Nearest-rank 95th percentile
+fn p95(durations: &mut [Duration]) -> Option<Duration> {
+    if durations.is_empty() {
+        return None;
+    }
+    durations.sort();
+    let rank = (durations.len() as f64 * 0.95).ceil() as usize;
+    Some(durations[rank.clamp(1, durations.len()) - 1])
+}
+
+fn evaluate(provider: &str, calls: &VecDeque<Call>, config: &SloConfig) -> ProviderHealth {
+    let target = config.target(provider);
+    let requests = calls.len();
+    let errors = calls.iter().filter(|call| !call.ok).count();
+    let error_rate = if requests == 0 { 0.0 } else { errors as f64 / requests as f64 };
+    let p95_ms = p95(&mut calls.iter().map(|call| call.duration).collect::<Vec<_>>()).map(|p95| p95.as_millis() as u64);
+
+    let mut breaches = Vec::new();
+    if requests >= config.min_requests {
+        if let Some(p95_ms) = p95_ms.filter(|p95_ms| *p95_ms > target.p95_ms) {
+            breaches.push(format!("p95 {} ms above {} ms", p95_ms, target.p95_ms));
+        }
+        if error_rate > target.error_rate {
+            breaches.push(format!("error rate {:.1}% above {:.1}%", error_rate * 100.0, target.error_rate * 100.0));
+        }
+    }
+    ProviderHealth { provider: provider.to_string(), requests, errors, error_rate, p95_ms, target, breaches, circuit: None, budgets: Vec::new() }
+}
+
+fn expire(calls: &mut VecDeque<Call>, window: Duration, now: Instant) {
+    while calls.front().is_some_and(|call| now.duration_since(call.at) > window) {
+        calls.pop_front();
+    }
+}
+
+/// Counts a provider call, logging when the provider starts or stops breaching its SLO
+pub fn record(provider: &str, duration: Duration, ok: bool) {
+    let config = &*SLO_CONFIG;
+    let now = Instant::now();
+    let Ok(mut windows) = WINDOWS.lock() else { return };
+    let window = windows.entry(provider.to_string()).or_default();
+    window.calls.push_back(Call { at: now, duration, ok });
+    expire(&mut window.calls, Duration::from_secs(config.window_secs), now);
+
+    let health = evaluate(provider, &window.calls, config);
+    if health.breached() != window.breached {
+        window.breached = health.breached();
+        if health.breached() {
+            log::warn!("[slo] {} is breaching its SLO over the last {} s: {}", provider, config.window_secs, health.breaches.join(", "));
+        } else {
+            log::warn!("[slo] {} meets its SLO again", provider);
+        }
+    }
+}
+
+/// Every provider called within the window, by name
+pub fn health() -> Vec<ProviderHealth> {
+    let config = &*SLO_CONFIG;
+    let now = Instant::now();
+    let Ok(mut windows) = WINDOWS.lock() else { return Vec::new() };
+    let mut health: Vec<ProviderHealth> = windows.iter_mut()
+        .map(|(provider, window)| {
+            expire(&mut window.calls, Duration::from_secs(config.window_secs), now);
+            evaluate(provider, &window.calls, config)
+        })
+        .collect();
+    health.sort_by(|a, b| a.provider.cmp(&b.provider));
+    health
+}
+
+fn register_collectors() {
+    fn per_provider(value: fn(&ProviderHealth) -> Option<f64>) -> Vec<(Vec<String>, f64)> {
+        health().iter().filter_map(|h| value(h).map(|v| (vec![h.provider.clone()], v))).collect()
+    }
+    metrics::collector(Kind::Gauge, "jupiter_provider_p95_latency_seconds", "95th percentile provider latency over the SLO window", &["provider"],
+        || per_provider(|h| h.p95_ms.map(|ms| ms as f64 / 1000.0)));
+    metrics::collector(Kind::Gauge, "jupiter_provider_error_rate", "Share of failed provider requests over the SLO window", &["provider"],
+        || per_provider(|h| Some(h.error_rate)));
+    metrics::collector(Kind::Gauge, "jupiter_provider_slo_breached", "1 while a provider misses its latency or error SLO", &["provider"],
+        || per_provider(|h| Some(if h.breached() { 1.0 } else { 0.0 })));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slo_config() {
+        let config = SloConfig::default().parse_targets("*=2000/5, NWS=5000ms/20%").unwrap();
+        assert_eq!(config.target("AccuWeather"), SloTarget { p95_ms: 2000, error_rate: 0.05 });
+        assert_eq!(config.target("NWS"), SloTarget { p95_ms: 5000, error_rate: 0.2 });
+        assert!(SloConfig::default().parse_targets("NWS=fast").is_err());
+        assert!(SloConfig::default().parse_targets("NWS=5000/150").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let now = Instant::now();
+        let call = |ms: u64, ok: bool| Call { at: now, duration: Duration::from_millis(ms), ok };
+        let config = SloConfig::default();
+
+        let mut calls: VecDeque<Call> = (1..=20).map(|i| call(i * 100, true)).collect();
+        let health = evaluate("NWS", &calls, &config);
+        assert_eq!((health.p95_ms, health.breached()), (Some(1900), false));
+
+        calls.push_back(call(9000, false));
+        calls.push_back(call(9000, false));
+        calls.push_back(call(9000, false));
+        let health = evaluate("NWS", &calls, &config);
+        assert_eq!(health.errors, 3);
+        assert_eq!(health.breaches, vec!["p95 9000 ms above 3000 ms".to_string(), "error rate 13.0% above 10.0%".to_string()]);
+
+        let few: VecDeque<Call> = (0..4).map(|_| call(9000, false)).collect();
+        assert!(!evaluate("NWS", &few, &config).breached(), "too few requests to judge");
+
+        let mut old: VecDeque<Call> = VecDeque::from(vec![call(100, true)]);
+        expire(&mut old, Duration::from_secs(60), now + Duration::from_secs(61));
+        assert!(old.is_empty());
+    }
+}
@@ -0,0 +1,549 @@
+use std::net::UdpSocket;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::combo_enhanced::ComboProvider;
+use crate::provider::common::WeatherProvider;
+use crate::provider::homebrew::{latest_aqi, Config as HomebrewConfig};
+use crate::secret::Secret;
+use crate::units::Pressure;
+
+// Read-only SNMP v1/v2c agent for building-management systems. Serves the latest combined
+// readings as scalar INTEGER objects under the configured base OID:
+//   <base>.1.0  temperature, tenths of °C
+//   <base>.2.0  relative humidity, tenths of %
+//   <base>.3.0  pressure, tenths of hPa
+//   <base>.4.0  US AQI (from homebrew PM2.5/PM10)
+
+// IANA's documentation enterprise number (RFC 5612); set SNMP_BASE_OID for production use
+const DEFAULT_BASE_OID: &str = "1.3.6.1.4.1.32473.1";
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+
+const PDU_GET: u8 = 0xa0;
+const PDU_GET_NEXT: u8 = 0xa1;
+const PDU_RESPONSE: u8 = 0xa2;
+const PDU_SET: u8 = 0xa3;
+const PDU_GET_BULK: u8 = 0xa5;
+
+const VERSION_1: i64 = 0;
+const VERSION_2C: i64 = 1;
+
+const ERROR_NO_SUCH_NAME: i64 = 2;
+const ERROR_NOT_WRITABLE: i64 = 17;
+
+#[derive(Debug, Clone)]
+pub struct SnmpConfig {
+    pub listen: String,
+    pub community: Secret<String>,
+    pub base_oid: Vec<u32>,
+    pub refresh_secs: u64,
+}
+
+impl SnmpConfig {
+    /// Enabled by setting SNMP_LISTEN (e.g. `0.0.0.0:161`)
+    pub fn from_env() -> JupiterResult<Option<Self>> {
+        let listen = match std::env::var("SNMP_LISTEN") {
+            Ok(listen) if !listen.is_empty() => listen,
+            _ => return Ok(None),
+        };
+        let base = std::env::var("SNMP_BASE_OID").unwrap_or_else(|_| DEFAULT_BASE_OID.to_string());
+        let base_oid = parse_oid(&base)
+            .ok_or_else(|| JupiterError::ConfigurationError(format!("Invalid SNMP_BASE_OID: {}", base)))?;
+        let refresh_secs = std::env::var("SNMP_REFRESH_SECS").ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(60);
+
+        Ok(Some(Self {
+            listen,
+            community: std::env::var("SNMP_COMMUNITY").unwrap_or_else(|_| "public".to_string()).into(),
+            base_oid,
+            refresh_secs,
+        }))
+    }
+}
+
+pub fn parse_oid(oid: &str) -> Option<Vec<u32>> {
+    let arcs: Option<Vec<u32>> = oid.trim_start_matches('.').split('.').map(|a| a.parse().ok()).collect();
+    arcs.filter(|a| a.len() >= 2)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnmpValues {
+    pub temperature: Option<f64>,
+    pub humidity: Option<f64>,
+    pub pressure: Option<f64>,
+    pub aqi: Option<u32>,
+}
+
+impl SnmpValues {
+    /// Exposed objects in OID order; `None` values are currently unavailable
+    fn objects(&self, base: &[u32]) -> Vec<(Vec<u32>, Option<i64>)> {
+        let tenths = |v: Option<f64>| v.map(|v| (v * 10.0).round() as i64);
+        let entries = [
+            (1, tenths(self.temperature)),
+            (2, tenths(self.humidity)),
+            (3, tenths(self.pressure)),
+            (4, self.aqi.map(|a| a as i64)),
+        ];
+        entries.iter().map(|(arc, value)| {
+            let mut oid = base.to_vec();
+            oid.extend_from_slice(&[*arc, 0]);
+            (oid, *value)
+        }).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum VarValue {
+    Integer(i64),
+    Null,
+    NoSuchObject,
+    NoSuchInstance,
+    EndOfMibView,
+}
+
+impl VarValue {
+    fn is_exception(&self) -> bool {
+        !matches!(self, VarValue::Integer(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SnmpRequest {
+    version: i64,
+    community: Vec<u8>,
+    pdu_type: u8,
+    request_id: i64,
+    // error-status/error-index for most PDUs, non-repeaters/max-repetitions for GetBulk
+    field2: i64,
+    field3: i64,
+    oids: Vec<Vec<u32>>,
+}
+
+// Returns (tag, contents, remaining bytes)
+fn read_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (len, header) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        let len = data.get(2..2 + n)?.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + n)
+    };
+    let contents = data.get(header..header + len)?;
+    Some((tag, contents, &data[header + len..]))
+}
+
+fn decode_integer(contents: &[u8]) -> Option<i64> {
+    if contents.is_empty() || contents.len() > 8 {
+        return None;
+    }
+    let negative = contents[0] & 0x80 != 0;
+    let init: i64 = if negative { -1 } else { 0 };
+    Some(contents.iter().fold(init, |acc, b| (acc << 8) | *b as i64))
+}
+
+fn decode_oid(contents: &[u8]) -> Option<Vec<u32>> {
+    // The first byte packs the first two arcs as 40 * x + y
+    let first = *contents.first()? as u32;
+    let mut oid = if first < 80 { vec![first / 40, first % 40] } else { vec![2, first - 80] };
+    let mut arc: u32 = 0;
+    for b in &contents[1..] {
+        arc = arc.checked_mul(128)? | (b & 0x7f) as u32;
+        if b & 0x80 == 0 {
+            oid.push(arc);
+            arc = 0;
+        }
+    }
+    Some(oid)
+}
+
+fn encode_tlv(tag: u8, contents: &[u8], out: &mut Vec<u8>) {
+    out.push(tag);
+    let len = contents.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().iter().copied().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend_from_slice(&bytes);
+    }
+    out.extend_from_slice(contents);
+}
+
+fn encode_integer(value: i64, out: &mut Vec<u8>) {
+    let bytes = value.to_be_bytes();
+    // Drop redundant leading sign bytes, keeping the minimal two's complement form
+    let mut start = 0;
+    while start < 7 {
+        let (b, next) = (bytes[start], bytes[start + 1]);
+        if (b == 0x00 && next & 0x80 == 0) || (b == 0xff && next & 0x80 != 0) {
+            start += 1;
+        } else {
+            break;
+        }
+    }
+    encode_tlv(TAG_INTEGER, &bytes[start..], out);
+}
+
+fn encode_oid(oid: &[u32], out: &mut Vec<u8>) {
+    let mut contents = vec![(oid[0] * 40 + oid.get(1).copied().unwrap_or(0)) as u8];
+    for arc in oid.iter().skip(2) {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        contents.extend(chunk.iter().rev());
+    }
+    encode_tlv(TAG_OID, &contents, out);
+}
+
+fn decode_request(packet: &[u8]) -> Option<SnmpRequest> {
+    let (tag, message, _) = read_tlv(packet)?;
+    if tag != TAG_SEQUENCE {
+        return None;
+    }
+    let (tag, version, rest) = read_tlv(message)?;
+    if tag != TAG_INTEGER {
+        return None;
+    }
+    let (tag, community, rest) = read_tlv(rest)?;
+    if tag != TAG_OCTET_STRING {
+        return None;
+    }
+    let (pdu_type, pdu, _) = read_tlv(rest)?;
+
+    let mut fields = [0i64; 3];
+    let mut rest = pdu;
+    for field in fields.iter_mut() {
+        let (tag, contents, next) = read_tlv(rest)?;
+        if tag != TAG_INTEGER {
+            return None;
+        }
+        *field = decode_integer(contents)?;
+        rest = next;
+    }
+
+    let (tag, mut bindings, _) = read_tlv(rest)?;
+    if tag != TAG_SEQUENCE {
+        return None;
+    }
+    let mut oids = Vec::new();
+    while !bindings.is_empty() {
+        let (tag, binding, next) = read_tlv(bindings)?;
+        if tag != TAG_SEQUENCE {
+            return None;
+        }
+        let (tag, oid, _) = read_tlv(binding)?;
+        if tag != TAG_OID {
+            return None;
+        }
+        oids.push(decode_oid(oid)?);
+        bindings = next;
+    }
+
+    Some(SnmpRequest {
+        version: decode_integer(version)?,
+        community: community.to_vec(),
+        pdu_type,
+        request_id: fields[0],
+        field2: fields[1],
+        field3: fields[2],
+        oids,
+    })
+}
+
+fn encode_response(request: &SnmpRequest, error_status: i64, error_index: i64, bindings: &[(Vec<u32>, VarValue)]) -> Vec<u8> {
+    let mut varbinds = Vec::new();
+    for (oid, value) in bindings {
+        let mut binding = Vec::new();
+        encode_oid(oid, &mut binding);
+        match value {
+            VarValue::Integer(v) => encode_integer(*v, &mut binding),
+            VarValue::Null => encode_tlv(TAG_NULL, &[], &mut binding),
+            VarValue::NoSuchObject => encode_tlv(0x80, &[], &mut binding),
+            VarValue::NoSuchInstance => encode_tlv(0x81, &[], &mut binding),
+            VarValue::EndOfMibView => encode_tlv(0x82, &[], &mut binding),
+        }
+        encode_tlv(TAG_SEQUENCE, &binding, &mut varbinds);
+    }
+
+    let mut pdu = Vec::new();
+    encode_integer(request.request_id, &mut pdu);
+    encode_integer(error_status, &mut pdu);
+    encode_integer(error_index, &mut pdu);
+    encode_tlv(TAG_SEQUENCE, &varbinds, &mut pdu);
+
+    let mut message = Vec::new();
+    encode_integer(request.version, &mut message);
+    encode_tlv(TAG_OCTET_STRING, &request.community, &mut message);
+    encode_tlv(PDU_RESPONSE, &pdu, &mut message);
+
+    let mut out = Vec::new();
+    encode_tlv(TAG_SEQUENCE, &message, &mut out);
+    out
+}
+
+fn get(objects: &[(Vec<u32>, Option<i64>)], oid: &[u32]) -> VarValue {
+    match objects.iter().find(|(o, _)| o.as_slice() == oid) {
+        Some((_, Some(value))) => VarValue::Integer(*value),
+        Some((_, None)) => VarValue::NoSuchInstance,
+        None => VarValue::NoSuchObject,
+    }
+}
+
+fn get_next(objects: &[(Vec<u32>, Option<i64>)], oid: &[u32]) -> (Vec<u32>, VarValue) {
+    objects.iter()
+        .find(|(o, value)| o.as_slice() > oid && value.is_some())
+        .map(|(o, value)| (o.clone(), VarValue::Integer(value.unwrap_or(0))))
+        .unwrap_or_else(|| (oid.to_vec(), VarValue::EndOfMibView))
+}
+
+/// Builds the response to one SNMP datagram. Requests with an unknown version or the wrong
+/// community get no reply, as RFC 3584 agents silently drop them.
+pub fn handle_packet(packet: &[u8], community: &str, base_oid: &[u32], values: &SnmpValues) -> Option<Vec<u8>> {
+    let request = decode_request(packet)?;
+    if (request.version != VERSION_1 && request.version != VERSION_2C) || request.community != community.as_bytes() {
+        return None;
+    }
+    let objects = values.objects(base_oid);
+
+    let bindings: Vec<(Vec<u32>, VarValue)> = match request.pdu_type {
+        PDU_GET => request.oids.iter().map(|oid| (oid.clone(), get(&objects, oid))).collect(),
+        PDU_GET_NEXT => request.oids.iter().map(|oid| get_next(&objects, oid)).collect(),
+        PDU_GET_BULK if request.version == VERSION_2C => {
+            let non_repeaters = (request.field2.max(0) as usize).min(request.oids.len());
+            let max_repetitions = request.field3.clamp(0, 64) as usize;
+            let mut bindings: Vec<_> = request.oids[..non_repeaters].iter().map(|oid| get_next(&objects, oid)).collect();
+            let mut cursors: Vec<Vec<u32>> = request.oids[non_repeaters..].to_vec();
+            for _ in 0..max_repetitions {
+                if cursors.is_empty() {
+                    break;
+                }
+                let row: Vec<_> = cursors.iter().map(|oid| get_next(&objects, oid)).collect();
+                let finished = row.iter().all(|(_, value)| *value == VarValue::EndOfMibView);
+                cursors = row.iter().map(|(oid, _)| oid.clone()).collect();
+                bindings.extend(row);
+                if finished {
+                    break;
+                }
+            }
+            bindings
+        },
+        PDU_SET => {
+            let status = if request.version == VERSION_1 { ERROR_NO_SUCH_NAME } else { ERROR_NOT_WRITABLE };
+            let echoed: Vec<_> = request.oids.iter().map(|oid| (oid.clone(), VarValue::Null)).collect();
+            return Some(encode_response(&request, status, 1, &echoed));
+        },
+        _ => return None,
+    };
+
+    // SNMPv1 has no exception values; report the first failing binding as noSuchName instead
+    if request.version == VERSION_1 {
+        if let Some(index) = bindings.iter().position(|(_, value)| value.is_exception()) {
+            let echoed: Vec<_> = request.oids.iter().map(|oid| (oid.clone(), VarValue::Null)).collect();
+            return Some(encode_response(&request, ERROR_NO_SUCH_NAME, index as i64 + 1, &echoed));
+        }
+    }
+
+    Some(encode_response(&request, 0, 0, &bindings))
+}
+
+async fn fetch_weather(providers: &ComboProvider, location: &str) -> SnmpValues {
+    match providers.get_current_weather(location).await {
+        Ok(weather) => SnmpValues {
+            temperature: Some(weather.temperature.celsius()),
+            humidity: weather.humidity,
+            pressure: weather.pressure.map(Pressure::hpa),
+            aqi: None,
+        },
+        Err(e) => {
+            log::warn!("[snmp] Failed to refresh weather values: {}", e);
+            SnmpValues::default()
+        }
+    }
+}
+
+/// Starts the refresh and UDP listener threads. Both exit once `shutdown_flag` is set.
+/// Must be called from within the process runtime, which drives the providers.
+pub fn start(config: SnmpConfig, providers: Arc<ComboProvider>, location: String, homebrew: Option<HomebrewConfig>, shutdown_flag: Arc<AtomicBool>) -> JupiterResult<Vec<JoinHandle<()>>> {
+    let socket = UdpSocket::bind(&config.listen)?;
+    socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+    let values = Arc::new(RwLock::new(SnmpValues::default()));
+
+    let refresh_values = values.clone();
+    let refresh_shutdown = shutdown_flag.clone();
+    let refresh_interval = Duration::from_secs(config.refresh_secs.max(1));
+    let runtime = tokio::runtime::Handle::current();
+    let refresher = thread::spawn(move || {
+        while !refresh_shutdown.load(Ordering::Relaxed) {
+            let started = Instant::now();
+            let mut latest = runtime.block_on(fetch_weather(&providers, &location));
+
+            if let Some(ref homebrew) = homebrew {
+                match runtime.block_on(latest_aqi(homebrew)) {
+                    Ok(aqi) => latest.aqi = aqi,
+                    Err(e) => log::warn!("[snmp] Failed to read homebrew air quality: {}", e),
+                }
+            }
+
+            match refresh_values.write() {
+                Ok(mut guard) => *guard = latest,
+                Err(poisoned) => *poisoned.into_inner() = latest,
+            }
+
+            while started.elapsed() < refresh_interval && !refresh_shutdown.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    });
+
+    log::info!("[snmp] Agent listening on {}", config.listen);
+    let listener = thread::spawn(move || {
+        let mut buf = [0u8; 1500];
+        while !shutdown_flag.load(Ordering::Relaxed) {
+            let (len, peer) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(e) => {
+                    log::error!("[snmp] Receive error: {}", e);
+                    continue;
+                }
+            };
+
+            let snapshot = match values.read() {
+                Ok(guard) => guard.clone(),
+                Err(poisoned) => poisoned.into_inner().clone(),
+            };
+            if let Some(response) = handle_packet(&buf[..len], config.community.expose(), &config.base_oid, &snapshot) {
+                if let Err(e) = socket.send_to(&response, peer) {
+                    log::warn!("[snmp] Failed to reply to {}: {}", peer, e);
+                }
+            }
+        }
+        log::info!("[snmp] Agent stopped");
+    });
+
+    Ok(vec![refresher, listener])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An OID and the raw BER contents of its value
+    type RawBinding = (Vec<u32>, Vec<u8>);
+
+    fn request(version: i64, community: &str, pdu_type: u8, field2: i64, field3: i64, oids: &[&str]) -> Vec<u8> {
+        let request = SnmpRequest {
+            version,
+            community: community.as_bytes().to_vec(),
+            pdu_type,
+            request_id: 42,
+            field2,
+            field3,
+            oids: oids.iter().map(|o| parse_oid(o).unwrap()).collect(),
+        };
+        // Requests share the response layout apart from the PDU tag
+        let bindings: Vec<_> = request.oids.iter().map(|o| (o.clone(), VarValue::Null)).collect();
+        let mut packet = encode_response(&request, field2, field3, &bindings);
+        let pdu_offset = packet.iter().position(|b| *b == PDU_RESPONSE).unwrap();
+        packet[pdu_offset] = pdu_type;
+        packet
+    }
+
+    fn values() -> SnmpValues {
+        SnmpValues { temperature: Some(-3.2), humidity: Some(81.0), pressure: None, aqi: Some(42) }
+    }
+
+    fn base() -> Vec<u32> {
+        parse_oid(DEFAULT_BASE_OID).unwrap()
+    }
+
+    fn response_bindings(packet: &[u8]) -> (i64, Vec<RawBinding>) {
+        let decoded = decode_request(packet).unwrap();
+        assert_eq!(decoded.pdu_type, PDU_RESPONSE);
+        assert_eq!(decoded.request_id, 42);
+        // Re-walk the varbinds to read the values as well
+        let (_, message, _) = read_tlv(packet).unwrap();
+        let (_, _, rest) = read_tlv(message).unwrap();
+        let (_, _, rest) = read_tlv(rest).unwrap();
+        let (_, pdu, _) = read_tlv(rest).unwrap();
+        let (_, _, rest) = read_tlv(pdu).unwrap();
+        let (_, _, rest) = read_tlv(rest).unwrap();
+        let (_, _, rest) = read_tlv(rest).unwrap();
+        let (_, mut bindings, _) = read_tlv(rest).unwrap();
+        let mut out = Vec::new();
+        while !bindings.is_empty() {
+            let (_, binding, next) = read_tlv(bindings).unwrap();
+            let (_, oid, value) = read_tlv(binding).unwrap();
+            out.push((decode_oid(oid).unwrap(), value.to_vec()));
+            bindings = next;
+        }
+        (decoded.field2, out)
+    }
+
+    #[test]
+    fn test_ber_round_trip() {
+        for value in [0i64, 127, 128, -1, -129, 65535, i32::MIN as i64] {
+            let mut out = Vec::new();
+            encode_integer(value, &mut out);
+            let (_, contents, _) = read_tlv(&out).unwrap();
+            assert_eq!(decode_integer(contents), Some(value));
+        }
+        let oid = parse_oid("1.3.6.1.4.1.32473.1.4.0").unwrap();
+        let mut out = Vec::new();
+        encode_oid(&oid, &mut out);
+        let (_, contents, _) = read_tlv(&out).unwrap();
+        assert_eq!(decode_oid(contents), Some(oid));
+    }
+
+    #[test]
+    fn test_get_and_get_next() {
+        let packet = request(VERSION_2C, "public", PDU_GET, 0, 0, &["1.3.6.1.4.1.32473.1.1.0", "1.3.6.1.4.1.32473.1.3.0"]);
+        let response = handle_packet(&packet, "public", &base(), &values()).unwrap();
+        let (status, bindings) = response_bindings(&response);
+        assert_eq!(status, 0);
+        assert_eq!(bindings[0].1, vec![TAG_INTEGER, 1, 0xe0]); // -32 tenths of °C
+        assert_eq!(bindings[1].1, vec![0x81, 0]);              // pressure unavailable
+
+        // GETNEXT from the base walks to the first object and skips unavailable ones
+        let packet = request(VERSION_2C, "public", PDU_GET_NEXT, 0, 0, &["1.3.6.1.4.1.32473.1", "1.3.6.1.4.1.32473.1.2.0", "1.3.6.1.4.1.32473.1.4.0"]);
+        let (_, bindings) = response_bindings(&handle_packet(&packet, "public", &base(), &values()).unwrap());
+        assert_eq!(bindings[0].0, parse_oid("1.3.6.1.4.1.32473.1.1.0").unwrap());
+        assert_eq!(bindings[1].0, parse_oid("1.3.6.1.4.1.32473.1.4.0").unwrap());
+        assert_eq!(bindings[2].1, vec![0x82, 0]);
+    }
+
+    #[test]
+    fn test_v1_errors_and_community() {
+        let packet = request(VERSION_1, "public", PDU_GET, 0, 0, &["1.3.6.1.4.1.32473.1.2.0", "1.3.6.1.4.1.32473.1.3.0"]);
+        let (status, _) = response_bindings(&handle_packet(&packet, "public", &base(), &values()).unwrap());
+        assert_eq!(status, ERROR_NO_SUCH_NAME);
+
+        assert!(handle_packet(&packet, "secret", &base(), &values()).is_none());
+    }
+
+    #[test]
+    fn test_get_bulk() {
+        let packet = request(VERSION_2C, "public", PDU_GET_BULK, 0, 10, &["1.3.6.1.4.1.32473.1"]);
+        let (_, bindings) = response_bindings(&handle_packet(&packet, "public", &base(), &values()).unwrap());
+        // temperature, humidity, aqi, then endOfMibView
+        assert_eq!(bindings.len(), 4);
+        assert_eq!(bindings[3].1, vec![0x82, 0]);
+    }
+}
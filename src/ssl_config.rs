@@ -1,9 +1,18 @@
+#[cfg(not(feature = "rustls"))]
 use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
-use postgres_openssl::MakeTlsConnector;
 use std::env;
 use std::path::Path;
 use std::error::Error as StdError;
 
+#[cfg(not(any(feature = "openssl-tls", feature = "rustls")))]
+compile_error!("enable either the `openssl-tls` (default) or the `rustls` feature");
+
+/// Postgres TLS connector of the selected backend; rustls wins if both features are enabled
+#[cfg(not(feature = "rustls"))]
+pub type TlsConnector = postgres_openssl::MakeTlsConnector;
+#[cfg(feature = "rustls")]
+pub type TlsConnector = tokio_postgres_rustls::MakeRustlsConnect;
+
 /// SSL/TLS configuration for secure database connections
 pub struct SslConfig {
     /// Optional path to custom CA certificate
@@ -39,7 +48,8 @@ impl SslConfig {
     }
     
     /// Build an SSL connector with the configured settings
-    pub fn build_connector(&self) -> Result<MakeTlsConnector, Box<dyn StdError>> {
+    #[cfg(not(feature = "rustls"))]
+    pub fn build_connector(&self) -> Result<TlsConnector, Box<dyn StdError>> {
         let mut builder = SslConnector::builder(SslMethod::tls())?;
         
         // Set verification mode
@@ -71,7 +81,54 @@ impl SslConfig {
         // Set minimum TLS version to 1.2
         builder.set_min_proto_version(Some(openssl::ssl::SslVersion::TLS1_2))?;
         
-        Ok(MakeTlsConnector::new(builder.build()))
+        Ok(TlsConnector::new(builder.build()))
+    }
+
+    /// Build a rustls connector with the configured settings. Trusts the bundled Mozilla roots
+    /// plus the custom CA; rustls only speaks TLS 1.2 and 1.3.
+    #[cfg(feature = "rustls")]
+    pub fn build_connector(&self) -> Result<TlsConnector, Box<dyn StdError>> {
+        use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore};
+        use std::sync::Arc;
+
+        let mut roots = RootCertStore::empty();
+        roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+        }));
+
+        // Load custom CA certificate if provided
+        if let Some(ref ca_path) = self.ca_cert_path {
+            if Path::new(ca_path).exists() {
+                let mut reader = std::io::BufReader::new(std::fs::File::open(ca_path)?);
+                let certs = rustls_pemfile::certs(&mut reader).map_err(|e| {
+                    log::error!("{}: Failed to load CA certificate from {}: {}", self.env_prefix, ca_path, e);
+                    e
+                })?;
+                for cert in certs {
+                    roots.add(&Certificate(cert))
+                        .map_err(|e| format!("Invalid CA certificate in {}: {:?}", ca_path, e))?;
+                }
+                log::info!("{}: Loaded custom CA certificate from {}", self.env_prefix, ca_path);
+            } else {
+                log::warn!("{}: CA certificate path {} does not exist", self.env_prefix, ca_path);
+            }
+        }
+
+        let mut config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        // Set verification mode
+        if self.verify_peer {
+            log::info!("{}: SSL certificate verification enabled", self.env_prefix);
+        } else {
+            // WARNING: Only for development/testing - never use in production!
+            log::warn!("{}: SSL certificate verification DISABLED - This is insecure and should only be used in development!", self.env_prefix);
+            config.dangerous().set_certificate_verifier(Arc::new(NoVerification));
+        }
+
+        Ok(TlsConnector::new(config))
     }
     
     /// Get the appropriate sslmode parameter for PostgreSQL connection string
@@ -85,13 +142,31 @@ impl SslConfig {
 }
 
 /// Create a secure SSL connector for Homebrew provider
-pub fn create_homebrew_connector() -> Result<MakeTlsConnector, Box<dyn StdError>> {
+pub fn create_homebrew_connector() -> Result<TlsConnector, Box<dyn StdError>> {
     let config = SslConfig::new("HOMEBREW");
     config.build_connector()
 }
 
 /// Create a secure SSL connector for Combo provider
-pub fn create_combo_connector() -> Result<MakeTlsConnector, Box<dyn StdError>> {
+pub fn create_combo_connector() -> Result<TlsConnector, Box<dyn StdError>> {
     let config = SslConfig::new("COMBO");
     config.build_connector()
-}
\ No newline at end of file
+}
+/// Accepts any server certificate, the rustls equivalent of `SslVerifyMode::NONE`
+#[cfg(feature = "rustls")]
+struct NoVerification;
+
+#[cfg(feature = "rustls")]
+impl rustls::client::ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
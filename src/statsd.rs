@@ -0,0 +1,281 @@
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::env;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::metrics::{self, Kind, Sample};
+
+// The counters and gauges of GET /metrics pushed to a StatsD or DogStatsD agent over UDP, for
+// setups without Prometheus:
+//
+//   STATSD_HOST            agent address, host or host:port (port 8125 by default); unset, nothing is sent
+//   STATSD_PREFIX          prepended to every metric name, "jupiter" by default
+//   STATSD_FLAVOR          statsd (default) or datadog
+//   STATSD_TAGS            tags added to every metric, e.g. env:prod,site:garage (datadog only)
+//   STATSD_INTERVAL_SECS   how often counters and gauges are sent, 10 by default
+//
+// Names drop the Prometheus "jupiter_" prefix and "_total" or "_seconds" suffixes, e.g.
+// jupiter_http_responses_total becomes jupiter.http_responses. Counters are sent as the increase
+// since the last flush, gauges (pool sizes) as read, and every histogram observation (request,
+// provider and pool acquire latencies) as a timing in milliseconds. DogStatsD gets labels as
+// tags; plain StatsD, which has no tags, gets label values as extra name segments
+// (jupiter.http_responses.combo.GET.200).
+
+/// Largest datagram sent, safe for the usual 1500 byte MTU
+const MAX_PACKET: usize = 1432;
+
+/// Timings kept between flushes; more are dropped rather than buffered without bound
+const MAX_PENDING_TIMINGS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    Statsd,
+    Datadog,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsdConfig {
+    pub address: String,           // host:port
+    pub prefix: String,
+    pub flavor: Flavor,
+    pub tags: Vec<String>,         // key:value
+    pub interval_secs: u64,
+}
+
+impl StatsdConfig {
+    /// Reads the settings through `var`, None without STATSD_HOST; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let Some(host) = var("STATSD_HOST") else {
+            return Ok(None);
+        };
+        let mut problems = Vec::new();
+
+        let address = if host.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+            host
+        } else {
+            format!("{}:8125", host)
+        };
+        let flavor = match var("STATSD_FLAVOR").as_deref() {
+            None | Some("statsd") => Flavor::Statsd,
+            Some("datadog") | Some("dogstatsd") => Flavor::Datadog,
+            Some(other) => {
+                problems.push(("STATSD_FLAVOR", format!("must be statsd or datadog, got {}", other)));
+                Flavor::Statsd
+            },
+        };
+        let tags: Vec<String> = var("STATSD_TAGS")
+            .map(|tags| tags.split(',').map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect())
+            .unwrap_or_default();
+        if let Some(tag) = tags.iter().find(|tag| tag.contains('|') || tag.contains('#')) {
+            problems.push(("STATSD_TAGS", format!("tags can't contain | or #, got {}", tag)));
+        }
+        if !tags.is_empty() && flavor == Flavor::Statsd {
+            problems.push(("STATSD_TAGS", "tags need STATSD_FLAVOR=datadog, plain StatsD has none".to_string()));
+        }
+        let interval_secs = match var("STATSD_INTERVAL_SECS").map(|secs| secs.parse::<u64>()) {
+            None => 10,
+            Some(Ok(secs)) if secs > 0 => secs,
+            Some(_) => {
+                problems.push(("STATSD_INTERVAL_SECS", "must be a number of seconds above 0".to_string()));
+                10
+            },
+        };
+
+        if problems.is_empty() {
+            Ok(Some(Self { address, prefix: var("STATSD_PREFIX").unwrap_or_else(|| "jupiter".to_string()), flavor, tags, interval_secs }))
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Option<Self>, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// StatsD name of a metric, with the plain StatsD label segments
+    fn name(&self, metric: &str, labels: &[(&str, String)]) -> String {
+        let base = metric.strip_prefix("jupiter_").unwrap_or(metric);
+        let base = base.strip_suffix("_total").or_else(|| base.strip_suffix("_seconds")).unwrap_or(base);
+        let mut name = if self.prefix.is_empty() { base.to_string() } else { format!("{}.{}", self.prefix, base) };
+        if self.flavor == Flavor::Statsd {
+            for (_, value) in labels {
+                name.push('.');
+                name.extend(value.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' }));
+            }
+        }
+        name
+    }
+
+    /// `name:value|type`, with `|#tags` for DogStatsD
+    fn line(&self, metric: &str, labels: &[(&str, String)], value: f64, kind: &str) -> String {
+        let mut line = format!("{}:{}|{}", self.name(metric, labels), value, kind);
+        if self.flavor == Flavor::Datadog {
+            let tags: Vec<String> = labels.iter()
+                .map(|(name, value)| format!("{}:{}", name, value.replace([',', '|', '#'], "_")))
+                .chain(self.tags.iter().cloned())
+                .collect();
+            if !tags.is_empty() {
+                line.push_str("|#");
+                line.push_str(&tags.join(","));
+            }
+        }
+        line
+    }
+}
+
+struct Emitter {
+    config: StatsdConfig,
+    socket: UdpSocket,
+    counters: Mutex<HashMap<String, f64>>,   // Values last sent, by line without the value
+    timings: Mutex<Vec<String>>,
+}
+
+static EMITTER: OnceCell<Emitter> = OnceCell::new();
+
+/// Queues a histogram observation in seconds as a timing, when StatsD is on
+pub fn timing(metric: &'static str, names: &[&'static str], values: &[&str], seconds: f64) {
+    let Some(emitter) = EMITTER.get() else { return };
+    let labels: Vec<(&str, String)> = names.iter().copied().zip(values.iter().map(|v| v.to_string())).collect();
+    let line = emitter.config.line(metric, &labels, (seconds * 1000.0 * 1000.0).round() / 1000.0, "ms");
+    if let Ok(mut timings) = emitter.timings.lock() {
+        if timings.len() < MAX_PENDING_TIMINGS {
+            timings.push(line);
+        }
+    }
+}
+
+impl Emitter {
+    /// Lines for the counters' increase since the last call and the gauges' current values
+    fn lines(&self, samples: Vec<Sample>) -> Vec<String> {
+        let mut lines = Vec::new();
+        let Ok(mut sent) = self.counters.lock() else { return lines };
+        for sample in samples {
+            match sample.kind {
+                Kind::Gauge => lines.push(self.config.line(sample.name, &sample.labels, sample.value, "g")),
+                Kind::Counter => {
+                    let key = self.config.line(sample.name, &sample.labels, 0.0, "c");
+                    let previous = sent.insert(key, sample.value).unwrap_or(0.0);
+                    if sample.value > previous {
+                        lines.push(self.config.line(sample.name, &sample.labels, sample.value - previous, "c"));
+                    }
+                },
+            }
+        }
+        lines
+    }
+
+    fn flush(&self) {
+        let mut lines = self.lines(metrics::samples());
+        if let Ok(mut timings) = self.timings.lock() {
+            lines.append(&mut timings);
+        }
+        for packet in packets(&lines) {
+            if let Err(e) = self.socket.send(packet.as_bytes()) {
+                log::debug!("[statsd] Failed to send metrics to {}: {}", self.config.address, e);
+            }
+        }
+    }
+}
+
+/// Lines joined by newlines into datagrams of at most MAX_PACKET bytes
+fn packets(lines: &[String]) -> Vec<String> {
+    let mut packets: Vec<String> = Vec::new();
+    for line in lines {
+        match packets.last_mut() {
+            Some(packet) if packet.len() + 1 + line.len() <= MAX_PACKET => {
+                packet.push('\n');
+                packet.push_str(line);
+            },
+            _ => packets.push(line.clone()),
+        }
+    }
+    packets
+}
+
+/// Starts flushing to the agent every interval until `shutdown_flag` is set, with a last flush
+/// on the way out
+pub fn start(config: StatsdConfig, shutdown_flag: Arc<AtomicBool>) -> Option<JoinHandle<()>> {
+    let socket = config.address.to_socket_addrs().ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "address did not resolve"))
+        .and_then(|address| {
+            let socket = UdpSocket::bind(if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+            socket.connect(address)?;
+            socket.set_nonblocking(true)?;
+            Ok(socket)
+        });
+    let socket = match socket {
+        Ok(socket) => socket,
+        Err(e) => {
+            log::error!("[statsd] Failed to reach STATSD_HOST {}, metrics won't be sent: {}", config.address, e);
+            return None;
+        }
+    };
+    log::info!("[statsd] Sending metrics to {} every {} s", config.address, config.interval_secs);
+    let interval = config.interval_secs;
+    if EMITTER.set(Emitter { config, socket, counters: Mutex::new(HashMap::new()), timings: Mutex::new(Vec::new()) }).is_err() {
+        return None;
+    }
+
+    Some(std::thread::spawn(move || {
+        let Some(emitter) = EMITTER.get() else { return };
+        while !shutdown_flag.load(Ordering::Relaxed) {
+            // Sleep in short steps so shutdown isn't delayed
+            for _ in 0..interval * 10 {
+                if shutdown_flag.load(Ordering::Relaxed) {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            emitter.flush();
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(vars: &[(&str, &str)]) -> Result<Option<StatsdConfig>, Vec<(&'static str, String)>> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        StatsdConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_statsd_lines() {
+        assert_eq!(config(&[]).unwrap(), None);
+        assert_eq!(config(&[("STATSD_TAGS", "env:prod"), ("STATSD_HOST", "agent")]).unwrap_err(),
+            vec![("STATSD_TAGS", "tags need STATSD_FLAVOR=datadog, plain StatsD has none".to_string())]);
+
+        let statsd = config(&[("STATSD_HOST", "agent")]).unwrap().unwrap();
+        assert_eq!((statsd.address.as_str(), statsd.interval_secs), ("agent:8125", 10));
+        let labels = vec![("server", "combo".to_string()), ("method", "GET".to_string()), ("status", "200".to_string())];
+        assert_eq!(statsd.line("jupiter_http_responses_total", &labels, 3.0, "c"), "jupiter.http_responses.combo.GET.200:3|c");
+
+        let datadog = config(&[("STATSD_HOST", "127.0.0.1:9125"), ("STATSD_FLAVOR", "datadog"), ("STATSD_TAGS", "env:prod"), ("STATSD_PREFIX", "wx")])
+            .unwrap().unwrap();
+        assert_eq!(datadog.line("jupiter_provider_request_duration_seconds", &[("provider", "NWS".to_string())], 250.5, "ms"),
+            "wx.provider_request_duration:250.5|ms|#provider:NWS,env:prod");
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let emitter = Emitter { config: datadog, socket, counters: Mutex::new(HashMap::new()), timings: Mutex::new(Vec::new()) };
+        let counter = |value: f64| Sample { name: "jupiter_auth_failures_total", kind: Kind::Counter, labels: Vec::new(), value };
+        let gauge = Sample { name: "jupiter_db_pool_waiting", kind: Kind::Gauge, labels: vec![("pool", "homebrew".to_string())], value: 0.0 };
+        assert_eq!(emitter.lines(vec![counter(5.0), gauge.clone()]), vec![
+            "wx.auth_failures:5|c|#env:prod".to_string(),
+            "wx.db_pool_waiting:0|g|#pool:homebrew,env:prod".to_string(),
+        ]);
+        assert_eq!(emitter.lines(vec![counter(7.0)]), vec!["wx.auth_failures:2|c|#env:prod".to_string()], "increase since the last flush");
+        assert!(emitter.lines(vec![counter(7.0)]).is_empty());
+
+        let lines: Vec<String> = (0..100).map(|i| format!("wx.metric_{}:1|c", i)).collect();
+        let packets = packets(&lines);
+        assert!(packets.len() > 1 && packets.iter().all(|p| p.len() <= MAX_PACKET));
+        assert_eq!(packets.join("\n"), lines.join("\n"));
+    }
+}
@@ -0,0 +1,500 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::db_pool::homebrew_client;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::homebrew::{Cursor, FilterParams, Resolution, WeatherReport};
+use crate::summary::{self, SummaryView};
+
+// Where the homebrew server keeps its reports. Postgres (HOMEBREW_PG_*) is the default and the
+// only store with devices and API keys in tables, rollups, erasure and the audit chain. With
+// HOMEBREW_SQLITE_PATH set (builds with `--features sqlite`) reports go to a single SQLite file
+// instead, for a Raspberry Pi that shouldn't run a database server: devices then come from
+// DEVICE_REGISTRY_FILE and only the API key is accepted.
+
+/// Reads and writes of weather reports, in the order and shape of the Postgres queries
+#[async_trait]
+pub trait WeatherStore: Send + Sync {
+    /// For logs, e.g. "SQLite database /var/lib/jupiter/reports.db"
+    fn name(&self) -> String;
+
+    /// Whether reports are in the homebrew Postgres database, which everything else needs
+    fn uses_postgres(&self) -> bool {
+        false
+    }
+
+    /// Creates the report table if it is missing
+    async fn build(&self) -> JupiterResult<()>;
+
+    /// Inserts the report, or merges its readings into the stored one with the same oid
+    async fn save(&self, report: &WeatherReport) -> JupiterResult<()>;
+
+    async fn latest(&self) -> JupiterResult<Option<WeatherReport>>;
+
+    /// Newest first by `order_column` (one of id, timestamp, temperature, humidity, oid; id otherwise)
+    async fn select(&self, limit: Option<usize>, offset: Option<usize>, order_column: Option<String>, filter_params: Option<FilterParams>) -> JupiterResult<Vec<WeatherReport>>;
+
+    /// See `WeatherReport::select_range`
+    async fn select_range(&self, start_ts: i64, end_ts: i64, device_type: Option<String>, resolution: Resolution) -> JupiterResult<Vec<WeatherReport>>;
+
+    /// Up to `limit` raw reports with `start_ts <= timestamp < end_ts` ordered by (timestamp, id),
+    /// after the last report of the previous page if given
+    async fn select_page(&self, start_ts: i64, end_ts: i64, device_type: Option<String>, after: Option<Cursor>, limit: usize) -> JupiterResult<Vec<WeatherReport>>;
+
+    /// Up to `limit` reports newest first by (timestamp, id), before the last report of the
+    /// previous page or, deprecated, skipping `offset` reports; see `WeatherReport::list_query`
+    async fn list(&self, filter_params: Option<FilterParams>, before: Option<Cursor>, offset: Option<usize>, limit: usize) -> JupiterResult<Vec<WeatherReport>>;
+}
+
+/// The store HOMEBREW_SQLITE_PATH names, if set
+pub fn sqlite_path_from_env() -> Option<String> {
+    std::env::var("HOMEBREW_SQLITE_PATH").ok()
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+}
+
+/// SQLite at `sqlite_path`, or the homebrew Postgres database without one
+pub fn open(sqlite_path: Option<&str>) -> JupiterResult<Arc<dyn WeatherStore>> {
+    match sqlite_path {
+        None => Ok(postgres()),
+        #[cfg(feature = "sqlite")]
+        Some(path) => Ok(Arc::new(Sqlite::open(path)?)),
+        #[cfg(not(feature = "sqlite"))]
+        Some(_) => Err(JupiterError::ConfigurationError("HOMEBREW_SQLITE_PATH needs a build with the sqlite feature (cargo build --features sqlite)".to_string())),
+    }
+}
+
+pub fn postgres() -> Arc<dyn WeatherStore> {
+    Arc::new(Postgres)
+}
+
+/// Reports in the homebrew Postgres database, through its pool
+pub struct Postgres;
+
+#[async_trait]
+impl WeatherStore for Postgres {
+    fn name(&self) -> String {
+        "Postgres".to_string()
+    }
+
+    fn uses_postgres(&self) -> bool {
+        true
+    }
+
+    // Built with the other homebrew tables, see `Config::build_tables`
+    async fn build(&self) -> JupiterResult<()> {
+        Ok(())
+    }
+
+    async fn save(&self, report: &WeatherReport) -> JupiterResult<()> {
+        let client = homebrew_client().await.map_err(|e| {
+            log::error!("Failed to get database connection: {}", e);
+            e
+        })?;
+
+        let exists = !client.query("SELECT 1 FROM weather_reports WHERE oid = $1", &[&report.oid]).await?.is_empty();
+        if !exists {
+            client.execute("INSERT INTO weather_reports (oid, device_type, timestamp) VALUES ($1, $2, $3)",
+                &[&report.oid, &report.device_type, &report.timestamp]
+            ).await?;
+        }
+
+        // Only the readings this report carries, so partial reports of the same oid merge
+        let readings = [
+            ("temperature", report.temperature),
+            ("humidity", report.humidity),
+            ("percipitation", report.percipitation),
+            ("pm10", report.pm10),
+            ("pm25", report.pm25),
+            ("co2", report.co2),
+            ("tvoc", report.tvoc),
+            ("wind_speed", report.wind_speed),
+            ("wind_direction", report.wind_direction),
+            ("battery_voltage", report.battery_voltage),
+            ("battery_percent", report.battery_percent),
+            ("rssi", report.rssi),
+        ];
+        for (column, value) in readings {
+            if value.is_some() {
+                client.execute(&format!("UPDATE weather_reports SET {} = $1 WHERE oid = $2;", column), &[&value, &report.oid]).await?;
+            }
+        }
+        if report.device_id.is_some() {
+            client.execute("UPDATE weather_reports SET device_id = $1 WHERE oid = $2;", &[&report.device_id, &report.oid]).await?;
+        }
+        Ok(())
+    }
+
+    async fn latest(&self) -> JupiterResult<Option<WeatherReport>> {
+        WeatherReport::latest(&homebrew_client().await?).await
+    }
+
+    async fn select(&self, limit: Option<usize>, offset: Option<usize>, order_column: Option<String>, filter_params: Option<FilterParams>) -> JupiterResult<Vec<WeatherReport>> {
+        let (query, params) = WeatherReport::select_query(limit, offset, order_column, filter_params);
+        let query_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params.iter()
+            .map(|p| p as &(dyn tokio_postgres::types::ToSql + Sync))
+            .collect();
+        let rows = homebrew_client().await?.query(&query, &query_params).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+
+        rows.iter()
+            .map(|row| WeatherReport::from_row(row)
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e))))
+            .collect()
+    }
+
+    async fn select_range(&self, start_ts: i64, end_ts: i64, device_type: Option<String>, resolution: Resolution) -> JupiterResult<Vec<WeatherReport>> {
+        let client = homebrew_client().await?;
+        // Ranges from a bucket boundary can start from the summarized buckets
+        let query = match SummaryView::for_resolution(resolution) {
+            Some(view) if start_ts % view.bucket_secs() == 0 && summary::usable(&client).await => view.range_query(),
+            _ => WeatherReport::range_query(resolution),
+        };
+        let rows = client.query(&query, &[&start_ts, &end_ts, &device_type]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+
+        let mut reports = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut report = WeatherReport::from_row(&row)
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e)))?;
+            // ATAN2 gives -180..180
+            report.wind_direction = report.wind_direction.map(|degrees| degrees.rem_euclid(360.0));
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    async fn select_page(&self, start_ts: i64, end_ts: i64, device_type: Option<String>, after: Option<Cursor>, limit: usize) -> JupiterResult<Vec<WeatherReport>> {
+        let query = format!(
+            "SELECT * FROM weather_reports WHERE timestamp >= $1 AND timestamp < $2 AND ($3::varchar IS NULL OR device_type = $3) \
+            AND ($4::bigint IS NULL OR (timestamp, id) > ($4, $5)) ORDER BY timestamp ASC, id ASC LIMIT {}",
+            limit,
+        );
+        let (after_ts, after_id) = (after.map(|cursor| cursor.timestamp), after.map(|cursor| cursor.id).unwrap_or(0));
+        let rows = homebrew_client().await?.query(&query, &[&start_ts, &end_ts, &device_type, &after_ts, &after_id]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+
+        rows.iter()
+            .map(|row| WeatherReport::from_row(row)
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e))))
+            .collect()
+    }
+
+    async fn list(&self, filter_params: Option<FilterParams>, before: Option<Cursor>, offset: Option<usize>, limit: usize) -> JupiterResult<Vec<WeatherReport>> {
+        let (query, params) = WeatherReport::list_query(filter_params, before, offset, limit);
+        let query_params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params.iter()
+            .map(|p| p as &(dyn tokio_postgres::types::ToSql + Sync))
+            .collect();
+        let rows = homebrew_client().await?.query(&query, &query_params).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?;
+
+        rows.iter()
+            .map(|row| WeatherReport::from_row(row)
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to parse row: {}", e))))
+            .collect()
+    }
+}
+
+/// Averages raw reports, oldest first, into buckets of `secs` per device type the way
+/// `WeatherReport::range_query` does in SQL
+pub fn bucket(reports: &[WeatherReport], secs: i64) -> Vec<WeatherReport> {
+    const FIELDS: [&str; 11] = ["temperature", "humidity", "percipitation", "pm10", "pm25", "co2", "tvoc",
+        "wind_speed", "battery_voltage", "battery_percent", "rssi"];
+
+    let mut groups: Vec<((i64, String), Vec<&WeatherReport>)> = Vec::new();
+    for report in reports {
+        let key = (report.timestamp.div_euclid(secs) * secs, report.device_type.clone());
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, members)) => members.push(report),
+            None => groups.push((key, vec![report])),
+        }
+    }
+    groups.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    groups.into_iter().map(|((timestamp, device_type), members)| {
+        let mut bucketed = WeatherReport::new();
+        bucketed.oid = String::new();
+        bucketed.timestamp = timestamp;
+        bucketed.device_type = device_type;
+        for field in FIELDS {
+            let values: Vec<f64> = members.iter().filter_map(|r| r.value(field)).collect();
+            if let Some(slot) = bucketed.value_mut(field) {
+                *slot = (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64);
+            }
+        }
+        let directions: Vec<f64> = members.iter().filter_map(|r| r.wind_direction).map(f64::to_radians).collect();
+        if !directions.is_empty() {
+            let (sin, cos) = directions.iter().fold((0.0, 0.0), |(s, c), d| (s + d.sin(), c + d.cos()));
+            bucketed.wind_direction = Some(sin.atan2(cos).to_degrees().rem_euclid(360.0));
+        }
+        bucketed
+    }).collect()
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::Sqlite;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use super::*;
+    use crate::provider::homebrew::RANGE_LIMIT;
+    use rusqlite::{params_from_iter, Connection, OptionalExtension, Row};
+    use std::sync::Mutex;
+
+    const BUILD_STATEMENT: &str = "CREATE TABLE IF NOT EXISTS weather_reports (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            oid TEXT NOT NULL UNIQUE,
+            temperature REAL NULL,
+            humidity REAL NULL,
+            percipitation REAL NULL,
+            pm10 REAL NULL,
+            pm25 REAL NULL,
+            co2 REAL NULL,
+            tvoc REAL NULL,
+            wind_speed REAL NULL,
+            wind_direction REAL NULL,
+            battery_voltage REAL NULL,
+            battery_percent REAL NULL,
+            rssi REAL NULL,
+            device_type TEXT NULL,
+            device_id TEXT NULL,
+            timestamp INTEGER DEFAULT 0);
+        CREATE INDEX IF NOT EXISTS weather_reports_timestamp_idx ON weather_reports (timestamp DESC, id DESC);
+        CREATE INDEX IF NOT EXISTS weather_reports_device_id_idx ON weather_reports (device_id);";
+
+    // Readings left out of a report keep their stored values, like the Postgres updates
+    const SAVE_STATEMENT: &str = "INSERT INTO weather_reports (oid, temperature, humidity, percipitation, pm10, pm25, co2, tvoc,
+            wind_speed, wind_direction, battery_voltage, battery_percent, rssi, device_type, device_id, timestamp)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        ON CONFLICT (oid) DO UPDATE SET
+            temperature = COALESCE(excluded.temperature, temperature),
+            humidity = COALESCE(excluded.humidity, humidity),
+            percipitation = COALESCE(excluded.percipitation, percipitation),
+            pm10 = COALESCE(excluded.pm10, pm10),
+            pm25 = COALESCE(excluded.pm25, pm25),
+            co2 = COALESCE(excluded.co2, co2),
+            tvoc = COALESCE(excluded.tvoc, tvoc),
+            wind_speed = COALESCE(excluded.wind_speed, wind_speed),
+            wind_direction = COALESCE(excluded.wind_direction, wind_direction),
+            battery_voltage = COALESCE(excluded.battery_voltage, battery_voltage),
+            battery_percent = COALESCE(excluded.battery_percent, battery_percent),
+            rssi = COALESCE(excluded.rssi, rssi),
+            device_id = COALESCE(excluded.device_id, device_id)";
+
+    /// Reports in one SQLite file. Queries run one at a time on a blocking thread.
+    pub struct Sqlite {
+        path: String,
+        connection: Arc<Mutex<Connection>>,
+    }
+
+    fn db_error(e: rusqlite::Error) -> JupiterError {
+        JupiterError::DatabaseError(format!("SQLite query failed: {}", e))
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<WeatherReport> {
+        Ok(WeatherReport {
+            id: row.get("id")?,
+            oid: row.get("oid")?,
+            temperature: row.get("temperature")?,
+            humidity: row.get("humidity")?,
+            percipitation: row.get("percipitation")?,
+            pm10: row.get("pm10")?,
+            pm25: row.get("pm25")?,
+            co2: row.get("co2")?,
+            tvoc: row.get("tvoc")?,
+            wind_speed: row.get("wind_speed")?,
+            wind_direction: row.get("wind_direction")?,
+            battery_voltage: row.get("battery_voltage")?,
+            battery_percent: row.get("battery_percent")?,
+            rssi: row.get("rssi")?,
+            device_type: row.get::<_, Option<String>>("device_type")?.unwrap_or_default(),
+            device_id: row.get("device_id")?,
+            timestamp: row.get("timestamp")?,
+        })
+    }
+
+    impl Sqlite {
+        /// Opens or creates the database at `path` (":memory:" for one that isn't kept)
+        pub fn open(path: &str) -> JupiterResult<Self> {
+            let connection = Connection::open(path)
+                .map_err(|e| JupiterError::DatabaseError(format!("Failed to open SQLite database {}: {}", path, e)))?;
+            // Readers don't block the writer, and SD cards see fewer syncs
+            connection.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(())).map_err(db_error)?;
+            connection.execute_batch("PRAGMA synchronous = NORMAL;").map_err(db_error)?;
+            Ok(Self { path: path.to_string(), connection: Arc::new(Mutex::new(connection)) })
+        }
+
+        async fn run<T: Send + 'static>(&self, query: impl FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static) -> JupiterResult<T> {
+            let connection = self.connection.clone();
+            tokio::task::spawn_blocking(move || {
+                let connection = connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                query(&connection)
+            }).await
+                .map_err(|e| JupiterError::DatabaseError(format!("SQLite query did not finish: {}", e)))?
+                .map_err(db_error)
+        }
+
+        async fn query(&self, query: String, params: Vec<rusqlite::types::Value>) -> JupiterResult<Vec<WeatherReport>> {
+            self.run(move |connection| {
+                let mut statement = connection.prepare_cached(&query)?;
+                let reports = statement.query_map(params_from_iter(params), from_row)?.collect();
+                reports
+            }).await
+        }
+    }
+
+    #[async_trait]
+    impl WeatherStore for Sqlite {
+        fn name(&self) -> String {
+            format!("SQLite database {}", self.path)
+        }
+
+        async fn build(&self) -> JupiterResult<()> {
+            self.run(|connection| connection.execute_batch(BUILD_STATEMENT)).await
+        }
+
+        async fn save(&self, report: &WeatherReport) -> JupiterResult<()> {
+            let report = report.clone();
+            self.run(move |connection| {
+                connection.prepare_cached(SAVE_STATEMENT)?.execute(rusqlite::params![
+                    report.oid, report.temperature, report.humidity, report.percipitation, report.pm10, report.pm25,
+                    report.co2, report.tvoc, report.wind_speed, report.wind_direction, report.battery_voltage,
+                    report.battery_percent, report.rssi, report.device_type, report.device_id, report.timestamp,
+                ])
+            }).await?;
+            Ok(())
+        }
+
+        async fn latest(&self) -> JupiterResult<Option<WeatherReport>> {
+            self.run(|connection| {
+                connection.prepare_cached("SELECT * FROM weather_reports ORDER BY timestamp DESC, id DESC LIMIT 1")?
+                    .query_row([], from_row)
+                    .optional()
+            }).await
+        }
+
+        // The Postgres query runs as is: SQLite binds $1, $2.. in order of appearance
+        async fn select(&self, limit: Option<usize>, offset: Option<usize>, order_column: Option<String>, filter_params: Option<FilterParams>) -> JupiterResult<Vec<WeatherReport>> {
+            let (query, params) = WeatherReport::select_query(limit, offset, order_column, filter_params);
+            self.query(query, params.into_iter().map(rusqlite::types::Value::Text).collect()).await
+        }
+
+        // Without SQL math functions buckets are averaged here rather than in the query
+        async fn select_range(&self, start_ts: i64, end_ts: i64, device_type: Option<String>, resolution: Resolution) -> JupiterResult<Vec<WeatherReport>> {
+            let limit = match resolution.bucket_secs() {
+                None => format!(" LIMIT {}", RANGE_LIMIT + 1),
+                Some(_) => String::new(),
+            };
+            let query = format!(
+                "SELECT * FROM weather_reports WHERE timestamp >= ?1 AND timestamp < ?2 AND (?3 IS NULL OR device_type = ?3) \
+                ORDER BY timestamp ASC, id ASC{}",
+                limit,
+            );
+            let params = vec![start_ts.into(), end_ts.into(), device_type.into()];
+            let reports = self.query(query, params).await?;
+            Ok(match resolution.bucket_secs() {
+                None => reports,
+                Some(secs) => {
+                    let mut buckets = bucket(&reports, secs);
+                    buckets.truncate(RANGE_LIMIT + 1);
+                    buckets
+                },
+            })
+        }
+
+        async fn select_page(&self, start_ts: i64, end_ts: i64, device_type: Option<String>, after: Option<Cursor>, limit: usize) -> JupiterResult<Vec<WeatherReport>> {
+            let query = format!(
+                "SELECT * FROM weather_reports WHERE timestamp >= ?1 AND timestamp < ?2 AND (?3 IS NULL OR device_type = ?3) \
+                AND (?4 IS NULL OR (timestamp, id) > (?4, ?5)) ORDER BY timestamp ASC, id ASC LIMIT {}",
+                limit,
+            );
+            let (after_ts, after_id) = (after.map(|cursor| cursor.timestamp), after.map(|cursor| cursor.id).unwrap_or(0));
+            let params = vec![start_ts.into(), end_ts.into(), device_type.into(), after_ts.into(), after_id.into()];
+            self.query(query, params).await
+        }
+
+        async fn list(&self, filter_params: Option<FilterParams>, before: Option<Cursor>, offset: Option<usize>, limit: usize) -> JupiterResult<Vec<WeatherReport>> {
+            let (query, params) = WeatherReport::list_query(filter_params, before, offset, limit);
+            self.query(query, params.into_iter().map(rusqlite::types::Value::Text).collect()).await
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn report(oid: &str, device_type: &str, timestamp: i64, temperature: Option<f64>) -> WeatherReport {
+            let mut report = WeatherReport::new();
+            report.oid = oid.to_string();
+            report.device_type = device_type.to_string();
+            report.timestamp = timestamp;
+            report.temperature = temperature;
+            report
+        }
+
+        #[tokio::test]
+        async fn test_sqlite_store() {
+            let store = Sqlite::open(":memory:").unwrap();
+            store.build().await.unwrap();
+            assert!(store.latest().await.unwrap().is_none());
+
+            store.save(&report("a", "outdoor", 1_000, Some(10.0))).await.unwrap();
+            store.save(&report("b", "indoor", 1_100, Some(21.0))).await.unwrap();
+            let mut humidity = report("a", "outdoor", 1_000, None);
+            humidity.humidity = Some(80.0);
+            store.save(&humidity).await.unwrap();
+
+            let latest = store.latest().await.unwrap().unwrap();
+            assert_eq!((latest.oid.as_str(), latest.temperature), ("b", Some(21.0)));
+
+            let filter = FilterParams { oid: None, device_type: Some("outdoor".to_string()), device_id: None };
+            let outdoor = store.select(Some(10), None, Some("timestamp".to_string()), Some(filter)).await.unwrap();
+            assert_eq!(outdoor.len(), 1);
+            assert_eq!((outdoor[0].temperature, outdoor[0].humidity), (Some(10.0), Some(80.0)), "readings merged by oid");
+
+            store.save(&report("c", "outdoor", 1_200, Some(14.0))).await.unwrap();
+            let raw = store.select_range(0, 2_000, Some("outdoor".to_string()), Resolution::Raw).await.unwrap();
+            assert_eq!(raw.iter().map(|r| r.oid.as_str()).collect::<Vec<_>>(), vec!["a", "c"]);
+            let hourly = store.select_range(0, 7_200, None, Resolution::Hour).await.unwrap();
+            let buckets: Vec<(i64, &str, Option<f64>)> = hourly.iter().map(|r| (r.timestamp, r.device_type.as_str(), r.temperature)).collect();
+            assert_eq!(buckets, vec![(0, "indoor", Some(21.0)), (0, "outdoor", Some(12.0))]);
+
+            let first = store.select_page(0, 2_000, None, None, 2).await.unwrap();
+            assert_eq!(first.iter().map(|r| r.oid.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+            let last = first.last().map(Cursor::after);
+            let rest = store.select_page(0, 2_000, None, last, 2).await.unwrap();
+            assert_eq!(rest.iter().map(|r| r.oid.as_str()).collect::<Vec<_>>(), vec!["c"]);
+
+            let newest = store.list(None, None, None, 2).await.unwrap();
+            assert_eq!(newest.iter().map(|r| r.oid.as_str()).collect::<Vec<_>>(), vec!["c", "b"]);
+            let older = store.list(None, newest.last().map(Cursor::after), None, 2).await.unwrap();
+            assert_eq!(older.iter().map(|r| r.oid.as_str()).collect::<Vec<_>>(), vec!["a"]);
+            let outdoor = Some(FilterParams { oid: None, device_type: Some("outdoor".to_string()), device_id: None });
+            let skipped = store.list(outdoor, None, Some(1), 2).await.unwrap();
+            assert_eq!(skipped.iter().map(|r| r.oid.as_str()).collect::<Vec<_>>(), vec!["a"]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let reading = |timestamp: i64, wind_direction: f64| {
+            let mut report = WeatherReport::new();
+            report.device_type = "outdoor".to_string();
+            report.timestamp = timestamp;
+            report.wind_speed = Some(timestamp as f64);
+            report.wind_direction = Some(wind_direction);
+            report
+        };
+        let buckets = bucket(&[reading(0, 350.0), reading(60, 10.0), reading(300, 90.0)], 300);
+        assert_eq!(buckets.len(), 2);
+        assert_eq!((buckets[0].timestamp, buckets[0].wind_speed, buckets[0].temperature), (0, Some(30.0), None));
+        assert!(buckets[0].wind_direction.unwrap() < 1e-9 || buckets[0].wind_direction.unwrap() > 360.0 - 1e-9, "circular mean of 350 and 10");
+        assert_eq!((buckets[1].timestamp, buckets[1].wind_direction), (300, Some(90.0)));
+        assert!(buckets.iter().all(|b| b.oid.is_empty() && b.device_id.is_none()));
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use crate::db_pool::get_homebrew_pool;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::provider::homebrew::{Resolution, BUCKET_AVERAGES, RANGE_LIMIT};
+
+// Materialized hourly and daily summaries of homebrew reports for the bucketed history queries
+// when weather_reports isn't a TimescaleDB hypertable (TIMESCALE_ENABLED unset). The views
+// `weather_reports_hourly_summary` and `weather_reports_daily_summary` hold what
+// `WeatherReport::range_query` averages, per bucket and device type. A migration creates them
+// empty and the "summary_refresh" scheduler job (every 10 minutes by default) refreshes them
+// with REFRESH MATERIALIZED VIEW CONCURRENTLY, which doesn't block reads.
+//
+// Once populated, hour and day resolution ranges starting on a bucket boundary take complete
+// buckets from the view, up to its newest one, and average the rest from raw reports, so the
+// latest readings show before the next refresh. Late reports for buckets already summarized,
+// and erased or pruned ones, show after it.
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static POPULATED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryView {
+    Hourly,
+    Daily,
+}
+
+impl SummaryView {
+    pub const ALL: [SummaryView; 2] = [SummaryView::Hourly, SummaryView::Daily];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SummaryView::Hourly => "weather_reports_hourly_summary",
+            SummaryView::Daily => "weather_reports_daily_summary",
+        }
+    }
+
+    pub fn bucket_secs(&self) -> i64 {
+        match self {
+            SummaryView::Hourly => 3600,
+            SummaryView::Daily => 86_400,
+        }
+    }
+
+    /// The view with the buckets of `resolution`, if there is one
+    pub fn for_resolution(resolution: Resolution) -> Option<Self> {
+        match resolution {
+            Resolution::Hour => Some(SummaryView::Hourly),
+            Resolution::Day => Some(SummaryView::Daily),
+            Resolution::Raw | Resolution::FiveMinutes => None,
+        }
+    }
+
+    /// `WeatherReport::range_query` for this view's resolution, reading summarized buckets from
+    /// the view; $1 start (on a bucket boundary), $2 end and $3 an optional device type
+    pub fn range_query(&self) -> String {
+        format!(
+            "WITH mark AS (SELECT LEAST(COALESCE(MAX(bucket), 0), ($2 / {secs}) * {secs}) AS split FROM {view}) \
+            SELECT * FROM ( \
+                SELECT 0 AS id, '' AS oid, bucket AS timestamp, device_type, NULL::varchar AS device_id, \
+                    temperature, humidity, percipitation, pm10, pm25, co2, tvoc, wind_speed, wind_direction, \
+                    battery_voltage, battery_percent, rssi \
+                FROM {view}, mark WHERE bucket >= $1 AND bucket < mark.split AND ($3::varchar IS NULL OR device_type = $3) \
+                UNION ALL \
+                SELECT 0 AS id, '' AS oid, (timestamp / {secs}) * {secs} AS timestamp, device_type, NULL::varchar AS device_id, {averages} \
+                FROM weather_reports, mark WHERE timestamp >= GREATEST($1, mark.split) AND timestamp < $2 AND ($3::varchar IS NULL OR device_type = $3) \
+                GROUP BY 3, device_type \
+            ) buckets ORDER BY timestamp ASC, device_type ASC LIMIT {limit}",
+            secs = self.bucket_secs(),
+            view = self.name(),
+            averages = BUCKET_AVERAGES,
+            limit = RANGE_LIMIT + 1,
+        )
+    }
+}
+
+/// Both views, empty until their first refresh, each with the unique index a concurrent
+/// refresh needs
+pub fn sql_build_statement() -> String {
+    SummaryView::ALL.iter()
+        .map(|view| format!(
+            "CREATE MATERIALIZED VIEW IF NOT EXISTS public.{view} AS
+        SELECT (timestamp / {secs}) * {secs} AS bucket, device_type, {averages}
+        FROM weather_reports GROUP BY 1, 2 WITH NO DATA;
+    CREATE UNIQUE INDEX IF NOT EXISTS {view}_key ON public.{view} (bucket, device_type);",
+            view = view.name(),
+            secs = view.bucket_secs(),
+            averages = BUCKET_AVERAGES,
+        ))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn sql_drop_statement() -> String {
+    format!("DROP MATERIALIZED VIEW IF EXISTS {};", SummaryView::ALL.map(|view| view.name()).join(", "))
+}
+
+/// Whether history queries use the views, set when the homebrew tables are built
+pub fn enable(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Whether the views can be read: enabled and populated, by this instance's refresh or another's
+pub async fn usable(client: &deadpool_postgres::Client) -> bool {
+    if !enabled() {
+        return false;
+    }
+    if POPULATED.load(Ordering::SeqCst) {
+        return true;
+    }
+    let names: Vec<&str> = SummaryView::ALL.iter().map(|view| view.name()).collect();
+    match client.query_one("SELECT COUNT(*) FILTER (WHERE ispopulated) = $2 FROM pg_matviews WHERE matviewname = ANY($1)",
+        &[&names, &(names.len() as i64)]).await {
+        Ok(row) => {
+            let populated: bool = row.get(0);
+            POPULATED.store(populated, Ordering::SeqCst);
+            populated
+        },
+        Err(e) => {
+            log::error!("[summary] Failed to check the summary views: {}", e);
+            false
+        }
+    }
+}
+
+/// Refreshes both views, concurrently once they were populated
+pub async fn refresh() -> JupiterResult<()> {
+    let pool = get_homebrew_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    let started = Instant::now();
+
+    for view in SummaryView::ALL {
+        let populated: bool = client.query_opt("SELECT ispopulated FROM pg_matviews WHERE matviewname = $1", &[&view.name()]).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Query failed: {}", e)))?
+            .ok_or_else(|| JupiterError::DatabaseError(format!("{} is missing, check the homebrew migrations", view.name())))?
+            .get(0);
+        let statement = if populated { "REFRESH MATERIALIZED VIEW CONCURRENTLY" } else { "REFRESH MATERIALIZED VIEW" };
+        client.batch_execute(&format!("{} {};", statement, view.name())).await
+            .map_err(|e| JupiterError::DatabaseError(format!("Failed to refresh {}: {}", view.name(), e)))?;
+    }
+    POPULATED.store(true, Ordering::SeqCst);
+    log::info!("[summary] Refreshed the summary views in {} ms", started.elapsed().as_millis());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_views() {
+        let statement = sql_build_statement();
+        assert!(statement.contains("CREATE MATERIALIZED VIEW IF NOT EXISTS public.weather_reports_hourly_summary AS"));
+        assert!(statement.contains("SELECT (timestamp / 86400) * 86400 AS bucket, device_type, AVG(temperature) AS temperature,"));
+        assert!(statement.contains("CREATE UNIQUE INDEX IF NOT EXISTS weather_reports_daily_summary_key ON public.weather_reports_daily_summary (bucket, device_type);"));
+        assert_eq!(statement.matches("WITH NO DATA").count(), 2);
+
+        assert_eq!(SummaryView::for_resolution(Resolution::Hour), Some(SummaryView::Hourly));
+        assert_eq!(SummaryView::for_resolution(Resolution::FiveMinutes), None);
+
+        let query = SummaryView::Hourly.range_query();
+        assert!(query.contains("LEAST(COALESCE(MAX(bucket), 0), ($2 / 3600) * 3600) AS split FROM weather_reports_hourly_summary"));
+        assert!(query.contains("FROM weather_reports_hourly_summary, mark WHERE bucket >= $1 AND bucket < mark.split"));
+        assert!(query.contains("FROM weather_reports, mark WHERE timestamp >= GREATEST($1, mark.split) AND timestamp < $2"));
+        assert!(query.ends_with(&format!("ORDER BY timestamp ASC, device_type ASC LIMIT {}", RANGE_LIMIT + 1)));
+    }
+}
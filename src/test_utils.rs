@@ -62,6 +62,7 @@ pub mod db_config {
                 username: env::var(&user_var)
                     .map_err(|_| TestDbError::MissingRequiredVar(user_var.clone()))?,
                 password: env::var(&pass_var)
+                    .map(Into::into)
                     .map_err(|_| TestDbError::MissingRequiredVar(pass_var.clone()))?,
                 host: host.clone(),
                 address: host,  // For backward compatibility
@@ -77,7 +78,7 @@ pub mod db_config {
             DatabaseConfig {
                 db_name: format!("test_{}_db", prefix.to_lowercase()),
                 username: "postgres".to_string(),
-                password: "password".to_string(),
+                password: "password".into(),
                 host: "localhost".to_string(),
                 address: "localhost".to_string(),  // For backward compatibility
                 port: Some(5432),
@@ -0,0 +1,113 @@
+use std::env;
+
+// TimescaleDB for the homebrew weather_reports table (TIMESCALE_ENABLED=true), which on plain
+// Postgres gets slow to query by time after a few million rows. When the tables are built the
+// table becomes a hypertable in chunks of TIMESCALE_CHUNK_DAYS (7 by default), moving any rows
+// it already has, and chunks older than TIMESCALE_COMPRESS_AFTER_DAYS (also 7; 0 turns
+// compression off) are compressed per device type by a TimescaleDB background job.
+//
+// Needs the timescaledb extension (2.11 or later, which can update and delete compressed rows)
+// in shared_preload_libraries. Hypertables can only have unique constraints that include the
+// time column, so the id primary key and the unique oid become (id, timestamp) and
+// (oid, timestamp). Every statement is idempotent and runs at each startup.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimescaleConfig {
+    pub chunk_days: u32,
+    pub compress_after_days: u32,  // 0 doesn't compress
+}
+
+impl TimescaleConfig {
+    /// Reads the settings through `var`, None unless TIMESCALE_ENABLED; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        if !matches!(var("TIMESCALE_ENABLED").as_deref(), Some("true") | Some("1")) {
+            return Ok(None);
+        }
+        let mut problems = Vec::new();
+        let mut days = |setting: &'static str, default: u32, min: u32| match var(setting).map(|v| v.parse::<u32>()) {
+            None => default,
+            Some(Ok(days)) if days >= min => days,
+            Some(_) => {
+                problems.push((setting, format!("must be a number of days, at least {}", min)));
+                default
+            },
+        };
+        let config = Self {
+            chunk_days: days("TIMESCALE_CHUNK_DAYS", 7, 1),
+            compress_after_days: days("TIMESCALE_COMPRESS_AFTER_DAYS", 7, 0),
+        };
+        if problems.is_empty() {
+            Ok(Some(config))
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Option<Self>, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Turns weather_reports into a compressed hypertable, run after its other migrations
+    pub fn statements(&self) -> Vec<String> {
+        let mut statements = vec![
+            "CREATE EXTENSION IF NOT EXISTS timescaledb;".to_string(),
+            // Unique constraints of a hypertable have to include its time column
+            "DO $$ BEGIN
+                IF NOT EXISTS (SELECT 1 FROM timescaledb_information.hypertables WHERE hypertable_name = 'weather_reports') THEN
+                    ALTER TABLE weather_reports DROP CONSTRAINT IF EXISTS weather_reports_oid_key;
+                    ALTER TABLE weather_reports DROP CONSTRAINT IF EXISTS weather_reports_pkey;
+                    ALTER TABLE weather_reports ADD CONSTRAINT weather_reports_pkey PRIMARY KEY (id, timestamp);
+                    ALTER TABLE weather_reports ADD CONSTRAINT weather_reports_oid_key UNIQUE (oid, timestamp);
+                END IF;
+            END $$;".to_string(),
+            // Reports are timed in Unix seconds
+            "CREATE OR REPLACE FUNCTION unix_now() RETURNS BIGINT LANGUAGE SQL STABLE AS $$ SELECT EXTRACT(EPOCH FROM NOW())::BIGINT $$;".to_string(),
+            format!(
+                "SELECT create_hypertable('weather_reports', 'timestamp', chunk_time_interval => {}::BIGINT, if_not_exists => TRUE, migrate_data => TRUE);",
+                i64::from(self.chunk_days) * 86_400,
+            ),
+            "SELECT set_integer_now_func('weather_reports', 'unix_now', replace_if_exists => TRUE);".to_string(),
+        ];
+        if self.compress_after_days > 0 {
+            statements.push("ALTER TABLE weather_reports SET (timescaledb.compress, timescaledb.compress_segmentby = 'device_type', \
+                timescaledb.compress_orderby = 'timestamp DESC, id DESC');".to_string());
+            statements.push("SELECT remove_compression_policy('weather_reports', if_exists => TRUE);".to_string());
+            statements.push(format!(
+                "SELECT add_compression_policy('weather_reports', compress_after => {}::BIGINT);",
+                i64::from(self.compress_after_days) * 86_400,
+            ));
+        } else {
+            statements.push("SELECT remove_compression_policy('weather_reports', if_exists => TRUE);".to_string());
+        }
+        statements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Result<Option<TimescaleConfig>, Vec<(&'static str, String)>> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        TimescaleConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_timescale_config() {
+        assert_eq!(config(&[("TIMESCALE_CHUNK_DAYS", "1")]).unwrap(), None);
+
+        let timescale = config(&[("TIMESCALE_ENABLED", "true"), ("TIMESCALE_COMPRESS_AFTER_DAYS", "30")]).unwrap().unwrap();
+        assert_eq!(timescale, TimescaleConfig { chunk_days: 7, compress_after_days: 30 });
+        let statements = timescale.statements();
+        assert!(statements[3].contains("chunk_time_interval => 604800::BIGINT"));
+        assert!(statements.last().unwrap().contains("compress_after => 2592000::BIGINT"));
+
+        let uncompressed = TimescaleConfig { chunk_days: 1, compress_after_days: 0 }.statements();
+        assert!(!uncompressed.iter().any(|s| s.contains("add_compression_policy")));
+
+        assert_eq!(config(&[("TIMESCALE_ENABLED", "1"), ("TIMESCALE_CHUNK_DAYS", "0")]).unwrap_err(),
+            vec![("TIMESCALE_CHUNK_DAYS", "must be a number of days, at least 1".to_string())]);
+    }
+}
@@ -0,0 +1,273 @@
+use once_cell::sync::Lazy;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::provider::common::Alert;
+use crate::provider::homebrew::WeatherReport;
+
+// Polling trigger feeds for IFTTT and Zapier (GET /api/triggers/*). Both services poll an
+// endpoint, remember the ids it returned and fire once for each id they haven't seen, so a feed
+// is a newest-first list of recent events whose ids stay the same from one poll to the next.
+// IFTTT expects {"data": [...]} with each event's id and unix timestamp under "meta"; Zapier
+// takes a bare array with a top-level id (?format=zapier).
+
+pub const DEFAULT_LIMIT: usize = 50;
+pub const MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ifttt,
+    Zapier,
+}
+
+impl Format {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "ifttt" => Some(Format::Ifttt),
+            "zapier" => Some(Format::Zapier),
+            _ => None,
+        }
+    }
+}
+
+/// `format` and `limit` query parameters
+pub fn options(params: &HashMap<String, String>) -> Result<(Format, usize), String> {
+    let format = match params.get("format") {
+        Some(value) => Format::parse(value).ok_or_else(|| format!("unknown format '{}', expected ifttt or zapier", value))?,
+        None => Format::Ifttt,
+    };
+    let limit = match params.get("limit") {
+        Some(value) => match value.parse::<usize>() {
+            Ok(limit) if (1..=MAX_LIMIT).contains(&limit) => limit,
+            _ => return Err(format!("limit must be between 1 and {}", MAX_LIMIT)),
+        },
+        None => DEFAULT_LIMIT,
+    };
+    Ok((format, limit))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Above => "above",
+            Direction::Below => "below",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "above" => Some(Direction::Above),
+            "below" => Some(Direction::Below),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub id: String,
+    pub timestamp: i64,
+    pub fields: Map<String, Value>,
+}
+
+/// The newest `limit` of `events` in the shape `format` expects
+pub fn render(mut events: Vec<Event>, format: Format, limit: usize) -> Value {
+    events.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.id.cmp(&a.id)));
+    events.truncate(limit);
+    let items = events.into_iter().map(|event| {
+        let mut item = event.fields;
+        match format {
+            Format::Ifttt => {
+                item.insert("meta".to_string(), json!({"id": event.id, "timestamp": event.timestamp}));
+            },
+            Format::Zapier => {
+                item.insert("id".to_string(), json!(event.id));
+                item.insert("timestamp".to_string(), json!(event.timestamp));
+            },
+        }
+        Value::Object(item)
+    }).collect::<Vec<_>>();
+    match format {
+        Format::Ifttt => json!({ "data": items }),
+        Format::Zapier => Value::Array(items),
+    }
+}
+
+/// Identifies an alert by its title and regions, which unlike its start time stay the same from
+/// one fetch to the next
+pub fn alert_key(alert: &Alert) -> String {
+    let mut regions = alert.regions.clone();
+    regions.sort();
+    Sha256::digest(format!("{}\n{}", alert.title, regions.join(",")).as_bytes()).iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// When each active alert was first seen. Providers don't all give alerts a usable start time
+/// (homebrew alerts are stamped with the time of the request), so an alert is identified by
+/// `alert_key` and dated by the poll that first returned it. It keeps its id while it stays
+/// active; if it clears and comes back it's a new event. The log is in memory, so alerts active
+/// across a restart come back as new events.
+#[derive(Debug, Default)]
+pub struct AlertLog {
+    first_seen: HashMap<String, i64>,
+}
+
+impl AlertLog {
+    /// Events for the currently active `alerts`, forgetting alerts no longer active
+    pub fn observe(&mut self, alerts: &[Alert], now: i64) -> Vec<Event> {
+        let mut active = HashMap::new();
+        let events = alerts.iter().filter_map(|alert| {
+            let key = alert_key(alert);
+            if active.contains_key(&key) {
+                return None;
+            }
+            let first_seen = *self.first_seen.get(&key).unwrap_or(&now);
+            active.insert(key.clone(), first_seen);
+            let mut fields = Map::new();
+            fields.insert("title".to_string(), json!(alert.title));
+            fields.insert("description".to_string(), json!(alert.description));
+            fields.insert("severity".to_string(), json!(alert.severity));
+            fields.insert("regions".to_string(), json!(alert.regions.join(", ")));
+            fields.insert("start".to_string(), json!(alert.start));
+            fields.insert("end".to_string(), json!(alert.end));
+            Some(Event { id: format!("{}-{}", key, first_seen), timestamp: first_seen, fields })
+        }).collect();
+        self.first_seen = active;
+        events
+    }
+}
+
+static ALERT_LOG: Lazy<Mutex<AlertLog>> = Lazy::new(|| Mutex::new(AlertLog::default()));
+
+/// `AlertLog::observe` on the server's log
+pub fn alert_events(alerts: &[Alert], now: i64) -> Vec<Event> {
+    match ALERT_LOG.lock() {
+        Ok(mut log) => log.observe(alerts, now),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Readings of `field` crossing `threshold`, in `direction` or either way. Each device's readings
+/// (by device id, or device type for unregistered senders) are compared in time order; a reading
+/// above the threshold after one at or below it crosses above, and the reverse crosses below.
+pub fn crossings(reports: &[WeatherReport], field: &str, threshold: f64, direction: Option<Direction>) -> Vec<Event> {
+    let mut readings: Vec<&WeatherReport> = reports.iter().collect();
+    readings.sort_by_key(|r| (r.timestamp, r.id));
+
+    let mut previous: HashMap<String, f64> = HashMap::new();
+    let mut events = Vec::new();
+    for report in readings {
+        let Some(value) = report.value(field) else { continue };
+        let device = report.device_id.clone().unwrap_or_else(|| report.device_type.clone());
+        if let Some(before) = previous.insert(device.clone(), value) {
+            let crossed = match (before > threshold, value > threshold) {
+                (false, true) => Direction::Above,
+                (true, false) => Direction::Below,
+                _ => continue,
+            };
+            if direction.is_some_and(|d| d != crossed) {
+                continue;
+            }
+            let mut fields = Map::new();
+            fields.insert("device".to_string(), json!(device));
+            fields.insert("field".to_string(), json!(field));
+            fields.insert("direction".to_string(), json!(crossed.as_str()));
+            fields.insert("threshold".to_string(), json!(threshold));
+            fields.insert("value".to_string(), json!(value));
+            fields.insert("previous".to_string(), json!(before));
+            events.push(Event {
+                id: format!("{}:{}:{}:{}:{}", device, field, crossed.as_str(), threshold, report.timestamp),
+                timestamp: report.timestamp,
+                fields,
+            });
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::common::AlertSeverity;
+
+    fn alert(title: &str, start: &str) -> Alert {
+        Alert {
+            title: title.to_string(),
+            description: String::new(),
+            severity: AlertSeverity::Moderate,
+            start: start.to_string(),
+            end: None,
+            regions: vec!["10001".to_string()],
+        }
+    }
+
+    fn reading(device: &str, timestamp: i64, temperature: f64) -> WeatherReport {
+        let mut report = WeatherReport::new();
+        report.device_id = Some(device.to_string());
+        report.timestamp = timestamp;
+        report.temperature = Some(temperature);
+        report
+    }
+
+    #[test]
+    fn test_alert_ids_stable_while_active() {
+        let mut log = AlertLog::default();
+        let first = log.observe(&[alert("Heat Advisory", "t1")], 100);
+        // Same alert restamped by its provider, plus a new one
+        let second = log.observe(&[alert("Heat Advisory", "t2"), alert("Wind Advisory", "t2")], 200);
+        assert_eq!(second[0].id, first[0].id);
+        assert_eq!(second[0].timestamp, 100);
+        assert_eq!(second[1].timestamp, 200);
+
+        // Cleared and reissued later is a new event
+        log.observe(&[], 300);
+        let reissued = log.observe(&[alert("Heat Advisory", "t3")], 400);
+        assert_ne!(reissued[0].id, first[0].id);
+
+        let feed = render(second, Format::Ifttt, 10);
+        assert_eq!(feed["data"][0]["title"], "Wind Advisory");
+        assert_eq!(feed["data"][0]["meta"]["timestamp"], 200);
+        let feed = render(reissued, Format::Zapier, 10);
+        assert_eq!(feed[0]["timestamp"], 400);
+        assert!(feed[0]["id"].is_string());
+    }
+
+    #[test]
+    fn test_threshold_crossings() {
+        let reports = vec![
+            reading("garage", 10, 1.0),
+            reading("porch", 15, -3.0),
+            reading("garage", 20, -0.5),
+            reading("garage", 30, 0.0),
+            reading("porch", 35, -2.0),
+            reading("garage", 40, 2.0),
+        ];
+        let events = crossings(&reports, "temperature", 0.0, None);
+        let directions: Vec<_> = events.iter().map(|e| (e.fields["device"].as_str(), e.fields["direction"].as_str(), e.timestamp)).collect();
+        assert_eq!(directions, vec![(Some("garage"), Some("below"), 20), (Some("garage"), Some("above"), 40)]);
+
+        let below = crossings(&reports, "temperature", 0.0, Some(Direction::Below));
+        assert_eq!(below.len(), 1);
+        assert_eq!(below[0].id, crossings(&reports, "temperature", 0.0, None)[0].id);
+
+        let feed = render(events, Format::Ifttt, 1);
+        assert_eq!(feed["data"].as_array().map(Vec::len), Some(1));
+        assert_eq!(feed["data"][0]["direction"], "above");
+    }
+
+    #[test]
+    fn test_options() {
+        let params = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>();
+        assert_eq!(options(&params(&[])), Ok((Format::Ifttt, DEFAULT_LIMIT)));
+        assert_eq!(options(&params(&[("format", "Zapier"), ("limit", "5")])), Ok((Format::Zapier, 5)));
+        assert!(options(&params(&[("limit", "0")])).is_err());
+        assert!(options(&params(&[("format", "rss")])).is_err());
+    }
+}
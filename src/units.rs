@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+// Typed quantities for the common provider models, so a reading can't be used in the wrong
+// unit: providers, ingest bridges and analyses convert explicitly where values come in or go out.
+// Each serializes as a plain number in the unit the JSON API uses, °C, km/h and hPa:
+//   Speed::from_meters_per_second(5.0).kmh()     // 18.0
+//   Temperature::from_fahrenheit(50.0).celsius() // 10.0
+//   {"temperature": 10.0, "wind_speed": 18.0, "pressure": 1013.2}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Temperature(f64);
+
+impl Temperature {
+    pub fn from_celsius(celsius: f64) -> Self {
+        Self(celsius)
+    }
+
+    pub fn from_fahrenheit(fahrenheit: f64) -> Self {
+        Self((fahrenheit - 32.0) * 5.0 / 9.0)
+    }
+
+    pub fn celsius(self) -> f64 {
+        self.0
+    }
+
+    pub fn fahrenheit(self) -> f64 {
+        self.0 * 9.0 / 5.0 + 32.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Speed(f64);
+
+const KMH_PER_METER_PER_SECOND: f64 = 3.6;
+const KMH_PER_MPH: f64 = 1.609_344;
+const KMH_PER_KNOT: f64 = 1.852;
+
+impl Speed {
+    pub fn from_kmh(kmh: f64) -> Self {
+        Self(kmh)
+    }
+
+    pub fn from_meters_per_second(meters_per_second: f64) -> Self {
+        Self(meters_per_second * KMH_PER_METER_PER_SECOND)
+    }
+
+    pub fn from_mph(mph: f64) -> Self {
+        Self(mph * KMH_PER_MPH)
+    }
+
+    pub fn from_knots(knots: f64) -> Self {
+        Self(knots * KMH_PER_KNOT)
+    }
+
+    pub fn kmh(self) -> f64 {
+        self.0
+    }
+
+    pub fn meters_per_second(self) -> f64 {
+        self.0 / KMH_PER_METER_PER_SECOND
+    }
+
+    pub fn mph(self) -> f64 {
+        self.0 / KMH_PER_MPH
+    }
+
+    pub fn knots(self) -> f64 {
+        self.0 / KMH_PER_KNOT
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Pressure(f64);
+
+const HPA_PER_INHG: f64 = 33.863_886_67;
+
+impl Pressure {
+    pub fn from_hpa(hpa: f64) -> Self {
+        Self(hpa)
+    }
+
+    pub fn from_pascals(pascals: f64) -> Self {
+        Self(pascals / 100.0)
+    }
+
+    pub fn from_inhg(inhg: f64) -> Self {
+        Self(inhg * HPA_PER_INHG)
+    }
+
+    pub fn hpa(self) -> f64 {
+        self.0
+    }
+
+    pub fn inhg(self) -> f64 {
+        self.0 / HPA_PER_INHG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert!(close(Temperature::from_fahrenheit(50.0).celsius(), 10.0));
+        assert!(close(Temperature::from_celsius(-40.0).fahrenheit(), -40.0));
+        assert!(close(Speed::from_meters_per_second(5.0).kmh(), 18.0));
+        assert!(close(Speed::from_mph(10.0).meters_per_second(), 4.4704));
+        assert!(close(Speed::from_knots(10.0).kmh(), 18.52));
+        assert!(close(Pressure::from_pascals(101_325.0).hpa(), 1013.25));
+        assert!(close(Pressure::from_hpa(1013.25).inhg(), 29.921_255));
+        assert!(Temperature::from_celsius(1.0) < Temperature::from_fahrenheit(34.0));
+    }
+
+    #[test]
+    fn test_serialized_in_api_units() {
+        let speed = Speed::from_meters_per_second(5.0);
+        assert_eq!(serde_json::to_string(&speed).unwrap(), "18.0");
+        let temperature: Temperature = serde_json::from_str("21.5").unwrap();
+        assert_eq!(temperature.celsius(), 21.5);
+    }
+}
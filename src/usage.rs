@@ -0,0 +1,333 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::db_pool::get_homebrew_pool;
+use crate::error::{JupiterError, Result as JupiterResult};
+use crate::utils::time::safe_timestamp_with_fallback;
+
+// Storage used per device, listed at GET /api/admin/storage. Rows of weather_reports and of the
+// hourly and daily rollups are counted per device and device type; each table's size on disk
+// (pg_total_relation_size, so with its indexes) is split between them by row count, which makes
+// the bytes an estimate. Reports posted without a device are listed without a device_id.
+//
+// STORAGE_QUOTA_MB caps what each device may store, as `device=megabytes` pairs separated by
+// commas with `*` for every other device, e.g. `*=100,garden=500`. Once a device is over its
+// quota, its reports are rejected with 507 Insufficient Storage until retention or an erasure
+// frees space. The usage behind that check is counted again every STORAGE_USAGE_REFRESH_SECS
+// (900 by default) and grows by the table's average row size with every report in between.
+
+const MB: u64 = 1024 * 1024;
+
+/// Tables whose rows are attributed to devices
+const TABLES: [&str; 3] = ["weather_reports", "weather_reports_hourly", "weather_reports_daily"];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageQuotas {
+    pub default: Option<u64>,              // Bytes per device
+    pub devices: BTreeMap<String, u64>,
+    pub refresh: Duration,
+}
+
+impl StorageQuotas {
+    /// Quotas like "*=100,garden=500" (STORAGE_QUOTA_MB), in megabytes
+    pub fn parse(quotas: &str) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        for entry in quotas.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let quota = entry.split_once('=').and_then(|(device, megabytes)| {
+                let megabytes = megabytes.trim().trim_end_matches("MB").trim().parse::<u64>().ok().filter(|mb| *mb > 0)?;
+                Some((device.trim(), megabytes.saturating_mul(MB)))
+            });
+            match quota {
+                Some(("*", bytes)) => parsed.default = Some(bytes),
+                Some((device, bytes)) if !device.is_empty() => {
+                    parsed.devices.insert(device.to_string(), bytes);
+                },
+                _ => return Err(format!("expected device=megabytes (e.g. garden=500), got '{}'", entry)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Reads the settings through `var`; problems are (setting, message)
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Vec<(&'static str, String)>> {
+        let var = |name: &str| var(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let mut problems = Vec::new();
+        let mut quotas = match var("STORAGE_QUOTA_MB").map(|quotas| Self::parse(&quotas)) {
+            None => Self::default(),
+            Some(Ok(quotas)) => quotas,
+            Some(Err(message)) => {
+                problems.push(("STORAGE_QUOTA_MB", message));
+                Self::default()
+            },
+        };
+        quotas.refresh = match var("STORAGE_USAGE_REFRESH_SECS").map(|v| v.parse::<u64>()) {
+            None => Duration::from_secs(900),
+            Some(Ok(secs)) if secs > 0 => Duration::from_secs(secs),
+            Some(_) => {
+                problems.push(("STORAGE_USAGE_REFRESH_SECS", "must be a whole number above 0".to_string()));
+                Duration::from_secs(900)
+            },
+        };
+        if problems.is_empty() {
+            Ok(quotas)
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn from_env() -> Result<Self, Vec<(&'static str, String)>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.default.is_some() || !self.devices.is_empty()
+    }
+
+    /// Bytes `device_id` may store, if limited
+    pub fn quota(&self, device_id: &str) -> Option<u64> {
+        self.devices.get(device_id).copied().or(self.default)
+    }
+}
+
+pub static STORAGE_QUOTAS: Lazy<StorageQuotas> = Lazy::new(|| {
+    StorageQuotas::from_env().unwrap_or_else(|problems| {
+        for (setting, message) in problems {
+            log::error!("Invalid {}, storage quotas disabled: {}", setting, message);
+        }
+        StorageQuotas::default()
+    })
+});
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableUsage {
+    pub table: String,
+    pub rows: i64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceUsage {
+    pub device_id: Option<String>,
+    pub reports: i64,
+    pub rollups: i64,
+    pub estimated_bytes: u64,
+    pub quota_bytes: Option<u64>,
+    pub over_quota: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceTypeUsage {
+    pub device_type: String,
+    pub reports: i64,
+    pub rollups: i64,
+    pub estimated_bytes: u64,
+}
+
+/// GET /api/admin/storage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub generated_at: i64,
+    pub total_bytes: u64,
+    pub tables: Vec<TableUsage>,
+    pub devices: Vec<DeviceUsage>,          // Largest first
+    pub device_types: Vec<DeviceTypeUsage>,
+}
+
+/// Rows of one table for one device and device type
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowCount {
+    pub table: String,
+    pub device_id: Option<String>,
+    pub device_type: String,
+    pub rows: i64,
+}
+
+impl StorageUsage {
+    /// Splits each table's `bytes` between the devices and device types by their rows
+    pub fn estimate(tables: Vec<TableUsage>, counts: &[RowCount], quotas: &StorageQuotas, generated_at: i64) -> Self {
+        let row_bytes: HashMap<&str, f64> = tables.iter()
+            .map(|table| (table.table.as_str(), if table.rows > 0 { table.bytes as f64 / table.rows as f64 } else { 0.0 }))
+            .collect();
+        let mut devices: BTreeMap<Option<String>, (i64, i64, f64)> = BTreeMap::new();
+        let mut device_types: BTreeMap<String, (i64, i64, f64)> = BTreeMap::new();
+        for count in counts {
+            let bytes = count.rows as f64 * row_bytes.get(count.table.as_str()).copied().unwrap_or(0.0);
+            let is_report = count.table == TABLES[0];
+            for usage in [devices.entry(count.device_id.clone()).or_default(), device_types.entry(count.device_type.clone()).or_default()] {
+                if is_report { usage.0 += count.rows } else { usage.1 += count.rows }
+                usage.2 += bytes;
+            }
+        }
+
+        let mut devices: Vec<DeviceUsage> = devices.into_iter()
+            .map(|(device_id, (reports, rollups, bytes))| {
+                let quota_bytes = device_id.as_deref().and_then(|id| quotas.quota(id));
+                let estimated_bytes = bytes.round() as u64;
+                DeviceUsage {
+                    over_quota: quota_bytes.is_some_and(|quota| estimated_bytes >= quota),
+                    device_id, reports, rollups, estimated_bytes, quota_bytes,
+                }
+            })
+            .collect();
+        devices.sort_by(|a, b| b.estimated_bytes.cmp(&a.estimated_bytes).then_with(|| a.device_id.cmp(&b.device_id)));
+
+        Self {
+            generated_at,
+            total_bytes: tables.iter().map(|table| table.bytes).sum(),
+            device_types: device_types.into_iter()
+                .map(|(device_type, (reports, rollups, bytes))| DeviceTypeUsage { device_type, reports, rollups, estimated_bytes: bytes.round() as u64 })
+                .collect(),
+            tables,
+            devices,
+        }
+    }
+}
+
+/// Each device's estimated bytes as of the last count, for the quota check
+#[derive(Debug, Default)]
+struct Tally {
+    counted: Option<Instant>,
+    report_bytes: u64,             // Average weather_reports row, indexes included
+    devices: HashMap<String, u64>,
+}
+
+static TALLY: Lazy<Mutex<Tally>> = Lazy::new(|| Mutex::new(Tally::default()));
+// One count at a time when several saves find the tally stale
+static COUNTING: Lazy<AsyncMutex<()>> = Lazy::new(|| AsyncMutex::new(()));
+
+/// Counts the rows of every table per device and device type, and refreshes the quota tally
+pub async fn report() -> JupiterResult<StorageUsage> {
+    let pool = get_homebrew_pool()
+        .ok_or_else(|| JupiterError::DatabaseError("Database pool not initialized".to_string()))?;
+    let client = pool.get_connection_with_retry(3).await
+        .map_err(|e| JupiterError::DatabaseError(format!("Failed to get database connection: {}", e)))?;
+    let db_error = |e: tokio_postgres::Error| JupiterError::DatabaseError(format!("Storage usage query failed: {}", e));
+
+    let mut tables = Vec::new();
+    let mut counts = Vec::new();
+    for table in TABLES {
+        // The rollup tables are only built alongside Postgres reports
+        let bytes: Option<i64> = client.query_one("SELECT pg_total_relation_size(to_regclass($1))", &[&format!("public.{}", table)]).await
+            .map_err(db_error)?
+            .get(0);
+        let Some(bytes) = bytes else { continue };
+        let rows = client.query(&format!("SELECT NULLIF(device_id, ''), device_type, COUNT(*) FROM {} GROUP BY 1, 2", table), &[]).await
+            .map_err(db_error)?;
+        let mut total = 0;
+        for row in rows {
+            let count = RowCount { table: table.to_string(), device_id: row.get(0), device_type: row.get(1), rows: row.get(2) };
+            total += count.rows;
+            counts.push(count);
+        }
+        tables.push(TableUsage { table: table.to_string(), rows: total, bytes: bytes.max(0) as u64 });
+    }
+
+    let usage = StorageUsage::estimate(tables, &counts, &STORAGE_QUOTAS, safe_timestamp_with_fallback());
+    if let Ok(mut tally) = TALLY.lock() {
+        let reports = usage.tables.iter().find(|table| table.table == TABLES[0]);
+        *tally = Tally {
+            counted: Some(Instant::now()),
+            report_bytes: reports.filter(|reports| reports.rows > 0).map(|reports| reports.bytes / reports.rows as u64).unwrap_or(0),
+            devices: usage.devices.iter()
+                .filter_map(|device| Some((device.device_id.clone()?, device.estimated_bytes)))
+                .collect(),
+        };
+    }
+    for device in usage.devices.iter().filter(|device| device.over_quota) {
+        log::warn!("[usage] Device {} stores about {} MB, over its {} MB quota",
+            device.device_id.as_deref().unwrap_or_default(), device.estimated_bytes / MB, device.quota_bytes.unwrap_or_default() / MB);
+    }
+    Ok(usage)
+}
+
+/// Fails with `QuotaExceeded` when `device_id` is over its STORAGE_QUOTA_MB, counting usage
+/// again first if the last count is older than STORAGE_USAGE_REFRESH_SECS
+pub async fn check(device_id: Option<&str>) -> JupiterResult<()> {
+    let quotas = &*STORAGE_QUOTAS;
+    let Some((device_id, quota)) = device_id.and_then(|id| Some((id, quotas.quota(id)?))) else {
+        return Ok(());
+    };
+    let stale = |tally: &Tally| tally.counted.is_none_or(|counted| counted.elapsed() >= quotas.refresh);
+    if TALLY.lock().map(|tally| stale(&tally)).unwrap_or(false) {
+        let _counting = COUNTING.lock().await;
+        if TALLY.lock().map(|tally| stale(&tally)).unwrap_or(false) {
+            // Reports keep flowing when usage can't be counted
+            if let Err(e) = report().await {
+                log::error!("[usage] Failed to count storage usage: {}", e);
+                return Ok(());
+            }
+        }
+    }
+
+    let used = TALLY.lock().ok().and_then(|tally| tally.devices.get(device_id).copied()).unwrap_or(0);
+    if used >= quota {
+        return Err(JupiterError::QuotaExceeded(format!("device {} stores about {} MB of its {} MB quota", device_id, used / MB, quota / MB)));
+    }
+    Ok(())
+}
+
+/// Adds a report saved by `device_id` to its tally until the next count
+pub fn record(device_id: Option<&str>) {
+    let Some(device_id) = device_id.filter(|id| STORAGE_QUOTAS.quota(id).is_some()) else { return };
+    if let Ok(mut tally) = TALLY.lock() {
+        let report_bytes = tally.report_bytes;
+        *tally.devices.entry(device_id.to_string()).or_default() += report_bytes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_quotas() {
+        let quotas = StorageQuotas::parse("*=100, garden=500MB").unwrap();
+        assert_eq!(quotas.quota("garden"), Some(500 * MB));
+        assert_eq!(quotas.quota("shed"), Some(100 * MB));
+        assert!(quotas.enabled());
+        assert!(StorageQuotas::parse("garden=0").is_err());
+        assert!(StorageQuotas::parse("garden").is_err());
+        assert!(!StorageQuotas::parse("").unwrap().enabled());
+
+        let vars: HashMap<&str, &str> = [("STORAGE_QUOTA_MB", "garden=lots"), ("STORAGE_USAGE_REFRESH_SECS", "0")].into();
+        let problems = StorageQuotas::from_vars(|name| vars.get(name).map(|v| v.to_string())).unwrap_err();
+        assert_eq!(problems.iter().map(|(setting, _)| *setting).collect::<Vec<_>>(), ["STORAGE_QUOTA_MB", "STORAGE_USAGE_REFRESH_SECS"]);
+    }
+
+    #[test]
+    fn test_storage_estimate() {
+        let count = |table: &str, device_id: Option<&str>, device_type: &str, rows: i64| RowCount {
+            table: table.to_string(), device_id: device_id.map(str::to_string), device_type: device_type.to_string(), rows,
+        };
+        let tables = vec![
+            TableUsage { table: "weather_reports".to_string(), rows: 100, bytes: 100 * MB },
+            TableUsage { table: "weather_reports_hourly".to_string(), rows: 10, bytes: MB },
+        ];
+        let counts = [
+            count("weather_reports", Some("garden"), "outdoor", 60),
+            count("weather_reports", Some("shed"), "outdoor", 30),
+            count("weather_reports", None, "indoor", 10),
+            count("weather_reports_hourly", Some("garden"), "outdoor", 10),
+        ];
+        let quotas = StorageQuotas::parse("*=50,shed=100").unwrap();
+        let usage = StorageUsage::estimate(tables, &counts, &quotas, 0);
+
+        assert_eq!(usage.total_bytes, 101 * MB);
+        let garden = &usage.devices[0];
+        assert_eq!((garden.device_id.as_deref(), garden.reports, garden.rollups), (Some("garden"), 60, 10));
+        assert_eq!(garden.estimated_bytes, 61 * MB);
+        assert!(garden.over_quota);
+        let shed = &usage.devices[1];
+        assert_eq!((shed.estimated_bytes, shed.quota_bytes, shed.over_quota), (30 * MB, Some(100 * MB), false));
+        // Reports posted without a device have no quota
+        assert_eq!((usage.devices[2].device_id.as_deref(), usage.devices[2].quota_bytes), (None, None));
+
+        let outdoor = usage.device_types.iter().find(|usage| usage.device_type == "outdoor").unwrap();
+        assert_eq!((outdoor.reports, outdoor.rollups, outdoor.estimated_bytes), (90, 10, 91 * MB));
+    }
+}
@@ -27,6 +27,85 @@ impl From<SystemTimeError> for TimeError {
     }
 }
 
+/// (year, month, day) of a day count since 1970-01-01
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Day count since 1970-01-01 of a (year, month, day); the inverse of `civil_from_days`
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Unix timestamp of an RFC 3339 date-time such as "2024-05-01T12:51:00+00:00" or
+/// "2024-05-01T12:51:00.5Z"; fractional seconds are dropped
+pub fn parse_rfc3339(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let number = |range: std::ops::Range<usize>| value.get(range).and_then(|v| v.parse::<i64>().ok());
+    if value.len() < 20 || value.get(4..5) != Some("-") || value.get(7..8) != Some("-")
+        || !matches!(value.get(10..11), Some("T") | Some("t") | Some(" ")) {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = &value[19..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        rest = fraction.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.get(0..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let (hours, minutes) = rest[1..].split_once(':')?;
+            sign * (hours.parse::<i64>().ok()? * 3600 + minutes.parse::<i64>().ok()? * 60)
+        },
+    };
+
+    Some(days_from_civil(year, month as u32, day as u32) * 86_400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+/// RFC 3339 UTC date-time of a unix timestamp, e.g. "2023-11-14T22:13:20Z"
+pub fn format_rfc3339(timestamp: i64) -> String {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86_400));
+    let seconds_of_day = timestamp.rem_euclid(86_400);
+    format!("{}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day,
+        seconds_of_day / 3600, seconds_of_day % 3600 / 60, seconds_of_day % 60)
+}
+
+/// RFC 5322 date of a unix timestamp as used in email headers, e.g. "Tue, 14 Nov 2023 22:13:20 +0000"
+pub fn format_rfc2822(timestamp: i64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let days = timestamp.div_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let seconds_of_day = timestamp.rem_euclid(86_400);
+    format!("{}, {} {} {} {:02}:{:02}:{:02} +0000", WEEKDAYS[days.rem_euclid(7) as usize], day, MONTHS[month as usize - 1], year,
+        seconds_of_day / 3600, seconds_of_day % 3600 / 60, seconds_of_day % 60)
+}
+
 pub fn safe_timestamp() -> Result<i64, TimeError> {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -172,6 +251,19 @@ mod tests {
         assert!(fallback > 0);
     }
 
+    #[test]
+    fn test_parse_rfc3339() {
+        assert_eq!(parse_rfc3339("2023-11-14T22:13:20Z"), Some(1700000000));
+        assert_eq!(parse_rfc3339("2023-11-14T17:13:20.25-05:00"), Some(1700000000));
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+        assert_eq!(parse_rfc3339("2023-11-14"), None);
+        assert_eq!(parse_rfc3339("2023-13-14T22:13:20Z"), None);
+        assert_eq!(format_rfc3339(1700000000), "2023-11-14T22:13:20Z");
+        assert_eq!(format_rfc2822(1700000000), "Tue, 14 Nov 2023 22:13:20 +0000");
+        assert_eq!(format_rfc2822(0), "Thu, 1 Jan 1970 00:00:00 +0000");
+        assert_eq!(parse_rfc3339(&format_rfc3339(951_782_400)), Some(951_782_400));
+    }
+
     #[test]
     fn test_safe_timestamp_millis() {
         let result = safe_timestamp_millis();
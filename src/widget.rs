@@ -0,0 +1,133 @@
+use crate::analysis::conditions::ConditionCode;
+use crate::locale::Locale;
+use crate::provider::common::Weather;
+
+// Small current-conditions card rendered as SVG (GET /api/widget.svg, and /public/widget.svg in
+// public mode) for embedding in forum signatures, README files and dashboards. Both need no API
+// key; /api/widget.svg is rate limited per IP to WIDGET_RATE_LIMIT requests per minute.
+
+const WIDTH: u32 = 240;
+const HEIGHT: u32 = 96;
+
+fn escape_xml(text: &str) -> String {
+    text.chars().map(|c| match c {
+        '&' => "&amp;".to_string(),
+        '<' => "&lt;".to_string(),
+        '>' => "&gt;".to_string(),
+        '"' => "&quot;".to_string(),
+        '\'' => "&apos;".to_string(),
+        c => c.to_string(),
+    }).collect()
+}
+
+fn icon(condition: ConditionCode) -> &'static str {
+    match condition {
+        ConditionCode::Thunderstorm => "⛈",
+        ConditionCode::Snow | ConditionCode::Sleet => "❄",
+        ConditionCode::Rain | ConditionCode::Drizzle => "🌧",
+        ConditionCode::Fog => "🌫",
+        ConditionCode::PartlyCloudy => "⛅",
+        ConditionCode::Cloudy => "☁",
+        ConditionCode::Windy => "🌬",
+        ConditionCode::Clear | ConditionCode::Unknown => "☀",
+    }
+}
+
+/// EPA colour band for an AQI value
+fn aqi_color(aqi: u32) -> &'static str {
+    match aqi {
+        0..=50 => "#00e400",
+        51..=100 => "#ffff00",
+        101..=150 => "#ff7e00",
+        151..=200 => "#ff0000",
+        201..=300 => "#8f3f97",
+        _ => "#7e0023",
+    }
+}
+
+/// Short description shown on the card: the first provider's text for combined readings
+fn card_description(description: &str) -> String {
+    let description = description.trim_start_matches("Combined: ");
+    let first = description.split(" | ").next().unwrap_or(description);
+    let text = first.split_once(": ").map(|(_, text)| text).unwrap_or(first).trim();
+    if text.chars().count() > 28 {
+        format!("{}…", text.chars().take(27).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn render_svg(weather: &Weather, aqi: Option<u32>, locale: &Locale) -> String {
+    let description = card_description(&weather.description);
+    let aqi_badge = match aqi {
+        Some(aqi) => format!(
+            r##"<rect x="160" y="60" width="68" height="24" rx="12" fill="{}"/><text x="194" y="77" font-size="12" text-anchor="middle" fill="#111">AQI {}</text>"##,
+            aqi_color(aqi), aqi
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="Helvetica, Arial, sans-serif"><title>{location}: {temperature}{unit}, {description} ({date})</title><rect width="{w}" height="{h}" rx="10" fill="#1e293b"/><text x="16" y="56" font-size="36">{icon}</text><text x="64" y="52" font-size="32" font-weight="bold" fill="#f8fafc">{temperature}{unit}</text><text x="16" y="80" font-size="12" fill="#cbd5e1">{description}</text><text x="228" y="24" font-size="11" text-anchor="end" fill="#94a3b8">{location}</text>{aqi_badge}</svg>"##,
+        w = WIDTH,
+        h = HEIGHT,
+        icon = icon(weather.condition),
+        temperature = locale.number(locale.temperature(weather.temperature.celsius()), 0),
+        unit = locale.temperature_unit(),
+        date = locale.format_date(weather.timestamp),
+        description = escape_xml(&description),
+        location = escape_xml(&weather.location.name),
+        aqi_badge = aqi_badge,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::common::Location;
+    use crate::units::Temperature;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_render_svg() {
+        let weather = Weather {
+            temperature: Temperature::from_celsius(18.4),
+            feels_like: None,
+            humidity: None,
+            pressure: None,
+            wind_speed: None,
+            wind_direction: None,
+            description: "Combined: AccuWeather: Light rain & wind".to_string(),
+            condition: ConditionCode::Rain,
+            icon: None,
+            precipitation: None,
+            visibility: None,
+            uv_index: None,
+            road_condition: None,
+            comfort: None,
+            provider: "Combo".to_string(),
+            location: Location {
+                latitude: 0.0,
+                longitude: 0.0,
+                name: "<Home>".to_string(),
+                country: None,
+                region: None,
+                postal_code: None,
+            },
+            timestamp: 0,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
+        };
+
+        let svg = render_svg(&weather, Some(72), &Locale::default());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(">18°C</text>"));
+        assert!(svg.contains("Light rain &amp; wind"));
+        assert!(svg.contains("&lt;Home&gt;"));
+        assert!(svg.contains("🌧"));
+        assert!(svg.contains("AQI 72"));
+        assert!(svg.contains("(1970-01-01)</title>"));
+        assert!(!render_svg(&weather, None, &Locale::default()).contains("AQI"));
+        assert!(render_svg(&weather, None, &Locale::parse("en-US").unwrap()).contains(">65°F</text>"));
+    }
+}
@@ -1,5 +1,6 @@
 use jupiter::auth::{constant_time_eq, validate_auth_header, RateLimiter};
-use rouille::{Request, Response};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use std::net::SocketAddr;
 use std::time::Duration;
 use std::thread;
 
@@ -79,6 +80,34 @@ fn test_rate_limiter_concurrent_clients() {
     }
 }
 
+#[test]
+fn test_validate_auth_header() {
+    let remote_addr: SocketAddr = "192.0.2.10:51000".parse().unwrap();
+    let limiter = RateLimiter::new(2, 60);
+    let mut headers = HeaderMap::new();
+
+    let missing = validate_auth_header(&headers, &remote_addr, "valid_api_key_12345", None).unwrap_err();
+    assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(missing.headers()[header::WWW_AUTHENTICATE], "Bearer");
+
+    headers.insert(header::AUTHORIZATION, HeaderValue::from_static("wrong_api_key"));
+    let wrong = validate_auth_header(&headers, &remote_addr, "valid_api_key_12345", None).unwrap_err();
+    assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+
+    headers.insert(header::AUTHORIZATION, HeaderValue::from_static("valid_api_key_12345"));
+    assert!(validate_auth_header(&headers, &remote_addr, "valid_api_key_12345", Some(&limiter)).is_ok());
+    assert!(validate_auth_header(&headers, &remote_addr, "valid_api_key_12345", Some(&limiter)).is_ok());
+    let limited = validate_auth_header(&headers, &remote_addr, "valid_api_key_12345", Some(&limiter)).unwrap_err();
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(limited.headers()[header::RETRY_AFTER], "60");
+
+    // Each rejection is counted by reason for GET /metrics
+    let exposition = jupiter::metrics::render();
+    for reason in ["missing_key", "invalid_key", "rate_limited"] {
+        assert!(exposition.contains(&format!("jupiter_auth_failures_total{{reason=\"{}\"}}", reason)), "{}", exposition);
+    }
+}
+
 // Mock authentication scenarios tests
 mod mock_auth_tests {
     use super::*;
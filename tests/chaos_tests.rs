@@ -0,0 +1,151 @@
+// Chaos tests: providers fail and slow down through the fault injection layer, and the combo
+// provider must keep answering from healthy providers or its cache instead of erroring.
+// The fault settings are process-wide, so the tests take turns.
+//
+//     cargo test --features fault-injection --test chaos_tests
+
+#![cfg(feature = "fault-injection")]
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use jupiter::analysis::conditions::ConditionCode;
+use jupiter::fault::{self, FaultConfig};
+use jupiter::provider::combo_enhanced::ComboProvider;
+use jupiter::provider::common::{Alert, Forecast, Location, Weather, WeatherError, WeatherFeature, WeatherProvider};
+use jupiter::units::Temperature;
+
+static FAULTS: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+struct FixedProvider {
+    name: &'static str,
+    temperature: f64,
+}
+
+fn location() -> Location {
+    Location {
+        latitude: 47.61,
+        longitude: -122.33,
+        name: "Seattle".to_string(),
+        country: Some("US".to_string()),
+        region: None,
+        postal_code: None,
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for FixedProvider {
+    async fn get_current_weather(&self, _location: &str) -> Result<Weather, WeatherError> {
+        Ok(Weather {
+            temperature: Temperature::from_celsius(self.temperature),
+            feels_like: None,
+            humidity: Some(50.0),
+            pressure: None,
+            wind_speed: None,
+            wind_direction: None,
+            description: "Clear".to_string(),
+            condition: ConditionCode::Clear,
+            icon: None,
+            precipitation: None,
+            visibility: None,
+            uv_index: None,
+            road_condition: None,
+            comfort: None,
+            provider: self.name.to_string(),
+            location: location(),
+            timestamp: 1_700_000_000,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
+        })
+    }
+
+    async fn get_forecast(&self, _location: &str, _days: u8) -> Result<Forecast, WeatherError> {
+        Ok(Forecast { location: location(), provider: self.name.to_string(), daily: Vec::new(), hourly: None })
+    }
+
+    async fn get_alerts(&self, _location: &str) -> Result<Vec<Alert>, WeatherError> {
+        Ok(Vec::new())
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn supports_feature(&self, feature: WeatherFeature) -> bool {
+        matches!(feature, WeatherFeature::CurrentWeather | WeatherFeature::Forecast)
+    }
+}
+
+fn combo(providers: &[(&'static str, f64)]) -> ComboProvider {
+    providers.iter().fold(ComboProvider::new().set_cache_duration(1), |combo, &(name, temperature)| {
+        combo.add_provider(Box::new(FixedProvider { name, temperature }), 1.0)
+    })
+}
+
+#[tokio::test]
+async fn broken_providers_are_skipped() {
+    let _turn = FAULTS.lock().await;
+    fault::configure(FaultConfig {
+        provider_failure_rate: 1.0,
+        providers: vec!["Broken".to_string()],
+        ..FaultConfig::default()
+    });
+
+    let combo = combo(&[("Broken", 30.0), ("Healthy", 10.0)]);
+    let weather = combo.get_current_weather("47.61,-122.33").await;
+    fault::configure(FaultConfig::default());
+
+    assert_eq!(weather.expect("healthy provider answers").temperature.celsius(), 10.0);
+}
+
+#[tokio::test]
+async fn stale_cache_is_served_when_every_provider_fails() {
+    let _turn = FAULTS.lock().await;
+    fault::configure(FaultConfig::default());
+
+    let combo = combo(&[("Primary", 12.0), ("Secondary", 14.0)]);
+    let fresh = combo.get_current_weather("47.61,-122.33").await.expect("fresh conditions");
+    combo.get_forecast("47.61,-122.33", 1).await.expect("fresh forecast");
+
+    // Past the one second cache lifetime, with every provider down
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    fault::configure(FaultConfig { provider_failure_rate: 1.0, ..FaultConfig::default() });
+    let stale = combo.get_current_weather("47.61,-122.33").await;
+    let forecast = combo.get_forecast("47.61,-122.33", 1).await;
+    let freshness = combo.cache_freshness("current:47.61,-122.33").await;
+    fault::configure(FaultConfig::default());
+
+    assert_eq!(stale.expect("stale conditions instead of an error").temperature, fresh.temperature);
+    assert!(forecast.is_ok());
+    // Not advertised as cacheable downstream
+    assert!(freshness.is_none());
+
+    // Nothing cached for this location: the failure is reported
+    fault::configure(FaultConfig { provider_failure_rate: 1.0, ..FaultConfig::default() });
+    let uncached = combo.get_current_weather("40.71,-74.01").await;
+    fault::configure(FaultConfig::default());
+    assert!(uncached.is_err());
+}
+
+#[tokio::test]
+async fn latency_is_injected() {
+    let _turn = FAULTS.lock().await;
+    fault::configure(FaultConfig {
+        provider_latency_ms: 200,
+        pool_failure_rate: 1.0,
+        ..FaultConfig::default()
+    });
+
+    let started = Instant::now();
+    let weather = combo(&[("Slow", 20.0)]).get_current_weather("47.61,-122.33").await;
+    let elapsed = started.elapsed();
+    let pool = fault::pool_acquire("combo").await;
+    fault::configure(FaultConfig::default());
+
+    assert!(weather.is_ok());
+    assert!(elapsed >= Duration::from_millis(200), "took {:?}", elapsed);
+    assert!(pool.is_err());
+}
@@ -0,0 +1,480 @@
+// Contract tests for the public response bodies: each schema's field paths and JSON types
+// (see `schema::shape`) must match tests/contracts/<name>.v<version>.txt. A failure means a
+// serde model changed shape; bump the version in src/schema.rs for renames, removals and type
+// changes, then regenerate the golden files with UPDATE_CONTRACTS=1 cargo test --test contract_tests.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use jupiter::alert_rules::{AlertRule, Comparator};
+use jupiter::analysis::aqi::{air_quality_index, Pollutant};
+use jupiter::analysis::comfort::Comfort;
+use jupiter::analysis::conditions::ConditionCode;
+use jupiter::analysis::history::{self, HistoryOptions};
+use jupiter::analysis::hvac::{HvacHint, HvacHintKind, HvacHints};
+use jupiter::analysis::nowcast::Nowcast;
+use jupiter::analysis::road::estimate_road_condition;
+use jupiter::analysis::solar::{IrradianceSource, SolarConfig, SolarForecast, SolarHour};
+use jupiter::analysis::uv::{SkinType, UvGuidance};
+use jupiter::auth::{ApiKey, IssuedKey, Scope};
+use jupiter::devices::{Device, DeviceConfig, DeviceKey, DeviceSettings, DeviceState, DeviceStatus, Rtl433Match};
+use jupiter::groups::{FieldSummary, Group, GroupClimate, GroupRule};
+use jupiter::provider::air_quality::AirQualityReport;
+use jupiter::provider::aviation::{CloudLayer, TafChange, TafPeriod, TafReport, TafTime, TafWind};
+use jupiter::provider::combo::{self, CachedWeatherData};
+use jupiter::provider::combo_enhanced::{Capabilities, FeatureSupport};
+use jupiter::provider::common::{
+    Alert, AlertSeverity, DailyForecast, Location, MarineForecast, MinutePrecipitation, MinutelyForecast, PollenLevel, PollenReport, PollenType,
+    PrecipitationType, SourceContribution, TidePrediction, TideType, Weather, WeatherFeature,
+};
+use jupiter::provider::homebrew::{self, ReportPage, ReportRange, Resolution, WeatherReport};
+use jupiter::report_chain::ChainVerification;
+use jupiter::schema::{shape, Schema};
+use jupiter::triggers::{self, AlertLog, Format};
+use jupiter::usage::{DeviceTypeUsage, DeviceUsage, StorageUsage, TableUsage};
+use jupiter::units::{Pressure, Speed, Temperature};
+
+fn json<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("sample serializes")
+}
+
+fn location() -> Location {
+    Location {
+        latitude: 47.61,
+        longitude: -122.33,
+        name: "Seattle".to_string(),
+        country: Some("US".to_string()),
+        region: Some("WA".to_string()),
+        postal_code: Some("98101".to_string()),
+    }
+}
+
+fn device() -> Device {
+    Device {
+        id: "ws-2".to_string(),
+        name: "Greenhouse sensor".to_string(),
+        device_type: "greenhouse".to_string(),
+        rtl433: Some(Rtl433Match { model: "Acurite-Tower".to_string(), channel: Some("A".to_string()), id: Some("1234".to_string()) }),
+        mac: Some("A4:C1:38:00:00:01".to_string()),
+        room: Some("greenhouse".to_string()),
+        low_battery_voltage: Some(2.2),
+        latitude: Some(47.62),
+        longitude: Some(-122.35),
+        config: DeviceSettings {
+            reporting_interval_secs: Some(300),
+            calibration: BTreeMap::from([("humidity".to_string(), 2.0)]),
+            units: Some("metric".to_string()),
+        },
+        state: DeviceState::Active,
+    }
+}
+
+fn weather_report() -> WeatherReport {
+    let mut report = WeatherReport::new();
+    report.temperature = Some(21.5);
+    report.humidity = Some(45.0);
+    report.percipitation = Some(0.2);
+    report.pm10 = Some(12.0);
+    report.pm25 = Some(6.5);
+    report.co2 = Some(640.0);
+    report.tvoc = Some(120.0);
+    report.wind_speed = Some(3.4);
+    report.wind_direction = Some(225.0);
+    report.battery_voltage = Some(3.9);
+    report.battery_percent = Some(82.0);
+    report.rssi = Some(-67.0);
+    report.device_type = "indoor".to_string();
+    report.device_id = Some("ws-1".to_string());
+    report
+}
+
+fn group() -> Group {
+    Group {
+        id: "upstairs".to_string(),
+        name: "Upstairs".to_string(),
+        parent: Some("house".to_string()),
+        devices: vec!["bedroom".to_string()],
+        placements: vec!["indoor".to_string()],
+        rules: vec![GroupRule { field: "co2".to_string(), above: Some(1200.0), below: None, severity: AlertSeverity::Moderate }],
+    }
+}
+
+fn alert_rule() -> AlertRule {
+    AlertRule {
+        id: "greenhouse-frost".to_string(),
+        metric: "temperature".to_string(),
+        comparator: Comparator::Below,
+        value: 2.0,
+        severity: AlertSeverity::Severe,
+        duration_secs: 600,
+        device: Some("greenhouse".to_string()),
+        title: Some("Frost in the greenhouse".to_string()),
+        enabled: true,
+    }
+}
+
+fn api_key() -> ApiKey {
+    ApiKey {
+        id: "k3x9q2m8v1c4".to_string(),
+        name: "Garden station".to_string(),
+        scope: Scope::Ingest,
+        device_id: Some("garden".to_string()),
+        created_at: 1_700_000_000,
+        expires_at: Some(1_731_536_000),
+        revoked_at: Some(1_700_086_400),
+        key_hash: String::new(),
+    }
+}
+
+fn weather() -> Weather {
+    Weather {
+        temperature: Temperature::from_celsius(18.2),
+        feels_like: Some(Temperature::from_celsius(17.5)),
+        humidity: Some(62.0),
+        pressure: Some(Pressure::from_hpa(1014.2)),
+        wind_speed: Some(Speed::from_kmh(11.0)),
+        wind_direction: Some(200.0),
+        description: "Partly sunny".to_string(),
+        condition: ConditionCode::PartlyCloudy,
+        icon: Some("3".to_string()),
+        precipitation: Some(0.0),
+        visibility: Some(16.1),
+        uv_index: Some(4.0),
+        road_condition: None,
+        comfort: Some(Comfort {
+            score: 85,
+            level: "Very comfortable".to_string(),
+            apparent_temperature: 17.5,
+            clothing: "Light jacket".to_string(),
+        }),
+        provider: "AccuWeather".to_string(),
+        location: location(),
+        timestamp: 1_700_000_000,
+        sources: Vec::new(),
+        spread: BTreeMap::new(),
+    }
+}
+
+fn taf_time(day: u8, hour: u8) -> Option<TafTime> {
+    Some(TafTime { day, hour, minute: 0 })
+}
+
+/// A representative body for each schema, with every optional field populated so its type is
+/// part of the contract
+fn sample(schema: Schema) -> Value {
+    match schema {
+        Schema::CachedConditions => json(&CachedWeatherData {
+            id: 1,
+            oid: "a1b2c3d4e5f6g7h".to_string(),
+            accuweather: Some("{}".to_string()),
+            homebrew: Some("{}".to_string()),
+            openweathermap: Some("{}".to_string()),
+            timestamp: 1_700_000_000,
+        }),
+        Schema::WeatherReport => json(&weather_report()),
+        Schema::PublicCurrent => json(&combo::public_current(&weather())),
+        Schema::PublicDaily => json(&combo::public_daily(&DailyForecast {
+            date: "2024-05-01".to_string(),
+            temperature_min: Temperature::from_celsius(9.5),
+            temperature_max: Temperature::from_celsius(19.0),
+            humidity: Some(60.0),
+            precipitation_probability: Some(20.0),
+            precipitation_amount: Some(0.5),
+            wind_speed: Some(Speed::from_kmh(12.0)),
+            wind_direction: Some(250.0),
+            description: "Showers".to_string(),
+            condition: ConditionCode::Rain,
+            icon: Some("12".to_string()),
+            sunrise: Some("05:51".to_string()),
+            sunset: Some("20:17".to_string()),
+            solar_irradiance: None,
+            day: None,
+            night: None,
+        })),
+        Schema::Pollen => {
+            let mut report = PollenReport::new(location(), "AccuWeather".to_string(), "2024-05-01".to_string());
+            for pollen_type in PollenType::all() {
+                report.set_level(pollen_type, PollenLevel { value: 12.0, category: "Moderate".to_string(), category_value: 2.0 });
+            }
+            json(&report)
+        },
+        Schema::UvGuidance => json(&UvGuidance {
+            uv_index: 6.0,
+            effective_uv_index: 6.0,
+            category: "High".to_string(),
+            skin_type: SkinType::II,
+            hour: Some(13),
+            safe_exposure_minutes: Some(27),
+            recommended_spf: Some(30),
+            recommendations: vec!["Seek shade during midday hours".to_string()],
+        }),
+        Schema::HvacHints => json(&HvacHints {
+            indoor_temperature: Some(25.5),
+            indoor_dew_point: Some(13.2),
+            hints: vec![HvacHint {
+                kind: HvacHintKind::FreeCooling,
+                start: Some("22:00".to_string()),
+                end: Some("06:00".to_string()),
+                message: "Open windows from 22:00 to 06:00".to_string(),
+            }],
+        }),
+        Schema::SolarForecast => json(&SolarForecast {
+            system: SolarConfig { peak_kw: 6.4, tilt: 30.0, azimuth: 180.0, losses_percent: 14.0 },
+            total_kwh: 52.3,
+            peak_power_kw: 4.81,
+            hours: vec![SolarHour {
+                time: "2024-06-20T20:00:00Z".to_string(),
+                sun_elevation: 65.8,
+                cloud_cover: Some(20.0),
+                irradiance: 912.0,
+                power_kw: 4.81,
+                source: IrradianceSource::CloudCover,
+            }],
+        }),
+        Schema::Marine => json(&MarineForecast {
+            location: location(),
+            provider: "AccuWeather".to_string(),
+            marine_zone: Some("PZZ135".to_string()),
+            tide_station: Some("9447130".to_string()),
+            buoy_station: Some("46087".to_string()),
+            wave_height: Some(1.2),
+            wave_period: Some(8.0),
+            swell_height: Some(0.9),
+            swell_period: Some(11.0),
+            swell_direction: Some(270.0),
+            water_temperature: Some(11.4),
+            tides: vec![TidePrediction { time: "2024-05-01 04:12".to_string(), height: 1.4, tide_type: TideType::High }],
+            timestamp: 1_700_000_000,
+        }),
+        Schema::Aviation => json(&TafReport {
+            station: "KSEA".to_string(),
+            issued: Some(TafTime { day: 1, hour: 11, minute: 30 }),
+            valid_from: taf_time(1, 12),
+            valid_to: taf_time(2, 18),
+            amended: false,
+            periods: vec![TafPeriod {
+                change: TafChange::From,
+                start: taf_time(1, 18),
+                end: taf_time(2, 18),
+                wind: Some(TafWind { direction: Some(200), speed_kt: 15, gust_kt: Some(25) }),
+                visibility_sm: Some(3.0),
+                ceiling_ft: Some(1500),
+                clouds: vec![CloudLayer { cover: "BKN".to_string(), base_ft: 1500, cloud_type: Some("CB".to_string()) }],
+                conditions: vec!["-SHRA".to_string()],
+                raw: "FM011800 20015G25KT 3SM -SHRA BKN015CB".to_string(),
+            }],
+            raw: "TAF KSEA 011130Z 0112/0218 FM011800 20015G25KT 3SM -SHRA BKN015CB".to_string(),
+        }),
+        Schema::Ventilation => {
+            // Two hours of occupancy, then a decay at one air change per hour
+            let mut samples: Vec<(i64, f64)> = (0..=8).map(|i| (i * 900, 500.0 + i as f64 * 100.0)).collect();
+            samples.extend((1..=8).map(|i| (7200 + i * 900, 420.0 + 880.0 * (-(i as f64) * 0.25).exp())));
+            json(&homebrew::ventilation_body(24, &[("bedroom".to_string(), vec![samples])]))
+        },
+        Schema::DeviceStatus => json(&vec![DeviceStatus {
+            id: "ws-1".to_string(),
+            name: "Bedroom sensor".to_string(),
+            device_type: "indoor".to_string(),
+            room: Some("bedroom".to_string()),
+            last_seen: Some(1_700_000_000),
+            online: true,
+            battery_voltage: Some(3.9),
+            battery_percent: Some(82.0),
+            rssi: Some(-67.0),
+            low_battery: false,
+            weak_signal: false,
+        }]),
+        Schema::DeviceConfig => json(&DeviceConfig {
+            id: "ws-1".to_string(),
+            reporting_interval_secs: 60,
+            calibration: BTreeMap::from([("temperature".to_string(), -0.5)]),
+            units: "metric".to_string(),
+        }),
+        Schema::Devices => json(&vec![device()]),
+        Schema::Device => json(&device()),
+        Schema::DeviceKey => json(&DeviceKey { device: device(), api_key: "k".repeat(40) }),
+        Schema::History => json(&history::series(
+            "temperature",
+            Some("outdoor".to_string()),
+            &[(0, 10.0), (300, 11.0), (900, 13.0), (3000, 18.0)],
+            HistoryOptions { start: 0, end: 3000, step_secs: 300, max_gap_secs: 900, interpolate: true },
+        )),
+        Schema::ReportRange => json(&ReportRange {
+            start: 1_700_000_000,
+            end: 1_700_086_400,
+            device_type: Some("outdoor".to_string()),
+            resolution: Resolution::Raw,
+            truncated: true,
+            next_cursor: Some("00000000655e74000000002a".to_string()),
+            reports: vec![weather_report()],
+        }),
+        Schema::ReportPage => json(&ReportPage {
+            reports: vec![weather_report()],
+            next_cursor: Some("00000000655e74000000002a".to_string()),
+        }),
+        Schema::AuditVerification => json(&ChainVerification {
+            valid: false,
+            entries: 3,
+            erased: 1,
+            head: Some("9f86d081884c7d65".to_string()),
+            problems: vec!["entry 2: hash mismatch".to_string()],
+        }),
+        Schema::Rooms => {
+            let reading = |device: &str, temperature: f64| {
+                let mut report = weather_report();
+                report.device_id = Some(device.to_string());
+                report.temperature = Some(temperature);
+                report
+            };
+            json(&homebrew::rooms_body(&[
+                ("bedroom".to_string(), reading("ws-1", 18.0)),
+                ("living room".to_string(), reading("ws-2", 22.0)),
+            ]))
+        },
+        Schema::AirQuality => json(&AirQualityReport {
+            location: "98101".to_string(),
+            providers: vec!["Homebrew".to_string(), "OpenWeather".to_string()],
+            index: air_quality_index(&[(Pollutant::Pm25, 12.5), (Pollutant::O3, 68.7), (Pollutant::Co, 201.9)]),
+            timestamp: 1_700_000_000,
+        }),
+        Schema::CurrentWeather => json(&Weather {
+            provider: "Combo".to_string(),
+            road_condition: Some(estimate_road_condition(Temperature::from_celsius(18.2), Some(62.0), Some(0.0), None)),
+            sources: vec![SourceContribution {
+                provider: "AccuWeather".to_string(),
+                local: false,
+                weight: 1.0,
+                distance_km: Some(3.2),
+                age_secs: 42,
+                values: [("temperature".to_string(), 18.2), ("humidity".to_string(), 62.0)].into_iter().collect(),
+                rejected: vec!["humidity".to_string()],
+            }],
+            spread: [("temperature".to_string(), 0.4)].into_iter().collect(),
+            ..weather()
+        }),
+        Schema::Nowcast => json(&Nowcast {
+            precipitating: false,
+            precipitation_type: Some(PrecipitationType::Rain),
+            starts_in_minutes: Some(12),
+            stops_in_minutes: Some(40),
+            peak_intensity: 3.2,
+            summary: "Light rain starting in 12 minutes".to_string(),
+            forecast: MinutelyForecast {
+                location: location(),
+                provider: "Tomorrow.io".to_string(),
+                minutes: vec![MinutePrecipitation {
+                    timestamp: 1_700_000_000,
+                    intensity: 0.8,
+                    probability: Some(65.0),
+                    precipitation_type: Some(PrecipitationType::Rain),
+                }],
+                timestamp: 1_700_000_000,
+            },
+        }),
+        Schema::Capabilities => json(&Capabilities {
+            providers: vec!["AccuWeather".to_string(), "OpenWeather".to_string()],
+            features: WeatherFeature::ALL.iter().map(|feature| (feature.name().to_string(), FeatureSupport {
+                supported: true,
+                providers: vec!["AccuWeather".to_string()],
+            })).collect(),
+        }),
+        Schema::Groups => json(&vec![group()]),
+        Schema::Group => json(&group()),
+        Schema::GroupClimate => json(&GroupClimate {
+            group: "upstairs".to_string(),
+            name: "Upstairs".to_string(),
+            devices: vec!["bedroom".to_string(), "office".to_string()],
+            reporting: vec!["bedroom".to_string()],
+            fields: [("temperature".to_string(), FieldSummary { mean: 21.4, min: 20.9, max: 21.9, devices: 2 })].into_iter().collect(),
+            timestamp: Some(1_700_000_000),
+        }),
+        Schema::TemperatureTrigger => {
+            let before = WeatherReport { temperature: Some(19.5), timestamp: 1_700_000_000, ..weather_report() };
+            let after = WeatherReport { temperature: Some(20.5), timestamp: 1_700_000_600, ..weather_report() };
+            triggers::render(triggers::crossings(&[before, after], "temperature", 20.0, None), Format::Ifttt, triggers::DEFAULT_LIMIT)
+        },
+        Schema::AlertTrigger => {
+            let alert = Alert {
+                title: "Heat Advisory".to_string(),
+                description: "Heat index up to 41°C".to_string(),
+                severity: AlertSeverity::Moderate,
+                start: "2024-07-01T12:00:00Z".to_string(),
+                end: Some("2024-07-01T20:00:00Z".to_string()),
+                regions: vec!["10001".to_string()],
+            };
+            triggers::render(AlertLog::default().observe(&[alert], 1_700_000_000), Format::Ifttt, triggers::DEFAULT_LIMIT)
+        },
+        Schema::AlertRules => json(&vec![alert_rule()]),
+        Schema::AlertRule => json(&alert_rule()),
+        Schema::StorageUsage => json(&StorageUsage {
+            generated_at: 1_700_000_000,
+            total_bytes: 52_428_800,
+            tables: vec![TableUsage { table: "weather_reports".to_string(), rows: 120_000, bytes: 41_943_040 }],
+            devices: vec![DeviceUsage {
+                device_id: Some("garden".to_string()),
+                reports: 80_000,
+                rollups: 2_400,
+                estimated_bytes: 28_311_552,
+                quota_bytes: Some(104_857_600),
+                over_quota: false,
+            }],
+            device_types: vec![DeviceTypeUsage { device_type: "outdoor".to_string(), reports: 80_000, rollups: 2_400, estimated_bytes: 28_311_552 }],
+        }),
+        Schema::ApiKeys => json(&vec![api_key()]),
+        Schema::IssuedKey => json(&IssuedKey {
+            key: ApiKey { revoked_at: None, ..api_key() },
+            api_key: "4fJ9sQ2mX7pL0aZ8cV3bN6tR1yU5wE2hK9dGq7Ws".to_string(),
+        }),
+    }
+}
+
+fn golden_path(schema: Schema) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("contracts")
+        .join(format!("{}.v{}.txt", schema.name(), schema.version()))
+}
+
+#[test]
+fn test_response_contracts() {
+    let update = std::env::var("UPDATE_CONTRACTS").is_ok();
+    let mut failures = Vec::new();
+
+    for schema in Schema::ALL {
+        let actual = shape(&sample(schema));
+        let path = golden_path(schema);
+        if update {
+            std::fs::write(&path, actual.join("\n") + "\n").expect("golden file written");
+            continue;
+        }
+
+        let expected: Vec<String> = match std::fs::read_to_string(&path) {
+            Ok(text) => text.lines().map(str::to_string).collect(),
+            Err(_) => {
+                failures.push(format!("{}: missing {}", schema.header_value(), path.display()));
+                continue;
+            },
+        };
+        if schema.contract().lines().ne(expected.iter().map(String::as_str)) {
+            failures.push(format!("{}: Schema::contract() doesn't include {}", schema.header_value(), path.display()));
+        }
+        if actual != expected {
+            let removed: Vec<&String> = expected.iter().filter(|line| !actual.contains(line)).collect();
+            let added: Vec<&String> = actual.iter().filter(|line| !expected.contains(line)).collect();
+            failures.push(format!("{}: removed {:?}, added {:?}", schema.header_value(), removed, added));
+        }
+    }
+
+    assert!(failures.is_empty(), "response contracts changed:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_schema_names_are_unique() {
+    let mut names: Vec<&str> = Schema::ALL.iter().map(|s| s.name()).collect();
+    names.sort();
+    names.dedup();
+    assert_eq!(names.len(), Schema::ALL.len());
+}
@@ -0,0 +1,389 @@
+// End-to-end tests against a real Postgres in a throwaway Docker container: the schema is
+// migrated by the servers' own init, both servers run in-process on free ports, and requests go
+// through the typed client. The database pools are process-wide singletons bound to the runtime
+// that created them, so the whole suite shares one container and one runtime and runs as a
+// single test. Needs a Docker daemon:
+//
+//     cargo test --test e2e_tests -- --ignored --nocapture
+
+use std::net::TcpListener;
+use std::time::Duration;
+
+use testcontainers::clients::Cli;
+use testcontainers::Container;
+use testcontainers_modules::postgres::Postgres;
+use tokio_postgres::NoTls;
+
+use jupiter::auth::{NewApiKey, Scope};
+use jupiter::client::{JupiterClient, NewWeatherReport};
+use jupiter::deploy::Candidate;
+use jupiter::provider::common::WeatherError;
+use jupiter::db_pool::get_homebrew_pool;
+use jupiter::devices::{Device, DeviceRegistry};
+use jupiter::provider::{combo, homebrew};
+use jupiter::provider::homebrew::Resolution;
+use jupiter::rollup;
+
+const API_KEY: &str = "e2e-test-key";
+
+struct Harness {
+    _postgres: Container<'static, Postgres>,
+    address: String,
+    homebrew: homebrew::Config,
+    combo: combo::Config,
+    homebrew_client: JupiterClient,
+    combo_client: JupiterClient,
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .expect("free local port")
+}
+
+/// The servers rate limit by remote address including the port, so every request gets its
+/// own connection instead of reusing a pooled one
+fn client(port: u16) -> JupiterClient {
+    keyed_client(port, API_KEY)
+}
+
+fn keyed_client(port: u16, apikey: &str) -> JupiterClient {
+    let http = reqwest::Client::builder()
+        .pool_max_idle_per_host(0)
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("http client");
+    JupiterClient::new(&format!("http://127.0.0.1:{}", port), apikey)
+        .expect("client")
+        .with_http_client(http)
+}
+
+async fn connect(address: &str) -> tokio_postgres::Client {
+    let (host, port) = jupiter::db_pool::split_address(address);
+    let (client, connection) = tokio_postgres::connect(
+        &format!("host={} port={} user=postgres password=postgres dbname=postgres", host, port),
+        NoTls,
+    ).await.expect("direct database connection");
+    tokio::spawn(connection);
+    client
+}
+
+/// Tables as an older release created them, before the telemetry and device_id migrations
+const LEGACY_SCHEMA: &str = "CREATE TABLE public.weather_reports (
+        id serial NOT NULL,
+        oid varchar NOT NULL UNIQUE,
+        temperature DOUBLE PRECISION NULL,
+        humidity DOUBLE PRECISION NULL,
+        percipitation DOUBLE PRECISION NULL,
+        pm10 DOUBLE PRECISION NULL,
+        pm25 DOUBLE PRECISION NULL,
+        co2 DOUBLE PRECISION NULL,
+        tvoc DOUBLE PRECISION NULL,
+        device_type VARCHAR NULL,
+        timestamp BIGINT DEFAULT 0,
+        CONSTRAINT weather_reports_pkey PRIMARY KEY (id));
+    INSERT INTO weather_reports (oid, temperature, device_type, timestamp)
+        VALUES ('legacyreport000', 12.5, 'outdoor', 1600000000);";
+
+impl Harness {
+    async fn start() -> Harness {
+        let docker: &'static Cli = Box::leak(Box::new(Cli::default()));
+        let postgres = docker.run(Postgres::default());
+        let address = format!("127.0.0.1:{}", postgres.get_host_port_ipv4(5432));
+
+        connect(&address).await.batch_execute(LEGACY_SCHEMA).await.expect("legacy schema");
+
+        let registry: DeviceRegistry = serde_json::from_value(serde_json::json!({
+            "devices": [{
+                "id": "garden",
+                "name": "Garden sensor",
+                "device_type": "outdoor",
+                "rtl433": { "model": "Acurite-Tower", "channel": "A", "id": 1234 }
+            }]
+        })).expect("device registry");
+
+        let mut homebrew = homebrew::Config::new(API_KEY, homebrew::PostgresServer {
+            db_name: "postgres".to_string(),
+            username: "postgres".to_string(),
+            password: "postgres".into(),
+            address: address.clone(),
+        }, free_port()).with_devices(registry);
+        homebrew.init().await.expect("homebrew server");
+
+        let mut combo = combo::Config::new(None, Some(homebrew.clone()), API_KEY, Some(3600), combo::PostgresServer {
+            db_name: "postgres".to_string(),
+            username: "postgres".to_string(),
+            password: "postgres".into(),
+            address: address.clone(),
+        }, free_port(), "12345".to_string());
+        combo.init().await.expect("combo server");
+
+        // Servers bind on their own threads
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        Harness {
+            homebrew_client: client(homebrew.port),
+            combo_client: client(combo.port),
+            _postgres: postgres,
+            address,
+            homebrew,
+            combo,
+        }
+    }
+
+    async fn stop(mut self) {
+        self.combo.shutdown().await;
+        self.homebrew.shutdown().await;
+    }
+}
+
+async fn migrations_upgrade_legacy_tables(harness: &Harness) {
+    let db = connect(&harness.address).await;
+    let columns: Vec<String> = db.query(
+        "SELECT column_name::text FROM information_schema.columns WHERE table_name = 'weather_reports'", &[],
+    ).await.expect("columns").iter().map(|row| row.get(0)).collect();
+    for column in ["wind_speed", "wind_direction", "device_id", "battery_voltage", "battery_percent", "rssi"] {
+        assert!(columns.iter().any(|c| c == column), "migration did not add {}", column);
+    }
+
+    let legacy: f64 = db.query_one("SELECT temperature FROM weather_reports WHERE oid = 'legacyreport000'", &[])
+        .await.expect("legacy row kept").get(0);
+    assert_eq!(legacy, 12.5);
+
+    // Running the migrations again on an up-to-date schema is a no-op
+    harness.homebrew.build_tables().await.expect("idempotent migrations");
+}
+
+async fn ingest_then_query(harness: &Harness) {
+    let posted = harness.homebrew_client.post_report(&NewWeatherReport {
+        temperature: Some(21.5),
+        humidity: Some(40.0),
+        co2: Some(650.0),
+        device_type: "indoor".to_string(),
+        ..Default::default()
+    }).await.expect("POST report");
+    assert_eq!(posted.temperature, Some(21.5));
+
+    let latest = harness.homebrew_client.latest_report().await.expect("latest report");
+    assert_eq!(latest.oid, posted.oid);
+    assert_eq!(harness.homebrew_client.value("co2", Some("indoor")).await.expect("co2 value"), 650.0);
+
+    // rtl_433 events are attributed to registered sensors; unknown ones are counted and dropped
+    let summary = harness.homebrew_client.ingest_rtl433(concat!(
+        r#"{"time":"2024-05-01 12:00:00","model":"Acurite-Tower","channel":"A","id":1234,"temperature_C":14.2,"humidity":71}"#, "\n",
+        r#"{"time":"2024-05-01 12:00:05","model":"Acurite-Tower","channel":"B","id":999,"temperature_C":30.0}"#,
+    )).await.expect("rtl_433 ingest");
+    assert_eq!(summary.stored.len(), 1);
+    assert_eq!(summary.ignored, 1);
+    assert_eq!(harness.homebrew_client.value("temperature", Some("garden")).await.expect("garden temperature"), 14.2);
+
+    let statuses = harness.homebrew_client.device_statuses().await.expect("device status");
+    assert!(statuses.iter().any(|status| status.id == "garden" && status.online));
+}
+
+async fn device_registry(harness: &Harness) {
+    let devices = harness.homebrew_client.devices().await.expect("devices");
+    assert!(devices.iter().any(|device| device.id == "garden"), "file devices are imported");
+
+    let device: Device = serde_json::from_value(serde_json::json!({
+        "id": "greenhouse-1",
+        "name": "Greenhouse sensor",
+        "device_type": "greenhouse",
+        "latitude": 47.62,
+        "longitude": -122.35
+    })).expect("device");
+    let issued = harness.homebrew_client.register_device(&device).await.expect("register device");
+    assert_eq!(issued.device, device);
+    assert!(harness.homebrew_client.register_device(&device).await.is_err(), "ids are unique");
+
+    // The device's own ingest key posts reports attributed to it, and nothing else
+    let device_client = keyed_client(harness.homebrew.port, &issued.api_key);
+    let posted = device_client.post_report(&NewWeatherReport {
+        temperature: Some(28.0),
+        humidity: Some(85.0),
+        ..Default::default()
+    }).await.expect("POST report with device key");
+    assert_eq!(posted.device_id.as_deref(), Some("greenhouse-1"));
+    assert_eq!(posted.device_type, "greenhouse");
+    device_client.device_config("greenhouse-1").await.expect("own config");
+    assert!(forbidden(device_client.devices().await));
+    assert!(forbidden(device_client.device_config("garden").await));
+
+    let reissued = harness.homebrew_client.issue_device_key("greenhouse-1").await.expect("new device key");
+    assert_ne!(reissued.api_key, issued.api_key);
+    assert!(matches!(device_client.device_config("greenhouse-1").await, Err(WeatherError::InvalidApiKey)));
+
+    // Unregistering keeps the device's reports, no longer attributed to it
+    harness.homebrew_client.unregister_device("greenhouse-1").await.expect("unregister device");
+    assert!(!harness.homebrew_client.devices().await.expect("devices").iter().any(|device| device.id == "greenhouse-1"));
+    let db = connect(&harness.address).await;
+    let row = db.query_one("SELECT device_id FROM weather_reports WHERE oid = $1", &[&posted.oid]).await.expect("report kept");
+    assert_eq!(row.get::<_, Option<String>>(0), None);
+}
+
+/// Whether a request was refused with 403, which a known key gets outside its scope
+fn forbidden<T>(result: Result<T, WeatherError>) -> bool {
+    matches!(result, Err(WeatherError::NetworkError(message)) if message.starts_with("403"))
+}
+
+async fn scoped_keys(harness: &Harness) {
+    let issued = harness.homebrew_client.create_api_key(&NewApiKey {
+        name: "dashboard".to_string(),
+        scope: Scope::Read,
+        device_id: None,
+        expires_at: None,
+    }).await.expect("create read key");
+    assert_eq!(issued.key.scope, Scope::Read);
+
+    // A read key reads on both servers and writes nothing
+    let reader = keyed_client(harness.homebrew.port, &issued.api_key);
+    reader.devices().await.expect("read with read key");
+    keyed_client(harness.combo.port, &issued.api_key).current_conditions().await.expect("combo read with read key");
+    assert!(forbidden(reader.post_report(&NewWeatherReport { temperature: Some(1.0), ..Default::default() }).await));
+    assert!(forbidden(reader.api_keys().await), "admin routes need an admin key");
+
+    let keys = harness.homebrew_client.api_keys().await.expect("keys");
+    assert!(keys.iter().any(|key| key.id == issued.key.id && key.revoked_at.is_none()));
+    harness.homebrew_client.revoke_api_key(&issued.key.id).await.expect("revoke key");
+    assert!(matches!(reader.devices().await, Err(WeatherError::InvalidApiKey)));
+    assert!(matches!(harness.homebrew_client.revoke_api_key(&issued.key.id).await, Err(WeatherError::NotFound(_))));
+    let keys = harness.homebrew_client.api_keys().await.expect("keys");
+    assert!(keys.iter().any(|key| key.id == issued.key.id && key.revoked_at.is_some()), "revoked keys are kept");
+
+    let expired = harness.homebrew_client.create_api_key(&NewApiKey {
+        name: "expired".to_string(),
+        scope: Scope::Admin,
+        device_id: None,
+        expires_at: Some(1),
+    }).await;
+    assert!(expired.is_err(), "keys can't be issued already expired");
+}
+
+async fn combo_caches_homebrew_conditions(harness: &Harness) {
+    let first = harness.combo_client.current_conditions().await.expect("current conditions");
+    let homebrew: homebrew::WeatherReport = serde_json::from_str(first.homebrew.as_deref().expect("homebrew conditions"))
+        .expect("cached homebrew report");
+    assert!(homebrew.temperature.is_some());
+
+    // A newer report doesn't change the answer until the cache expires
+    harness.homebrew_client.post_report(&NewWeatherReport {
+        temperature: Some(-5.0),
+        device_type: "outdoor".to_string(),
+        ..Default::default()
+    }).await.expect("POST report");
+    let second = harness.combo_client.current_conditions().await.expect("cached conditions");
+    assert_eq!(second.oid, first.oid);
+    assert_eq!(second.homebrew, first.homebrew);
+}
+
+async fn config_deployment(harness: &Harness) {
+    let client = &harness.combo_client;
+    let running = client.running_config().await.expect("running config");
+
+    let invalid = client.preview_config(&Candidate { cache_ttl_secs: Some(5), ..Default::default() }).await.expect("preview");
+    assert!(!invalid.valid);
+    assert_eq!(invalid.problems[0].field, "CACHE_TTL_SECS");
+    assert!(invalid.confirmation_token.is_none());
+
+    let candidate = Candidate { cache_ttl_secs: Some(120), fusion_policy: Some("humidity=blend".to_string()), ..Default::default() };
+    let preview = client.preview_config(&candidate).await.expect("preview");
+    let changed: Vec<&str> = preview.changes.iter().map(|change| change.setting.as_str()).collect();
+    assert_eq!(changed, vec!["cache_ttl_secs", "fusion_policy.humidity"]);
+    assert_eq!(client.running_config().await.expect("running config"), running, "previews change nothing");
+
+    // A preview taken before another deployment is refused
+    let outdated = client.preview_config(&Candidate { cache_ttl_secs: Some(300), ..Default::default() }).await.expect("preview");
+    let token = preview.confirmation_token.expect("token");
+    let deployed = client.deploy_config(&token).await.expect("deploy");
+    assert_eq!(deployed.running.settings.cache_ttl_secs, 120);
+    assert_eq!(client.running_config().await.expect("running config"), deployed.running);
+    assert!(client.deploy_config(&token).await.is_err(), "tokens are single-use");
+    assert!(client.deploy_config(&outdated.confirmation_token.expect("token")).await.is_err());
+
+    // Back to the startup settings for the remaining steps
+    let restore = Candidate {
+        cache_ttl_secs: Some(running.settings.cache_ttl_secs),
+        fusion_policy: Some(String::new()),
+        ..Default::default()
+    };
+    let preview = client.preview_config(&restore).await.expect("preview");
+    client.deploy_config(&preview.confirmation_token.expect("token")).await.expect("restore");
+    assert_eq!(client.running_config().await.expect("running config"), running);
+}
+
+async fn concurrent_ingest(harness: &Harness) {
+    let pool = get_homebrew_pool().expect("homebrew pool");
+    let count = |pool: std::sync::Arc<jupiter::db_pool::DatabasePool>| async move {
+        let client = pool.get_connection_with_retry(3).await.expect("connection");
+        client.query_one("SELECT COUNT(*) FROM weather_reports", &[]).await.expect("count").get::<_, i64>(0)
+    };
+    let before = count(pool.clone()).await;
+
+    // Under the per-client rate limit, since each request arrives from a new port
+    let posts = (0..25).map(|i| {
+        let client = harness.homebrew_client.clone();
+        tokio::spawn(async move {
+            client.post_report(&NewWeatherReport {
+                temperature: Some(i as f64),
+                device_type: "outdoor".to_string(),
+                ..Default::default()
+            }).await
+        })
+    }).collect::<Vec<_>>();
+
+    let mut oids = Vec::new();
+    for post in posts {
+        oids.push(post.await.expect("task").expect("concurrent POST").oid);
+    }
+    oids.sort();
+    oids.dedup();
+    assert_eq!(oids.len(), 25, "every report gets its own oid");
+    assert_eq!(count(pool).await, before + 25);
+}
+
+async fn report_ranges(harness: &Harness) {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).expect("clock").as_secs() as i64;
+    let raw = harness.homebrew_client.report_range(now - 3600, now + 60, Resolution::Raw, Some("outdoor")).await.expect("raw range");
+    assert!(raw.reports.len() >= 25, "{} reports", raw.reports.len());
+    assert!(raw.reports.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp), "oldest first");
+    assert!(raw.reports.iter().all(|report| report.device_type == "outdoor"));
+
+    let daily = harness.combo_client.report_range(now - 86_400, now + 60, Resolution::Day, Some("outdoor")).await.expect("daily range");
+    assert!(!daily.reports.is_empty() && daily.reports.len() <= 2, "{} buckets", daily.reports.len());
+    assert!(daily.reports.iter().all(|bucket| bucket.timestamp % 86_400 == 0 && bucket.oid.is_empty()));
+    assert!(harness.homebrew_client.report_range(now, now - 60, Resolution::Hour, None).await.is_err(), "start must come first");
+}
+
+async fn rollups(harness: &Harness) {
+    let db = connect(&harness.address).await;
+    let raw: i64 = db.query_one("SELECT COUNT(*) FROM weather_reports", &[]).await.expect("count reports").get(0);
+    let (hourly, daily) = rollup::run().await.expect("rollup");
+    assert!(hourly > 0 && daily > 0);
+    let rolled: i64 = db.query_one("SELECT SUM(readings)::BIGINT FROM weather_reports_daily", &[]).await.expect("sum readings").get(0);
+    assert_eq!(rolled, raw, "every report is in exactly one bucket");
+
+    // Running again rewrites the recent buckets instead of adding to them
+    rollup::run().await.expect("second rollup");
+    let rolled: i64 = db.query_one("SELECT SUM(readings)::BIGINT FROM weather_reports_hourly", &[]).await.expect("sum readings").get(0);
+    assert_eq!(rolled, raw);
+}
+
+#[test]
+#[ignore = "starts a Postgres container, needs Docker"]
+fn end_to_end() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().expect("runtime");
+    runtime.block_on(async {
+        let harness = Harness::start().await;
+        migrations_upgrade_legacy_tables(&harness).await;
+        ingest_then_query(&harness).await;
+        device_registry(&harness).await;
+        scoped_keys(&harness).await;
+        combo_caches_homebrew_conditions(&harness).await;
+        config_deployment(&harness).await;
+        concurrent_ingest(&harness).await;
+        report_ranges(&harness).await;
+        rollups(&harness).await;
+        harness.stop().await;
+    });
+}
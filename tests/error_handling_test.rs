@@ -125,7 +125,7 @@ mod postgres_server_tests {
         if let Ok(server) = result {
             assert_eq!(server.db_name, "test_db");
             assert_eq!(server.username, "test_user");
-            assert_eq!(server.password, "test_pass");
+            assert_eq!(server.password.expose(), "test_pass");
             assert_eq!(server.address, "localhost:5432");
         }
     }
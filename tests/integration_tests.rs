@@ -1,9 +1,13 @@
+#![cfg(feature = "enhanced-providers")]
+
 use jupiter::provider::common::*;
+use jupiter::analysis::conditions::ConditionCode;
 use jupiter::provider::accuweather_enhanced::AccuWeatherProvider;
 use jupiter::provider::openweather::OpenWeatherProvider;
 use jupiter::provider::homebrew_enhanced::HomebrewProvider;
-use jupiter::provider::combo_enhanced::ComboProvider;
+use jupiter::provider::combo_enhanced::{ComboProvider, FieldPreference, FieldWeights, FusionPolicy, OutlierRejection};
 use jupiter::provider::homebrew::{Config as HomebrewConfig, PostgresServer};
+use jupiter::units::{Pressure, Speed, Temperature};
 
 #[tokio::test]
 async fn test_combo_provider_with_multiple_providers() {
@@ -32,9 +36,11 @@ async fn test_weather_provider_trait_implementation() {
 #[cfg(test)]
 mod mock_tests {
     use super::*;
+    use std::collections::BTreeMap;
     use std::sync::Arc;
     use tokio::sync::RwLock;
     
+    #[derive(Clone)]
     struct MockWeatherProvider {
         name: String,
         weather_data: Arc<RwLock<Option<Weather>>>,
@@ -99,17 +105,20 @@ mod mock_tests {
         let provider = MockWeatherProvider::new("Mock".to_string());
         
         let test_weather = Weather {
-            temperature: 25.0,
-            feels_like: Some(24.0),
+            temperature: Temperature::from_celsius(25.0),
+            feels_like: Some(Temperature::from_celsius(24.0)),
             humidity: Some(60.0),
-            pressure: Some(1015.0),
-            wind_speed: Some(10.0),
+            pressure: Some(Pressure::from_hpa(1015.0)),
+            wind_speed: Some(Speed::from_kmh(10.0)),
             wind_direction: Some(180.0),
             description: "Test weather".to_string(),
+            condition: ConditionCode::Unknown,
             icon: None,
             precipitation: Some(0.0),
             visibility: Some(10000.0),
             uv_index: Some(5.0),
+            road_condition: None,
+            comfort: None,
             provider: "Mock".to_string(),
             location: Location {
                 latitude: 0.0,
@@ -120,12 +129,14 @@ mod mock_tests {
                 postal_code: None,
             },
             timestamp: 0,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
         };
         
         provider.set_weather(test_weather.clone()).await;
         
         let result = provider.get_current_weather("test").await.unwrap();
-        assert_eq!(result.temperature, 25.0);
+        assert_eq!(result.temperature.celsius(), 25.0);
         assert_eq!(result.description, "Test weather");
     }
     
@@ -135,17 +146,20 @@ mod mock_tests {
         let mock2 = Box::new(MockWeatherProvider::new("Mock2".to_string()));
         
         let weather1 = Weather {
-            temperature: 20.0,
+            temperature: Temperature::from_celsius(20.0),
             feels_like: None,
             humidity: Some(50.0),
             pressure: None,
             wind_speed: None,
             wind_direction: None,
             description: "Mock1 weather".to_string(),
+            condition: ConditionCode::Unknown,
             icon: None,
             precipitation: None,
             visibility: None,
             uv_index: None,
+            road_condition: None,
+            comfort: None,
             provider: "Mock1".to_string(),
             location: Location {
                 latitude: 0.0,
@@ -156,20 +170,25 @@ mod mock_tests {
                 postal_code: None,
             },
             timestamp: 0,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
         };
         
         let weather2 = Weather {
-            temperature: 22.0,
+            temperature: Temperature::from_celsius(22.0),
             feels_like: None,
             humidity: Some(60.0),
             pressure: None,
             wind_speed: None,
             wind_direction: None,
             description: "Mock2 weather".to_string(),
+            condition: ConditionCode::Unknown,
             icon: None,
             precipitation: None,
             visibility: None,
             uv_index: None,
+            road_condition: None,
+            comfort: None,
             provider: "Mock2".to_string(),
             location: Location {
                 latitude: 0.0,
@@ -180,6 +199,8 @@ mod mock_tests {
                 postal_code: None,
             },
             timestamp: 0,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
         };
         
         mock1.set_weather(weather1).await;
@@ -187,11 +208,233 @@ mod mock_tests {
         
         let combo = ComboProvider::new()
             .add_provider(mock1, 1.0)
-            .add_provider(mock2, 1.0);
+            .add_provider(mock2, 1.0)
+            .set_cache_duration(600);
         
+        assert!(combo.cache_freshness("current:test").await.is_none());
         let result = combo.get_current_weather("test").await.unwrap();
-        assert_eq!(result.temperature, 21.0);
+        assert_eq!(result.temperature.celsius(), 21.0);
         assert!(result.description.contains("Combined"));
+        
+        // Each contributing provider's raw readings, weight and data age
+        assert_eq!(result.sources.len(), 2);
+        assert_eq!(result.sources[0].provider, "Mock1");
+        assert_eq!(result.sources[0].weight, 1.0);
+        assert_eq!(result.sources[0].values.get("temperature"), Some(&20.0));
+        assert_eq!(result.sources[1].values.get("humidity"), Some(&60.0));
+        assert!(!result.sources[1].values.contains_key("pressure"));
+        assert_eq!(result.sources[1].age_secs, result.timestamp);
+        
+        // Spread of the providers' readings around the combined value
+        assert_eq!(result.spread.get("temperature"), Some(&1.0));
+        assert_eq!(result.spread.get("humidity"), Some(&5.0));
+        assert!(!result.spread.contains_key("pressure"));
+        
+        let freshness = combo.cache_freshness("current:test").await.unwrap();
+        assert_eq!(freshness.ttl, 600);
+        assert!(freshness.remaining() > 590);
+    }
+    
+    #[tokio::test]
+    async fn test_combo_provider_fusion_policy() {
+        let station = Box::new(MockWeatherProvider::new("Station".to_string()));
+        let regional = Box::new(MockWeatherProvider::new("Regional".to_string()));
+        
+        let local_weather = Weather {
+            temperature: Temperature::from_celsius(18.0),
+            feels_like: None,
+            humidity: Some(70.0),
+            pressure: Some(Pressure::from_hpa(1000.0)),
+            wind_speed: None,
+            wind_direction: None,
+            description: "Station weather".to_string(),
+            condition: ConditionCode::Unknown,
+            icon: None,
+            precipitation: None,
+            visibility: None,
+            uv_index: None,
+            road_condition: None,
+            comfort: None,
+            provider: "Station".to_string(),
+            location: Location {
+                latitude: 0.0,
+                longitude: 0.0,
+                name: "Garden".to_string(),
+                country: None,
+                region: None,
+                postal_code: None,
+            },
+            timestamp: 0,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
+        };
+        let regional_weather = Weather {
+            temperature: Temperature::from_celsius(22.0),
+            humidity: Some(50.0),
+            pressure: Some(Pressure::from_hpa(1012.0)),
+            wind_speed: Some(Speed::from_kmh(4.0)),
+            provider: "Regional".to_string(),
+            location: Location { name: "Test".to_string(), ..local_weather.location.clone() },
+            ..local_weather.clone()
+        };
+        
+        station.set_weather(local_weather).await;
+        regional.set_weather(regional_weather).await;
+        
+        let policy = FusionPolicy::parse("humidity=blend").unwrap();
+        assert_eq!(policy.preference("temperature"), FieldPreference::Local);
+        assert_eq!(policy.preference("pressure"), FieldPreference::Regional);
+        assert!(FusionPolicy::parse("pressure=nearby").is_err());
+        assert!(FusionPolicy::parse("dew_point=local").is_err());
+        
+        let combo = ComboProvider::new()
+            .add_provider(regional, 1.0)
+            .add_local_provider(station, 1.0)
+            .set_fusion_policy(policy);
+        let result = combo.get_current_weather("test").await.unwrap();
+        
+        assert_eq!(result.temperature.celsius(), 18.0);               // Local sensor
+        assert_eq!(result.pressure.map(Pressure::hpa), Some(1012.0)); // Regional provider
+        assert_eq!(result.humidity, Some(60.0));                      // Blended
+        assert_eq!(result.wind_speed.map(Speed::kmh), Some(4.0));     // Only the provider reports it
+        assert_eq!(result.location.name, "Test");
+        assert!(!result.sources[0].local);
+        assert!(result.sources[1].local);
+    }
+    
+    #[tokio::test]
+    async fn test_combo_provider_distance_weighting() {
+        let backyard = MockWeatherProvider::new("Backyard".to_string());
+        let airport = MockWeatherProvider::new("Airport".to_string());
+        
+        let near = Weather {
+            temperature: Temperature::from_celsius(20.0),
+            feels_like: None,
+            humidity: None,
+            pressure: None,
+            wind_speed: None,
+            wind_direction: None,
+            description: "Backyard weather".to_string(),
+            condition: ConditionCode::Unknown,
+            icon: None,
+            precipitation: None,
+            visibility: None,
+            uv_index: None,
+            road_condition: None,
+            comfort: None,
+            provider: "Backyard".to_string(),
+            location: Location {
+                latitude: 49.2600,
+                longitude: -123.1100,
+                name: "Backyard".to_string(),
+                country: None,
+                region: None,
+                postal_code: None,
+            },
+            timestamp: 0,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
+        };
+        // About 20 km south
+        let far = Weather {
+            temperature: Temperature::from_celsius(30.0),
+            provider: "Airport".to_string(),
+            location: Location { latitude: 49.0800, ..near.location.clone() },
+            ..near.clone()
+        };
+        backyard.set_weather(near).await;
+        airport.set_weather(far).await;
+        
+        let combo = ComboProvider::new()
+            .add_provider(Box::new(backyard.clone()), 1.0)
+            .add_provider(Box::new(airport.clone()), 1.0);
+        let result = combo.get_current_weather("49.2605,-123.1100").await.unwrap();
+        assert!(result.temperature.celsius() > 20.0 && result.temperature.celsius() < 20.1, "got {}", result.temperature.celsius());
+        let distance = result.sources[1].distance_km.unwrap();
+        assert!((distance - 20.07).abs() < 0.1, "got {}", distance);
+        assert!(result.sources[1].weight < 0.003);
+        
+        // Plain weights for a query without coordinates, or with distance weighting off
+        let result = combo.get_current_weather("12345").await.unwrap();
+        assert_eq!(result.temperature.celsius(), 25.0);
+        assert!(result.sources[0].distance_km.is_none());
+        let combo = ComboProvider::new()
+            .add_provider(Box::new(backyard), 1.0)
+            .add_provider(Box::new(airport), 1.0)
+            .set_distance_scale(None);
+        assert_eq!(combo.get_current_weather("49.2605,-123.1100").await.unwrap().temperature.celsius(), 25.0);
+    }
+    
+    #[tokio::test]
+    async fn test_combo_provider_outlier_rejection() {
+        let weather = |provider: &str, temperature: f64, humidity: f64| Weather {
+            temperature: Temperature::from_celsius(temperature),
+            feels_like: None,
+            humidity: Some(humidity),
+            pressure: None,
+            wind_speed: None,
+            wind_direction: None,
+            description: format!("{} weather", provider),
+            condition: ConditionCode::Unknown,
+            icon: None,
+            precipitation: None,
+            visibility: None,
+            uv_index: None,
+            road_condition: None,
+            comfort: None,
+            provider: provider.to_string(),
+            location: Location {
+                latitude: 0.0,
+                longitude: 0.0,
+                name: "Test".to_string(),
+                country: None,
+                region: None,
+                postal_code: None,
+            },
+            timestamp: 0,
+            sources: Vec::new(),
+            spread: BTreeMap::new(),
+        };
+        let providers = || async {
+            let mut mocks = Vec::new();
+            for (name, temperature, humidity) in [("Mock1", 20.0, 60.0), ("Mock2", 21.0, 62.0), ("Mock3", -40.0, 64.0)] {
+                let mock = MockWeatherProvider::new(name.to_string());
+                mock.set_weather(weather(name, temperature, humidity)).await;
+                mocks.push(Box::new(mock));
+            }
+            mocks.into_iter().fold(ComboProvider::new(), |combo, mock| combo.add_provider(mock, 1.0))
+        };
+        
+        // Blindly averaged
+        assert_eq!(providers().await.get_current_weather("test").await.unwrap().temperature.celsius(), 1.0 / 3.0);
+        
+        let combo = providers().await.set_outlier_rejection("stddev:3".parse().unwrap());
+        let result = combo.get_current_weather("test").await.unwrap();
+        assert_eq!(result.temperature.celsius(), 20.5);
+        assert_eq!(result.humidity, Some(62.0), "agreeing within the noise floor");
+        assert_eq!(result.sources[2].rejected, vec!["temperature".to_string()]);
+        assert!(result.sources[0].rejected.is_empty());
+        assert_eq!(result.spread.get("temperature"), Some(&0.5));
+        
+        let combo = providers().await.set_outlier_rejection("delta:temperature=10,humidity=1".parse().unwrap());
+        let result = combo.get_current_weather("test").await.unwrap();
+        assert_eq!(result.temperature.celsius(), 20.5);
+        assert_eq!(result.humidity, Some(62.0), "two of three outside the delta");
+        assert_eq!(result.sources[0].rejected, vec!["humidity".to_string()]);
+        
+        // Per-field weights multiply the provider weight
+        let weights = FieldWeights::parse("mock2.temperature=3,MOCK3.temperature=0").unwrap();
+        let result = providers().await.set_field_weights(weights).get_current_weather("test").await.unwrap();
+        assert_eq!(result.temperature.celsius(), 20.75);
+        assert_eq!(result.humidity, Some(62.0));
+        
+        assert_eq!("off".parse::<OutlierRejection>().unwrap(), OutlierRejection::Off);
+        assert!("stddev:0".parse::<OutlierRejection>().is_err());
+        assert!("delta:wind_direction=90".parse::<OutlierRejection>().is_err());
+        assert!("median:3".parse::<OutlierRejection>().is_err());
+        assert!(FieldWeights::parse("nws.dew_point=2").is_err());
+        assert!(FieldWeights::parse("nws=2").is_err());
+        assert_eq!(FieldWeights::parse("Tomorrow.io.uv_index=2").unwrap().weight("tomorrow.io", "uv_index"), 2.0);
     }
     
     #[tokio::test]
@@ -8,7 +8,7 @@ async fn test_homebrew_shutdown() {
     let pg = homebrew::PostgresServer {
         db_name: String::from("test_db"),
         username: String::from("test_user"),
-        password: String::from("test_pass"),
+        password: "test_pass".into(),
         address: String::from("localhost:5432"),
     };
     
@@ -39,7 +39,7 @@ async fn test_combo_shutdown() {
     let pg = combo::PostgresServer {
         db_name: String::from("test_db"),
         username: String::from("test_user"),
-        password: String::from("test_pass"),
+        password: "test_pass".into(),
         address: String::from("localhost:5432"),
     };
     
@@ -73,7 +73,7 @@ async fn test_shutdown_with_custom_timeout() {
     let pg = homebrew::PostgresServer {
         db_name: String::from("test_db"),
         username: String::from("test_user"),
-        password: String::from("test_pass"),
+        password: "test_pass".into(),
         address: String::from("localhost:5432"),
     };
     
@@ -138,14 +138,14 @@ async fn test_concurrent_server_shutdown() {
     let pg1 = homebrew::PostgresServer {
         db_name: String::from("test_db1"),
         username: String::from("test_user"),
-        password: String::from("test_pass"),
+        password: "test_pass".into(),
         address: String::from("localhost:5432"),
     };
     
     let pg2 = combo::PostgresServer {
         db_name: String::from("test_db2"),
         username: String::from("test_user"),
-        password: String::from("test_pass"),
+        password: "test_pass".into(),
         address: String::from("localhost:5432"),
     };
     
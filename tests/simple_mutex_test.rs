@@ -42,7 +42,7 @@ async fn test_async_mutex_with_timeout() {
 #[tokio::test]
 async fn test_server_handle_pattern() {
     // Simulate the server handle pattern from homebrew/combo
-    type ServerHandle = Arc<AsyncMutex<Option<JoinHandle<()>>>>;
+    type ServerHandle = Arc<AsyncMutex<Option<JoinHandle<i32>>>>;
     
     let server_handle: ServerHandle = Arc::new(AsyncMutex::new(None));
     
@@ -0,0 +1,71 @@
+// GET /api/stream on the combo server, served from its router on a free port. Nothing here needs
+// a database: updates are published straight onto the live channel.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+
+use jupiter::live::{self, LiveUpdate};
+use jupiter::provider::combo;
+use jupiter::provider::homebrew::WeatherReport;
+
+const API_KEY: &str = "stream-test-key";
+
+async fn serve() -> SocketAddr {
+    let config = combo::Config::new(None, None, API_KEY, Some(3600), combo::PostgresServer {
+        db_name: "unused".to_string(),
+        username: "unused".to_string(),
+        password: "unused".into(),
+        address: "localhost:5432".to_string(),
+    }, 0, "12345".to_string());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, combo::router(config).into_make_service_with_connect_info::<SocketAddr>()).await
+    });
+    address
+}
+
+#[tokio::test]
+async fn test_stream_pushes_stored_reports() {
+    let address = serve().await;
+    let mut request = format!("ws://{}/api/stream", address).into_client_request().unwrap();
+    request.headers_mut().insert("Authorization", API_KEY.parse().unwrap());
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.expect("WebSocket handshake");
+
+    let mut report = WeatherReport::new();
+    report.temperature = Some(-3.5);
+    report.device_type = "outdoor".to_string();
+
+    // The stream subscribes once the upgrade completes, so keep publishing until it's listening
+    let received = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            live::publish(LiveUpdate::Report(report.clone()));
+            if let Ok(Some(message)) = tokio::time::timeout(Duration::from_millis(100), socket.next()).await {
+                break message.expect("stream message");
+            }
+        }
+    }).await.expect("no update within 5s");
+
+    let message: serde_json::Value = match received {
+        Message::Text(text) => serde_json::from_str(&text).unwrap(),
+        other => panic!("expected a text message, got {:?}", other),
+    };
+    assert_eq!(message["type"], "report");
+    assert_eq!(message["schema"], "weather_report/1");
+    assert_eq!(message["data"]["oid"], report.oid.as_str());
+    assert_eq!(message["data"]["temperature"], -3.5);
+}
+
+#[tokio::test]
+async fn test_stream_requires_api_key() {
+    let address = serve().await;
+    match tokio_tungstenite::connect_async(format!("ws://{}/api/stream", address)).await {
+        Err(WsError::Http(response)) => assert_eq!(response.status(), 401),
+        other => panic!("expected 401, got {:?}", other.map(|(_, response)| response.status())),
+    }
+}